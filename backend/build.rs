@@ -0,0 +1,9 @@
+//! Génère le code du service gRPC (`proto/carlgpt.proto`) avec le `protoc` fourni par
+//! `protoc-bin-vendored` : la compilation ne dépend pas d'un `protoc` installé.
+
+fn main() {
+    let protoc = protoc_bin_vendored::protoc_bin_path().expect("protoc introuvable");
+    // SAFETY: le script de build est mono-thread, rien d'autre ne lit l'environnement ici.
+    unsafe { std::env::set_var("PROTOC", protoc) };
+    tonic_build::compile_protos("proto/carlgpt.proto").expect("Compilation de carlgpt.proto");
+}
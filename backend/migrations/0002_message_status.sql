@@ -0,0 +1,4 @@
+-- Une réponse interrompue (erreur du provider en cours de stream) reste `incomplete`
+-- jusqu'à ce que l'endpoint de continuation la termine.
+ALTER TABLE chat_messages
+    ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'complete';
@@ -0,0 +1,4 @@
+-- Cycle de vie d'une réponse : pending -> streaming -> complete | incomplete | failed
+ALTER TABLE chat_messages
+    ADD CONSTRAINT chat_messages_status_check
+    CHECK (status IN ('pending', 'streaming', 'complete', 'incomplete', 'failed'));
@@ -0,0 +1,7 @@
+-- Brouillon non envoyé d'une session (un seul par session).
+CREATE TABLE IF NOT EXISTS chat_drafts (
+    session_id UUID PRIMARY KEY REFERENCES chat_sessions(id) ON DELETE CASCADE,
+    content TEXT NOT NULL DEFAULT '',
+    attachments JSONB NOT NULL DEFAULT '[]'::jsonb,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
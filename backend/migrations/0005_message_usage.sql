@@ -0,0 +1,5 @@
+-- Consommation de tokens par réponse, telle que renvoyée par le provider.
+-- cached_tokens : part du prompt servie depuis le cache de préfixe du provider.
+ALTER TABLE chat_messages ADD COLUMN IF NOT EXISTS prompt_tokens INTEGER;
+ALTER TABLE chat_messages ADD COLUMN IF NOT EXISTS completion_tokens INTEGER;
+ALTER TABLE chat_messages ADD COLUMN IF NOT EXISTS cached_tokens INTEGER;
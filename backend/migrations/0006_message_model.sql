@@ -0,0 +1,4 @@
+-- Modèle qui a produit chaque réponse, et route choisie quand le client a demandé `auto`
+-- (simple, long, attachments, code, math, cost). NULL pour les messages antérieurs.
+ALTER TABLE chat_messages ADD COLUMN IF NOT EXISTS model TEXT;
+ALTER TABLE chat_messages ADD COLUMN IF NOT EXISTS route TEXT;
@@ -0,0 +1,2 @@
+-- Emoji choisi par l'IA avec le titre résumé, affiché dans la barre latérale.
+ALTER TABLE chat_sessions ADD COLUMN IF NOT EXISTS icon TEXT;
@@ -0,0 +1,3 @@
+-- Raison de fin renvoyée par le provider pour chaque réponse (stop, length, content_filter,
+-- tool_calls). NULL si le provider ne l'a pas communiquée ou pour les messages antérieurs.
+ALTER TABLE chat_messages ADD COLUMN IF NOT EXISTS finish_reason TEXT;
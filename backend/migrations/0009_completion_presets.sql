@@ -0,0 +1,14 @@
+-- Jeux de paramètres de completion nommés, choisis par `preset_id` à l'envoi d'un message.
+CREATE TABLE IF NOT EXISTS completion_presets (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL UNIQUE,
+    params JSONB NOT NULL DEFAULT '{}'::jsonb,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO completion_presets (name, params) VALUES
+    ('Précis', '{"temperature": 0.2, "top_p": 1.0}'),
+    ('Créatif', '{"temperature": 1.1, "top_p": 0.95, "presence_penalty": 0.4}'),
+    ('Déterministe (seed 42)', '{"temperature": 0.0, "seed": 42}')
+ON CONFLICT (name) DO NOTHING;
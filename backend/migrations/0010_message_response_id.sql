@@ -0,0 +1,3 @@
+-- Identifiant de la réponse chez OpenAI quand le modèle passe par l'API Responses : le tour
+-- suivant le reprend dans `previous_response_id` au lieu de renvoyer tout l'historique.
+ALTER TABLE chat_messages ADD COLUMN IF NOT EXISTS response_id TEXT;
@@ -0,0 +1,10 @@
+-- Texte extrait des fichiers uploadés (PDF, texte), calculé en tâche de fond après l'upload.
+-- Indexé par clé de stockage : le fichier existe avant le message qui le joint.
+CREATE TABLE IF NOT EXISTS attachment_extractions (
+    storage_key TEXT PRIMARY KEY,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'ready', 'failed')),
+    content TEXT,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
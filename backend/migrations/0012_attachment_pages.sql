@@ -0,0 +1,2 @@
+-- Pages d'un PDF joint à envoyer au modèle (`10-25`, `1-3,7`) ; NULL pour tout le document.
+ALTER TABLE chat_attachments ADD COLUMN IF NOT EXISTS pages TEXT;
@@ -0,0 +1,14 @@
+-- Exports de l'historique (zip construit en tâche de fond) et leur lien de téléchargement.
+CREATE TABLE IF NOT EXISTS chat_exports (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    status TEXT NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'running', 'ready', 'failed')),
+    sessions_done INTEGER NOT NULL DEFAULT 0,
+    sessions_total INTEGER NOT NULL DEFAULT 0,
+    -- Fichier dans `EXPORT_DIR`, et jeton exigé par le lien de téléchargement
+    file_name TEXT,
+    download_token UUID NOT NULL DEFAULT gen_random_uuid(),
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ
+);
@@ -0,0 +1,12 @@
+-- Consommation agrégée par jour (UTC) et par modèle, recalculée par la tâche `usage_rollup`.
+-- Les jours déjà agrégés sont conservés quand la rétention supprime les messages.
+CREATE TABLE IF NOT EXISTS usage_daily (
+    day DATE NOT NULL,
+    model TEXT NOT NULL,
+    answers BIGINT NOT NULL DEFAULT 0,
+    prompt_tokens BIGINT NOT NULL DEFAULT 0,
+    completion_tokens BIGINT NOT NULL DEFAULT 0,
+    cached_tokens BIGINT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (day, model)
+);
@@ -0,0 +1,7 @@
+-- L'URL d'une pièce jointe est calculée à la lecture depuis sa clé de stockage : changer
+-- UPLOAD_BASE_URL ou le domaine du déploiement ne casse plus les anciens liens.
+UPDATE chat_attachments
+SET storage_key = regexp_replace(split_part(url, '?', 1), '^.*/', '')
+WHERE storage_key = '';
+
+ALTER TABLE chat_attachments DROP COLUMN IF EXISTS url;
@@ -0,0 +1,6 @@
+-- Métadonnées lues à l'upload : dimensions des images, pages des PDF, durée des sons et vidéos.
+ALTER TABLE chat_attachments
+    ADD COLUMN IF NOT EXISTS width INTEGER,
+    ADD COLUMN IF NOT EXISTS height INTEGER,
+    ADD COLUMN IF NOT EXISTS page_count INTEGER,
+    ADD COLUMN IF NOT EXISTS duration_ms BIGINT;
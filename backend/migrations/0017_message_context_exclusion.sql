@@ -0,0 +1,3 @@
+-- Messages gardés dans la discussion mais plus envoyés au modèle.
+ALTER TABLE chat_messages
+    ADD COLUMN IF NOT EXISTS excluded_from_context BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+-- Message cité par une question de l'utilisateur (réponse à un message précis).
+ALTER TABLE chat_messages
+    ADD COLUMN IF NOT EXISTS reply_to_message_id UUID REFERENCES chat_messages(id) ON DELETE SET NULL;
@@ -0,0 +1,8 @@
+-- Réponses mises de côté (signets) et réaction (emoji) sur un message.
+ALTER TABLE chat_messages
+    ADD COLUMN IF NOT EXISTS bookmarked_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS reaction TEXT;
+
+CREATE INDEX IF NOT EXISTS chat_messages_bookmarked_at_idx
+    ON chat_messages (bookmarked_at DESC)
+    WHERE bookmarked_at IS NOT NULL;
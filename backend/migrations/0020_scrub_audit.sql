@@ -0,0 +1,12 @@
+-- Requêtes dont des secrets ont été masqués avant l'envoi à un provider externe. Seules les
+-- règles déclenchées et le nombre de remplacements sont conservés, jamais le texte masqué.
+CREATE TABLE IF NOT EXISTS scrub_audit (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    provider TEXT NOT NULL,
+    model TEXT,
+    rules TEXT[] NOT NULL,
+    replacements INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS scrub_audit_created_at_idx ON scrub_audit (created_at DESC);
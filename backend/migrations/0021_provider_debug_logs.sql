@@ -0,0 +1,16 @@
+-- Journal de débogage des échanges avec les providers (`PROVIDER_DEBUG_LOG`) : corps de la
+-- requête envoyée et flux SSE brut reçu, secrets masqués, rattachés au message produit.
+CREATE TABLE IF NOT EXISTS provider_debug_logs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    message_id UUID REFERENCES chat_messages(id) ON DELETE CASCADE,
+    provider TEXT NOT NULL,
+    url TEXT NOT NULL,
+    request JSONB NOT NULL,
+    http_status INTEGER,
+    response TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS provider_debug_logs_message_id_idx ON provider_debug_logs (message_id);
+CREATE INDEX IF NOT EXISTS provider_debug_logs_created_at_idx ON provider_debug_logs (created_at DESC);
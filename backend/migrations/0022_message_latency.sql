@@ -0,0 +1,13 @@
+-- Décomposition du temps de génération de chaque réponse de l'IA (dernière génération) :
+-- premier token, flux complet, requêtes à la base et traitement des pièces jointes, en ms.
+CREATE TABLE IF NOT EXISTS message_latency (
+    message_id UUID PRIMARY KEY REFERENCES chat_messages(id) ON DELETE CASCADE,
+    model TEXT NOT NULL,
+    first_token_ms INTEGER,
+    generation_ms INTEGER NOT NULL,
+    db_ms INTEGER NOT NULL,
+    attachments_ms INTEGER NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS message_latency_recorded_at_idx ON message_latency (recorded_at);
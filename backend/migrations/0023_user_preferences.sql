@@ -0,0 +1,8 @@
+-- Préférences de l'utilisateur (modèle, paramètres, persona, langue, thème, streaming),
+-- gardées côté serveur pour le suivre d'un appareil à l'autre. Le déploiement n'a pas de
+-- comptes : une seule ligne.
+CREATE TABLE IF NOT EXISTS user_preferences (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    preferences JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,6 @@
+-- Export d'une seule session en PDF (`format = 'pdf'`), à côté de l'archive zip de tout
+-- l'historique.
+ALTER TABLE chat_exports
+    ADD COLUMN IF NOT EXISTS format TEXT NOT NULL DEFAULT 'zip'
+        CHECK (format IN ('zip', 'pdf')),
+    ADD COLUMN IF NOT EXISTS session_id UUID REFERENCES chat_sessions(id) ON DELETE SET NULL;
@@ -0,0 +1,4 @@
+-- Fiches de révision d'une session, à importer dans Anki (`format = 'anki'`).
+ALTER TABLE chat_exports DROP CONSTRAINT IF EXISTS chat_exports_format_check;
+ALTER TABLE chat_exports
+    ADD CONSTRAINT chat_exports_format_check CHECK (format IN ('zip', 'pdf', 'anki'));
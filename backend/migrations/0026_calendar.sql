@@ -0,0 +1,20 @@
+-- Évènements et tâches relevés dans les discussions par l'outil `add_to_calendar`, servis
+-- en flux ICS. Le déploiement n'a pas de comptes : un seul calendrier, dont le lien
+-- d'abonnement porte un jeton (une seule ligne).
+CREATE TABLE IF NOT EXISTS calendar_items (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    kind TEXT NOT NULL CHECK (kind IN ('event', 'todo')),
+    title TEXT NOT NULL,
+    description TEXT,
+    -- Évènement : début et fin ; tâche : échéance (`starts_at`), facultative
+    starts_at TIMESTAMPTZ,
+    ends_at TIMESTAMPTZ,
+    -- Journée entière : seule la date de `starts_at` / `ends_at` (UTC) compte
+    all_day BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS calendar_feed (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    token UUID NOT NULL DEFAULT gen_random_uuid()
+);
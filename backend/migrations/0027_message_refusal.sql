@@ -0,0 +1,3 @@
+-- Réponse refusée par le modèle (`refusal`) ou filtrée par le provider (`content_filter`)
+ALTER TABLE chat_messages ADD COLUMN IF NOT EXISTS refused BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE chat_messages SET refused = TRUE WHERE finish_reason = 'content_filter';
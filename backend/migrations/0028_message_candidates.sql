@@ -0,0 +1,15 @@
+-- Réponses candidates générées en un seul appel (`candidates` > 1) : toutes sont gardées, le
+-- contenu du message est celui de la candidate retenue (`selected`).
+CREATE TABLE IF NOT EXISTS message_candidates (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    message_id UUID NOT NULL REFERENCES chat_messages(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    status TEXT NOT NULL,
+    finish_reason TEXT,
+    refused BOOLEAN NOT NULL DEFAULT FALSE,
+    response_id TEXT,
+    selected BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (message_id, position)
+);
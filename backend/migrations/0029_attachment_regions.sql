@@ -0,0 +1,2 @@
+-- Zones d'une image tracées par l'utilisateur, découpées avant l'envoi au modèle.
+ALTER TABLE chat_attachments ADD COLUMN IF NOT EXISTS regions JSONB;
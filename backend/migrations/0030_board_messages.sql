@@ -0,0 +1,4 @@
+-- Messages publics (`/api/messages`) : date de modification, et index de la pagination par
+-- identifiant et du filtre par auteur.
+ALTER TABLE messages ADD COLUMN IF NOT EXISTS edited_at TIMESTAMPTZ;
+CREATE INDEX IF NOT EXISTS messages_author_id_idx ON messages (author, id DESC);
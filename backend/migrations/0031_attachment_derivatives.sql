@@ -0,0 +1,18 @@
+-- Résultats du traitement des fichiers uploadés, indexés par empreinte SHA-256 du contenu et
+-- type MIME : un fichier déjà reçu reprend son texte extrait et ses métadonnées au lieu d'être
+-- relu. `ocr_text`, `scan_verdict` et `thumbnail_key` restent vides tant qu'aucune étape de
+-- l'upload ne les calcule.
+CREATE TABLE IF NOT EXISTS attachment_derivatives (
+    content_hash TEXT NOT NULL,
+    mime_type TEXT NOT NULL,
+    extracted_text TEXT,
+    ocr_text TEXT,
+    scan_verdict TEXT,
+    thumbnail_key TEXT,
+    width INTEGER,
+    height INTEGER,
+    page_count INTEGER,
+    duration_ms BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (content_hash, mime_type)
+);
@@ -0,0 +1,11 @@
+-- Drapeaux de fonctionnalités (`/api/admin/feature-flags`) : sous-systèmes expérimentaux
+-- ouverts à une liste de clients ou à un pourcentage d'entre eux.
+CREATE TABLE IF NOT EXISTS feature_flags (
+    name TEXT PRIMARY KEY,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    rollout_percent SMALLINT NOT NULL DEFAULT 100
+        CHECK (rollout_percent BETWEEN 0 AND 100),
+    clients TEXT[] NOT NULL DEFAULT '{}',
+    description TEXT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
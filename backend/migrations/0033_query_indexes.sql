@@ -0,0 +1,12 @@
+-- Index des lectures fréquentes, devenues lentes au-delà de quelques milliers de messages :
+-- historique d'une session dans l'ordre, pièces jointes des messages lus et liste des
+-- sessions actives de la plus récente à la plus ancienne.
+CREATE INDEX IF NOT EXISTS chat_messages_session_position_idx
+    ON chat_messages (session_id, position);
+
+CREATE INDEX IF NOT EXISTS chat_attachments_message_id_idx
+    ON chat_attachments (message_id);
+
+CREATE INDEX IF NOT EXISTS chat_sessions_active_updated_at_idx
+    ON chat_sessions (updated_at DESC)
+    WHERE archived = FALSE;
@@ -0,0 +1,4 @@
+-- Rangement des discussions : un dossier au plus et des étiquettes, modifiables en masse
+-- via `POST /api/chat/sessions/bulk`.
+ALTER TABLE chat_sessions ADD COLUMN IF NOT EXISTS folder TEXT;
+ALTER TABLE chat_sessions ADD COLUMN IF NOT EXISTS tags TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,7 @@
+-- Tokens consommés par jour (UTC) pour `DAILY_TOKEN_QUOTA`, comptés à la réception de chaque
+-- réponse d'un provider, qu'elle soit enregistrée ou non. Supprimer une discussion ne rend
+-- pas ses tokens.
+CREATE TABLE IF NOT EXISTS token_ledger (
+    day DATE PRIMARY KEY,
+    tokens BIGINT NOT NULL DEFAULT 0
+);
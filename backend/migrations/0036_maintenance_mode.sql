@@ -0,0 +1,7 @@
+-- Mode maintenance en cours (`PUT /api/admin/maintenance`), partagé par toutes les
+-- instances : au plus une ligne, présente tant que le mode est actif.
+CREATE TABLE IF NOT EXISTS maintenance_mode (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    message TEXT,
+    since TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,3 @@
+-- Erreur du provider pour une réponse candidate dont la requête a échoué : les autres
+-- candidates sont gardées, celle-ci est enregistrée en `failed` avec la cause.
+ALTER TABLE message_candidates ADD COLUMN IF NOT EXISTS error TEXT;
@@ -0,0 +1,136 @@
+//! Contrôle d'accès : appelant admin (`X-Admin-Token`) et modèles autorisés par déploiement
+//! (`DEFAULT_MODEL`, `ALLOWED_MODELS`).
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{HeaderMap, StatusCode, request::Parts},
+};
+
+use crate::{
+    AppState,
+    models::ChatMessagePayload,
+    providers::AiModelChoice,
+    routing::{self, AUTO_MODEL, ModelSelection},
+};
+
+pub(crate) const ADMIN_TOKEN_HEADER: &str = "x-admin-token";
+
+/// Appelant d'une requête. Il est admin s'il présente le jeton `ADMIN_TOKEN` dans l'en-tête
+/// `X-Admin-Token` ; un jeton erroné est refusé plutôt que traité comme anonyme.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Caller {
+    pub(crate) admin: bool,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for Caller {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        Caller::from_headers(&parts.headers, state)
+    }
+}
+
+impl Caller {
+    /// Appelant d'après les en-têtes HTTP (ou les métadonnées d'un appel gRPC).
+    pub(crate) fn from_headers(
+        headers: &HeaderMap,
+        state: &AppState,
+    ) -> Result<Self, (StatusCode, String)> {
+        let Some(provided) = headers.get(ADMIN_TOKEN_HEADER) else {
+            return Ok(Caller { admin: false });
+        };
+        match &state.admin_token {
+            Some(expected) if constant_time_eq(provided.as_bytes(), expected.as_bytes()) => {
+                Ok(Caller { admin: true })
+            }
+            _ => Err((
+                StatusCode::UNAUTHORIZED,
+                "Jeton admin invalide.".to_string(),
+            )),
+        }
+    }
+
+    /// Refuse les endpoints d'administration aux appelants sans jeton admin.
+    pub(crate) fn require_admin(self) -> Result<(), (StatusCode, String)> {
+        if self.admin {
+            Ok(())
+        } else {
+            Err((
+                StatusCode::FORBIDDEN,
+                "Réservé aux administrateurs (X-Admin-Token).".to_string(),
+            ))
+        }
+    }
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Modèle utilisé quand la requête n'en précise pas, et modèles accessibles aux appelants
+/// non admin (tous si `allowed` vaut `None`). Les admins gardent accès à tous les modèles.
+#[derive(Clone, Debug, Default)]
+pub struct ModelPolicy {
+    pub default: AiModelChoice,
+    pub allowed: Option<Vec<AiModelChoice>>,
+}
+
+impl ModelPolicy {
+    pub(crate) fn allows(&self, model: AiModelChoice, caller: Caller) -> bool {
+        caller.admin
+            || self
+                .allowed
+                .as_ref()
+                .is_none_or(|allowed| allowed.contains(&model))
+    }
+
+    /// Modèle demandé par le client ; un identifiant inconnu retombe sur le modèle par défaut.
+    pub(crate) fn resolve(
+        &self,
+        requested: Option<&str>,
+        caller: Caller,
+    ) -> Result<AiModelChoice, (StatusCode, String)> {
+        let model = requested
+            .and_then(AiModelChoice::from_id)
+            .unwrap_or(self.default);
+        if self.allows(model, caller) {
+            Ok(model)
+        } else {
+            Err((
+                StatusCode::FORBIDDEN,
+                format!(
+                    "Le modèle {} n'est pas disponible sur ce déploiement.",
+                    model.model_id()
+                ),
+            ))
+        }
+    }
+
+    /// Comme [`ModelPolicy::resolve`], mais `auto` choisit le modèle d'après `payload`.
+    pub(crate) fn select(
+        &self,
+        requested: Option<&str>,
+        payload: &[ChatMessagePayload],
+        caller: Caller,
+    ) -> Result<ModelSelection, (StatusCode, String)> {
+        if requested.map(str::trim) == Some(AUTO_MODEL) {
+            return Ok(routing::route(payload, self, caller));
+        }
+        Ok(ModelSelection {
+            model: self.resolve(requested, caller)?,
+            route: None,
+        })
+    }
+
+    pub(crate) fn available(&self, caller: Caller) -> Vec<AiModelChoice> {
+        AiModelChoice::ALL
+            .into_iter()
+            .filter(|model| self.allows(*model, caller))
+            .collect()
+    }
+}
@@ -0,0 +1,251 @@
+//! Archivage des échanges pour la conformité (`ARCHIVE_*`) : chaque réponse enregistrée est
+//! envoyée avec sa question, dans un format JSON stable, vers un webhook (`POST`) ou un
+//! stockage objet (S3, dossier local), hors de la base principale. Un envoi est retenté
+//! plusieurs fois ; en cas d'échec persistant, l'échange est écrit dans un dossier de
+//! lettres mortes que la tâche `archive_retry` renvoie ensuite.
+
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use object_store::{ObjectStore, PutPayload, aws::AmazonS3Builder, path::Path};
+use serde::Serialize;
+use url::Url;
+use uuid::Uuid;
+
+use crate::{AppState, models::ChatMessage, outbound::Outbound};
+
+/// Version du format des échanges archivés, incrémentée à chaque changement incompatible
+const ARCHIVE_FORMAT_VERSION: u32 = 1;
+/// Durée maximale d'un envoi au webhook
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Attente avant la deuxième tentative, doublée à chaque tentative suivante
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Réglages de l'archivage ; rien n'est archivé sans `url`.
+#[derive(Clone, Debug)]
+pub struct ArchiveSettings {
+    /// Webhook (`https://...`, appelé en `POST`) ou stockage objet (`s3://bucket/dossier`,
+    /// `file:///chemin`)
+    pub url: Option<String>,
+    /// Tentatives d'envoi d'un échange avant de l'écrire dans `dead_letter_dir`
+    pub max_attempts: u32,
+    /// Échanges dont l'envoi a échoué, un fichier JSON chacun
+    pub dead_letter_dir: String,
+    /// Intervalle de la tâche `archive_retry`, qui renvoie les lettres mortes
+    pub retry_interval: Duration,
+}
+
+impl Default for ArchiveSettings {
+    fn default() -> Self {
+        ArchiveSettings {
+            url: None,
+            max_attempts: 5,
+            dead_letter_dir: "./archive-dead-letters".to_string(),
+            retry_interval: Duration::from_secs(15 * 60),
+        }
+    }
+}
+
+pub(crate) struct ArchiveSink {
+    target: ArchiveTarget,
+    max_attempts: u32,
+    dead_letter_dir: PathBuf,
+    outbound: Outbound,
+}
+
+enum ArchiveTarget {
+    Webhook(String),
+    Store {
+        store: Arc<dyn ObjectStore>,
+        prefix: Path,
+    },
+}
+
+/// Échange archivé : la question et la réponse au format de `GET /api/chat/sessions/:id`.
+#[derive(Serialize)]
+struct ArchivedExchange<'a> {
+    version: u32,
+    /// Identifiant de l'archive : une réponse régénérée ou continuée est archivée à nouveau
+    id: Uuid,
+    archived_at: DateTime<Utc>,
+    session_id: Uuid,
+    session_title: &'a str,
+    /// Absente si la réponse ne suit aucune question (historique importé)
+    question: Option<&'a ChatMessage>,
+    answer: &'a ChatMessage,
+}
+
+impl ArchiveSink {
+    /// `Ok(None)` si l'archivage n'est pas configuré.
+    pub(crate) fn from_settings(
+        settings: &ArchiveSettings,
+        outbound: &Outbound,
+    ) -> Result<Option<Self>, String> {
+        let Some(url) = &settings.url else {
+            return Ok(None);
+        };
+        let parsed = Url::parse(url).map_err(|err| format!("{url}: {err}"))?;
+        let target = if matches!(parsed.scheme(), "http" | "https") {
+            ArchiveTarget::Webhook(url.clone())
+        } else {
+            let (store, prefix) =
+                object_store::parse_url(&parsed).map_err(|err| err.to_string())?;
+            let store: Arc<dyn ObjectStore> = if parsed.scheme() == "s3" {
+                // Identifiants, région et endpoint lus dans les variables `AWS_*` habituelles
+                Arc::new(
+                    AmazonS3Builder::from_env()
+                        .with_url(url.as_str())
+                        .build()
+                        .map_err(|err| err.to_string())?,
+                )
+            } else {
+                Arc::from(store)
+            };
+            ArchiveTarget::Store { store, prefix }
+        };
+        Ok(Some(ArchiveSink {
+            target,
+            max_attempts: settings.max_attempts.max(1),
+            dead_letter_dir: PathBuf::from(&settings.dead_letter_dir),
+            outbound: outbound.clone(),
+        }))
+    }
+
+    async fn deliver(&self, name: &str, body: &[u8]) -> Result<(), String> {
+        match &self.target {
+            ArchiveTarget::Webhook(url) => {
+                let response = self
+                    .outbound
+                    .client(url)
+                    .await?
+                    .post(url)
+                    .timeout(WEBHOOK_TIMEOUT)
+                    .header("content-type", "application/json")
+                    .header("idempotency-key", name.trim_end_matches(".json"))
+                    .body(body.to_vec())
+                    .send()
+                    .await
+                    .map_err(|err| err.to_string())?;
+                if !response.status().is_success() {
+                    return Err(format!("{url} a répondu {}", response.status()));
+                }
+                Ok(())
+            }
+            ArchiveTarget::Store { store, prefix } => store
+                .put(&prefix.child(name), PutPayload::from(body.to_vec()))
+                .await
+                .map(|_| ())
+                .map_err(|err| err.to_string()),
+        }
+    }
+
+    /// Envoie un échange en retentant avec une attente croissante ; après `max_attempts`
+    /// échecs, l'écrit dans le dossier des lettres mortes.
+    async fn deliver_with_retry(&self, name: &str, body: &[u8]) {
+        let mut delay = RETRY_BASE_DELAY;
+        for attempt in 1..=self.max_attempts {
+            match self.deliver(name, body).await {
+                Ok(()) => return,
+                Err(err) if attempt == self.max_attempts => {
+                    eprintln!("Archivage de {name} impossible après {attempt} tentatives: {err}");
+                }
+                Err(_) => {
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+            }
+        }
+        let written = async {
+            tokio::fs::create_dir_all(&self.dead_letter_dir).await?;
+            tokio::fs::write(self.dead_letter_dir.join(name), body).await
+        };
+        if let Err(err) = written.await {
+            eprintln!("Impossible d'écrire {name} dans les lettres mortes de l'archivage: {err}");
+        }
+    }
+
+    /// Renvoie une fois chaque lettre morte et supprime celles qui sont passées. Renvoie le
+    /// nombre d'échanges archivés et de ceux encore en attente.
+    pub(crate) async fn retry_dead_letters(&self) -> Result<(usize, usize), String> {
+        let mut entries = match tokio::fs::read_dir(&self.dead_letter_dir).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok((0, 0)),
+            Err(err) => return Err(err.to_string()),
+        };
+        let (mut delivered, mut pending) = (0, 0);
+        while let Some(entry) = entries.next_entry().await.map_err(|err| err.to_string())? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.ends_with(".json") {
+                continue;
+            }
+            let body = tokio::fs::read(entry.path())
+                .await
+                .map_err(|err| err.to_string())?;
+            match self.deliver(&name, &body).await {
+                Ok(()) => {
+                    tokio::fs::remove_file(entry.path())
+                        .await
+                        .map_err(|err| err.to_string())?;
+                    delivered += 1;
+                }
+                Err(err) => {
+                    eprintln!("Archivage de {name} toujours impossible: {err}");
+                    pending += 1;
+                }
+            }
+        }
+        Ok((delivered, pending))
+    }
+}
+
+/// Archive en tâche de fond la réponse `message_id` et la question qui la précède, telles
+/// qu'enregistrées : un échec n'a aucun effet sur la réponse.
+pub(crate) fn archive_exchange(state: &AppState, session_id: Uuid, message_id: Uuid) {
+    let Some(sink) = state.archive.clone() else {
+        return;
+    };
+    let state = state.clone();
+    tokio::spawn(async move {
+        let session = match state.repo.fetch_session(session_id).await {
+            Ok(session) => session,
+            Err(err) => {
+                eprintln!("Impossible de lire l'échange {message_id} à archiver: {err}");
+                return;
+            }
+        };
+        let Some(index) = session
+            .messages
+            .iter()
+            .position(|message| message.id == message_id)
+        else {
+            return;
+        };
+        let question = session.messages[..index]
+            .iter()
+            .rev()
+            .find(|message| message.role == "user");
+        let exchange = ArchivedExchange {
+            version: ARCHIVE_FORMAT_VERSION,
+            id: Uuid::new_v4(),
+            archived_at: Utc::now(),
+            session_id,
+            session_title: &session.title,
+            question,
+            answer: &session.messages[index],
+        };
+        let body = match serde_json::to_vec(&exchange) {
+            Ok(body) => body,
+            Err(err) => {
+                eprintln!("Impossible de sérialiser l'échange {message_id}: {err}");
+                return;
+            }
+        };
+        // Trié par date dans le stockage, unique pour chaque archive
+        let name = format!(
+            "{}-{}.json",
+            exchange.archived_at.format("%Y%m%dT%H%M%S%3fZ"),
+            exchange.id
+        );
+        sink.deliver_with_retry(&name, &body).await;
+    });
+}
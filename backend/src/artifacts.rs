@@ -0,0 +1,227 @@
+//! Blocs de code d'un message, exposés comme fichiers à télécharger un par un ou ensemble
+//! dans une archive zip : pratique quand une réponse contient plusieurs fichiers.
+
+use std::collections::HashSet;
+
+use crate::{export::ZipWriter, models::CodeArtifact, storage::sanitize_file_name};
+
+/// Longueur maximale de la ligne qui annonce un fichier juste avant son bloc de code.
+const HINT_LINE_MAX_CHARS: usize = 160;
+
+/// Blocs de code délimités par ``` ou ~~~, dans l'ordre du message. Un bloc resté ouvert
+/// (réponse tronquée) va jusqu'à la fin du message.
+pub(crate) fn extract_artifacts(content: &str) -> Vec<CodeArtifact> {
+    let mut artifacts = Vec::new();
+    let mut used_names = HashSet::new();
+    let mut previous_line = "";
+    let mut lines = content.lines();
+
+    while let Some(line) = lines.next() {
+        let Some((fence, info)) = opening_fence(line) else {
+            if !line.trim().is_empty() {
+                previous_line = line;
+            }
+            continue;
+        };
+
+        let mut code = String::new();
+        for line in lines.by_ref() {
+            if is_closing_fence(line, fence) {
+                break;
+            }
+            code.push_str(line);
+            code.push('\n');
+        }
+
+        let (language, info_hint) = parse_info(info);
+        let filename_hint = info_hint.or_else(|| hint_from_line(previous_line));
+        let index = artifacts.len();
+        let file_name = unique_name(
+            &mut used_names,
+            filename_hint
+                .clone()
+                .unwrap_or_else(|| format!("extrait-{}.{}", index + 1, extension(&language))),
+        );
+        artifacts.push(CodeArtifact {
+            index,
+            language,
+            filename_hint,
+            file_name,
+            content: code,
+        });
+        previous_line = "";
+    }
+    artifacts
+}
+
+/// Archive zip des blocs de code, chacun sous son nom de fichier.
+pub(crate) fn artifacts_zip(artifacts: &[CodeArtifact]) -> std::io::Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Vec::new());
+    for artifact in artifacts {
+        zip.add(&artifact.file_name, artifact.content.as_bytes())?;
+    }
+    zip.finish()
+}
+
+/// Bloc de code ```ext:chemin contenant `content`, avec un délimiteur plus long que toute
+/// suite d'accents graves du fichier pour qu'il soit relu tel quel.
+pub(crate) fn fenced_file(path: &str, content: &str) -> String {
+    let longest_run = content
+        .split(|ch| ch != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    let language = path
+        .rsplit('/')
+        .next()
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, extension)| extension)
+        .unwrap_or("text");
+    let newline = if content.ends_with('\n') || content.is_empty() {
+        ""
+    } else {
+        "\n"
+    };
+    format!("{fence}{language}:{path}\n{content}{newline}{fence}")
+}
+
+/// Délimiteur ouvrant (caractère et longueur) et chaîne d'info d'une ligne, indentée de
+/// trois espaces au plus.
+fn opening_fence(line: &str) -> Option<((char, usize), &str)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let fence_char = trimmed
+        .chars()
+        .next()
+        .filter(|ch| matches!(ch, '`' | '~'))?;
+    let length = trimmed.chars().take_while(|ch| *ch == fence_char).count();
+    let info = &trimmed[length..];
+    // Une chaîne d'info ne contient pas d'accent grave (code en ligne ```x```).
+    (length >= 3 && !(fence_char == '`' && info.contains('`')))
+        .then_some(((fence_char, length), info.trim()))
+}
+
+fn is_closing_fence(line: &str, (fence_char, length): (char, usize)) -> bool {
+    let trimmed = line.trim();
+    trimmed.chars().take_while(|ch| *ch == fence_char).count() >= length
+        && trimmed.chars().all(|ch| ch == fence_char)
+}
+
+/// Langage et nom de fichier de la chaîne d'info : ```rust, ```rust:src/main.rs,
+/// ```python title="app.py" (ou `file=`, `filename=`), ```Cargo.toml.
+fn parse_info(info: &str) -> (Option<String>, Option<String>) {
+    let mut words = info.split_whitespace();
+    let Some(first) = words.next() else {
+        return (None, None);
+    };
+    let (language, mut hint) = match first.split_once(':') {
+        Some((language, path)) => (Some(language), clean_path(path)),
+        None if looks_like_file_name(first) => (None, clean_path(first)),
+        None => (Some(first), None),
+    };
+    for word in words {
+        if let Some((key, value)) = word.split_once('=')
+            && matches!(key, "title" | "file" | "filename" | "name")
+        {
+            hint = hint.or_else(|| clean_path(value.trim_matches(['"', '\''])));
+        }
+    }
+    let language = language
+        .map(str::to_lowercase)
+        .filter(|language| !language.is_empty());
+    (language, hint)
+}
+
+/// Nom de fichier annoncé par la ligne qui précède le bloc, entre accents graves ou en
+/// gras : « Fichier `src/main.rs` : », « **Cargo.toml** ».
+fn hint_from_line(line: &str) -> Option<String> {
+    if line.chars().count() > HINT_LINE_MAX_CHARS {
+        return None;
+    }
+    ["`", "**"]
+        .into_iter()
+        .flat_map(|delimiter| line.split(delimiter).skip(1).step_by(2))
+        .find(|candidate| looks_like_file_name(candidate))
+        .and_then(clean_path)
+}
+
+/// `chemin/nom.ext` sans espace, avec une extension de 1 à 10 caractères alphanumériques.
+fn looks_like_file_name(candidate: &str) -> bool {
+    let valid_chars = candidate
+        .chars()
+        .all(|ch| ch.is_alphanumeric() || matches!(ch, '.' | '-' | '_' | '/'));
+    let extension = candidate
+        .rsplit('/')
+        .next()
+        .and_then(|name| name.rsplit_once('.'))
+        .filter(|(stem, _)| !stem.is_empty())
+        .map(|(_, extension)| extension);
+    valid_chars
+        && extension.is_some_and(|extension| {
+            (1..=10).contains(&extension.len())
+                && extension.chars().all(|ch| ch.is_ascii_alphanumeric())
+        })
+}
+
+/// Chemin relatif sûr dans une archive : segments nettoyés, sans `.` ni `..`.
+fn clean_path(path: &str) -> Option<String> {
+    let segments: Vec<String> = path
+        .split(['/', '\\'])
+        .filter(|segment| !segment.is_empty() && *segment != "." && *segment != "..")
+        .map(sanitize_file_name)
+        .collect();
+    (!segments.is_empty()).then(|| segments.join("/"))
+}
+
+/// Ajoute `-2`, `-3`... avant l'extension d'un nom déjà pris (fichier montré deux fois).
+fn unique_name(used: &mut HashSet<String>, name: String) -> String {
+    let mut candidate = name.clone();
+    let mut counter = 2;
+    while !used.insert(candidate.clone()) {
+        candidate = match name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() && !stem.ends_with('/') => {
+                format!("{stem}-{counter}.{extension}")
+            }
+            _ => format!("{name}-{counter}"),
+        };
+        counter += 1;
+    }
+    candidate
+}
+
+fn extension(language: &Option<String>) -> &'static str {
+    match language.as_deref() {
+        Some("rust" | "rs") => "rs",
+        Some("python" | "py") => "py",
+        Some("javascript" | "js" | "node") => "js",
+        Some("typescript" | "ts") => "ts",
+        Some("tsx") => "tsx",
+        Some("jsx") => "jsx",
+        Some("json") => "json",
+        Some("yaml" | "yml") => "yaml",
+        Some("toml") => "toml",
+        Some("html") => "html",
+        Some("css") => "css",
+        Some("scss") => "scss",
+        Some("sql") => "sql",
+        Some("bash" | "sh" | "shell" | "zsh" | "console") => "sh",
+        Some("powershell" | "ps1") => "ps1",
+        Some("go" | "golang") => "go",
+        Some("java") => "java",
+        Some("kotlin" | "kt") => "kt",
+        Some("swift") => "swift",
+        Some("c") => "c",
+        Some("cpp" | "c++" | "cc") => "cpp",
+        Some("csharp" | "cs" | "c#") => "cs",
+        Some("ruby" | "rb") => "rb",
+        Some("php") => "php",
+        Some("markdown" | "md") => "md",
+        Some("xml") => "xml",
+        Some("dockerfile" | "docker") => "dockerfile",
+        Some("latex" | "tex") => "tex",
+        _ => "txt",
+    }
+}
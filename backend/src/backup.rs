@@ -0,0 +1,249 @@
+//! Sauvegarde planifiée des discussions dans un stockage objet (`BACKUP_URL`) : un fichier
+//! `backup-<date>.json.gz` par sauvegarde, listé et restauré via `/api/admin/backups`.
+
+use std::{io::Read, sync::Arc};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use futures::TryStreamExt;
+use object_store::{ObjectStore, PutPayload, aws::AmazonS3Builder, path::Path};
+use serde::{Deserialize, Serialize};
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    models::{BackupInfo, ChatSession, RestoreReport},
+};
+
+/// Version du format écrit ; une sauvegarde d'une version plus récente n'est pas restaurée.
+const BACKUP_FORMAT_VERSION: u32 = 1;
+const BACKUP_PREFIX: &str = "backup-";
+const BACKUP_SUFFIX: &str = ".json.gz";
+/// Date UTC de la sauvegarde dans son nom, à la milliseconde
+const BACKUP_TIME_FORMAT: &str = "%Y%m%dT%H%M%S%3fZ";
+
+/// Contenu d'une sauvegarde, compressé en gzip.
+#[derive(Serialize, Deserialize)]
+struct BackupFile {
+    version: u32,
+    created_at: DateTime<Utc>,
+    /// Sessions complètes, archivées comprises, au format de `GET /api/chat/sessions`
+    sessions: Vec<ChatSession>,
+}
+
+/// Emplacement des sauvegardes : un bucket S3 (`s3://bucket/dossier`) ou un dossier local
+/// (`file:///chemin`).
+pub(crate) struct BackupStore {
+    store: Arc<dyn ObjectStore>,
+    prefix: Path,
+}
+
+impl BackupStore {
+    pub(crate) fn from_url(url: &str) -> Result<Self, String> {
+        let url = Url::parse(url).map_err(|err| format!("{url}: {err}"))?;
+        let (store, prefix) = object_store::parse_url(&url).map_err(|err| err.to_string())?;
+        let store: Arc<dyn ObjectStore> = if url.scheme() == "s3" {
+            // Identifiants, région et endpoint lus dans les variables `AWS_*` habituelles
+            Arc::new(
+                AmazonS3Builder::from_env()
+                    .with_url(url.as_str())
+                    .build()
+                    .map_err(|err| err.to_string())?,
+            )
+        } else {
+            Arc::from(store)
+        };
+        Ok(BackupStore { store, prefix })
+    }
+
+    fn path(&self, name: &str) -> Path {
+        self.prefix.child(name)
+    }
+}
+
+/// Écrit une sauvegarde de toutes les sessions. Les fichiers joints eux-mêmes restent dans
+/// le dossier des uploads : seules leurs métadonnées sont sauvegardées.
+pub(crate) async fn run_backup(state: &AppState) -> Result<BackupInfo, String> {
+    let backups = backup_store(state)?;
+    let session_ids = state
+        .repo
+        .all_session_ids()
+        .await
+        .map_err(|err| err.to_string())?;
+    let mut sessions = Vec::with_capacity(session_ids.len());
+    for session_id in session_ids {
+        match state.repo.fetch_session(session_id).await {
+            Ok(session) => sessions.push(session),
+            // Session supprimée pendant la sauvegarde
+            Err(sqlx::Error::RowNotFound) => {}
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+
+    let created_at = Utc::now();
+    let file = BackupFile {
+        version: BACKUP_FORMAT_VERSION,
+        created_at,
+        sessions,
+    };
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    serde_json::to_writer(&mut encoder, &file).map_err(|err| err.to_string())?;
+    let data = encoder.finish().map_err(|err| err.to_string())?;
+
+    let name = format!(
+        "{BACKUP_PREFIX}{}{BACKUP_SUFFIX}",
+        created_at.format(BACKUP_TIME_FORMAT)
+    );
+    let size_bytes = data.len() as u64;
+    backups
+        .store
+        .put(&backups.path(&name), PutPayload::from(data))
+        .await
+        .map_err(|err| format!("écriture de {name}: {err}"))?;
+    Ok(BackupInfo {
+        name,
+        size_bytes,
+        created_at,
+        sessions: Some(file.sessions.len()),
+    })
+}
+
+/// Sauvegardes présentes dans le stockage, de la plus récente à la plus ancienne.
+pub(crate) async fn list_backups(state: &AppState) -> Result<Vec<BackupInfo>, String> {
+    let backups = backup_store(state)?;
+    let objects: Vec<_> = backups
+        .store
+        .list(Some(&backups.prefix))
+        .try_collect()
+        .await
+        .map_err(|err| err.to_string())?;
+    let mut infos: Vec<BackupInfo> = objects
+        .into_iter()
+        .filter_map(|object| {
+            let name = object.location.filename()?.to_string();
+            if !is_backup_name(&name) {
+                return None;
+            }
+            Some(BackupInfo {
+                created_at: backup_time(&name).unwrap_or(object.last_modified),
+                name,
+                size_bytes: object.size as u64,
+                sessions: None,
+            })
+        })
+        .collect();
+    // La date fait partie du nom : l'ordre alphabétique est l'ordre chronologique.
+    infos.sort_by(|a, b| b.name.cmp(&a.name));
+    Ok(infos)
+}
+
+/// Recrée les sessions de la sauvegarde `name` absentes de la base ; celles qui existent
+/// encore sont laissées telles quelles. `Ok(None)` si la sauvegarde n'existe pas.
+pub(crate) async fn restore_backup(
+    state: &AppState,
+    name: &str,
+) -> Result<Option<RestoreReport>, String> {
+    let Some(file) = read_backup(backup_store(state)?, name).await? else {
+        return Ok(None);
+    };
+    let mut report = RestoreReport {
+        backup: name.to_string(),
+        sessions_restored: 0,
+        sessions_skipped: 0,
+    };
+    for session in &file.sessions {
+        let restored = state
+            .repo
+            .restore_session(session)
+            .await
+            .map_err(|err| format!("session {}: {err}", session.id))?;
+        if restored {
+            report.sessions_restored += 1;
+        } else {
+            report.sessions_skipped += 1;
+        }
+    }
+    Ok(Some(report))
+}
+
+/// Session supprimée telle qu'elle était à la date `at`. La première sauvegarde écrite
+/// après `at` qui la contient est la plus fidèle : les messages postérieurs en sont écartés.
+/// À défaut, la plus récente des sauvegardes antérieures. `Ok(None)` si aucune sauvegarde
+/// ne contient la session.
+pub(crate) async fn find_session_at(
+    state: &AppState,
+    session_id: Uuid,
+    at: DateTime<Utc>,
+) -> Result<Option<(String, ChatSession)>, String> {
+    let backups = backup_store(state)?;
+    let (mut after, before): (Vec<_>, Vec<_>) = list_backups(state)
+        .await?
+        .into_iter()
+        .partition(|backup| backup.created_at >= at);
+    after.reverse();
+    for backup in after.into_iter().chain(before) {
+        let Some(file) = read_backup(backups, &backup.name).await? else {
+            continue;
+        };
+        let Some(mut session) = file
+            .sessions
+            .into_iter()
+            .find(|session| session.id == session_id)
+        else {
+            continue;
+        };
+        if session.created_at > at {
+            return Ok(None);
+        }
+        session.messages.retain(|message| message.created_at <= at);
+        session.draft = session.draft.filter(|draft| draft.updated_at <= at);
+        return Ok(Some((backup.name, session)));
+    }
+    Ok(None)
+}
+
+async fn read_backup(backups: &BackupStore, name: &str) -> Result<Option<BackupFile>, String> {
+    if !is_backup_name(name) {
+        return Ok(None);
+    }
+    let data = match backups.store.get(&backups.path(name)).await {
+        Ok(result) => result.bytes().await.map_err(|err| err.to_string())?,
+        Err(object_store::Error::NotFound { .. }) => return Ok(None),
+        Err(err) => return Err(err.to_string()),
+    };
+    let mut json = Vec::new();
+    GzDecoder::new(data.as_ref())
+        .read_to_end(&mut json)
+        .map_err(|err| format!("{name}: {err}"))?;
+    let file: BackupFile = serde_json::from_slice(&json).map_err(|err| format!("{name}: {err}"))?;
+    if file.version > BACKUP_FORMAT_VERSION {
+        return Err(format!(
+            "{name}: format {} non pris en charge (au plus {BACKUP_FORMAT_VERSION})",
+            file.version
+        ));
+    }
+    Ok(Some(file))
+}
+
+fn backup_store(state: &AppState) -> Result<&BackupStore, String> {
+    state
+        .backups
+        .as_deref()
+        .ok_or_else(|| "sauvegardes désactivées (BACKUP_URL)".to_string())
+}
+
+/// Nom d'un fichier écrit par `run_backup`, sans chemin.
+fn is_backup_name(name: &str) -> bool {
+    name.starts_with(BACKUP_PREFIX) && name.ends_with(BACKUP_SUFFIX) && !name.contains('/')
+}
+
+/// Date d'une sauvegarde lue dans son nom : elle ne change pas si le fichier est copié.
+fn backup_time(name: &str) -> Option<DateTime<Utc>> {
+    let time = name
+        .strip_prefix(BACKUP_PREFIX)?
+        .strip_suffix(BACKUP_SUFFIX)?;
+    NaiveDateTime::parse_from_str(time, BACKUP_TIME_FORMAT)
+        .ok()
+        .map(|time| time.and_utc())
+}
@@ -0,0 +1,172 @@
+//! Tâches de maintenance sans passer par l'API HTTP : `carlgpt-admin --help`.
+
+use std::{path::PathBuf, process::ExitCode, time::Duration};
+
+use backend::{
+    config::Config,
+    maintenance,
+    repository::{ChatRepository, connect_database, run_migrations},
+    retention::apply_retention,
+    urls::PublicUrls,
+};
+use clap::{Parser, Subcommand};
+use dotenvy::dotenv;
+use uuid::Uuid;
+
+#[derive(Parser)]
+#[command(name = "carlgpt-admin", about = "Maintenance du backend CarlGPT")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Applique les migrations SQL en attente
+    Migrate,
+    /// Supprime les fichiers uploadés qui ne sont plus référencés (pièce jointe ou brouillon)
+    PurgeOrphanUploads {
+        /// Ignore les fichiers plus récents (upload en cours de rédaction)
+        #[arg(long, default_value_t = 24)]
+        older_than_hours: u64,
+        /// Liste les fichiers sans les supprimer
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Exporte une discussion (messages, pièces jointes, brouillon) en JSON
+    ExportSession {
+        id: Uuid,
+        /// Fichier de sortie (sortie standard par défaut)
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Applique une fois les règles de rétention configurées (`RETENTION_*`)
+    ApplyRetention,
+    /// Recalcule la consommation de tokens de chaque discussion depuis ses messages
+    RecomputeUsage {
+        /// Sortie JSON au lieu du tableau
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenv().ok();
+    let cli = Cli::parse();
+    match run(cli.command, &Config::from_env()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("❌ {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(command: Command, config: &Config) -> Result<(), String> {
+    let pool = connect_database(config).await;
+    let repo = ChatRepository::new(pool.clone(), PublicUrls::from_config(config));
+
+    match command {
+        Command::Migrate => {
+            run_migrations(&pool).await.map_err(|err| err.to_string())?;
+            println!("✅ Migrations appliquées");
+            Ok(())
+        }
+        Command::PurgeOrphanUploads {
+            older_than_hours,
+            dry_run,
+        } => purge_orphan_uploads(&repo, config, older_than_hours, dry_run).await,
+        Command::ExportSession { id, output } => {
+            let session = repo.fetch_session(id).await.map_err(|err| match err {
+                sqlx::Error::RowNotFound => format!("Discussion {id} introuvable"),
+                err => err.to_string(),
+            })?;
+            let json = serde_json::to_string_pretty(&session).map_err(|err| err.to_string())?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, json).map_err(|err| err.to_string())?;
+                    println!("✅ Discussion exportée dans {}", path.display());
+                }
+                None => println!("{json}"),
+            }
+            Ok(())
+        }
+        Command::ApplyRetention => {
+            if !config.retention.is_enabled() {
+                return Err("Aucune règle de rétention configurée (RETENTION_*)".to_string());
+            }
+            let report = apply_retention(&repo, &config.retention)
+                .await
+                .map_err(|err| err.to_string())?;
+            println!("✅ {report}");
+            Ok(())
+        }
+        Command::RecomputeUsage { json } => {
+            let usage = repo
+                .usage_by_session()
+                .await
+                .map_err(|err| err.to_string())?;
+            if json {
+                let json = serde_json::to_string_pretty(&usage).map_err(|err| err.to_string())?;
+                println!("{json}");
+                return Ok(());
+            }
+            println!(
+                "{:<36}  {:>8}  {:>10}  {:>10}  {:>10}  titre",
+                "session", "réponses", "prompt", "complétion", "en cache"
+            );
+            for session in &usage {
+                println!(
+                    "{:<36}  {:>8}  {:>10}  {:>10}  {:>10}  {}{}",
+                    session.session_id,
+                    session.answers,
+                    session.prompt_tokens,
+                    session.completion_tokens,
+                    session.cached_tokens,
+                    session.title,
+                    if session.archived { " (archivée)" } else { "" }
+                );
+            }
+            println!(
+                "Total : {} prompt, {} complétion, {} en cache",
+                usage.iter().map(|s| s.prompt_tokens).sum::<i64>(),
+                usage.iter().map(|s| s.completion_tokens).sum::<i64>(),
+                usage.iter().map(|s| s.cached_tokens).sum::<i64>()
+            );
+            Ok(())
+        }
+    }
+}
+
+async fn purge_orphan_uploads(
+    repo: &ChatRepository,
+    config: &Config,
+    older_than_hours: u64,
+    dry_run: bool,
+) -> Result<(), String> {
+    let orphans = maintenance::purge_orphan_uploads(
+        repo,
+        &config.upload_dir,
+        Duration::from_secs(older_than_hours * 3600),
+        dry_run,
+    )
+    .await?;
+    if dry_run {
+        for name in &orphans.files {
+            println!("{name}");
+        }
+    }
+
+    let verb = if dry_run {
+        "à supprimer"
+    } else {
+        "supprimés"
+    };
+    println!(
+        "✅ {} fichiers orphelins {verb} ({} Ko)",
+        orphans.files.len(),
+        orphans.bytes / 1024
+    );
+    Ok(())
+}
@@ -0,0 +1,415 @@
+//! Cache des réponses de `POST /api/ai` et des sessions lues en entier, en mémoire ou partagés
+//! via Redis, et cache en mémoire du contenu des pièces jointes.
+
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use redis::{AsyncCommands, Script, aio::ConnectionManager};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    models::{ChatMessagePayload, ChatSession, CompletionParams, TokenUsage},
+    providers::AiModelChoice,
+    storage::AttachmentContent,
+};
+
+const REDIS_CACHE_PREFIX: &str = "carlgpt:ai-cache:";
+const REDIS_SESSION_PREFIX: &str = "carlgpt:session-cache:";
+/// Compteur incrémenté à chaque écriture : une lecture en base commencée avant n'est pas gardée
+const REDIS_SESSION_EPOCH: &str = "carlgpt:session-cache:epoch";
+/// Compteur incrémenté quand plusieurs sessions changent d'un coup : les copies plus anciennes
+/// ne sont plus servies
+const REDIS_SESSION_GENERATION: &str = "carlgpt:session-cache:generation";
+
+/// Cache des réponses de `POST /api/ai` : une requête identique (messages, modèle,
+/// paramètres) renvoie la réponse déjà payée tant qu'elle n'a pas expiré. En mémoire,
+/// l'entrée la moins récemment servie est évincée au-delà de `max_entries` ; avec Redis,
+/// le cache est partagé entre instances et l'expiration est confiée à Redis.
+pub(crate) struct ResponseCache {
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<u64, CachedResponse>>,
+    redis: Option<ConnectionManager>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RedisCachedResponse {
+    response: String,
+    usage: Option<TokenUsage>,
+}
+
+struct CachedResponse {
+    stored_at: Instant,
+    last_used: Instant,
+    response: String,
+    usage: Option<TokenUsage>,
+}
+
+impl ResponseCache {
+    pub(crate) fn new(ttl: Duration, max_entries: usize, redis: Option<ConnectionManager>) -> Self {
+        ResponseCache {
+            ttl,
+            max_entries: max_entries.max(1),
+            entries: Mutex::new(HashMap::new()),
+            redis,
+        }
+    }
+
+    pub(crate) fn key(
+        model: AiModelChoice,
+        messages: &[ChatMessagePayload],
+        params: Option<&CompletionParams>,
+    ) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        model.model_id().hash(&mut hasher);
+        serde_json::to_string(messages)
+            .unwrap_or_default()
+            .hash(&mut hasher);
+        serde_json::to_string(&params)
+            .unwrap_or_default()
+            .hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub(crate) async fn get(&self, key: u64) -> Option<(String, Option<TokenUsage>)> {
+        if let Some(redis) = &self.redis {
+            let mut conn = redis.clone();
+            let cached: Option<String> = conn
+                .get(format!("{REDIS_CACHE_PREFIX}{key:016x}"))
+                .await
+                .map_err(|err| eprintln!("Lecture du cache Redis impossible: {err}"))
+                .ok()
+                .flatten();
+            let cached: RedisCachedResponse = serde_json::from_str(&cached?).ok()?;
+            return Some((cached.response, cached.usage));
+        }
+
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        let entry = entries.get_mut(&key)?;
+        if entry.stored_at.elapsed() > self.ttl {
+            entries.remove(&key);
+            return None;
+        }
+        entry.last_used = Instant::now();
+        Some((entry.response.clone(), entry.usage))
+    }
+
+    pub(crate) async fn insert(&self, key: u64, response: String, usage: Option<TokenUsage>) {
+        if let Some(redis) = &self.redis {
+            let mut conn = redis.clone();
+            let value = json!(RedisCachedResponse { response, usage }).to_string();
+            let stored: redis::RedisResult<()> = conn
+                .set_ex(
+                    format!("{REDIS_CACHE_PREFIX}{key:016x}"),
+                    value,
+                    self.ttl.as_secs(),
+                )
+                .await;
+            if let Err(err) = stored {
+                eprintln!("Écriture du cache Redis impossible: {err}");
+            }
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            entries.retain(|_, entry| entry.stored_at.elapsed() <= self.ttl);
+            if entries.len() >= self.max_entries {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(key, _)| *key);
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+        let now = Instant::now();
+        entries.insert(
+            key,
+            CachedResponse {
+                stored_at: now,
+                last_used: now,
+                response,
+                usage,
+            },
+        );
+    }
+}
+
+/// Cache des sessions lues en entier (`ChatRepository::fetch_session`) : la fin de chaque
+/// génération et chaque modification relisent tout l'historique, souvent inchangé depuis la
+/// lecture précédente. Chaque écriture du dépôt invalide la session touchée, ou tout le cache
+/// quand elle en touche plusieurs ; la durée de vie courte borne l'écart avec une écriture faite
+/// hors du serveur (`carlgpt-admin`). Avec Redis, le cache est partagé entre instances et une
+/// écriture sur l'une invalide la copie pour toutes.
+pub(crate) struct SessionCache {
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<LocalSessions>,
+    redis: Option<ConnectionManager>,
+}
+
+#[derive(Default)]
+struct LocalSessions {
+    epoch: u64,
+    by_id: HashMap<Uuid, CachedSession>,
+}
+
+struct CachedSession {
+    stored_at: Instant,
+    last_used: Instant,
+    session: ChatSession,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RedisCachedSession {
+    generation: u64,
+    session: ChatSession,
+}
+
+/// Compteurs relevés avant de lire une session absente du cache : la copie n'est gardée que si
+/// aucune écriture n'a eu lieu pendant la lecture.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SessionReadMark {
+    epoch: u64,
+    generation: u64,
+}
+
+pub(crate) enum SessionLookup {
+    Hit(Box<ChatSession>),
+    /// `None` si Redis n'a pas répondu : la session lue ne sera pas mise en cache.
+    Miss(Option<SessionReadMark>),
+}
+
+impl SessionCache {
+    pub(crate) fn new(ttl: Duration, max_entries: usize, redis: Option<ConnectionManager>) -> Self {
+        SessionCache {
+            ttl,
+            max_entries: max_entries.max(1),
+            entries: Mutex::new(LocalSessions::default()),
+            redis,
+        }
+    }
+
+    pub(crate) async fn get(&self, session_id: Uuid) -> SessionLookup {
+        if let Some(redis) = &self.redis {
+            let mut conn = redis.clone();
+            let read: redis::RedisResult<(Option<u64>, Option<u64>, Option<String>)> =
+                redis::cmd("MGET")
+                    .arg(REDIS_SESSION_GENERATION)
+                    .arg(REDIS_SESSION_EPOCH)
+                    .arg(format!("{REDIS_SESSION_PREFIX}{session_id}"))
+                    .query_async(&mut conn)
+                    .await;
+            let (generation, epoch, cached) = match read {
+                Ok((generation, epoch, cached)) => {
+                    (generation.unwrap_or(0), epoch.unwrap_or(0), cached)
+                }
+                Err(err) => {
+                    eprintln!("Lecture du cache Redis impossible: {err}");
+                    return SessionLookup::Miss(None);
+                }
+            };
+            if let Some(cached) = cached
+                .and_then(|cached| serde_json::from_str::<RedisCachedSession>(&cached).ok())
+                .filter(|cached| cached.generation == generation)
+            {
+                return SessionLookup::Hit(Box::new(cached.session));
+            }
+            return SessionLookup::Miss(Some(SessionReadMark { epoch, generation }));
+        }
+
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        let mark = SessionReadMark {
+            epoch: entries.epoch,
+            generation: 0,
+        };
+        let Some(entry) = entries.by_id.get_mut(&session_id) else {
+            return SessionLookup::Miss(Some(mark));
+        };
+        if entry.stored_at.elapsed() > self.ttl {
+            entries.by_id.remove(&session_id);
+            return SessionLookup::Miss(Some(mark));
+        }
+        entry.last_used = Instant::now();
+        SessionLookup::Hit(Box::new(entry.session.clone()))
+    }
+
+    /// Garde la session lue en base, sauf si une écriture a eu lieu depuis `mark`.
+    pub(crate) async fn insert(&self, mark: SessionReadMark, session: &ChatSession) {
+        if let Some(redis) = &self.redis {
+            let mut conn = redis.clone();
+            let value = json!(RedisCachedSession {
+                generation: mark.generation,
+                session: session.clone(),
+            })
+            .to_string();
+            let stored: redis::RedisResult<()> = Script::new(
+                r"if (redis.call('GET', KEYS[1]) or '0') == ARGV[1] then
+                    redis.call('SET', KEYS[2], ARGV[2], 'EX', ARGV[3])
+                  end",
+            )
+            .key(REDIS_SESSION_EPOCH)
+            .key(format!("{REDIS_SESSION_PREFIX}{}", session.id))
+            .arg(mark.epoch)
+            .arg(value)
+            .arg(self.ttl.as_secs().max(1))
+            .invoke_async(&mut conn)
+            .await;
+            if let Err(err) = stored {
+                eprintln!("Écriture du cache Redis impossible: {err}");
+            }
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        if entries.epoch != mark.epoch {
+            return;
+        }
+        if entries.by_id.len() >= self.max_entries && !entries.by_id.contains_key(&session.id) {
+            entries
+                .by_id
+                .retain(|_, entry| entry.stored_at.elapsed() <= self.ttl);
+            if entries.by_id.len() >= self.max_entries {
+                let oldest = entries
+                    .by_id
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(id, _)| *id);
+                if let Some(oldest) = oldest {
+                    entries.by_id.remove(&oldest);
+                }
+            }
+        }
+        let now = Instant::now();
+        entries.by_id.insert(
+            session.id,
+            CachedSession {
+                stored_at: now,
+                last_used: now,
+                session: session.clone(),
+            },
+        );
+    }
+
+    /// À appeler après chaque écriture touchant la session.
+    pub(crate) async fn invalidate(&self, session_id: Uuid) {
+        if let Some(redis) = &self.redis {
+            let mut conn = redis.clone();
+            let invalidated: redis::RedisResult<()> = redis::pipe()
+                .atomic()
+                .incr(REDIS_SESSION_EPOCH, 1)
+                .ignore()
+                .del(format!("{REDIS_SESSION_PREFIX}{session_id}"))
+                .ignore()
+                .query_async(&mut conn)
+                .await;
+            if let Err(err) = invalidated {
+                eprintln!("Invalidation du cache Redis impossible: {err}");
+            }
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        entries.epoch += 1;
+        entries.by_id.remove(&session_id);
+    }
+
+    /// Écriture touchant un nombre indéterminé de sessions (rétention, extractions).
+    pub(crate) async fn invalidate_all(&self) {
+        if let Some(redis) = &self.redis {
+            let mut conn = redis.clone();
+            let invalidated: redis::RedisResult<()> = redis::pipe()
+                .atomic()
+                .incr(REDIS_SESSION_EPOCH, 1)
+                .ignore()
+                .incr(REDIS_SESSION_GENERATION, 1)
+                .ignore()
+                .query_async(&mut conn)
+                .await;
+            if let Err(err) = invalidated {
+                eprintln!("Invalidation du cache Redis impossible: {err}");
+            }
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        entries.epoch += 1;
+        entries.by_id.clear();
+    }
+}
+
+/// Contenu déjà lu des pièces jointes (texte extrait, data URL des images), par clé de stockage
+/// et type MIME : un fichier uploadé ne change plus, inutile de le relire et de ré-extraire un
+/// PDF à chaque message. Au-delà de `max_bytes`, l'entrée la moins récemment servie est évincée.
+pub(crate) struct AttachmentCache {
+    max_bytes: usize,
+    entries: Mutex<AttachmentEntries>,
+}
+
+#[derive(Default)]
+struct AttachmentEntries {
+    bytes: usize,
+    by_key: HashMap<(String, String), CachedAttachment>,
+}
+
+struct CachedAttachment {
+    last_used: Instant,
+    content: AttachmentContent,
+}
+
+impl AttachmentCache {
+    pub(crate) fn new(max_bytes: usize) -> Self {
+        AttachmentCache {
+            max_bytes,
+            entries: Mutex::new(AttachmentEntries::default()),
+        }
+    }
+
+    pub(crate) fn get(&self, storage_key: &str, mime_type: &str) -> Option<AttachmentContent> {
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        let entry = entries
+            .by_key
+            .get_mut(&(storage_key.to_string(), mime_type.to_string()))?;
+        entry.last_used = Instant::now();
+        Some(entry.content.clone())
+    }
+
+    pub(crate) fn insert(&self, storage_key: &str, mime_type: &str, content: AttachmentContent) {
+        let size = content.byte_len();
+        if size > self.max_bytes {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        let key = (storage_key.to_string(), mime_type.to_string());
+        if let Some(previous) = entries.by_key.remove(&key) {
+            entries.bytes -= previous.content.byte_len();
+        }
+        while entries.bytes + size > self.max_bytes {
+            let oldest = entries
+                .by_key
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            let Some(oldest) = oldest else { break };
+            if let Some(evicted) = entries.by_key.remove(&oldest) {
+                entries.bytes -= evicted.content.byte_len();
+            }
+        }
+        entries.bytes += size;
+        entries.by_key.insert(
+            key,
+            CachedAttachment {
+                last_used: Instant::now(),
+                content,
+            },
+        );
+    }
+}
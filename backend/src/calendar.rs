@@ -0,0 +1,259 @@
+//! Calendrier de l'utilisateur : l'outil `add_to_calendar` (`completion_params.calendar`)
+//! enregistre les évènements et tâches évoqués dans une discussion (« rappelle-moi de... »),
+//! servis en flux ICS auquel une application de calendrier s'abonne
+//! (`GET /api/me/calendar.ics?token=...`).
+
+use chrono::{DateTime, Days, NaiveDate, NaiveDateTime, Utc};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::{
+    AppState,
+    models::{CalendarItem, CalendarItemKind, NewCalendarItem},
+};
+
+pub(crate) const CALENDAR_TOOL: &str = "add_to_calendar";
+
+pub(crate) const CALENDAR_DESCRIPTION: &str = "Ajoute au calendrier de l'utilisateur un \
+évènement (rendez-vous, réunion, échéance datée) ou une tâche à faire qu'il mentionne ou \
+demande de lui rappeler. N'ajoute que ce que l'utilisateur prévoit vraiment de faire ; \
+utilise la date et le fuseau horaire actuels pour résoudre « demain » ou « vendredi ».";
+
+/// Longueur maximale d'une ligne ICS, en octets, avant repli (RFC 5545, 3.1)
+const ICS_LINE_MAX: usize = 75;
+
+#[derive(Deserialize)]
+struct CalendarArgs {
+    kind: CalendarItemKind,
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    start: Option<String>,
+    #[serde(default)]
+    end: Option<String>,
+}
+
+pub(crate) fn calendar_parameters() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "kind": {
+                "type": "string",
+                "enum": ["event", "todo"],
+                "description": "event : a lieu à une date ; todo : tâche à faire, avec une échéance facultative"
+            },
+            "title": {
+                "type": "string",
+                "description": "Intitulé court (ex. Dentiste, Envoyer le rapport à Julie)"
+            },
+            "description": {
+                "type": "string",
+                "description": "Détails utiles : lieu, personnes, documents à prévoir"
+            },
+            "start": {
+                "type": "string",
+                "description": "Début de l'évènement ou échéance de la tâche, en ISO 8601 : 2026-03-14 pour une journée entière, 2026-03-14T15:00:00+01:00 pour une heure précise (avec le décalage du fuseau de l'utilisateur)"
+            },
+            "end": {
+                "type": "string",
+                "description": "Fin de l'évènement, même format que start ; dernier jour inclus pour des journées entières"
+            }
+        },
+        "required": ["kind", "title"],
+        "additionalProperties": false
+    })
+}
+
+/// Exécute un appel de l'outil ; le résultat, renvoyé au modèle, décrit l'élément ajouté
+/// ou l'erreur à corriger.
+pub(crate) async fn add_to_calendar(state: &AppState, arguments: &str) -> String {
+    let args: CalendarArgs = match serde_json::from_str(arguments) {
+        Ok(args) => args,
+        Err(err) => return format!("Arguments invalides pour {CALENDAR_TOOL} : {err}."),
+    };
+    let item = match new_item(args) {
+        Ok(item) => item,
+        Err(err) => return format!("Élément non ajouté : {err}."),
+    };
+    match state.repo.insert_calendar_item(&item).await {
+        Ok(item) => match item.starts_at {
+            Some(starts_at) => format!(
+                "Ajouté au calendrier de l'utilisateur : {} ({}).",
+                item.title,
+                if item.all_day {
+                    starts_at.format("%Y-%m-%d").to_string()
+                } else {
+                    starts_at.to_rfc3339()
+                }
+            ),
+            None => format!("Ajouté aux tâches de l'utilisateur : {}.", item.title),
+        },
+        Err(err) => {
+            eprintln!("Impossible d'enregistrer l'élément du calendrier: {err}");
+            "Le calendrier est indisponible, l'élément n'a pas été ajouté.".to_string()
+        }
+    }
+}
+
+fn new_item(args: CalendarArgs) -> Result<NewCalendarItem, String> {
+    let title = args.title.trim().to_string();
+    if title.is_empty() {
+        return Err("l'intitulé est vide".to_string());
+    }
+    let start = args.start.as_deref().map(parse_when).transpose()?;
+    let end = args.end.as_deref().map(parse_when).transpose()?;
+    if args.kind == CalendarItemKind::Event && start.is_none() {
+        return Err("un évènement doit avoir une date de début (start)".to_string());
+    }
+    if args.kind == CalendarItemKind::Todo && end.is_some() {
+        return Err("une tâche n'a qu'une échéance (start), pas de fin".to_string());
+    }
+    let all_day = start.is_some_and(|(_, all_day)| all_day);
+    if let (Some((starts_at, _)), Some((ends_at, end_all_day))) = (start, end) {
+        if end_all_day != all_day {
+            return Err(
+                "start et end doivent être tous deux des dates, ou tous deux des heures"
+                    .to_string(),
+            );
+        }
+        if ends_at < starts_at {
+            return Err("la fin précède le début".to_string());
+        }
+    }
+    Ok(NewCalendarItem {
+        kind: args.kind,
+        title,
+        description: args
+            .description
+            .map(|description| description.trim().to_string())
+            .filter(|description| !description.is_empty()),
+        starts_at: start.map(|(starts_at, _)| starts_at),
+        ends_at: end.map(|(ends_at, _)| ends_at),
+        all_day,
+    })
+}
+
+/// Date d'une journée entière (`2026-03-14`, à minuit UTC) ou instant précis avec décalage.
+fn parse_when(value: &str) -> Result<(DateTime<Utc>, bool), String> {
+    let value = value.trim();
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Ok((datetime.with_timezone(&Utc), false));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok((date.and_time(chrono::NaiveTime::MIN).and_utc(), true));
+    }
+    if NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S").is_ok()
+        || NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M").is_ok()
+    {
+        return Err(format!(
+            "{value} n'a pas de fuseau horaire : ajoute le décalage de l'utilisateur (ex. +01:00)"
+        ));
+    }
+    Err(format!(
+        "date invalide : {value} (attendu 2026-03-14 ou 2026-03-14T15:00:00+01:00)"
+    ))
+}
+
+/// Flux iCalendar (RFC 5545) des éléments : `VEVENT` pour les évènements, `VTODO` pour les
+/// tâches. Un évènement sans fin dure une heure, ou sa journée.
+pub(crate) fn calendar_ics(items: &[CalendarItem]) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//CarlGPT//Calendrier//FR".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "X-WR-CALNAME:CarlGPT".to_string(),
+    ];
+    for item in items {
+        let component = match item.kind {
+            CalendarItemKind::Event => "VEVENT",
+            CalendarItemKind::Todo => "VTODO",
+        };
+        lines.push(format!("BEGIN:{component}"));
+        lines.push(format!("UID:{}@carlgpt", item.id));
+        lines.push(format!("DTSTAMP:{}", ics_datetime(item.created_at)));
+        lines.push(format!("SUMMARY:{}", ics_text(&item.title)));
+        if let Some(description) = &item.description {
+            lines.push(format!("DESCRIPTION:{}", ics_text(description)));
+        }
+        match (item.kind, item.starts_at) {
+            (CalendarItemKind::Event, Some(starts_at)) if item.all_day => {
+                // DTEND d'une journée entière : le lendemain du dernier jour
+                let last_day = item.ends_at.unwrap_or(starts_at).date_naive();
+                lines.push(format!("DTSTART;VALUE=DATE:{}", ics_date(starts_at)));
+                lines.push(format!(
+                    "DTEND;VALUE=DATE:{}",
+                    last_day
+                        .checked_add_days(Days::new(1))
+                        .unwrap_or(last_day)
+                        .format("%Y%m%d")
+                ));
+            }
+            (CalendarItemKind::Event, Some(starts_at)) => {
+                lines.push(format!("DTSTART:{}", ics_datetime(starts_at)));
+                match item.ends_at {
+                    Some(ends_at) => lines.push(format!("DTEND:{}", ics_datetime(ends_at))),
+                    None => lines.push("DURATION:PT1H".to_string()),
+                }
+            }
+            (CalendarItemKind::Todo, Some(due)) if item.all_day => {
+                lines.push(format!("DUE;VALUE=DATE:{}", ics_date(due)));
+            }
+            (CalendarItemKind::Todo, Some(due)) => {
+                lines.push(format!("DUE:{}", ics_datetime(due)));
+            }
+            (_, None) => {}
+        }
+        if item.kind == CalendarItemKind::Todo {
+            lines.push("STATUS:NEEDS-ACTION".to_string());
+        }
+        lines.push(format!("END:{component}"));
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut ics = String::new();
+    for line in lines {
+        fold_line(&mut ics, &line);
+    }
+    ics
+}
+
+fn ics_datetime(datetime: DateTime<Utc>) -> String {
+    datetime.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn ics_date(datetime: DateTime<Utc>) -> String {
+    datetime.format("%Y%m%d").to_string()
+}
+
+/// Valeur TEXT : `\`, `;`, `,` et les retours à la ligne sont échappés.
+fn ics_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Ajoute une ligne terminée par CRLF, repliée en lignes de 75 octets au plus : chaque
+/// suite commence par une espace, sans couper un caractère UTF-8.
+fn fold_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for ch in line.chars() {
+        if width + ch.len_utf8() > ICS_LINE_MAX {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(ch);
+        width += ch.len_utf8();
+    }
+    out.push_str("\r\n");
+}
@@ -0,0 +1,119 @@
+//! Enregistrement et rejeu des réponses des providers (`PROVIDER_CASSETTE_MODE`). En
+//! `record`, chaque flux SSE reçu de Groq/OpenAI est écrit dans `PROVIDER_CASSETTE_DIR` ; en
+//! `replay`, la même requête est servie depuis ce fichier, sans clé API ni coût.
+
+use std::{path::PathBuf, str::FromStr};
+
+use bytes::Bytes;
+use futures::stream::{self, BoxStream, StreamExt};
+use serde_json::Value;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CassetteMode {
+    Record,
+    Replay,
+}
+
+impl FromStr for CassetteMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "record" => Ok(CassetteMode::Record),
+            "replay" => Ok(CassetteMode::Replay),
+            other => Err(format!("mode de cassette inconnu: {other}")),
+        }
+    }
+}
+
+pub(crate) struct Cassettes {
+    pub(crate) mode: CassetteMode,
+    dir: PathBuf,
+}
+
+impl Cassettes {
+    pub(crate) fn new(mode: CassetteMode, dir: impl Into<PathBuf>) -> Self {
+        Cassettes {
+            mode,
+            dir: dir.into(),
+        }
+    }
+
+    /// Fichier de la requête : FNV-1a de l'URL et du corps, stable d'une compilation à
+    /// l'autre. `prompt_cache_key` en est exclue, elle ne fait que dériver des messages.
+    fn path(&self, provider: &str, url: &str, body: &Value) -> PathBuf {
+        let mut body = body.clone();
+        if let Some(object) = body.as_object_mut() {
+            object.remove("prompt_cache_key");
+        }
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in url
+            .bytes()
+            .chain([b'\n'])
+            .chain(body.to_string().into_bytes())
+        {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        self.dir.join(format!("{provider}-{hash:016x}.sse"))
+    }
+
+    /// Flux SSE enregistré pour cette requête.
+    pub(crate) async fn replay(
+        &self,
+        provider: &str,
+        url: &str,
+        body: &Value,
+    ) -> Result<BoxStream<'static, Result<Bytes, reqwest::Error>>, (axum::http::StatusCode, String)>
+    {
+        let path = self.path(provider, url, body);
+        let recorded = tokio::fs::read(&path).await.map_err(|err| {
+            (
+                axum::http::StatusCode::BAD_GATEWAY,
+                format!(
+                    "Aucune réponse enregistrée pour cette requête ({}): {err}",
+                    path.display()
+                ),
+            )
+        })?;
+        Ok(Box::pin(stream::iter([Ok(Bytes::from(recorded))])))
+    }
+
+    /// Relaie le flux du provider en le copiant ; le fichier n'est écrit que si le flux va
+    /// jusqu'au bout, pour ne pas rejouer une réponse coupée.
+    pub(crate) fn record(
+        &self,
+        provider: &str,
+        url: &str,
+        body: &Value,
+        stream: BoxStream<'static, Result<Bytes, reqwest::Error>>,
+    ) -> BoxStream<'static, Result<Bytes, reqwest::Error>> {
+        let path = self.path(provider, url, body);
+        Box::pin(stream::unfold(
+            (stream, Vec::new(), Some(path)),
+            |(mut stream, mut recorded, mut path)| async move {
+                match stream.next().await {
+                    Some(Ok(chunk)) => {
+                        recorded.extend_from_slice(&chunk);
+                        Some((Ok(chunk), (stream, recorded, path)))
+                    }
+                    Some(Err(err)) => Some((Err(err), (stream, recorded, None))),
+                    None => {
+                        if let Some(path) = path.take() {
+                            if let Some(dir) = path.parent() {
+                                let _ = tokio::fs::create_dir_all(dir).await;
+                            }
+                            if let Err(err) = tokio::fs::write(&path, &recorded).await {
+                                eprintln!(
+                                    "Impossible d'enregistrer la cassette {}: {err}",
+                                    path.display()
+                                );
+                            }
+                        }
+                        None
+                    }
+                }
+            },
+        ))
+    }
+}
@@ -0,0 +1,584 @@
+//! Configuration du backend, lue depuis les variables d'environnement (`.env`).
+
+use std::{collections::HashSet, env, time::Duration};
+
+use ipnet::IpNet;
+
+use crate::{
+    access::ModelPolicy,
+    archive::ArchiveSettings,
+    cassette::CassetteMode,
+    hooks::StreamHookSettings,
+    models::{ConversationTemplate, ServiceTier, Widget},
+    notify::NotifySettings,
+    plugins::PluginSettings,
+    prompt::PromptSettings,
+    providers::AiModelChoice,
+    proxy::parse_trusted_proxies,
+    retention::RetentionPolicy,
+    routing::AUTO_MODEL,
+    scrub::ScrubPolicy,
+};
+
+/// Paramètres de démarrage de l'application. `Config::from_env` reprend les variables
+/// documentées dans DOCUMENTATION.md ; les tests et autres binaires peuvent aussi construire
+/// la structure directement.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub database_url: String,
+    pub db_max_connections: u32,
+    pub db_min_connections: u32,
+    pub db_acquire_timeout: Duration,
+    pub db_statement_timeout_ms: Option<u64>,
+    /// Tentatives supplémentaires si PostgreSQL n'est pas encore prêt au démarrage
+    pub db_connect_retries: u32,
+    pub db_connect_retry_delay: Duration,
+    pub upload_dir: String,
+    /// URL publique du dossier des uploads ; relative (`/uploads`), elle est complétée
+    /// comme les autres liens générés
+    pub upload_base_url: String,
+    /// Racine des liens générés (`https://chat.example.com`) ; à défaut, schéma et hôte de
+    /// la requête
+    pub public_base_url: Option<String>,
+    /// Reverse proxies dont les en-têtes `X-Forwarded-*` sont crus (adresses ou réseaux)
+    pub trusted_proxies: Vec<IpNet>,
+    /// Dossier des archives d'export, hors du dossier public des uploads
+    pub export_dir: String,
+    /// Durée de validité du lien de téléchargement d'un export
+    pub export_link_ttl: Duration,
+    /// Passe de validation/réparation des blocs de code avant persistance
+    pub validate_code_blocks: bool,
+    pub redis_url: Option<String>,
+    /// Durée de vie du cache de `POST /api/ai` ; `None` le désactive
+    pub ai_cache_ttl: Option<Duration>,
+    pub ai_cache_max_entries: usize,
+    /// Durée de vie du cache des sessions lues en entier ; `None` le désactive
+    pub session_cache_ttl: Option<Duration>,
+    pub session_cache_max_entries: usize,
+    /// Taille maximale du cache en mémoire du contenu des pièces jointes ; 0 le désactive
+    pub attachment_cache_bytes: usize,
+    /// Plafond du texte envoyé au modèle pour une pièce jointe, en tokens
+    pub attachment_max_tokens: u64,
+    /// Durée maximale de l'extraction du texte d'un PDF joint
+    pub pdf_extract_timeout: Duration,
+    /// Attente maximale d'une extraction en tâche de fond avant de répondre sans le fichier
+    pub attachment_extraction_wait: Duration,
+    /// Regroupement des tokens streamés : délai maximal avant envoi du texte en attente et
+    /// taille à partir de laquelle il part ; `None` / 0 envoie chaque chunk du provider
+    pub stream_coalesce_interval: Option<Duration>,
+    pub stream_coalesce_chars: usize,
+    /// Remplace les providers par `MockProvider` (tests, développement sans clé API)
+    pub mock_provider: bool,
+    /// Refuse tout provider externe (déploiements isolés du réseau) ; seuls les providers
+    /// locaux restent joignables
+    pub local_only: bool,
+    /// Serveur Ollama (`http://localhost:11434`) du modèle local `ollama` ; `None` le
+    /// désactive
+    pub ollama_base_url: Option<String>,
+    /// Modèle servi par Ollama (`llama3.2`, `mistral`...)
+    pub ollama_model: String,
+    /// Enregistre (`record`) ou rejoue (`replay`) les réponses des providers
+    pub cassette_mode: Option<CassetteMode>,
+    pub cassette_dir: String,
+    /// Journalise le corps de chaque requête aux providers et le flux SSE reçu
+    pub provider_debug_log: bool,
+    /// Modèle par défaut et modèles ouverts aux non-admins
+    pub models: ModelPolicy,
+    /// Modèle des générations annexes (titres), indépendant de celui choisi par l'utilisateur
+    pub title_model: AiModelChoice,
+    /// Modèle rapide dont le brouillon est streamé en attendant la réponse (`draft`)
+    pub draft_model: AiModelChoice,
+    /// Niveau de traitement OpenAI des requêtes qui n'en précisent pas
+    pub service_tier: ServiceTier,
+    /// Modèles OpenAI appelés via l'API Responses plutôt que Chat Completions
+    pub responses_api_models: Vec<AiModelChoice>,
+    /// Jeton de l'en-tête `X-Admin-Token` ; sans lui, personne n'est admin
+    pub admin_token: Option<String>,
+    /// Archivage et purge automatiques des discussions
+    pub retention: RetentionPolicy,
+    /// Intervalle de la suppression planifiée des uploads orphelins ; `None` la désactive
+    pub upload_gc_interval: Option<Duration>,
+    /// Requêtes de génération par minute et par client ; `None` désactive la limite
+    pub rate_limit_per_minute: Option<u32>,
+    /// Générations streamées mises en file par client au-delà de `rate_limit_per_minute`,
+    /// au lieu d'un 429 ; `None` les refuse toutes
+    pub rate_limit_queue: Option<usize>,
+    /// Tokens consommables par jour (UTC) par tout le déploiement ; `None` pour illimité
+    pub daily_token_quota: Option<i64>,
+    /// Intervalle du recalcul de `usage_daily`
+    pub usage_rollup_interval: Duration,
+    /// Intervalle de la relecture des drapeaux de fonctionnalités, modifiés par une autre
+    /// instance
+    pub feature_flags_refresh: Duration,
+    /// Stockage des sauvegardes (`s3://bucket/dossier`, `file:///chemin`) ; `None` les
+    /// désactive
+    pub backup_url: Option<String>,
+    /// Intervalle des sauvegardes planifiées
+    pub backup_interval: Duration,
+    /// Secrets masqués dans les requêtes envoyées aux providers
+    pub scrub: ScrubPolicy,
+    /// Gabarit du prompt système et instructions du projet
+    pub system_prompt: PromptSettings,
+    /// Discussions prêtes à l'emploi proposées par `GET /api/templates`
+    pub templates: Vec<ConversationTemplate>,
+    /// Widgets de chat intégrables sur d'autres sites
+    pub widgets: Vec<Widget>,
+    /// Hooks intégrés appliqués au texte des réponses
+    pub stream_hooks: StreamHookSettings,
+    /// Sert aussi le service gRPC `carlgpt.v1.Chat` sur le port de l'API
+    pub grpc_enabled: bool,
+    /// Frontend compilé servi par le backend ; `None` s'il est servi à part
+    pub frontend_dir: Option<String>,
+    /// Email et webhook envoyés à la fin des longues générations
+    pub notifications: NotifySettings,
+    /// Archivage des échanges vers un webhook ou un stockage objet
+    pub archive: ArchiveSettings,
+    /// Service de capture de pages web (chromium headless, API `/screenshot` de
+    /// browserless) de l'outil `screenshot_page` ; `None` désactive l'outil
+    pub screenshot_service_url: Option<String>,
+    /// Durée maximale d'une capture
+    pub screenshot_timeout: Duration,
+    /// Hôtes internes que l'outil `screenshot_page` peut quand même capturer
+    pub screenshot_allowed_hosts: Vec<String>,
+    /// Plugins WASM des outils et filtres du flux
+    pub plugins: PluginSettings,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        Config {
+            database_url: env::var("DATABASE_URL")
+                .expect("DATABASE_URL doit être défini dans .env"),
+            db_max_connections: env_parse("DB_MAX_CONNECTIONS").unwrap_or(10),
+            db_min_connections: env_parse("DB_MIN_CONNECTIONS").unwrap_or(0),
+            db_acquire_timeout: Duration::from_secs(
+                env_parse("DB_ACQUIRE_TIMEOUT_SECS").unwrap_or(30),
+            ),
+            db_statement_timeout_ms: env_parse("DB_STATEMENT_TIMEOUT_MS"),
+            db_connect_retries: env_parse("DB_CONNECT_RETRIES").unwrap_or(0),
+            db_connect_retry_delay: Duration::from_secs(
+                env_parse("DB_CONNECT_RETRY_DELAY_SECS").unwrap_or(2),
+            ),
+            upload_dir: env::var("UPLOAD_DIR").unwrap_or_else(|_| "uploads".to_string()),
+            upload_base_url: env::var("UPLOAD_BASE_URL")
+                .unwrap_or_else(|_| "http://127.0.0.1:4000/uploads".to_string()),
+            public_base_url: env::var("PUBLIC_BASE_URL")
+                .ok()
+                .filter(|url| !url.trim().is_empty()),
+            trusted_proxies: env::var("TRUSTED_PROXIES")
+                .map(|value| {
+                    parse_trusted_proxies(&value)
+                        .unwrap_or_else(|err| panic!("TRUSTED_PROXIES invalide: {err}"))
+                })
+                .unwrap_or_default(),
+            export_dir: env::var("EXPORT_DIR").unwrap_or_else(|_| "exports".to_string()),
+            export_link_ttl: Duration::from_secs(
+                env_parse::<u64>("EXPORT_LINK_TTL_HOURS").unwrap_or(24) * 3600,
+            ),
+            validate_code_blocks: env::var("VALIDATE_CODE_BLOCKS")
+                .map(|value| !matches!(value.trim(), "0" | "false" | "off"))
+                .unwrap_or(true),
+            redis_url: env::var("REDIS_URL").ok(),
+            ai_cache_ttl: env_parse("AI_RESPONSE_CACHE_TTL_SECS")
+                .filter(|ttl| *ttl > 0)
+                .map(Duration::from_secs),
+            ai_cache_max_entries: env_parse("AI_RESPONSE_CACHE_MAX_ENTRIES").unwrap_or(1000),
+            session_cache_ttl: Some(env_parse("SESSION_CACHE_TTL_SECS").unwrap_or(5))
+                .filter(|ttl| *ttl > 0)
+                .map(Duration::from_secs),
+            session_cache_max_entries: env_parse("SESSION_CACHE_MAX_ENTRIES").unwrap_or(500),
+            attachment_cache_bytes: env_parse::<usize>("ATTACHMENT_CACHE_MAX_MB").unwrap_or(64)
+                * 1024
+                * 1024,
+            attachment_max_tokens: env_parse("ATTACHMENT_MAX_TOKENS").unwrap_or(12_500),
+            pdf_extract_timeout: Duration::from_secs(
+                env_parse("PDF_EXTRACT_TIMEOUT_SECS").unwrap_or(30),
+            ),
+            attachment_extraction_wait: Duration::from_secs(
+                env_parse("ATTACHMENT_EXTRACTION_WAIT_SECS").unwrap_or(15),
+            ),
+            stream_coalesce_interval: env_parse("STREAM_COALESCE_MS")
+                .filter(|ms| *ms > 0)
+                .map(Duration::from_millis),
+            stream_coalesce_chars: env_parse("STREAM_COALESCE_CHARS").unwrap_or(0),
+            mock_provider: env_parse("MOCK_PROVIDER").unwrap_or(false),
+            local_only: env_parse("LOCAL_ONLY").unwrap_or(false),
+            ollama_base_url: env::var("OLLAMA_BASE_URL")
+                .ok()
+                .map(|url| url.trim().trim_end_matches('/').to_string())
+                .filter(|url| !url.is_empty()),
+            ollama_model: env::var("OLLAMA_MODEL")
+                .ok()
+                .map(|model| model.trim().to_string())
+                .filter(|model| !model.is_empty())
+                .unwrap_or_else(|| "llama3.2".to_string()),
+            cassette_mode: env::var("PROVIDER_CASSETTE_MODE")
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+                .map(|value| value.parse().expect("PROVIDER_CASSETTE_MODE invalide")),
+            cassette_dir: env::var("PROVIDER_CASSETTE_DIR")
+                .unwrap_or_else(|_| "cassettes".to_string()),
+            provider_debug_log: env_parse("PROVIDER_DEBUG_LOG").unwrap_or(false),
+            models: model_policy_from_env(),
+            title_model: env_model("TITLE_MODEL"),
+            draft_model: env_model("DRAFT_MODEL"),
+            service_tier: env::var("SERVICE_TIER")
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+                .map(|value| {
+                    value
+                        .parse()
+                        .unwrap_or_else(|err| panic!("SERVICE_TIER invalide: {err}"))
+                })
+                .unwrap_or_default(),
+            responses_api_models: responses_api_models_from_env(),
+            admin_token: env::var("ADMIN_TOKEN")
+                .ok()
+                .filter(|token| !token.trim().is_empty()),
+            retention: RetentionPolicy {
+                archive_idle_days: env_parse("RETENTION_ARCHIVE_IDLE_DAYS"),
+                purge_archived_days: env_parse("RETENTION_PURGE_ARCHIVED_DAYS"),
+                max_messages_per_session: env_parse("RETENTION_MAX_MESSAGES_PER_SESSION")
+                    .filter(|max| *max > 0),
+                interval: Duration::from_secs(
+                    env_parse::<u64>("RETENTION_INTERVAL_MINS")
+                        .filter(|mins| *mins > 0)
+                        .unwrap_or(60)
+                        * 60,
+                ),
+            },
+            upload_gc_interval: env_parse::<u64>("UPLOAD_GC_INTERVAL_HOURS")
+                .filter(|hours| *hours > 0)
+                .map(|hours| Duration::from_secs(hours * 3600)),
+            rate_limit_per_minute: env_parse("RATE_LIMIT_PER_MINUTE").filter(|limit| *limit > 0),
+            rate_limit_queue: env_parse("RATE_LIMIT_QUEUE").filter(|size| *size > 0),
+            daily_token_quota: env_parse("DAILY_TOKEN_QUOTA").filter(|quota| *quota > 0),
+            usage_rollup_interval: Duration::from_secs(
+                env_parse::<u64>("USAGE_ROLLUP_INTERVAL_HOURS")
+                    .filter(|hours| *hours > 0)
+                    .unwrap_or(24)
+                    * 3600,
+            ),
+            feature_flags_refresh: Duration::from_secs(
+                env_parse::<u64>("FEATURE_FLAGS_REFRESH_SECS")
+                    .filter(|secs| *secs > 0)
+                    .unwrap_or(30),
+            ),
+            backup_url: env::var("BACKUP_URL")
+                .ok()
+                .filter(|url| !url.trim().is_empty()),
+            backup_interval: Duration::from_secs(
+                env_parse::<u64>("BACKUP_INTERVAL_HOURS")
+                    .filter(|hours| *hours > 0)
+                    .unwrap_or(24)
+                    * 3600,
+            ),
+            scrub: ScrubPolicy {
+                builtin_secrets: env::var("SCRUB_SECRETS")
+                    .map(|value| !matches!(value.trim(), "0" | "false" | "off"))
+                    .unwrap_or(true),
+                hostnames: env::var("SCRUB_HOSTNAMES")
+                    .map(|value| {
+                        value
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|host| !host.is_empty())
+                            .map(str::to_string)
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                patterns: scrub_patterns_from_env(),
+            },
+            system_prompt: PromptSettings {
+                template: file_from_env("SYSTEM_PROMPT_TEMPLATE_FILE"),
+                project_instructions: file_from_env("PROJECT_INSTRUCTIONS_FILE"),
+                datetime: env::var("PROMPT_DATETIME")
+                    .ok()
+                    .filter(|value| !value.trim().is_empty())
+                    .map(|value| {
+                        value
+                            .parse()
+                            .unwrap_or_else(|err| panic!("PROMPT_DATETIME invalide: {err}"))
+                    })
+                    .unwrap_or_default(),
+                default_timezone: env::var("DEFAULT_TIMEZONE")
+                    .ok()
+                    .filter(|timezone| !timezone.trim().is_empty()),
+            },
+            templates: templates_from_env(),
+            widgets: widgets_from_env(),
+            stream_hooks: StreamHookSettings {
+                hooks: list_from_env("STREAM_HOOKS"),
+                masked_words: list_from_env("STREAM_MASKED_WORDS"),
+                link_rewrites: list_from_env("STREAM_LINK_REWRITES")
+                    .into_iter()
+                    .map(|rewrite| {
+                        let (from, to) = rewrite.split_once("=>").unwrap_or_else(|| {
+                            panic!("STREAM_LINK_REWRITES: « {rewrite} » (attendu : avant=>après)")
+                        });
+                        (from.trim().to_string(), to.trim().to_string())
+                    })
+                    .collect(),
+            },
+            grpc_enabled: env_parse("GRPC_ENABLED").unwrap_or(false),
+            frontend_dir: env::var("FRONTEND_DIR")
+                .ok()
+                .filter(|dir| !dir.trim().is_empty()),
+            notifications: NotifySettings {
+                webhook_url: env::var("NOTIFY_WEBHOOK_URL")
+                    .ok()
+                    .filter(|url| !url.trim().is_empty()),
+                smtp_url: env::var("NOTIFY_SMTP_URL")
+                    .ok()
+                    .filter(|url| !url.trim().is_empty()),
+                email_from: env::var("NOTIFY_EMAIL_FROM")
+                    .ok()
+                    .filter(|from| !from.trim().is_empty()),
+                email_to: list_from_env("NOTIFY_EMAIL_TO"),
+                min_duration: env_parse::<u64>("NOTIFY_MIN_DURATION_SECS")
+                    .map(Duration::from_secs)
+                    .unwrap_or(NotifySettings::default().min_duration),
+                session_url: env::var("NOTIFY_SESSION_URL")
+                    .ok()
+                    .filter(|url| url.contains("{id}"))
+                    .unwrap_or(NotifySettings::default().session_url),
+            },
+            archive: ArchiveSettings {
+                url: env::var("ARCHIVE_URL")
+                    .ok()
+                    .filter(|url| !url.trim().is_empty()),
+                max_attempts: env_parse::<u32>("ARCHIVE_MAX_ATTEMPTS")
+                    .filter(|attempts| *attempts > 0)
+                    .unwrap_or(ArchiveSettings::default().max_attempts),
+                dead_letter_dir: env::var("ARCHIVE_DEAD_LETTER_DIR")
+                    .ok()
+                    .filter(|dir| !dir.trim().is_empty())
+                    .unwrap_or(ArchiveSettings::default().dead_letter_dir),
+                retry_interval: env_parse::<u64>("ARCHIVE_RETRY_INTERVAL_SECS")
+                    .filter(|secs| *secs > 0)
+                    .map(Duration::from_secs)
+                    .unwrap_or(ArchiveSettings::default().retry_interval),
+            },
+            screenshot_service_url: env::var("SCREENSHOT_SERVICE_URL")
+                .ok()
+                .filter(|url| !url.trim().is_empty()),
+            screenshot_timeout: Duration::from_secs(
+                env_parse("SCREENSHOT_TIMEOUT_SECS").unwrap_or(30),
+            ),
+            screenshot_allowed_hosts: list_from_env("SCREENSHOT_ALLOWED_HOSTS")
+                .into_iter()
+                .map(|host| host.to_lowercase())
+                .collect(),
+            plugins: PluginSettings {
+                dir: env::var("PLUGIN_DIR")
+                    .ok()
+                    .filter(|dir| !dir.trim().is_empty()),
+                fuel: env_parse("PLUGIN_FUEL").unwrap_or(PluginSettings::default().fuel),
+                max_memory_bytes: env_parse::<usize>("PLUGIN_MAX_MEMORY_MB")
+                    .map(|mb| mb * 1024 * 1024)
+                    .unwrap_or(PluginSettings::default().max_memory_bytes),
+                reload_interval: env_parse::<u64>("PLUGIN_RELOAD_SECS")
+                    .map(Duration::from_secs)
+                    .unwrap_or(PluginSettings::default().reload_interval),
+            },
+        }
+    }
+}
+
+/// `DEFAULT_MODEL` et `ALLOWED_MODELS` (identifiants séparés par des virgules). Le modèle
+/// par défaut doit faire partie de la liste, sinon les non-admins ne pourraient rien envoyer.
+fn model_policy_from_env() -> ModelPolicy {
+    let parse = |value: &str| {
+        value
+            .parse::<AiModelChoice>()
+            .unwrap_or_else(|err| panic!("Configuration des modèles invalide: {err}"))
+    };
+    let allowed: Option<Vec<AiModelChoice>> = env::var("ALLOWED_MODELS")
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .map(|value| value.split(',').map(parse).collect());
+    let default = match env::var("DEFAULT_MODEL") {
+        Ok(value) if !value.trim().is_empty() => parse(&value),
+        _ => allowed
+            .as_ref()
+            .and_then(|allowed| allowed.first().copied())
+            .unwrap_or_default(),
+    };
+    if let Some(allowed) = &allowed {
+        assert!(
+            allowed.contains(&default),
+            "DEFAULT_MODEL ({}) doit faire partie de ALLOWED_MODELS",
+            default.model_id()
+        );
+    }
+    ModelPolicy { default, allowed }
+}
+
+/// `RESPONSES_API_MODELS` (identifiants séparés par des virgules) : seuls les modèles OpenAI
+/// sont acceptés, Groq et Anthropic n'ayant pas d'API Responses.
+fn responses_api_models_from_env() -> Vec<AiModelChoice> {
+    let Ok(value) = env::var("RESPONSES_API_MODELS") else {
+        return Vec::new();
+    };
+    value
+        .split(',')
+        .filter(|id| !id.trim().is_empty())
+        .map(|id| {
+            let model: AiModelChoice = id
+                .parse()
+                .unwrap_or_else(|err| panic!("RESPONSES_API_MODELS invalide: {err}"));
+            assert!(
+                model.is_openai(),
+                "RESPONSES_API_MODELS: {} n'est pas un modèle OpenAI",
+                model.model_id()
+            );
+            model
+        })
+        .collect()
+}
+
+/// `SCRUB_PATTERNS_FILE` : une expression régulière par ligne, les lignes vides et celles
+/// qui commencent par `#` sont ignorées.
+fn scrub_patterns_from_env() -> Vec<String> {
+    let Some(path) = env::var("SCRUB_PATTERNS_FILE")
+        .ok()
+        .filter(|path| !path.trim().is_empty())
+    else {
+        return Vec::new();
+    };
+    std::fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("SCRUB_PATTERNS_FILE ({path}) illisible: {err}"))
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// `CONVERSATION_TEMPLATES_FILE` : tableau JSON de gabarits de discussion. Un identifiant en
+/// double, un titre vide ou un modèle inconnu empêchent le démarrage.
+fn templates_from_env() -> Vec<ConversationTemplate> {
+    let Some(content) = file_from_env("CONVERSATION_TEMPLATES_FILE") else {
+        return Vec::new();
+    };
+    let templates: Vec<ConversationTemplate> = serde_json::from_str(&content)
+        .unwrap_or_else(|err| panic!("CONVERSATION_TEMPLATES_FILE invalide: {err}"));
+    let mut ids = HashSet::new();
+    for template in &templates {
+        assert!(
+            !template.id.is_empty()
+                && template
+                    .id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+            "CONVERSATION_TEMPLATES_FILE: identifiant invalide « {} » (lettres, chiffres, - et _)",
+            template.id
+        );
+        assert!(
+            ids.insert(template.id.as_str()),
+            "CONVERSATION_TEMPLATES_FILE: identifiant en double « {} »",
+            template.id
+        );
+        assert!(
+            !template.title.trim().is_empty(),
+            "CONVERSATION_TEMPLATES_FILE: titre vide pour « {} »",
+            template.id
+        );
+        if let Some(model) = template.model.as_deref() {
+            assert!(
+                model == AUTO_MODEL || AiModelChoice::from_id(model).is_some(),
+                "CONVERSATION_TEMPLATES_FILE: modèle inconnu {model} pour « {} »",
+                template.id
+            );
+        }
+    }
+    templates
+}
+
+/// `WIDGETS_FILE` : tableau JSON de widgets. Un identifiant invalide ou en double, un jeton
+/// trop court ou partagé, un modèle inconnu ou une limite nulle empêchent le démarrage.
+fn widgets_from_env() -> Vec<Widget> {
+    let Some(content) = file_from_env("WIDGETS_FILE") else {
+        return Vec::new();
+    };
+    let widgets: Vec<Widget> =
+        serde_json::from_str(&content).unwrap_or_else(|err| panic!("WIDGETS_FILE invalide: {err}"));
+    let mut ids = HashSet::new();
+    let mut tokens = HashSet::new();
+    for widget in &widgets {
+        assert!(
+            !widget.id.is_empty()
+                && widget
+                    .id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+            "WIDGETS_FILE: identifiant invalide « {} » (lettres, chiffres, - et _)",
+            widget.id
+        );
+        assert!(
+            ids.insert(widget.id.as_str()),
+            "WIDGETS_FILE: identifiant en double « {} »",
+            widget.id
+        );
+        assert!(
+            widget.token.trim().len() >= 16 && tokens.insert(widget.token.trim()),
+            "WIDGETS_FILE: jeton de « {} » trop court (16 caractères au moins) ou déjà utilisé",
+            widget.id
+        );
+        assert!(
+            widget.rate_limit_per_minute > 0 && widget.max_messages > 0,
+            "WIDGETS_FILE: rate_limit_per_minute et max_messages doivent être positifs pour « {} »",
+            widget.id
+        );
+        if let Some(model) = widget.model.as_deref() {
+            assert!(
+                model == AUTO_MODEL || AiModelChoice::from_id(model).is_some(),
+                "WIDGETS_FILE: modèle inconnu {model} pour « {} »",
+                widget.id
+            );
+        }
+    }
+    widgets
+}
+
+/// Valeurs séparées par des virgules, sans les vides.
+fn list_from_env(name: &str) -> Vec<String> {
+    env::var(name)
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Contenu du fichier désigné par la variable `name`, s'il y en a un.
+fn file_from_env(name: &str) -> Option<String> {
+    let path = env::var(name).ok().filter(|path| !path.trim().is_empty())?;
+    Some(
+        std::fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("{name} ({path}) illisible: {err}")),
+    )
+}
+
+/// Modèle de la variable `name` ; Llama 3.1 8B si elle est absente.
+fn env_model(name: &str) -> AiModelChoice {
+    env::var(name)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .map(|value| {
+            value
+                .parse()
+                .unwrap_or_else(|err| panic!("{name} invalide: {err}"))
+        })
+        .unwrap_or_default()
+}
+
+fn env_parse<T: std::str::FromStr>(name: &str) -> Option<T> {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+}
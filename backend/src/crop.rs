@@ -0,0 +1,157 @@
+//! Zones d'une image annotée par l'utilisateur (`regions`) : chacune est découpée avant
+//! l'envoi au modèle de vision, avec une légende qui la situe dans l'image d'origine.
+
+use std::io::Cursor;
+
+use axum::http::StatusCode;
+use base64::{Engine as _, engine::general_purpose};
+use image::{GenericImageView, ImageFormat};
+
+use crate::{
+    internal_error,
+    models::{AttachmentPayload, ImageRegion},
+};
+
+/// Zones tracées au plus sur une image
+const MAX_REGIONS: usize = 8;
+/// Longueur maximale de la légende d'une zone
+const MAX_LABEL_CHARS: usize = 200;
+
+/// Image envoyée au modèle, avec la légende de la zone s'il s'agit d'une découpe.
+pub(crate) struct ImagePart {
+    pub caption: Option<String>,
+    pub url: String,
+}
+
+/// Vérifie les zones demandées avec une pièce jointe, d'après les dimensions lues à l'upload
+/// quand elles sont connues.
+pub(crate) fn validate_regions(attachment: &AttachmentPayload) -> Result<(), String> {
+    let Some(regions) = &attachment.regions else {
+        return Ok(());
+    };
+    let file_name = &attachment.file_name;
+    if !attachment.mime_type.starts_with("image/") {
+        return Err(format!(
+            "{file_name} n'est pas une image : impossible d'en découper des zones."
+        ));
+    }
+    if regions.len() > MAX_REGIONS {
+        return Err(format!(
+            "{MAX_REGIONS} zones au plus par image ({} sur {file_name}).",
+            regions.len()
+        ));
+    }
+    for (index, region) in regions.iter().enumerate() {
+        let number = index + 1;
+        if region.width == 0 || region.height == 0 {
+            return Err(format!("La zone {number} de {file_name} est vide."));
+        }
+        if region
+            .label
+            .as_ref()
+            .is_some_and(|label| label.chars().count() > MAX_LABEL_CHARS)
+        {
+            return Err(format!(
+                "La légende de la zone {number} de {file_name} dépasse {MAX_LABEL_CHARS} caractères."
+            ));
+        }
+        let (Some(width), Some(height)) = (attachment.metadata.width, attachment.metadata.height)
+        else {
+            continue;
+        };
+        if i64::from(region.x) >= i64::from(width) || i64::from(region.y) >= i64::from(height) {
+            return Err(format!(
+                "La zone {number} commence en dehors de {file_name} ({width} × {height} px)."
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Images à envoyer pour `attachment`, dont le contenu est `url` : chaque zone découpée, ou
+/// l'image entière sans zone. Une image hébergée ailleurs (URL sans fichier stocké) est
+/// envoyée entière.
+pub(crate) async fn image_parts(
+    attachment: &AttachmentPayload,
+    url: String,
+) -> Result<Vec<ImagePart>, (StatusCode, String)> {
+    let regions = match &attachment.regions {
+        Some(regions) if !regions.is_empty() => regions.clone(),
+        _ => return Ok(vec![ImagePart { caption: None, url }]),
+    };
+    let Some(data) = data_url_bytes(&url) else {
+        return Ok(vec![ImagePart { caption: None, url }]);
+    };
+    let file_name = attachment.file_name.clone();
+    tokio::task::spawn_blocking(move || crop_regions(&file_name, &data, &regions))
+        .await
+        .map_err(internal_error)?
+}
+
+fn data_url_bytes(url: &str) -> Option<Vec<u8>> {
+    let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+    if !header.ends_with(";base64") {
+        return None;
+    }
+    general_purpose::STANDARD.decode(data).ok()
+}
+
+/// Découpe chaque zone (réduite à la partie qui chevauche l'image), réencodée en JPEG pour
+/// une photo JPEG et en PNG sinon.
+fn crop_regions(
+    file_name: &str,
+    data: &[u8],
+    regions: &[ImageRegion],
+) -> Result<Vec<ImagePart>, (StatusCode, String)> {
+    let image = image::load_from_memory(data).map_err(|err| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Impossible de découper {file_name} : image illisible ({err})."),
+        )
+    })?;
+    let (format, mime_type) = match image::guess_format(data) {
+        Ok(ImageFormat::Jpeg) => (ImageFormat::Jpeg, "image/jpeg"),
+        _ => (ImageFormat::Png, "image/png"),
+    };
+    let (width, height) = image.dimensions();
+    let total = regions.len();
+    regions
+        .iter()
+        .enumerate()
+        .map(|(index, region)| {
+            let number = index + 1;
+            if region.x >= width || region.y >= height {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!(
+                        "La zone {number} commence en dehors de {file_name} ({width} × {height} px)."
+                    ),
+                ));
+            }
+            let crop_width = region.width.min(width - region.x);
+            let crop_height = region.height.min(height - region.y);
+            let mut encoded = Vec::new();
+            image
+                .crop_imm(region.x, region.y, crop_width, crop_height)
+                .write_to(&mut Cursor::new(&mut encoded), format)
+                .map_err(internal_error)?;
+            let mut caption = format!(
+                "Zone {number}/{total} de {file_name} (x {}, y {}, {crop_width} × {crop_height} px \
+                 sur {width} × {height})",
+                region.x, region.y
+            );
+            if let Some(label) = region.label.as_deref().map(str::trim)
+                && !label.is_empty()
+            {
+                caption.push_str(&format!(" : « {label} »"));
+            }
+            Ok(ImagePart {
+                caption: Some(caption),
+                url: format!(
+                    "data:{mime_type};base64,{}",
+                    general_purpose::STANDARD.encode(encoded)
+                ),
+            })
+        })
+        .collect()
+}
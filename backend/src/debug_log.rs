@@ -0,0 +1,91 @@
+//! Journal de débogage des échanges avec les providers (`PROVIDER_DEBUG_LOG=true`) : corps
+//! exact de chaque requête envoyée et flux SSE brut reçu, rattachés au message produit, pour
+//! comprendre une réponse inattendue. Les deux passent par le masquage des secrets.
+
+use bytes::Bytes;
+use futures::stream::{BoxStream, StreamExt};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{AppState, scrub::ScrubReport};
+
+/// Enregistre une requête sur le point de partir ; `None` si le journal est désactivé ou
+/// si l'écriture échoue (la requête part quand même).
+pub(crate) async fn start(
+    state: &AppState,
+    provider: &str,
+    url: &str,
+    request_body: &Value,
+) -> Option<Uuid> {
+    if !state.provider_debug_log {
+        return None;
+    }
+    state
+        .repo
+        .insert_debug_log(provider, url, request_body)
+        .await
+        .map_err(|err| eprintln!("Impossible de journaliser la requête {provider}: {err}"))
+        .ok()
+}
+
+/// Réponse d'erreur du provider reçue à la place du flux, ou erreur réseau (sans statut).
+pub(crate) async fn fail(state: &AppState, log_id: Option<Uuid>, status: Option<u16>, body: &str) {
+    if let Some(log_id) = log_id {
+        save(state, log_id, status, body.as_bytes()).await;
+    }
+}
+
+/// Relaie le flux SSE en le copiant ; la copie est enregistrée quand le flux est lâché, qu'il
+/// soit allé au bout ou non (client parti, erreur réseau).
+pub(crate) fn tee(
+    state: &AppState,
+    log_id: Uuid,
+    status: u16,
+    stream: BoxStream<'static, Result<Bytes, reqwest::Error>>,
+) -> BoxStream<'static, Result<Bytes, reqwest::Error>> {
+    let mut recorder = Recorder {
+        state: state.clone(),
+        log_id,
+        status,
+        recorded: Vec::new(),
+    };
+    Box::pin(stream.map(move |chunk| {
+        if let Ok(bytes) = &chunk {
+            recorder.recorded.extend_from_slice(bytes);
+        }
+        chunk
+    }))
+}
+
+struct Recorder {
+    state: AppState,
+    log_id: Uuid,
+    status: u16,
+    recorded: Vec<u8>,
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let state = self.state.clone();
+        let (log_id, status) = (self.log_id, self.status);
+        let recorded = std::mem::take(&mut self.recorded);
+        runtime.spawn(async move { save(&state, log_id, Some(status), &recorded).await });
+    }
+}
+
+async fn save(state: &AppState, log_id: Uuid, status: Option<u16>, response: &[u8]) {
+    let mut response = String::from_utf8_lossy(response).into_owned();
+    state
+        .scrubber
+        .scrub_text(&mut response, &mut ScrubReport::default());
+    if let Err(err) = state
+        .repo
+        .complete_debug_log(log_id, status.map(i32::from), &response)
+        .await
+    {
+        eprintln!("Impossible de journaliser la réponse {log_id}: {err}");
+    }
+}
@@ -0,0 +1,95 @@
+//! Évènements temps réel de `/api/events` et leur relais via Redis.
+
+use std::time::Duration;
+
+use futures::StreamExt;
+use redis::{AsyncCommands, aio::ConnectionManager};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::{broadcast, mpsc};
+use uuid::Uuid;
+
+use crate::models::{ExportStatus, MessageStatus};
+
+/// Évènement diffusé à tous les clients abonnés à `/api/events` (autres onglets/appareils).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum AppEvent {
+    GenerationStarted {
+        #[serde(rename = "chatId")]
+        chat_id: Uuid,
+        #[serde(rename = "messageId")]
+        message_id: Option<Uuid>,
+    },
+    GenerationFinished {
+        #[serde(rename = "chatId")]
+        chat_id: Uuid,
+        #[serde(rename = "messageId")]
+        message_id: Option<Uuid>,
+        status: MessageStatus,
+    },
+    /// Avancement d'un export de l'historique (`POST /api/export`)
+    ExportProgress {
+        #[serde(rename = "exportId")]
+        export_id: Uuid,
+        status: ExportStatus,
+        #[serde(rename = "sessionsDone")]
+        sessions_done: i32,
+        #[serde(rename = "sessionsTotal")]
+        sessions_total: i32,
+    },
+}
+
+/// Canal Redis pub/sub relayant les `AppEvent` entre les instances.
+const REDIS_EVENTS_CHANNEL: &str = "carlgpt:events";
+
+/// Publie les évènements locaux sur le canal Redis, dans l'ordre d'émission. Si Redis est
+/// indisponible, l'évènement est au moins délivré aux abonnés de cette instance.
+pub(crate) fn spawn_redis_event_publisher(
+    mut conn: ConnectionManager,
+    local: broadcast::Sender<AppEvent>,
+) -> mpsc::UnboundedSender<AppEvent> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<AppEvent>();
+    tokio::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            let payload = json!(event).to_string();
+            let published: redis::RedisResult<()> =
+                conn.publish(REDIS_EVENTS_CHANNEL, payload).await;
+            if let Err(err) = published {
+                eprintln!("Publication Redis impossible: {err}");
+                let _ = local.send(event);
+            }
+        }
+    });
+    tx
+}
+
+/// Relaie vers les abonnés locaux de `/api/events` les évènements publiés par toutes les
+/// instances (y compris celle-ci). Se réabonne après une coupure de Redis.
+pub(crate) async fn relay_redis_events(client: redis::Client, local: broadcast::Sender<AppEvent>) {
+    loop {
+        match client.get_async_pubsub().await {
+            Ok(mut pubsub) => {
+                if let Err(err) = pubsub.subscribe(REDIS_EVENTS_CHANNEL).await {
+                    eprintln!("Abonnement Redis impossible: {err}");
+                } else {
+                    let mut messages = pubsub.on_message();
+                    while let Some(message) = messages.next().await {
+                        let Ok(payload) = message.get_payload::<String>() else {
+                            continue;
+                        };
+                        match serde_json::from_str::<AppEvent>(&payload) {
+                            Ok(event) => {
+                                let _ = local.send(event);
+                            }
+                            Err(err) => eprintln!("Évènement Redis illisible: {err}"),
+                        }
+                    }
+                    eprintln!("Connexion pub/sub Redis perdue, reconnexion…");
+                }
+            }
+            Err(err) => eprintln!("Connexion pub/sub Redis impossible: {err}"),
+        }
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+}
@@ -0,0 +1,437 @@
+//! Export de tout l'historique en archive zip, construite en tâche de fond : `export.json`
+//! (sessions complètes), une page markdown par session et les fichiers joints. Une session
+//! seule peut aussi être exportée en PDF (`pdf.rs`), ou en fiches de révision rédigées par le
+//! modèle, dans un CSV qu'Anki importe tel quel.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{Datelike, Timelike, Utc};
+use flate2::{Compression, write::DeflateEncoder};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    events::AppEvent,
+    models::{ChatSession, ExportStatus},
+    pdf::session_pdf,
+    providers::{AiModelChoice, Flashcard, generate_flashcards},
+    storage::{attachment_local_path, sanitize_file_name},
+};
+
+/// Longueur maximale du titre repris dans le nom de la page markdown d'une session
+const SLUG_MAX_LEN: usize = 60;
+
+/// Chemin de l'archive d'un export dans `EXPORT_DIR`.
+pub(crate) fn export_path(state: &AppState, file_name: &str) -> PathBuf {
+    Path::new(&state.export_dir).join(file_name)
+}
+
+/// Contenu à produire pour un export.
+pub(crate) enum ExportJob {
+    /// Archive zip de tout l'historique
+    Archive,
+    /// PDF d'une session
+    Pdf(Uuid),
+    /// Fiches de révision d'une session, rédigées par `model`
+    Flashcards {
+        session_id: Uuid,
+        model: AiModelChoice,
+    },
+}
+
+/// Construit le fichier de l'export `export_id` et enregistre son issue ; l'avancement est
+/// diffusé sur `/api/events` après chaque session.
+pub(crate) async fn run_export(state: AppState, export_id: Uuid, job: ExportJob) {
+    let result = match job {
+        ExportJob::Archive => build_export(&state, export_id).await,
+        ExportJob::Pdf(session_id) => build_pdf(&state, export_id, session_id).await,
+        ExportJob::Flashcards { session_id, model } => {
+            build_flashcards(&state, export_id, session_id, model).await
+        }
+    };
+    let finished = match &result {
+        Ok(file_name) => {
+            let expires_at = Utc::now() + state.export_link_ttl;
+            state
+                .repo
+                .finish_export(export_id, Ok((file_name, expires_at)))
+                .await
+        }
+        Err(err) => {
+            eprintln!("Export {export_id} impossible: {err}");
+            state.repo.finish_export(export_id, Err(err)).await
+        }
+    };
+    if let Err(err) = finished {
+        eprintln!("Impossible d'enregistrer la fin de l'export {export_id}: {err}");
+    }
+    if let Ok(Some(export)) = state.repo.fetch_export(export_id).await {
+        state.publish(AppEvent::ExportProgress {
+            export_id,
+            status: export.status,
+            sessions_done: export.sessions_done,
+            sessions_total: export.sessions_total,
+        });
+    }
+}
+
+async fn build_export(state: &AppState, export_id: Uuid) -> Result<String, String> {
+    let session_ids = state
+        .repo
+        .all_session_ids()
+        .await
+        .map_err(|err| err.to_string())?;
+    let total = session_ids.len() as i32;
+    report_progress(state, export_id, 0, total).await?;
+
+    let mut sessions = Vec::with_capacity(session_ids.len());
+    for (index, session_id) in session_ids.into_iter().enumerate() {
+        match state.repo.fetch_session(session_id).await {
+            Ok(session) => sessions.push(session),
+            // Session supprimée pendant l'export
+            Err(sqlx::Error::RowNotFound) => {}
+            Err(err) => return Err(err.to_string()),
+        }
+        report_progress(state, export_id, index as i32 + 1, total).await?;
+    }
+
+    let mut entries = vec![(
+        "export.json".to_string(),
+        serde_json::to_vec_pretty(&sessions).map_err(|err| err.to_string())?,
+    )];
+    let mut attachments = BTreeMap::new();
+    for (index, session) in sessions.iter().enumerate() {
+        entries.push((
+            format!("sessions/{:03}-{}.md", index + 1, session_slug(session)),
+            session_markdown(session).into_bytes(),
+        ));
+        for attachment in session.messages.iter().flat_map(|m| &m.attachments) {
+            if is_plain_key(&attachment.storage_key) {
+                attachments.insert(
+                    attachment.storage_key.clone(),
+                    attachment_local_path(&state.upload_dir, &attachment.storage_key),
+                );
+            }
+        }
+    }
+
+    let file_name = format!("{export_id}.zip");
+    let path = export_path(state, &file_name);
+    tokio::task::spawn_blocking(move || write_archive(&path, entries, attachments))
+        .await
+        .map_err(|err| err.to_string())?
+        .map_err(|err| format!("écriture de l'archive: {err}"))?;
+    Ok(file_name)
+}
+
+async fn build_pdf(state: &AppState, export_id: Uuid, session_id: Uuid) -> Result<String, String> {
+    report_progress(state, export_id, 0, 1).await?;
+    let session = fetch_exported_session(state, session_id).await?;
+
+    let file_name = format!("{export_id}.pdf");
+    let path = export_path(state, &file_name);
+    // La compilation typst prend de quelques dizaines de millisecondes à plusieurs secondes.
+    tokio::task::spawn_blocking(move || {
+        let pdf = session_pdf(&session).map_err(|err| format!("rendu du PDF: {err}"))?;
+        let partial = path.with_extension("pdf.part");
+        std::fs::write(&partial, pdf)
+            .and_then(|()| std::fs::rename(&partial, &path))
+            .map_err(|err| format!("écriture du PDF: {err}"))
+    })
+    .await
+    .map_err(|err| err.to_string())??;
+    report_progress(state, export_id, 1, 1).await?;
+    Ok(file_name)
+}
+
+async fn build_flashcards(
+    state: &AppState,
+    export_id: Uuid,
+    session_id: Uuid,
+    model: AiModelChoice,
+) -> Result<String, String> {
+    report_progress(state, export_id, 0, 1).await?;
+    let session = fetch_exported_session(state, session_id).await?;
+    let cards = generate_flashcards(state, model, &session_transcript(&session))
+        .await
+        .map_err(|(_, err)| err)?;
+
+    let file_name = format!("{export_id}.csv");
+    let path = export_path(state, &file_name);
+    let partial = path.with_extension("csv.part");
+    tokio::fs::write(&partial, flashcards_csv(&session, &cards))
+        .await
+        .map_err(|err| format!("écriture des fiches: {err}"))?;
+    tokio::fs::rename(&partial, &path)
+        .await
+        .map_err(|err| format!("écriture des fiches: {err}"))?;
+    report_progress(state, export_id, 1, 1).await?;
+    Ok(file_name)
+}
+
+async fn fetch_exported_session(state: &AppState, session_id: Uuid) -> Result<ChatSession, String> {
+    match state.repo.fetch_session(session_id).await {
+        Ok(session) => Ok(session),
+        Err(sqlx::Error::RowNotFound) => Err("discussion introuvable".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+async fn report_progress(
+    state: &AppState,
+    export_id: Uuid,
+    sessions_done: i32,
+    sessions_total: i32,
+) -> Result<(), String> {
+    state
+        .repo
+        .set_export_progress(export_id, sessions_done, sessions_total)
+        .await
+        .map_err(|err| err.to_string())?;
+    state.publish(AppEvent::ExportProgress {
+        export_id,
+        status: ExportStatus::Running,
+        sessions_done,
+        sessions_total,
+    });
+    Ok(())
+}
+
+/// Écrit l'archive dans un fichier temporaire renommé une fois complet : un lien de
+/// téléchargement ne sert jamais une archive tronquée. Les fichiers joints disparus du
+/// stockage sont ignorés.
+fn write_archive(
+    path: &Path,
+    entries: Vec<(String, Vec<u8>)>,
+    attachments: BTreeMap<String, PathBuf>,
+) -> io::Result<()> {
+    let partial = path.with_extension("zip.part");
+    let mut zip = ZipWriter::new(BufWriter::new(File::create(&partial)?));
+    for (name, data) in entries {
+        zip.add(&name, &data)?;
+    }
+    for (key, source) in attachments {
+        match std::fs::read(&source) {
+            Ok(data) => zip.add(&format!("attachments/{key}"), &data)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+    }
+    zip.finish()?.flush()?;
+    std::fs::rename(&partial, path)
+}
+
+/// Les clés de stockage viennent aussi des clients : seules celles qui désignent un fichier
+/// du dossier des uploads sont exportées.
+fn is_plain_key(key: &str) -> bool {
+    !key.starts_with('.') && sanitize_file_name(key) == key
+}
+
+fn session_slug(session: &ChatSession) -> String {
+    let mut slug = sanitize_file_name(&session.title).to_lowercase();
+    slug.truncate(SLUG_MAX_LEN);
+    slug
+}
+
+/// Discussion mise à plat pour le modèle qui en tire les fiches de révision.
+fn session_transcript(session: &ChatSession) -> String {
+    let mut transcript = format!("Discussion « {} »\n", session.title);
+    for message in &session.messages {
+        let author = match message.role.as_str() {
+            "user" => "Utilisateur",
+            "system" => continue,
+            _ => "Assistant",
+        };
+        transcript.push_str(&format!("\n{author} :\n{}\n", message.content.trim_end()));
+    }
+    transcript
+}
+
+/// Fiches au format texte d'Anki (Fichier > Importer) : les lignes `#` indiquent le
+/// séparateur, le type de note « Basique » et le paquet, nommé d'après la session. Le HTML
+/// est activé pour garder les retours à la ligne des réponses.
+fn flashcards_csv(session: &ChatSession, cards: &[Flashcard]) -> String {
+    let field = |text: &str| {
+        let html = text
+            .trim()
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace("\r\n", "\n")
+            .replace('\n', "<br>");
+        format!("\"{}\"", html.replace('"', "\"\""))
+    };
+    // `::` sépare les sous-paquets dans Anki.
+    let deck = session.title.replace("::", ":").replace(['\n', '\r'], " ");
+    let mut csv = format!(
+        "#separator:Comma\n#html:true\n#notetype:Basic\n#deck:CarlGPT::{deck}\n#tags column:3\n"
+    );
+    for card in cards {
+        csv.push_str(&format!(
+            "{},{},carlgpt\n",
+            field(&card.question),
+            field(&card.answer)
+        ));
+    }
+    csv
+}
+
+/// Page markdown lisible d'une session : un titre par message, pièces jointes en liens
+/// vers le dossier `attachments/` de l'archive.
+fn session_markdown(session: &ChatSession) -> String {
+    let mut page = match &session.icon {
+        Some(icon) => format!("# {icon} {}\n\n", session.title),
+        None => format!("# {}\n\n", session.title),
+    };
+    page.push_str(&format!(
+        "_Créée le {} — {} messages{}_\n",
+        session.created_at.format("%d/%m/%Y %H:%M"),
+        session.messages.len(),
+        if session.archived { ", archivée" } else { "" }
+    ));
+    for message in &session.messages {
+        let author = match (message.role.as_str(), &message.model) {
+            ("user", _) => "👤 Utilisateur".to_string(),
+            (_, Some(model)) => format!("🤖 Assistant ({model})"),
+            _ => "🤖 Assistant".to_string(),
+        };
+        page.push_str(&format!(
+            "\n## {author} — {}\n\n{}\n",
+            message.created_at.format("%d/%m/%Y %H:%M"),
+            message.content.trim_end()
+        ));
+        if !message.attachments.is_empty() {
+            page.push_str("\nPièces jointes :\n");
+            for attachment in &message.attachments {
+                page.push_str(&format!(
+                    "- [{}](../attachments/{})\n",
+                    attachment.file_name, attachment.storage_key
+                ));
+            }
+        }
+    }
+    page
+}
+
+/// Écriture minimale d'une archive zip (deflate, noms UTF-8, sans zip64 : 4 Go maximum).
+pub(crate) struct ZipWriter<W: Write> {
+    out: W,
+    offset: u64,
+    entries: Vec<ZipEntry>,
+    /// Date et heure MS-DOS communes à toutes les entrées
+    dos_time: u16,
+    dos_date: u16,
+}
+
+struct ZipEntry {
+    name: String,
+    crc: u32,
+    compressed_size: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// Bit 11 : noms de fichiers encodés en UTF-8
+const ZIP_UTF8_FLAG: u16 = 0x0800;
+const ZIP_DEFLATE: u16 = 8;
+const ZIP_VERSION: u16 = 20;
+
+impl<W: Write> ZipWriter<W> {
+    pub(crate) fn new(out: W) -> Self {
+        let now = chrono::Local::now();
+        ZipWriter {
+            out,
+            offset: 0,
+            entries: Vec::new(),
+            dos_time: ((now.hour() << 11) | (now.minute() << 5) | (now.second() / 2)) as u16,
+            dos_date: (((now.year().max(1980) - 1980) as u32) << 9 | (now.month() << 5) | now.day())
+                as u16,
+        }
+    }
+
+    pub(crate) fn add(&mut self, name: &str, data: &[u8]) -> io::Result<()> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        let compressed = encoder.finish()?;
+        let entry = ZipEntry {
+            name: name.to_string(),
+            crc: crc32fast::hash(data),
+            compressed_size: zip32(compressed.len() as u64)?,
+            size: zip32(data.len() as u64)?,
+            offset: zip32(self.offset)?,
+        };
+
+        let mut header = Vec::with_capacity(30 + name.len());
+        header.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        header.extend_from_slice(&ZIP_VERSION.to_le_bytes());
+        self.push_common_fields(&mut header, &entry);
+        header.extend_from_slice(&0u16.to_le_bytes()); // champ extra
+        header.extend_from_slice(name.as_bytes());
+        self.write(&header)?;
+        self.write(&compressed)?;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Écrit le répertoire central et la fin d'archive, puis rend la sortie.
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        let directory_offset = zip32(self.offset)?;
+        let mut directory = Vec::new();
+        for entry in &self.entries {
+            directory.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+            directory.extend_from_slice(&ZIP_VERSION.to_le_bytes()); // créé par
+            directory.extend_from_slice(&ZIP_VERSION.to_le_bytes()); // nécessaire
+            self.push_common_fields(&mut directory, entry);
+            directory.extend_from_slice(&0u16.to_le_bytes()); // champ extra
+            directory.extend_from_slice(&0u16.to_le_bytes()); // commentaire
+            directory.extend_from_slice(&0u16.to_le_bytes()); // disque
+            directory.extend_from_slice(&0u16.to_le_bytes()); // attributs internes
+            directory.extend_from_slice(&0u32.to_le_bytes()); // attributs externes
+            directory.extend_from_slice(&entry.offset.to_le_bytes());
+            directory.extend_from_slice(entry.name.as_bytes());
+        }
+        let count = u16::try_from(self.entries.len())
+            .map_err(|_| io::Error::other("trop de fichiers pour une archive zip"))?;
+
+        let mut end = Vec::with_capacity(22);
+        end.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes()); // disque
+        end.extend_from_slice(&0u16.to_le_bytes()); // disque du répertoire
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&zip32(directory.len() as u64)?.to_le_bytes());
+        end.extend_from_slice(&directory_offset.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes()); // commentaire
+        self.write(&directory)?;
+        self.write(&end)?;
+        Ok(self.out)
+    }
+
+    /// Champs partagés par l'en-tête local et l'entrée du répertoire central, de `flags`
+    /// à la longueur du nom.
+    fn push_common_fields(&self, buf: &mut Vec<u8>, entry: &ZipEntry) {
+        buf.extend_from_slice(&ZIP_UTF8_FLAG.to_le_bytes());
+        buf.extend_from_slice(&ZIP_DEFLATE.to_le_bytes());
+        buf.extend_from_slice(&self.dos_time.to_le_bytes());
+        buf.extend_from_slice(&self.dos_date.to_le_bytes());
+        buf.extend_from_slice(&entry.crc.to_le_bytes());
+        buf.extend_from_slice(&entry.compressed_size.to_le_bytes());
+        buf.extend_from_slice(&entry.size.to_le_bytes());
+        buf.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.out.write_all(bytes)?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+}
+
+fn zip32(value: u64) -> io::Result<u32> {
+    u32::try_from(value).map_err(|_| io::Error::other("archive zip de plus de 4 Go"))
+}
@@ -0,0 +1,212 @@
+//! Drapeaux de fonctionnalités (`/api/admin/feature-flags`) : les sous-systèmes
+//! expérimentaux s'ouvrent à une liste de clients ou à un pourcentage d'entre eux, sans
+//! redéploiement. Les drapeaux sont enregistrés en base et gardés en mémoire ; la tâche
+//! `feature_flags` relit la base pour suivre les modifications faites sur une autre instance.
+//!
+//! Le client est celui de l'en-tête `X-Client-Id` (identifiant stable choisi par le
+//! frontend), à défaut son adresse IP : le même client tombe toujours du même côté d'un
+//! déploiement progressif.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
+    sync::{Arc, RwLock},
+};
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{HeaderMap, request::Parts},
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    AppState,
+    models::{CompletionParams, FeatureFlag},
+    proxy::ClientOrigin,
+    repository::ChatRepository,
+};
+
+pub(crate) const CLIENT_ID_HEADER: &str = "x-client-id";
+/// Au-delà, l'en-tête est ignoré au profit de l'adresse IP
+const MAX_CLIENT_ID_LEN: usize = 128;
+
+/// Fonctionnalité contrôlée par un drapeau du même nom.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Feature {
+    /// Outils des modèles : `code_edit`, `calendar`, `screenshot` et `plugins` des
+    /// `completion_params`
+    Tools,
+}
+
+impl Feature {
+    pub(crate) const ALL: &[Feature] = &[Feature::Tools];
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Feature::Tools => "tools",
+        }
+    }
+
+    /// État sans drapeau en base : les fonctionnalités déjà livrées restent actives.
+    fn default_enabled(self) -> bool {
+        match self {
+            Feature::Tools => true,
+        }
+    }
+}
+
+/// Drapeaux en mémoire. `version` change à chaque modification locale : une relecture de
+/// la base commencée avant n'écrase pas la modification.
+#[derive(Default)]
+pub(crate) struct FeatureFlags {
+    flags: RwLock<(u64, Arc<HashMap<String, FeatureFlag>>)>,
+}
+
+impl FeatureFlags {
+    /// Remplace les drapeaux par ceux de la base ; renvoie leur nombre.
+    pub(crate) async fn reload(&self, repo: &ChatRepository) -> Result<usize, sqlx::Error> {
+        let version = self.flags.read().unwrap().0;
+        let flags: HashMap<_, _> = repo
+            .fetch_feature_flags()
+            .await?
+            .into_iter()
+            .map(|flag| (flag.name.clone(), flag))
+            .collect();
+        let count = flags.len();
+        let mut current = self.flags.write().unwrap();
+        if current.0 == version {
+            current.1 = Arc::new(flags);
+        }
+        Ok(count)
+    }
+
+    pub(crate) fn list(&self) -> Vec<FeatureFlag> {
+        let mut flags: Vec<_> = self.flags.read().unwrap().1.values().cloned().collect();
+        flags.sort_by(|a, b| a.name.cmp(&b.name));
+        flags
+    }
+
+    pub(crate) fn put(&self, flag: FeatureFlag) {
+        self.update(|flags| {
+            flags.insert(flag.name.clone(), flag);
+        });
+    }
+
+    pub(crate) fn remove(&self, name: &str) {
+        self.update(|flags| {
+            flags.remove(name);
+        });
+    }
+
+    fn update(&self, change: impl FnOnce(&mut HashMap<String, FeatureFlag>)) {
+        let mut current = self.flags.write().unwrap();
+        current.0 += 1;
+        change(Arc::make_mut(&mut current.1));
+    }
+
+    fn for_client(&self, client: String) -> Features {
+        Features {
+            flags: self.flags.read().unwrap().1.clone(),
+            client,
+        }
+    }
+}
+
+/// Fonctionnalités ouvertes au client de la requête, d'après les drapeaux au moment où elle
+/// arrive. Sans drapeau (`Default`), chaque fonctionnalité a son état par défaut.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Features {
+    flags: Arc<HashMap<String, FeatureFlag>>,
+    client: String,
+}
+
+/// Réponse de `GET /api/me/features`.
+#[derive(Serialize)]
+pub(crate) struct ClientFeatures {
+    /// Identifiant à ajouter aux `clients` d'un drapeau pour ouvrir la fonctionnalité
+    pub(crate) client: String,
+    pub(crate) features: BTreeMap<String, bool>,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for Features {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let Ok(origin) = ClientOrigin::from_request_parts(parts, state).await;
+        Ok(state
+            .feature_flags
+            .for_client(client_id(&parts.headers, &origin)))
+    }
+}
+
+impl Features {
+    pub(crate) fn is_enabled(&self, feature: Feature) -> bool {
+        self.flag(feature.name())
+            .unwrap_or_else(|| feature.default_enabled())
+    }
+
+    /// État d'un drapeau pour le client ; `None` s'il n'existe pas.
+    fn flag(&self, name: &str) -> Option<bool> {
+        let flag = self.flags.get(name)?;
+        Some(
+            flag.enabled
+                && (flag.clients.contains(&self.client)
+                    || bucket(name, &self.client)
+                        < u32::try_from(flag.rollout_percent).unwrap_or(0)),
+        )
+    }
+
+    /// Retire des paramètres les outils que le client n'a pas le droit d'utiliser.
+    pub(crate) fn restrict(&self, params: Option<CompletionParams>) -> Option<CompletionParams> {
+        if self.is_enabled(Feature::Tools) {
+            return params;
+        }
+        params.map(CompletionParams::without_tools)
+    }
+
+    /// Fonctionnalités connues du serveur et drapeaux en base, pour que le frontend masque
+    /// ce qui n'est pas ouvert au client.
+    pub(crate) fn summary(&self) -> ClientFeatures {
+        let mut features: BTreeMap<_, _> = Feature::ALL
+            .iter()
+            .map(|feature| (feature.name().to_string(), self.is_enabled(*feature)))
+            .collect();
+        for name in self.flags.keys() {
+            features.insert(name.clone(), self.flag(name).unwrap_or_default());
+        }
+        ClientFeatures {
+            client: self.client.clone(),
+            features,
+        }
+    }
+}
+
+/// `X-Client-Id` s'il est présent, sinon l'adresse IP du client (`local` si le serveur ne
+/// la connaît pas).
+fn client_id(headers: &HeaderMap, origin: &ClientOrigin) -> String {
+    headers
+        .get(CLIENT_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_CLIENT_ID_LEN)
+        .map(str::to_string)
+        .or_else(|| origin.ip.map(|ip| ip.to_string()))
+        .unwrap_or_else(|| "local".to_string())
+}
+
+/// Rang du client, de 0 à 99, dans le déploiement progressif du drapeau `name` : stable
+/// d'une instance à l'autre et indépendant d'un drapeau à l'autre.
+fn bucket(name: &str, client: &str) -> u32 {
+    let digest = Sha256::new()
+        .chain_update(name)
+        .chain_update([0])
+        .chain_update(client)
+        .finalize();
+    u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) % 100
+}
@@ -0,0 +1,50 @@
+//! Frontend compilé (export statique de Next.js) servi par le backend quand `FRONTEND_DIR`
+//! est défini : l'API et l'interface tiennent dans un seul processus.
+
+use std::path::Path;
+
+use axum::{
+    Router,
+    extract::Request,
+    http::{HeaderValue, StatusCode, header},
+    middleware::{self, Next},
+    response::Response,
+    routing::any,
+};
+use tower_http::services::{ServeDir, ServeFile};
+
+/// Fichiers dont le nom contient un hash de leur contenu : jamais modifiés une fois publiés.
+const IMMUTABLE_PREFIX: &str = "/_next/static/";
+const IMMUTABLE_CACHE: &str = "public, max-age=31536000, immutable";
+/// Pages et autres fichiers : revalidés à chaque chargement pour suivre un déploiement.
+const REVALIDATE_CACHE: &str = "no-cache";
+
+/// Routes du frontend, à fusionner après celles de l'API. Un chemin sans fichier renvoie
+/// `index.html` : le routage côté client prend le relais.
+pub(crate) fn routes(dir: &Path) -> Router {
+    let index = dir.join("index.html");
+    if !index.is_file() {
+        panic!("FRONTEND_DIR invalide: {} introuvable", index.display());
+    }
+    Router::new()
+        // Endpoint inconnu : 404 plutôt que la page du frontend
+        .route("/api/*path", any(|| async { StatusCode::NOT_FOUND }))
+        .fallback_service(ServeDir::new(dir).fallback(ServeFile::new(index)))
+        .layer(middleware::from_fn(cache_control))
+}
+
+async fn cache_control(request: Request, next: Next) -> Response {
+    let immutable = request.uri().path().starts_with(IMMUTABLE_PREFIX);
+    let mut response = next.run(request).await;
+    if response.status().is_success() || response.status() == StatusCode::NOT_MODIFIED {
+        let value = if immutable {
+            IMMUTABLE_CACHE
+        } else {
+            REVALIDATE_CACHE
+        };
+        response
+            .headers_mut()
+            .insert(header::CACHE_CONTROL, HeaderValue::from_static(value));
+    }
+    response
+}
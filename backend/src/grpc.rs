@@ -0,0 +1,300 @@
+//! Service gRPC `carlgpt.v1.Chat` (`proto/carlgpt.proto`), servi sur le même port que l'API
+//! HTTP quand `GRPC_ENABLED` est activé. Il passe par `ChatService` comme les handlers HTTP :
+//! mêmes règles d'accès (métadonnée `x-admin-token`), même limite de requêtes et même quota,
+//! et les évènements d'une génération sont ceux du flux SSE, typés.
+
+use std::net::SocketAddr;
+
+use axum::{extract::ConnectInfo, http::StatusCode};
+use futures::stream::{BoxStream, StreamExt};
+use serde_json::Value;
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    access::Caller,
+    internal_error,
+    limits::CompletionLimits,
+    models::{ChatAttachment, ChatMessage, ChatSession, CreateChatMessageRequest, TokenUsage},
+    proxy::ClientOrigin,
+    service::ChatService,
+    stream::{ClientEvent, EventKind},
+};
+
+/// Code généré depuis `proto/carlgpt.proto`.
+pub mod proto {
+    tonic::include_proto!("carlgpt.v1");
+}
+
+use proto::{
+    ChatEvent, CreateSessionRequest, DeleteSessionRequest, DeleteSessionResponse,
+    GetSessionRequest, ListSessionsRequest, ListSessionsResponse, SendMessageRequest,
+    chat_event::Event,
+    chat_server::{Chat, ChatServer},
+};
+
+/// Routes du service, à fusionner dans le routeur HTTP.
+pub(crate) fn routes(state: AppState) -> axum::Router {
+    tonic::service::Routes::new(ChatServer::new(GrpcChat { state })).into_axum_router()
+}
+
+struct GrpcChat {
+    state: AppState,
+}
+
+impl GrpcChat {
+    fn caller<T>(&self, request: &Request<T>) -> Result<Caller, (StatusCode, String)> {
+        Caller::from_headers(&request.metadata().clone().into_headers(), &self.state)
+    }
+
+    fn origin<T>(&self, request: &Request<T>) -> ClientOrigin {
+        let peer = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        ClientOrigin::resolve(
+            peer,
+            &request.metadata().clone().into_headers(),
+            &self.state.trusted_proxies,
+        )
+    }
+}
+
+#[tonic::async_trait]
+impl Chat for GrpcChat {
+    type SendMessageStream = BoxStream<'static, Result<ChatEvent, Status>>;
+
+    async fn list_sessions(
+        &self,
+        _request: Request<ListSessionsRequest>,
+    ) -> Result<Response<ListSessionsResponse>, Status> {
+        let sessions = self
+            .state
+            .repo
+            .list_sessions()
+            .await
+            .map_err(|err| status(internal_error(err)))?;
+        Ok(Response::new(ListSessionsResponse {
+            sessions: sessions.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn get_session(
+        &self,
+        request: Request<GetSessionRequest>,
+    ) -> Result<Response<proto::Session>, Status> {
+        let session_id = parse_id(&request.get_ref().session_id).map_err(status)?;
+        match self.state.repo.fetch_session(session_id).await {
+            Ok(session) => Ok(Response::new(session.into())),
+            Err(sqlx::Error::RowNotFound) => Err(Status::not_found("Discussion introuvable.")),
+            Err(err) => Err(status(internal_error(err))),
+        }
+    }
+
+    async fn create_session(
+        &self,
+        request: Request<CreateSessionRequest>,
+    ) -> Result<Response<proto::Session>, Status> {
+        let session = ChatService::new(&self.state)
+            .create_session(request.into_inner().title)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(session.into()))
+    }
+
+    async fn delete_session(
+        &self,
+        request: Request<DeleteSessionRequest>,
+    ) -> Result<Response<DeleteSessionResponse>, Status> {
+        let session_id = parse_id(&request.get_ref().session_id).map_err(status)?;
+        ChatService::new(&self.state)
+            .delete(session_id)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(DeleteSessionResponse {}))
+    }
+
+    async fn send_message(
+        &self,
+        request: Request<SendMessageRequest>,
+    ) -> Result<Response<Self::SendMessageStream>, Status> {
+        let caller = self.caller(&request).map_err(status)?;
+        let limits = CompletionLimits::check(&self.state, caller, &self.origin(&request))
+            .await
+            .map_err(status)?;
+        if let Some((_, message)) = limits.refusal() {
+            return Err(Status::resource_exhausted(message));
+        }
+
+        let request = request.into_inner();
+        let session_id = parse_id(&request.session_id).map_err(status)?;
+        let payload = CreateChatMessageRequest {
+            content: request.content,
+            model: request.model,
+            attachments: None,
+            completion_params: None,
+            preset_id: request
+                .preset_id
+                .as_deref()
+                .map(parse_id)
+                .transpose()
+                .map_err(status)?,
+            context_message_ids: None,
+            reply_to_message_id: None,
+            messages: None,
+            candidates: None,
+            draft: None,
+        };
+        let generation = ChatService::new(&self.state)
+            .with_caller(caller)
+            .start_exchange(session_id, payload)
+            .await
+            .map_err(status)?;
+        let events = generation
+            .spawn(self.state.clone())
+            .filter_map(|event| async move { chat_event(event).map(Ok) });
+        Ok(Response::new(Box::pin(events)))
+    }
+}
+
+/// Évènement SSE d'une génération en message gRPC ; `None` si ses données sont illisibles.
+fn chat_event(mut event: ClientEvent) -> Option<ChatEvent> {
+    let data = &mut event.data;
+    let text = |value: &Value| value.as_str().map(str::to_string);
+    let session = |value: &mut Value| {
+        serde_json::from_value::<ChatSession>(value.take())
+            .ok()
+            .map(proto::Session::from)
+    };
+    let event_data = match event.kind {
+        EventKind::Session => Event::Session(session(&mut data["session"])?),
+        EventKind::Token => Event::Token(proto::TextDelta {
+            content: text(&data["content"])?,
+        }),
+        EventKind::Reasoning => Event::Reasoning(proto::TextDelta {
+            content: text(&data["content"])?,
+        }),
+        EventKind::Title => Event::Title(proto::Title {
+            title: text(&data["title"])?,
+            icon: text(&data["icon"]),
+        }),
+        EventKind::Final => Event::Final(proto::Final {
+            session: Some(session(&mut data["session"])?),
+            finish_reason: text(&data["finishReason"]),
+        }),
+        EventKind::Usage => Event::Usage(proto::Usage {
+            model: text(&data["model"])?,
+            tokens: data["promptTokens"]
+                .as_i64()
+                .map(|prompt_tokens| proto::TokenUsage {
+                    prompt_tokens: prompt_tokens as i32,
+                    completion_tokens: data["completionTokens"].as_i64().unwrap_or(0) as i32,
+                    cached_tokens: data["cachedTokens"].as_i64().unwrap_or(0) as i32,
+                }),
+            cost_usd: data["costUsd"].as_f64(),
+            latency_ms: data["latencyMs"].as_u64().unwrap_or_default(),
+            first_token_ms: data["firstTokenMs"].as_u64(),
+            finish_reason: text(&data["finishReason"]),
+        }),
+        EventKind::Notice => Event::Notice(proto::Notice {
+            message: text(&data["message"])?,
+        }),
+        EventKind::Refusal => Event::Refusal(proto::Refusal {
+            reason: text(&data["reason"])?,
+            message: text(&data["message"]),
+        }),
+        EventKind::Revision => Event::Revision(proto::Revision {
+            content: text(&data["content"])?,
+            draft_model: text(&data["draftModel"])?,
+        }),
+        EventKind::Error => Event::Error(proto::Error {
+            message: text(&data["message"])?,
+            partial: data["partial"].as_bool().unwrap_or(false),
+        }),
+    };
+    Some(ChatEvent {
+        seq: event.seq,
+        event: Some(event_data),
+    })
+}
+
+impl From<ChatSession> for proto::Session {
+    fn from(session: ChatSession) -> Self {
+        proto::Session {
+            id: session.id.to_string(),
+            title: session.title,
+            icon: session.icon,
+            created_at: session.created_at.to_rfc3339(),
+            updated_at: session.updated_at.to_rfc3339(),
+            archived: session.archived,
+            messages: session.messages.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<ChatMessage> for proto::Message {
+    fn from(message: ChatMessage) -> Self {
+        proto::Message {
+            id: message.id.to_string(),
+            role: message.role,
+            content: message.content,
+            position: message.position,
+            status: message.status.as_str().to_string(),
+            model: message.model,
+            finish_reason: message
+                .finish_reason
+                .map(|reason| reason.as_str().to_string()),
+            usage: message.usage.map(Into::into),
+            created_at: message.created_at.to_rfc3339(),
+            attachments: message.attachments.into_iter().map(Into::into).collect(),
+            refused: message.refused,
+        }
+    }
+}
+
+impl From<ChatAttachment> for proto::Attachment {
+    fn from(attachment: ChatAttachment) -> Self {
+        proto::Attachment {
+            file_name: attachment.file_name,
+            mime_type: attachment.mime_type,
+            size_bytes: attachment.size_bytes,
+            url: attachment.url,
+        }
+    }
+}
+
+impl From<TokenUsage> for proto::TokenUsage {
+    fn from(usage: TokenUsage) -> Self {
+        proto::TokenUsage {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            cached_tokens: usage.cached_tokens,
+        }
+    }
+}
+
+fn parse_id(id: &str) -> Result<Uuid, (StatusCode, String)> {
+    id.parse().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Identifiant invalide : {id}"),
+        )
+    })
+}
+
+/// Erreur HTTP d'un service en statut gRPC.
+fn status((code, message): (StatusCode, String)) -> Status {
+    match code {
+        StatusCode::BAD_REQUEST | StatusCode::PAYLOAD_TOO_LARGE => {
+            Status::invalid_argument(message)
+        }
+        StatusCode::UNAUTHORIZED => Status::unauthenticated(message),
+        StatusCode::FORBIDDEN => Status::permission_denied(message),
+        StatusCode::NOT_FOUND => Status::not_found(message),
+        StatusCode::CONFLICT => Status::failed_precondition(message),
+        StatusCode::TOO_MANY_REQUESTS => Status::resource_exhausted(message),
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => Status::unavailable(message),
+        _ => Status::internal(message),
+    }
+}
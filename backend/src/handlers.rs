@@ -0,0 +1,1628 @@
+//! Handlers HTTP : désérialisent la requête, délèguent à `ChatService` et mettent en forme
+//! la réponse (JSON ou SSE).
+
+use std::{
+    convert::Infallible,
+    path::{Path as StdPath, PathBuf},
+};
+
+use axum::{
+    Json,
+    body::Body,
+    extract::{Multipart, Path, Query, Request, State},
+    http::{HeaderValue, header},
+    response::{
+        IntoResponse,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use futures::stream::{self, StreamExt};
+use serde_json::{Value, json};
+use tokio::sync::broadcast;
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    access::Caller,
+    artifacts::{artifacts_zip, extract_artifacts},
+    backup,
+    cache::ResponseCache,
+    calendar::calendar_ics,
+    export::{ExportJob, export_path, run_export},
+    features::{ClientFeatures, Features},
+    internal_error,
+    latency::LatencyClock,
+    maintenance_mode::{MaintenanceRequest, MaintenanceStatus},
+    metrics::UploadEndpoint,
+    models::{
+        AIRequest, AIResponse, AttachmentPayload, BackupInfo, Bookmark, BookmarkRequest,
+        BulkSessionRequest, BulkSessionResult, Calendar, CalendarFeedQuery, ChatDraft, ChatExport,
+        ChatMessagePayload, ChatSession, CodeArtifact, CompletionParams, CompletionPreset,
+        CompletionPresetRequest, ContinueRequest, ConversationTemplate, CostEstimate,
+        CreateChatMessageRequest, CreateChatSessionRequest, CreateMessageRequest, DailyUsage,
+        ExportDownloadQuery, ExportFormat, ExportRequest, ExportStatus, FeatureFlag,
+        FeatureFlagRequest, LatencyQuery, LatencyReport, Message, MessageContextRequest,
+        MessageListQuery, PasteTextRequest, ProviderDebugLog, ProviderLogQuery, QueryReport,
+        QueryReportQuery, ReactionRequest, RegenerateRequest, RestoreReport, SaveDraftRequest,
+        ScrubAuditEntry, ScrubAuditQuery, SelectCandidateRequest, SessionRestore,
+        SessionRestoreRequest, SystemPromptPreview, UpdateMessageRequest, UploadedFile, UsageQuery,
+        UserPreferences, WidgetChatRequest, WidgetInfo,
+    },
+    plugins::PluginReport,
+    prompt,
+    providers::{
+        AiModelChoice, ProviderChunk, model_available, request_ai_completion,
+        request_completion_with_prompt,
+    },
+    proxy::ClientOrigin,
+    scheduler::JobStatus,
+    service::ChatService,
+    storage::{process_upload, sanitize_file_name},
+    stream::{
+        ClientEvents, ClientSink, EventKind, StreamSegment, ThinkingSplitter, coalesce,
+        refusal_data, usage_data,
+    },
+    widget::{self, WidgetAccess},
+};
+
+// --------- Handlers ---------
+
+pub(crate) async fn health_check(State(state): State<AppState>) -> &'static str {
+    if let Err(e) = state.repo.ping().await {
+        eprintln!("DB health check failed: {e}");
+        "DB ERROR"
+    } else {
+        "OK ça marche"
+    }
+}
+
+// GET /api/models : modèles accessibles à l'appelant et modèle par défaut
+pub(crate) async fn list_models(State(state): State<AppState>, caller: Caller) -> Json<Value> {
+    let models: Vec<Value> = state
+        .models
+        .available(caller)
+        .into_iter()
+        .map(|model| {
+            json!({
+                "id": model.model_id(),
+                "supports_attachments": model.supports_attachments(),
+                "context_window": model.context_window(),
+                "max_output_tokens": model.max_output_tokens(),
+                "supported_params": model
+                    .supported_params()
+                    .iter()
+                    .map(|param| param.name())
+                    .collect::<Vec<_>>(),
+                "available": model_available(&state, model),
+            })
+        })
+        .collect();
+    Json(json!({
+        "default": state.models.default.model_id(),
+        "models": models,
+    }))
+}
+
+// GET /api/openapi.yaml : endpoints SSE et schéma de leurs évènements
+pub(crate) async fn openapi_spec() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "application/yaml")],
+        include_str!("../openapi.yaml"),
+    )
+}
+
+// GET /api/events : flux SSE des générations démarrées/terminées, toutes sessions confondues
+pub(crate) async fn events_stream(
+    State(state): State<AppState>,
+) -> Sse<impl futures::Stream<Item = Result<Event, Infallible>>> {
+    let rx = state.events.subscribe();
+    let stream = stream::unfold(rx, |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => match Event::default().json_data(&event) {
+                    Ok(ev) => return Some((Ok(ev), rx)),
+                    Err(err) => eprintln!("Erreur sérialisation évènement: {err}"),
+                },
+                // Un abonné trop lent perd des évènements, il n'est pas déconnecté.
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+// GET /api/messages?limit=&before=&author= : du plus récent au plus ancien ; la page
+// suivante s'obtient avec `before` = identifiant du dernier message reçu
+pub(crate) async fn list_messages(
+    State(state): State<AppState>,
+    Query(query): Query<MessageListQuery>,
+) -> Result<Json<Vec<Message>>, (axum::http::StatusCode, String)> {
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+    let messages = state
+        .repo
+        .list_board_messages(query.before, query.author.as_deref(), limit)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(messages))
+}
+
+// POST /api/messages
+pub(crate) async fn create_message(
+    State(state): State<AppState>,
+    Json(payload): Json<CreateMessageRequest>,
+) -> Result<Json<Message>, (axum::http::StatusCode, String)> {
+    let author = board_field("auteur", Some(&payload.author))?.unwrap_or_default();
+    let content = board_field("message", Some(&payload.content))?.unwrap_or_default();
+    let message = state
+        .repo
+        .create_board_message(author, content)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(message))
+}
+
+// PATCH /api/messages/:id : change l'auteur et/ou le texte ; le livre d'or est anonyme,
+// seule la modération (jeton admin) peut retoucher un message
+pub(crate) async fn update_message(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    caller: Caller,
+    Json(payload): Json<UpdateMessageRequest>,
+) -> Result<Json<Message>, (axum::http::StatusCode, String)> {
+    caller.require_admin()?;
+    let author = board_field("auteur", payload.author.as_deref())?;
+    let content = board_field("message", payload.content.as_deref())?;
+    if author.is_none() && content.is_none() {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            "Rien à modifier : donne `author` et/ou `content`.".to_string(),
+        ));
+    }
+    state
+        .repo
+        .update_board_message(id, author, content)
+        .await
+        .map_err(internal_error)?
+        .map(Json)
+        .ok_or_else(board_message_not_found)
+}
+
+// DELETE /api/messages/:id : réservé à la modération, comme PATCH
+pub(crate) async fn delete_message(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    caller: Caller,
+) -> Result<axum::http::StatusCode, (axum::http::StatusCode, String)> {
+    caller.require_admin()?;
+    if state
+        .repo
+        .delete_board_message(id)
+        .await
+        .map_err(internal_error)?
+    {
+        Ok(axum::http::StatusCode::NO_CONTENT)
+    } else {
+        Err(board_message_not_found())
+    }
+}
+
+/// Champ d'un message public sans les espaces autour ; vide, il est refusé.
+fn board_field<'a>(
+    name: &str,
+    value: Option<&'a str>,
+) -> Result<Option<&'a str>, (axum::http::StatusCode, String)> {
+    match value.map(str::trim) {
+        Some("") => Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            format!("Le champ {name} ne peut pas être vide."),
+        )),
+        value => Ok(value),
+    }
+}
+
+fn board_message_not_found() -> (axum::http::StatusCode, String) {
+    (
+        axum::http::StatusCode::NOT_FOUND,
+        "Message introuvable.".to_string(),
+    )
+}
+
+// POST /api/ai
+pub(crate) async fn ai_handler(
+    State(state): State<AppState>,
+    caller: Caller,
+    features: Features,
+    Json(payload): Json<AIRequest>,
+) -> Result<Json<AIResponse>, (axum::http::StatusCode, String)> {
+    let AIRequest {
+        messages,
+        model,
+        completion_params,
+    } = payload;
+    let completion_params = features.restrict(completion_params);
+    if messages.is_empty() {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            "Le corps de la requête doit contenir au moins un message.".to_string(),
+        ));
+    }
+
+    let ai_model = state
+        .models
+        .select(model.as_deref(), &messages, caller)?
+        .model;
+    if !ai_model.supports_attachments() && messages.iter().any(|msg| !msg.attachments.is_empty()) {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            "Ce modèle n'accepte pas les fichiers ni les images.".to_string(),
+        ));
+    }
+
+    let cache_key = ResponseCache::key(ai_model, &messages, completion_params.as_ref());
+    let cached = match &state.ai_cache {
+        Some(cache) => cache.get(cache_key).await,
+        None => None,
+    };
+    if let Some((response, usage)) = cached {
+        return Ok(Json(AIResponse {
+            response,
+            usage,
+            cached: true,
+            refused: false,
+            response_id: None,
+        }));
+    }
+
+    let mut stream = request_ai_completion(&state, &messages, ai_model, completion_params).await?;
+    let mut answer = String::new();
+    let mut usage = None;
+    let mut finish_reason = None;
+    let mut refusal = String::new();
+    let mut response_id = None;
+    let mut complete = true;
+    while let Some(chunk_res) = stream.next().await {
+        match chunk_res {
+            Ok(ProviderChunk::Text(chunk)) => answer.push_str(&chunk),
+            Ok(ProviderChunk::Refusal(text)) => refusal.push_str(&text),
+            Ok(ProviderChunk::Usage(reported)) => usage = Some(reported),
+            Ok(ProviderChunk::Finish(reason)) => finish_reason = Some(reason),
+            Ok(ProviderChunk::ResponseId(id)) => response_id = Some(id),
+            Ok(
+                ProviderChunk::DebugLogId(_)
+                | ProviderChunk::AttachmentTime(_)
+                | ProviderChunk::Notice(_)
+                | ProviderChunk::Attachment(_),
+            ) => {}
+            Err(_) => complete = false,
+        }
+    }
+
+    answer.push_str(&refusal);
+    let refused = refusal_data(&refusal, finish_reason).is_some();
+
+    // Une réponse tronquée par une erreur du provider ou refusée n'est jamais mise en cache.
+    if let Some(cache) = state.ai_cache.as_ref().filter(|_| complete && !refused) {
+        cache.insert(cache_key, answer.clone(), usage).await;
+    }
+
+    Ok(Json(AIResponse {
+        response: answer,
+        usage,
+        cached: false,
+        refused,
+        response_id,
+    }))
+}
+
+// POST /api/ai/stream : même schéma d'évènements SSE que les sessions, sans persistance
+pub(crate) async fn ai_stream_handler(
+    State(state): State<AppState>,
+    caller: Caller,
+    features: Features,
+    Json(payload): Json<AIRequest>,
+) -> Result<
+    Sse<impl futures::Stream<Item = Result<Event, Infallible>>>,
+    (axum::http::StatusCode, String),
+> {
+    let AIRequest {
+        messages,
+        model,
+        completion_params,
+    } = payload;
+    let completion_params = features.restrict(completion_params);
+    if messages.is_empty() {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            "Le corps de la requête doit contenir au moins un message.".to_string(),
+        ));
+    }
+
+    let ai_model = state
+        .models
+        .select(model.as_deref(), &messages, caller)?
+        .model;
+    if !ai_model.supports_attachments() && messages.iter().any(|msg| !msg.attachments.is_empty()) {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            "Ce modèle n'accepte pas les fichiers ni les images.".to_string(),
+        ));
+    }
+    let system_prompt = prompt::system_prompt(&state).await;
+    let events = ai_reply_events(
+        &state,
+        &system_prompt,
+        &messages,
+        ai_model,
+        completion_params,
+    )
+    .await?;
+    Ok(Sse::new(events.map(|event| Ok(event.into_sse()))))
+}
+
+/// Génère une réponse sans session, précédée de `system_prompt`, et la renvoie en évènements
+/// `token`, `reasoning`, `refusal`, `final`, `usage` et `error` ; rien n'est persisté.
+pub(crate) async fn ai_reply_events(
+    state: &AppState,
+    system_prompt: &str,
+    messages: &[ChatMessagePayload],
+    ai_model: AiModelChoice,
+    completion_params: Option<CompletionParams>,
+) -> Result<ClientEvents, (axum::http::StatusCode, String)> {
+    let mut clock = LatencyClock::start();
+    let stream =
+        request_completion_with_prompt(state, system_prompt, messages, ai_model, completion_params)
+            .await?;
+    let mut stream = coalesce(
+        stream,
+        state.stream_coalesce_interval,
+        state.stream_coalesce_chars,
+    );
+
+    let (mut client, events) = ClientSink::channel(None, None);
+    tokio::spawn(async move {
+        let mut full_answer = String::new();
+        let mut splitter = ThinkingSplitter::default();
+        let mut usage = None;
+        let mut finish_reason = None;
+        let mut refusal = String::new();
+        let mut response_id = None;
+
+        loop {
+            // Client parti : on abandonne la tâche, ce qui ferme la requête au provider.
+            let chunk_res = tokio::select! {
+                chunk_res = stream.next() => match chunk_res {
+                    Some(chunk_res) => chunk_res,
+                    None => break,
+                },
+                () = client.closed() => return,
+            };
+            if let Ok(chunk) = &chunk_res {
+                clock.observe(chunk);
+            }
+            match chunk_res {
+                Ok(ProviderChunk::Text(chunk)) => {
+                    for segment in splitter.push(&chunk) {
+                        if let StreamSegment::Token(content) = &segment {
+                            full_answer.push_str(content);
+                        }
+                        if !client.send_segment(segment) {
+                            return;
+                        }
+                    }
+                }
+                Ok(ProviderChunk::Usage(reported)) => usage = Some(reported),
+                Ok(ProviderChunk::Finish(reason)) => finish_reason = Some(reason),
+                Ok(ProviderChunk::ResponseId(id)) => response_id = Some(id),
+                Ok(
+                    ProviderChunk::DebugLogId(_)
+                    | ProviderChunk::AttachmentTime(_)
+                    | ProviderChunk::Attachment(_),
+                ) => {}
+                Ok(ProviderChunk::Notice(message)) => {
+                    client.send(EventKind::Notice, json!({ "message": message }));
+                }
+                Ok(ProviderChunk::Refusal(text)) => refusal.push_str(&text),
+                Err(err) => {
+                    eprintln!("Erreur stream: {err}");
+                    client.send(EventKind::Error, json!({ "message": err }));
+                    return;
+                }
+            }
+        }
+        clock.end_of_stream();
+
+        if let Some(segment) = splitter.finish() {
+            if let StreamSegment::Token(content) = &segment {
+                full_answer.push_str(content);
+            }
+            client.send_segment(segment);
+        }
+        full_answer.push_str(&refusal);
+        let refused = match refusal_data(&refusal, finish_reason) {
+            Some(data) => {
+                client.send(EventKind::Refusal, data);
+                true
+            }
+            None => false,
+        };
+
+        client.send(
+            EventKind::Final,
+            json!({
+                "response": full_answer,
+                "usage": usage,
+                "refused": refused,
+                "finishReason": finish_reason.map(|reason| reason.as_str()),
+                "responseId": response_id
+            }),
+        );
+        client.send(
+            EventKind::Usage,
+            usage_data(ai_model, usage, &clock, finish_reason),
+        );
+    });
+
+    Ok(events)
+}
+
+pub(crate) async fn widget_info(WidgetAccess(widget): WidgetAccess) -> Json<WidgetInfo> {
+    Json(WidgetInfo {
+        id: widget.widget.id.clone(),
+        name: widget.widget.name.clone(),
+    })
+}
+
+/// Réponse streamée du widget, avec son persona, son modèle et ses paramètres. Le prompt
+/// système se limite au prompt de base : les préférences et instructions du propriétaire ne
+/// sont pas exposées aux visiteurs.
+pub(crate) async fn widget_chat_stream(
+    State(state): State<AppState>,
+    WidgetAccess(widget): WidgetAccess,
+    Json(payload): Json<WidgetChatRequest>,
+) -> Result<
+    Sse<impl futures::Stream<Item = Result<Event, Infallible>>>,
+    (axum::http::StatusCode, String),
+> {
+    let widget = &widget.widget;
+    let messages = widget::conversation(widget, payload.messages)?;
+    // Le modèle est choisi par l'admin dans `WIDGETS_FILE`, hors `ALLOWED_MODELS`.
+    let ai_model = state
+        .models
+        .select(widget.model.as_deref(), &messages, Caller { admin: true })?
+        .model;
+    let events = ai_reply_events(
+        &state,
+        prompt::base_prompt(),
+        &messages,
+        ai_model,
+        widget.completion_params.clone(),
+    )
+    .await?;
+    Ok(Sse::new(events.map(|event| Ok(event.into_sse()))))
+}
+
+/// Réponses les plus lentes listées par `GET /api/admin/latency`
+const SLOWEST_MESSAGES: i64 = 20;
+
+/// Taille maximale d'un fichier uploadé ou d'un texte collé
+pub(crate) const MAX_UPLOAD_SIZE: usize = 20 * 1024 * 1024; // 20 MB
+
+pub(crate) async fn upload_file(
+    State(state): State<AppState>,
+    origin: ClientOrigin,
+    mut multipart: Multipart,
+) -> Result<Json<UploadedFile>, (axum::http::StatusCode, String)> {
+    if let Some(field) = multipart.next_field().await.map_err(internal_error)? {
+        let original_name = field
+            .file_name()
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| format!("fichier-{}.bin", Uuid::new_v4()));
+        let mime_type = field
+            .content_type()
+            .map(|m| m.to_string())
+            .unwrap_or_else(|| "application/octet-stream".to_string());
+        let data = field.bytes().await.map_err(internal_error)?;
+
+        let uploaded = store_upload(
+            &state,
+            &origin,
+            UploadEndpoint::File,
+            original_name,
+            mime_type,
+            &data,
+        )
+        .await?;
+        return Ok(Json(uploaded));
+    }
+
+    Err((
+        axum::http::StatusCode::BAD_REQUEST,
+        "Aucun fichier reçu.".to_string(),
+    ))
+}
+
+/// Enregistre un long texte collé comme pièce jointe texte, pour ne pas l'écrire dans le
+/// message : le modèle le reçoit en entier, l'interface n'affiche que l'aperçu.
+pub(crate) async fn upload_text(
+    State(state): State<AppState>,
+    origin: ClientOrigin,
+    Json(payload): Json<PasteTextRequest>,
+) -> Result<Json<UploadedFile>, (axum::http::StatusCode, String)> {
+    if payload.text.trim().is_empty() {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            "Le texte collé est vide.".to_string(),
+        ));
+    }
+    let file_name = payload
+        .file_name
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| "texte-colle.txt".to_string());
+
+    let mut uploaded = store_upload(
+        &state,
+        &origin,
+        UploadEndpoint::Text,
+        file_name,
+        "text/plain".to_string(),
+        payload.text.as_bytes(),
+    )
+    .await?;
+    uploaded.preview = Some(paste_preview(&payload.text));
+    Ok(Json(uploaded))
+}
+
+/// Écrit le fichier dans le dossier des uploads et lance l'extraction de son contenu.
+async fn store_upload(
+    state: &AppState,
+    origin: &ClientOrigin,
+    endpoint: UploadEndpoint,
+    original_name: String,
+    mime_type: String,
+    data: &[u8],
+) -> Result<UploadedFile, (axum::http::StatusCode, String)> {
+    if data.len() > MAX_UPLOAD_SIZE {
+        return Err((
+            axum::http::StatusCode::PAYLOAD_TOO_LARGE,
+            "Fichier trop volumineux (max 20 Mo).".to_string(),
+        ));
+    }
+
+    let sanitized = sanitize_file_name(&original_name);
+    let extension = StdPath::new(&sanitized)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("bin");
+    let stored_name = format!("{}.{extension}", Uuid::new_v4());
+
+    let mut path = PathBuf::from(&state.upload_dir);
+    path.push(&stored_name);
+    tokio::fs::write(&path, data)
+        .await
+        .map_err(internal_error)?;
+
+    let url = state.urls.upload(Some(origin), &stored_name);
+
+    let mut attachment = AttachmentPayload {
+        file_name: original_name,
+        mime_type,
+        size_bytes: data.len() as i64,
+        url,
+        storage_key: Some(stored_name),
+        pages: None,
+        regions: None,
+        metadata: Default::default(),
+    };
+    let processing_status = process_upload(state, &mut attachment, data)
+        .await
+        .map_err(internal_error)?;
+    state
+        .upload_metrics
+        .record_upload(endpoint, &attachment.mime_type, data.len());
+
+    Ok(UploadedFile {
+        attachment,
+        processing_status,
+        preview: None,
+    })
+}
+
+/// Début du texte collé, sur une ligne, pour l'afficher à la place du contenu.
+fn paste_preview(text: &str) -> String {
+    const PREVIEW_CHARS: usize = 200;
+    let flattened = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match flattened.char_indices().nth(PREVIEW_CHARS) {
+        Some((end, _)) => format!("{}…", &flattened[..end]),
+        None => flattened,
+    }
+}
+
+// Utilitaire: transformer erreurs SQLx en 500
+pub(crate) async fn list_chat_sessions(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ChatSession>>, (axum::http::StatusCode, String)> {
+    let sessions = state.repo.list_sessions().await.map_err(internal_error)?;
+    Ok(Json(sessions))
+}
+
+pub(crate) async fn create_chat_session(
+    State(state): State<AppState>,
+    Json(payload): Json<CreateChatSessionRequest>,
+) -> Result<Json<ChatSession>, (axum::http::StatusCode, String)> {
+    let session = ChatService::new(&state)
+        .create_session(payload.title)
+        .await?;
+    Ok(Json(session))
+}
+
+// GET /api/templates : discussions prêtes à l'emploi proposées par le déploiement
+pub(crate) async fn list_templates(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Json<Vec<ConversationTemplate>> {
+    Json(ChatService::new(&state).with_caller(caller).templates())
+}
+
+// POST /api/chat/sessions/from-template/:id : nouvelle discussion d'après un gabarit
+pub(crate) async fn create_session_from_template(
+    State(state): State<AppState>,
+    Path(template_id): Path<String>,
+) -> Result<Json<ChatSession>, (axum::http::StatusCode, String)> {
+    let session = ChatService::new(&state)
+        .create_session_from_template(&template_id)
+        .await?;
+    Ok(Json(session))
+}
+
+pub(crate) async fn append_chat_message(
+    State(state): State<AppState>,
+    caller: Caller,
+    features: Features,
+    Path(session_id): Path<Uuid>,
+    Json(payload): Json<CreateChatMessageRequest>,
+) -> Result<Json<ChatSession>, (axum::http::StatusCode, String)> {
+    let session = ChatService::new(&state)
+        .with_caller(caller)
+        .with_features(features)
+        .create_exchange(session_id, payload)
+        .await?;
+    Ok(Json(session))
+}
+
+pub(crate) async fn append_chat_message_stream(
+    State(state): State<AppState>,
+    caller: Caller,
+    features: Features,
+    Path(session_id): Path<Uuid>,
+    Json(payload): Json<CreateChatMessageRequest>,
+) -> Result<
+    Sse<impl futures::Stream<Item = Result<Event, Infallible>>>,
+    (axum::http::StatusCode, String),
+> {
+    let generation = ChatService::new(&state)
+        .with_caller(caller)
+        .with_features(features)
+        .start_exchange(session_id, payload)
+        .await?;
+    let events = generation.spawn(state.clone());
+    Ok(Sse::new(events.map(|event| Ok(event.into_sse()))))
+}
+
+pub(crate) async fn regenerate_message(
+    State(state): State<AppState>,
+    caller: Caller,
+    features: Features,
+    Path(session_id): Path<Uuid>,
+    Json(payload): Json<RegenerateRequest>,
+) -> Result<Json<ChatSession>, (axum::http::StatusCode, String)> {
+    let session = ChatService::new(&state)
+        .with_caller(caller)
+        .with_features(features)
+        .regenerate(session_id, payload)
+        .await?;
+    Ok(Json(session))
+}
+
+pub(crate) async fn regenerate_message_stream(
+    State(state): State<AppState>,
+    caller: Caller,
+    features: Features,
+    Path(session_id): Path<Uuid>,
+    Json(payload): Json<RegenerateRequest>,
+) -> Result<
+    Sse<impl futures::Stream<Item = Result<Event, Infallible>>>,
+    (axum::http::StatusCode, String),
+> {
+    let generation = ChatService::new(&state)
+        .with_caller(caller)
+        .with_features(features)
+        .start_regeneration(session_id, payload)
+        .await?;
+    let events = generation.spawn(state.clone());
+    Ok(Sse::new(events.map(|event| Ok(event.into_sse()))))
+}
+
+pub(crate) async fn continue_message_stream(
+    State(state): State<AppState>,
+    caller: Caller,
+    features: Features,
+    Path(session_id): Path<Uuid>,
+    Json(payload): Json<ContinueRequest>,
+) -> Result<
+    Sse<impl futures::Stream<Item = Result<Event, Infallible>>>,
+    (axum::http::StatusCode, String),
+> {
+    let generation = ChatService::new(&state)
+        .with_caller(caller)
+        .with_features(features)
+        .start_continuation(session_id, payload)
+        .await?;
+    let events = generation.spawn(state.clone());
+    Ok(Sse::new(events.map(|event| Ok(event.into_sse()))))
+}
+
+// POST /api/chat/sessions/:id/estimate : coût estimé du message avant envoi
+pub(crate) async fn estimate_chat_message(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(session_id): Path<Uuid>,
+    Json(payload): Json<CreateChatMessageRequest>,
+) -> Result<Json<CostEstimate>, (axum::http::StatusCode, String)> {
+    let estimate = ChatService::new(&state)
+        .with_caller(caller)
+        .estimate(session_id, payload)
+        .await?;
+    Ok(Json(estimate))
+}
+
+// PUT /api/chat/sessions/:id/draft : un brouillon vide (ni texte ni fichier) est supprimé
+pub(crate) async fn save_chat_draft(
+    State(state): State<AppState>,
+    Path(session_id): Path<Uuid>,
+    Json(payload): Json<SaveDraftRequest>,
+) -> Result<Json<Option<ChatDraft>>, (axum::http::StatusCode, String)> {
+    let draft = ChatService::new(&state)
+        .save_draft(session_id, payload)
+        .await?;
+    Ok(Json(draft))
+}
+
+pub(crate) async fn list_presets(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<CompletionPreset>>, (axum::http::StatusCode, String)> {
+    let presets = state.repo.list_presets().await.map_err(internal_error)?;
+    Ok(Json(presets))
+}
+
+pub(crate) async fn create_preset(
+    State(state): State<AppState>,
+    Json(payload): Json<CompletionPresetRequest>,
+) -> Result<Json<CompletionPreset>, (axum::http::StatusCode, String)> {
+    let preset = ChatService::new(&state).create_preset(payload).await?;
+    Ok(Json(preset))
+}
+
+pub(crate) async fn update_preset(
+    State(state): State<AppState>,
+    Path(preset_id): Path<Uuid>,
+    Json(payload): Json<CompletionPresetRequest>,
+) -> Result<Json<CompletionPreset>, (axum::http::StatusCode, String)> {
+    let preset = ChatService::new(&state)
+        .update_preset(preset_id, payload)
+        .await?;
+    Ok(Json(preset))
+}
+
+pub(crate) async fn delete_preset(
+    State(state): State<AppState>,
+    Path(preset_id): Path<Uuid>,
+) -> Result<axum::http::StatusCode, (axum::http::StatusCode, String)> {
+    ChatService::new(&state).delete_preset(preset_id).await?;
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+// GET /api/me/preferences : préférences de l'utilisateur, vides tant qu'il n'en a pas
+// enregistré
+pub(crate) async fn get_preferences(
+    State(state): State<AppState>,
+) -> Result<Json<UserPreferences>, (axum::http::StatusCode, String)> {
+    let preferences = ChatService::new(&state).preferences().await?;
+    Ok(Json(preferences))
+}
+
+// PUT /api/me/preferences : remplace toutes les préférences
+pub(crate) async fn put_preferences(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(payload): Json<UserPreferences>,
+) -> Result<Json<UserPreferences>, (axum::http::StatusCode, String)> {
+    let preferences = ChatService::new(&state)
+        .with_caller(caller)
+        .save_preferences(payload)
+        .await?;
+    Ok(Json(preferences))
+}
+
+// GET /api/me/calendar : évènements et tâches ajoutés par l'outil `add_to_calendar`, et lien
+// d'abonnement au flux ICS
+pub(crate) async fn get_calendar(
+    State(state): State<AppState>,
+    origin: ClientOrigin,
+) -> Result<Json<Calendar>, (axum::http::StatusCode, String)> {
+    let token = state
+        .repo
+        .calendar_feed_token()
+        .await
+        .map_err(internal_error)?;
+    calendar_response(&state, &origin, token).await
+}
+
+// POST /api/me/calendar/token : nouveau lien d'abonnement, l'ancien cesse de fonctionner
+pub(crate) async fn rotate_calendar_token(
+    State(state): State<AppState>,
+    origin: ClientOrigin,
+) -> Result<Json<Calendar>, (axum::http::StatusCode, String)> {
+    let token = state
+        .repo
+        .rotate_calendar_token()
+        .await
+        .map_err(internal_error)?;
+    calendar_response(&state, &origin, token).await
+}
+
+async fn calendar_response(
+    state: &AppState,
+    origin: &ClientOrigin,
+    token: Uuid,
+) -> Result<Json<Calendar>, (axum::http::StatusCode, String)> {
+    let items = state.repo.calendar_items().await.map_err(internal_error)?;
+    Ok(Json(Calendar {
+        feed_url: state.urls.calendar_feed(Some(origin), token),
+        items,
+    }))
+}
+
+// DELETE /api/me/calendar/items/:id
+pub(crate) async fn delete_calendar_item(
+    State(state): State<AppState>,
+    Path(item_id): Path<Uuid>,
+) -> Result<axum::http::StatusCode, (axum::http::StatusCode, String)> {
+    if state
+        .repo
+        .delete_calendar_item(item_id)
+        .await
+        .map_err(internal_error)?
+    {
+        Ok(axum::http::StatusCode::NO_CONTENT)
+    } else {
+        Err((
+            axum::http::StatusCode::NOT_FOUND,
+            "Élément du calendrier introuvable.".to_string(),
+        ))
+    }
+}
+
+/// GET /api/me/calendar.ics?token=... : flux auquel s'abonne une application de calendrier,
+/// qui n'envoie pas d'en-têtes ; un jeton erroné répond comme un flux inexistant.
+pub(crate) async fn calendar_feed(
+    State(state): State<AppState>,
+    Query(query): Query<CalendarFeedQuery>,
+) -> Result<axum::response::Response, (axum::http::StatusCode, String)> {
+    let token = state
+        .repo
+        .calendar_feed_token()
+        .await
+        .map_err(internal_error)?;
+    if query.token != token {
+        return Err((
+            axum::http::StatusCode::NOT_FOUND,
+            "Calendrier introuvable.".to_string(),
+        ));
+    }
+    let items = state.repo.calendar_items().await.map_err(internal_error)?;
+    Ok((
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        calendar_ics(&items),
+    )
+        .into_response())
+}
+
+// GET /api/admin/system-prompt : prompt système qu'enverrait la prochaine requête, avec la
+// valeur de chaque variable du gabarit ; il contient les préférences et instructions privées
+pub(crate) async fn system_prompt_preview(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<Json<SystemPromptPreview>, (axum::http::StatusCode, String)> {
+    caller.require_admin()?;
+    Ok(Json(prompt::preview(&state).await))
+}
+
+pub(crate) async fn archive_chat_session(
+    State(state): State<AppState>,
+    Path(session_id): Path<Uuid>,
+) -> Result<axum::http::StatusCode, (axum::http::StatusCode, String)> {
+    ChatService::new(&state).archive(session_id).await?;
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+// PUT /api/chat/sessions/:id/messages/:message_id/context : exclut le message du contexte
+// envoyé au modèle (`excluded_from_context: true`) ou l'y remet
+pub(crate) async fn set_message_context(
+    State(state): State<AppState>,
+    Path((session_id, message_id)): Path<(Uuid, Uuid)>,
+    Json(payload): Json<MessageContextRequest>,
+) -> Result<axum::http::StatusCode, (axum::http::StatusCode, String)> {
+    ChatService::new(&state)
+        .set_excluded_from_context(session_id, message_id, payload.excluded_from_context)
+        .await?;
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+// PUT /api/chat/sessions/:id/messages/:message_id/bookmark : met le message en signet
+// (`bookmarked: true`) ou l'en retire
+pub(crate) async fn set_message_bookmark(
+    State(state): State<AppState>,
+    Path((session_id, message_id)): Path<(Uuid, Uuid)>,
+    Json(payload): Json<BookmarkRequest>,
+) -> Result<axum::http::StatusCode, (axum::http::StatusCode, String)> {
+    ChatService::new(&state)
+        .set_bookmarked(session_id, message_id, payload.bookmarked)
+        .await?;
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+// PUT /api/chat/sessions/:id/messages/:message_id/reaction : `reaction: null` la retire
+pub(crate) async fn set_message_reaction(
+    State(state): State<AppState>,
+    Path((session_id, message_id)): Path<(Uuid, Uuid)>,
+    Json(payload): Json<ReactionRequest>,
+) -> Result<axum::http::StatusCode, (axum::http::StatusCode, String)> {
+    ChatService::new(&state)
+        .set_reaction(session_id, message_id, payload.reaction)
+        .await?;
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+// PUT /api/chat/sessions/:id/messages/:message_id/candidate : retient une des réponses
+// candidates (`candidate_id`) comme contenu du message
+pub(crate) async fn select_message_candidate(
+    State(state): State<AppState>,
+    Path((session_id, message_id)): Path<(Uuid, Uuid)>,
+    Json(payload): Json<SelectCandidateRequest>,
+) -> Result<Json<ChatSession>, (axum::http::StatusCode, String)> {
+    let session = ChatService::new(&state)
+        .select_candidate(session_id, message_id, payload)
+        .await?;
+    Ok(Json(session))
+}
+
+// GET /api/bookmarks : messages en signet de toutes les discussions
+pub(crate) async fn list_bookmarks(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<Bookmark>>, (axum::http::StatusCode, String)> {
+    let bookmarks = state.repo.list_bookmarks().await.map_err(internal_error)?;
+    Ok(Json(bookmarks))
+}
+
+// GET /api/chat/messages/:id/artifacts : blocs de code du message, avec langage et nom de
+// fichier
+pub(crate) async fn list_artifacts(
+    State(state): State<AppState>,
+    Path(message_id): Path<Uuid>,
+) -> Result<Json<Vec<CodeArtifact>>, (axum::http::StatusCode, String)> {
+    Ok(Json(message_artifacts(&state, message_id).await?))
+}
+
+// GET /api/chat/messages/:id/artifacts/:index : un bloc de code, en fichier texte
+pub(crate) async fn download_artifact(
+    State(state): State<AppState>,
+    Path((message_id, index)): Path<(Uuid, usize)>,
+) -> Result<axum::response::Response, (axum::http::StatusCode, String)> {
+    let artifact = message_artifacts(&state, message_id)
+        .await?
+        .into_iter()
+        .nth(index)
+        .ok_or((
+            axum::http::StatusCode::NOT_FOUND,
+            "Bloc de code introuvable.".to_string(),
+        ))?;
+    let download_name = artifact
+        .file_name
+        .rsplit('/')
+        .next()
+        .unwrap_or(&artifact.file_name);
+    Ok(attachment_response(
+        "text/plain; charset=utf-8",
+        download_name,
+        artifact.content.into_bytes(),
+    ))
+}
+
+// GET /api/chat/messages/:id/artifacts/download : archive zip de tous les blocs de code
+pub(crate) async fn download_artifacts(
+    State(state): State<AppState>,
+    Path(message_id): Path<Uuid>,
+) -> Result<axum::response::Response, (axum::http::StatusCode, String)> {
+    let artifacts = message_artifacts(&state, message_id).await?;
+    if artifacts.is_empty() {
+        return Err((
+            axum::http::StatusCode::NOT_FOUND,
+            "Ce message ne contient aucun bloc de code.".to_string(),
+        ));
+    }
+    let zip = artifacts_zip(&artifacts).map_err(internal_error)?;
+    let short_id = &message_id.simple().to_string()[..8];
+    Ok(attachment_response(
+        "application/zip",
+        &format!("code-{short_id}.zip"),
+        zip,
+    ))
+}
+
+async fn message_artifacts(
+    state: &AppState,
+    message_id: Uuid,
+) -> Result<Vec<CodeArtifact>, (axum::http::StatusCode, String)> {
+    let content = state
+        .repo
+        .fetch_message_content(message_id)
+        .await
+        .map_err(internal_error)?
+        .ok_or((
+            axum::http::StatusCode::NOT_FOUND,
+            "Message introuvable.".to_string(),
+        ))?;
+    Ok(extract_artifacts(&content))
+}
+
+/// Fichier à télécharger ; `file_name` est déjà nettoyé (ASCII, sans guillemets).
+fn attachment_response(
+    content_type: &'static str,
+    file_name: &str,
+    data: Vec<u8>,
+) -> axum::response::Response {
+    let mut response = ([(header::CONTENT_TYPE, content_type)], data).into_response();
+    if let Ok(value) = HeaderValue::from_str(&format!("attachment; filename=\"{file_name}\"")) {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_DISPOSITION, value);
+    }
+    response
+}
+
+// POST /api/chat/sessions/bulk : archive, supprime, étiquette ou range plusieurs sessions
+// dans une seule transaction
+pub(crate) async fn bulk_update_sessions(
+    State(state): State<AppState>,
+    Json(payload): Json<BulkSessionRequest>,
+) -> Result<Json<BulkSessionResult>, (axum::http::StatusCode, String)> {
+    let result = ChatService::new(&state).bulk_update(payload).await?;
+    Ok(Json(result))
+}
+
+pub(crate) async fn delete_chat_session(
+    State(state): State<AppState>,
+    Path(session_id): Path<Uuid>,
+) -> Result<axum::http::StatusCode, (axum::http::StatusCode, String)> {
+    ChatService::new(&state).delete(session_id).await?;
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+// POST /api/export : lance la construction de l'archive de tout l'historique, ou du PDF
+// (`{"format": "pdf", "session_id": …}`) ou des fiches Anki (`"format": "anki"`) d'une
+// session ; suivre l'avancement via `GET /api/export/:id` ou les évènements `export_progress`
+pub(crate) async fn start_export(
+    State(state): State<AppState>,
+    origin: ClientOrigin,
+    caller: Caller,
+    body: axum::body::Bytes,
+) -> Result<(axum::http::StatusCode, Json<ChatExport>), (axum::http::StatusCode, String)> {
+    // Le corps est facultatif : sans corps, l'export reste l'archive de tout l'historique.
+    let request: ExportRequest = if body.iter().all(u8::is_ascii_whitespace) {
+        ExportRequest::default()
+    } else {
+        serde_json::from_slice(&body).map_err(|err| {
+            (
+                axum::http::StatusCode::BAD_REQUEST,
+                format!("Requête d'export invalide : {err}"),
+            )
+        })?
+    };
+    let job = match (request.format, request.session_id) {
+        (ExportFormat::Zip, None) => ExportJob::Archive,
+        (ExportFormat::Zip, Some(_)) => {
+            return Err((
+                axum::http::StatusCode::BAD_REQUEST,
+                "L'archive zip contient tout l'historique : session_id ne s'applique qu'aux \
+                 formats pdf et anki."
+                    .to_string(),
+            ));
+        }
+        (_, None) => {
+            return Err((
+                axum::http::StatusCode::BAD_REQUEST,
+                format!(
+                    "L'export {} porte sur une session : session_id est requis.",
+                    request.format.as_str()
+                ),
+            ));
+        }
+        (format, Some(session_id)) => {
+            if !state
+                .repo
+                .session_exists(session_id)
+                .await
+                .map_err(internal_error)?
+            {
+                return Err((
+                    axum::http::StatusCode::NOT_FOUND,
+                    "Discussion introuvable.".to_string(),
+                ));
+            }
+            match format {
+                ExportFormat::Anki => ExportJob::Flashcards {
+                    session_id,
+                    model: state.models.resolve(request.model.as_deref(), caller)?,
+                },
+                _ => ExportJob::Pdf(session_id),
+            }
+        }
+    };
+
+    let export_id = state
+        .repo
+        .insert_export(request.format, request.session_id)
+        .await
+        .map_err(internal_error)?;
+    let export = fetch_export(&state, &origin, export_id).await?;
+    tokio::spawn(run_export(state, export_id, job));
+    Ok((axum::http::StatusCode::ACCEPTED, Json(export)))
+}
+
+pub(crate) async fn get_export(
+    State(state): State<AppState>,
+    origin: ClientOrigin,
+    Path(export_id): Path<Uuid>,
+) -> Result<Json<ChatExport>, (axum::http::StatusCode, String)> {
+    Ok(Json(fetch_export(&state, &origin, export_id).await?))
+}
+
+/// Sert l'archive ou le PDF tant que le lien est valable ; un jeton erroné répond comme un export
+/// inconnu.
+pub(crate) async fn download_export(
+    State(state): State<AppState>,
+    Path(export_id): Path<Uuid>,
+    Query(query): Query<ExportDownloadQuery>,
+    origin: ClientOrigin,
+    request: Request,
+) -> Result<axum::response::Response, (axum::http::StatusCode, String)> {
+    let export = fetch_export(&state, &origin, export_id).await?;
+    if export.download_token != query.token {
+        return Err(export_not_found());
+    }
+    let file_name = match (export.status, export.file_name) {
+        (ExportStatus::Ready, Some(file_name)) => file_name,
+        (ExportStatus::Expired, _) => {
+            return Err((
+                axum::http::StatusCode::GONE,
+                "Le lien de téléchargement a expiré.".to_string(),
+            ));
+        }
+        _ => {
+            return Err((
+                axum::http::StatusCode::CONFLICT,
+                "L'export n'est pas encore prêt.".to_string(),
+            ));
+        }
+    };
+
+    let response = ServeFile::new(export_path(&state, &file_name))
+        .oneshot(request)
+        .await
+        .map_err(internal_error)?;
+    let mut response = response.map(Body::new);
+    let attachment_name = format!(
+        "attachment; filename=\"carlgpt-export-{}.{}\"",
+        export.created_at.format("%Y%m%d"),
+        export.format.extension()
+    );
+    if let Ok(value) = HeaderValue::from_str(&attachment_name) {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_DISPOSITION, value);
+    }
+    Ok(response)
+}
+
+/// Export et, s'il est prêt, son lien de téléchargement absolu.
+async fn fetch_export(
+    state: &AppState,
+    origin: &ClientOrigin,
+    export_id: Uuid,
+) -> Result<ChatExport, (axum::http::StatusCode, String)> {
+    let mut export = state
+        .repo
+        .fetch_export(export_id)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(export_not_found)?;
+    if export.status == ExportStatus::Ready {
+        export.download_url = Some(state.urls.export_download(
+            Some(origin),
+            export.id,
+            export.download_token,
+        ));
+    }
+    Ok(export)
+}
+
+fn export_not_found() -> (axum::http::StatusCode, String) {
+    (
+        axum::http::StatusCode::NOT_FOUND,
+        "Export introuvable.".to_string(),
+    )
+}
+
+// GET /api/admin/jobs : tâches planifiées et issue de leur dernière exécution
+pub(crate) async fn list_jobs(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<Json<Vec<JobStatus>>, (axum::http::StatusCode, String)> {
+    caller.require_admin()?;
+    Ok(Json(state.scheduler.statuses()))
+}
+
+// GET /api/admin/backups : sauvegardes présentes dans `BACKUP_URL`, les plus récentes d'abord
+pub(crate) async fn list_backups(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<Json<Vec<BackupInfo>>, (axum::http::StatusCode, String)> {
+    caller.require_admin()?;
+    require_backups(&state)?;
+    let backups = backup::list_backups(&state).await.map_err(internal_error)?;
+    Ok(Json(backups))
+}
+
+// POST /api/admin/backups : sauvegarde immédiate, en plus de la tâche planifiée `backup`
+pub(crate) async fn create_backup(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<Json<BackupInfo>, (axum::http::StatusCode, String)> {
+    caller.require_admin()?;
+    require_backups(&state)?;
+    let backup = backup::run_backup(&state).await.map_err(internal_error)?;
+    Ok(Json(backup))
+}
+
+// POST /api/admin/backups/:name/restore : recrée les sessions de la sauvegarde absentes de
+// la base
+pub(crate) async fn restore_backup(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(name): Path<String>,
+) -> Result<Json<RestoreReport>, (axum::http::StatusCode, String)> {
+    caller.require_admin()?;
+    require_backups(&state)?;
+    match backup::restore_backup(&state, &name)
+        .await
+        .map_err(internal_error)?
+    {
+        Some(report) => Ok(Json(report)),
+        None => Err((
+            axum::http::StatusCode::NOT_FOUND,
+            format!("Sauvegarde introuvable : {name}."),
+        )),
+    }
+}
+
+// POST /api/admin/restore/session/:id : recrée une session supprimée telle qu'elle était à
+// la date `at`, depuis la dernière sauvegarde antérieure qui la contient
+pub(crate) async fn restore_session(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(session_id): Path<Uuid>,
+    Json(request): Json<SessionRestoreRequest>,
+) -> Result<Json<SessionRestore>, (axum::http::StatusCode, String)> {
+    caller.require_admin()?;
+    require_backups(&state)?;
+    let session_exists = || {
+        (
+            axum::http::StatusCode::CONFLICT,
+            "La session existe encore : supprimez-la avant de la restaurer.".to_string(),
+        )
+    };
+    if state
+        .repo
+        .session_exists(session_id)
+        .await
+        .map_err(internal_error)?
+    {
+        return Err(session_exists());
+    }
+    let at = request.at.unwrap_or_else(chrono::Utc::now);
+    let Some((backup, session)) = backup::find_session_at(&state, session_id, at)
+        .await
+        .map_err(internal_error)?
+    else {
+        return Err((
+            axum::http::StatusCode::NOT_FOUND,
+            format!("Aucune sauvegarde antérieure au {at} ne contient cette session."),
+        ));
+    };
+    if !state
+        .repo
+        .restore_session(&session)
+        .await
+        .map_err(internal_error)?
+    {
+        return Err(session_exists());
+    }
+    let session = state
+        .repo
+        .fetch_session(session_id)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(SessionRestore { backup, session }))
+}
+
+fn require_backups(state: &AppState) -> Result<(), (axum::http::StatusCode, String)> {
+    if state.backups.is_none() {
+        return Err((
+            axum::http::StatusCode::NOT_FOUND,
+            "Sauvegardes désactivées : BACKUP_URL n'est pas défini.".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// GET /api/admin/usage : consommation par jour et par modèle, lue dans `usage_daily`
+pub(crate) async fn daily_usage(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<Vec<DailyUsage>>, (axum::http::StatusCode, String)> {
+    caller.require_admin()?;
+    let usage = state
+        .repo
+        .daily_usage(query.from, query.to)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(usage))
+}
+
+// GET /api/admin/latency : temps de génération par modèle et réponses les plus lentes,
+// lus dans `message_latency`
+pub(crate) async fn latency_report(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(query): Query<LatencyQuery>,
+) -> Result<Json<LatencyReport>, (axum::http::StatusCode, String)> {
+    caller.require_admin()?;
+    let model = query.model.as_deref();
+    let models = state
+        .repo
+        .latency_stats(query.from, query.to, model)
+        .await
+        .map_err(internal_error)?;
+    let slowest = state
+        .repo
+        .slowest_messages(query.from, query.to, model, SLOWEST_MESSAGES)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(LatencyReport { models, slowest }))
+}
+
+// GET /api/admin/scrub-audit : requêtes dont des secrets ont été masqués avant envoi
+pub(crate) async fn scrub_audit(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(query): Query<ScrubAuditQuery>,
+) -> Result<Json<Vec<ScrubAuditEntry>>, (axum::http::StatusCode, String)> {
+    caller.require_admin()?;
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    let entries = state
+        .repo
+        .scrub_audit(limit)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(entries))
+}
+
+// GET /api/admin/queries : requêtes les plus lentes (`pg_stat_statements`) et plans des
+// lectures fréquentes
+pub(crate) async fn query_report(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(query): Query<QueryReportQuery>,
+) -> Result<Json<QueryReport>, (axum::http::StatusCode, String)> {
+    caller.require_admin()?;
+    let limit = query.limit.unwrap_or(20).clamp(1, 100);
+    let slow_queries = state
+        .repo
+        .slow_queries(limit)
+        .await
+        .map_err(internal_error)?;
+    let plans = state
+        .repo
+        .explain_hot_queries()
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(QueryReport {
+        slow_queries,
+        plans,
+    }))
+}
+
+// GET /api/admin/metrics : métriques des uploads au format texte de Prometheus
+pub(crate) async fn metrics_report(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<impl IntoResponse, (axum::http::StatusCode, String)> {
+    caller.require_admin()?;
+    Ok((
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        state.upload_metrics.render(),
+    ))
+}
+
+// GET /api/admin/maintenance : état du mode lecture seule
+pub(crate) async fn maintenance_status(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<Json<MaintenanceStatus>, (axum::http::StatusCode, String)> {
+    caller.require_admin()?;
+    Ok(Json(state.maintenance.status(&state.repo).await))
+}
+
+// PUT /api/admin/maintenance : active ou lève le mode lecture seule
+pub(crate) async fn set_maintenance(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(request): Json<MaintenanceRequest>,
+) -> Result<Json<MaintenanceStatus>, (axum::http::StatusCode, String)> {
+    caller.require_admin()?;
+    let status = state
+        .maintenance
+        .set(&state.repo, request)
+        .await
+        .map_err(internal_error)?;
+    if status.enabled {
+        eprintln!("Mode maintenance activé : écritures et générations refusées");
+    } else {
+        eprintln!("Mode maintenance levé");
+    }
+    Ok(Json(status))
+}
+
+// GET /api/me/features : fonctionnalités expérimentales ouvertes au client
+pub(crate) async fn get_features(features: Features) -> Json<ClientFeatures> {
+    Json(features.summary())
+}
+
+// GET /api/admin/feature-flags : drapeaux des fonctionnalités expérimentales
+pub(crate) async fn list_feature_flags(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<Json<Vec<FeatureFlag>>, (axum::http::StatusCode, String)> {
+    caller.require_admin()?;
+    Ok(Json(state.feature_flags.list()))
+}
+
+// PUT /api/admin/feature-flags/:name : crée ou remplace un drapeau, appliqué aussitôt sur
+// cette instance et à la prochaine relecture (`feature_flags`) sur les autres
+pub(crate) async fn put_feature_flag(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(name): Path<String>,
+    Json(request): Json<FeatureFlagRequest>,
+) -> Result<Json<FeatureFlag>, (axum::http::StatusCode, String)> {
+    caller.require_admin()?;
+    if name.is_empty()
+        || name.len() > 64
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+    {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            "Nom de drapeau invalide : lettres minuscules, chiffres, `_` ou `-`, 64 caractères au plus."
+                .to_string(),
+        ));
+    }
+    let rollout_percent = request.rollout_percent.unwrap_or(100);
+    if !(0..=100).contains(&rollout_percent) {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            "`rollout_percent` doit être compris entre 0 et 100.".to_string(),
+        ));
+    }
+    let mut clients: Vec<String> = request
+        .clients
+        .iter()
+        .map(|client| client.trim().to_string())
+        .filter(|client| !client.is_empty())
+        .collect();
+    clients.sort();
+    clients.dedup();
+    let description = request
+        .description
+        .as_deref()
+        .map(str::trim)
+        .filter(|description| !description.is_empty());
+    let flag = state
+        .repo
+        .upsert_feature_flag(
+            &name,
+            request.enabled,
+            rollout_percent,
+            &clients,
+            description,
+        )
+        .await
+        .map_err(internal_error)?;
+    state.feature_flags.put(flag.clone());
+    Ok(Json(flag))
+}
+
+pub(crate) async fn delete_feature_flag(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(name): Path<String>,
+) -> Result<axum::http::StatusCode, (axum::http::StatusCode, String)> {
+    caller.require_admin()?;
+    if !state
+        .repo
+        .delete_feature_flag(&name)
+        .await
+        .map_err(internal_error)?
+    {
+        return Err((
+            axum::http::StatusCode::NOT_FOUND,
+            "Drapeau introuvable.".to_string(),
+        ));
+    }
+    state.feature_flags.remove(&name);
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+// GET /api/admin/plugins : plugins WASM chargés de `PLUGIN_DIR` et fichiers refusés
+pub(crate) async fn list_plugins(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<Json<PluginReport>, (axum::http::StatusCode, String)> {
+    caller.require_admin()?;
+    Ok(Json(state.plugins.report()))
+}
+
+// POST /api/admin/plugins/reload : relit `PLUGIN_DIR` sans attendre la tâche `plugins`
+pub(crate) async fn reload_plugins(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<Json<PluginReport>, (axum::http::StatusCode, String)> {
+    caller.require_admin()?;
+    state.plugins.reload().await.map_err(internal_error)?;
+    Ok(Json(state.plugins.report()))
+}
+
+// GET /api/admin/provider-logs : requêtes envoyées aux providers et flux SSE reçus
+// (`PROVIDER_DEBUG_LOG`), d'un message (`message_id`) ou les plus récentes
+pub(crate) async fn provider_logs(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(query): Query<ProviderLogQuery>,
+) -> Result<Json<Vec<ProviderDebugLog>>, (axum::http::StatusCode, String)> {
+    caller.require_admin()?;
+    let limit = query.limit.unwrap_or(20).clamp(1, 200);
+    let logs = state
+        .repo
+        .debug_logs(query.message_id, limit)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(logs))
+}
@@ -0,0 +1,192 @@
+//! Points d'extension du flux des réponses : chaque hook enregistré voit passer le texte du
+//! modèle et peut le réécrire avant qu'il parte au client et soit enregistré. Le déploiement
+//! choisit les hooks intégrés (`STREAM_HOOKS`) ; un binaire qui embarque le backend peut
+//! ajouter les siens avec [`crate::AppState::add_stream_hook`].
+
+use std::sync::Arc;
+
+use futures::stream::{self, StreamExt};
+use regex::{Captures, Regex};
+
+use crate::providers::{AiModelChoice, CompletionStream, ProviderChunk};
+
+/// Réponse en cours de génération.
+#[derive(Clone, Copy, Debug)]
+pub struct HookContext {
+    pub model: AiModelChoice,
+}
+
+/// Middleware du flux de texte, instancié pour chaque réponse.
+pub trait StreamHook: Send + Sync {
+    fn start(&self, context: &HookContext) -> Box<dyn StreamFilter>;
+}
+
+/// Filtre d'une réponse. Le texte arrive par morceaux arbitraires : un filtre qui travaille
+/// sur des mots ou des lignes retient la fin incomplète jusqu'au morceau suivant.
+pub trait StreamFilter: Send {
+    /// Texte reçu ; renvoie ce qui peut déjà être émis.
+    fn push(&mut self, text: &str) -> String;
+
+    /// Fin du flux, terminé ou coupé : renvoie le texte encore retenu.
+    fn finish(&mut self) -> String {
+        String::new()
+    }
+}
+
+/// Hooks intégrés activés par `STREAM_HOOKS`, avec leurs réglages.
+#[derive(Clone, Debug, Default)]
+pub struct StreamHookSettings {
+    /// Hooks dans l'ordre d'application : `mask_words`, `rewrite_links`
+    pub hooks: Vec<String>,
+    /// Mots masqués par `mask_words` (`STREAM_MASKED_WORDS`), sans tenir compte de la casse
+    pub masked_words: Vec<String>,
+    /// Préfixes d'URL réécrits par `rewrite_links` (`STREAM_LINK_REWRITES`)
+    pub link_rewrites: Vec<(String, String)>,
+}
+
+/// Construit les hooks intégrés ; un nom inconnu ou un hook sans réglage est une erreur de
+/// configuration.
+pub(crate) fn builtin_hooks(
+    settings: &StreamHookSettings,
+) -> Result<Vec<Arc<dyn StreamHook>>, String> {
+    let mut hooks: Vec<Arc<dyn StreamHook>> = Vec::new();
+    for name in &settings.hooks {
+        match name.as_str() {
+            "mask_words" => {
+                if settings.masked_words.is_empty() {
+                    return Err("mask_words nécessite STREAM_MASKED_WORDS".to_string());
+                }
+                let words: Vec<String> = settings
+                    .masked_words
+                    .iter()
+                    .map(|word| regex::escape(word))
+                    .collect();
+                let regex = Regex::new(&format!(r"(?i)\b(?:{})\b", words.join("|")))
+                    .map_err(|err| format!("STREAM_MASKED_WORDS: {err}"))?;
+                hooks.push(Arc::new(MaskWords(regex)));
+            }
+            "rewrite_links" => {
+                if settings.link_rewrites.is_empty() {
+                    return Err("rewrite_links nécessite STREAM_LINK_REWRITES".to_string());
+                }
+                hooks.push(Arc::new(RewriteLinks(settings.link_rewrites.clone())));
+            }
+            other => return Err(format!("hook inconnu: {other} (mask_words, rewrite_links)")),
+        }
+    }
+    Ok(hooks)
+}
+
+/// Fait passer le texte de la réponse par les filtres des hooks, dans l'ordre. Le texte
+/// retenu est rendu avant une erreur du provider et à la fin du flux.
+pub(crate) fn apply(
+    hooks: &[Arc<dyn StreamHook>],
+    context: HookContext,
+    stream: CompletionStream,
+) -> CompletionStream {
+    if hooks.is_empty() {
+        return stream;
+    }
+    let mut filters: Vec<Box<dyn StreamFilter>> =
+        hooks.iter().map(|hook| hook.start(&context)).collect();
+    Box::pin(
+        stream
+            .map(Some)
+            .chain(stream::once(async { None }))
+            .flat_map(move |item| {
+                let mut chunks = Vec::new();
+                match item {
+                    Some(Ok(ProviderChunk::Text(text))) => {
+                        let text = filters
+                            .iter_mut()
+                            .fold(text, |text, filter| filter.push(&text));
+                        if !text.is_empty() {
+                            chunks.push(Ok(ProviderChunk::Text(text)));
+                        }
+                    }
+                    Some(Ok(chunk)) => chunks.push(Ok(chunk)),
+                    Some(Err(err)) => {
+                        chunks.extend(flush(&mut filters));
+                        chunks.push(Err(err));
+                    }
+                    None => chunks.extend(flush(&mut filters)),
+                }
+                stream::iter(chunks)
+            }),
+    )
+}
+
+/// Vide les filtres : ce que retenait chacun passe encore par les suivants.
+fn flush(filters: &mut [Box<dyn StreamFilter>]) -> Option<Result<ProviderChunk, String>> {
+    let mut text = String::new();
+    for filter in filters.iter_mut() {
+        text = filter.push(&text);
+        text.push_str(&filter.finish());
+    }
+    (!text.is_empty()).then_some(Ok(ProviderChunk::Text(text)))
+}
+
+/// Filtre qui retient la fin du texte après le dernier séparateur et transforme le reste.
+struct Buffered<F> {
+    pending: String,
+    is_separator: fn(char) -> bool,
+    transform: F,
+}
+
+impl<F: Fn(&str) -> String + Send> StreamFilter for Buffered<F> {
+    fn push(&mut self, text: &str) -> String {
+        self.pending.push_str(text);
+        let Some((index, separator)) = self
+            .pending
+            .char_indices()
+            .rfind(|(_, c)| (self.is_separator)(*c))
+        else {
+            return String::new();
+        };
+        let rest = self.pending.split_off(index + separator.len_utf8());
+        let ready = std::mem::replace(&mut self.pending, rest);
+        (self.transform)(&ready)
+    }
+
+    fn finish(&mut self) -> String {
+        (self.transform)(&std::mem::take(&mut self.pending))
+    }
+}
+
+/// `mask_words` : remplace chaque mot listé par autant d'astérisques.
+struct MaskWords(Regex);
+
+impl StreamHook for MaskWords {
+    fn start(&self, _context: &HookContext) -> Box<dyn StreamFilter> {
+        let regex = self.0.clone();
+        Box::new(Buffered {
+            pending: String::new(),
+            is_separator: |c| !c.is_alphanumeric(),
+            transform: move |text: &str| {
+                regex
+                    .replace_all(text, |caps: &Captures| "*".repeat(caps[0].chars().count()))
+                    .into_owned()
+            },
+        })
+    }
+}
+
+/// `rewrite_links` : remplace les préfixes d'URL configurés (lien interne vers son miroir
+/// public...).
+struct RewriteLinks(Vec<(String, String)>);
+
+impl StreamHook for RewriteLinks {
+    fn start(&self, _context: &HookContext) -> Box<dyn StreamFilter> {
+        let rewrites = self.0.clone();
+        Box::new(Buffered {
+            pending: String::new(),
+            // Une URL s'arrête au premier blanc ; Markdown la ferme par `)` ou `>`.
+            is_separator: |c| c.is_whitespace() || matches!(c, ')' | '>' | '"'),
+            transform: move |text: &str| {
+                rewrites
+                    .iter()
+                    .fold(text.to_string(), |text, (from, to)| text.replace(from, to))
+            },
+        })
+    }
+}
@@ -0,0 +1,100 @@
+//! Chronométrage des générations : premier token, flux complet du provider, requêtes à la
+//! base et traitement des pièces jointes. Envoyé dans l'évènement `usage` et enregistré par
+//! réponse dans `message_latency` (`GET /api/admin/latency`) pour trouver d'où vient une
+//! lenteur.
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    models::LatencyBreakdown,
+    providers::{AiModelChoice, ProviderChunk},
+};
+
+/// Temps cumulé des requêtes à la base d'une requête HTTP.
+#[derive(Debug, Default)]
+pub(crate) struct DbTimer {
+    micros: AtomicU64,
+}
+
+impl DbTimer {
+    /// Attend `query` en ajoutant sa durée au total.
+    pub(crate) async fn time<T>(&self, query: impl Future<Output = T>) -> T {
+        let started_at = Instant::now();
+        let result = query.await;
+        self.micros
+            .fetch_add(started_at.elapsed().as_micros() as u64, Ordering::Relaxed);
+        result
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        Duration::from_micros(self.micros.load(Ordering::Relaxed))
+    }
+}
+
+/// Chronomètre d'une génération, démarré juste avant l'appel au provider.
+pub(crate) struct LatencyClock {
+    started_at: Instant,
+    breakdown: LatencyBreakdown,
+}
+
+impl LatencyClock {
+    pub(crate) fn start() -> Self {
+        LatencyClock {
+            started_at: Instant::now(),
+            breakdown: LatencyBreakdown::default(),
+        }
+    }
+
+    /// Note l'arrivée du premier texte (ou du refus) et le temps passé sur les pièces jointes.
+    pub(crate) fn observe(&mut self, chunk: &ProviderChunk) {
+        match chunk {
+            ProviderChunk::Text(text) | ProviderChunk::Refusal(text) if !text.is_empty() => {
+                self.breakdown
+                    .first_token
+                    .get_or_insert_with(|| self.started_at.elapsed());
+            }
+            ProviderChunk::AttachmentTime(elapsed) => self.breakdown.attachments += *elapsed,
+            _ => {}
+        }
+    }
+
+    /// Fin du flux du provider, terminé ou coupé.
+    pub(crate) fn end_of_stream(&mut self) {
+        self.breakdown.generation = self.started_at.elapsed();
+    }
+
+    pub(crate) fn add_db(&mut self, elapsed: Duration) {
+        self.breakdown.db += elapsed;
+    }
+
+    /// Durée depuis l'appel au provider.
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    pub(crate) fn breakdown(&self) -> LatencyBreakdown {
+        self.breakdown
+    }
+}
+
+/// Enregistre le temps de génération d'une réponse ; un échec ne touche que les statistiques.
+pub(crate) async fn record(
+    state: &AppState,
+    message_id: Uuid,
+    model: AiModelChoice,
+    breakdown: &LatencyBreakdown,
+) {
+    if let Err(err) = state
+        .repo
+        .record_latency(message_id, model.model_id(), breakdown)
+        .await
+    {
+        eprintln!("Impossible d'enregistrer la latence du message {message_id}: {err}");
+    }
+}
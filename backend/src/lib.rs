@@ -0,0 +1,700 @@
+//! Backend du chat : l'application Axum complète, réutilisable par le binaire, les tests
+//! d'intégration ou d'autres points d'entrée via `build_router`.
+
+pub mod access;
+pub mod archive;
+pub mod cassette;
+pub mod config;
+pub mod grpc;
+pub mod hooks;
+pub mod maintenance;
+pub mod mock;
+pub mod models;
+pub mod notify;
+pub mod outbound;
+pub mod plugins;
+pub mod prompt;
+pub mod providers;
+pub mod repository;
+pub mod retention;
+pub mod scrub;
+pub mod seed;
+pub mod service;
+pub mod sse;
+pub mod urls;
+
+mod artifacts;
+mod backup;
+mod cache;
+mod calendar;
+mod crop;
+mod debug_log;
+mod events;
+mod export;
+mod features;
+mod frontend;
+mod handlers;
+mod latency;
+mod limits;
+mod maintenance_mode;
+mod media;
+mod metrics;
+mod patch;
+mod pdf;
+mod proxy;
+mod queue;
+mod routing;
+mod sanitize;
+mod scheduler;
+mod screenshot;
+mod smtp;
+mod storage;
+mod stream;
+mod tools;
+mod widget;
+
+use std::{path::Path, sync::Arc, time::Duration};
+
+use axum::{
+    Router,
+    extract::DefaultBodyLimit,
+    middleware,
+    routing::{delete, get, patch, post, put},
+};
+use ipnet::IpNet;
+use redis::aio::ConnectionManager;
+use tokio::sync::{broadcast, mpsc};
+use tower_http::{
+    cors::{Any, CorsLayer},
+    services::ServeDir,
+};
+
+use access::ModelPolicy;
+use archive::ArchiveSink;
+use backup::BackupStore;
+use cache::{AttachmentCache, ResponseCache, SessionCache};
+use cassette::Cassettes;
+use config::Config;
+use events::{AppEvent, relay_redis_events, spawn_redis_event_publisher};
+use features::FeatureFlags;
+use handlers::*;
+use hooks::{StreamHook, builtin_hooks};
+use limits::{
+    JSON_BODY_LIMIT, RateLimiter, UPLOAD_BODY_LIMIT, limit_completions, limit_widget_completions,
+    reject_oversize_body,
+};
+use maintenance_mode::{MaintenanceMode, reject_writes_in_maintenance};
+use metrics::{UploadMetrics, track_upload_failures};
+use mock::MockProvider;
+use models::{ConversationTemplate, ServiceTier};
+use notify::Notifier;
+use outbound::Outbound;
+use plugins::Plugins;
+use prompt::SystemPrompt;
+use providers::{AiModelChoice, ProviderRegistry};
+use queue::RateLimitQueue;
+use repository::{ChatRepository, connect_database, run_migrations};
+use retention::apply_retention;
+use scheduler::Scheduler;
+use scrub::Scrubber;
+use urls::PublicUrls;
+use widget::Widgets;
+
+// État partagé de l'application
+#[derive(Clone)]
+pub struct AppState {
+    repo: ChatRepository,
+    upload_dir: String,
+    /// URLs publiques des pièces jointes et des exports
+    urls: PublicUrls,
+    /// Proxies dont les en-têtes `X-Forwarded-*` sont crus (`TRUSTED_PROXIES`)
+    trusted_proxies: Arc<[IpNet]>,
+    export_dir: String,
+    export_link_ttl: Duration,
+    /// Passe de validation/réparation des blocs de code avant persistance
+    validate_code_blocks: bool,
+    events: broadcast::Sender<AppEvent>,
+    /// File vers Redis quand `REDIS_URL` est défini : les évènements passent alors par le
+    /// canal pub/sub partagé pour atteindre les abonnés de toutes les instances.
+    redis_events: Option<mpsc::UnboundedSender<AppEvent>>,
+    /// Cache de `POST /api/ai`, absent si `AI_RESPONSE_CACHE_TTL_SECS` n'est pas défini
+    ai_cache: Option<Arc<ResponseCache>>,
+    /// Limite de requêtes des endpoints de génération (`RATE_LIMIT_PER_MINUTE`)
+    rate_limiter: Option<Arc<RateLimiter>>,
+    /// File des générations streamées au-delà de la limite (`RATE_LIMIT_QUEUE`)
+    rate_limit_queue: Option<Arc<RateLimitQueue>>,
+    daily_token_quota: Option<i64>,
+    /// Contenu lu des pièces jointes, absent si `ATTACHMENT_CACHE_MAX_MB` vaut 0
+    attachment_cache: Option<Arc<AttachmentCache>>,
+    attachment_max_tokens: u64,
+    pdf_extract_timeout: Duration,
+    attachment_extraction_wait: Duration,
+    /// Regroupement des tokens streamés (`STREAM_COALESCE_MS`, `STREAM_COALESCE_CHARS`)
+    stream_coalesce_interval: Option<Duration>,
+    stream_coalesce_chars: usize,
+    /// Provider simulé utilisé à la place de Groq/OpenAI (`MOCK_PROVIDER=true`)
+    mock_provider: Option<Arc<MockProvider>>,
+    /// Providers externes désactivés (`LOCAL_ONLY`)
+    local_only: bool,
+    /// Requêtes sortantes hors providers, limitées aux hôtes locaux avec `LOCAL_ONLY`
+    outbound: Outbound,
+    /// Provider et identifiant chez lui de chaque modèle servi
+    providers: Arc<ProviderRegistry>,
+    /// Enregistrement/rejeu des réponses des providers (`PROVIDER_CASSETTE_MODE`)
+    cassettes: Option<Arc<Cassettes>>,
+    /// Journal des requêtes et réponses des providers (`PROVIDER_DEBUG_LOG`)
+    provider_debug_log: bool,
+    models: ModelPolicy,
+    title_model: AiModelChoice,
+    draft_model: AiModelChoice,
+    service_tier: ServiceTier,
+    admin_token: Option<String>,
+    /// Masquage des secrets avant envoi aux providers (`SCRUB_*`)
+    scrubber: Arc<Scrubber>,
+    /// Gabarit du prompt système (`SYSTEM_PROMPT_TEMPLATE_FILE`, `PROJECT_INSTRUCTIONS_FILE`)
+    system_prompt: Arc<SystemPrompt>,
+    /// Discussions prêtes à l'emploi (`CONVERSATION_TEMPLATES_FILE`)
+    templates: Arc<Vec<ConversationTemplate>>,
+    /// Widgets intégrables (`WIDGETS_FILE`) et leur décompte de requêtes
+    widgets: Arc<Widgets>,
+    /// Hooks appliqués au texte des réponses (`STREAM_HOOKS`, `add_stream_hook`)
+    stream_hooks: Arc<Vec<Arc<dyn StreamHook>>>,
+    /// Stockage des sauvegardes planifiées (`BACKUP_URL`)
+    backups: Option<Arc<BackupStore>>,
+    /// Service gRPC servi avec l'API (`GRPC_ENABLED`)
+    grpc_enabled: bool,
+    /// Export statique du frontend servi à la racine (`FRONTEND_DIR`)
+    frontend_dir: Option<String>,
+    /// Notifications de fin des longues générations (`NOTIFY_*`)
+    notifier: Option<Arc<Notifier>>,
+    /// Archivage des échanges pour la conformité (`ARCHIVE_*`)
+    archive: Option<Arc<ArchiveSink>>,
+    /// Métriques des uploads (`GET /api/admin/metrics`)
+    upload_metrics: Arc<UploadMetrics>,
+    /// Lecture seule activée par `PUT /api/admin/maintenance`, relue en base par chaque instance
+    maintenance: Arc<MaintenanceMode>,
+    /// Drapeaux des fonctionnalités expérimentales, relus de la base par `feature_flags`
+    feature_flags: Arc<FeatureFlags>,
+    /// Service de capture de pages web (`SCREENSHOT_SERVICE_URL`)
+    screenshot_service_url: Option<String>,
+    screenshot_timeout: Duration,
+    screenshot_allowed_hosts: Vec<String>,
+    /// Plugins WASM du dossier `PLUGIN_DIR`, relus par `plugins`
+    plugins: Arc<Plugins>,
+    /// Tâches de maintenance planifiées et état de leur dernière exécution
+    scheduler: Arc<Scheduler>,
+}
+
+/// Âge minimal d'un upload orphelin avant sa suppression planifiée (pièce jointe d'un
+/// message encore en cours de rédaction)
+const UPLOAD_GC_MIN_AGE: Duration = Duration::from_secs(24 * 3600);
+/// Intervalle de la suppression des archives d'export expirées
+const EXPORT_CLEANUP_INTERVAL: Duration = Duration::from_secs(3600);
+
+impl AppState {
+    /// Connecte PostgreSQL (et Redis si configuré), applique les migrations et prépare les
+    /// dossiers des uploads et des exports.
+    pub async fn new(config: &Config) -> Self {
+        storage::install_pdf_log_capture();
+        let pool = connect_database(config).await;
+
+        run_migrations(&pool)
+            .await
+            .expect("Impossible d'appliquer les migrations");
+
+        let events = broadcast::channel(256).0;
+        let redis = match &config.redis_url {
+            Some(redis_url) => {
+                let client = redis::Client::open(redis_url.as_str()).expect("REDIS_URL invalide");
+                let manager = ConnectionManager::new(client.clone())
+                    .await
+                    .expect("Impossible de se connecter à Redis");
+                tokio::spawn(relay_redis_events(client, events.clone()));
+                Some(manager)
+            }
+            None => None,
+        };
+
+        let mut repo = ChatRepository::new(pool, PublicUrls::from_config(config));
+        if let Some(ttl) = config.session_cache_ttl {
+            repo = repo.with_session_cache(Arc::new(SessionCache::new(
+                ttl,
+                config.session_cache_max_entries,
+                redis.clone(),
+            )));
+        }
+        if let Err(err) = repo.close_interrupted_generations().await {
+            eprintln!("Impossible de clôturer les générations interrompues: {err}");
+        }
+        if let Err(err) = repo.close_interrupted_extractions().await {
+            eprintln!("Impossible de clôturer les extractions interrompues: {err}");
+        }
+        if let Err(err) = repo.close_interrupted_exports().await {
+            eprintln!("Impossible de clôturer les exports interrompus: {err}");
+        }
+
+        tokio::fs::create_dir_all(&config.upload_dir)
+            .await
+            .expect("Impossible de créer le dossier des uploads");
+        tokio::fs::create_dir_all(&config.export_dir)
+            .await
+            .expect("Impossible de créer le dossier des exports");
+
+        let redis_events = redis
+            .clone()
+            .map(|manager| spawn_redis_event_publisher(manager, events.clone()));
+
+        let rate_limiter = config
+            .rate_limit_per_minute
+            .map(|limit| Arc::new(RateLimiter::new(limit, redis.clone())));
+        let rate_limit_queue = rate_limiter
+            .clone()
+            .zip(config.rate_limit_queue)
+            .map(|(limiter, capacity)| Arc::new(RateLimitQueue::new(limiter, capacity)));
+
+        let ai_cache = config.ai_cache_ttl.map(|ttl| {
+            Arc::new(ResponseCache::new(
+                ttl,
+                config.ai_cache_max_entries,
+                redis.clone(),
+            ))
+        });
+
+        if config.local_only && !config.mock_provider && config.ollama_base_url.is_none() {
+            eprintln!(
+                "LOCAL_ONLY : aucun provider local n'est configuré (OLLAMA_BASE_URL), les générations seront refusées."
+            );
+        }
+
+        let plugins = Arc::new(
+            Plugins::new(&config.plugins)
+                .unwrap_or_else(|err| panic!("Runtime des plugins indisponible: {err}")),
+        );
+        let stream_hooks = builtin_hooks(&config.stream_hooks)
+            .unwrap_or_else(|err| panic!("STREAM_HOOKS invalide: {err}"));
+
+        let outbound = Outbound::new(config.local_only);
+        let state = AppState {
+            repo,
+            upload_dir: config.upload_dir.clone(),
+            urls: PublicUrls::from_config(config),
+            trusted_proxies: config.trusted_proxies.clone().into(),
+            export_dir: config.export_dir.clone(),
+            export_link_ttl: config.export_link_ttl,
+            validate_code_blocks: config.validate_code_blocks,
+            events,
+            redis_events,
+            ai_cache,
+            rate_limiter,
+            rate_limit_queue,
+            daily_token_quota: config.daily_token_quota,
+            attachment_cache: (config.attachment_cache_bytes > 0)
+                .then(|| Arc::new(AttachmentCache::new(config.attachment_cache_bytes))),
+            attachment_max_tokens: config.attachment_max_tokens,
+            pdf_extract_timeout: config.pdf_extract_timeout,
+            attachment_extraction_wait: config.attachment_extraction_wait,
+            stream_coalesce_interval: config.stream_coalesce_interval,
+            stream_coalesce_chars: config.stream_coalesce_chars,
+            mock_provider: config
+                .mock_provider
+                .then(|| Arc::new(MockProvider::default())),
+            local_only: config.local_only,
+            outbound: outbound.clone(),
+            providers: Arc::new(ProviderRegistry::from_config(config)),
+            cassettes: config
+                .cassette_mode
+                .map(|mode| Arc::new(Cassettes::new(mode, &config.cassette_dir))),
+            provider_debug_log: config.provider_debug_log,
+            models: config.models.clone(),
+            title_model: config.title_model,
+            draft_model: config.draft_model,
+            service_tier: config.service_tier,
+            admin_token: config.admin_token.clone(),
+            scrubber: Arc::new(
+                Scrubber::new(&config.scrub)
+                    .unwrap_or_else(|err| panic!("Règles de masquage invalides: {err}")),
+            ),
+            system_prompt: Arc::new(
+                SystemPrompt::new(&config.system_prompt)
+                    .unwrap_or_else(|err| panic!("Prompt système mal configuré: {err}")),
+            ),
+            templates: Arc::new(config.templates.clone()),
+            widgets: Arc::new(Widgets::new(&config.widgets, redis.clone())),
+            stream_hooks: Arc::new(stream_hooks),
+            backups: config.backup_url.as_deref().map(|url| {
+                Arc::new(
+                    BackupStore::from_url(url)
+                        .unwrap_or_else(|err| panic!("BACKUP_URL invalide: {err}")),
+                )
+            }),
+            grpc_enabled: config.grpc_enabled,
+            frontend_dir: config.frontend_dir.clone(),
+            notifier: Notifier::from_settings(&config.notifications, &outbound)
+                .unwrap_or_else(|err| panic!("Notifications invalides: {err}"))
+                .map(Arc::new),
+            archive: ArchiveSink::from_settings(&config.archive, &outbound)
+                .unwrap_or_else(|err| panic!("ARCHIVE_URL invalide: {err}"))
+                .map(Arc::new),
+            upload_metrics: Arc::new(UploadMetrics::default()),
+            maintenance: Arc::new(MaintenanceMode::default()),
+            feature_flags: Arc::new(FeatureFlags::default()),
+            screenshot_service_url: config.screenshot_service_url.clone(),
+            screenshot_timeout: config.screenshot_timeout,
+            screenshot_allowed_hosts: config.screenshot_allowed_hosts.clone(),
+            plugins,
+            scheduler: Arc::new(Scheduler::default()),
+        };
+        if let Err(err) = state.feature_flags.reload(&state.repo).await {
+            eprintln!("Impossible de lire les drapeaux de fonctionnalités: {err}");
+        }
+        if let Err(err) = state.plugins.reload().await {
+            eprintln!("Impossible de charger les plugins: {err}");
+        }
+        state.start_jobs(config);
+        state
+    }
+
+    /// Lance les tâches de maintenance planifiées (`GET /api/admin/jobs`).
+    fn start_jobs(&self, config: &Config) {
+        if config.retention.is_enabled() {
+            let policy = config.retention;
+            self.scheduler.spawn(
+                self,
+                "retention",
+                policy.interval,
+                move |state| async move {
+                    apply_retention(&state.repo, &policy)
+                        .await
+                        .map(|report| report.to_string())
+                        .map_err(|err| err.to_string())
+                },
+            );
+        }
+        if let Some(interval) = config.upload_gc_interval {
+            self.scheduler
+                .spawn(self, "upload_gc", interval, |state| async move {
+                    let orphans = maintenance::purge_orphan_uploads(
+                        &state.repo,
+                        &state.upload_dir,
+                        UPLOAD_GC_MIN_AGE,
+                        false,
+                    )
+                    .await?;
+                    Ok(format!(
+                        "{} fichiers orphelins supprimés",
+                        orphans.files.len()
+                    ))
+                });
+        }
+        // Reprend la veille du dernier jour agrégé : la journée en cours lors de la dernière
+        // exécution n'était pas terminée.
+        self.scheduler.spawn(
+            self,
+            "usage_rollup",
+            config.usage_rollup_interval,
+            |state| async move {
+                let last_day = state
+                    .repo
+                    .last_usage_rollup_day()
+                    .await
+                    .map_err(|err| err.to_string())?;
+                let since = last_day.and_then(|day| day.pred_opt());
+                let rows = state
+                    .repo
+                    .rollup_usage(since)
+                    .await
+                    .map_err(|err| err.to_string())?;
+                Ok(format!("{rows} lignes de consommation recalculées"))
+            },
+        );
+        if self.backups.is_some() {
+            self.scheduler
+                .spawn(self, "backup", config.backup_interval, |state| async move {
+                    let backup = backup::run_backup(&state).await?;
+                    Ok(format!(
+                        "{} sauvegardée ({} sessions)",
+                        backup.name,
+                        backup.sessions.unwrap_or_default()
+                    ))
+                });
+        }
+        if self.archive.is_some() {
+            self.scheduler.spawn(
+                self,
+                "archive_retry",
+                config.archive.retry_interval,
+                |state| async move {
+                    let Some(archive) = &state.archive else {
+                        return Ok(String::new());
+                    };
+                    let (delivered, pending) = archive.retry_dead_letters().await?;
+                    Ok(format!(
+                        "{delivered} échanges archivés, {pending} encore en attente"
+                    ))
+                },
+            );
+        }
+        self.scheduler.spawn(
+            self,
+            "feature_flags",
+            config.feature_flags_refresh,
+            |state| async move {
+                let count = state
+                    .feature_flags
+                    .reload(&state.repo)
+                    .await
+                    .map_err(|err| err.to_string())?;
+                Ok(format!("{count} drapeaux de fonctionnalités chargés"))
+            },
+        );
+        if self.plugins.is_enabled() {
+            self.scheduler.spawn(
+                self,
+                "plugins",
+                config.plugins.reload_interval,
+                |state| async move {
+                    let count = state.plugins.reload().await?;
+                    Ok(format!("{count} plugins chargés"))
+                },
+            );
+        }
+        self.scheduler.spawn(
+            self,
+            "export_cleanup",
+            EXPORT_CLEANUP_INTERVAL,
+            |state| async move {
+                let purged =
+                    maintenance::purge_expired_exports(&state.repo, &state.export_dir).await?;
+                Ok(format!("{purged} archives d'export supprimées"))
+            },
+        );
+    }
+
+    /// Provider simulé, pour scripter ses réponses depuis les tests.
+    pub fn mock_provider(&self) -> Option<&MockProvider> {
+        self.mock_provider.as_deref()
+    }
+
+    /// Ajoute un hook après ceux de `STREAM_HOOKS`, avant de servir le routeur.
+    pub fn add_stream_hook(&mut self, hook: impl StreamHook + 'static) {
+        Arc::make_mut(&mut self.stream_hooks).push(Arc::new(hook));
+    }
+
+    /// Client HTTP d'une requête sortante hors providers ; refuse un hôte externe en mode
+    /// `LOCAL_ONLY`.
+    async fn outbound_client(&self, url: &str) -> Result<&reqwest::Client, String> {
+        self.outbound.client(url).await
+    }
+
+    /// Modèle servi par l'API Responses d'OpenAI (`RESPONSES_API_MODELS`)
+    fn uses_responses_api(&self, model: AiModelChoice) -> bool {
+        self.providers
+            .route(model)
+            .is_some_and(|route| route.provider.stores_responses())
+    }
+
+    /// Diffuse un évènement ; l'absence d'abonnés n'est pas une erreur.
+    fn publish(&self, event: AppEvent) {
+        match &self.redis_events {
+            Some(redis_events) => {
+                if let Err(err) = redis_events.send(event) {
+                    let _ = self.events.send(err.0);
+                }
+            }
+            None => {
+                let _ = self.events.send(event);
+            }
+        }
+    }
+}
+
+/// Construit l'état de l'application à partir de la configuration puis les routes.
+pub async fn build_router(config: &Config) -> Router {
+    router(AppState::new(config).await)
+}
+
+/// Routes de l'API, CORS, fichiers uploadés et limites de taille des requêtes.
+pub fn router(state: AppState) -> Router {
+    // CORS
+    let cors = CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods(Any)
+        .allow_headers(Any)
+        // En-têtes `X-RateLimit-*` / `X-Quota-*` lisibles par le frontend
+        .expose_headers(Any);
+
+    // Endpoints qui appellent un modèle : limite de requêtes et quota de tokens
+    let completions = Router::new()
+        .route("/api/chat/sessions/:id/messages", post(append_chat_message))
+        .route(
+            "/api/chat/sessions/:id/messages/stream",
+            post(append_chat_message_stream),
+        )
+        .route(
+            "/api/chat/sessions/:id/regenerate",
+            post(regenerate_message),
+        )
+        .route(
+            "/api/chat/sessions/:id/regenerate/stream",
+            post(regenerate_message_stream),
+        )
+        .route(
+            "/api/chat/sessions/:id/continue/stream",
+            post(continue_message_stream),
+        )
+        .route("/api/ai", post(ai_handler)) // 👈 route générique IA
+        .route("/api/ai/stream", post(ai_stream_handler))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            limit_completions,
+        ));
+
+    // Widgets intégrables : leur jeton et leur propre limite de requêtes
+    let widgets = Router::new()
+        .route("/api/widget/chat/stream", post(widget_chat_stream))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            limit_widget_completions,
+        ))
+        .route("/api/widget", get(widget_info));
+
+    // Seuls les uploads acceptent de gros corps
+    let uploads = Router::new()
+        .route("/api/uploads", post(upload_file))
+        .route("/api/uploads/text", post(upload_text));
+    let uploads = with_body_limit(uploads, UPLOAD_BODY_LIMIT).route_layer(
+        middleware::from_fn_with_state(state.clone(), track_upload_failures),
+    );
+
+    let upload_dir = state.upload_dir.clone();
+    let api = Router::new()
+        .route("/health", get(health_check))
+        .route("/api/messages", get(list_messages).post(create_message))
+        .route(
+            "/api/messages/:id",
+            patch(update_message).delete(delete_message),
+        )
+        .route(
+            "/api/chat/sessions",
+            get(list_chat_sessions).post(create_chat_session),
+        )
+        .route(
+            "/api/chat/sessions/from-template/:id",
+            post(create_session_from_template),
+        )
+        .route("/api/chat/sessions/bulk", post(bulk_update_sessions))
+        .route("/api/chat/sessions/:id", delete(delete_chat_session))
+        .route("/api/chat/sessions/:id/archive", post(archive_chat_session))
+        .route("/api/chat/sessions/:id/draft", put(save_chat_draft))
+        .route(
+            "/api/chat/sessions/:id/messages/:message_id/context",
+            put(set_message_context),
+        )
+        .route(
+            "/api/chat/sessions/:id/messages/:message_id/bookmark",
+            put(set_message_bookmark),
+        )
+        .route(
+            "/api/chat/sessions/:id/messages/:message_id/reaction",
+            put(set_message_reaction),
+        )
+        .route(
+            "/api/chat/sessions/:id/messages/:message_id/candidate",
+            put(select_message_candidate),
+        )
+        .route("/api/bookmarks", get(list_bookmarks))
+        .route("/api/chat/messages/:id/artifacts", get(list_artifacts))
+        .route(
+            "/api/chat/messages/:id/artifacts/download",
+            get(download_artifacts),
+        )
+        .route(
+            "/api/chat/messages/:id/artifacts/:index",
+            get(download_artifact),
+        )
+        .route(
+            "/api/chat/sessions/:id/estimate",
+            post(estimate_chat_message),
+        )
+        .route("/api/presets", get(list_presets).post(create_preset))
+        .route("/api/templates", get(list_templates))
+        .route("/api/presets/:id", put(update_preset).delete(delete_preset))
+        .route(
+            "/api/me/preferences",
+            get(get_preferences).put(put_preferences),
+        )
+        .route("/api/me/features", get(get_features))
+        .route("/api/me/calendar", get(get_calendar))
+        .route("/api/me/calendar/token", post(rotate_calendar_token))
+        .route("/api/me/calendar/items/:id", delete(delete_calendar_item))
+        .route("/api/me/calendar.ics", get(calendar_feed))
+        .route("/api/models", get(list_models))
+        .route("/api/openapi.yaml", get(openapi_spec))
+        .route("/api/events", get(events_stream))
+        .route("/api/export", post(start_export))
+        .route("/api/export/:id", get(get_export))
+        .route("/api/export/:id/download", get(download_export))
+        .route("/api/admin/jobs", get(list_jobs))
+        .route("/api/admin/system-prompt", get(system_prompt_preview))
+        .route("/api/admin/backups", get(list_backups).post(create_backup))
+        .route("/api/admin/backups/:name/restore", post(restore_backup))
+        .route("/api/admin/restore/session/:id", post(restore_session))
+        .route("/api/admin/usage", get(daily_usage))
+        .route("/api/admin/latency", get(latency_report))
+        .route("/api/admin/scrub-audit", get(scrub_audit))
+        .route("/api/admin/queries", get(query_report))
+        .route("/api/admin/provider-logs", get(provider_logs))
+        .route("/api/admin/metrics", get(metrics_report))
+        .route(
+            "/api/admin/maintenance",
+            get(maintenance_status).put(set_maintenance),
+        )
+        .route("/api/admin/feature-flags", get(list_feature_flags))
+        .route(
+            "/api/admin/feature-flags/:name",
+            put(put_feature_flag).delete(delete_feature_flag),
+        )
+        .route("/api/admin/plugins", get(list_plugins))
+        .route("/api/admin/plugins/reload", post(reload_plugins))
+        .merge(completions)
+        .merge(widgets);
+
+    // Service gRPC sur le même port : HTTP/2 en clair, routé par `/carlgpt.v1.Chat/*`
+    let grpc = if state.grpc_enabled {
+        grpc::routes(state.clone())
+    } else {
+        Router::new()
+    };
+    // Frontend en dernier : il ne répond qu'aux chemins que l'API ne connaît pas
+    let frontend = state
+        .frontend_dir
+        .as_deref()
+        .map(|dir| frontend::routes(Path::new(dir)))
+        .unwrap_or_default();
+
+    // Le mode maintenance couvre l'API et le gRPC ; les fichiers et le frontend ne sont que lus
+    let maintenance = middleware::from_fn_with_state(state.clone(), reject_writes_in_maintenance);
+    with_body_limit(api, JSON_BODY_LIMIT)
+        .merge(uploads)
+        .with_state(state)
+        .merge(grpc)
+        .layer(maintenance)
+        .nest_service("/uploads", ServeDir::new(upload_dir))
+        .merge(frontend)
+        .layer(cors)
+}
+
+/// Applique une taille maximale de corps aux routes déjà déclarées de `routes` : refus en
+/// 413 d'après `Content-Length`, et corps coupé à la même taille s'il arrive sans longueur.
+fn with_body_limit(routes: Router<AppState>, limit: usize) -> Router<AppState> {
+    routes
+        .route_layer(DefaultBodyLimit::max(limit))
+        .route_layer(middleware::from_fn_with_state(limit, reject_oversize_body))
+}
+
+fn internal_error<E: std::fmt::Display>(err: E) -> (axum::http::StatusCode, String) {
+    (
+        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Internal server error: {err}"),
+    )
+}
@@ -0,0 +1,386 @@
+//! Limites des endpoints de génération : nombre de requêtes par minute et par client
+//! (`RATE_LIMIT_PER_MINUTE`) et quota journalier de tokens du déploiement
+//! (`DAILY_TOKEN_QUOTA`). Les réponses portent les en-têtes `X-RateLimit-*` et `X-Quota-*`
+//! pour que le client affiche ce qui reste et ralentisse avant d'être refusé.
+//!
+//! Avec `RATE_LIMIT_QUEUE`, une génération streamée au-delà de la limite attend son tour au
+//! lieu d'être refusée (voir `queue`).
+//!
+//! Limite aussi la taille du corps des requêtes, endpoint par endpoint.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{Days, Utc};
+use redis::aio::ConnectionManager;
+
+use crate::{
+    AppState,
+    access::Caller,
+    handlers::MAX_UPLOAD_SIZE,
+    internal_error,
+    proxy::ClientOrigin,
+    queue::{QueueTicket, queued_response},
+    widget::{RegisteredWidget, WidgetAccess},
+};
+
+/// Taille maximale du corps des endpoints JSON (messages, sessions, presets…) : les longs
+/// textes passent par `/api/uploads/text`
+pub(crate) const JSON_BODY_LIMIT: usize = 1024 * 1024; // 1 Mo
+/// Taille maximale du corps des uploads : le fichier et l'enveloppe multipart ou JSON
+pub(crate) const UPLOAD_BODY_LIMIT: usize = MAX_UPLOAD_SIZE + 1024 * 1024;
+
+const REDIS_RATE_LIMIT_PREFIX: &str = "carlgpt:rate-limit:";
+/// Fenêtre du décompte des requêtes
+pub(crate) const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Compteur de requêtes par client sur une fenêtre fixe d'une minute, en mémoire ou partagé
+/// entre instances via Redis.
+pub(crate) struct RateLimiter {
+    limit: u32,
+    windows: Mutex<HashMap<String, Window>>,
+    redis: Option<ConnectionManager>,
+}
+
+struct Window {
+    started_at: Instant,
+    count: u32,
+}
+
+/// Décompte d'un client après sa requête.
+pub(crate) struct RateLimitState {
+    limit: u32,
+    count: u32,
+    pub(crate) reset_after: Duration,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: u32, redis: Option<ConnectionManager>) -> Self {
+        RateLimiter {
+            limit,
+            windows: Mutex::new(HashMap::new()),
+            redis,
+        }
+    }
+
+    pub(crate) fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    /// Compte une requête du client `key`. Si Redis est indisponible, la requête n'est pas
+    /// bloquée pour autant.
+    pub(crate) async fn hit(&self, key: &str) -> RateLimitState {
+        if let Some(redis) = &self.redis {
+            let mut conn = redis.clone();
+            let redis_key = format!("{REDIS_RATE_LIMIT_PREFIX}{key}");
+            // Fenêtre créée avec son expiration (`SET NX EX`) puis incrémentée, dans une même
+            // transaction : une clé sans expiration bloquerait le client pour toujours.
+            let counted: redis::RedisResult<(u32, i64)> = redis::pipe()
+                .atomic()
+                .cmd("SET")
+                .arg(&redis_key)
+                .arg(0)
+                .arg("NX")
+                .arg("EX")
+                .arg(RATE_LIMIT_WINDOW.as_secs())
+                .ignore()
+                .incr(&redis_key, 1)
+                .ttl(&redis_key)
+                .query_async(&mut conn)
+                .await;
+            let (count, ttl) = counted.unwrap_or_else(|err| {
+                eprintln!("Décompte Redis des requêtes impossible: {err}");
+                (0, RATE_LIMIT_WINDOW.as_secs() as i64)
+            });
+            return RateLimitState {
+                limit: self.limit,
+                count,
+                reset_after: Duration::from_secs(ttl.max(0) as u64),
+            };
+        }
+
+        let mut windows = self.windows.lock().unwrap_or_else(|err| err.into_inner());
+        let now = Instant::now();
+        windows.retain(|_, window| now.duration_since(window.started_at) < RATE_LIMIT_WINDOW);
+        let window = windows.entry(key.to_string()).or_insert(Window {
+            started_at: now,
+            count: 0,
+        });
+        window.count += 1;
+        RateLimitState {
+            limit: self.limit,
+            count: window.count,
+            reset_after: RATE_LIMIT_WINDOW.saturating_sub(now.duration_since(window.started_at)),
+        }
+    }
+}
+
+impl RateLimitState {
+    pub(crate) fn exceeded(&self) -> bool {
+        self.count > self.limit
+    }
+
+    fn add_headers(&self, headers: &mut HeaderMap) {
+        set_header(headers, "x-ratelimit-limit", self.limit.into());
+        set_header(
+            headers,
+            "x-ratelimit-remaining",
+            self.limit.saturating_sub(self.count).into(),
+        );
+        set_header(headers, "x-ratelimit-reset", seconds(self.reset_after));
+    }
+}
+
+/// Tokens consommés aujourd'hui (UTC) par tout le déploiement, face au quota.
+struct QuotaState {
+    limit: i64,
+    used: i64,
+    reset_after: Duration,
+}
+
+impl QuotaState {
+    fn exceeded(&self) -> bool {
+        self.used >= self.limit
+    }
+
+    fn add_headers(&self, headers: &mut HeaderMap) {
+        set_header(headers, "x-quota-limit", self.limit as u64);
+        set_header(
+            headers,
+            "x-quota-remaining",
+            (self.limit - self.used).max(0) as u64,
+        );
+        set_header(headers, "x-quota-reset", seconds(self.reset_after));
+    }
+}
+
+/// Décompte d'une requête de génération : limite de requêtes du client et quota du jour.
+pub(crate) struct CompletionLimits {
+    rate: Option<RateLimitState>,
+    quota: Option<QuotaState>,
+}
+
+impl CompletionLimits {
+    /// Compte la requête ; les admins ne sont pas limités.
+    pub(crate) async fn check(
+        state: &AppState,
+        caller: Caller,
+        origin: &ClientOrigin,
+    ) -> Result<Self, (StatusCode, String)> {
+        if caller.admin {
+            return Ok(CompletionLimits {
+                rate: None,
+                quota: None,
+            });
+        }
+        let rate = match &state.rate_limiter {
+            Some(limiter) => Some(limiter.hit(&client_key(origin)).await),
+            None => None,
+        };
+        let quota = match state.daily_token_quota {
+            Some(limit) => Some(quota_state(state, limit).await?),
+            None => None,
+        };
+        Ok(CompletionLimits { rate, quota })
+    }
+
+    /// Compte la requête d'un visiteur du widget : limite propre au widget, quota du jour.
+    pub(crate) async fn check_widget(
+        state: &AppState,
+        widget: &RegisteredWidget,
+        origin: &ClientOrigin,
+    ) -> Result<Self, (StatusCode, String)> {
+        let key = format!("widget:{}:{}", widget.widget.id, client_key(origin));
+        let rate = Some(widget.limiter.hit(&key).await);
+        let quota = match state.daily_token_quota {
+            Some(limit) => Some(quota_state(state, limit).await?),
+            None => None,
+        };
+        Ok(CompletionLimits { rate, quota })
+    }
+
+    /// Délai avant de réessayer et motif, si la requête doit être refusée.
+    pub(crate) fn refusal(&self) -> Option<(Duration, String)> {
+        if let Some(rate) = self.rate.as_ref().filter(|rate| rate.exceeded()) {
+            return Some((
+                rate.reset_after,
+                format!(
+                    "Trop de requêtes : {} par minute au plus. Réessayez dans {} s.",
+                    rate.limit,
+                    seconds(rate.reset_after)
+                ),
+            ));
+        }
+        self.quota
+            .as_ref()
+            .filter(|quota| quota.exceeded())
+            .map(|quota| {
+                (
+                    quota.reset_after,
+                    "Le quota journalier de tokens est épuisé. Il sera rétabli à minuit (UTC)."
+                        .to_string(),
+                )
+            })
+    }
+
+    /// Place dans la file du client si seule la limite de requêtes refuse cette génération
+    /// streamée et que `RATE_LIMIT_QUEUE` le permet.
+    fn enqueue(&self, state: &AppState, origin: &ClientOrigin, path: &str) -> Option<QueueTicket> {
+        let rate = self.rate.as_ref().filter(|rate| rate.exceeded())?;
+        if !path.ends_with("/stream") || self.quota.as_ref().is_some_and(QuotaState::exceeded) {
+            return None;
+        }
+        state
+            .rate_limit_queue
+            .as_ref()?
+            .enqueue(client_key(origin), rate.reset_after)
+    }
+
+    /// Refuse la requête en 429 (avec `Retry-After`) ou la laisse passer, et ajoute les
+    /// en-têtes de décompte à la réponse.
+    async fn apply(self, request: Request, next: Next) -> Response {
+        let mut response = match self.refusal() {
+            Some((retry_after, message)) => {
+                let mut response = (StatusCode::TOO_MANY_REQUESTS, message).into_response();
+                set_header(response.headers_mut(), "retry-after", seconds(retry_after));
+                response
+            }
+            None => next.run(request).await,
+        };
+        self.add_headers(response.headers_mut());
+        response
+    }
+
+    fn add_headers(&self, headers: &mut HeaderMap) {
+        if let Some(rate) = &self.rate {
+            rate.add_headers(headers);
+        }
+        if let Some(quota) = &self.quota {
+            quota.add_headers(headers);
+        }
+    }
+}
+
+/// Middleware des endpoints de génération : refuse en 429 (avec `Retry-After`) un client
+/// qui dépasse `RATE_LIMIT_PER_MINUTE` ou un déploiement qui a épuisé `DAILY_TOKEN_QUOTA`,
+/// et ajoute les en-têtes de décompte à toutes les réponses. Les admins ne sont pas limités.
+/// Avec `RATE_LIMIT_QUEUE`, une génération streamée au-delà de la limite est mise en file.
+pub(crate) async fn limit_completions(
+    State(state): State<AppState>,
+    caller: Caller,
+    origin: ClientOrigin,
+    request: Request,
+    next: Next,
+) -> Response {
+    match CompletionLimits::check(&state, caller, &origin).await {
+        Ok(limits) => match limits.enqueue(&state, &origin, request.uri().path()) {
+            Some(ticket) => {
+                let mut response = queued_response(ticket, request, next);
+                limits.add_headers(response.headers_mut());
+                response
+            }
+            None => limits.apply(request, next).await,
+        },
+        Err(err) => err.into_response(),
+    }
+}
+
+/// Comme `limit_completions` pour `/api/widget/*`, avec la limite du widget : les visiteurs
+/// d'un site ne consomment pas celle des utilisateurs de l'instance.
+pub(crate) async fn limit_widget_completions(
+    State(state): State<AppState>,
+    WidgetAccess(widget): WidgetAccess,
+    origin: ClientOrigin,
+    request: Request,
+    next: Next,
+) -> Response {
+    match CompletionLimits::check_widget(&state, &widget, &origin).await {
+        Ok(limits) => limits.apply(request, next).await,
+        Err(err) => err.into_response(),
+    }
+}
+
+async fn quota_state(state: &AppState, limit: i64) -> Result<QuotaState, (StatusCode, String)> {
+    let today = Utc::now().date_naive();
+    let used = state
+        .repo
+        .tokens_used_on(today)
+        .await
+        .map_err(internal_error)?;
+    let midnight = today
+        .checked_add_days(Days::new(1))
+        .and_then(|day| day.and_hms_opt(0, 0, 0))
+        .map(|midnight| midnight.and_utc());
+    let reset_after = midnight
+        .and_then(|midnight| (midnight - Utc::now()).to_std().ok())
+        .unwrap_or_default();
+    Ok(QuotaState {
+        limit,
+        used,
+        reset_after,
+    })
+}
+
+/// Refuse en 413, avant de lire le corps, une requête dont le `Content-Length` dépasse
+/// `limit`. Un corps envoyé sans longueur est coupé à la même limite par `DefaultBodyLimit`.
+pub(crate) async fn reject_oversize_body(
+    State(limit): State<usize>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let length = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    match length {
+        Some(length) if length > limit as u64 => (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!(
+                "Requête trop volumineuse : {} pour {} au plus sur cet endpoint.",
+                size_label(length),
+                size_label(limit as u64)
+            ),
+        )
+            .into_response(),
+        _ => next.run(request).await,
+    }
+}
+
+/// Taille lisible, en Ko ou Mo arrondis au supérieur.
+fn size_label(bytes: u64) -> String {
+    const KB: u64 = 1024;
+    if bytes < KB * KB {
+        format!("{} Ko", bytes.div_ceil(KB))
+    } else {
+        format!("{} Mo", bytes.div_ceil(KB * KB))
+    }
+}
+
+/// Client d'une requête : son adresse IP (derrière un proxy de confiance, celle de
+/// `X-Forwarded-For`) quand le serveur la connaît, sinon un compteur commun (intégrations
+/// sans `ConnectInfo`).
+fn client_key(origin: &ClientOrigin) -> String {
+    origin
+        .ip
+        .map(|ip| ip.to_string())
+        .unwrap_or_else(|| "local".to_string())
+}
+
+/// Secondes restantes, arrondies au supérieur pour ne pas inviter à réessayer trop tôt.
+fn seconds(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+fn set_header(headers: &mut HeaderMap, name: &'static str, value: u64) {
+    headers.insert(HeaderName::from_static(name), HeaderValue::from(value));
+}
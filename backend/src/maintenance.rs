@@ -0,0 +1,80 @@
+//! Nettoyage des fichiers sur disque : uploads qui ne sont plus référencés et archives
+//! d'export expirées. Utilisé par les tâches planifiées et par `carlgpt-admin`.
+
+use std::{
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use crate::repository::ChatRepository;
+
+/// Fichiers uploadés orphelins trouvés (et supprimés, hors simulation).
+#[derive(Debug, Default)]
+pub struct OrphanUploads {
+    pub files: Vec<String>,
+    pub bytes: u64,
+}
+
+/// Supprime les fichiers du dossier des uploads qui ne sont plus référencés par une pièce
+/// jointe ou un brouillon, en ignorant ceux de moins de `min_age` (upload en cours de
+/// rédaction). Avec `dry_run`, les liste sans les supprimer.
+pub async fn purge_orphan_uploads(
+    repo: &ChatRepository,
+    upload_dir: &str,
+    min_age: Duration,
+    dry_run: bool,
+) -> Result<OrphanUploads, String> {
+    let referenced = repo
+        .referenced_storage_keys()
+        .await
+        .map_err(|err| err.to_string())?;
+    let cutoff = SystemTime::now() - min_age;
+
+    let entries = std::fs::read_dir(upload_dir)
+        .map_err(|err| format!("Dossier des uploads illisible ({upload_dir}): {err}"))?;
+    let mut orphans = OrphanUploads::default();
+    for entry in entries.flatten() {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let recent = metadata.modified().is_ok_and(|modified| modified > cutoff);
+        if !metadata.is_file() || recent || referenced.contains(&name) {
+            continue;
+        }
+        if !dry_run {
+            if let Err(err) = std::fs::remove_file(entry.path()) {
+                eprintln!("Impossible de supprimer {name}: {err}");
+                continue;
+            }
+            if let Err(err) = repo.delete_extraction(&name).await {
+                eprintln!("Impossible de supprimer l'extraction de {name}: {err}");
+            }
+        }
+        orphans.bytes += metadata.len();
+        orphans.files.push(name);
+    }
+    Ok(orphans)
+}
+
+/// Supprime les archives des exports dont le lien a expiré et renvoie leur nombre.
+pub(crate) async fn purge_expired_exports(
+    repo: &ChatRepository,
+    export_dir: &str,
+) -> Result<usize, String> {
+    let expired = repo
+        .expired_export_files()
+        .await
+        .map_err(|err| err.to_string())?;
+    for (export_id, file_name) in &expired {
+        match tokio::fs::remove_file(Path::new(export_dir).join(file_name)).await {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(format!("Impossible de supprimer {file_name}: {err}")),
+        }
+        repo.clear_export_file(*export_id)
+            .await
+            .map_err(|err| err.to_string())?;
+    }
+    Ok(expired.len())
+}
@@ -0,0 +1,159 @@
+//! Mode maintenance (`PUT /api/admin/maintenance`) : l'API passe en lecture seule le temps
+//! d'une migration de la base ou d'une panne d'un provider, sans arrêter le processus. Les
+//! lectures restent servies ; les écritures et les générations sont refusées en 503 avec un
+//! message pour l'utilisateur. L'état est enregistré en base, partagé par toutes les
+//! instances et conservé au redémarrage ; chaque instance le garde en mémoire quelques
+//! secondes pour ne pas interroger la base à chaque requête.
+
+use std::{
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Request, State},
+    http::{Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{AppState, repository::ChatRepository};
+
+/// Message renvoyé avec les 503 quand l'administrateur n'en donne pas
+const DEFAULT_MESSAGE: &str = "CarlGPT est en maintenance : les discussions restent consultables, \
+     mais les modifications et les nouvelles réponses sont suspendues quelques minutes.";
+/// Durée pendant laquelle une instance se fie à l'état lu en base
+const CACHE_TTL: Duration = Duration::from_secs(2);
+/// Délai suggéré aux clients avant de réessayer, en secondes (`Retry-After`)
+const RETRY_AFTER_SECS: &str = "60";
+/// Méthodes gRPC qui écrivent dans la base ou lancent une génération
+const GRPC_WRITES: &[&str] = &[
+    "/carlgpt.v1.Chat/CreateSession",
+    "/carlgpt.v1.Chat/DeleteSession",
+    "/carlgpt.v1.Chat/SendMessage",
+];
+
+/// État du mode maintenance (`GET /api/admin/maintenance`).
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct MaintenanceStatus {
+    pub enabled: bool,
+    /// Message renvoyé avec les refus
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<DateTime<Utc>>,
+}
+
+/// Corps de `PUT /api/admin/maintenance`.
+#[derive(Deserialize)]
+pub struct MaintenanceRequest {
+    pub enabled: bool,
+    /// Message pour les utilisateurs ; un texte générique par défaut
+    pub message: Option<String>,
+}
+
+/// Dernier état lu en base, avec l'instant de la lecture.
+#[derive(Default)]
+pub(crate) struct MaintenanceMode {
+    cached: RwLock<Option<(Instant, MaintenanceStatus)>>,
+}
+
+impl MaintenanceMode {
+    /// État du mode, relu en base au-delà de `CACHE_TTL`. Si la base ne répond pas, le
+    /// dernier état connu reste appliqué.
+    pub(crate) async fn status(&self, repo: &ChatRepository) -> MaintenanceStatus {
+        if let Some((read_at, status)) = &*self.cached.read().unwrap()
+            && read_at.elapsed() < CACHE_TTL
+        {
+            return status.clone();
+        }
+        match repo.fetch_maintenance().await {
+            Ok(maintenance) => {
+                let status = maintenance
+                    .map(|(message, since)| MaintenanceStatus {
+                        enabled: true,
+                        message,
+                        since: Some(since),
+                    })
+                    .unwrap_or_default();
+                self.remember(status.clone());
+                status
+            }
+            Err(err) => {
+                eprintln!("Lecture du mode maintenance impossible : {err}");
+                self.cached
+                    .read()
+                    .unwrap()
+                    .as_ref()
+                    .map(|(_, status)| status.clone())
+                    .unwrap_or_default()
+            }
+        }
+    }
+
+    /// Active ou désactive le mode pour toutes les instances ; réactiver le mode garde sa
+    /// date de début.
+    pub(crate) async fn set(
+        &self,
+        repo: &ChatRepository,
+        request: MaintenanceRequest,
+    ) -> Result<MaintenanceStatus, sqlx::Error> {
+        let status = if request.enabled {
+            let message = request
+                .message
+                .map(|message| message.trim().to_string())
+                .filter(|message| !message.is_empty());
+            let since = repo.start_maintenance(message.as_deref()).await?;
+            MaintenanceStatus {
+                enabled: true,
+                message,
+                since: Some(since),
+            }
+        } else {
+            repo.end_maintenance().await?;
+            MaintenanceStatus::default()
+        };
+        self.remember(status.clone());
+        Ok(status)
+    }
+
+    fn remember(&self, status: MaintenanceStatus) {
+        *self.cached.write().unwrap() = Some((Instant::now(), status));
+    }
+}
+
+/// Refuse en 503 les requêtes qui écrivent pendant la maintenance. Les lectures (`GET`),
+/// les estimations de coût et les routes d'administration, dont celle qui lève le mode,
+/// passent toujours.
+pub(crate) async fn reject_writes_in_maintenance(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let status = state.maintenance.status(&state.repo).await;
+    if !status.enabled || !is_write(&request) {
+        return next.run(request).await;
+    }
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(header::RETRY_AFTER, RETRY_AFTER_SECS)],
+        status
+            .message
+            .unwrap_or_else(|| DEFAULT_MESSAGE.to_string()),
+    )
+        .into_response()
+}
+
+fn is_write(request: &Request) -> bool {
+    let path = request.uri().path();
+    if path.starts_with("/carlgpt.v1.Chat/") {
+        return GRPC_WRITES.contains(&path);
+    }
+    !matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) && !path.starts_with("/api/admin/")
+        && !path.ends_with("/estimate")
+}
@@ -0,0 +1,196 @@
+//! Métadonnées des fichiers uploadés, lues dans leur en-tête : dimensions des images (PNG,
+//! JPEG, GIF, WebP), nombre de pages des PDF et durée des sons et vidéos (WAV, MP4/MOV/M4A).
+//! Un format non reconnu ou un fichier mal formé laisse simplement les champs vides.
+
+use std::time::Duration;
+
+use lopdf::Document;
+
+use crate::models::AttachmentMetadata;
+
+/// Lit les métadonnées de `data` selon son type MIME. Le décompte des pages d'un PDF est
+/// borné par `pdf_timeout`, comme l'extraction de son texte.
+pub(crate) async fn probe_metadata(
+    mime_type: &str,
+    data: &[u8],
+    pdf_timeout: Duration,
+) -> AttachmentMetadata {
+    let mut metadata = AttachmentMetadata::default();
+    if mime_type.starts_with("image/") {
+        if let Some((width, height)) = image_dimensions(data) {
+            metadata.width = Some(width as i32);
+            metadata.height = Some(height as i32);
+        }
+    } else if mime_type == "application/pdf" {
+        let data = data.to_vec();
+        let pages = tokio::task::spawn_blocking(move || {
+            Document::load_mem(&data)
+                .ok()
+                .map(|document| document.get_pages().len())
+        });
+        if let Ok(Ok(Some(pages))) = tokio::time::timeout(pdf_timeout, pages).await {
+            metadata.page_count = Some(pages as i32);
+        }
+    } else if mime_type.starts_with("audio/") || mime_type.starts_with("video/") {
+        metadata.duration_ms = media_duration(data).map(|duration| duration.as_millis() as i64);
+    }
+    metadata
+}
+
+fn image_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        // IHDR, premier chunk : largeur puis hauteur
+        return Some((be_u32(data, 16)?, be_u32(data, 20)?));
+    }
+    if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        return Some((le_u16(data, 6)?.into(), le_u16(data, 8)?.into()));
+    }
+    if data.starts_with(b"\xff\xd8") {
+        return jpeg_dimensions(data);
+    }
+    if data.get(0..4) == Some(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+        return webp_dimensions(data);
+    }
+    None
+}
+
+/// Dimensions du premier segment SOF (Start Of Frame) d'un JPEG.
+fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let mut offset = 2;
+    loop {
+        while *data.get(offset)? == 0xff && *data.get(offset + 1)? == 0xff {
+            offset += 1;
+        }
+        if *data.get(offset)? != 0xff {
+            return None;
+        }
+        let marker = *data.get(offset + 1)?;
+        let length = usize::from(be_u16(data, offset + 2)?);
+        // SOF0 à SOF15, hors DHT (C4), JPG (C8) et DAC (CC)
+        if (0xc0..=0xcf).contains(&marker) && !matches!(marker, 0xc4 | 0xc8 | 0xcc) {
+            let height = be_u16(data, offset + 5)?;
+            let width = be_u16(data, offset + 7)?;
+            return Some((width.into(), height.into()));
+        }
+        offset += 2 + length;
+    }
+}
+
+fn webp_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    match data.get(12..16)? {
+        // Avec perte : dimensions sur 14 bits après le code de départ de la frame
+        b"VP8 " => Some((
+            u32::from(le_u16(data, 26)? & 0x3fff),
+            u32::from(le_u16(data, 28)? & 0x3fff),
+        )),
+        // Sans perte : 14 bits par dimension, stockées moins un
+        b"VP8L" => {
+            let bits = le_u32(data, 21)?;
+            Some(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+        }
+        // Étendu : dimensions moins un sur 24 bits
+        b"VP8X" => Some((le_u24(data, 24)? + 1, le_u24(data, 27)? + 1)),
+        _ => None,
+    }
+}
+
+fn media_duration(data: &[u8]) -> Option<Duration> {
+    if data.get(0..4) == Some(b"RIFF") && data.get(8..12) == Some(b"WAVE") {
+        return wav_duration(data);
+    }
+    if data.get(4..8) == Some(b"ftyp") {
+        return mp4_duration(data);
+    }
+    None
+}
+
+/// Durée d'un WAV : taille du chunk `data` divisée par le débit annoncé dans `fmt `.
+fn wav_duration(data: &[u8]) -> Option<Duration> {
+    let mut offset = 12;
+    let mut byte_rate = None;
+    while offset + 8 <= data.len() {
+        let id = data.get(offset..offset + 4)?;
+        let size = le_u32(data, offset + 4)? as usize;
+        match id {
+            b"fmt " => byte_rate = le_u32(data, offset + 16),
+            b"data" => {
+                let byte_rate = byte_rate.filter(|rate| *rate > 0)?;
+                return Some(Duration::from_secs_f64(size as f64 / f64::from(byte_rate)));
+            }
+            _ => {}
+        }
+        // Les chunks sont alignés sur deux octets.
+        offset += 8 + size + size % 2;
+    }
+    None
+}
+
+/// Durée d'un fichier ISO BMFF (MP4, MOV, M4A) : boîte `mvhd` de la boîte `moov`.
+fn mp4_duration(data: &[u8]) -> Option<Duration> {
+    let moov = find_box(data, b"moov")?;
+    let mvhd = find_box(moov, b"mvhd")?;
+    let (timescale, duration) = match *mvhd.first()? {
+        0 => (be_u32(mvhd, 12)?, u64::from(be_u32(mvhd, 16)?)),
+        1 => (be_u32(mvhd, 20)?, be_u64(mvhd, 24)?),
+        _ => return None,
+    };
+    (timescale > 0).then(|| Duration::from_secs_f64(duration as f64 / f64::from(timescale)))
+}
+
+/// Contenu de la première boîte `kind` parmi les boîtes de premier niveau de `data`.
+fn find_box<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    let mut offset = 0;
+    while offset + 8 <= data.len() {
+        let size = be_u32(data, offset)? as usize;
+        let (header, size) = match size {
+            // Taille sur 64 bits après le type
+            1 => (16, usize::try_from(be_u64(data, offset + 8)?).ok()?),
+            // Jusqu'à la fin du fichier
+            0 => (8, data.len() - offset),
+            size => (8, size),
+        };
+        if size < header {
+            return None;
+        }
+        if data.get(offset + 4..offset + 8)? == kind {
+            return data.get(offset + header..(offset + size).min(data.len()));
+        }
+        offset += size;
+    }
+    None
+}
+
+fn be_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn be_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+fn le_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn le_u24(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 3)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+}
+
+fn le_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
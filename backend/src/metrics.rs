@@ -0,0 +1,280 @@
+//! Métriques des uploads (`POST /api/uploads`, `POST /api/uploads/text`) servies au format
+//! texte de Prometheus par `GET /api/admin/metrics` : tailles reçues, types MIME, durée des
+//! extractions et causes des refus, pour régler les limites d'après l'usage réel. Elles sont
+//! tenues en mémoire par instance et repartent de zéro au redémarrage, comme tout compteur
+//! Prometheus.
+
+use std::{collections::BTreeMap, fmt::Write as _, sync::Mutex, time::Duration};
+
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+};
+
+use crate::AppState;
+
+/// Bornes de l'histogramme des tailles, en octets (de 1 Ko à la limite de 20 Mo)
+const SIZE_BUCKETS: &[f64] = &[
+    1024.0,
+    10_240.0,
+    102_400.0,
+    1_048_576.0,
+    5_242_880.0,
+    10_485_760.0,
+    20_971_520.0,
+];
+/// Bornes de l'histogramme des durées d'extraction, en secondes
+const EXTRACTION_BUCKETS: &[f64] = &[0.01, 0.05, 0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+/// Types MIME distincts suivis ; les suivants, envoyés par les clients, sont comptés sous
+/// `other` pour borner le nombre de séries
+const MAX_MIME_TYPES: usize = 50;
+
+#[derive(Clone, Copy)]
+pub(crate) enum UploadEndpoint {
+    /// `POST /api/uploads`
+    File,
+    /// `POST /api/uploads/text`
+    Text,
+}
+
+impl UploadEndpoint {
+    fn label(self) -> &'static str {
+        match self {
+            UploadEndpoint::File => "file",
+            UploadEndpoint::Text => "text",
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct UploadMetrics {
+    inner: Mutex<Counters>,
+}
+
+#[derive(Default)]
+struct Counters {
+    /// Fichiers enregistrés, par endpoint et type MIME
+    uploads: BTreeMap<(&'static str, String), u64>,
+    sizes: BTreeMap<&'static str, Histogram>,
+    /// Requêtes refusées, par endpoint et cause
+    failures: BTreeMap<(&'static str, &'static str), u64>,
+    /// Extractions, par type de fichier et résultat
+    extractions: BTreeMap<(&'static str, &'static str), Histogram>,
+}
+
+struct Histogram {
+    bounds: &'static [f64],
+    /// Observations par intervalle : `counts[i]` compte celles comprises entre
+    /// `bounds[i - 1]` (exclu) et `bounds[i]` (inclus), la dernière case celles au-delà
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Histogram {
+            bounds,
+            counts: vec![0; bounds.len() + 1],
+            sum: 0.0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        let index = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.counts[index] += 1;
+        self.sum += value;
+    }
+
+    /// Lignes `_bucket` (cumulées), `_sum` et `_count` de la série `labels`.
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulated = 0;
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            cumulated += count;
+            let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {cumulated}");
+        }
+        let total = cumulated + self.counts[self.bounds.len()];
+        let _ = writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {total}");
+        let _ = writeln!(out, "{name}_sum{{{labels}}} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{{{labels}}} {total}");
+    }
+}
+
+impl UploadMetrics {
+    /// Fichier enregistré.
+    pub(crate) fn record_upload(&self, endpoint: UploadEndpoint, mime_type: &str, size: usize) {
+        let mut counters = self.inner.lock().unwrap();
+        let mut mime_type = mime_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        let tracked = counters
+            .uploads
+            .keys()
+            .filter(|(_, known)| *known != "other")
+            .count();
+        if !counters
+            .uploads
+            .contains_key(&(endpoint.label(), mime_type.clone()))
+            && tracked >= MAX_MIME_TYPES
+        {
+            mime_type = "other".to_string();
+        }
+        *counters
+            .uploads
+            .entry((endpoint.label(), mime_type))
+            .or_default() += 1;
+        counters
+            .sizes
+            .entry(endpoint.label())
+            .or_insert_with(|| Histogram::new(SIZE_BUCKETS))
+            .observe(size as f64);
+    }
+
+    /// Requête d'upload refusée, avec le statut de la réponse.
+    fn record_failure(&self, endpoint: UploadEndpoint, status: StatusCode) {
+        let reason = match status {
+            StatusCode::PAYLOAD_TOO_LARGE => "too_large",
+            status if status.is_client_error() => "invalid",
+            _ => "server_error",
+        };
+        *self
+            .inner
+            .lock()
+            .unwrap()
+            .failures
+            .entry((endpoint.label(), reason))
+            .or_default() += 1;
+    }
+
+    /// Extraction du contenu d'un fichier terminée ; `error` est le statut de son échec (422
+    /// quand le PDF dépasse `PDF_EXTRACT_TIMEOUT_SECS`).
+    pub(crate) fn record_extraction(
+        &self,
+        mime_type: &str,
+        elapsed: Duration,
+        error: Option<StatusCode>,
+    ) {
+        let kind = if mime_type == "application/pdf" {
+            "pdf"
+        } else if mime_type.starts_with("text/") {
+            "text"
+        } else {
+            "other"
+        };
+        let outcome = match error {
+            None => "ready",
+            Some(StatusCode::UNPROCESSABLE_ENTITY) => "timeout",
+            Some(_) => "failed",
+        };
+        self.inner
+            .lock()
+            .unwrap()
+            .extractions
+            .entry((kind, outcome))
+            .or_insert_with(|| Histogram::new(EXTRACTION_BUCKETS))
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Toutes les séries au format texte de Prometheus (version 0.0.4).
+    pub(crate) fn render(&self) -> String {
+        let counters = self.inner.lock().unwrap();
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "carlgpt_uploads_total",
+            "counter",
+            "Fichiers enregistrés, par endpoint et type MIME.",
+        );
+        for ((endpoint, mime_type), count) in &counters.uploads {
+            let _ = writeln!(
+                out,
+                "carlgpt_uploads_total{{endpoint=\"{endpoint}\",mime_type=\"{}\"}} {count}",
+                escape(mime_type)
+            );
+        }
+
+        header(
+            &mut out,
+            "carlgpt_upload_size_bytes",
+            "histogram",
+            "Taille des fichiers enregistrés, en octets.",
+        );
+        for (endpoint, sizes) in &counters.sizes {
+            sizes.render(
+                &mut out,
+                "carlgpt_upload_size_bytes",
+                &format!("endpoint=\"{endpoint}\""),
+            );
+        }
+
+        header(
+            &mut out,
+            "carlgpt_upload_failures_total",
+            "counter",
+            "Uploads refusés, par endpoint et cause (too_large, invalid, server_error).",
+        );
+        for ((endpoint, reason), count) in &counters.failures {
+            let _ = writeln!(
+                out,
+                "carlgpt_upload_failures_total{{endpoint=\"{endpoint}\",reason=\"{reason}\"}} {count}"
+            );
+        }
+
+        header(
+            &mut out,
+            "carlgpt_attachment_extraction_seconds",
+            "histogram",
+            "Durée de l'extraction du contenu des fichiers, par type et résultat (ready, timeout, failed).",
+        );
+        for ((kind, outcome), durations) in &counters.extractions {
+            durations.render(
+                &mut out,
+                "carlgpt_attachment_extraction_seconds",
+                &format!("kind=\"{kind}\",outcome=\"{outcome}\""),
+            );
+        }
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// Valeur d'étiquette échappée (`\`, `"` et retours à la ligne).
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Middleware des routes d'upload : compte les requêtes refusées, y compris celles arrêtées
+/// avant le handler par la taille de leur corps.
+pub(crate) async fn track_upload_failures(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let endpoint = match request.uri().path() {
+        "/api/uploads/text" => UploadEndpoint::Text,
+        _ => UploadEndpoint::File,
+    };
+    let response = next.run(request).await;
+    if !response.status().is_success() {
+        state
+            .upload_metrics
+            .record_failure(endpoint, response.status());
+    }
+    response
+}
@@ -0,0 +1,219 @@
+//! Provider simulé (`MOCK_PROVIDER=true`) : remplace Groq/OpenAI par des réponses scriptées,
+//! streamées morceau par morceau, pour les tests d'intégration et le développement sans clé.
+
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+use futures::stream::{self, StreamExt};
+
+use crate::{
+    models::{AttachmentPayload, ChatMessagePayload, CompletionParams, FinishReason, TokenUsage},
+    providers::{
+        AiModelChoice, CompletionStream, ProviderChunk, ProviderStream, StreamItem,
+        TITLE_SUMMARY_PROMPT, upstream_error,
+    },
+    tools::{ToolCallDelta, ToolRound},
+};
+
+/// Réponse à renvoyer au prochain appel du provider simulé.
+#[derive(Clone, Debug)]
+pub enum MockReply {
+    /// Réponse complète, découpée en tokens
+    Text(String),
+    /// Réponse arrêtée par la limite de tokens (`finish_reason` = `length`)
+    Truncated(String),
+    /// Réponse coupée par le filtre de contenu (`finish_reason` = `content_filter`)
+    Filtered(String),
+    /// Le modèle refuse de répondre (`refusal`), avec ce texte
+    Refusal(String),
+    /// Réponse complète dont le premier token n'arrive qu'après `delay`
+    Delayed { delay: Duration, text: String },
+    /// Le texte est envoyé puis le stream est coupé (réponse `incomplete`)
+    Interrupted(String),
+    /// Le texte est envoyé puis le provider ne répond plus, sans fermer le stream
+    Stalled(String),
+    /// Le provider refuse la requête avant de streamer
+    Error(String),
+    /// Le provider répond par ce statut HTTP et ce corps d'erreur, interprétés comme ceux
+    /// d'un vrai provider
+    ProviderError { status: u16, body: String },
+    /// Le modèle appelle un outil (`arguments` en JSON) au lieu de répondre
+    ToolCall { name: String, arguments: String },
+}
+
+#[derive(Default)]
+pub struct MockProvider {
+    script: Mutex<VecDeque<MockReply>>,
+    requests: Mutex<Vec<MockRequest>>,
+}
+
+/// Appel reçu par le provider simulé (hors génération de titre).
+#[derive(Clone, Debug)]
+pub struct MockRequest {
+    pub model: AiModelChoice,
+    pub messages: Vec<ChatMessagePayload>,
+    pub params: Option<CompletionParams>,
+    /// Résultats des outils appelés au tour précédent, renvoyés au modèle
+    pub tool_outputs: Vec<String>,
+    /// Images produites par ces outils, montrées au modèle avec leurs résultats
+    pub tool_images: Vec<AttachmentPayload>,
+}
+
+impl MockProvider {
+    /// Ajoute une réponse à la file ; sans réponse scriptée, le provider répond
+    /// `Réponse simulée : <dernier message>`.
+    pub fn push_reply(&self, reply: MockReply) {
+        self.script.lock().unwrap().push_back(reply);
+    }
+
+    /// Appels reçus depuis le démarrage, dans l'ordre.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub(crate) fn complete(
+        &self,
+        messages: &[ChatMessagePayload],
+        model: AiModelChoice,
+        params: Option<CompletionParams>,
+        responses_api: bool,
+        rounds: &[ToolRound],
+    ) -> Result<ProviderStream, (axum::http::StatusCode, String)> {
+        let last_content = messages
+            .last()
+            .map(|message| message.content.as_str())
+            .unwrap_or_default();
+
+        // Les titres ne consomment pas le script : leur appel est concurrent de la réponse.
+        if messages
+            .first()
+            .is_some_and(|message| message.content == TITLE_SUMMARY_PROMPT)
+        {
+            let question = last_content.trim_start_matches("Question: ");
+            let title: Vec<&str> = question.split_whitespace().take(6).collect();
+            return Ok(provider_stream(reply_stream(
+                format!("💬 {}", title.join(" ")),
+                Some(FinishReason::Stop),
+                0,
+            )));
+        }
+
+        self.requests.lock().unwrap().push(MockRequest {
+            model,
+            messages: messages.to_vec(),
+            params,
+            tool_outputs: rounds
+                .last()
+                .map(|round| round.outputs.clone())
+                .unwrap_or_default(),
+            tool_images: rounds
+                .last()
+                .map(|round| round.images.clone())
+                .unwrap_or_default(),
+        });
+        let reply = self
+            .script
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| MockReply::Text(format!("Réponse simulée : {last_content}")));
+        let prompt_chars: usize = messages
+            .iter()
+            .map(|message| message.content.chars().count())
+            .sum();
+        let prompt_tokens = prompt_chars.div_ceil(4) as i32;
+        // Comme l'API Responses, un identifiant de réponse précède le texte.
+        let response_id = responses_api.then(|| {
+            let n = self.requests.lock().unwrap().len();
+            Ok(StreamItem::Chunk(ProviderChunk::ResponseId(format!(
+                "resp_mock_{n}"
+            ))))
+        });
+        let stream = match reply {
+            MockReply::Text(text) => reply_stream(text, Some(FinishReason::Stop), prompt_tokens),
+            MockReply::Truncated(text) => {
+                reply_stream(text, Some(FinishReason::Length), prompt_tokens)
+            }
+            MockReply::Filtered(text) => {
+                reply_stream(text, Some(FinishReason::ContentFilter), prompt_tokens)
+            }
+            MockReply::Refusal(text) => {
+                let chunks =
+                    reply_stream(text, Some(FinishReason::Stop), prompt_tokens).map(|chunk| {
+                        match chunk {
+                            Ok(ProviderChunk::Text(text)) => Ok(ProviderChunk::Refusal(text)),
+                            chunk => chunk,
+                        }
+                    });
+                Box::pin(chunks)
+            }
+            MockReply::Stalled(text) => {
+                let tokens: Vec<_> = text
+                    .split_inclusive(' ')
+                    .map(|token| Ok(ProviderChunk::Text(token.to_string())))
+                    .collect();
+                Box::pin(stream::iter(tokens).chain(stream::pending()))
+            }
+            MockReply::Delayed { delay, text } => {
+                let wait = stream::once(tokio::time::sleep(delay))
+                    .filter_map(|()| async { None::<Result<ProviderChunk, String>> });
+                Box::pin(wait.chain(reply_stream(text, Some(FinishReason::Stop), prompt_tokens)))
+            }
+            MockReply::Interrupted(text) => reply_stream(text, None, prompt_tokens),
+            MockReply::Error(message) => {
+                return Err((axum::http::StatusCode::BAD_GATEWAY, message));
+            }
+            MockReply::ProviderError { status, body } => {
+                return Err(upstream_error("Mock", status, None, &body));
+            }
+            MockReply::ToolCall { name, arguments } => {
+                let n = self.requests.lock().unwrap().len();
+                let call = Ok(StreamItem::ToolCall(ToolCallDelta {
+                    index: 0,
+                    id: Some(format!("call_mock_{n}")),
+                    name: Some(name),
+                    arguments,
+                }));
+                let end = provider_stream(reply_stream(
+                    String::new(),
+                    Some(FinishReason::ToolCalls),
+                    prompt_tokens,
+                ));
+                return Ok(Box::pin(
+                    stream::iter(response_id.into_iter().chain([call])).chain(end),
+                ));
+            }
+        };
+        Ok(Box::pin(
+            stream::iter(response_id).chain(provider_stream(stream)),
+        ))
+    }
+}
+
+fn provider_stream(stream: CompletionStream) -> ProviderStream {
+    Box::pin(stream.map(|chunk| chunk.map(StreamItem::Chunk)))
+}
+
+/// Découpe le texte en tokens (un mot et l'espace qui le suit), puis envoie la raison de fin
+/// et la consommation, ou l'erreur de coupure si `finish_reason` est absent.
+fn reply_stream(
+    text: String,
+    finish_reason: Option<FinishReason>,
+    prompt_tokens: i32,
+) -> CompletionStream {
+    let mut chunks: Vec<Result<ProviderChunk, String>> = text
+        .split_inclusive(' ')
+        .map(|token| Ok(ProviderChunk::Text(token.to_string())))
+        .collect();
+    if let Some(finish_reason) = finish_reason {
+        let completion_tokens = chunks.len() as i32;
+        chunks.push(Ok(ProviderChunk::Finish(finish_reason)));
+        chunks.push(Ok(ProviderChunk::Usage(TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+            cached_tokens: 0,
+        })));
+    } else {
+        chunks.push(Err("connexion au provider simulé coupée".to_string()));
+    }
+    Box::pin(stream::iter(chunks))
+}
@@ -0,0 +1,1349 @@
+//! Types échangés par l'API (requêtes, réponses, lignes de la base) et sérialisés en JSON.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Message {
+    pub id: i32,
+    pub author: String,
+    pub content: String,
+    // grâce à chrono + serde, ça sera automatiquement sérialisé en RFC3339
+    pub created_at: DateTime<Utc>,
+    /// Dernière modification (`PATCH /api/messages/:id`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edited_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateMessageRequest {
+    pub author: String,
+    pub content: String,
+}
+
+/// Paramètres de `GET /api/messages`, du plus récent au plus ancien.
+#[derive(Deserialize)]
+pub struct MessageListQuery {
+    /// Nombre de messages, 50 par défaut et 200 au plus
+    pub limit: Option<i64>,
+    /// Page suivante : messages plus anciens que cet identifiant (le dernier reçu)
+    pub before: Option<i32>,
+    /// Messages de cet auteur seulement
+    pub author: Option<String>,
+}
+
+/// `PATCH /api/messages/:id` : les champs absents restent inchangés.
+#[derive(Deserialize, Debug)]
+pub struct UpdateMessageRequest {
+    pub author: Option<String>,
+    pub content: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChatMessage {
+    pub id: Uuid,
+    pub session_id: Uuid,
+    pub role: String,
+    pub content: String,
+    pub position: i32,
+    pub status: MessageStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+    /// Modèle qui a produit la réponse (messages de l'IA)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Route suivie quand le modèle a été choisi par `auto`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route: Option<String>,
+    /// Raison de fin de la réponse : `length` ou `content_filter` signalent une réponse
+    /// tronquée ou filtrée par le provider
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<FinishReason>,
+    /// Le modèle a refusé de répondre (le contenu est alors son refus) ou le provider a
+    /// filtré la réponse
+    #[serde(default)]
+    pub refused: bool,
+    /// Identifiant de la réponse chez OpenAI (API Responses), repris par le tour suivant
+    #[serde(skip_serializing)]
+    pub response_id: Option<String>,
+    /// Message affiché dans la discussion mais plus envoyé au modèle
+    pub excluded_from_context: bool,
+    /// Message cité par cette question de l'utilisateur
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_to_message_id: Option<Uuid>,
+    /// Date de mise en signet, si le message est gardé pour plus tard
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bookmarked_at: Option<DateTime<Utc>>,
+    /// Réaction (emoji) laissée sur le message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reaction: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub attachments: Vec<ChatAttachment>,
+    /// Réponses candidates générées ensemble (`candidates`), dont celle retenue comme contenu
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub candidates: Vec<MessageCandidate>,
+}
+
+/// Une des réponses générées en un seul appel (`candidates` > 1). Le contenu du message est
+/// celui de la candidate `selected`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MessageCandidate {
+    pub id: Uuid,
+    pub position: i32,
+    pub content: String,
+    pub status: MessageStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<FinishReason>,
+    pub refused: bool,
+    #[serde(skip_serializing)]
+    pub response_id: Option<String>,
+    /// Erreur du provider si la requête de cette candidate a échoué (`status` = `failed`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub selected: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Tokens facturés pour une réponse. `cached_tokens` est la part de `prompt_tokens`
+/// servie depuis le cache de préfixe du provider (facturée moins cher, plus rapide).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub prompt_tokens: i32,
+    pub completion_tokens: i32,
+    pub cached_tokens: i32,
+}
+
+impl TokenUsage {
+    /// Lit l'objet `usage` de l'évènement `response.completed` de l'API Responses.
+    pub fn from_response(usage: &Value) -> Option<Self> {
+        if !usage.is_object() {
+            return None;
+        }
+        let count = |value: &Value| value.as_i64().unwrap_or(0) as i32;
+        Some(TokenUsage {
+            prompt_tokens: count(&usage["input_tokens"]),
+            completion_tokens: count(&usage["output_tokens"]),
+            cached_tokens: count(&usage["input_tokens_details"]["cached_tokens"]),
+        })
+    }
+
+    /// Lit l'objet `usage` d'un chunk OpenAI (ou `x_groq.usage` chez Groq).
+    pub fn from_chunk(val: &Value) -> Option<Self> {
+        let usage = [&val["usage"], &val["x_groq"]["usage"]]
+            .into_iter()
+            .find(|usage| usage.is_object())?;
+        let count = |value: &Value| value.as_i64().unwrap_or(0) as i32;
+        Some(TokenUsage {
+            prompt_tokens: count(&usage["prompt_tokens"]),
+            completion_tokens: count(&usage["completion_tokens"]),
+            cached_tokens: count(&usage["prompt_tokens_details"]["cached_tokens"]),
+        })
+    }
+
+    pub fn from_db(
+        prompt_tokens: Option<i32>,
+        completion_tokens: Option<i32>,
+        cached_tokens: Option<i32>,
+    ) -> Option<Self> {
+        Some(TokenUsage {
+            prompt_tokens: prompt_tokens?,
+            completion_tokens: completion_tokens.unwrap_or(0),
+            cached_tokens: cached_tokens.unwrap_or(0),
+        })
+    }
+}
+
+/// Cumul de deux appels (réponse continuée via `/continue/stream`).
+impl std::ops::Add for TokenUsage {
+    type Output = TokenUsage;
+
+    fn add(self, other: TokenUsage) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens: self.prompt_tokens + other.prompt_tokens,
+            completion_tokens: self.completion_tokens + other.completion_tokens,
+            cached_tokens: self.cached_tokens + other.cached_tokens,
+        }
+    }
+}
+
+/// Cycle de vie d'un message assistant : `pending` (placeholder créé), `streaming`
+/// (premiers tokens reçus), puis `complete`, `incomplete` (stream interrompu, à terminer
+/// via `/continue/stream`) ou `failed` (aucun contenu reçu).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageStatus {
+    Pending,
+    Streaming,
+    Complete,
+    Incomplete,
+    Failed,
+}
+
+impl MessageStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageStatus::Pending => "pending",
+            MessageStatus::Streaming => "streaming",
+            MessageStatus::Complete => "complete",
+            MessageStatus::Incomplete => "incomplete",
+            MessageStatus::Failed => "failed",
+        }
+    }
+
+    pub fn from_db(value: &str) -> Self {
+        match value {
+            "pending" => MessageStatus::Pending,
+            "streaming" => MessageStatus::Streaming,
+            "incomplete" => MessageStatus::Incomplete,
+            "failed" => MessageStatus::Failed,
+            _ => MessageStatus::Complete,
+        }
+    }
+
+    /// Statut final d'une génération interrompue selon ce qui a été reçu.
+    pub fn interrupted(content: &str) -> Self {
+        if content.is_empty() {
+            MessageStatus::Failed
+        } else {
+            MessageStatus::Incomplete
+        }
+    }
+}
+
+/// Raison de fin d'une réponse (`finish_reason` des API compatibles OpenAI).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    Stop,
+    /// Limite de tokens atteinte : la réponse est tronquée
+    Length,
+    /// Réponse coupée par le filtre de contenu du provider
+    ContentFilter,
+    ToolCalls,
+}
+
+impl FinishReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FinishReason::Stop => "stop",
+            FinishReason::Length => "length",
+            FinishReason::ContentFilter => "content_filter",
+            FinishReason::ToolCalls => "tool_calls",
+        }
+    }
+
+    /// Lit la valeur du provider ou de la base ; `function_call` (ancien nom de
+    /// `tool_calls`) est accepté, les valeurs inconnues sont ignorées.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "stop" => Some(FinishReason::Stop),
+            "length" => Some(FinishReason::Length),
+            "content_filter" => Some(FinishReason::ContentFilter),
+            "tool_calls" | "function_call" => Some(FinishReason::ToolCalls),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChatAttachment {
+    pub id: Uuid,
+    pub message_id: Uuid,
+    pub file_name: String,
+    pub mime_type: String,
+    pub size_bytes: i64,
+    /// Calculée à la lecture depuis `storage_key` (`PUBLIC_BASE_URL`, `UPLOAD_BASE_URL`)
+    pub url: String,
+    pub storage_key: String,
+    /// Pages d'un PDF envoyées au modèle ; toutes si absent
+    pub pages: Option<String>,
+    /// Zones de l'image envoyées au modèle ; l'image entière si absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regions: Option<Vec<ImageRegion>>,
+    #[serde(flatten)]
+    pub metadata: AttachmentMetadata,
+    /// Extraction du contenu en tâche de fond ; `ready` pour les images et les anciens fichiers
+    pub processing_status: AttachmentStatus,
+    pub created_at: DateTime<Utc>,
+}
+
+/// État de l'extraction du contenu d'un fichier uploadé (texte d'un PDF, d'un fichier texte).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AttachmentStatus {
+    Pending,
+    Ready,
+    Failed,
+}
+
+impl AttachmentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AttachmentStatus::Pending => "pending",
+            AttachmentStatus::Ready => "ready",
+            AttachmentStatus::Failed => "failed",
+        }
+    }
+
+    pub fn from_db(value: &str) -> Self {
+        match value {
+            "pending" => AttachmentStatus::Pending,
+            "failed" => AttachmentStatus::Failed,
+            _ => AttachmentStatus::Ready,
+        }
+    }
+}
+
+/// Résultat de l'extraction enregistrée pour une clé de stockage.
+pub struct AttachmentExtraction {
+    pub status: AttachmentStatus,
+    pub content: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Résultats du traitement d'un contenu déjà uploadé (`attachment_derivatives`), repris
+/// quand le même fichier est envoyé à nouveau.
+pub struct AttachmentDerivative {
+    /// Texte extrait ; absent pour les images, envoyées telles quelles au modèle
+    pub extracted_text: Option<String>,
+    pub metadata: AttachmentMetadata,
+}
+
+/// Export de tout l'historique ou d'une session (`POST /api/export`), construit en tâche de
+/// fond.
+#[derive(Serialize, Clone, Debug)]
+pub struct ChatExport {
+    pub id: Uuid,
+    pub status: ExportStatus,
+    pub format: ExportFormat,
+    /// Session exportée en PDF ou en fiches de révision
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<Uuid>,
+    pub sessions_done: i32,
+    pub sessions_total: i32,
+    /// Lien absolu de téléchargement de l'archive, valable jusqu'à `expires_at`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Archive ou PDF dans `EXPORT_DIR`
+    #[serde(skip)]
+    pub file_name: Option<String>,
+    #[serde(skip)]
+    pub download_token: Uuid,
+}
+
+/// Contenu d'un export : archive zip de tout l'historique, PDF d'une session, ou fiches de
+/// révision d'une session à importer dans Anki.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Zip,
+    Pdf,
+    Anki,
+}
+
+impl ExportFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExportFormat::Zip => "zip",
+            ExportFormat::Pdf => "pdf",
+            ExportFormat::Anki => "anki",
+        }
+    }
+
+    pub fn from_db(value: &str) -> Self {
+        match value {
+            "pdf" => ExportFormat::Pdf,
+            "anki" => ExportFormat::Anki,
+            _ => ExportFormat::Zip,
+        }
+    }
+
+    /// Extension du fichier téléchargé
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Zip => "zip",
+            ExportFormat::Pdf => "pdf",
+            ExportFormat::Anki => "csv",
+        }
+    }
+}
+
+/// Avancement d'un export ; `expired` une fois le lien de téléchargement périmé.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportStatus {
+    Pending,
+    Running,
+    Ready,
+    Failed,
+    Expired,
+}
+
+impl ExportStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExportStatus::Pending => "pending",
+            ExportStatus::Running => "running",
+            ExportStatus::Ready => "ready",
+            ExportStatus::Failed => "failed",
+            ExportStatus::Expired => "expired",
+        }
+    }
+
+    pub fn from_db(value: &str) -> Self {
+        match value {
+            "running" => ExportStatus::Running,
+            "ready" => ExportStatus::Ready,
+            "failed" => ExportStatus::Failed,
+            _ => ExportStatus::Pending,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChatSession {
+    pub id: Uuid,
+    pub title: String,
+    /// Emoji choisi avec le titre résumé par l'IA
+    pub icon: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub archived: bool,
+    /// Dossier où la discussion est rangée
+    #[serde(default)]
+    pub folder: Option<String>,
+    /// Étiquettes, triées par ordre alphabétique
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub messages: Vec<ChatMessage>,
+    pub draft: Option<ChatDraft>,
+}
+
+/// Sauvegarde présente dans `BACKUP_URL` (`GET /api/admin/backups`).
+#[derive(Serialize, Clone, Debug)]
+pub struct BackupInfo {
+    pub name: String,
+    pub size_bytes: u64,
+    pub created_at: DateTime<Utc>,
+    /// Sessions sauvegardées, connu seulement juste après l'écriture
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sessions: Option<usize>,
+}
+
+/// Issue de `POST /api/admin/backups/:name/restore`.
+#[derive(Serialize, Clone, Debug)]
+pub struct RestoreReport {
+    pub backup: String,
+    pub sessions_restored: usize,
+    /// Sessions encore présentes dans la base, laissées telles quelles
+    pub sessions_skipped: usize,
+}
+
+/// Corps de `POST /api/admin/restore/session/:id`.
+#[derive(Deserialize, Debug)]
+pub struct SessionRestoreRequest {
+    /// Date à laquelle reprendre la session ; maintenant si absente
+    pub at: Option<DateTime<Utc>>,
+}
+
+/// Session recréée par `POST /api/admin/restore/session/:id`.
+#[derive(Serialize, Clone, Debug)]
+pub struct SessionRestore {
+    /// Sauvegarde dont la session a été reprise
+    pub backup: String,
+    pub session: ChatSession,
+}
+
+/// Consommation cumulée des réponses d'une session (`carlgpt-admin recompute-usage`).
+#[derive(Serialize, Clone, Debug)]
+pub struct SessionUsage {
+    pub session_id: Uuid,
+    pub title: String,
+    pub archived: bool,
+    pub answers: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub cached_tokens: i64,
+}
+
+/// Consommation d'un modèle sur une journée (UTC), lue dans `usage_daily`.
+#[derive(Serialize, Clone, Debug)]
+pub struct DailyUsage {
+    pub day: NaiveDate,
+    pub model: String,
+    pub answers: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub cached_tokens: i64,
+}
+
+/// Paramètres de `GET /api/admin/usage` : période, bornes incluses.
+#[derive(Deserialize)]
+pub struct UsageQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+/// Requête envoyée à un provider après masquage de secrets (`GET /api/admin/scrub-audit`).
+#[derive(Serialize, Clone, Debug)]
+pub struct ScrubAuditEntry {
+    pub id: Uuid,
+    pub provider: String,
+    pub model: Option<String>,
+    /// Règles déclenchées (`openai_key`, `hostname`, `custom-1`...)
+    pub rules: Vec<String>,
+    pub replacements: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Paramètres de `GET /api/admin/latency` : période, bornes incluses.
+#[derive(Deserialize)]
+pub struct LatencyQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub model: Option<String>,
+}
+
+/// Décomposition du temps de génération d'une réponse, envoyée dans l'évènement `usage` et
+/// enregistrée dans `message_latency`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LatencyBreakdown {
+    /// De l'appel au provider au premier token ; `None` si aucun texte n'est arrivé
+    pub first_token: Option<Duration>,
+    /// De l'appel au provider à la fin de son flux
+    pub generation: Duration,
+    /// Requêtes à la base pour l'échange, jusqu'à l'enregistrement de la réponse
+    pub db: Duration,
+    /// Lecture et extraction des pièces jointes envoyées au provider
+    pub attachments: Duration,
+}
+
+/// Temps de génération d'une réponse, tel qu'enregistré à sa dernière génération.
+#[derive(Serialize, Clone, Debug)]
+pub struct MessageLatency {
+    pub message_id: Uuid,
+    pub model: String,
+    pub first_token_ms: Option<i32>,
+    pub generation_ms: i32,
+    pub db_ms: i32,
+    pub attachments_ms: i32,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// Temps de génération d'un modèle sur la période : moyennes et 95e centile, en ms.
+#[derive(Serialize, Clone, Debug)]
+pub struct LatencyStats {
+    pub model: String,
+    pub answers: i64,
+    pub first_token_ms_avg: Option<f64>,
+    pub first_token_ms_p95: Option<f64>,
+    pub generation_ms_avg: Option<f64>,
+    pub generation_ms_p95: Option<f64>,
+    pub db_ms_avg: Option<f64>,
+    pub attachments_ms_avg: Option<f64>,
+}
+
+/// Réponse de `GET /api/admin/latency`.
+#[derive(Serialize, Clone, Debug)]
+pub struct LatencyReport {
+    pub models: Vec<LatencyStats>,
+    /// Réponses les plus lentes de la période, de la plus lente à la plus rapide
+    pub slowest: Vec<MessageLatency>,
+}
+
+/// Requête envoyée à un provider et flux SSE brut reçu, secrets masqués
+/// (`GET /api/admin/provider-logs`).
+#[derive(Serialize, Clone, Debug)]
+pub struct ProviderDebugLog {
+    pub id: Uuid,
+    /// Message produit ; absent pour `POST /api/ai`, les titres et les générations échouées
+    pub message_id: Option<Uuid>,
+    pub provider: String,
+    pub url: String,
+    pub request: serde_json::Value,
+    pub http_status: Option<i32>,
+    /// Flux SSE brut, ou corps de l'erreur ; absent tant que la réponse n'est pas terminée
+    pub response: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+/// Paramètres de `GET /api/admin/provider-logs`.
+#[derive(Deserialize)]
+pub struct ProviderLogQuery {
+    pub message_id: Option<Uuid>,
+    /// Nombre d'entrées, 20 par défaut et 200 au plus
+    pub limit: Option<i64>,
+}
+
+/// Paramètres de `GET /api/admin/scrub-audit`.
+#[derive(Deserialize)]
+pub struct ScrubAuditQuery {
+    /// Nombre d'entrées, 100 par défaut et 1000 au plus
+    pub limit: Option<i64>,
+}
+
+/// Paramètres de `GET /api/admin/queries`.
+#[derive(Deserialize)]
+pub struct QueryReportQuery {
+    /// Nombre de requêtes, 20 par défaut et 100 au plus
+    pub limit: Option<i64>,
+}
+
+/// Requête normalisée (paramètres remplacés par `$1`, `$2`...) suivie par
+/// `pg_stat_statements`, temps cumulés depuis la dernière remise à zéro des statistiques.
+#[derive(Serialize, Clone, Debug)]
+pub struct SlowQuery {
+    pub query: String,
+    pub calls: i64,
+    pub total_ms: f64,
+    pub mean_ms: f64,
+    pub max_ms: f64,
+    pub rows: i64,
+    /// Part des blocs lus depuis le cache de PostgreSQL plutôt que le disque
+    pub cache_hit_ratio: Option<f64>,
+}
+
+/// Plan choisi par PostgreSQL pour une lecture fréquente du backend.
+#[derive(Serialize, Clone, Debug)]
+pub struct QueryPlan {
+    pub name: String,
+    /// Sortie de `EXPLAIN`, une ligne par nœud
+    pub plan: Vec<String>,
+}
+
+/// Réponse de `GET /api/admin/queries`.
+#[derive(Serialize, Clone, Debug)]
+pub struct QueryReport {
+    /// Requêtes les plus lentes en moyenne ; `None` si `pg_stat_statements` n'est pas chargé
+    pub slow_queries: Option<Vec<SlowQuery>>,
+    pub plans: Vec<QueryPlan>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChatDraft {
+    pub content: String,
+    pub attachments: Vec<AttachmentPayload>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChatMessagePayload {
+    pub role: String,
+    pub content: String,
+    #[serde(default)]
+    pub attachments: Vec<AttachmentPayload>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateChatSessionRequest {
+    pub title: Option<String>,
+}
+
+/// Action de `POST /api/chat/sessions/bulk`, appliquée à toutes les sessions de la requête.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum BulkSessionAction {
+    Archive,
+    Delete,
+    /// Ajoute les étiquettes `add` et retire celles de `remove`
+    Tag {
+        #[serde(default)]
+        add: Vec<String>,
+        #[serde(default)]
+        remove: Vec<String>,
+    },
+    /// Range les sessions dans `folder`, ou les sort de leur dossier avec `null`
+    Move {
+        folder: Option<String>,
+    },
+}
+
+impl BulkSessionAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BulkSessionAction::Archive => "archive",
+            BulkSessionAction::Delete => "delete",
+            BulkSessionAction::Tag { .. } => "tag",
+            BulkSessionAction::Move { .. } => "move",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BulkSessionRequest {
+    pub session_ids: Vec<Uuid>,
+    #[serde(flatten)]
+    pub action: BulkSessionAction,
+}
+
+/// Issue de `POST /api/chat/sessions/bulk`.
+#[derive(Serialize, Clone, Debug)]
+pub struct BulkSessionResult {
+    pub action: &'static str,
+    /// Sessions réellement modifiées : une session déjà archivée, déjà dans le dossier ou
+    /// qui a déjà les étiquettes n'est pas comptée
+    pub updated: u64,
+}
+
+#[derive(Deserialize)]
+pub struct CreateChatMessageRequest {
+    pub content: String,
+    pub model: Option<String>,
+    pub attachments: Option<Vec<AttachmentPayload>>,
+    pub completion_params: Option<CompletionParams>,
+    /// Preset dont les paramètres complètent `completion_params`
+    pub preset_id: Option<Uuid>,
+    /// Messages précédents à envoyer au modèle ; tout l'historique si absent. L'historique
+    /// stocké n'est pas modifié.
+    pub context_message_ids: Option<Vec<Uuid>>,
+    /// Message de la discussion auquel la question répond, cité dans le prompt
+    pub reply_to_message_id: Option<Uuid>,
+    /// Messages enregistrés avant la question, dans l'ordre (note `system`, exemple de
+    /// réponse `assistant`...), avec elle et envoyés au modèle
+    pub messages: Option<Vec<ChatMessagePayload>>,
+    /// Nombre de réponses candidates à générer (endpoint non-stream uniquement), 1 par défaut
+    pub candidates: Option<u8>,
+    /// Streame le brouillon d'un modèle rapide pendant que le modèle demandé rédige la
+    /// réponse, envoyée ensuite en évènement `revision` (streaming uniquement)
+    pub draft: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct MessageContextRequest {
+    pub excluded_from_context: bool,
+}
+
+#[derive(Deserialize)]
+pub struct BookmarkRequest {
+    pub bookmarked: bool,
+}
+
+/// `reaction: null` retire la réaction.
+#[derive(Deserialize)]
+pub struct ReactionRequest {
+    pub reaction: Option<String>,
+}
+
+/// Bloc de code d'un message (`GET /api/chat/messages/:id/artifacts`).
+#[derive(Serialize, Clone, Debug)]
+pub struct CodeArtifact {
+    /// Position du bloc dans le message, à partir de 0
+    pub index: usize,
+    /// Langage annoncé après le délimiteur (```rust)
+    pub language: Option<String>,
+    /// Nom de fichier donné par le message (chaîne d'info ou ligne qui précède le bloc)
+    pub filename_hint: Option<String>,
+    /// Nom du fichier téléchargé : l'indication, sinon `extrait-N.ext`, unique dans le message
+    pub file_name: String,
+    pub content: String,
+}
+
+/// Message mis en signet, avec la discussion d'où il vient (`GET /api/bookmarks`).
+#[derive(Serialize, Clone, Debug)]
+pub struct Bookmark {
+    pub message_id: Uuid,
+    pub session_id: Uuid,
+    pub session_title: String,
+    pub session_archived: bool,
+    pub role: String,
+    pub content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reaction: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub bookmarked_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct SaveDraftRequest {
+    pub content: String,
+    #[serde(default)]
+    pub attachments: Vec<AttachmentPayload>,
+}
+
+#[derive(Serialize)]
+pub struct ModelEstimate {
+    pub model: &'static str,
+    pub prompt_tokens: u64,
+    pub max_output_tokens: u64,
+    pub fits_in_context: bool,
+    pub prompt_cost_usd: f64,
+    pub worst_case_cost_usd: f64,
+}
+
+#[derive(Serialize)]
+pub struct CostEstimate {
+    pub system_tokens: u64,
+    pub history_tokens: u64,
+    pub message_tokens: u64,
+    /// Contenu des fichiers joints (texte extrait, images) ajouté au prompt
+    pub attachment_tokens: u64,
+    pub context_window: u64,
+    #[serde(flatten)]
+    pub selected: ModelEstimate,
+    /// Même requête sur les autres modèles compatibles, du moins cher au plus cher
+    pub alternatives: Vec<ModelEstimate>,
+}
+
+#[derive(Deserialize)]
+pub struct RegenerateRequest {
+    pub message_id: Uuid,
+    pub model: Option<String>,
+    pub completion_params: Option<CompletionParams>,
+    /// Nombre de réponses candidates à générer (endpoint non-stream uniquement), 1 par défaut
+    pub candidates: Option<u8>,
+}
+
+#[derive(Deserialize)]
+pub struct SelectCandidateRequest {
+    pub candidate_id: Uuid,
+}
+
+#[derive(Deserialize)]
+pub struct ContinueRequest {
+    pub message_id: Uuid,
+    pub model: Option<String>,
+    pub completion_params: Option<CompletionParams>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AttachmentPayload {
+    pub file_name: String,
+    pub mime_type: String,
+    pub size_bytes: i64,
+    /// URL publique ; recalculée depuis `storage_key` à la lecture, facultative à l'envoi
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage_key: Option<String>,
+    /// Pages d'un PDF à envoyer au modèle (`10-25`, `1-3,7`) ; toutes si absent
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pages: Option<String>,
+    /// Zones d'une image tracées par l'utilisateur : le modèle reçoit chacune découpée, au
+    /// lieu de l'image entière
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regions: Option<Vec<ImageRegion>>,
+    /// Lues à l'upload : renvoyées telles quelles avec le message
+    #[serde(default, flatten)]
+    pub metadata: AttachmentMetadata,
+}
+
+/// Rectangle tracé sur une image, en pixels de l'image d'origine depuis son coin supérieur
+/// gauche.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ImageRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Ce que l'utilisateur désigne (« le bouton rouge »), indiqué au modèle avec la zone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// Métadonnées d'un fichier lues à l'upload (voir `media`), vides si le format n'est pas
+/// reconnu.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AttachmentMetadata {
+    /// Dimensions d'une image, en pixels
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<i32>,
+    /// Nombre de pages d'un PDF
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_count: Option<i32>,
+    /// Durée d'un son ou d'une vidéo, en millisecondes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<i64>,
+}
+
+/// Réponse de `POST /api/uploads` et `POST /api/uploads/text` : la pièce jointe à renvoyer avec le message, et l'état de
+/// l'extraction de son contenu.
+#[derive(Serialize)]
+pub struct UploadedFile {
+    #[serde(flatten)]
+    pub attachment: AttachmentPayload,
+    pub processing_status: AttachmentStatus,
+    /// Début du texte, pour un texte collé (`POST /api/uploads/text`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct PasteTextRequest {
+    pub text: String,
+    /// Nom affiché de la pièce jointe ; `texte-colle.txt` par défaut
+    pub file_name: Option<String>,
+}
+
+/// Corps facultatif de `POST /api/export` ; sans corps, archive de tout l'historique.
+#[derive(Deserialize, Default)]
+pub struct ExportRequest {
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// Session à exporter, requise pour les formats `pdf` et `anki`
+    pub session_id: Option<Uuid>,
+    /// Modèle qui rédige les fiches du format `anki` ; modèle par défaut si absent
+    pub model: Option<String>,
+}
+
+/// Paramètres de `GET /api/export/:id/download`.
+#[derive(Deserialize)]
+pub struct ExportDownloadQuery {
+    pub token: Uuid,
+}
+
+/// Paramètres de completion pour l'API OpenAI
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CompletionParams {
+    /// Contrôle l'aléa/créativité (0-2). Valeur faible = déterministe, élevée = varié
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+
+    /// Nombre maximum de tokens à générer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+
+    /// Échantillonnage nucleus (0-1). Alternative à temperature
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+
+    /// Pénalise les tokens déjà présents (-2.0 à 2.0) → encourage nouveaux sujets
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+
+    /// Pénalise par fréquence d'apparition (-2.0 à 2.0) → réduit répétitions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+
+    /// Pour déterminisme (beta)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+
+    /// Niveau de traitement OpenAI ; à défaut, celui du déploiement (`SERVICE_TIER`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<ServiceTier>,
+
+    /// Outil de recherche web intégré (modèles servis par l'API Responses)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web_search: Option<bool>,
+
+    /// Outil `edit_file` : le modèle modifie un fichier de la conversation (bloc de code
+    /// nommé, pièce jointe texte) par un diff unifié que le serveur applique
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_edit: Option<bool>,
+
+    /// Outil `add_to_calendar` : le modèle ajoute au calendrier de l'utilisateur (flux ICS)
+    /// les évènements et tâches évoqués dans la discussion
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub calendar: Option<bool>,
+
+    /// Outil `screenshot_page` : le modèle capture une page web (`SCREENSHOT_SERVICE_URL`)
+    /// et la regarde ; la capture est jointe à la réponse
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub screenshot: Option<bool>,
+
+    /// Outils des plugins WASM (`PLUGIN_DIR`) proposés au modèle, par nom
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugins: Option<Vec<String>>,
+
+    /// Réponse précédente conservée par OpenAI (API Responses) : seuls les messages qui
+    /// suivent la dernière réponse de l'IA sont alors envoyés
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_response_id: Option<String>,
+
+    /// Début imposé de la réponse (ex. ```json) : le modèle la poursuit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assistant_prefix: Option<String>,
+}
+
+/// Niveau de traitement des requêtes OpenAI : `flex` coûte moins cher mais répond plus
+/// lentement (traitements par lots), `priority` l'inverse.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceTier {
+    #[default]
+    Standard,
+    Priority,
+    Flex,
+}
+
+impl ServiceTier {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ServiceTier::Standard => "standard",
+            ServiceTier::Priority => "priority",
+            ServiceTier::Flex => "flex",
+        }
+    }
+
+    /// Valeur du champ `service_tier` de l'API (`default` pour le niveau standard).
+    pub fn api_value(&self) -> &'static str {
+        match self {
+            ServiceTier::Standard => "default",
+            ServiceTier::Priority => "priority",
+            ServiceTier::Flex => "flex",
+        }
+    }
+}
+
+impl std::str::FromStr for ServiceTier {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "standard" => Ok(ServiceTier::Standard),
+            "priority" => Ok(ServiceTier::Priority),
+            "flex" => Ok(ServiceTier::Flex),
+            other => Err(format!("niveau de traitement inconnu: {other}")),
+        }
+    }
+}
+
+impl CompletionParams {
+    /// Garde les paramètres fournis et complète les autres avec ceux de `base`.
+    pub fn or(self, base: CompletionParams) -> Self {
+        CompletionParams {
+            temperature: self.temperature.or(base.temperature),
+            max_tokens: self.max_tokens.or(base.max_tokens),
+            top_p: self.top_p.or(base.top_p),
+            presence_penalty: self.presence_penalty.or(base.presence_penalty),
+            frequency_penalty: self.frequency_penalty.or(base.frequency_penalty),
+            seed: self.seed.or(base.seed),
+            service_tier: self.service_tier.or(base.service_tier),
+            web_search: self.web_search.or(base.web_search),
+            code_edit: self.code_edit.or(base.code_edit),
+            calendar: self.calendar.or(base.calendar),
+            screenshot: self.screenshot.or(base.screenshot),
+            plugins: self.plugins.or(base.plugins),
+            previous_response_id: self.previous_response_id.or(base.previous_response_id),
+            assistant_prefix: self.assistant_prefix.or(base.assistant_prefix),
+        }
+    }
+
+    /// Le modèle reçoit des outils (`code_edit`, `calendar`, `screenshot` ou `plugins`).
+    pub(crate) fn uses_tools(&self) -> bool {
+        self.code_edit == Some(true)
+            || self.calendar == Some(true)
+            || self.screenshot == Some(true)
+            || self
+                .plugins
+                .as_ref()
+                .is_some_and(|plugins| !plugins.is_empty())
+    }
+
+    /// Mêmes paramètres, sans aucun outil.
+    pub(crate) fn without_tools(self) -> Self {
+        CompletionParams {
+            code_edit: None,
+            calendar: None,
+            screenshot: None,
+            plugins: None,
+            ..self
+        }
+    }
+}
+
+impl Default for CompletionParams {
+    fn default() -> Self {
+        Self {
+            temperature: Some(0.7),       // Bon équilibre créativité/cohérence
+            max_tokens: None,             // Pas de limite par défaut
+            top_p: Some(1.0),             // Désactivé (on utilise temperature)
+            presence_penalty: Some(0.0),  // Neutre
+            frequency_penalty: Some(0.0), // Neutre
+            seed: None,                   // Pas de déterminisme
+            service_tier: None,           // Niveau du déploiement
+            web_search: None,             // Pas d'outil
+            code_edit: None,              // Pas d'outil
+            calendar: None,               // Pas d'outil
+            screenshot: None,             // Pas d'outil
+            plugins: None,                // Pas d'outil
+            previous_response_id: None,   // Historique complet envoyé
+            assistant_prefix: None,       // Réponse libre
+        }
+    }
+}
+
+/// Jeu de paramètres de completion nommé (« Précis », « Créatif »...).
+#[derive(Serialize, Clone, Debug)]
+pub struct CompletionPreset {
+    pub id: Uuid,
+    pub name: String,
+    pub params: CompletionParams,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct CompletionPresetRequest {
+    pub name: String,
+    pub params: CompletionParams,
+}
+
+/// Drapeau d'une fonctionnalité expérimentale (`/api/admin/feature-flags`). Elle est active
+/// pour un client si le drapeau est allumé et que le client est listé dans `clients` ou fait
+/// partie des `rollout_percent` % retenus.
+#[derive(Serialize, Clone, Debug)]
+pub struct FeatureFlag {
+    pub name: String,
+    /// Interrupteur général : éteint, la fonctionnalité est coupée pour tous
+    pub enabled: bool,
+    /// Part des clients, de 0 à 100, pour qui la fonctionnalité est active
+    pub rollout_percent: i16,
+    /// Clients (`X-Client-Id` ou adresse IP) qui l'ont quel que soit le pourcentage
+    pub clients: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Corps de `PUT /api/admin/feature-flags/:name`.
+#[derive(Deserialize)]
+pub struct FeatureFlagRequest {
+    pub enabled: bool,
+    /// 100 par défaut
+    pub rollout_percent: Option<i16>,
+    #[serde(default)]
+    pub clients: Vec<String>,
+    pub description: Option<String>,
+}
+
+/// Point de départ d'une discussion défini par le déploiement (`CONVERSATION_TEMPLATES_FILE`).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ConversationTemplate {
+    /// Identifiant stable (`revue-de-code`) de `POST /api/chat/sessions/from-template/:id`
+    pub id: String,
+    /// Titre de la discussion créée
+    pub title: String,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+    /// Consignes enregistrées en tête de la discussion et envoyées au modèle à chaque tour
+    pub system_message: Option<String>,
+    /// Première question proposée, enregistrée comme brouillon de la discussion
+    pub user_message: Option<String>,
+    /// Modèle suggéré : identifiant de `GET /api/models` ou `auto`
+    pub model: Option<String>,
+}
+
+/// Widget de chat intégrable sur un site (`WIDGETS_FILE`). Son jeton n'ouvre que
+/// `/api/widget/*` : une conversation sans historique enregistré, avec un seul persona.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Widget {
+    /// Identifiant stable (`site-vitrine`), repris dans le décompte des requêtes
+    pub id: String,
+    /// Jeton présenté par la page (`Authorization: Bearer …`), 16 caractères au moins
+    pub token: String,
+    /// Nom de l'assistant affiché par le widget
+    pub name: Option<String>,
+    /// Consignes du persona, envoyées au modèle en message système
+    pub persona: Option<String>,
+    /// Modèle imposé : identifiant de `GET /api/models` ou `auto` ; celui par défaut si absent
+    pub model: Option<String>,
+    /// Paramètres de completion imposés (`max_tokens`, `temperature`...)
+    pub completion_params: Option<CompletionParams>,
+    /// Origines des pages autorisées (`https://exemple.fr`) ; toutes si vide
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    /// Requêtes par minute et par visiteur (adresse IP)
+    #[serde(default = "Widget::default_rate_limit")]
+    pub rate_limit_per_minute: u32,
+    /// Messages les plus récents de la conversation envoyés au modèle
+    #[serde(default = "Widget::default_max_messages")]
+    pub max_messages: usize,
+    /// Longueur maximale d'un message, en caractères
+    #[serde(default = "Widget::default_max_message_chars")]
+    pub max_message_chars: usize,
+}
+
+impl Widget {
+    fn default_rate_limit() -> u32 {
+        10
+    }
+
+    fn default_max_messages() -> usize {
+        20
+    }
+
+    fn default_max_message_chars() -> usize {
+        4000
+    }
+}
+
+/// Présentation d'un widget (`GET /api/widget`).
+#[derive(Serialize)]
+pub struct WidgetInfo {
+    pub id: String,
+    pub name: Option<String>,
+}
+
+/// Conversation envoyée par le widget, gardée dans la page du visiteur.
+#[derive(Deserialize)]
+pub struct WidgetChatRequest {
+    pub messages: Vec<WidgetMessage>,
+}
+
+#[derive(Deserialize)]
+pub struct WidgetMessage {
+    /// `user` ou `assistant`
+    pub role: String,
+    pub content: String,
+}
+
+/// Préférences de l'utilisateur (`GET/PUT /api/me/preferences`), gardées côté serveur pour
+/// le suivre d'un appareil à l'autre. Toutes facultatives : le client applique ses valeurs
+/// par défaut pour les champs absents.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct UserPreferences {
+    /// Modèle proposé par défaut : identifiant de `GET /api/models`, ou `auto`
+    pub default_model: Option<String>,
+    /// Paramètres de completion envoyés par défaut avec les messages
+    pub default_params: Option<CompletionParams>,
+    /// Persona par défaut : consignes de ton et de style à donner au modèle
+    pub persona: Option<String>,
+    /// Nom sous lequel le modèle s'adresse à l'utilisateur
+    pub display_name: Option<String>,
+    /// Instructions ajoutées au prompt système de chaque requête
+    pub custom_instructions: Option<String>,
+    /// Langue préférée (étiquette BCP 47 : `fr`, `en-US`), donnée au modèle comme locale
+    pub language: Option<String>,
+    /// Fuseau horaire IANA (`Europe/Paris`) de la date et de l'heure données au modèle
+    pub timezone: Option<String>,
+    pub theme: Option<Theme>,
+    #[serde(default)]
+    pub streaming: StreamingPreferences,
+    /// Dernier enregistrement ; absent tant que rien n'a été enregistré
+    #[serde(default, skip_deserializing)]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Évènement ou tâche relevé dans une discussion par l'outil `add_to_calendar`, servi dans
+/// le flux ICS (`GET /api/me/calendar.ics`).
+#[derive(Serialize, Clone, Debug)]
+pub struct CalendarItem {
+    pub id: Uuid,
+    pub kind: CalendarItemKind,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Début d'un évènement, échéance d'une tâche
+    pub starts_at: Option<DateTime<Utc>>,
+    /// Fin d'un évènement ; dernier jour inclus d'un évènement sur des journées entières
+    pub ends_at: Option<DateTime<Utc>>,
+    /// Journées entières : seule la date (UTC) de `starts_at` et `ends_at` compte
+    pub all_day: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Élément du calendrier à enregistrer, d'après les arguments de l'outil.
+pub struct NewCalendarItem {
+    pub kind: CalendarItemKind,
+    pub title: String,
+    pub description: Option<String>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub all_day: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CalendarItemKind {
+    Event,
+    Todo,
+}
+
+impl CalendarItemKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CalendarItemKind::Event => "event",
+            CalendarItemKind::Todo => "todo",
+        }
+    }
+
+    pub fn from_db(value: &str) -> Self {
+        match value {
+            "todo" => CalendarItemKind::Todo,
+            _ => CalendarItemKind::Event,
+        }
+    }
+}
+
+/// Calendrier (`GET /api/me/calendar`) : lien d'abonnement ICS et éléments relevés.
+#[derive(Serialize, Clone, Debug)]
+pub struct Calendar {
+    /// Lien absolu du flux ICS, avec son jeton ; à ajouter comme abonnement dans une
+    /// application de calendrier
+    pub feed_url: String,
+    pub items: Vec<CalendarItem>,
+}
+
+/// Paramètres de `GET /api/me/calendar.ics`.
+#[derive(Deserialize)]
+pub struct CalendarFeedQuery {
+    pub token: Uuid,
+}
+
+/// Thème de l'interface ; `system` suit celui de l'appareil.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    Light,
+    Dark,
+    System,
+}
+
+/// Affichage des réponses pendant leur génération.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct StreamingPreferences {
+    /// Endpoints SSE (`/stream`) plutôt que réponse complète
+    pub enabled: Option<bool>,
+    /// Afficher le raisonnement du modèle (évènements `reasoning`)
+    pub show_reasoning: Option<bool>,
+}
+
+/// Prompt système de la prochaine requête (`GET /api/admin/system-prompt`).
+#[derive(Serialize, Clone, Debug)]
+pub struct SystemPromptPreview {
+    pub prompt: String,
+    /// Valeur de chaque variable du gabarit ; `null` quand elle n'est pas renseignée
+    pub variables: BTreeMap<String, Option<String>>,
+    pub template: String,
+    pub estimated_tokens: u64,
+}
+
+#[derive(Deserialize)]
+pub struct AIRequest {
+    pub messages: Vec<ChatMessagePayload>,
+    pub model: Option<String>,
+    pub completion_params: Option<CompletionParams>,
+}
+
+#[derive(Serialize)]
+pub struct AIResponse {
+    pub response: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+    /// Réponse servie depuis le cache (aucun appel au provider)
+    pub cached: bool,
+    /// Le modèle a refusé de répondre (`response` est son refus) ou le provider a filtré la
+    /// réponse
+    pub refused: bool,
+    /// À passer dans `completion_params.previous_response_id` pour enchaîner (API Responses)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_id: Option<String>,
+}
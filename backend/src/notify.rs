@@ -0,0 +1,230 @@
+//! Notification de fin des longues générations (`NOTIFY_*`) : un email et/ou un webhook quand
+//! une réponse streamée se termine, est interrompue ou échoue, avec un lien vers la discussion.
+//! Pratique pour un modèle lent lancé depuis un téléphone.
+
+use std::time::Duration;
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use chrono::Utc;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{AppState, models::MessageStatus, outbound::Outbound, smtp::SmtpTransport};
+
+/// Durée maximale d'un appel au webhook
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Réglages des notifications ; aucune n'est envoyée sans webhook ni serveur SMTP.
+#[derive(Clone, Debug)]
+pub struct NotifySettings {
+    /// Appelé en `POST` avec un corps JSON
+    pub webhook_url: Option<String>,
+    /// Serveur d'envoi des emails : `smtps://utilisateur:mot-de-passe@hôte` ou `smtp://hôte`
+    pub smtp_url: Option<String>,
+    pub email_from: Option<String>,
+    pub email_to: Vec<String>,
+    /// Les générations plus courtes ne sont pas notifiées
+    pub min_duration: Duration,
+    /// Lien vers une discussion, `{id}` remplacé par son identifiant ; relatif, il est
+    /// complété par `PUBLIC_BASE_URL`
+    pub session_url: String,
+}
+
+impl Default for NotifySettings {
+    fn default() -> Self {
+        NotifySettings {
+            webhook_url: None,
+            smtp_url: None,
+            email_from: None,
+            email_to: Vec::new(),
+            min_duration: Duration::from_secs(60),
+            session_url: "/?session={id}".to_string(),
+        }
+    }
+}
+
+pub(crate) struct Notifier {
+    webhook_url: Option<String>,
+    email: Option<EmailSettings>,
+    min_duration: Duration,
+    session_url: String,
+    outbound: Outbound,
+}
+
+struct EmailSettings {
+    /// `NOTIFY_SMTP_URL`, vérifiée par `outbound` avant chaque envoi
+    smtp_url: String,
+    transport: SmtpTransport,
+    from: String,
+    to: Vec<String>,
+}
+
+/// Génération terminée, telle que décrite dans la notification.
+pub(crate) struct FinishedGeneration {
+    pub(crate) session_id: Uuid,
+    pub(crate) message_id: Uuid,
+    pub(crate) title: String,
+    pub(crate) model: &'static str,
+    pub(crate) status: MessageStatus,
+    pub(crate) elapsed: Duration,
+    pub(crate) error: Option<String>,
+}
+
+impl Notifier {
+    /// `Ok(None)` si aucune notification n'est configurée.
+    pub(crate) fn from_settings(
+        settings: &NotifySettings,
+        outbound: &Outbound,
+    ) -> Result<Option<Self>, String> {
+        let email = match &settings.smtp_url {
+            Some(url) => {
+                let transport = SmtpTransport::from_url(url)
+                    .map_err(|err| format!("NOTIFY_SMTP_URL: {err}"))?;
+                let from = settings
+                    .email_from
+                    .clone()
+                    .ok_or("NOTIFY_EMAIL_FROM manquant")?;
+                if settings.email_to.is_empty() {
+                    return Err("NOTIFY_EMAIL_TO manquant".to_string());
+                }
+                Some(EmailSettings {
+                    smtp_url: url.clone(),
+                    transport,
+                    from,
+                    to: settings.email_to.clone(),
+                })
+            }
+            None => None,
+        };
+        if email.is_none() && settings.webhook_url.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Notifier {
+            webhook_url: settings.webhook_url.clone(),
+            email,
+            min_duration: settings.min_duration,
+            session_url: settings.session_url.clone(),
+            outbound: outbound.clone(),
+        }))
+    }
+}
+
+/// Notifie la fin d'une génération qui a duré au moins `NOTIFY_MIN_DURATION_SECS`, en tâche
+/// de fond : un envoi raté est journalisé sans toucher à la réponse.
+pub(crate) fn notify_generation(state: &AppState, finished: FinishedGeneration) {
+    let Some(notifier) = state.notifier.clone() else {
+        return;
+    };
+    if finished.elapsed < notifier.min_duration {
+        return;
+    }
+    let url = state.urls.absolute(
+        None,
+        &notifier
+            .session_url
+            .replace("{id}", &finished.session_id.to_string()),
+    );
+    tokio::spawn(async move {
+        if let Some(webhook_url) = &notifier.webhook_url
+            && let Err(err) = send_webhook(&notifier.outbound, webhook_url, &finished, &url).await
+        {
+            eprintln!("Notification webhook impossible: {err}");
+        }
+        if let Some(email) = &notifier.email {
+            let message = email_message(email, &finished, &url);
+            let sent = match notifier.outbound.check(&email.smtp_url).await {
+                Ok(()) => email.transport.send(&email.from, &email.to, &message).await,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = sent {
+                eprintln!("Notification email impossible: {err}");
+            }
+        }
+    });
+}
+
+async fn send_webhook(
+    outbound: &Outbound,
+    webhook_url: &str,
+    finished: &FinishedGeneration,
+    url: &str,
+) -> Result<(), String> {
+    let response = outbound
+        .client(webhook_url)
+        .await?
+        .post(webhook_url)
+        .timeout(WEBHOOK_TIMEOUT)
+        .json(&json!({
+            "type": "generation_finished",
+            "chatId": finished.session_id,
+            "messageId": finished.message_id,
+            "title": finished.title,
+            "status": finished.status,
+            "model": finished.model,
+            "durationMs": finished.elapsed.as_millis() as u64,
+            "error": finished.error,
+            "url": url,
+        }))
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("{webhook_url} a répondu {}", response.status()));
+    }
+    Ok(())
+}
+
+/// Email texte, encodé en base64 pour passer par tout serveur SMTP.
+fn email_message(email: &EmailSettings, finished: &FinishedGeneration, url: &str) -> String {
+    let title = &finished.title;
+    let seconds = finished.elapsed.as_secs();
+    let (subject, mut body) = match finished.status {
+        MessageStatus::Complete => (
+            format!("Réponse prête : {title}"),
+            format!(
+                "La réponse de {} dans « {title} » est prête, après {seconds} s.",
+                finished.model
+            ),
+        ),
+        MessageStatus::Incomplete => (
+            format!("Réponse interrompue : {title}"),
+            format!(
+                "La réponse de {} dans « {title} » a été interrompue après {seconds} s. \
+                 Le début est enregistré et peut être continué.",
+                finished.model
+            ),
+        ),
+        _ => (
+            format!("Échec de la génération : {title}"),
+            format!(
+                "La réponse de {} dans « {title} » a échoué après {seconds} s.",
+                finished.model
+            ),
+        ),
+    };
+    if let Some(error) = &finished.error {
+        body.push_str(&format!("\n\nErreur : {error}"));
+    }
+    body.push_str(&format!("\n\nOuvrir la discussion : {url}\n"));
+
+    let encoded = BASE64.encode(body);
+    let mut lines = vec![
+        format!("From: {}", email.from),
+        format!("To: {}", email.to.join(", ")),
+        format!("Subject: =?UTF-8?B?{}?=", BASE64.encode(subject)),
+        format!("Date: {}", Utc::now().to_rfc2822()),
+        format!("Message-ID: <{}@carlgpt>", Uuid::new_v4()),
+        "MIME-Version: 1.0".to_string(),
+        "Content-Type: text/plain; charset=utf-8".to_string(),
+        "Content-Transfer-Encoding: base64".to_string(),
+        String::new(),
+    ];
+    // Lignes de 76 caractères au plus (RFC 2045)
+    lines.extend(
+        encoded
+            .as_bytes()
+            .chunks(76)
+            .map(|chunk| String::from_utf8_lossy(chunk).into_owned()),
+    );
+    lines.join("\r\n")
+}
@@ -0,0 +1,83 @@
+//! Requêtes sortantes qui ne vont pas à un provider : webhooks de notification et
+//! d'archivage, emails de notification, service de capture de pages. Toutes passent par
+//! [`Outbound`], qui applique `LOCAL_ONLY` : seul un hôte de la machine ou du réseau privé
+//! du déploiement reste joignable.
+
+use std::net::IpAddr;
+
+use reqwest::Url;
+use url::Host;
+
+#[derive(Clone)]
+pub struct Outbound {
+    client: reqwest::Client,
+    local_only: bool,
+}
+
+impl Outbound {
+    pub fn new(local_only: bool) -> Self {
+        Outbound {
+            client: reqwest::Client::new(),
+            local_only,
+        }
+    }
+
+    /// Client HTTP pour `url`, ou la raison du refus en mode `LOCAL_ONLY`.
+    pub async fn client(&self, url: &str) -> Result<&reqwest::Client, String> {
+        self.check(url).await?;
+        Ok(&self.client)
+    }
+
+    /// Refuse `url` en mode `LOCAL_ONLY` si son hôte n'est pas local : une adresse IP doit
+    /// l'être, un nom ne doit résoudre que vers des adresses locales.
+    pub async fn check(&self, url: &str) -> Result<(), String> {
+        if !self.local_only {
+            return Ok(());
+        }
+        let parsed = Url::parse(url).map_err(|err| format!("URL invalide ({err})"))?;
+        let addresses = resolve(&parsed).await?;
+        if addresses.is_empty() || !addresses.into_iter().all(is_private) {
+            return Err(format!(
+                "Mode local uniquement (LOCAL_ONLY) : {} n'est pas un hôte local.",
+                parsed.host_str().unwrap_or(url)
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Adresses IP de l'hôte de `url`, résolu par DNS s'il s'agit d'un nom.
+pub(crate) async fn resolve(url: &Url) -> Result<Vec<IpAddr>, String> {
+    match url.host() {
+        Some(Host::Ipv4(ip)) => Ok(vec![ip.into()]),
+        Some(Host::Ipv6(ip)) => Ok(vec![ip.into()]),
+        Some(Host::Domain(name)) => {
+            let port = url.port_or_known_default().unwrap_or(80);
+            tokio::net::lookup_host((name, port))
+                .await
+                .map(|addresses| addresses.map(|address| address.ip()).collect())
+                .map_err(|err| format!("{name} introuvable ({err})"))
+        }
+        None => Err(format!("{url} n'a pas d'hôte")),
+    }
+}
+
+/// Adresse de la machine ou d'un réseau privé : boucle locale, réseaux privés (RFC 1918,
+/// IPv6 ULA), lien local (dont les métadonnées des clouds, 169.254.169.254) et adresse
+/// non spécifiée.
+pub(crate) fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified()
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private(ip.into()),
+            None => {
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+            }
+        },
+    }
+}
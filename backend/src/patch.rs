@@ -0,0 +1,124 @@
+//! Application d'un diff unifié (`@@ -a,b +c,d @@`) à un fichier texte, pour l'outil
+//! d'édition de code : le modèle envoie les lignes modifiées au lieu du fichier entier.
+
+/// Écart maximal, en lignes, entre la position annoncée par un hunk et celle où son
+/// contexte est trouvé (numéros de ligne approximatifs du modèle).
+const MAX_HUNK_DRIFT: usize = 200;
+
+struct Hunk {
+    /// Première ligne remplacée, à partir de 1 (0 : insertion en tête de fichier)
+    old_start: usize,
+    old_lines: Vec<String>,
+    new_lines: Vec<String>,
+}
+
+/// Applique `diff` à `original`. Les en-têtes (`---`, `+++`, `diff`, `index`) sont ignorés ;
+/// chaque hunk doit retrouver ses lignes de contexte et de suppression dans le fichier, à
+/// des espaces de fin de ligne près.
+pub(crate) fn apply_unified_diff(original: &str, diff: &str) -> Result<String, String> {
+    let hunks = parse_hunks(diff)?;
+    let trailing_newline = original.is_empty() || original.ends_with('\n');
+    let mut lines: Vec<&str> = original.lines().collect();
+    let mut patched: Vec<String> = Vec::with_capacity(lines.len());
+    let mut cursor = 0;
+
+    for (number, hunk) in hunks.iter().enumerate() {
+        let expected = hunk.old_start.saturating_sub(1).max(cursor);
+        let position = find_hunk(&lines, cursor, expected, &hunk.old_lines).ok_or_else(|| {
+            match hunk.old_lines.first() {
+                Some(first) => format!(
+                    "hunk {} : lignes introuvables dans le fichier (attendues vers la ligne {}, en commençant par « {} »)",
+                    number + 1,
+                    hunk.old_start,
+                    first.trim()
+                ),
+                None => format!(
+                    "hunk {} : ligne {} hors du fichier",
+                    number + 1,
+                    hunk.old_start
+                ),
+            }
+        })?;
+        patched.extend(lines[cursor..position].iter().map(|line| line.to_string()));
+        patched.extend(hunk.new_lines.iter().cloned());
+        cursor = position + hunk.old_lines.len();
+    }
+    patched.extend(lines.drain(cursor..).map(str::to_string));
+
+    let mut result = patched.join("\n");
+    if trailing_newline && !result.is_empty() {
+        result.push('\n');
+    }
+    Ok(result)
+}
+
+fn parse_hunks(diff: &str) -> Result<Vec<Hunk>, String> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    for line in diff.lines() {
+        if let Some(header) = line.strip_prefix("@@") {
+            hunks.push(Hunk {
+                old_start: parse_old_start(header)
+                    .ok_or_else(|| format!("en-tête de hunk illisible : « {line} »"))?,
+                old_lines: Vec::new(),
+                new_lines: Vec::new(),
+            });
+            continue;
+        }
+        let Some(hunk) = hunks.last_mut() else {
+            // En-têtes de fichier avant le premier hunk
+            continue;
+        };
+        if line.starts_with("--- ") || line.starts_with("+++ ") || line.starts_with("diff ") {
+            continue;
+        }
+        match line.chars().next() {
+            Some('+') => hunk.new_lines.push(line[1..].to_string()),
+            Some('-') => hunk.old_lines.push(line[1..].to_string()),
+            Some(' ') => {
+                hunk.old_lines.push(line[1..].to_string());
+                hunk.new_lines.push(line[1..].to_string());
+            }
+            // « \ No newline at end of file »
+            Some('\\') => {}
+            // Ligne de contexte vide dont l'espace initial a été perdu
+            None => {
+                hunk.old_lines.push(String::new());
+                hunk.new_lines.push(String::new());
+            }
+            Some(_) => return Err(format!("ligne de diff invalide : « {line} »")),
+        }
+    }
+    if hunks.is_empty() {
+        return Err("aucun hunk (@@ -a,b +c,d @@) dans le diff".to_string());
+    }
+    Ok(hunks)
+}
+
+/// Numéro de la première ligne remplacée dans ` -12,5 +12,6 @@`.
+fn parse_old_start(header: &str) -> Option<usize> {
+    let old = header.split_whitespace().next()?.strip_prefix('-')?;
+    old.split(',').next()?.parse().ok()
+}
+
+/// Position des lignes `old_lines` à partir de `cursor`, la plus proche de `expected`.
+fn find_hunk(
+    lines: &[&str],
+    cursor: usize,
+    expected: usize,
+    old_lines: &[String],
+) -> Option<usize> {
+    let last_start = lines.len().checked_sub(old_lines.len())?;
+    if old_lines.is_empty() {
+        return Some(expected.min(lines.len()));
+    }
+    let matches_at = |start: usize| {
+        lines[start..start + old_lines.len()]
+            .iter()
+            .zip(old_lines)
+            .all(|(line, old)| line.trim_end() == old.trim_end())
+    };
+    (0..=MAX_HUNK_DRIFT)
+        .flat_map(|drift| [expected.checked_add(drift), expected.checked_sub(drift)])
+        .flatten()
+        .find(|start| (cursor..=last_start).contains(start) && matches_at(*start))
+}
@@ -0,0 +1,717 @@
+//! Rendu d'une session en PDF (`POST /api/export` avec `format: pdf`), pour l'archiver ou la
+//! partager avec quelqu'un qui n'a pas accès à l'instance. Le markdown des messages est
+//! converti en document typst, compilé dans le processus avec les polices embarquées : ni
+//! navigateur ni outil externe à installer. Les blocs de code gardent leur langage, et les
+//! formules LaTeX (syntaxe KaTeX, `$…$` et `$$…$$`) sont traduites en formules typst.
+
+use std::{iter::Peekable, str::Chars, sync::LazyLock};
+
+use comemo::Prehashed;
+use pulldown_cmark::{Alignment, CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use typst::{
+    Library, World,
+    diag::{FileError, FileResult},
+    eval::Tracer,
+    foundations::{Bytes, Datetime, Smart},
+    syntax::{FileId, Source},
+    text::{Font, FontBook},
+};
+
+use crate::models::ChatSession;
+
+/// Bibliothèque standard de typst, construite une fois
+static LIBRARY: LazyLock<Prehashed<Library>> = LazyLock::new(|| Prehashed::new(Library::default()));
+/// Polices embarquées : Linux Libertine (texte), New Computer Modern Math, DejaVu Sans Mono
+static FONTS: LazyLock<(Prehashed<FontBook>, Vec<Font>)> = LazyLock::new(|| {
+    let fonts: Vec<Font> = typst_assets::fonts()
+        .flat_map(|data| Font::iter(Bytes::from_static(data)))
+        .collect();
+    (Prehashed::new(FontBook::from_fonts(&fonts)), fonts)
+});
+
+/// Mise en page commune : A4, texte en Linux Libertine, code sur fond gris.
+const PREAMBLE: &str = r##"#set page(paper: "a4", margin: 2cm, numbering: "1")
+#set text(font: "Linux Libertine", size: 10.5pt, lang: "fr")
+#set par(justify: true)
+#show raw: set text(font: "DejaVu Sans Mono", size: 9pt)
+#show raw.where(block: true): block.with(fill: luma(245), inset: 8pt, radius: 3pt, width: 100%)
+#show link: set text(fill: rgb("#1a5fb4"))
+"##;
+
+/// Rendu des formules LaTeX
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum MathMode {
+    /// Traduites en formules typst
+    Typeset,
+    /// Laissées en LaTeX, dans une police à chasse fixe : repli quand une formule n'a pas
+    /// pu être traduite
+    Source,
+}
+
+/// PDF d'une session : titre, date, puis chaque message avec son auteur, sa date et ses
+/// pièces jointes. Si une formule ne se compile pas, tout le document est rendu avec les
+/// formules en LaTeX plutôt que d'échouer.
+pub(crate) fn session_pdf(session: &ChatSession) -> Result<Vec<u8>, String> {
+    match compile(session_markup(session, MathMode::Typeset)) {
+        Ok(pdf) => Ok(pdf),
+        Err(err) => {
+            eprintln!(
+                "PDF de la session {} : formules laissées en LaTeX ({err})",
+                session.id
+            );
+            compile(session_markup(session, MathMode::Source))
+        }
+    }
+}
+
+fn compile(markup: String) -> Result<Vec<u8>, String> {
+    let world = PdfWorld {
+        source: Source::detached(markup),
+    };
+    let document = typst::compile(&world, &mut Tracer::new()).map_err(|errors| {
+        errors
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect::<Vec<_>>()
+            .join(" ; ")
+    })?;
+    Ok(typst_pdf::pdf(&document, Smart::Auto, None))
+}
+
+/// Document typst d'une session, sans autre fichier que lui-même.
+struct PdfWorld {
+    source: Source,
+}
+
+impl World for PdfWorld {
+    fn library(&self) -> &Prehashed<Library> {
+        &LIBRARY
+    }
+
+    fn book(&self) -> &Prehashed<FontBook> {
+        &FONTS.0
+    }
+
+    fn main(&self) -> Source {
+        self.source.clone()
+    }
+
+    fn source(&self, id: FileId) -> FileResult<Source> {
+        if id == self.source.id() {
+            Ok(self.source.clone())
+        } else {
+            Err(FileError::AccessDenied)
+        }
+    }
+
+    fn file(&self, _id: FileId) -> FileResult<Bytes> {
+        Err(FileError::AccessDenied)
+    }
+
+    fn font(&self, index: usize) -> Option<Font> {
+        FONTS.1.get(index).cloned()
+    }
+
+    fn today(&self, _offset: Option<i64>) -> Option<Datetime> {
+        None
+    }
+}
+
+fn session_markup(session: &ChatSession, math: MathMode) -> String {
+    let mut markup = PREAMBLE.to_string();
+    markup.push_str(&format!(
+        "#set document(title: {})\n",
+        string_literal(&session.title)
+    ));
+    markup.push_str(&format!("= {}\n\n", escape(&session.title)));
+    markup.push_str(&format!(
+        "#text(size: 9pt, fill: luma(110))[{}]\n\n",
+        escape(&format!(
+            "Créée le {} — {} messages{}",
+            session.created_at.format("%d/%m/%Y %H:%M"),
+            session.messages.len(),
+            if session.archived { ", archivée" } else { "" }
+        ))
+    ));
+    for message in &session.messages {
+        let author = match (message.role.as_str(), &message.model) {
+            ("user", _) => "Utilisateur".to_string(),
+            ("system", _) => "Système".to_string(),
+            (_, Some(model)) => format!("Assistant ({model})"),
+            _ => "Assistant".to_string(),
+        };
+        markup.push_str(&format!(
+            "#block(above: 1.8em, below: 0.9em, width: 100%, inset: (bottom: 4pt), \
+             stroke: (bottom: 0.5pt + luma(200)))[#strong[{}] #h(1fr) \
+             #text(size: 8.5pt, fill: luma(110))[{}]]\n\n",
+            escape(&author),
+            escape(&message.created_at.format("%d/%m/%Y %H:%M").to_string())
+        ));
+        markup.push_str(&markdown_to_typst(&message.content, math));
+        if !message.attachments.is_empty() {
+            let names: Vec<&str> = message
+                .attachments
+                .iter()
+                .map(|attachment| attachment.file_name.as_str())
+                .collect();
+            markup.push_str(&format!(
+                "#text(size: 9pt, fill: luma(90))[{}]\n\n",
+                escape(&format!("Pièces jointes : {}", names.join(", ")))
+            ));
+        }
+    }
+    markup
+}
+
+/// Markdown d'un message en markup typst. Les éléments sont écrits sous forme d'appels de
+/// fonctions (`#emph[…]`, `#list(…)`) plutôt qu'en syntaxe abrégée, qui dépend des retours
+/// à la ligne et de l'indentation ; le texte est échappé.
+fn markdown_to_typst(markdown: &str, math: MathMode) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_MATH;
+    let mut out = String::new();
+    // Bloc de code en cours : son langage et son contenu
+    let mut code: Option<(Option<String>, String)> = None;
+    for event in Parser::new_ext(markdown, options) {
+        if let Some((_, content)) = code.as_mut() {
+            match event {
+                Event::Text(text) => content.push_str(&text),
+                Event::End(TagEnd::CodeBlock) => {
+                    let (lang, content) = code.take().unwrap_or_default();
+                    let lang = lang
+                        .map(|lang| format!("lang: {}, ", string_literal(&lang)))
+                        .unwrap_or_default();
+                    out.push_str(&format!(
+                        "#raw(block: true, {lang}{})\n\n",
+                        string_literal(content.trim_end_matches('\n'))
+                    ));
+                }
+                _ => {}
+            }
+            continue;
+        }
+        match event {
+            Event::Start(tag) => match tag {
+                Tag::Paragraph => {}
+                Tag::Heading { level, .. } => {
+                    // `=` est le titre de la session : les titres des messages sont décalés.
+                    let level = match level {
+                        HeadingLevel::H1 => 2,
+                        HeadingLevel::H2 => 3,
+                        HeadingLevel::H3 => 4,
+                        HeadingLevel::H4 => 5,
+                        HeadingLevel::H5 | HeadingLevel::H6 => 6,
+                    };
+                    out.push_str(&format!("#heading(level: {level}, outlined: false)["));
+                }
+                Tag::BlockQuote(_) => out.push_str("#quote(block: true)["),
+                Tag::CodeBlock(kind) => {
+                    let lang = match kind {
+                        // ```rust:src/main.rs : le langage, sans le nom du fichier
+                        CodeBlockKind::Fenced(info) => info
+                            .split([':', ' ', ','])
+                            .next()
+                            .filter(|lang| !lang.is_empty())
+                            .map(str::to_string),
+                        CodeBlockKind::Indented => None,
+                    };
+                    code = Some((lang, String::new()));
+                }
+                Tag::List(Some(start)) => out.push_str(&format!("#enum(start: {start}, ")),
+                Tag::List(None) => out.push_str("#list("),
+                Tag::Item => out.push('['),
+                Tag::Table(alignments) => {
+                    let align: Vec<&str> = alignments
+                        .iter()
+                        .map(|alignment| match alignment {
+                            Alignment::None => "auto",
+                            Alignment::Left => "left",
+                            Alignment::Center => "center",
+                            Alignment::Right => "right",
+                        })
+                        .collect();
+                    out.push_str(&format!(
+                        "#table(columns: {}, align: ({},), inset: 5pt, stroke: 0.5pt + luma(180), ",
+                        alignments.len(),
+                        align.join(", ")
+                    ));
+                }
+                Tag::TableHead => out.push_str("table.header("),
+                Tag::TableCell => out.push('['),
+                Tag::Emphasis => out.push_str("#emph["),
+                Tag::Strong => out.push_str("#strong["),
+                Tag::Strikethrough => out.push_str("#strike["),
+                // Les images distantes ne sont pas téléchargées : un lien les remplace.
+                Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. } => {
+                    out.push_str(&format!("#link({})[", string_literal(&dest_url)));
+                }
+                _ => {}
+            },
+            Event::End(tag) => match tag {
+                TagEnd::Paragraph => out.push_str("\n\n"),
+                TagEnd::Heading(_) | TagEnd::BlockQuote(_) => out.push_str("]\n\n"),
+                TagEnd::List(_) => out.push_str(")\n\n"),
+                TagEnd::Item | TagEnd::TableCell => out.push_str("], "),
+                TagEnd::TableHead => out.push_str("), "),
+                TagEnd::Table => out.push_str(")\n\n"),
+                TagEnd::Emphasis
+                | TagEnd::Strong
+                | TagEnd::Strikethrough
+                | TagEnd::Link
+                | TagEnd::Image => out.push(']'),
+                _ => {}
+            },
+            Event::Text(text) | Event::Html(text) | Event::InlineHtml(text) => {
+                out.push_str(&escape(&text));
+            }
+            Event::Code(text) => out.push_str(&format!("#raw({})", string_literal(&text))),
+            Event::InlineMath(latex) => out.push_str(&math_markup(&latex, false, math)),
+            Event::DisplayMath(latex) => out.push_str(&math_markup(&latex, true, math)),
+            Event::SoftBreak => out.push(' '),
+            Event::HardBreak => out.push_str(" \\\n"),
+            Event::Rule => out.push_str("#line(length: 100%, stroke: 0.5pt + luma(180))\n\n"),
+            Event::TaskListMarker(checked) => {
+                out.push_str(if checked { "\\[x\\] " } else { "\\[ \\] " });
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Formule en ligne (`$x$`) ou en bloc (`$ x $`), ou son LaTeX en texte brut.
+fn math_markup(latex: &str, display: bool, math: MathMode) -> String {
+    match (math, display) {
+        (MathMode::Typeset, false) => format!("${}$", latex_to_typst(latex).trim()),
+        (MathMode::Typeset, true) => format!("$ {} $", latex_to_typst(latex).trim()),
+        (MathMode::Source, false) => format!("#raw({})", string_literal(latex)),
+        (MathMode::Source, true) => {
+            format!("#raw(block: true, {})", string_literal(latex.trim()))
+        }
+    }
+}
+
+/// Texte tel quel en markup typst : la ponctuation ASCII, qui peut y avoir un sens
+/// (`*`, `_`, `#`, `$`, `//`...), est échappée.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\n' | '\r' => escaped.push(' '),
+            ch if ch.is_ascii_punctuation() => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Chaîne typst (`"…"`).
+fn string_literal(text: &str) -> String {
+    let mut literal = String::with_capacity(text.len() + 2);
+    literal.push('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            ch => literal.push(ch),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// Commandes LaTeX dont le symbole typst porte un autre nom ; les autres (lettres grecques,
+/// `sin`, `lim`, `sum`...) sont reprises telles quelles.
+const LATEX_SYMBOLS: &[(&str, &str)] = &[
+    ("epsilon", "epsilon.alt"),
+    ("varepsilon", "epsilon"),
+    ("phi", "phi.alt"),
+    ("varphi", "phi"),
+    ("vartheta", "theta.alt"),
+    ("varpi", "pi.alt"),
+    ("varrho", "rho.alt"),
+    ("varsigma", "sigma.alt"),
+    ("cdot", "dot.op"),
+    ("pm", "plus.minus"),
+    ("mp", "minus.plus"),
+    ("leq", "<="),
+    ("le", "<="),
+    ("geq", ">="),
+    ("ge", ">="),
+    ("neq", "!="),
+    ("ne", "!="),
+    ("ll", "<<"),
+    ("gg", ">>"),
+    ("sim", "tilde.op"),
+    ("simeq", "tilde.eq"),
+    ("cong", "tilde.equiv"),
+    ("propto", "prop"),
+    ("infty", "infinity"),
+    ("partial", "diff"),
+    ("prod", "product"),
+    ("coprod", "product.co"),
+    ("int", "integral"),
+    ("iint", "integral.double"),
+    ("iiint", "integral.triple"),
+    ("oint", "integral.cont"),
+    ("to", "arrow.r"),
+    ("rightarrow", "arrow.r"),
+    ("longrightarrow", "arrow.r.long"),
+    ("gets", "arrow.l"),
+    ("leftarrow", "arrow.l"),
+    ("Rightarrow", "arrow.r.double"),
+    ("implies", "arrow.r.double"),
+    ("Leftarrow", "arrow.l.double"),
+    ("leftrightarrow", "arrow.l.r"),
+    ("Leftrightarrow", "arrow.l.r.double"),
+    ("iff", "arrow.l.r.double"),
+    ("mapsto", "arrow.r.bar"),
+    ("uparrow", "arrow.t"),
+    ("downarrow", "arrow.b"),
+    ("notin", "in.not"),
+    ("ni", "in.rev"),
+    ("subseteq", "subset.eq"),
+    ("supseteq", "supset.eq"),
+    ("cup", "union"),
+    ("cap", "sect"),
+    ("bigcup", "union.big"),
+    ("bigcap", "sect.big"),
+    ("setminus", "without"),
+    ("emptyset", "nothing"),
+    ("varnothing", "nothing"),
+    ("nexists", "exists.not"),
+    ("neg", "not"),
+    ("lnot", "not"),
+    ("land", "and"),
+    ("wedge", "and"),
+    ("lor", "or"),
+    ("vee", "or"),
+    ("oplus", "plus.circle"),
+    ("otimes", "times.circle"),
+    ("circ", "compose"),
+    ("star", "star.op"),
+    ("ldots", "dots.h"),
+    ("dots", "dots.h"),
+    ("cdots", "dots.h.c"),
+    ("vdots", "dots.v"),
+    ("ddots", "dots.down"),
+    ("qquad", "wide"),
+    ("langle", "angle.l"),
+    ("rangle", "angle.r"),
+    ("lceil", "ceil.l"),
+    ("rceil", "ceil.r"),
+    ("lfloor", "floor.l"),
+    ("rfloor", "floor.r"),
+    ("lbrace", "brace.l"),
+    ("rbrace", "brace.r"),
+    ("mid", "divides"),
+    ("vert", "bar.v"),
+    ("Vert", "bar.v.double"),
+    ("hbar", "planck.reduce"),
+    ("vdash", "tack.r"),
+    ("bmod", "mod"),
+];
+
+/// Commandes à un argument et leur fonction typst.
+const LATEX_FUNCTIONS: &[(&str, &str)] = &[
+    ("sqrt", "sqrt"),
+    ("mathbf", "bold"),
+    ("boldsymbol", "bold"),
+    ("bm", "bold"),
+    ("mathit", "italic"),
+    ("mathbb", "bb"),
+    ("mathcal", "cal"),
+    ("mathfrak", "frak"),
+    ("mathsf", "sans"),
+    ("mathtt", "mono"),
+    ("vec", "arrow"),
+    ("hat", "hat"),
+    ("widehat", "hat"),
+    ("tilde", "tilde"),
+    ("widetilde", "tilde"),
+    ("bar", "overline"),
+    ("overline", "overline"),
+    ("underline", "underline"),
+    ("dot", "dot"),
+    ("ddot", "dot.double"),
+    ("overbrace", "overbrace"),
+    ("underbrace", "underbrace"),
+];
+
+/// Commandes de mise en forme sans équivalent utile en typst, ignorées.
+const LATEX_IGNORED: &[&str] = &[
+    "left",
+    "right",
+    "big",
+    "Big",
+    "bigg",
+    "Bigg",
+    "bigl",
+    "bigr",
+    "Bigl",
+    "Bigr",
+    "displaystyle",
+    "textstyle",
+    "limits",
+    "nolimits",
+];
+
+/// Formule LaTeX (syntaxe KaTeX) en formule typst, pour les commandes courantes. Une
+/// commande inconnue est reprise sans `\` : si typst ne la connaît pas non plus, le document
+/// ne compile pas et `session_pdf` se rabat sur le LaTeX brut.
+fn latex_to_typst(latex: &str) -> String {
+    LatexConverter {
+        chars: latex.chars().peekable(),
+    }
+    .sequence(false)
+}
+
+struct LatexConverter<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl LatexConverter<'_> {
+    /// Convertit jusqu'à la fin, ou jusqu'à l'accolade fermante du groupe en cours.
+    fn sequence(&mut self, in_group: bool) -> String {
+        let mut out = String::new();
+        while let Some(&ch) = self.chars.peek() {
+            if ch == '}' && in_group {
+                self.chars.next();
+                break;
+            }
+            let atom = self.atom();
+            out.push_str(&atom);
+        }
+        out
+    }
+
+    fn atom(&mut self) -> String {
+        let Some(ch) = self.chars.next() else {
+            return String::new();
+        };
+        match ch {
+            '\\' => self.command(),
+            // Groupe LaTeX invisible : son contenu seul
+            '{' => self.sequence(true),
+            '}' => String::new(),
+            // Exposant et indice s'attachent à ce qui précède, sans espace.
+            '^' | '_' => format!("{ch}({})", self.argument()),
+            '%' => {
+                // Commentaire jusqu'à la fin de la ligne
+                for ch in self.chars.by_ref() {
+                    if ch == '\n' {
+                        break;
+                    }
+                }
+                String::new()
+            }
+            '~' => " ".to_string(),
+            // Variables d'une lettre, séparées pour ne pas former un nom typst
+            ch if ch.is_ascii_alphabetic() => format!("{ch} "),
+            ch if ch.is_whitespace() => " ".to_string(),
+            // Ponctuation qui a un sens dans les arguments ou la syntaxe typst
+            ',' | ';' | '"' | '/' | '#' | '$' | '@' | '`' | '[' | ']' => format!("\\{ch}"),
+            ch => ch.to_string(),
+        }
+    }
+
+    /// Argument d'une commande : un groupe `{…}` ou un seul élément.
+    fn argument(&mut self) -> String {
+        while self.chars.next_if(|ch| ch.is_whitespace()).is_some() {}
+        match self.chars.peek() {
+            Some('{') => {
+                self.chars.next();
+                self.sequence(true)
+            }
+            _ => self.atom(),
+        }
+    }
+
+    /// Contenu brut d'un groupe `{…}` (texte de `\text`, nom d'environnement).
+    fn raw_argument(&mut self) -> String {
+        while self.chars.next_if(|ch| ch.is_whitespace()).is_some() {}
+        if self.chars.next_if_eq(&'{').is_none() {
+            return self.chars.next().map(String::from).unwrap_or_default();
+        }
+        let mut depth = 0;
+        let mut raw = String::new();
+        for ch in self.chars.by_ref() {
+            match ch {
+                '{' => depth += 1,
+                '}' if depth == 0 => break,
+                '}' => depth -= 1,
+                _ => {}
+            }
+            raw.push(ch);
+        }
+        raw
+    }
+
+    fn command(&mut self) -> String {
+        let name: String =
+            std::iter::from_fn(|| self.chars.next_if(char::is_ascii_alphabetic)).collect();
+        if name.is_empty() {
+            return match self.chars.next() {
+                Some('\\') => " \\ ".to_string(),
+                Some(',') => " thin ".to_string(),
+                Some(':' | '>') => " med ".to_string(),
+                Some(';') => " thick ".to_string(),
+                Some('!') | None => String::new(),
+                Some(' ') => " space ".to_string(),
+                Some('|') => " bar.v.double ".to_string(),
+                Some(ch) => format!("\\{ch}"),
+            };
+        }
+        if LATEX_IGNORED.contains(&name.as_str()) {
+            // `\left.` : délimiteur invisible
+            self.chars.next_if_eq(&'.');
+            return String::new();
+        }
+        if let Some((_, function)) = LATEX_FUNCTIONS.iter().find(|(latex, _)| *latex == name) {
+            if name == "sqrt" && self.chars.next_if_eq(&'[').is_some() {
+                let index: String =
+                    std::iter::from_fn(|| self.chars.next_if(|ch| *ch != ']')).collect();
+                self.chars.next();
+                return format!("root({}, {})", latex_to_typst(&index), self.argument());
+            }
+            return format!("{function}({})", self.argument());
+        }
+        match name.as_str() {
+            "frac" | "dfrac" | "tfrac" | "cfrac" => {
+                let numerator = self.argument();
+                format!("frac({numerator}, {})", self.argument())
+            }
+            "binom" => {
+                let n = self.argument();
+                format!("binom({n}, {})", self.argument())
+            }
+            "text" | "textrm" | "textit" | "textbf" | "mbox" | "mathrm" => {
+                format!("upright({})", string_literal(&self.raw_argument()))
+            }
+            "operatorname" => format!(" op({}) ", string_literal(&self.raw_argument())),
+            "pmod" => format!(" (mod {}) ", self.argument()),
+            "begin" => {
+                let environment = self.raw_argument();
+                self.environment(&environment)
+            }
+            "end" => {
+                self.raw_argument();
+                String::new()
+            }
+            _ => {
+                let symbol = LATEX_SYMBOLS
+                    .iter()
+                    .find(|(latex, _)| *latex == name)
+                    .map_or(name.as_str(), |(_, typst)| typst);
+                format!(" {symbol} ")
+            }
+        }
+    }
+
+    /// Environnement `\begin{…}…\end{…}` : matrices, `cases`, alignements.
+    fn environment(&mut self, name: &str) -> String {
+        let end = format!("\\end{{{name}}}");
+        let begin = format!("\\begin{{{name}}}");
+        let rest: String = self.chars.clone().collect();
+        // Fin de l'environnement, en sautant les environnements du même nom imbriqués
+        let mut depth = 0;
+        let mut position = 0;
+        let body_len = loop {
+            let next_end = rest[position..].find(&end).map(|index| index + position);
+            let next_begin = rest[position..].find(&begin).map(|index| index + position);
+            match (next_begin, next_end) {
+                (Some(b), Some(e)) if b < e => {
+                    depth += 1;
+                    position = b + begin.len();
+                }
+                (_, Some(e)) if depth > 0 => {
+                    depth -= 1;
+                    position = e + end.len();
+                }
+                (_, Some(e)) => break e,
+                (_, None) => break rest.len(),
+            }
+        };
+        let mut body = &rest[..body_len];
+        for _ in 0..(body_len + end.len()).min(rest.chars().count()) {
+            self.chars.next();
+        }
+        // Spécification des colonnes d'`array` : `{cc}`
+        if name == "array" && body.starts_with('{') {
+            body = body.find('}').map_or(body, |index| &body[index + 1..]);
+        }
+
+        let rows: Vec<Vec<String>> = split_top_level(body, "\\\\")
+            .into_iter()
+            .filter(|row| !row.trim().is_empty())
+            .map(|row| {
+                split_top_level(row, "&")
+                    .into_iter()
+                    .map(|cell| latex_to_typst(cell).trim().to_string())
+                    .collect()
+            })
+            .collect();
+        let delim = match name {
+            "pmatrix" => Some("\"(\""),
+            "bmatrix" => Some("\"[\""),
+            "Bmatrix" => Some("\"{\""),
+            "vmatrix" => Some("\"|\""),
+            "Vmatrix" => Some("\"||\""),
+            "matrix" | "smallmatrix" | "array" => Some("#none"),
+            _ => None,
+        };
+        if let Some(delim) = delim {
+            let rows: Vec<String> = rows.iter().map(|cells| cells.join(", ")).collect();
+            return format!("mat(delim: {delim}, {})", rows.join("; "));
+        }
+        if name == "cases" {
+            let rows: Vec<String> = rows.iter().map(|cells| cells.join(" & ")).collect();
+            return format!("cases({})", rows.join(", "));
+        }
+        // align, aligned, gather, equation, split... : lignes et points d'alignement
+        let rows: Vec<String> = rows.iter().map(|cells| cells.join(" & ")).collect();
+        rows.join(" \\ ")
+    }
+}
+
+/// Découpe `text` sur `separator` hors des groupes `{…}` et des environnements imbriqués.
+fn split_top_level<'a>(text: &'a str, separator: &str) -> Vec<&'a str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    let mut index = 0;
+    while index < text.len() {
+        let rest = &text[index..];
+        if rest.starts_with("\\begin") {
+            depth += 1;
+        } else if rest.starts_with("\\end") {
+            depth = depth.saturating_sub(1);
+        } else if depth == 0 && rest.starts_with(separator) {
+            parts.push(&text[start..index]);
+            index += separator.len();
+            start = index;
+            continue;
+        }
+        match rest.chars().next() {
+            Some('{') => depth += 1,
+            Some('}') => depth = depth.saturating_sub(1),
+            // `\&`, `\\` dans un groupe... : le caractère échappé n'est pas un séparateur
+            Some('\\') if separator != "\\\\" || !rest.starts_with("\\\\") => {
+                index += rest.chars().nth(1).map_or(1, |ch| 1 + ch.len_utf8());
+                continue;
+            }
+            _ => {}
+        }
+        index += rest.chars().next().map_or(1, char::len_utf8);
+    }
+    parts.push(&text[start..]);
+    parts
+}
@@ -0,0 +1,529 @@
+//! Plugins WASM (`PLUGIN_DIR`) : l'exploitant dépose un module WebAssembly dans le dossier
+//! pour ajouter un outil appelable par le modèle ou un filtre du flux des réponses, sans
+//! recompiler le backend. Le dossier est relu par la tâche `plugins` et par
+//! `POST /api/admin/plugins/reload` ; un module déjà chargé et inchangé n'est pas recompilé.
+//!
+//! Le plugin `meteo.wasm` (ou `meteo.wat`, au format texte) s'appelle `meteo`. Il n'importe
+//! rien : sans accès aux fichiers, au réseau ni à l'horloge, il ne voit que les textes que
+//! le backend lui passe. Chaque appel est borné en instructions (`PLUGIN_FUEL`) et la
+//! mémoire du module en taille (`PLUGIN_MAX_MEMORY_MB`). Le module exporte :
+//!
+//! - `memory` et `alloc(len: i32) -> i32`, où le backend écrit les textes qu'il envoie ;
+//! - `describe() -> i64` : JSON `{"kind": "tool" | "filter", "description": "...",
+//!   "parameters": {...}}`, les paramètres (schéma JSON) n'étant lus que pour un outil ;
+//! - pour un outil, `call(ptr: i32, len: i32) -> i64` : arguments JSON de l'appel, résultat
+//!   renvoyé au modèle ;
+//! - pour un filtre, `push(ptr: i32, len: i32) -> i64` et, s'il retient du texte,
+//!   `finish() -> i64`, comme [`crate::hooks::StreamFilter`]. Chaque réponse a sa propre
+//!   instance, appelée hors du runtime async.
+//!
+//! Les textes sortants sont en UTF-8 et renvoyés en `i64` : adresse dans `memory` sur les 32
+//! bits de poids fort, longueur sur les 32 autres.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use wasmtime::{Engine, Instance, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use crate::{
+    calendar::CALENDAR_TOOL,
+    providers::{CompletionStream, ProviderChunk},
+    screenshot::SCREENSHOT_TOOL,
+    tools::EDIT_FILE_TOOL,
+};
+
+/// Au-delà, un nom de fichier ne peut pas servir de nom d'outil
+const MAX_NAME_LEN: usize = 64;
+
+/// Réglages des plugins ; aucun n'est chargé sans dossier.
+#[derive(Clone, Debug)]
+pub struct PluginSettings {
+    pub dir: Option<String>,
+    /// Instructions WebAssembly exécutées au plus par appel
+    pub fuel: u64,
+    /// Taille maximale de la mémoire d'un module
+    pub max_memory_bytes: usize,
+    /// Intervalle de la tâche `plugins` qui relit le dossier
+    pub reload_interval: Duration,
+}
+
+impl Default for PluginSettings {
+    fn default() -> Self {
+        PluginSettings {
+            dir: None,
+            fuel: 10_000_000,
+            max_memory_bytes: 16 * 1024 * 1024,
+            reload_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PluginKind {
+    /// Outil proposé au modèle quand la requête le demande (`completion_params.plugins`)
+    Tool,
+    /// Filtre appliqué au texte de toutes les réponses, après ceux de `STREAM_HOOKS`
+    Filter,
+}
+
+/// Contenu de `describe()`.
+#[derive(Deserialize)]
+struct Description {
+    kind: PluginKind,
+    #[serde(default)]
+    description: String,
+    #[serde(default = "empty_parameters")]
+    parameters: Value,
+}
+
+fn empty_parameters() -> Value {
+    serde_json::json!({ "type": "object", "properties": {} })
+}
+
+/// Module chargé, avec son fichier, sa date et sa taille pour savoir s'il a changé.
+struct Plugin {
+    info: PluginInfo,
+    module: Module,
+    path: PathBuf,
+    modified: SystemTime,
+    len: u64,
+}
+
+/// Plugin chargé (`GET /api/admin/plugins`).
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct PluginInfo {
+    pub(crate) name: String,
+    pub(crate) kind: PluginKind,
+    pub(crate) description: String,
+    /// Schéma JSON des arguments d'un outil
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) parameters: Option<Value>,
+}
+
+/// Fichier du dossier qui n'a pas pu être chargé.
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct PluginError {
+    pub(crate) file: String,
+    pub(crate) error: String,
+}
+
+/// Réponse de `GET /api/admin/plugins` et de `POST /api/admin/plugins/reload`.
+#[derive(Serialize)]
+pub(crate) struct PluginReport {
+    pub(crate) dir: Option<String>,
+    pub(crate) plugins: Vec<PluginInfo>,
+    pub(crate) errors: Vec<PluginError>,
+}
+
+#[derive(Default)]
+struct Loaded {
+    plugins: BTreeMap<String, Arc<Plugin>>,
+    errors: Vec<PluginError>,
+}
+
+/// Plugins du dossier, remplacés d'un bloc à chaque relecture : une réponse en cours garde
+/// les filtres qu'elle a commencé avec.
+pub(crate) struct Plugins {
+    engine: Engine,
+    settings: PluginSettings,
+    loaded: RwLock<Arc<Loaded>>,
+}
+
+impl Plugins {
+    pub(crate) fn new(settings: &PluginSettings) -> Result<Self, String> {
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(|err| err.to_string())?;
+        Ok(Plugins {
+            engine,
+            settings: settings.clone(),
+            loaded: RwLock::new(Arc::default()),
+        })
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.settings.dir.is_some()
+    }
+
+    /// Relit le dossier : charge les nouveaux modules, recompile ceux qui ont changé et
+    /// oublie ceux qui ont disparu. Renvoie le nombre de plugins chargés.
+    pub(crate) async fn reload(self: &Arc<Self>) -> Result<usize, String> {
+        let Some(dir) = self.settings.dir.clone() else {
+            return Ok(0);
+        };
+        let plugins = self.clone();
+        let loaded = tokio::task::spawn_blocking(move || plugins.scan(Path::new(&dir)))
+            .await
+            .map_err(|err| err.to_string())??;
+        let count = loaded.plugins.len();
+        *self.loaded.write().unwrap() = Arc::new(loaded);
+        Ok(count)
+    }
+
+    fn scan(&self, dir: &Path) -> Result<Loaded, String> {
+        let previous = self.loaded.read().unwrap().clone();
+        let entries = std::fs::read_dir(dir)
+            .map_err(|err| format!("PLUGIN_DIR {} illisible: {err}", dir.display()))?;
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                matches!(
+                    path.extension().and_then(|ext| ext.to_str()),
+                    Some("wasm" | "wat")
+                )
+            })
+            .collect();
+        files.sort();
+
+        let mut loaded = Loaded::default();
+        for path in files {
+            let file = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            match self.load(&path, &previous) {
+                Ok(plugin) if loaded.plugins.contains_key(&plugin.info.name) => {
+                    loaded.errors.push(PluginError {
+                        file,
+                        error: format!("un autre fichier définit déjà {}", plugin.info.name),
+                    });
+                }
+                Ok(plugin) => {
+                    loaded.plugins.insert(plugin.info.name.clone(), plugin);
+                }
+                Err(error) => loaded.errors.push(PluginError { file, error }),
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Module du fichier, repris tel quel s'il n'a pas changé depuis la dernière relecture.
+    fn load(&self, path: &Path, previous: &Loaded) -> Result<Arc<Plugin>, String> {
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .filter(|name| {
+                !name.is_empty()
+                    && name.len() <= MAX_NAME_LEN
+                    && name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            })
+            .ok_or("nom invalide : lettres, chiffres, `_` ou `-`, 64 caractères au plus")?;
+        if [EDIT_FILE_TOOL, CALENDAR_TOOL, SCREENSHOT_TOOL].contains(&name) {
+            return Err(format!("{name} est le nom d'un outil intégré"));
+        }
+        let metadata = std::fs::metadata(path).map_err(|err| err.to_string())?;
+        let modified = metadata.modified().map_err(|err| err.to_string())?;
+        if let Some(plugin) = previous.plugins.get(name)
+            && plugin.path == path
+            && plugin.modified == modified
+            && plugin.len == metadata.len()
+        {
+            return Ok(plugin.clone());
+        }
+
+        let module = Module::from_file(&self.engine, path).map_err(|err| format!("{err:#}"))?;
+        let description = self.instantiate(&module)?.call("describe", None)?;
+        let description: Description = serde_json::from_str(&description)
+            .map_err(|err| format!("describe() invalide: {err}"))?;
+        let exports = match description.kind {
+            PluginKind::Tool => &["call"][..],
+            PluginKind::Filter => &["push"][..],
+        };
+        for export in exports {
+            if module.get_export(export).is_none() {
+                return Err(format!("export {export} manquant"));
+            }
+        }
+        Ok(Arc::new(Plugin {
+            info: PluginInfo {
+                name: name.to_string(),
+                kind: description.kind,
+                description: description.description,
+                parameters: (description.kind == PluginKind::Tool)
+                    .then_some(description.parameters),
+            },
+            module,
+            path: path.to_path_buf(),
+            modified,
+            len: metadata.len(),
+        }))
+    }
+
+    fn instantiate(&self, module: &Module) -> Result<PluginInstance, String> {
+        PluginInstance::new(&self.engine, module, &self.settings)
+    }
+
+    pub(crate) fn report(&self) -> PluginReport {
+        let loaded = self.loaded.read().unwrap().clone();
+        PluginReport {
+            dir: self.settings.dir.clone(),
+            plugins: loaded
+                .plugins
+                .values()
+                .map(|plugin| plugin.info.clone())
+                .collect(),
+            errors: loaded.errors.clone(),
+        }
+    }
+
+    /// Outil chargé sous ce nom.
+    pub(crate) fn tool(&self, name: &str) -> Option<PluginInfo> {
+        self.loaded
+            .read()
+            .unwrap()
+            .plugins
+            .get(name)
+            .filter(|plugin| plugin.info.kind == PluginKind::Tool)
+            .map(|plugin| plugin.info.clone())
+    }
+
+    /// Exécute l'outil `name` hors du runtime async ; l'erreur est rédigée pour le modèle.
+    pub(crate) async fn call_tool(self: &Arc<Self>, name: &str, arguments: &str) -> String {
+        let Some(plugin) = self.loaded.read().unwrap().plugins.get(name).cloned() else {
+            return format!("Outil indisponible : {name}.");
+        };
+        let plugins = self.clone();
+        let arguments = arguments.to_string();
+        let result = tokio::task::spawn_blocking(move || {
+            plugins
+                .instantiate(&plugin.module)?
+                .call("call", Some(&arguments))
+        })
+        .await
+        .map_err(|err| err.to_string())
+        .and_then(|result| result);
+        match result {
+            Ok(output) => output,
+            Err(err) => {
+                eprintln!("Plugin {name} en échec: {err}");
+                format!("L'outil {name} a échoué : {err}.")
+            }
+        }
+    }
+}
+
+impl Plugins {
+    fn has_filters(&self) -> bool {
+        let loaded = self.loaded.read().unwrap().clone();
+        loaded
+            .plugins
+            .values()
+            .any(|plugin| plugin.info.kind == PluginKind::Filter)
+    }
+
+    /// Les filtres chargés au début de la réponse, dans l'ordre des noms.
+    fn start_filters(&self) -> PluginFilters {
+        let loaded = self.loaded.read().unwrap().clone();
+        let filters = loaded
+            .plugins
+            .values()
+            .filter(|plugin| plugin.info.kind == PluginKind::Filter)
+            .filter_map(|plugin| match self.instantiate(&plugin.module) {
+                Ok(instance) => Some((plugin.info.name.clone(), instance)),
+                Err(err) => {
+                    eprintln!("Plugin {} en échec: {err}", plugin.info.name);
+                    None
+                }
+            })
+            .collect();
+        PluginFilters(filters)
+    }
+}
+
+/// Fait passer le texte de la réponse par les filtres des plugins, comme `hooks::apply`.
+/// Les modules tournent hors du runtime async : l'instanciation et chaque morceau passent
+/// par `spawn_blocking`, un filtre lent ne retient que sa propre réponse.
+pub(crate) fn apply_filters(plugins: &Arc<Plugins>, stream: CompletionStream) -> CompletionStream {
+    if !plugins.has_filters() {
+        return stream;
+    }
+    let plugins = plugins.clone();
+    let filters: Arc<Mutex<Option<PluginFilters>>> = Arc::default();
+    Box::pin(
+        stream
+            .map(Some)
+            .chain(stream::once(async { None }))
+            .then(move |item| {
+                let (plugins, filters) = (plugins.clone(), filters.clone());
+                async move {
+                    let (text, error) = match item {
+                        Some(Ok(ProviderChunk::Text(text))) => (Some(text), None),
+                        Some(Ok(chunk)) => return vec![Ok(chunk)],
+                        Some(Err(err)) => (None, Some(Err(err))),
+                        None => (None, None),
+                    };
+                    let text = run_filters(plugins, filters, text).await;
+                    let mut chunks = Vec::new();
+                    if !text.is_empty() {
+                        chunks.push(Ok(ProviderChunk::Text(text)));
+                    }
+                    chunks.extend(error);
+                    chunks
+                }
+            })
+            .flat_map(stream::iter),
+    )
+}
+
+/// Passe `text` aux filtres de la réponse, instanciés au premier morceau, ou les vide à la
+/// fin du flux (`None`).
+async fn run_filters(
+    plugins: Arc<Plugins>,
+    filters: Arc<Mutex<Option<PluginFilters>>>,
+    text: Option<String>,
+) -> String {
+    // Verrou empoisonné par un filtre qui a paniqué : la tâche bloquante le signalera.
+    if text.is_none() && filters.lock().is_ok_and(|filters| filters.is_none()) {
+        return String::new();
+    }
+    let unfiltered = text.clone().unwrap_or_default();
+    tokio::task::spawn_blocking(move || {
+        let mut filters = filters.lock().unwrap();
+        let filters = filters.get_or_insert_with(|| plugins.start_filters());
+        match text {
+            Some(text) => filters.push(&text),
+            None => filters.finish(),
+        }
+    })
+    .await
+    .unwrap_or_else(|err| {
+        eprintln!("Filtres des plugins en échec: {err}");
+        unfiltered
+    })
+}
+
+/// Instances des filtres d'une réponse. Un filtre en échec (instructions ou mémoire épuisées,
+/// sortie invalide) laisse passer le texte tel quel et n'est plus appelé pour cette réponse.
+struct PluginFilters(Vec<(String, PluginInstance)>);
+
+impl PluginFilters {
+    /// Comme [`crate::hooks::StreamFilter::push`].
+    fn push(&mut self, text: &str) -> String {
+        let mut text = text.to_string();
+        self.0.retain_mut(|(name, instance)| {
+            match instance.call("push", Some(&text)) {
+                Ok(filtered) => text = filtered,
+                Err(err) => return disable(name, err),
+            }
+            true
+        });
+        text
+    }
+
+    /// Comme `hooks::flush` : ce que retenait un filtre passe encore par les suivants.
+    fn finish(&mut self) -> String {
+        let mut text = String::new();
+        self.0.retain_mut(|(name, instance)| {
+            if !text.is_empty() {
+                match instance.call("push", Some(&text)) {
+                    Ok(filtered) => text = filtered,
+                    Err(err) => return disable(name, err),
+                }
+            }
+            if instance.exports("finish") {
+                match instance.call("finish", None) {
+                    Ok(retained) => text.push_str(&retained),
+                    Err(err) => return disable(name, err),
+                }
+            }
+            true
+        });
+        text
+    }
+}
+
+fn disable(name: &str, err: String) -> bool {
+    eprintln!("Filtre {name} désactivé pour cette réponse: {err}");
+    false
+}
+
+/// Instance d'un module, avec ses limites.
+struct PluginInstance {
+    store: Store<StoreLimits>,
+    instance: Instance,
+    memory: Memory,
+    fuel: u64,
+}
+
+impl PluginInstance {
+    fn new(engine: &Engine, module: &Module, settings: &PluginSettings) -> Result<Self, String> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(settings.max_memory_bytes)
+            .instances(1)
+            .memories(1)
+            .build();
+        let mut store = Store::new(engine, limits);
+        store.limiter(|limits| limits);
+        store
+            .set_fuel(settings.fuel)
+            .map_err(|err| err.to_string())?;
+        // Aucun import : un module qui en demande ne s'instancie pas.
+        let instance = Linker::new(engine)
+            .instantiate(&mut store, module)
+            .map_err(|err| format!("{err:#}"))?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or("export memory manquant")?;
+        Ok(PluginInstance {
+            store,
+            instance,
+            memory,
+            fuel: settings.fuel,
+        })
+    }
+
+    fn exports(&mut self, export: &str) -> bool {
+        self.instance.get_func(&mut self.store, export).is_some()
+    }
+
+    /// Appelle `export` avec `input` écrit dans la mémoire du module (ou sans argument).
+    fn call(&mut self, export: &str, input: Option<&str>) -> Result<String, String> {
+        self.store
+            .set_fuel(self.fuel)
+            .map_err(|err| err.to_string())?;
+        let packed = match input {
+            Some(input) => {
+                let len = i32::try_from(input.len()).map_err(|_| "texte trop long")?;
+                let ptr = self
+                    .instance
+                    .get_typed_func::<i32, i32>(&mut self.store, "alloc")
+                    .map_err(|err| format!("{err:#}"))?
+                    .call(&mut self.store, len)
+                    .map_err(|err| format!("{err:#}"))?;
+                self.memory
+                    .write(&mut self.store, ptr as u32 as usize, input.as_bytes())
+                    .map_err(|err| err.to_string())?;
+                self.instance
+                    .get_typed_func::<(i32, i32), i64>(&mut self.store, export)
+                    .map_err(|err| format!("{err:#}"))?
+                    .call(&mut self.store, (ptr, len))
+            }
+            None => self
+                .instance
+                .get_typed_func::<(), i64>(&mut self.store, export)
+                .map_err(|err| format!("{err:#}"))?
+                .call(&mut self.store, ()),
+        }
+        .map_err(|err| format!("{err:#}"))?;
+        let (ptr, len) = (
+            (packed as u64 >> 32) as usize,
+            packed as u64 as u32 as usize,
+        );
+        let bytes = self
+            .memory
+            .data(&self.store)
+            .get(ptr..ptr.saturating_add(len))
+            .ok_or("sortie hors de la mémoire du module")?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| "sortie qui n'est pas en UTF-8".to_string())
+    }
+}
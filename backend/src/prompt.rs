@@ -0,0 +1,436 @@
+//! Prompt système assemblé à chaque requête à partir d'un gabarit : prompt de base (règles
+//! de formatage), date et heure, nom, instructions et persona de l'utilisateur (préférences),
+//! instructions du projet, langue et fuseau horaire. Le gabarit (`SYSTEM_PROMPT_TEMPLATE_FILE`) utilise une syntaxe à
+//! la Mustache : `{{variable}}` insère une valeur, `{{#variable}}…{{/variable}}` n'est rendu
+//! que si elle est renseignée. `GET /api/admin/system-prompt` montre le résultat.
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use chrono::{DateTime, Datelike, Utc, Weekday};
+use chrono_tz::Tz;
+
+use crate::{
+    AppState,
+    models::{SystemPromptPreview, UserPreferences},
+    service::estimate_tokens,
+};
+
+/// Règles de formatage des réponses (Markdown, LaTeX, code), variable `base`.
+const BASE_PROMPT: &str = r"
+<SYSTEM_PROMPT>
+TU ES UN **ASSISTANT IA ULTRA-EXPERT** SPÉCIALISÉ DANS LA PRODUCTION DE RÉPONSES **STRICTEMENT FORMATÉES EN MARKDOWN** ET TOTALLEMENT COMPATIBLES AVEC **react-markdown + rehype-katex**.
+
+TA MISSION EST D’APPLIQUER SANS EXCEPTION LES RÈGLES SUIVANTES.
+
+---
+
+# 🎯 **INSTRUCTIONS PRINCIPALES (OBLIGATOIRES)**
+
+- TU DOIS **LIRE, COMPRENDRE ET ANALYSER** la question de l’utilisateur avant de répondre (**COMPRÉHENSION AVANT PRODUCTION**).  
+- TU DOIS **RÉPONDRE EXCLUSIVEMENT EN MARKDOWN (GFM)**.  
+- TU DOIS **UTILISER LA LANGUE DE L’UTILISATEUR** (français, anglais, etc.).  
+- TU DOIS COMMENCER TA REPONSE PAR UN TITRE DE NIVEAU 1 EN MARKDOWN RESUMANT LE SUJET.
+- **AVANT DE RÉPONDRE**, TU DOIS EXPLIQUER TON RAISONNEMENT ÉTAPE PAR ÉTAPE À L'INTÉRIEUR DE BALISES `<thinking>`. CHAQUE ÉTAPE DOIT COMMENCER PAR UN TIRET `- `.
+  Exemple :
+  <thinking>
+  - Analyse de la demande utilisateur...
+  - Identification des concepts clés...
+  - Planification de la réponse...
+  </thinking>
+
+---
+
+# 🧮 **RÈGLES SPÉCIFIQUES POUR LE CODE ET LES MATHS**
+
+### **FORMAT MATHÉMATIQUE**
+- ÉCRIS LES MATHÉMATIQUES EN LaTeX INLINE :  
+  `$…$`
+- ÉCRIS LES ÉQUATIONS EN BLOC :  
+  $$
+  … équation …
+  $$
+
+### **INTERDICTIONS LaTeX**
+TU DOIS **NE JAMAIS UTILISER** d’environnements de mise en page LaTeX :
+- `\begin{table}`, `\begin{tabular}`, `\begin{figure}`, `\begin{document}`, etc.
+
+SEULS les environnements **mathématiques** sont autorisés :
+- `aligned`, `cases`, `matrix`, etc.
+
+### **TABLEAUX**
+- TU DOIS **TOUJOURS** UTILISER DES TABLES MARKDOWN  
+  même si l’entrée contient du LaTeX tabulaire.
+
+### **CODE**
+- TU DOIS **TOUJOURS** UTILISER DES BLOCS DE CODE TRIPLE-BACKTICKS :
+  ```lang
+  ...
+";
+
+/// Gabarit utilisé sans `SYSTEM_PROMPT_TEMPLATE_FILE`. La date et l'heure viennent en dernier :
+/// elles changent à chaque minute, le reste du prompt reste un préfixe stable pour le cache
+/// des providers.
+const DEFAULT_TEMPLATE: &str = "{{base}}
+{{#project_instructions}}
+
+# 📁 **INSTRUCTIONS DU PROJET**
+{{project_instructions}}
+{{/project_instructions}}
+{{#persona}}
+
+# 🎭 **PERSONA**
+{{persona}}
+{{/persona}}
+{{#custom_instructions}}
+
+# 📝 **INSTRUCTIONS DE L'UTILISATEUR**
+{{custom_instructions}}
+{{/custom_instructions}}
+{{#user_name}}
+
+# 👤 **UTILISATEUR**
+Tu t'adresses à {{user_name}}.
+{{/user_name}}
+{{#locale}}
+
+# 🌐 **LANGUE ET RÉGION**
+L'utilisateur utilise la locale {{locale}} : suis ses conventions pour les dates, les heures, les nombres et les unités.
+{{/locale}}
+{{#datetime}}
+
+# 🕒 **CONTEXTE**
+Date et heure actuelles : {{datetime}}, fuseau horaire {{timezone}}. Sers-t'en pour toute question relative à la date (« quel jour serons-nous vendredi prochain ? ») plutôt que de supposer une date.
+{{/datetime}}
+";
+
+/// Variables connues des gabarits ; toute autre est refusée au démarrage.
+pub(crate) const VARIABLES: &[&str] = &[
+    "base",
+    "datetime",
+    "timezone",
+    "locale",
+    "user_name",
+    "custom_instructions",
+    "persona",
+    "project_instructions",
+];
+
+/// Gabarit et instructions du projet, lus au démarrage (`SYSTEM_PROMPT_TEMPLATE_FILE`,
+/// `PROJECT_INSTRUCTIONS_FILE`), et date du prompt (`PROMPT_DATETIME`, `DEFAULT_TIMEZONE`).
+#[derive(Clone, Debug, Default)]
+pub struct PromptSettings {
+    /// Gabarit remplaçant celui par défaut
+    pub template: Option<String>,
+    /// Instructions communes à toutes les discussions du déploiement
+    pub project_instructions: Option<String>,
+    pub datetime: DatetimePrecision,
+    /// Fuseau IANA des utilisateurs qui n'en ont pas choisi ; UTC si absent
+    pub default_timezone: Option<String>,
+}
+
+/// Précision de la date et de l'heure données au modèle. Au jour, par défaut, le prompt
+/// système reste stable toute la journée et le cache de prompt des providers couvre
+/// l'historique ; à la minute, il change d'une minute à l'autre et le cache est perdu.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DatetimePrecision {
+    Off,
+    #[default]
+    Day,
+    Minute,
+}
+
+impl FromStr for DatetimePrecision {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "off" | "false" | "0" => Ok(DatetimePrecision::Off),
+            "day" => Ok(DatetimePrecision::Day),
+            "minute" | "true" | "1" => Ok(DatetimePrecision::Minute),
+            other => Err(format!("précision inconnue: {other} (off, day, minute)")),
+        }
+    }
+}
+
+/// Gabarit analysé et parts fixes du prompt.
+pub(crate) struct SystemPrompt {
+    template: Template,
+    source: String,
+    project_instructions: Option<String>,
+    datetime: DatetimePrecision,
+    default_timezone: Tz,
+}
+
+impl SystemPrompt {
+    /// Analyse le gabarit ; une erreur de syntaxe, une variable ou un fuseau horaire inconnus
+    /// sont des erreurs de configuration.
+    pub(crate) fn new(settings: &PromptSettings) -> Result<Self, String> {
+        let source = settings
+            .template
+            .clone()
+            .unwrap_or_else(|| DEFAULT_TEMPLATE.to_string());
+        Ok(SystemPrompt {
+            template: Template::parse(&source)?,
+            source,
+            project_instructions: settings
+                .project_instructions
+                .as_deref()
+                .map(str::trim)
+                .filter(|instructions| !instructions.is_empty())
+                .map(str::to_string),
+            datetime: settings.datetime,
+            default_timezone: match settings.default_timezone.as_deref() {
+                Some(timezone) => parse_timezone(timezone)
+                    .ok_or_else(|| format!("fuseau horaire inconnu: {timezone}"))?,
+                None => Tz::UTC,
+            },
+        })
+    }
+}
+
+/// Fuseau horaire IANA (`Europe/Paris`).
+pub(crate) fn parse_timezone(name: &str) -> Option<Tz> {
+    name.trim().parse().ok()
+}
+
+/// Valeurs des variables pour une requête.
+pub(crate) struct PromptParts {
+    values: BTreeMap<&'static str, String>,
+}
+
+impl PromptParts {
+    /// Parts du moment : date et heure dans le fuseau de l'utilisateur, préférences de
+    /// l'utilisateur. Des préférences illisibles n'empêchent pas la requête : leurs parts
+    /// restent vides et l'heure est donnée dans le fuseau par défaut.
+    pub(crate) async fn load(state: &AppState) -> Self {
+        let preferences = match state.repo.fetch_preferences().await {
+            Ok(preferences) => preferences.unwrap_or_default(),
+            Err(err) => {
+                eprintln!("Impossible de lire les préférences pour le prompt: {err}");
+                UserPreferences::default()
+            }
+        };
+        let mut values = BTreeMap::new();
+        values.insert("base", BASE_PROMPT.trim().to_string());
+        let settings = &state.system_prompt;
+        let timezone = preferences
+            .timezone
+            .as_deref()
+            .and_then(parse_timezone)
+            .unwrap_or(settings.default_timezone);
+        if let Some(datetime) = current_datetime(Utc::now(), timezone, settings.datetime) {
+            values.insert("datetime", datetime);
+            values.insert("timezone", timezone.name().to_string());
+        }
+        let optional = [
+            ("locale", preferences.language),
+            ("user_name", preferences.display_name),
+            ("custom_instructions", preferences.custom_instructions),
+            ("persona", preferences.persona),
+            (
+                "project_instructions",
+                state.system_prompt.project_instructions.clone(),
+            ),
+        ];
+        for (name, value) in optional {
+            if let Some(value) = value {
+                values.insert(name, value);
+            }
+        }
+        PromptParts { values }
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.values
+            .get(name)
+            .map(String::as_str)
+            .filter(|value| !value.trim().is_empty())
+    }
+}
+
+/// Prompt système de la prochaine requête.
+pub(crate) async fn system_prompt(state: &AppState) -> String {
+    let parts = PromptParts::load(state).await;
+    state.system_prompt.template.render(&parts)
+}
+
+/// Règles de formatage seules, sans date ni préférences ou instructions du propriétaire :
+/// prompt des widgets.
+pub(crate) fn base_prompt() -> &'static str {
+    BASE_PROMPT.trim()
+}
+
+/// Prompt système détaillé, pour `GET /api/admin/system-prompt`.
+pub(crate) async fn preview(state: &AppState) -> SystemPromptPreview {
+    let parts = PromptParts::load(state).await;
+    let prompt = state.system_prompt.template.render(&parts);
+    SystemPromptPreview {
+        estimated_tokens: estimate_tokens(&prompt),
+        variables: VARIABLES
+            .iter()
+            .map(|name| (name.to_string(), parts.get(name).map(str::to_string)))
+            .collect(),
+        template: state.system_prompt.source.clone(),
+        prompt,
+    }
+}
+
+/// « vendredi 16 octobre 2026 (2026-10-16), 16:05 (UTC+02:00) » à la minute, sans l'heure au
+/// jour ; `None` si la date n'est pas donnée au modèle.
+fn current_datetime(
+    now: DateTime<Utc>,
+    timezone: Tz,
+    precision: DatetimePrecision,
+) -> Option<String> {
+    let local = now.with_timezone(&timezone);
+    let weekday = match local.weekday() {
+        Weekday::Mon => "lundi",
+        Weekday::Tue => "mardi",
+        Weekday::Wed => "mercredi",
+        Weekday::Thu => "jeudi",
+        Weekday::Fri => "vendredi",
+        Weekday::Sat => "samedi",
+        Weekday::Sun => "dimanche",
+    };
+    const MONTHS: [&str; 12] = [
+        "janvier",
+        "février",
+        "mars",
+        "avril",
+        "mai",
+        "juin",
+        "juillet",
+        "août",
+        "septembre",
+        "octobre",
+        "novembre",
+        "décembre",
+    ];
+    let date = format!(
+        "{weekday} {} {} {} ({})",
+        local.day(),
+        MONTHS[local.month0() as usize],
+        local.year(),
+        local.format("%Y-%m-%d")
+    );
+    match precision {
+        DatetimePrecision::Off => None,
+        DatetimePrecision::Day => Some(date),
+        DatetimePrecision::Minute => Some(format!(
+            "{date}, {} (UTC{})",
+            local.format("%H:%M"),
+            local.format("%:z")
+        )),
+    }
+}
+
+enum Node {
+    Text(String),
+    Variable(&'static str),
+    Section(&'static str, Vec<Node>),
+}
+
+struct Template {
+    nodes: Vec<Node>,
+}
+
+impl Template {
+    fn parse(source: &str) -> Result<Self, String> {
+        // Pile des sections ouvertes : nom et nœuds déjà lus avant elle.
+        let mut open: Vec<(&'static str, Vec<Node>)> = Vec::new();
+        let mut nodes = Vec::new();
+        let mut rest = source;
+        while let Some(start) = rest.find("{{") {
+            let end = rest[start..]
+                .find("}}")
+                .map(|end| start + end)
+                .ok_or_else(|| "balise {{ non fermée".to_string())?;
+            let tag = rest[start + 2..end].trim();
+            let mut text = &rest[..start];
+            rest = &rest[end + 2..];
+
+            // Une balise de section seule sur sa ligne ne laisse pas de ligne vide.
+            let section_tag = tag.starts_with('#') || tag.starts_with('/');
+            if section_tag
+                && text
+                    .rsplit('\n')
+                    .next()
+                    .is_some_and(|line| line.trim().is_empty())
+                && (rest.is_empty() || rest.starts_with('\n'))
+            {
+                text = text.trim_end_matches([' ', '\t']);
+                rest = rest.strip_prefix('\n').unwrap_or(rest);
+            }
+            if !text.is_empty() {
+                nodes.push(Node::Text(text.to_string()));
+            }
+
+            if let Some(name) = tag.strip_prefix('#') {
+                open.push((variable(name.trim())?, std::mem::take(&mut nodes)));
+            } else if let Some(name) = tag.strip_prefix('/') {
+                let name = variable(name.trim())?;
+                match open.pop() {
+                    Some((opened, before)) if opened == name => {
+                        let section = std::mem::replace(&mut nodes, before);
+                        nodes.push(Node::Section(name, section));
+                    }
+                    Some((opened, _)) => {
+                        return Err(format!("{{{{/{name}}}}} ferme la section {opened}"));
+                    }
+                    None => return Err(format!("{{{{/{name}}}}} sans section ouverte")),
+                }
+            } else {
+                nodes.push(Node::Variable(variable(tag)?));
+            }
+        }
+        if let Some((name, _)) = open.last() {
+            return Err(format!("section {name} non fermée"));
+        }
+        if !rest.is_empty() {
+            nodes.push(Node::Text(rest.to_string()));
+        }
+        Ok(Template { nodes })
+    }
+
+    /// Rendu sans plus d'une ligne vide d'affilée, ni blancs autour.
+    fn render(&self, parts: &PromptParts) -> String {
+        let mut output = String::new();
+        render_nodes(&self.nodes, parts, &mut output);
+        let mut prompt = String::with_capacity(output.len());
+        let mut newlines = 0;
+        for ch in output.trim().chars() {
+            newlines = if ch == '\n' { newlines + 1 } else { 0 };
+            if newlines <= 2 {
+                prompt.push(ch);
+            }
+        }
+        prompt
+    }
+}
+
+fn render_nodes(nodes: &[Node], parts: &PromptParts, output: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => output.push_str(text),
+            Node::Variable(name) => output.push_str(parts.get(name).unwrap_or_default().trim()),
+            Node::Section(name, nodes) => {
+                if parts.get(name).is_some() {
+                    render_nodes(nodes, parts, output);
+                }
+            }
+        }
+    }
+}
+
+fn variable(name: &str) -> Result<&'static str, String> {
+    VARIABLES
+        .iter()
+        .find(|known| **known == name)
+        .copied()
+        .ok_or_else(|| {
+            format!(
+                "variable inconnue {{{{{name}}}}} (connues : {})",
+                VARIABLES.join(", ")
+            )
+        })
+}
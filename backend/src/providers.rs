@@ -0,0 +1,1373 @@
+//! Modèles disponibles et appels aux providers en streaming. Chaque provider (Groq,
+//! OpenAI, Anthropic, Gemini, Ollama) implémente `Provider` dans son module. Chaque modèle
+//! est décrit une fois dans `MODELS` (identifiant, provider, prix, limites, paramètres) ;
+//! `ProviderRegistry`, construit au démarrage, associe chaque modèle à son provider et à son
+//! identifiant chez celui-ci.
+
+mod anthropic;
+mod gemini;
+mod groq;
+mod ollama;
+mod openai;
+
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::async_trait;
+
+use bytes::Bytes;
+use chrono::Utc;
+use futures::stream::{self, BoxStream, StreamExt};
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    cassette::CassetteMode,
+    config::Config,
+    crop::image_parts,
+    debug_log,
+    hooks::{self, HookContext},
+    internal_error,
+    models::{
+        AttachmentPayload, ChatMessagePayload, CompletionParams, FinishReason, ServiceTier,
+        TokenUsage,
+    },
+    plugins, prompt,
+    scrub::{scrub_messages, scrub_request},
+    service::clamp_max_tokens,
+    sse::{SseEvent, SseParser},
+    storage::{AttachmentContent, load_attachment_content},
+    tools::{ToolCallDelta, ToolRound, run_tools},
+};
+use anthropic::Anthropic;
+use gemini::Gemini;
+use groq::Groq;
+use ollama::Ollama;
+use openai::{OpenAI, OpenAIResponses};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum AiModelChoice {
+    #[default]
+    GroqLlama31,
+    OpenAIGpt51,
+    OpenAIGpt5Mini,
+    OpenAIGpt5Nano,
+    OpenAIGpt5Pro,
+    OpenAIGpt5,
+    OpenAIGpt41,
+    AnthropicClaudeSonnet45,
+    AnthropicClaudeHaiku45,
+    AnthropicClaudeOpus41,
+    GoogleGemini25Flash,
+    GoogleGemini25Pro,
+    /// Modèle servi par Ollama sur le réseau du déploiement
+    Ollama,
+}
+
+/// Provider intégré qui sert un modèle de `MODELS`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ProviderKind {
+    Groq,
+    /// Chat Completions, ou l'API Responses pour les modèles de `RESPONSES_API_MODELS`
+    OpenAI,
+    Anthropic,
+    Gemini,
+    /// Serveur de `OLLAMA_BASE_URL`, qui sert le modèle de `OLLAMA_MODEL`
+    Ollama,
+}
+
+/// Caractéristiques d'un modèle proposé aux clients.
+struct ModelDescriptor {
+    model: AiModelChoice,
+    /// Identifiant d'API (`gpt-5-mini`), aussi envoyé au provider
+    id: &'static str,
+    provider: ProviderKind,
+    /// Prix publics en USD par million de tokens (entrée, sortie)
+    pricing: (f64, f64),
+    /// Taille de la fenêtre de contexte (entrée + sortie), en tokens
+    context_window: u64,
+    /// Nombre maximum de tokens générés par réponse
+    max_output_tokens: u64,
+    supports_attachments: bool,
+    /// Paramètres d'échantillonnage transmis au provider
+    supported_params: &'static [SamplingParam],
+    /// Nom de la longueur maximale de la réponse en Chat Completions (Groq, OpenAI) ; les
+    /// autres API ont leur propre champ
+    max_tokens_field: &'static str,
+}
+
+/// Modèles proposés aux clients : ajouter un modèle revient à ajouter sa variante à
+/// `AiModelChoice` et sa ligne ici.
+///
+/// Les modèles de raisonnement refusent `temperature`, `top_p` et les pénalités en 400
+/// (gpt-5.1 accepte les deux premiers, son raisonnement étant désactivé par défaut), ainsi
+/// que `max_tokens` ; Groq ignore `seed` et les pénalités. Claude n'a ni `seed` ni
+/// pénalités, et refuse `temperature` et `top_p` ensemble : seul le premier, le plus
+/// courant, est transmis. Gemini 2.5 refuse les pénalités. Ollama les accepte tous.
+const MODELS: &[ModelDescriptor] = {
+    use SamplingParam::*;
+    &[
+        ModelDescriptor {
+            model: AiModelChoice::GroqLlama31,
+            id: "llama-3.1-8b-instant",
+            provider: ProviderKind::Groq,
+            pricing: (0.05, 0.08),
+            context_window: 131_072,
+            max_output_tokens: 131_072,
+            supports_attachments: false,
+            supported_params: &[Temperature, MaxTokens, TopP],
+            max_tokens_field: "max_tokens",
+        },
+        ModelDescriptor {
+            model: AiModelChoice::OpenAIGpt51,
+            id: "gpt-5.1",
+            provider: ProviderKind::OpenAI,
+            pricing: (1.25, 10.0),
+            context_window: 400_000,
+            max_output_tokens: 128_000,
+            supports_attachments: true,
+            supported_params: &[Temperature, MaxTokens, TopP, Seed],
+            max_tokens_field: "max_completion_tokens",
+        },
+        ModelDescriptor {
+            model: AiModelChoice::OpenAIGpt5Mini,
+            id: "gpt-5-mini",
+            provider: ProviderKind::OpenAI,
+            pricing: (0.25, 2.0),
+            context_window: 400_000,
+            max_output_tokens: 128_000,
+            supports_attachments: true,
+            supported_params: &[MaxTokens, Seed],
+            max_tokens_field: "max_completion_tokens",
+        },
+        ModelDescriptor {
+            model: AiModelChoice::OpenAIGpt5Nano,
+            id: "gpt-5-nano",
+            provider: ProviderKind::OpenAI,
+            pricing: (0.05, 0.40),
+            context_window: 400_000,
+            max_output_tokens: 128_000,
+            supports_attachments: true,
+            supported_params: &[MaxTokens, Seed],
+            max_tokens_field: "max_completion_tokens",
+        },
+        ModelDescriptor {
+            model: AiModelChoice::OpenAIGpt5Pro,
+            id: "gpt-5-pro",
+            provider: ProviderKind::OpenAI,
+            pricing: (15.0, 120.0),
+            context_window: 400_000,
+            max_output_tokens: 272_000,
+            supports_attachments: true,
+            supported_params: &[MaxTokens, Seed],
+            max_tokens_field: "max_completion_tokens",
+        },
+        ModelDescriptor {
+            model: AiModelChoice::OpenAIGpt5,
+            id: "gpt-5",
+            provider: ProviderKind::OpenAI,
+            pricing: (1.25, 10.0),
+            context_window: 400_000,
+            max_output_tokens: 128_000,
+            supports_attachments: true,
+            supported_params: &[MaxTokens, Seed],
+            max_tokens_field: "max_completion_tokens",
+        },
+        ModelDescriptor {
+            model: AiModelChoice::OpenAIGpt41,
+            id: "gpt-4.1",
+            provider: ProviderKind::OpenAI,
+            pricing: (2.0, 8.0),
+            context_window: 1_047_576,
+            max_output_tokens: 32_768,
+            supports_attachments: true,
+            supported_params: &SamplingParam::ALL,
+            max_tokens_field: "max_tokens",
+        },
+        ModelDescriptor {
+            model: AiModelChoice::AnthropicClaudeSonnet45,
+            id: "claude-sonnet-4-5",
+            provider: ProviderKind::Anthropic,
+            pricing: (3.0, 15.0),
+            context_window: 200_000,
+            max_output_tokens: 64_000,
+            supports_attachments: true,
+            supported_params: &[Temperature, MaxTokens],
+            max_tokens_field: "max_tokens",
+        },
+        ModelDescriptor {
+            model: AiModelChoice::AnthropicClaudeHaiku45,
+            id: "claude-haiku-4-5",
+            provider: ProviderKind::Anthropic,
+            pricing: (1.0, 5.0),
+            context_window: 200_000,
+            max_output_tokens: 64_000,
+            supports_attachments: true,
+            supported_params: &[Temperature, MaxTokens],
+            max_tokens_field: "max_tokens",
+        },
+        ModelDescriptor {
+            model: AiModelChoice::AnthropicClaudeOpus41,
+            id: "claude-opus-4-1",
+            provider: ProviderKind::Anthropic,
+            pricing: (15.0, 75.0),
+            context_window: 200_000,
+            max_output_tokens: 32_000,
+            supports_attachments: true,
+            supported_params: &[Temperature, MaxTokens],
+            max_tokens_field: "max_tokens",
+        },
+        ModelDescriptor {
+            model: AiModelChoice::GoogleGemini25Flash,
+            id: "gemini-2.5-flash",
+            provider: ProviderKind::Gemini,
+            pricing: (0.30, 2.50),
+            context_window: 1_048_576,
+            max_output_tokens: 65_536,
+            supports_attachments: true,
+            supported_params: &[Temperature, MaxTokens, TopP, Seed],
+            max_tokens_field: "max_tokens",
+        },
+        ModelDescriptor {
+            model: AiModelChoice::GoogleGemini25Pro,
+            id: "gemini-2.5-pro",
+            provider: ProviderKind::Gemini,
+            pricing: (1.25, 10.0),
+            context_window: 1_048_576,
+            max_output_tokens: 65_536,
+            supports_attachments: true,
+            supported_params: &[Temperature, MaxTokens, TopP, Seed],
+            max_tokens_field: "max_tokens",
+        },
+        ModelDescriptor {
+            model: AiModelChoice::Ollama,
+            id: "ollama",
+            provider: ProviderKind::Ollama,
+            pricing: (0.0, 0.0),
+            context_window: ollama::CONTEXT_WINDOW,
+            max_output_tokens: 8_192,
+            supports_attachments: true,
+            supported_params: &SamplingParam::ALL,
+            max_tokens_field: "max_tokens",
+        },
+    ]
+};
+
+impl std::str::FromStr for AiModelChoice {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        AiModelChoice::from_id(value).ok_or_else(|| format!("modèle inconnu: {value}"))
+    }
+}
+
+impl AiModelChoice {
+    /// Modèles de `MODELS`, dans l'ordre.
+    pub const ALL: [AiModelChoice; MODELS.len()] = {
+        let mut all = [AiModelChoice::GroqLlama31; MODELS.len()];
+        let mut i = 0;
+        while i < MODELS.len() {
+            all[i] = MODELS[i].model;
+            i += 1;
+        }
+        all
+    };
+
+    fn descriptor(&self) -> &'static ModelDescriptor {
+        MODELS
+            .iter()
+            .find(|descriptor| descriptor.model == *self)
+            .expect("modèle absent de MODELS")
+    }
+
+    /// Modèle correspondant à un identifiant d'API (`gpt-5-mini`...), sans casse.
+    pub(crate) fn from_id(value: &str) -> Option<Self> {
+        MODELS
+            .iter()
+            .find(|descriptor| descriptor.id.eq_ignore_ascii_case(value.trim()))
+            .map(|descriptor| descriptor.model)
+    }
+
+    pub(crate) fn model_id(&self) -> &'static str {
+        self.descriptor().id
+    }
+
+    /// Taille de la fenêtre de contexte (entrée + sortie), en tokens.
+    pub(crate) fn context_window(&self) -> u64 {
+        self.descriptor().context_window
+    }
+
+    /// Nombre maximum de tokens générés par réponse.
+    pub(crate) fn max_output_tokens(&self) -> u64 {
+        self.descriptor().max_output_tokens
+    }
+
+    pub(crate) fn supports_attachments(&self) -> bool {
+        self.descriptor().supports_attachments
+    }
+
+    /// Modèle servi par OpenAI, seul provider de l'API Responses.
+    pub(crate) fn is_openai(&self) -> bool {
+        self.descriptor().provider == ProviderKind::OpenAI
+    }
+
+    pub(crate) fn cost_usd(&self, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+        let (input, output) = self.descriptor().pricing;
+        (prompt_tokens as f64 * input + completion_tokens as f64 * output) / 1_000_000.0
+    }
+
+    /// Paramètres d'échantillonnage transmis au provider (voir `MODELS`).
+    pub(crate) fn supported_params(&self) -> &'static [SamplingParam] {
+        self.descriptor().supported_params
+    }
+
+    fn max_tokens_field(&self) -> &'static str {
+        self.descriptor().max_tokens_field
+    }
+
+    /// Retire de `params` les paramètres d'échantillonnage que le modèle n'accepte pas, au
+    /// lieu de laisser le provider refuser la requête.
+    pub(crate) fn shape_params(&self, mut params: CompletionParams) -> CompletionParams {
+        let supported = self.supported_params();
+        for param in SamplingParam::ALL {
+            if supported.contains(&param) {
+                continue;
+            }
+            match param {
+                SamplingParam::Temperature => params.temperature = None,
+                SamplingParam::MaxTokens => params.max_tokens = None,
+                SamplingParam::TopP => params.top_p = None,
+                SamplingParam::PresencePenalty => params.presence_penalty = None,
+                SamplingParam::FrequencyPenalty => params.frequency_penalty = None,
+                SamplingParam::Seed => params.seed = None,
+            }
+        }
+        params
+    }
+}
+
+/// Paramètres d'échantillonnage de `CompletionParams`, acceptés ou non selon le modèle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SamplingParam {
+    Temperature,
+    MaxTokens,
+    TopP,
+    PresencePenalty,
+    FrequencyPenalty,
+    Seed,
+}
+
+impl SamplingParam {
+    pub(crate) const ALL: [SamplingParam; 6] = [
+        SamplingParam::Temperature,
+        SamplingParam::MaxTokens,
+        SamplingParam::TopP,
+        SamplingParam::PresencePenalty,
+        SamplingParam::FrequencyPenalty,
+        SamplingParam::Seed,
+    ];
+
+    /// Nom du champ dans `CompletionParams`
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            SamplingParam::Temperature => "temperature",
+            SamplingParam::MaxTokens => "max_tokens",
+            SamplingParam::TopP => "top_p",
+            SamplingParam::PresencePenalty => "presence_penalty",
+            SamplingParam::FrequencyPenalty => "frequency_penalty",
+            SamplingParam::Seed => "seed",
+        }
+    }
+}
+
+pub(crate) const TITLE_SUMMARY_PROMPT: &str = r"Tu es un assistant qui crée des titres ultra courts (6 mots maximum) et parlants pour résumer une question d'utilisateur. Réponds uniquement par un emoji représentatif du sujet, une espace, puis le titre, sans ponctuation superflue.";
+
+/// Consigne de l'export `anki` : fiches de révision tirées d'une discussion.
+pub(crate) const FLASHCARDS_PROMPT: &str = r#"Tu transformes une discussion d'étude en fiches de révision Anki. Extrais les notions importantes sous forme de paires question/réponse : une seule notion par fiche, une question précise et compréhensible sans la discussion, une réponse courte et exacte. Écris entre 5 et 30 fiches selon la matière, dans la langue de la discussion. Réponds uniquement par un tableau JSON de la forme [{"question": "...", "answer": "..."}], sans texte autour."#;
+
+/// Flux renvoyé par un provider : le texte au fil de l'eau, la raison de fin de la réponse,
+/// puis la consommation de tokens si le provider la communique (dernier chunk).
+pub(crate) type CompletionStream = BoxStream<'static, Result<ProviderChunk, String>>;
+
+pub(crate) enum ProviderChunk {
+    Text(String),
+    Finish(FinishReason),
+    Usage(TokenUsage),
+    /// Identifiant de la réponse (API Responses), pour `previous_response_id`
+    ResponseId(String),
+    /// Entrée du journal de débogage (`PROVIDER_DEBUG_LOG`), à rattacher au message produit
+    DebugLogId(Uuid),
+    /// Temps passé à lire et extraire les pièces jointes de la requête, avant son envoi
+    AttachmentTime(Duration),
+    /// Requête ajustée avant l'envoi (`max_tokens` réduit...), signalée au client
+    Notice(String),
+    /// Texte par lequel le modèle refuse de répondre (`refusal`), à la place de la réponse
+    Refusal(String),
+    /// Fichier produit par un outil (capture de `screenshot_page`), à joindre à la réponse
+    Attachment(AttachmentPayload),
+}
+
+/// Flux brut d'un provider : en plus des chunks, les appels d'outils que `tools::run_tools`
+/// exécute avant de relancer le modèle.
+pub(crate) type ProviderStream = BoxStream<'static, Result<StreamItem, String>>;
+
+pub(crate) enum StreamItem {
+    Chunk(ProviderChunk),
+    ToolCall(ToolCallDelta),
+}
+
+/// Réponse du modèle, précédée du prompt système du déploiement (préférences et
+/// instructions du propriétaire comprises).
+pub(crate) async fn request_ai_completion(
+    state: &AppState,
+    messages: &[ChatMessagePayload],
+    model: AiModelChoice,
+    params: Option<CompletionParams>,
+) -> Result<CompletionStream, (axum::http::StatusCode, String)> {
+    let system_prompt = prompt::system_prompt(state).await;
+    request_completion_with_prompt(state, &system_prompt, messages, model, params).await
+}
+
+/// Réponse du modèle précédée de `system_prompt` : les widgets, ouverts à des visiteurs
+/// anonymes, n'ont que le prompt de base et leur persona.
+pub(crate) async fn request_completion_with_prompt(
+    state: &AppState,
+    system_prompt: &str,
+    messages: &[ChatMessagePayload],
+    model: AiModelChoice,
+    params: Option<CompletionParams>,
+) -> Result<CompletionStream, (axum::http::StatusCode, String)> {
+    let mut messages = with_system_prompt(system_prompt, messages);
+    let (params, notice) = clamp_max_tokens(model, &messages, params);
+    let prefix = params
+        .as_ref()
+        .and_then(|params| params.assistant_prefix.clone())
+        .filter(|prefix| !prefix.trim().is_empty());
+    let prefill = state
+        .providers
+        .route(model)
+        .is_some_and(|route| route.provider.supports_prefill());
+    if let Some(prefix) = &prefix {
+        messages.push(if prefill {
+            ChatMessagePayload {
+                role: "assistant".to_string(),
+                content: prefix.clone(),
+                attachments: Vec::new(),
+            }
+        } else {
+            ChatMessagePayload {
+                role: "system".to_string(),
+                content: format!(
+                    "Commence ta réponse exactement par le texte suivant, puis poursuis-la \
+                     normalement :\n{prefix}"
+                ),
+                attachments: Vec::new(),
+            }
+        });
+    }
+    let stream = request_model_completion(state, &messages, model, params.clone(), &[]).await?;
+    let stream = run_tools(state.clone(), messages, model, params, stream);
+    let stream = match prefix {
+        Some(prefix) => with_assistant_prefix(stream, prefix, !prefill),
+        None => stream,
+    };
+    let stream = hooks::apply(&state.stream_hooks, HookContext { model }, stream);
+    let stream = plugins::apply_filters(&state.plugins, stream);
+    Ok(match notice {
+        Some(notice) => Box::pin(stream::iter([Ok(ProviderChunk::Notice(notice))]).chain(stream)),
+        None => stream,
+    })
+}
+
+/// Flux de la réponse précédé de `prefix`, envoyé à l'arrivée de la première donnée du
+/// provider. Sans préremplissage, le modèle a reçu la consigne de commencer par `prefix` et
+/// le réécrit : cette copie (`echoed`) est retirée du début de la réponse.
+fn with_assistant_prefix(
+    stream: CompletionStream,
+    prefix: String,
+    echoed: bool,
+) -> CompletionStream {
+    let mut head = Some(prefix.clone());
+    // Début de la réponse gardé tant qu'il peut encore être la copie du préfixe.
+    let mut echo = echoed.then(String::new);
+    Box::pin(
+        stream
+            .map(Some)
+            .chain(stream::once(async { None }))
+            .flat_map(move |item| {
+                let mut chunks = Vec::new();
+                if let Some(prefix) = head.take() {
+                    chunks.push(Ok(ProviderChunk::Text(prefix)));
+                }
+                match (item, &mut echo) {
+                    (Some(Ok(ProviderChunk::Text(text))), Some(start)) => {
+                        start.push_str(&text);
+                        let trimmed = start.trim_start();
+                        if let Some(rest) = trimmed.strip_prefix(prefix.as_str()) {
+                            if !rest.is_empty() {
+                                chunks.push(Ok(ProviderChunk::Text(rest.to_string())));
+                            }
+                            echo = None;
+                        } else if !prefix.starts_with(trimmed) {
+                            chunks.push(Ok(ProviderChunk::Text(std::mem::take(start))));
+                            echo = None;
+                        }
+                    }
+                    // Autre chunk ou fin du flux : le début gardé n'était pas une copie.
+                    (item, start) => {
+                        if let Some(start) = start.take().filter(|start| !start.is_empty()) {
+                            chunks.push(Ok(ProviderChunk::Text(start)));
+                        }
+                        chunks.extend(item);
+                    }
+                }
+                stream::iter(chunks)
+            }),
+    )
+}
+
+/// Requête au provider ; `rounds` contient les appels d'outils déjà exécutés pendant cette
+/// réponse et leurs résultats. Les paramètres que le modèle n'accepte pas sont retirés.
+pub(crate) async fn request_model_completion(
+    state: &AppState,
+    messages: &[ChatMessagePayload],
+    model: AiModelChoice,
+    params: Option<CompletionParams>,
+    rounds: &[ToolRound],
+) -> Result<ProviderStream, (axum::http::StatusCode, String)> {
+    let params = params.map(|params| model.shape_params(params));
+    if params
+        .as_ref()
+        .is_some_and(|params| params.web_search == Some(true))
+        && !state.uses_responses_api(model)
+    {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            format!(
+                "La recherche web nécessite un modèle servi par l'API Responses (RESPONSES_API_MODELS), ce qui n'est pas le cas de {}.",
+                model.model_id()
+            ),
+        ));
+    }
+    let route = state.providers.route(model);
+    if route.is_some_and(|route| !route.provider.supports_tools()) {
+        if params
+            .as_ref()
+            .is_some_and(|params| params.code_edit == Some(true))
+        {
+            return Err((
+                axum::http::StatusCode::BAD_REQUEST,
+                format!(
+                    "L'édition de fichiers par diff n'est pas disponible avec {}.",
+                    model.model_id()
+                ),
+            ));
+        }
+        if params
+            .as_ref()
+            .is_some_and(|params| params.calendar == Some(true))
+        {
+            return Err((
+                axum::http::StatusCode::BAD_REQUEST,
+                format!(
+                    "L'ajout au calendrier n'est pas disponible avec {}.",
+                    model.model_id()
+                ),
+            ));
+        }
+        if params
+            .as_ref()
+            .is_some_and(|params| params.screenshot == Some(true))
+        {
+            return Err((
+                axum::http::StatusCode::BAD_REQUEST,
+                format!(
+                    "La capture de pages web nécessite un modèle de vision, ce qui n'est pas le cas de {}.",
+                    model.model_id()
+                ),
+            ));
+        }
+    }
+    let plugins = params
+        .as_ref()
+        .and_then(|params| params.plugins.as_deref())
+        .unwrap_or_default();
+    if !plugins.is_empty() && route.is_some_and(|route| !route.provider.supports_tools()) {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            format!(
+                "Les outils des plugins ne sont pas disponibles avec {}.",
+                model.model_id()
+            ),
+        ));
+    }
+    if let Some(name) = plugins
+        .iter()
+        .find(|name| state.plugins.tool(name).is_none())
+    {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            format!("Aucun plugin d'outil ne s'appelle {name} (GET /api/admin/plugins)."),
+        ));
+    }
+    if state.screenshot_service_url.is_none()
+        && params
+            .as_ref()
+            .is_some_and(|params| params.screenshot == Some(true))
+    {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            "La capture de pages web n'est pas configurée (SCREENSHOT_SERVICE_URL).".to_string(),
+        ));
+    }
+    if state.local_only
+        && params
+            .as_ref()
+            .is_some_and(|params| params.screenshot == Some(true))
+    {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            "La capture de pages web n'est pas disponible en mode local uniquement (LOCAL_ONLY)."
+                .to_string(),
+        ));
+    }
+    if let Some(mock) = &state.mock_provider {
+        let messages = scrub_messages(state, "mock", model.model_id(), messages).await;
+        let stream = mock.complete(
+            &messages,
+            model,
+            params,
+            state.uses_responses_api(model),
+            rounds,
+        )?;
+        return Ok(with_token_ledger(state, stream));
+    }
+    let Some(route) = route else {
+        return Err((
+            axum::http::StatusCode::SERVICE_UNAVAILABLE,
+            format!(
+                "Le modèle {} n'est pas configuré sur ce déploiement.",
+                model.model_id()
+            ),
+        ));
+    };
+    let stream = route
+        .provider
+        .complete(
+            state,
+            ModelRequest {
+                messages,
+                model,
+                upstream_model: &route.upstream_model,
+                params,
+                rounds,
+            },
+        )
+        .await?;
+    Ok(with_token_ledger(state, stream))
+}
+
+/// Compte dans `token_ledger` la consommation annoncée par le provider, pour
+/// `DAILY_TOKEN_QUOTA` : chaque appel l'est, qu'il vienne d'une discussion, de `/api/ai`,
+/// d'un widget, d'un titre ou d'un tour d'outils. L'écriture précède la suite du flux.
+fn with_token_ledger(state: &AppState, stream: ProviderStream) -> ProviderStream {
+    let state = state.clone();
+    Box::pin(stream.then(move |item| {
+        let state = state.clone();
+        async move {
+            if let Ok(StreamItem::Chunk(ProviderChunk::Usage(usage))) = &item {
+                let tokens = i64::from(usage.prompt_tokens) + i64::from(usage.completion_tokens);
+                if let Err(err) = state
+                    .repo
+                    .record_tokens(Utc::now().date_naive(), tokens)
+                    .await
+                {
+                    eprintln!("Impossible de compter les tokens du quota: {err}");
+                }
+            }
+            item
+        }
+    }))
+}
+
+/// Durée de préparation des messages depuis `started_at` si l'un d'eux a des pièces jointes :
+/// le reste de la mise en forme est négligeable.
+fn attachment_time<'a>(
+    messages: impl IntoIterator<Item = &'a ChatMessagePayload>,
+    started_at: Instant,
+) -> Option<Duration> {
+    messages
+        .into_iter()
+        .any(|message| !message.attachments.is_empty())
+        .then(|| started_at.elapsed())
+}
+
+/// Le temps de traitement des pièces jointes précède les chunks du provider.
+fn with_attachment_time(stream: ProviderStream, elapsed: Option<Duration>) -> ProviderStream {
+    match elapsed {
+        Some(elapsed) => Box::pin(
+            stream::iter([Ok(StreamItem::Chunk(ProviderChunk::AttachmentTime(
+                elapsed,
+            )))])
+            .chain(stream),
+        ),
+        None => stream,
+    }
+}
+
+/// Message qui montre au modèle les images produites par les outils d'un tour.
+fn round_images(round: &ToolRound) -> Option<ChatMessagePayload> {
+    (!round.images.is_empty()).then(|| ChatMessagePayload {
+        role: "user".to_string(),
+        content: "Captures demandées avec l'outil screenshot_page :".to_string(),
+        attachments: round.images.clone(),
+    })
+}
+
+/// Contenu d'un message au format OpenAI : le texte puis les pièces jointes (images en
+/// data URL, texte extrait des documents). `responses` choisit les types de l'API Responses.
+async fn content_parts(
+    state: &AppState,
+    message: &ChatMessagePayload,
+    responses: bool,
+    max_chars: usize,
+) -> Result<Vec<Value>, (axum::http::StatusCode, String)> {
+    let text_type = match (responses, message.role.as_str()) {
+        (false, _) => "text",
+        (true, "assistant") => "output_text",
+        (true, _) => "input_text",
+    };
+    let mut parts = Vec::new();
+    if !message.content.trim().is_empty() {
+        parts.push(json!({ "type": text_type, "text": message.content }));
+    }
+    for attachment in &message.attachments {
+        let url = match load_attachment_content(attachment, state, max_chars).await? {
+            AttachmentContent::Image(url) => url,
+            AttachmentContent::Text(text) => {
+                parts.push(json!({ "type": text_type, "text": text }));
+                continue;
+            }
+        };
+        // Les zones annotées sont envoyées découpées, chacune après sa légende.
+        for image in image_parts(attachment, url).await? {
+            if let Some(caption) = image.caption {
+                parts.push(json!({ "type": text_type, "text": caption }));
+            }
+            parts.push(if responses {
+                json!({ "type": "input_image", "image_url": image.url })
+            } else {
+                json!({ "type": "image_url", "image_url": { "url": image.url } })
+            });
+        }
+    }
+    if parts.is_empty() {
+        parts.push(json!({ "type": text_type, "text": "" }));
+    }
+    Ok(parts)
+}
+
+/// Requête d'un modèle, telle que la reçoit son provider.
+pub(crate) struct ModelRequest<'a> {
+    pub(crate) messages: &'a [ChatMessagePayload],
+    pub(crate) model: AiModelChoice,
+    /// Identifiant du modèle chez le provider (`ProviderRegistry`)
+    pub(crate) upstream_model: &'a str,
+    pub(crate) params: Option<CompletionParams>,
+    /// Appels d'outils déjà exécutés pendant cette réponse, avec leurs résultats
+    pub(crate) rounds: &'a [ToolRound],
+}
+
+/// Éléments d'un évènement du flux d'un provider ; `None` termine le flux (`[DONE]`).
+/// `pending_usage` garde la consommation annoncée en plusieurs évènements.
+pub(crate) type EventDecoder =
+    fn(&SseEvent, &mut Option<TokenUsage>) -> Option<Vec<Result<StreamItem, String>>>;
+
+/// Backend qui sert des modèles : il met la requête à son format, l'envoie par
+/// `send_completion` et décode son flux. Un nouveau provider s'ajoute dans son module et
+/// dans `ProviderRegistry::from_config`, sans toucher aux handlers.
+#[async_trait]
+pub(crate) trait Provider: Send + Sync {
+    /// Identifiant technique : cassettes, journal de débogage, masquage des secrets
+    fn name(&self) -> &'static str;
+
+    /// Nom affiché dans les messages d'erreur
+    fn label(&self) -> &'static str;
+
+    /// Adresse de l'appel, qui peut dépendre du modèle (Gemini)
+    fn url(&self, upstream_model: &str) -> String;
+
+    /// Variable de la clé API ; `None` pour un provider sans authentification.
+    fn api_key_var(&self) -> Option<&'static str>;
+
+    /// En-têtes d'authentification de la requête
+    fn authorize(&self, request: RequestBuilder, api_key: &str) -> RequestBuilder {
+        request.header("Authorization", format!("Bearer {api_key}"))
+    }
+
+    /// Provider joignable sans sortir du réseau du déploiement, seul autorisé avec
+    /// `LOCAL_ONLY`.
+    fn is_local(&self) -> bool {
+        false
+    }
+
+    /// Le provider poursuit un dernier message `assistant` au lieu d'y répondre
+    /// (`assistant_prefix`) ; sinon, le préfixe est demandé par une consigne.
+    fn supports_prefill(&self) -> bool {
+        false
+    }
+
+    /// Outils des modèles (`code_edit`, `calendar`, `screenshot`)
+    fn supports_tools(&self) -> bool {
+        true
+    }
+
+    /// Le provider garde les réponses et reprend la conversation à partir de
+    /// `previous_response_id` ; il propose aussi la recherche web (API Responses).
+    fn stores_responses(&self) -> bool {
+        false
+    }
+
+    /// Découpage du flux de réponse en évènements
+    fn stream_parser(&self) -> SseParser {
+        SseParser::new()
+    }
+
+    fn event_decoder(&self) -> EventDecoder;
+
+    async fn complete(
+        &self,
+        state: &AppState,
+        request: ModelRequest<'_>,
+    ) -> Result<ProviderStream, (axum::http::StatusCode, String)>;
+}
+
+/// Provider d'un modèle et identifiant du modèle chez lui.
+#[derive(Clone)]
+pub(crate) struct ModelRoute {
+    pub(crate) provider: Arc<dyn Provider>,
+    pub(crate) upstream_model: String,
+}
+
+/// Modèles proposés aux clients et provider qui sert chacun, fixés au démarrage. Un modèle
+/// absent (`ollama` sans `OLLAMA_BASE_URL`) n'est pas disponible.
+#[derive(Clone, Default)]
+pub(crate) struct ProviderRegistry {
+    routes: HashMap<AiModelChoice, ModelRoute>,
+}
+
+impl ProviderRegistry {
+    /// Providers intégrés : les modèles de `RESPONSES_API_MODELS` passent par l'API
+    /// Responses d'OpenAI, le modèle local par le serveur Ollama s'il est configuré.
+    pub(crate) fn from_config(config: &Config) -> Self {
+        let groq: Arc<dyn Provider> = Arc::new(Groq);
+        let openai: Arc<dyn Provider> = Arc::new(OpenAI);
+        let openai_responses: Arc<dyn Provider> = Arc::new(OpenAIResponses);
+        let anthropic: Arc<dyn Provider> = Arc::new(Anthropic);
+        let gemini: Arc<dyn Provider> = Arc::new(Gemini);
+        let mut registry = ProviderRegistry::default();
+        for descriptor in MODELS {
+            let provider = match descriptor.provider {
+                ProviderKind::Groq => &groq,
+                ProviderKind::OpenAI if config.responses_api_models.contains(&descriptor.model) => {
+                    &openai_responses
+                }
+                ProviderKind::OpenAI => &openai,
+                ProviderKind::Anthropic => &anthropic,
+                ProviderKind::Gemini => &gemini,
+                ProviderKind::Ollama => {
+                    if let Some(base_url) = &config.ollama_base_url {
+                        registry.register(
+                            descriptor.model,
+                            Arc::new(Ollama::new(base_url)),
+                            &config.ollama_model,
+                        );
+                    }
+                    continue;
+                }
+            };
+            registry.register(descriptor.model, provider.clone(), descriptor.id);
+        }
+        registry
+    }
+
+    /// Sert `model` par `provider`, sous l'identifiant `upstream_model`, à la place de
+    /// l'éventuel provider précédent.
+    pub(crate) fn register(
+        &mut self,
+        model: AiModelChoice,
+        provider: Arc<dyn Provider>,
+        upstream_model: &str,
+    ) {
+        self.routes.insert(
+            model,
+            ModelRoute {
+                provider,
+                upstream_model: upstream_model.to_string(),
+            },
+        );
+    }
+
+    pub(crate) fn route(&self, model: AiModelChoice) -> Option<&ModelRoute> {
+        self.routes.get(&model)
+    }
+}
+
+/// Le modèle peut être appelé sur ce déploiement : toujours, sauf en mode `LOCAL_ONLY` où
+/// seuls les providers locaux (et le provider simulé) restent joignables. Un modèle sans
+/// provider enregistré ne l'est jamais.
+pub(crate) fn model_available(state: &AppState, model: AiModelChoice) -> bool {
+    if state.mock_provider.is_some() {
+        return true;
+    }
+    state
+        .providers
+        .route(model)
+        .is_some_and(|route| !state.local_only || route.provider.is_local())
+}
+
+/// Envoie la requête streamée au provider, après masquage des secrets, ou la sert depuis
+/// les cassettes enregistrées (`PROVIDER_CASSETTE_MODE`). `service_tier` fixe l'en-tête de
+/// niveau de traitement d'OpenAI. Avec `LOCAL_ONLY`, tout provider externe est refusé ici,
+/// par où passent toutes les requêtes sortantes.
+async fn send_completion(
+    state: &AppState,
+    provider: &dyn Provider,
+    upstream_model: &str,
+    request_body: &Value,
+    service_tier: Option<ServiceTier>,
+) -> Result<ProviderStream, (axum::http::StatusCode, String)> {
+    if state.local_only && !provider.is_local() {
+        return Err((
+            axum::http::StatusCode::FORBIDDEN,
+            format!(
+                "Mode local uniquement (LOCAL_ONLY) : le provider {} est désactivé sur ce déploiement.",
+                provider.label()
+            ),
+        ));
+    }
+    let scrubbed = scrub_request(state, provider.name(), request_body).await;
+    let request_body = scrubbed.as_ref().unwrap_or(request_body);
+    let url = provider.url(upstream_model);
+    let cassettes = state.cassettes.as_deref();
+    if let Some(cassettes) = cassettes.filter(|c| c.mode == CassetteMode::Replay) {
+        let recorded = cassettes
+            .replay(provider.name(), &url, request_body)
+            .await?;
+        let log_id = debug_log::start(state, provider.name(), &url, request_body).await;
+        return Ok(logged_stream(state, provider, log_id, 200, recorded));
+    }
+    let log_id = debug_log::start(state, provider.name(), &url, request_body).await;
+
+    let api_key = provider
+        .api_key_var()
+        .map(|var| env::var(var).map_err(|_| internal_error(format!("{var} manquant dans .env"))))
+        .transpose()?;
+
+    let request = Client::new()
+        .post(&url)
+        .header("Content-Type", "application/json");
+    let mut request = match api_key {
+        Some(api_key) => provider.authorize(request, &api_key),
+        None => request,
+    };
+    if let Some(service_tier) = service_tier {
+        request = request.header("x-openai-processing-tier", service_tier.as_str());
+    }
+    let res = match request.json(request_body).send().await {
+        Ok(res) => res,
+        Err(err) => {
+            debug_log::fail(state, log_id, None, &err.to_string()).await;
+            return Err(internal_error(err));
+        }
+    };
+
+    let status = res.status();
+    if !status.is_success() {
+        let retry_after = res
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok());
+        let body_text = res.text().await.unwrap_or_default();
+        debug_log::fail(state, log_id, Some(status.as_u16()), &body_text).await;
+        return Err(upstream_error(
+            provider.label(),
+            status.as_u16(),
+            retry_after,
+            &body_text,
+        ));
+    }
+
+    let stream = Box::pin(res.bytes_stream());
+    let stream = match cassettes {
+        Some(cassettes) => cassettes.record(provider.name(), &url, request_body, stream),
+        None => stream,
+    };
+    Ok(logged_stream(
+        state,
+        provider,
+        log_id,
+        status.as_u16(),
+        stream,
+    ))
+}
+
+/// Flux SSE décodé, copié dans le journal de débogage s'il est actif : l'identifiant de
+/// l'entrée précède alors les chunks.
+fn logged_stream(
+    state: &AppState,
+    provider: &dyn Provider,
+    log_id: Option<Uuid>,
+    status: u16,
+    stream: BoxStream<'static, Result<Bytes, reqwest::Error>>,
+) -> ProviderStream {
+    match log_id {
+        Some(log_id) => {
+            let logged = debug_log::tee(state, log_id, status, stream);
+            let id = Ok(StreamItem::Chunk(ProviderChunk::DebugLogId(log_id)));
+            Box::pin(stream::iter([id]).chain(process_stream(logged, provider)))
+        }
+        None => process_stream(stream, provider),
+    }
+}
+
+/// Catégorie d'une erreur de provider, tirée du corps JSON `{"error": {"code", "type",
+/// "message"}}` commun à OpenAI, Groq et Anthropic, ou à défaut du statut HTTP.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum UpstreamErrorKind {
+    /// La conversation dépasse la fenêtre de contexte du modèle
+    ContextLengthExceeded,
+    /// Clé API absente, invalide ou révoquée
+    InvalidApiKey,
+    /// Trop de requêtes ou de tokens par minute pour le compte
+    RateLimited,
+    /// Crédit du compte épuisé
+    QuotaExhausted,
+    /// Demande refusée par la modération du provider
+    ContentPolicy,
+    /// Provider en panne ou surchargé
+    Unavailable,
+    Other,
+}
+
+impl UpstreamErrorKind {
+    fn classify(status: Option<u16>, code: Option<&str>, message: &str) -> Self {
+        match code {
+            Some("context_length_exceeded" | "string_above_max_length" | "request_too_large") => {
+                return UpstreamErrorKind::ContextLengthExceeded;
+            }
+            Some("invalid_api_key" | "authentication_error") => {
+                return UpstreamErrorKind::InvalidApiKey;
+            }
+            Some("insufficient_quota" | "billing_hard_limit_reached") => {
+                return UpstreamErrorKind::QuotaExhausted;
+            }
+            Some("rate_limit_exceeded" | "tokens_exceeded" | "rate_limit_error") => {
+                return UpstreamErrorKind::RateLimited;
+            }
+            Some("content_policy_violation" | "content_filter") => {
+                return UpstreamErrorKind::ContentPolicy;
+            }
+            Some("server_error" | "service_unavailable" | "overloaded_error" | "api_error") => {
+                return UpstreamErrorKind::Unavailable;
+            }
+            _ => {}
+        }
+        // Certaines erreurs de contexte n'ont pas de code, seulement un message.
+        let lower = message.to_lowercase();
+        if lower.contains("context length")
+            || lower.contains("context window")
+            || lower.contains("prompt is too long")
+        {
+            return UpstreamErrorKind::ContextLengthExceeded;
+        }
+        match status {
+            Some(401 | 403) => UpstreamErrorKind::InvalidApiKey,
+            Some(429) => UpstreamErrorKind::RateLimited,
+            Some(500..) => UpstreamErrorKind::Unavailable,
+            _ => UpstreamErrorKind::Other,
+        }
+    }
+
+    fn status(&self) -> axum::http::StatusCode {
+        match self {
+            UpstreamErrorKind::ContextLengthExceeded => axum::http::StatusCode::BAD_REQUEST,
+            UpstreamErrorKind::RateLimited => axum::http::StatusCode::TOO_MANY_REQUESTS,
+            UpstreamErrorKind::ContentPolicy => axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+            UpstreamErrorKind::InvalidApiKey
+            | UpstreamErrorKind::QuotaExhausted
+            | UpstreamErrorKind::Unavailable => axum::http::StatusCode::SERVICE_UNAVAILABLE,
+            UpstreamErrorKind::Other => axum::http::StatusCode::BAD_GATEWAY,
+        }
+    }
+
+    /// Message à afficher, qui dit quoi faire ; `None` pour une erreur non reconnue, dont
+    /// le message du provider est repris tel quel.
+    fn user_message(&self, retry_after: Option<u64>) -> Option<String> {
+        let message = match self {
+            UpstreamErrorKind::ContextLengthExceeded => "La conversation est trop longue pour \
+                ce modèle : raccourcissez le message, retirez des pièces jointes ou démarrez \
+                une nouvelle discussion."
+                .to_string(),
+            UpstreamErrorKind::InvalidApiKey => "Le serveur n'est pas autorisé à utiliser ce \
+                modèle (clé API refusée). Contactez l'administrateur."
+                .to_string(),
+            UpstreamErrorKind::RateLimited => match retry_after {
+                Some(seconds) => {
+                    format!("Le modèle reçoit trop de requêtes. Réessayez dans {seconds} s.")
+                }
+                None => "Le modèle reçoit trop de requêtes. Réessayez dans quelques instants."
+                    .to_string(),
+            },
+            UpstreamErrorKind::QuotaExhausted => "Le crédit du compte utilisé pour ce modèle \
+                est épuisé. Contactez l'administrateur ou choisissez un autre modèle."
+                .to_string(),
+            UpstreamErrorKind::ContentPolicy => "La demande a été refusée par le filtre de \
+                contenu du modèle. Reformulez-la."
+                .to_string(),
+            UpstreamErrorKind::Unavailable => "Le modèle est momentanément indisponible. \
+                Réessayez dans quelques instants ou choisissez un autre modèle."
+                .to_string(),
+            UpstreamErrorKind::Other => return None,
+        };
+        Some(message)
+    }
+}
+
+/// Traduit une réponse en échec d'un provider en statut et message exploitables par le
+/// client : contexte trop long (400), filtre de contenu (422), limite de débit (429, avec le
+/// délai de `Retry-After`), clé ou crédit du serveur et panne du provider (503). Les autres
+/// erreurs restent en 502 avec le message du provider.
+pub(crate) fn upstream_error(
+    label: &str,
+    status: u16,
+    retry_after: Option<u64>,
+    body: &str,
+) -> (axum::http::StatusCode, String) {
+    let parsed: Value = serde_json::from_str(body).unwrap_or(Value::Null);
+    let error = &parsed["error"];
+    let message = error["message"].as_str().unwrap_or(body).trim();
+    let kind = UpstreamErrorKind::classify(
+        Some(status),
+        error["code"].as_str().or(error["type"].as_str()),
+        message,
+    );
+    if matches!(
+        kind,
+        UpstreamErrorKind::InvalidApiKey | UpstreamErrorKind::QuotaExhausted
+    ) {
+        eprintln!("Erreur {label}: HTTP {status} - {message}");
+    }
+    let user_message = kind
+        .user_message(retry_after)
+        .unwrap_or_else(|| format!("Erreur {label}: HTTP {status} - {message}"));
+    (kind.status(), user_message)
+}
+
+/// Lit le flux d'un provider, découpé par son parser et décodé évènement par évènement.
+fn process_stream(
+    stream: BoxStream<'static, Result<Bytes, reqwest::Error>>,
+    provider: &dyn Provider,
+) -> ProviderStream {
+    let decode = provider.event_decoder();
+    Box::pin(stream::unfold(
+        (
+            Some(stream),
+            provider.stream_parser(),
+            VecDeque::new(),
+            None,
+        ),
+        move |(mut stream, mut parser, mut pending, mut prompt_usage)| async move {
+            loop {
+                if let Some(chunk) = pending.pop_front() {
+                    return Some((chunk, (stream, parser, pending, prompt_usage)));
+                }
+                let events = match stream.as_mut()?.next().await {
+                    Some(Ok(bytes)) => parser.feed(&bytes),
+                    Some(Err(e)) => {
+                        return Some((Err(e.to_string()), (stream, parser, pending, prompt_usage)));
+                    }
+                    None => {
+                        stream = None;
+                        parser.finish()
+                    }
+                };
+                for event in events {
+                    match decode(&event, &mut prompt_usage) {
+                        Some(items) => pending.extend(items),
+                        // `[DONE]` : la suite éventuelle du flux est ignorée.
+                        None => {
+                            stream = None;
+                            break;
+                        }
+                    }
+                }
+            }
+        },
+    ))
+}
+
+/// Corps JSON d'un évènement ; `None` pour un évènement illisible, ignoré.
+fn event_json(event: &SseEvent) -> Option<Value> {
+    serde_json::from_str(&event.data).ok()
+}
+
+/// Message d'une erreur reçue dans le flux, traduit comme une erreur HTTP du provider.
+fn stream_error(error: &Value) -> String {
+    let message = error["message"].as_str().unwrap_or("réponse en échec");
+    let kind = UpstreamErrorKind::classify(
+        None,
+        error["code"].as_str().or(error["type"].as_str()),
+        message,
+    );
+    kind.user_message(None)
+        .unwrap_or_else(|| message.to_string())
+}
+
+/// Clé de routage du cache de prompt : dérivée du premier message de la discussion, donc
+/// identique pour tous ses tours. Le prompt système n'y entre pas : il contient l'heure.
+fn prompt_cache_key(messages: &[ChatMessagePayload]) -> String {
+    let mut hasher = DefaultHasher::new();
+    for message in messages
+        .iter()
+        .filter(|message| message.role != "system")
+        .take(1)
+    {
+        message.role.hash(&mut hasher);
+        message.content.hash(&mut hasher);
+    }
+    format!("carl-{:016x}", hasher.finish())
+}
+
+fn with_system_prompt(
+    system_prompt: &str,
+    messages: &[ChatMessagePayload],
+) -> Vec<ChatMessagePayload> {
+    let mut result = Vec::with_capacity(messages.len() + 1);
+    result.push(ChatMessagePayload {
+        role: "system".to_string(),
+        content: system_prompt.to_string(),
+        attachments: Vec::new(),
+    });
+    result.extend(messages.iter().cloned());
+    result
+}
+
+/// Titre résumé d'une discussion et l'emoji qui le précède dans la réponse du modèle.
+pub(crate) struct SessionTitle {
+    pub(crate) title: String,
+    pub(crate) icon: Option<String>,
+}
+
+pub(crate) async fn generate_concise_title(
+    state: &AppState,
+    content: &str,
+) -> Result<SessionTitle, (axum::http::StatusCode, String)> {
+    let messages = vec![
+        ChatMessagePayload {
+            role: "system".to_string(),
+            content: TITLE_SUMMARY_PROMPT.to_string(),
+            attachments: Vec::new(),
+        },
+        ChatMessagePayload {
+            role: "user".to_string(),
+            content: format!("Question: {content}"),
+            attachments: Vec::new(),
+        },
+    ];
+
+    let mut stream =
+        request_model_completion(state, &messages, state.title_model, None, &[]).await?;
+    let mut summary = String::new();
+    while let Some(chunk_res) = stream.next().await {
+        if let Ok(StreamItem::Chunk(ProviderChunk::Text(chunk))) = chunk_res {
+            summary.push_str(&chunk);
+        }
+    }
+
+    let cleaned = summary.lines().next().unwrap_or("").trim();
+    // Le modèle peut oublier l'emoji : le premier mot n'est une icône que s'il ne contient
+    // ni lettre ni chiffre.
+    let (icon, title) = match cleaned.split_once(char::is_whitespace) {
+        Some((first, rest)) if !first.is_ascii() && !first.chars().any(char::is_alphanumeric) => {
+            (Some(first.to_string()), rest.trim())
+        }
+        _ => (None, cleaned),
+    };
+    if title.is_empty() {
+        Err((
+            axum::http::StatusCode::BAD_GATEWAY,
+            "Aucun résumé n'a été renvoyé pour le titre.".to_string(),
+        ))
+    } else {
+        Ok(SessionTitle {
+            title: title.to_string(),
+            icon,
+        })
+    }
+}
+
+/// Fiche de révision (question/réponse) de l'export `anki`.
+#[derive(Deserialize)]
+pub(crate) struct Flashcard {
+    pub(crate) question: String,
+    pub(crate) answer: String,
+}
+
+/// Fait distiller `transcript` (la discussion mise à plat) en fiches de révision par `model`.
+pub(crate) async fn generate_flashcards(
+    state: &AppState,
+    model: AiModelChoice,
+    transcript: &str,
+) -> Result<Vec<Flashcard>, (axum::http::StatusCode, String)> {
+    let messages = vec![
+        ChatMessagePayload {
+            role: "system".to_string(),
+            content: FLASHCARDS_PROMPT.to_string(),
+            attachments: Vec::new(),
+        },
+        ChatMessagePayload {
+            role: "user".to_string(),
+            content: transcript.to_string(),
+            attachments: Vec::new(),
+        },
+    ];
+
+    let mut stream = request_model_completion(state, &messages, model, None, &[]).await?;
+    let mut reply = String::new();
+    while let Some(chunk_res) = stream.next().await {
+        match chunk_res {
+            Ok(StreamItem::Chunk(ProviderChunk::Text(chunk))) => reply.push_str(&chunk),
+            Ok(_) => {}
+            Err(err) => return Err((axum::http::StatusCode::BAD_GATEWAY, err)),
+        }
+    }
+
+    // Le modèle entoure parfois le tableau d'un bloc de code ou d'une phrase.
+    let cards: Vec<Flashcard> = reply
+        .find('[')
+        .zip(reply.rfind(']'))
+        .and_then(|(start, end)| serde_json::from_str(reply.get(start..=end)?).ok())
+        .unwrap_or_default();
+    let cards: Vec<Flashcard> = cards
+        .into_iter()
+        .filter(|card| !card.question.trim().is_empty() && !card.answer.trim().is_empty())
+        .collect();
+    if cards.is_empty() {
+        Err((
+            axum::http::StatusCode::BAD_GATEWAY,
+            "Aucune fiche de révision n'a été renvoyée par le modèle.".to_string(),
+        ))
+    } else {
+        Ok(cards)
+    }
+}
@@ -0,0 +1,326 @@
+//! API Messages d'Anthropic.
+
+use std::time::Instant;
+
+use axum::async_trait;
+use reqwest::RequestBuilder;
+use serde_json::{Value, json};
+
+use super::{
+    EventDecoder, ModelRequest, Provider, ProviderChunk, ProviderStream, StreamItem,
+    attachment_time, content_parts, event_json, round_images, send_completion, stream_error,
+    with_attachment_time,
+};
+use crate::{
+    AppState,
+    models::{FinishReason, TokenUsage},
+    service::attachment_char_budget,
+    sse::SseEvent,
+    tools::{ToolCallDelta, chat_tool_definitions},
+};
+
+/// Version de l'API Messages d'Anthropic (en-tête `anthropic-version`)
+const API_VERSION: &str = "2023-06-01";
+/// `max_tokens` des requêtes à Anthropic qui n'en précisent pas : l'API Messages l'exige
+const DEFAULT_MAX_TOKENS: u32 = 16_000;
+
+pub(super) struct Anthropic;
+
+#[async_trait]
+impl Provider for Anthropic {
+    fn name(&self) -> &'static str {
+        "anthropic"
+    }
+
+    fn label(&self) -> &'static str {
+        "Anthropic"
+    }
+
+    fn url(&self, _: &str) -> String {
+        "https://api.anthropic.com/v1/messages".to_string()
+    }
+
+    fn api_key_var(&self) -> Option<&'static str> {
+        Some("ANTHROPIC_API_KEY")
+    }
+
+    fn authorize(&self, request: RequestBuilder, api_key: &str) -> RequestBuilder {
+        request
+            .header("x-api-key", api_key)
+            .header("anthropic-version", API_VERSION)
+    }
+
+    fn supports_prefill(&self) -> bool {
+        true
+    }
+
+    fn event_decoder(&self) -> EventDecoder {
+        anthropic_event
+    }
+
+    async fn complete(
+        &self,
+        state: &AppState,
+        request: ModelRequest<'_>,
+    ) -> Result<ProviderStream, (axum::http::StatusCode, String)> {
+        request_anthropic_message(state, request).await
+    }
+}
+
+/// Même requête via l'API Messages d'Anthropic : le prompt système passe dans `system`, les
+/// images en blocs `image` et les appels d'outils en blocs `tool_use`, suivis d'un message
+/// de l'utilisateur portant leurs `tool_result`. `max_tokens` est obligatoire.
+///
+/// Anthropic ne met en cache que jusqu'aux blocs marqués `cache_control` : le dernier bloc
+/// du prompt système et celui du dernier message de la conversation le sont, pour que le
+/// tour suivant relise tout l'historique depuis le cache.
+async fn request_anthropic_message(
+    state: &AppState,
+    request: ModelRequest<'_>,
+) -> Result<ProviderStream, (axum::http::StatusCode, String)> {
+    let ModelRequest {
+        messages,
+        model,
+        upstream_model,
+        params,
+        rounds,
+    } = request;
+    let params = params.unwrap_or_default();
+    let mut system: Vec<Value> = messages
+        .iter()
+        .filter(|message| message.role == "system" && !message.content.trim().is_empty())
+        .map(|message| json!({ "type": "text", "text": message.content }))
+        .collect();
+    if let Some(last) = system.last_mut() {
+        last["cache_control"] = cache_control();
+    }
+
+    let max_chars = attachment_char_budget(state, model, messages, Some(&params));
+    let loading_started_at = Instant::now();
+    let mut formatted_messages = Vec::with_capacity(messages.len());
+    for message in messages.iter().filter(|message| message.role != "system") {
+        let content = anthropic_content(content_parts(state, message, false, max_chars).await?);
+        // Anthropic refuse les messages vides (placeholder d'une réponse sans contenu).
+        if !content.is_empty() {
+            formatted_messages.push(json!({ "role": message.role, "content": content }));
+        }
+    }
+    let attachment_time = attachment_time(messages, loading_started_at);
+    // Préremplissage (`assistant_prefix`) : la réponse commencée ne peut pas finir par une
+    // espace.
+    if let Some(last) = formatted_messages
+        .last_mut()
+        .filter(|message| message["role"] == "assistant")
+        && let Some(text) = last["content"][0]["text"]
+            .as_str()
+            .map(|text| text.trim_end().to_string())
+    {
+        last["content"][0]["text"] = json!(text);
+    }
+    if let Some(block) = formatted_messages
+        .last_mut()
+        .and_then(|message| message["content"].as_array_mut())
+        .and_then(|content| content.last_mut())
+    {
+        block["cache_control"] = cache_control();
+    }
+    for round in rounds {
+        let mut content = Vec::new();
+        if !round.text.is_empty() {
+            content.push(json!({ "type": "text", "text": round.text }));
+        }
+        for call in &round.calls {
+            content.push(json!({
+                "type": "tool_use",
+                "id": call.id,
+                "name": call.name,
+                "input": serde_json::from_str::<Value>(&call.arguments).unwrap_or(json!({}))
+            }));
+        }
+        formatted_messages.push(json!({ "role": "assistant", "content": content }));
+        let mut results: Vec<Value> = round
+            .calls
+            .iter()
+            .zip(&round.outputs)
+            .map(|(call, output)| {
+                json!({ "type": "tool_result", "tool_use_id": call.id, "content": output })
+            })
+            .collect();
+        if let Some(images) = round_images(round) {
+            results.extend(anthropic_content(
+                content_parts(state, &images, false, max_chars).await?,
+            ));
+        }
+        formatted_messages.push(json!({ "role": "user", "content": results }));
+    }
+
+    let max_tokens = params
+        .max_tokens
+        .unwrap_or(DEFAULT_MAX_TOKENS)
+        .min(model.max_output_tokens() as u32);
+    let mut request_body = json!({
+        "model": upstream_model,
+        "system": system,
+        "messages": formatted_messages,
+        "max_tokens": max_tokens,
+        "stream": true,
+    });
+    // La température d'Anthropic va de 0 à 1, celle d'OpenAI jusqu'à 2.
+    if let Some(temp) = params.temperature {
+        request_body["temperature"] = json!(temp.clamp(0.0, 1.0));
+    }
+    let tools: Vec<Value> = chat_tool_definitions(state, &params)
+        .into_iter()
+        .map(|tool| {
+            let function = &tool["function"];
+            json!({
+                "name": function["name"],
+                "description": function["description"],
+                "input_schema": function["parameters"]
+            })
+        })
+        .collect();
+    if !tools.is_empty() {
+        request_body["tools"] = json!(tools);
+    }
+
+    let stream = send_completion(state, &Anthropic, upstream_model, &request_body, None).await?;
+    Ok(with_attachment_time(stream, attachment_time))
+}
+
+/// Point de cache de la requête, gardé cinq minutes par Anthropic.
+fn cache_control() -> Value {
+    json!({ "type": "ephemeral" })
+}
+
+/// Blocs de contenu d'Anthropic tirés des parties au format Chat Completions : les images
+/// en base64 ou par URL, sans les textes vides qu'Anthropic refuse.
+fn anthropic_content(parts: Vec<Value>) -> Vec<Value> {
+    parts
+        .into_iter()
+        .filter_map(|part| match part["type"].as_str() {
+            Some("image_url") => {
+                let url = part["image_url"]["url"].as_str().unwrap_or_default();
+                let source = match url
+                    .strip_prefix("data:")
+                    .and_then(|data| data.split_once(";base64,"))
+                {
+                    Some((media_type, data)) => {
+                        json!({ "type": "base64", "media_type": media_type, "data": data })
+                    }
+                    None => json!({ "type": "url", "url": url }),
+                };
+                Some(json!({ "type": "image", "source": source }))
+            }
+            _ => part["text"]
+                .as_str()
+                .filter(|text| !text.trim().is_empty())
+                .map(|text| json!({ "type": "text", "text": text })),
+        })
+        .collect()
+}
+
+/// Évènement de l'API Messages d'Anthropic. Les blocs d'un message sont numérotés (`index`)
+/// : un appel d'outil commence avec son identifiant et son nom, ses arguments JSON suivent
+/// par morceaux. La consommation arrive en deux fois, le prompt au début du message et la
+/// réponse à la fin : la première attend la seconde dans `prompt_usage`. Le flux se termine
+/// sans `[DONE]`.
+fn anthropic_event(
+    event: &SseEvent,
+    prompt_usage: &mut Option<TokenUsage>,
+) -> Option<Vec<Result<StreamItem, String>>> {
+    let Some(val) = event_json(event) else {
+        return Some(Vec::new());
+    };
+    let val = &val;
+    let chunk = |chunk| Ok(StreamItem::Chunk(chunk));
+    let index = val["index"].as_u64().unwrap_or_default() as usize;
+    let items = match val["type"]
+        .as_str()
+        .or(event.event.as_deref())
+        .unwrap_or_default()
+    {
+        "message_start" => {
+            let usage = &val["message"]["usage"];
+            if !usage.is_object() {
+                return Some(Vec::new());
+            }
+            let count = |field: &str| usage[field].as_i64().unwrap_or(0) as i32;
+            // `input_tokens` ne compte pas la part lue ou écrite dans le cache de prompt.
+            *prompt_usage = Some(TokenUsage {
+                prompt_tokens: count("input_tokens")
+                    + count("cache_read_input_tokens")
+                    + count("cache_creation_input_tokens"),
+                completion_tokens: 0,
+                cached_tokens: count("cache_read_input_tokens"),
+            });
+            Vec::new()
+        }
+        "content_block_start" => {
+            let block = &val["content_block"];
+            match block["type"].as_str() {
+                Some("tool_use") => vec![Ok(StreamItem::ToolCall(ToolCallDelta {
+                    index,
+                    id: block["id"].as_str().map(str::to_string),
+                    name: block["name"].as_str().map(str::to_string),
+                    arguments: String::new(),
+                }))],
+                Some("text") => block["text"]
+                    .as_str()
+                    .filter(|text| !text.is_empty())
+                    .map(|text| chunk(ProviderChunk::Text(text.to_string())))
+                    .into_iter()
+                    .collect(),
+                _ => Vec::new(),
+            }
+        }
+        "content_block_delta" => {
+            let delta = &val["delta"];
+            match delta["type"].as_str() {
+                Some("text_delta") => delta["text"]
+                    .as_str()
+                    .map(|text| chunk(ProviderChunk::Text(text.to_string())))
+                    .into_iter()
+                    .collect(),
+                Some("input_json_delta") => vec![Ok(StreamItem::ToolCall(ToolCallDelta {
+                    index,
+                    id: None,
+                    name: None,
+                    arguments: delta["partial_json"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                }))],
+                _ => Vec::new(),
+            }
+        }
+        "message_delta" => {
+            let mut chunks = Vec::new();
+            let reason = match val["delta"]["stop_reason"].as_str() {
+                Some("max_tokens") => Some(FinishReason::Length),
+                Some("tool_use") => Some(FinishReason::ToolCalls),
+                Some("refusal") => Some(FinishReason::ContentFilter),
+                Some(_) => Some(FinishReason::Stop),
+                None => None,
+            };
+            chunks.extend(reason.map(|reason| chunk(ProviderChunk::Finish(reason))));
+            // Un seul usage par réponse : celui du prompt complété par les tokens générés.
+            if let Some(output_tokens) = val["usage"]["output_tokens"].as_i64() {
+                let usage = prompt_usage.take().unwrap_or_default();
+                chunks.push(chunk(ProviderChunk::Usage(TokenUsage {
+                    completion_tokens: output_tokens as i32,
+                    ..usage
+                })));
+            }
+            chunks
+        }
+        "error" => {
+            let error = Some(&val["error"])
+                .filter(|error| error.is_object())
+                .unwrap_or(val);
+            vec![Err(stream_error(error))]
+        }
+        _ => Vec::new(),
+    };
+    Some(items)
+}
@@ -0,0 +1,198 @@
+//! API Gemini de Google (generativelanguage).
+
+use std::time::Instant;
+
+use axum::async_trait;
+use reqwest::RequestBuilder;
+use serde_json::{Value, json};
+
+use super::{
+    EventDecoder, ModelRequest, Provider, ProviderChunk, ProviderStream, StreamItem,
+    attachment_time, content_parts, event_json, send_completion, stream_error,
+    with_attachment_time,
+};
+use crate::{
+    AppState,
+    models::{FinishReason, TokenUsage},
+    service::attachment_char_budget,
+    sse::SseEvent,
+};
+
+pub(super) struct Gemini;
+
+#[async_trait]
+impl Provider for Gemini {
+    fn name(&self) -> &'static str {
+        "gemini"
+    }
+
+    fn label(&self) -> &'static str {
+        "Gemini"
+    }
+
+    /// Le modèle fait partie de l'URL ; `alt=sse` demande le flux en SSE plutôt qu'en
+    /// tableau JSON.
+    fn url(&self, upstream_model: &str) -> String {
+        format!(
+            "https://generativelanguage.googleapis.com/v1beta/models/{upstream_model}:streamGenerateContent?alt=sse"
+        )
+    }
+
+    fn api_key_var(&self) -> Option<&'static str> {
+        Some("GEMINI_API_KEY")
+    }
+
+    fn authorize(&self, request: RequestBuilder, api_key: &str) -> RequestBuilder {
+        request.header("x-goog-api-key", api_key)
+    }
+
+    fn supports_tools(&self) -> bool {
+        false
+    }
+
+    fn event_decoder(&self) -> EventDecoder {
+        gemini_event
+    }
+
+    async fn complete(
+        &self,
+        state: &AppState,
+        request: ModelRequest<'_>,
+    ) -> Result<ProviderStream, (axum::http::StatusCode, String)> {
+        request_gemini_content(state, request).await
+    }
+}
+
+/// Même requête via `streamGenerateContent` : le prompt système passe dans
+/// `systemInstruction`, les réponses de l'IA ont le rôle `model`, et chaque message devient
+/// une liste de `parts` où les images sont en base64 dans `inlineData` (celles qui ne sont
+/// connues que par leur URL sont ignorées). Les paramètres vont dans `generationConfig`.
+async fn request_gemini_content(
+    state: &AppState,
+    request: ModelRequest<'_>,
+) -> Result<ProviderStream, (axum::http::StatusCode, String)> {
+    let ModelRequest {
+        messages,
+        model,
+        upstream_model,
+        params,
+        ..
+    } = request;
+    let system: Vec<Value> = messages
+        .iter()
+        .filter(|message| message.role == "system")
+        .map(|message| json!({ "text": message.content }))
+        .collect();
+
+    let max_chars = attachment_char_budget(state, model, messages, params.as_ref());
+    let loading_started_at = Instant::now();
+    let mut contents = Vec::with_capacity(messages.len());
+    for message in messages.iter().filter(|message| message.role != "system") {
+        let parts = gemini_parts(content_parts(state, message, false, max_chars).await?);
+        // Gemini refuse les messages sans partie (placeholder d'une réponse sans contenu).
+        if parts.is_empty() {
+            continue;
+        }
+        let role = match message.role.as_str() {
+            "assistant" => "model",
+            _ => "user",
+        };
+        contents.push(json!({ "role": role, "parts": parts }));
+    }
+    let attachment_time = attachment_time(messages, loading_started_at);
+
+    let params = params.unwrap_or_default();
+    let mut generation_config = json!({});
+    if let Some(temp) = params.temperature {
+        generation_config["temperature"] = json!(temp);
+    }
+    if let Some(max_tok) = params.max_tokens {
+        generation_config["maxOutputTokens"] = json!(max_tok);
+    }
+    if let Some(top) = params.top_p {
+        generation_config["topP"] = json!(top);
+    }
+    if let Some(s) = params.seed {
+        generation_config["seed"] = json!(s);
+    }
+    let mut request_body = json!({
+        "contents": contents,
+        "generationConfig": generation_config,
+    });
+    if !system.is_empty() {
+        request_body["systemInstruction"] = json!({ "parts": system });
+    }
+
+    let stream = send_completion(state, &Gemini, upstream_model, &request_body, None).await?;
+    Ok(with_attachment_time(stream, attachment_time))
+}
+
+/// Parties de Gemini tirées des parties au format Chat Completions, sans les textes vides.
+fn gemini_parts(parts: Vec<Value>) -> Vec<Value> {
+    parts
+        .into_iter()
+        .filter_map(|part| match part["image_url"]["url"].as_str() {
+            Some(url) => {
+                let (mime_type, data) = url
+                    .strip_prefix("data:")
+                    .and_then(|data| data.split_once(";base64,"))?;
+                Some(json!({ "inlineData": { "mimeType": mime_type, "data": data } }))
+            }
+            None => part["text"]
+                .as_str()
+                .filter(|text| !text.trim().is_empty())
+                .map(|text| json!({ "text": text })),
+        })
+        .collect()
+}
+
+/// Chunk de `streamGenerateContent`, sans `[DONE]` final. Seul le premier candidat compte ;
+/// les parties de réflexion (`thought`) sont ignorées. `usageMetadata`, cumulée, est
+/// reprise du dernier chunk, celui qui porte `finishReason` ; les tokens de réflexion sont
+/// facturés comme la réponse. Un prompt bloqué n'a pas de candidat, seulement
+/// `promptFeedback.blockReason`.
+fn gemini_event(
+    event: &SseEvent,
+    _: &mut Option<TokenUsage>,
+) -> Option<Vec<Result<StreamItem, String>>> {
+    let Some(val) = event_json(event) else {
+        return Some(Vec::new());
+    };
+    if val["error"].is_object() {
+        return Some(vec![Err(stream_error(&val["error"]))]);
+    }
+    let chunk = |chunk| Ok(StreamItem::Chunk(chunk));
+    let candidate = &val["candidates"][0];
+    let mut chunks: Vec<_> = candidate["content"]["parts"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|part| part["thought"] != true)
+        .filter_map(|part| part["text"].as_str().filter(|text| !text.is_empty()))
+        .map(|text| chunk(ProviderChunk::Text(text.to_string())))
+        .collect();
+    let reason = match candidate["finishReason"].as_str() {
+        Some("MAX_TOKENS") => Some(FinishReason::Length),
+        Some("SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII") => {
+            Some(FinishReason::ContentFilter)
+        }
+        Some(_) => Some(FinishReason::Stop),
+        None if val["promptFeedback"]["blockReason"].is_string() => {
+            Some(FinishReason::ContentFilter)
+        }
+        None => None,
+    };
+    if let Some(reason) = reason {
+        chunks.push(chunk(ProviderChunk::Finish(reason)));
+        let usage = &val["usageMetadata"];
+        if usage.is_object() {
+            let count = |field: &str| usage[field].as_i64().unwrap_or(0) as i32;
+            chunks.push(chunk(ProviderChunk::Usage(TokenUsage {
+                prompt_tokens: count("promptTokenCount"),
+                completion_tokens: count("candidatesTokenCount") + count("thoughtsTokenCount"),
+                cached_tokens: count("cachedContentTokenCount"),
+            })));
+        }
+    }
+    Some(chunks)
+}
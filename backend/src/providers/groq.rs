@@ -0,0 +1,99 @@
+//! Groq, par son API compatible avec Chat Completions.
+
+use axum::async_trait;
+use serde_json::{Value, json};
+
+use super::{
+    EventDecoder, ModelRequest, Provider, ProviderStream, openai::completion_event, send_completion,
+};
+use crate::AppState;
+
+/// Groq, compatible avec Chat Completions : texte seul, sans outils.
+pub(super) struct Groq;
+
+#[async_trait]
+impl Provider for Groq {
+    fn name(&self) -> &'static str {
+        "groq"
+    }
+
+    fn label(&self) -> &'static str {
+        "Groq"
+    }
+
+    fn url(&self, _: &str) -> String {
+        "https://api.groq.com/openai/v1/chat/completions".to_string()
+    }
+
+    fn api_key_var(&self) -> Option<&'static str> {
+        Some("GROQ_API_KEY")
+    }
+
+    fn supports_prefill(&self) -> bool {
+        true
+    }
+
+    fn supports_tools(&self) -> bool {
+        false
+    }
+
+    fn event_decoder(&self) -> EventDecoder {
+        completion_event
+    }
+
+    async fn complete(
+        &self,
+        state: &AppState,
+        request: ModelRequest<'_>,
+    ) -> Result<ProviderStream, (axum::http::StatusCode, String)> {
+        request_groq_completion(state, request).await
+    }
+}
+
+async fn request_groq_completion(
+    state: &AppState,
+    request: ModelRequest<'_>,
+) -> Result<ProviderStream, (axum::http::StatusCode, String)> {
+    let ModelRequest {
+        messages,
+        model,
+        upstream_model,
+        params,
+        ..
+    } = request;
+    if messages.iter().any(|msg| !msg.attachments.is_empty()) {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            "Les fichiers ne sont pas supportés par ce modèle.".to_string(),
+        ));
+    }
+
+    let simple_messages: Vec<Value> = messages
+        .iter()
+        .map(|msg| {
+            json!({
+                "role": msg.role,
+                "content": msg.content,
+            })
+        })
+        .collect();
+
+    let params = params.unwrap_or_default();
+    let mut request_body = json!({
+        "model": upstream_model,
+        "messages": simple_messages,
+        "stream": true,
+        "stream_options": { "include_usage": true }
+    });
+    if let Some(temp) = params.temperature {
+        request_body["temperature"] = json!(temp);
+    }
+    if let Some(max_tok) = params.max_tokens {
+        request_body[model.max_tokens_field()] = json!(max_tok);
+    }
+    if let Some(top) = params.top_p {
+        request_body["top_p"] = json!(top);
+    }
+
+    send_completion(state, &Groq, upstream_model, &request_body, None).await
+}
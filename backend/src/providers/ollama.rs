@@ -0,0 +1,194 @@
+//! Serveur Ollama du modèle local (`OLLAMA_BASE_URL`), joignable en mode `LOCAL_ONLY`.
+
+use std::time::Instant;
+
+use axum::async_trait;
+use serde_json::{Value, json};
+
+use super::{
+    EventDecoder, ModelRequest, Provider, ProviderChunk, ProviderStream, StreamItem,
+    attachment_time, content_parts, event_json, send_completion, with_attachment_time,
+};
+use crate::{
+    AppState,
+    models::{FinishReason, TokenUsage},
+    service::attachment_char_budget,
+    sse::{SseEvent, SseParser},
+};
+
+/// Fenêtre de contexte demandée à Ollama (`num_ctx`), dont le défaut tronque les longues
+/// discussions
+pub(super) const CONTEXT_WINDOW: u64 = 32_768;
+
+pub(super) struct Ollama {
+    /// URL de base, sans `/` final
+    base_url: String,
+}
+
+impl Ollama {
+    pub(super) fn new(base_url: &str) -> Self {
+        Ollama {
+            base_url: base_url.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for Ollama {
+    fn name(&self) -> &'static str {
+        "ollama"
+    }
+
+    fn label(&self) -> &'static str {
+        "Ollama"
+    }
+
+    fn url(&self, _: &str) -> String {
+        format!("{}/api/chat", self.base_url)
+    }
+
+    fn api_key_var(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    fn supports_tools(&self) -> bool {
+        false
+    }
+
+    /// Un objet JSON par ligne plutôt que du SSE
+    fn stream_parser(&self) -> SseParser {
+        SseParser::json_lines()
+    }
+
+    fn event_decoder(&self) -> EventDecoder {
+        ollama_event
+    }
+
+    async fn complete(
+        &self,
+        state: &AppState,
+        request: ModelRequest<'_>,
+    ) -> Result<ProviderStream, (axum::http::StatusCode, String)> {
+        request_ollama_chat(state, self, request).await
+    }
+}
+
+/// Même requête à un serveur Ollama (`/api/chat`) : le contenu d'un message est du texte
+/// seul, où le texte extrait des documents joints suit le message, et les images passent en
+/// base64 dans `images` (celles qui ne sont connues que par leur URL sont ignorées). Les
+/// paramètres d'échantillonnage vont dans `options`. Aucun outil n'est proposé au modèle.
+async fn request_ollama_chat(
+    state: &AppState,
+    server: &Ollama,
+    request: ModelRequest<'_>,
+) -> Result<ProviderStream, (axum::http::StatusCode, String)> {
+    let ModelRequest {
+        messages,
+        model,
+        upstream_model,
+        params,
+        ..
+    } = request;
+    let max_chars = attachment_char_budget(state, model, messages, params.as_ref());
+    let loading_started_at = Instant::now();
+    let mut formatted_messages = Vec::with_capacity(messages.len());
+    for message in messages {
+        let mut texts = Vec::new();
+        let mut images = Vec::new();
+        for part in content_parts(state, message, false, max_chars).await? {
+            match part["image_url"]["url"].as_str() {
+                Some(url) => images.extend(
+                    url.strip_prefix("data:")
+                        .and_then(|data| data.split_once(";base64,"))
+                        .map(|(_, data)| data.to_string()),
+                ),
+                None => texts.extend(
+                    part["text"]
+                        .as_str()
+                        .filter(|text| !text.is_empty())
+                        .map(str::to_string),
+                ),
+            }
+        }
+        let mut formatted = json!({ "role": message.role, "content": texts.join("\n\n") });
+        if !images.is_empty() {
+            formatted["images"] = json!(images);
+        }
+        formatted_messages.push(formatted);
+    }
+    let attachment_time = attachment_time(messages, loading_started_at);
+
+    let params = params.unwrap_or_default();
+    let mut options = json!({ "num_ctx": CONTEXT_WINDOW });
+    if let Some(temp) = params.temperature {
+        options["temperature"] = json!(temp);
+    }
+    if let Some(max_tok) = params.max_tokens {
+        options["num_predict"] = json!(max_tok);
+    }
+    if let Some(top) = params.top_p {
+        options["top_p"] = json!(top);
+    }
+    if let Some(pres) = params.presence_penalty {
+        options["presence_penalty"] = json!(pres);
+    }
+    if let Some(freq) = params.frequency_penalty {
+        options["frequency_penalty"] = json!(freq);
+    }
+    if let Some(s) = params.seed {
+        options["seed"] = json!(s);
+    }
+    let request_body = json!({
+        "model": upstream_model,
+        "messages": formatted_messages,
+        "stream": true,
+        "options": options,
+    });
+
+    let stream = send_completion(state, server, upstream_model, &request_body, None).await?;
+    Ok(with_attachment_time(stream, attachment_time))
+}
+
+/// Ligne du flux d'Ollama : un morceau de la réponse, la dernière (`done`) portant la raison
+/// de fin et la consommation. `prompt_eval_count` manque quand tout le prompt était déjà
+/// en cache. Une erreur en cours de flux arrive seule : `{"error": "..."}`.
+fn ollama_event(
+    event: &SseEvent,
+    _: &mut Option<TokenUsage>,
+) -> Option<Vec<Result<StreamItem, String>>> {
+    let Some(val) = event_json(event) else {
+        return Some(Vec::new());
+    };
+    if let Some(message) = val["error"].as_str() {
+        return Some(vec![Err(message.to_string())]);
+    }
+    Some(ollama_chunk(&val).into_iter().map(Ok).collect())
+}
+
+fn ollama_chunk(val: &Value) -> Vec<StreamItem> {
+    let mut chunks = Vec::new();
+    if let Some(content) = val["message"]["content"]
+        .as_str()
+        .filter(|text| !text.is_empty())
+    {
+        chunks.push(StreamItem::Chunk(ProviderChunk::Text(content.to_string())));
+    }
+    if val["done"] == true {
+        let reason = match val["done_reason"].as_str() {
+            Some("length") => FinishReason::Length,
+            _ => FinishReason::Stop,
+        };
+        chunks.push(StreamItem::Chunk(ProviderChunk::Finish(reason)));
+        let count = |field: &str| val[field].as_i64().unwrap_or(0) as i32;
+        chunks.push(StreamItem::Chunk(ProviderChunk::Usage(TokenUsage {
+            prompt_tokens: count("prompt_eval_count"),
+            completion_tokens: count("eval_count"),
+            cached_tokens: 0,
+        })));
+    }
+    chunks
+}
@@ -0,0 +1,447 @@
+//! API Chat Completions et API Responses d'OpenAI.
+
+use std::time::Instant;
+
+use axum::async_trait;
+use serde_json::{Value, json};
+
+use super::{
+    EventDecoder, ModelRequest, Provider, ProviderChunk, ProviderStream, StreamItem,
+    attachment_time, content_parts, event_json, prompt_cache_key, round_images, send_completion,
+    stream_error, with_attachment_time,
+};
+use crate::{
+    AppState,
+    models::{ChatMessagePayload, FinishReason, TokenUsage},
+    service::attachment_char_budget,
+    sse::SseEvent,
+    tools::{ToolCallDelta, chat_tool_definitions, responses_tool_definitions},
+};
+
+/// API Chat Completions d'OpenAI.
+pub(super) struct OpenAI;
+
+/// API Responses d'OpenAI, pour les modèles de `RESPONSES_API_MODELS`.
+pub(super) struct OpenAIResponses;
+
+#[async_trait]
+impl Provider for OpenAI {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    fn label(&self) -> &'static str {
+        "OpenAI"
+    }
+
+    fn url(&self, _: &str) -> String {
+        "https://api.openai.com/v1/chat/completions".to_string()
+    }
+
+    fn api_key_var(&self) -> Option<&'static str> {
+        Some("OPENAI_API_KEY")
+    }
+
+    fn event_decoder(&self) -> EventDecoder {
+        completion_event
+    }
+
+    async fn complete(
+        &self,
+        state: &AppState,
+        request: ModelRequest<'_>,
+    ) -> Result<ProviderStream, (axum::http::StatusCode, String)> {
+        request_openai_completion(state, request).await
+    }
+}
+
+#[async_trait]
+impl Provider for OpenAIResponses {
+    fn name(&self) -> &'static str {
+        "openai-responses"
+    }
+
+    fn label(&self) -> &'static str {
+        "OpenAI"
+    }
+
+    fn url(&self, _: &str) -> String {
+        "https://api.openai.com/v1/responses".to_string()
+    }
+
+    fn api_key_var(&self) -> Option<&'static str> {
+        Some("OPENAI_API_KEY")
+    }
+
+    fn stores_responses(&self) -> bool {
+        true
+    }
+
+    fn event_decoder(&self) -> EventDecoder {
+        response_event
+    }
+
+    async fn complete(
+        &self,
+        state: &AppState,
+        request: ModelRequest<'_>,
+    ) -> Result<ProviderStream, (axum::http::StatusCode, String)> {
+        request_openai_response(state, request).await
+    }
+}
+
+async fn request_openai_completion(
+    state: &AppState,
+    request: ModelRequest<'_>,
+) -> Result<ProviderStream, (axum::http::StatusCode, String)> {
+    let ModelRequest {
+        messages,
+        model,
+        upstream_model,
+        params,
+        rounds,
+    } = request;
+    let max_chars = attachment_char_budget(state, model, messages, params.as_ref());
+    let loading_started_at = Instant::now();
+    let mut formatted_messages = Vec::with_capacity(messages.len());
+    for message in messages {
+        formatted_messages.push(json!({
+            "role": message.role,
+            "content": content_parts(state, message, false, max_chars).await?
+        }));
+    }
+    let attachment_time = attachment_time(messages, loading_started_at);
+    // Chaque tour d'outils : la réponse de l'IA avec ses appels, puis un message `tool`
+    // par résultat.
+    for round in rounds {
+        let tool_calls: Vec<Value> = round
+            .calls
+            .iter()
+            .map(|call| {
+                json!({
+                    "id": call.id,
+                    "type": "function",
+                    "function": { "name": call.name, "arguments": call.arguments }
+                })
+            })
+            .collect();
+        formatted_messages.push(json!({
+            "role": "assistant",
+            "content": (!round.text.is_empty()).then_some(&round.text),
+            "tool_calls": tool_calls
+        }));
+        for (call, output) in round.calls.iter().zip(&round.outputs) {
+            formatted_messages.push(json!({
+                "role": "tool",
+                "tool_call_id": call.id,
+                "content": output
+            }));
+        }
+        // Un résultat d'outil ne porte que du texte : les captures suivent en message
+        // utilisateur.
+        if let Some(images) = round_images(round) {
+            formatted_messages.push(json!({
+                "role": "user",
+                "content": content_parts(state, &images, false, max_chars).await?
+            }));
+        }
+    }
+    let params = params.unwrap_or_default();
+
+    // Le cache de préfixe d'OpenAI est automatique : le prompt système et l'historique
+    // restent identiques d'un tour à l'autre, la clé regroupe les requêtes d'une même
+    // discussion sur la même machine pour maximiser les hits.
+    // Construct request body - serde will skip None values
+    let mut request_body = json!({
+        "model": upstream_model,
+        "messages": formatted_messages,
+        "stream": true,
+        "stream_options": { "include_usage": true },
+        "prompt_cache_key": prompt_cache_key(messages),
+    });
+
+    // Manually add optional params only if Some
+    if let Some(temp) = params.temperature {
+        request_body["temperature"] = json!(temp);
+    }
+    if let Some(max_tok) = params.max_tokens {
+        request_body[model.max_tokens_field()] = json!(max_tok);
+    }
+    if let Some(top) = params.top_p {
+        request_body["top_p"] = json!(top);
+    }
+    if let Some(pres) = params.presence_penalty {
+        request_body["presence_penalty"] = json!(pres);
+    }
+    if let Some(freq) = params.frequency_penalty {
+        request_body["frequency_penalty"] = json!(freq);
+    }
+    if let Some(s) = params.seed {
+        request_body["seed"] = json!(s);
+    }
+    let tools = chat_tool_definitions(state, &params);
+    if !tools.is_empty() {
+        request_body["tools"] = json!(tools);
+    }
+    let service_tier = params.service_tier.unwrap_or(state.service_tier);
+    request_body["service_tier"] = json!(service_tier.api_value());
+
+    let stream = send_completion(
+        state,
+        &OpenAI,
+        upstream_model,
+        &request_body,
+        Some(service_tier),
+    )
+    .await?;
+    Ok(with_attachment_time(stream, attachment_time))
+}
+
+/// Même requête via l'API Responses : le prompt système passe dans `instructions`, et avec
+/// `previous_response_id` OpenAI reprend la conversation qu'il a conservée, seuls les
+/// messages postérieurs à la dernière réponse de l'IA sont envoyés. Les pénalités et `seed`
+/// n'existent pas dans cette API. Après des appels d'outils, seule leur sortie est envoyée,
+/// en reprenant la réponse qui les a demandés.
+async fn request_openai_response(
+    state: &AppState,
+    request: ModelRequest<'_>,
+) -> Result<ProviderStream, (axum::http::StatusCode, String)> {
+    let ModelRequest {
+        messages,
+        model,
+        upstream_model,
+        params,
+        rounds,
+    } = request;
+    let mut params = params.unwrap_or_default();
+    let instructions: Vec<&str> = messages
+        .iter()
+        .filter(|message| message.role == "system")
+        .map(|message| message.content.as_str())
+        .collect();
+    let conversation: Vec<&ChatMessagePayload> = messages
+        .iter()
+        .filter(|message| message.role != "system")
+        .collect();
+    let new_messages = match params.previous_response_id {
+        Some(_) => {
+            let after_answer = conversation
+                .iter()
+                .rposition(|message| message.role == "assistant")
+                .map_or(0, |index| index + 1);
+            &conversation[after_answer..]
+        }
+        None => &conversation[..],
+    };
+
+    let max_chars = attachment_char_budget(state, model, messages, Some(&params));
+    let loading_started_at = Instant::now();
+    let mut input = Vec::with_capacity(new_messages.len());
+    match rounds.last() {
+        Some(round) => {
+            params.previous_response_id = round.response_id.clone();
+            for (call, output) in round.calls.iter().zip(&round.outputs) {
+                input.push(json!({
+                    "type": "function_call_output",
+                    "call_id": call.id,
+                    "output": output
+                }));
+            }
+            if let Some(images) = round_images(round) {
+                input.push(json!({
+                    "role": "user",
+                    "content": content_parts(state, &images, true, max_chars).await?
+                }));
+            }
+        }
+        None => {
+            for message in new_messages {
+                input.push(json!({
+                    "role": message.role,
+                    "content": content_parts(state, message, true, max_chars).await?
+                }));
+            }
+        }
+    }
+
+    let attachment_time = match rounds {
+        [] => attachment_time(new_messages.iter().copied(), loading_started_at),
+        _ => None,
+    };
+
+    let service_tier = params.service_tier.unwrap_or(state.service_tier);
+    let mut request_body = json!({
+        "model": upstream_model,
+        "instructions": instructions.join("\n\n"),
+        "input": input,
+        "stream": true,
+        "store": true,
+        "prompt_cache_key": prompt_cache_key(messages),
+        "service_tier": service_tier.api_value(),
+    });
+    if let Some(previous_response_id) = &params.previous_response_id {
+        request_body["previous_response_id"] = json!(previous_response_id);
+    }
+    let mut tools = Vec::new();
+    if params.web_search == Some(true) {
+        tools.push(json!({ "type": "web_search" }));
+    }
+    tools.extend(responses_tool_definitions(state, &params));
+    if !tools.is_empty() {
+        request_body["tools"] = json!(tools);
+    }
+    if let Some(temp) = params.temperature {
+        request_body["temperature"] = json!(temp);
+    }
+    if let Some(max_tok) = params.max_tokens {
+        request_body["max_output_tokens"] = json!(max_tok);
+    }
+    if let Some(top) = params.top_p {
+        request_body["top_p"] = json!(top);
+    }
+
+    let stream = send_completion(
+        state,
+        &OpenAIResponses,
+        upstream_model,
+        &request_body,
+        Some(service_tier),
+    )
+    .await?;
+    Ok(with_attachment_time(stream, attachment_time))
+}
+
+/// Évènement de Chat Completions, partagé par les providers compatibles (Groq) : `data:`
+/// seul, jusqu'à `[DONE]`.
+pub(super) fn completion_event(
+    event: &SseEvent,
+    _: &mut Option<TokenUsage>,
+) -> Option<Vec<Result<StreamItem, String>>> {
+    if event.data.trim() == "[DONE]" {
+        return None;
+    }
+    let Some(val) = event_json(event) else {
+        return Some(Vec::new());
+    };
+    // Erreur en cours de flux : `data: {"error": {...}}`
+    if val["error"].is_object() {
+        return Some(vec![Err(stream_error(&val["error"]))]);
+    }
+    Some(completion_chunk(&val).into_iter().map(Ok).collect())
+}
+
+/// Chunk de Chat Completions. Seul le premier choix compte (une seule réponse est
+/// demandée).
+fn completion_chunk(val: &Value) -> Vec<StreamItem> {
+    let mut chunks = Vec::new();
+    for choice in val["choices"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|choice| choice["index"].as_u64().unwrap_or_default() == 0)
+    {
+        let delta = &choice["delta"];
+        if let Some(content) = delta["content"].as_str().filter(|text| !text.is_empty()) {
+            chunks.push(StreamItem::Chunk(ProviderChunk::Text(content.to_string())));
+        }
+        if let Some(refusal) = delta["refusal"].as_str().filter(|text| !text.is_empty()) {
+            chunks.push(StreamItem::Chunk(ProviderChunk::Refusal(
+                refusal.to_string(),
+            )));
+        }
+        // Appels d'outils par morceaux : l'identifiant et le nom d'abord, puis les arguments.
+        for (position, call) in delta["tool_calls"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .enumerate()
+        {
+            chunks.push(StreamItem::ToolCall(ToolCallDelta {
+                index: call["index"]
+                    .as_u64()
+                    .map_or(position, |index| index as usize),
+                id: call["id"].as_str().map(str::to_string),
+                name: call["function"]["name"].as_str().map(str::to_string),
+                arguments: call["function"]["arguments"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+            }));
+        }
+        if let Some(reason) = choice["finish_reason"]
+            .as_str()
+            .and_then(FinishReason::parse)
+        {
+            chunks.push(StreamItem::Chunk(ProviderChunk::Finish(reason)));
+        }
+    }
+    if let Some(usage) = TokenUsage::from_chunk(val) {
+        chunks.push(StreamItem::Chunk(ProviderChunk::Usage(usage)));
+    }
+    chunks
+}
+
+/// Évènement typé de l'API Responses (`type` = `response.*`, repris dans `event:`), sans
+/// `[DONE]` final ; les autres types (annotations, recherche web...) sont ignorés.
+fn response_event(
+    event: &SseEvent,
+    _: &mut Option<TokenUsage>,
+) -> Option<Vec<Result<StreamItem, String>>> {
+    let Some(val) = event_json(event) else {
+        return Some(Vec::new());
+    };
+    let val = &val;
+    let response = &val["response"];
+    let chunk = |chunk| Ok(StreamItem::Chunk(chunk));
+    let items = match val["type"]
+        .as_str()
+        .or(event.event.as_deref())
+        .unwrap_or_default()
+    {
+        "response.created" => response["id"]
+            .as_str()
+            .map(|id| chunk(ProviderChunk::ResponseId(id.to_string())))
+            .into_iter()
+            .collect(),
+        "response.output_text.delta" => val["delta"]
+            .as_str()
+            .map(|delta| chunk(ProviderChunk::Text(delta.to_string())))
+            .into_iter()
+            .collect(),
+        "response.refusal.delta" => val["delta"]
+            .as_str()
+            .map(|delta| chunk(ProviderChunk::Refusal(delta.to_string())))
+            .into_iter()
+            .collect(),
+        "response.output_item.done" if val["item"]["type"] == "function_call" => {
+            let item = &val["item"];
+            vec![Ok(StreamItem::ToolCall(ToolCallDelta {
+                index: val["output_index"].as_u64().unwrap_or_default() as usize,
+                id: item["call_id"].as_str().map(str::to_string),
+                name: item["name"].as_str().map(str::to_string),
+                arguments: item["arguments"].as_str().unwrap_or_default().to_string(),
+            }))]
+        }
+        "response.completed" | "response.incomplete" => {
+            let reason = match response["incomplete_details"]["reason"].as_str() {
+                Some("max_output_tokens") => FinishReason::Length,
+                Some("content_filter") => FinishReason::ContentFilter,
+                _ => FinishReason::Stop,
+            };
+            let mut chunks = vec![chunk(ProviderChunk::Finish(reason))];
+            if let Some(usage) = TokenUsage::from_response(&response["usage"]) {
+                chunks.push(chunk(ProviderChunk::Usage(usage)));
+            }
+            chunks
+        }
+        "response.failed" | "error" => {
+            let error = [&response["error"], &val["error"]]
+                .into_iter()
+                .find(|error| error.is_object())
+                .unwrap_or(val);
+            vec![Err(stream_error(error))]
+        }
+        _ => Vec::new(),
+    };
+    Some(items)
+}
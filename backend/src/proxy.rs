@@ -0,0 +1,140 @@
+//! Déploiement derrière un reverse proxy (nginx, Traefik) : les en-têtes `X-Forwarded-For`,
+//! `X-Forwarded-Proto` et `X-Forwarded-Host` ne sont crus que s'ils viennent d'un proxy de
+//! confiance (`TRUSTED_PROXIES`), sans quoi n'importe quel client pourrait choisir son
+//! adresse IP ou l'hôte des liens générés.
+
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{HeaderMap, header, request::Parts},
+};
+use ipnet::IpNet;
+
+use crate::AppState;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+const X_FORWARDED_HOST: &str = "x-forwarded-host";
+
+/// Origine d'une requête telle que le client l'a envoyée, avant le reverse proxy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ClientOrigin {
+    /// Adresse du client ; absente quand le serveur ne connaît pas la connexion (tests)
+    pub(crate) ip: Option<IpAddr>,
+    /// `http` ou `https`
+    pub(crate) scheme: &'static str,
+    /// Hôte demandé par le client, port compris
+    pub(crate) host: Option<String>,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientOrigin {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let peer = ConnectInfo::<SocketAddr>::from_request_parts(parts, state)
+            .await
+            .ok()
+            .map(|ConnectInfo(addr)| addr.ip());
+        Ok(ClientOrigin::resolve(
+            peer,
+            &parts.headers,
+            &state.trusted_proxies,
+        ))
+    }
+}
+
+impl ClientOrigin {
+    /// Lit les en-têtes `X-Forwarded-*` si la connexion vient d'un proxy de confiance.
+    /// L'adresse du client est la dernière de `X-Forwarded-For` qui n'est pas un proxy de
+    /// confiance : les précédentes ont pu être écrites par le client lui-même.
+    pub(crate) fn resolve(peer: Option<IpAddr>, headers: &HeaderMap, trusted: &[IpNet]) -> Self {
+        let is_trusted = |ip: &IpAddr| trusted.iter().any(|net| net.contains(ip));
+        let host = first_value(headers, header::HOST.as_str());
+        if !peer.as_ref().is_some_and(is_trusted) {
+            return ClientOrigin {
+                ip: peer,
+                scheme: "http",
+                host,
+            };
+        }
+
+        let forwarded_for: Vec<IpAddr> = headers
+            .get_all(X_FORWARDED_FOR)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(parse_forwarded_ip)
+            .collect();
+        let ip = forwarded_for
+            .iter()
+            .rev()
+            .find(|ip| !is_trusted(ip))
+            .or(forwarded_for.first())
+            .copied()
+            .or(peer);
+        let scheme = match first_value(headers, X_FORWARDED_PROTO).as_deref() {
+            Some(proto) if proto.eq_ignore_ascii_case("https") => "https",
+            _ => "http",
+        };
+        ClientOrigin {
+            ip,
+            scheme,
+            host: first_value(headers, X_FORWARDED_HOST).or(host),
+        }
+    }
+
+    /// `scheme://hôte` vu par le client, si l'hôte est connu.
+    pub(crate) fn base_url(&self) -> Option<String> {
+        self.host
+            .as_ref()
+            .map(|host| format!("{}://{host}", self.scheme))
+    }
+}
+
+/// Première valeur d'un en-tête, éventuellement liste séparée par des virgules : celle
+/// écrite par le proxy le plus proche du client.
+fn first_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)?
+        .to_str()
+        .ok()?
+        .split(',')
+        .next()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+/// Adresse d'une entrée de `X-Forwarded-For`, avec ou sans port (`1.2.3.4:5678`,
+/// `[::1]:5678`).
+fn parse_forwarded_ip(entry: &str) -> Option<IpAddr> {
+    let entry = entry.trim();
+    entry
+        .parse()
+        .ok()
+        .or_else(|| entry.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+/// `TRUSTED_PROXIES` : adresses ou réseaux CIDR séparés par des virgules.
+pub(crate) fn parse_trusted_proxies(value: &str) -> Result<Vec<IpNet>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry
+                .parse::<IpNet>()
+                .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| format!("adresse ou réseau invalide: {entry}"))
+        })
+        .collect()
+}
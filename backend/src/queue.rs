@@ -0,0 +1,248 @@
+//! File d'attente des générations streamées au-delà de `RATE_LIMIT_PER_MINUTE`
+//! (`RATE_LIMIT_QUEUE`) : au lieu d'un 429, la réponse SSE commence par des évènements `queued`
+//! (position et attente estimée), puis la génération démarre quand la fenêtre du client laisse
+//! passer une requête. Lisse les rafales d'un petit déploiement au quota amont serré.
+//!
+//! La file est propre à chaque instance ; le décompte des requêtes reste partagé par Redis.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use serde_json::{Value, json};
+use tokio::{
+    sync::{Notify, mpsc},
+    time::Instant,
+};
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::limits::{RATE_LIMIT_WINDOW, RateLimiter};
+
+/// Intervalle maximal entre deux évènements `queued`, qui gardent aussi la connexion ouverte
+/// derrière un proxy
+const QUEUE_UPDATE_INTERVAL: Duration = Duration::from_secs(15);
+/// Taille maximale lue du corps d'une requête refusée à la sortie de la file
+const MAX_ERROR_BODY: usize = 64 * 1024;
+
+/// Requêtes en attente, par client, face à la limite `RATE_LIMIT_PER_MINUTE`.
+pub(crate) struct RateLimitQueue {
+    limiter: Arc<RateLimiter>,
+    /// Requêtes en attente par client au-delà desquelles la requête est refusée en 429
+    capacity: usize,
+    clients: Mutex<HashMap<String, ClientQueue>>,
+    /// Réveille les requêtes en attente quand une file avance
+    advanced: Notify,
+    next_ticket: AtomicU64,
+}
+
+struct ClientQueue {
+    tickets: VecDeque<u64>,
+    /// Fin de la fenêtre en cours du client, d'après le dernier refus
+    reset_at: Instant,
+}
+
+/// Place d'une requête dans la file de son client, libérée quand la requête sort de la file
+/// ou que le client se déconnecte.
+pub(crate) struct QueueTicket {
+    queue: Arc<RateLimitQueue>,
+    key: String,
+    id: u64,
+}
+
+impl RateLimitQueue {
+    pub(crate) fn new(limiter: Arc<RateLimiter>, capacity: usize) -> Self {
+        RateLimitQueue {
+            limiter,
+            capacity,
+            clients: Mutex::new(HashMap::new()),
+            advanced: Notify::new(),
+            next_ticket: AtomicU64::new(0),
+        }
+    }
+
+    /// Met en file une requête refusée par la limite, dont la fenêtre se termine dans
+    /// `reset_after` ; `None` si la file du client est pleine.
+    pub(crate) fn enqueue(
+        self: &Arc<Self>,
+        key: String,
+        reset_after: Duration,
+    ) -> Option<QueueTicket> {
+        let mut clients = self.clients.lock().unwrap_or_else(|err| err.into_inner());
+        let client = clients.entry(key.clone()).or_insert_with(|| ClientQueue {
+            tickets: VecDeque::new(),
+            reset_at: Instant::now() + reset_after,
+        });
+        if client.tickets.len() >= self.capacity {
+            return None;
+        }
+        let id = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        client.tickets.push_back(id);
+        Some(QueueTicket {
+            queue: self.clone(),
+            key,
+            id,
+        })
+    }
+}
+
+impl QueueTicket {
+    /// Position dans la file (1 en tête) et attente avant la fenêtre qui devrait laisser
+    /// passer la requête, en supposant que les suivantes ne sont prises par personne d'autre.
+    fn position(&self) -> (usize, Duration) {
+        let clients = self
+            .queue
+            .clients
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let Some(client) = clients.get(&self.key) else {
+            return (1, Duration::ZERO);
+        };
+        let index = client
+            .tickets
+            .iter()
+            .position(|id| *id == self.id)
+            .unwrap_or_default();
+        let windows = (index / self.queue.limiter.limit() as usize) as u32;
+        let wait = client.reset_at.saturating_duration_since(Instant::now());
+        (index + 1, wait + RATE_LIMIT_WINDOW * windows)
+    }
+
+    fn set_reset_after(&self, reset_after: Duration) {
+        let mut clients = self
+            .queue
+            .clients
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        if let Some(client) = clients.get_mut(&self.key) {
+            client.reset_at = Instant::now() + reset_after;
+        }
+    }
+
+    /// Attend que la requête soit en tête de file et que la limite la laisse passer, en
+    /// envoyant sa position à chaque changement et au moins toutes les 15 s. `false` si le
+    /// client s'est déconnecté.
+    async fn wait_turn(&self, tx: &mpsc::Sender<Result<Bytes, axum::Error>>) -> bool {
+        loop {
+            let advanced = self.queue.advanced.notified();
+            tokio::pin!(advanced);
+            advanced.as_mut().enable();
+
+            let (position, wait) = self.position();
+            if position == 1 && wait.is_zero() {
+                let rate = self.queue.limiter.hit(&self.key).await;
+                if !rate.exceeded() {
+                    return true;
+                }
+                self.set_reset_after(rate.reset_after);
+                continue;
+            }
+            let queued = json!({
+                "type": "queued",
+                "position": position,
+                "etaSeconds": wait.as_secs() + u64::from(wait.subsec_nanos() > 0),
+            });
+            if tx.send(Ok(sse_event("queued", &queued))).await.is_err() {
+                return false;
+            }
+            // La tête de file attend la fin de la fenêtre, les suivantes que la file avance.
+            let timeout = if position == 1 {
+                wait.min(QUEUE_UPDATE_INTERVAL)
+            } else {
+                QUEUE_UPDATE_INTERVAL
+            };
+            tokio::select! {
+                _ = advanced => {}
+                _ = tokio::time::sleep(timeout) => {}
+            }
+        }
+    }
+}
+
+impl Drop for QueueTicket {
+    fn drop(&mut self) {
+        let mut clients = self
+            .queue
+            .clients
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        if let Some(client) = clients.get_mut(&self.key) {
+            client.tickets.retain(|id| *id != self.id);
+            if client.tickets.is_empty() {
+                clients.remove(&self.key);
+            }
+        }
+        self.queue.advanced.notify_waiters();
+    }
+}
+
+/// Réponse SSE d'une requête mise en file : évènements `queued`, puis le flux de la
+/// génération, ou un évènement `error` si l'endpoint refuse la requête à sa sortie de file.
+pub(crate) fn queued_response(ticket: QueueTicket, request: Request, next: Next) -> Response {
+    let (tx, rx) = mpsc::channel(16);
+    tokio::spawn(async move {
+        // Une déconnexion abandonne la place dans la file, ou la génération comme sans file.
+        tokio::select! {
+            _ = tx.closed() => {}
+            _ = forward(ticket, request, next, &tx) => {}
+        }
+    });
+    (
+        [
+            (header::CONTENT_TYPE, "text/event-stream"),
+            (header::CACHE_CONTROL, "no-cache"),
+        ],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response()
+}
+
+async fn forward(
+    ticket: QueueTicket,
+    request: Request,
+    next: Next,
+    tx: &mpsc::Sender<Result<Bytes, axum::Error>>,
+) {
+    if !ticket.wait_turn(tx).await {
+        return;
+    }
+    drop(ticket);
+
+    let response = next.run(request).await;
+    let status = response.status();
+    if !status.is_success() {
+        let body = axum::body::to_bytes(response.into_body(), MAX_ERROR_BODY)
+            .await
+            .unwrap_or_default();
+        let error = json!({
+            "type": "error",
+            "status": status.as_u16(),
+            "message": String::from_utf8_lossy(&body),
+        });
+        let _ = tx.send(Ok(sse_event("error", &error))).await;
+        return;
+    }
+    let mut body = response.into_body().into_data_stream();
+    while let Some(chunk) = futures::StreamExt::next(&mut body).await {
+        if tx.send(chunk).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Évènement SSE hors génération : sans `id:`, la numérotation `seq` commence avec elle.
+fn sse_event(name: &str, data: &Value) -> Bytes {
+    Bytes::from(format!("event: {name}\ndata: {data}\n\n"))
+}
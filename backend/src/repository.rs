@@ -0,0 +1,2719 @@
+//! Accès PostgreSQL : toutes les requêtes SQL des discussions (sessions, messages, pièces
+//! jointes, brouillons) et du mur de messages passent par `ChatRepository`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::{
+    PgConnection, PgPool,
+    postgres::{PgConnectOptions, PgPoolOptions},
+};
+use uuid::Uuid;
+
+use crate::{
+    cache::{SessionCache, SessionLookup},
+    config::Config,
+    models::{
+        AttachmentDerivative, AttachmentExtraction, AttachmentMetadata, AttachmentPayload,
+        AttachmentStatus, Bookmark, BulkSessionAction, CalendarItem, CalendarItemKind,
+        ChatAttachment, ChatDraft, ChatExport, ChatMessage, ChatMessagePayload, ChatSession,
+        CompletionParams, CompletionPreset, ConversationTemplate, DailyUsage, ExportFormat,
+        ExportStatus, FeatureFlag, FinishReason, ImageRegion, LatencyBreakdown, LatencyStats,
+        Message, MessageCandidate, MessageLatency, MessageStatus, NewCalendarItem,
+        ProviderDebugLog, QueryPlan, ScrubAuditEntry, SessionUsage, SlowQuery, TokenUsage,
+        UserPreferences,
+    },
+    storage::storage_key_from_url,
+    urls::PublicUrls,
+};
+
+/// Ouvre le pool PostgreSQL selon la configuration `DB_*`. Avec `DB_CONNECT_RETRIES`, le
+/// démarrage attend que la base soit prête (docker-compose) au lieu d'échouer aussitôt.
+pub async fn connect_database(config: &Config) -> PgPool {
+    let mut connect_options: PgConnectOptions =
+        config.database_url.parse().expect("DATABASE_URL invalide");
+    if let Some(timeout_ms) = config.db_statement_timeout_ms {
+        connect_options = connect_options.options([("statement_timeout", timeout_ms.to_string())]);
+    }
+
+    let pool_options = PgPoolOptions::new()
+        .max_connections(config.db_max_connections)
+        .min_connections(config.db_min_connections)
+        .acquire_timeout(config.db_acquire_timeout);
+
+    let retries = config.db_connect_retries;
+    let delay = config.db_connect_retry_delay;
+    let mut attempt = 0;
+    loop {
+        match pool_options
+            .clone()
+            .connect_with(connect_options.clone())
+            .await
+        {
+            Ok(pool) => return pool,
+            Err(err) if attempt < retries => {
+                attempt += 1;
+                eprintln!(
+                    "PostgreSQL indisponible ({err}), nouvelle tentative {attempt}/{retries} dans {}s",
+                    delay.as_secs()
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(err) => panic!("Impossible de se connecter à la base PostgreSQL: {err}"),
+        }
+    }
+}
+
+/// Applique les migrations de `backend/migrations`, embarquées à la compilation.
+pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::migrate::MigrateError> {
+    sqlx::migrate!().run(pool).await
+}
+
+/// Échange à enregistrer d'un bloc : le message utilisateur, ses pièces jointes et la
+/// réponse de l'IA (ou son placeholder vide pendant un stream).
+pub struct NewExchange<'a> {
+    pub session_id: Uuid,
+    pub user_content: &'a str,
+    pub attachments: &'a [AttachmentPayload],
+    /// Message cité par la question
+    pub reply_to_message_id: Option<Uuid>,
+    /// Messages envoyés avec la question, enregistrés avant elle
+    pub preceding: &'a [ChatMessagePayload],
+    pub answer: &'a str,
+    pub status: MessageStatus,
+    pub usage: Option<TokenUsage>,
+    pub finish_reason: Option<FinishReason>,
+    /// Le modèle a refusé de répondre ou la réponse a été filtrée
+    pub refused: bool,
+    /// Identifiant de la réponse chez OpenAI (API Responses)
+    pub response_id: Option<&'a str>,
+    /// Modèle de la réponse et route `auto` éventuelle
+    pub model: Option<&'a str>,
+    pub route: Option<&'a str>,
+    /// Nouveau titre et icône de la session (premier message), sinon inchangés
+    pub title: Option<&'a str>,
+    pub icon: Option<&'a str>,
+}
+
+/// Réponse candidate à enregistrer avec le message (`candidates` > 1).
+pub struct NewCandidate<'a> {
+    pub content: &'a str,
+    pub status: MessageStatus,
+    pub finish_reason: Option<FinishReason>,
+    pub refused: bool,
+    pub response_id: Option<&'a str>,
+    /// Erreur de la requête au provider, pour une candidate qui n'a pas abouti
+    pub error: Option<&'a str>,
+}
+
+#[derive(Clone)]
+pub struct ChatRepository {
+    pool: PgPool,
+    /// URL des pièces jointes, calculée à la lecture depuis leur clé de stockage
+    urls: PublicUrls,
+    /// Sessions lues en entier, invalidées par chaque écriture de ce dépôt
+    sessions: Option<Arc<SessionCache>>,
+}
+
+impl ChatRepository {
+    pub fn new(pool: PgPool, urls: PublicUrls) -> Self {
+        ChatRepository {
+            pool,
+            urls,
+            sessions: None,
+        }
+    }
+
+    pub(crate) fn with_session_cache(mut self, sessions: Arc<SessionCache>) -> Self {
+        self.sessions = Some(sessions);
+        self
+    }
+
+    async fn invalidate_session(&self, session_id: Uuid) {
+        if let Some(sessions) = &self.sessions {
+            sessions.invalidate(session_id).await;
+        }
+    }
+
+    async fn invalidate_sessions(&self) {
+        if let Some(sessions) = &self.sessions {
+            sessions.invalidate_all().await;
+        }
+    }
+
+    /// Invalide la session d'un message désigné par son seul identifiant.
+    async fn invalidate_message_session(&self, message_id: Uuid) -> Result<(), sqlx::Error> {
+        if self.sessions.is_none() {
+            return Ok(());
+        }
+        let session_id = sqlx::query_scalar!(
+            r#"SELECT session_id FROM chat_messages WHERE id = $1"#,
+            message_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        if let Some(session_id) = session_id {
+            self.invalidate_session(session_id).await;
+        }
+        Ok(())
+    }
+
+    pub async fn ping(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Les générations en cours lors d'un arrêt du serveur ne reprendront jamais.
+    pub async fn close_interrupted_generations(&self) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE chat_messages
+            SET status = CASE WHEN content = '' THEN 'failed' ELSE 'incomplete' END
+            WHERE status IN ('pending', 'streaming')
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        self.invalidate_sessions().await;
+        Ok(result.rows_affected())
+    }
+
+    /// Comme les générations, les extractions en cours lors d'un arrêt ne se termineront pas.
+    pub async fn close_interrupted_extractions(&self) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE attachment_extractions
+            SET status = 'failed', error = 'extraction interrompue', updated_at = NOW()
+            WHERE status = 'pending'
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        self.invalidate_sessions().await;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn insert_pending_extraction(&self, storage_key: &str) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO attachment_extractions (storage_key)
+            VALUES ($1)
+            ON CONFLICT (storage_key)
+            DO UPDATE SET status = 'pending', content = NULL, error = NULL, updated_at = NOW()
+            "#,
+            storage_key
+        )
+        .execute(&self.pool)
+        .await?;
+        // L'état de l'extraction apparaît dans les pièces jointes de toutes les sessions
+        // qui partagent le fichier.
+        self.invalidate_sessions().await;
+        Ok(())
+    }
+
+    /// Enregistre le texte extrait, ou l'erreur qui a fait échouer l'extraction.
+    pub async fn finish_extraction(
+        &self,
+        storage_key: &str,
+        result: Result<&str, &str>,
+    ) -> Result<(), sqlx::Error> {
+        let (status, content, error) = match result {
+            Ok(content) => (AttachmentStatus::Ready, Some(content), None),
+            Err(error) => (AttachmentStatus::Failed, None, Some(error)),
+        };
+        sqlx::query!(
+            r#"
+            UPDATE attachment_extractions
+            SET status = $2, content = $3, error = $4, updated_at = NOW()
+            WHERE storage_key = $1
+            "#,
+            storage_key,
+            status.as_str(),
+            content,
+            error
+        )
+        .execute(&self.pool)
+        .await?;
+        self.invalidate_sessions().await;
+        Ok(())
+    }
+
+    pub async fn fetch_extraction(
+        &self,
+        storage_key: &str,
+    ) -> Result<Option<AttachmentExtraction>, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            SELECT status, content, error
+            FROM attachment_extractions
+            WHERE storage_key = $1
+            "#,
+            storage_key
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|row| AttachmentExtraction {
+            status: AttachmentStatus::from_db(&row.status),
+            content: row.content,
+            error: row.error,
+        }))
+    }
+
+    pub async fn delete_extraction(&self, storage_key: &str) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM attachment_extractions WHERE storage_key = $1",
+            storage_key
+        )
+        .execute(&self.pool)
+        .await?;
+        self.invalidate_sessions().await;
+        Ok(())
+    }
+
+    /// Résultats enregistrés pour un contenu (empreinte `content_hash`) de ce type MIME.
+    pub async fn fetch_attachment_derivative(
+        &self,
+        content_hash: &str,
+        mime_type: &str,
+    ) -> Result<Option<AttachmentDerivative>, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            SELECT extracted_text, width, height, page_count, duration_ms
+            FROM attachment_derivatives
+            WHERE content_hash = $1 AND mime_type = $2
+            "#,
+            content_hash,
+            mime_type
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|row| AttachmentDerivative {
+            extracted_text: row.extracted_text,
+            metadata: AttachmentMetadata {
+                width: row.width,
+                height: row.height,
+                page_count: row.page_count,
+                duration_ms: row.duration_ms,
+            },
+        }))
+    }
+
+    /// Enregistre les résultats du traitement d'un contenu ; le premier enregistré est
+    /// conservé.
+    pub async fn insert_attachment_derivative(
+        &self,
+        content_hash: &str,
+        mime_type: &str,
+        derivative: &AttachmentDerivative,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO attachment_derivatives
+                (content_hash, mime_type, extracted_text, width, height, page_count, duration_ms)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (content_hash, mime_type) DO NOTHING
+            "#,
+            content_hash,
+            mime_type,
+            derivative.extracted_text,
+            derivative.metadata.width,
+            derivative.metadata.height,
+            derivative.metadata.page_count,
+            derivative.metadata.duration_ms
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Clés de stockage encore utilisées par une pièce jointe ou un brouillon.
+    pub async fn referenced_storage_keys(&self) -> Result<HashSet<String>, sqlx::Error> {
+        let mut keys: HashSet<String> =
+            sqlx::query_scalar!(r#"SELECT storage_key FROM chat_attachments"#)
+                .fetch_all(&self.pool)
+                .await?
+                .into_iter()
+                .collect();
+
+        let drafts = sqlx::query!(
+            r#"
+            SELECT
+                attachment->>'storage_key' as storage_key,
+                attachment->>'url' as url
+            FROM chat_drafts, jsonb_array_elements(attachments) AS attachment
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        keys.extend(drafts.into_iter().filter_map(|row| {
+            row.storage_key
+                .or_else(|| row.url.as_deref().and_then(storage_key_from_url))
+        }));
+        Ok(keys)
+    }
+
+    /// Consommation cumulée des réponses de chaque session, recalculée depuis les messages.
+    pub async fn usage_by_session(&self) -> Result<Vec<SessionUsage>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                s.id,
+                s.title,
+                s.archived,
+                COUNT(m.id) as "answers!",
+                COALESCE(SUM(m.prompt_tokens), 0)::BIGINT as "prompt_tokens!",
+                COALESCE(SUM(m.completion_tokens), 0)::BIGINT as "completion_tokens!",
+                COALESCE(SUM(m.cached_tokens), 0)::BIGINT as "cached_tokens!"
+            FROM chat_sessions s
+            LEFT JOIN chat_messages m ON m.session_id = s.id AND m.role = 'assistant'
+            GROUP BY s.id
+            ORDER BY s.updated_at DESC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| SessionUsage {
+                session_id: row.id,
+                title: row.title,
+                archived: row.archived,
+                answers: row.answers,
+                prompt_tokens: row.prompt_tokens,
+                completion_tokens: row.completion_tokens,
+                cached_tokens: row.cached_tokens,
+            })
+            .collect())
+    }
+
+    /// Messages publics du plus récent au plus ancien, paginés par identifiant.
+    pub async fn list_board_messages(
+        &self,
+        before: Option<i32>,
+        author: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<Message>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                id,
+                author,
+                content,
+                created_at as "created_at: chrono::DateTime<chrono::Utc>",
+                edited_at as "edited_at: chrono::DateTime<chrono::Utc>"
+            FROM messages
+            WHERE ($1::INT IS NULL OR id < $1)
+                AND ($2::TEXT IS NULL OR author = $2)
+            ORDER BY id DESC
+            LIMIT $3
+            "#,
+            before,
+            author,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| Message {
+                id: row.id,
+                author: row.author,
+                content: row.content,
+                created_at: row.created_at,
+                edited_at: row.edited_at,
+            })
+            .collect())
+    }
+
+    pub async fn create_board_message(
+        &self,
+        author: &str,
+        content: &str,
+    ) -> Result<Message, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            INSERT INTO messages (author, content)
+            VALUES ($1, $2)
+            RETURNING
+                id,
+                author,
+                content,
+                created_at as "created_at: chrono::DateTime<chrono::Utc>",
+                edited_at as "edited_at: chrono::DateTime<chrono::Utc>"
+            "#,
+            author,
+            content
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(Message {
+            id: row.id,
+            author: row.author,
+            content: row.content,
+            created_at: row.created_at,
+            edited_at: row.edited_at,
+        })
+    }
+
+    /// Modifie un message public ; `None` s'il n'existe pas.
+    pub async fn update_board_message(
+        &self,
+        id: i32,
+        author: Option<&str>,
+        content: Option<&str>,
+    ) -> Result<Option<Message>, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            UPDATE messages
+            SET author = COALESCE($2, author),
+                content = COALESCE($3, content),
+                edited_at = NOW()
+            WHERE id = $1
+            RETURNING
+                id,
+                author,
+                content,
+                created_at as "created_at: chrono::DateTime<chrono::Utc>",
+                edited_at as "edited_at: chrono::DateTime<chrono::Utc>"
+            "#,
+            id,
+            author,
+            content
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|row| Message {
+            id: row.id,
+            author: row.author,
+            content: row.content,
+            created_at: row.created_at,
+            edited_at: row.edited_at,
+        }))
+    }
+
+    pub async fn delete_board_message(&self, id: i32) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!("DELETE FROM messages WHERE id = $1", id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Ajoute `tokens` à la consommation du jour `day` (UTC).
+    pub async fn record_tokens(&self, day: NaiveDate, tokens: i64) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO token_ledger (day, tokens) VALUES ($1, $2)
+            ON CONFLICT (day) DO UPDATE SET tokens = token_ledger.tokens + EXCLUDED.tokens
+            "#,
+            day,
+            tokens
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Tokens (prompt et complétion) consommés le jour `day` (UTC), d'après `token_ledger`.
+    pub async fn tokens_used_on(&self, day: NaiveDate) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"SELECT COALESCE(SUM(tokens), 0)::BIGINT as "tokens!" FROM token_ledger WHERE day = $1"#,
+            day
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Dernier jour présent dans `usage_daily`.
+    pub async fn last_usage_rollup_day(&self) -> Result<Option<NaiveDate>, sqlx::Error> {
+        sqlx::query_scalar!(r#"SELECT MAX(day) FROM usage_daily"#)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Recalcule `usage_daily` depuis les réponses enregistrées à partir de `since` (tout
+    /// l'historique si `None`) et renvoie le nombre de lignes écrites. Les jours antérieurs
+    /// ne sont pas touchés, même si leurs messages ont été supprimés depuis.
+    pub async fn rollup_usage(&self, since: Option<NaiveDate>) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO usage_daily (day, model, answers, prompt_tokens, completion_tokens, cached_tokens)
+            SELECT
+                (created_at AT TIME ZONE 'UTC')::DATE,
+                COALESCE(model, 'inconnu'),
+                COUNT(*),
+                COALESCE(SUM(prompt_tokens), 0),
+                COALESCE(SUM(completion_tokens), 0),
+                COALESCE(SUM(cached_tokens), 0)
+            FROM chat_messages
+            WHERE role = 'assistant'
+              AND ($1::DATE IS NULL OR (created_at AT TIME ZONE 'UTC')::DATE >= $1)
+            GROUP BY 1, 2
+            ON CONFLICT (day, model) DO UPDATE SET
+                answers = EXCLUDED.answers,
+                prompt_tokens = EXCLUDED.prompt_tokens,
+                completion_tokens = EXCLUDED.completion_tokens,
+                cached_tokens = EXCLUDED.cached_tokens,
+                updated_at = NOW()
+            "#,
+            since
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Consommation par jour et par modèle entre `from` et `to` inclus.
+    pub async fn daily_usage(
+        &self,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Result<Vec<DailyUsage>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT day, model, answers, prompt_tokens, completion_tokens, cached_tokens
+            FROM usage_daily
+            WHERE ($1::DATE IS NULL OR day >= $1) AND ($2::DATE IS NULL OR day <= $2)
+            ORDER BY day, model
+            "#,
+            from,
+            to
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| DailyUsage {
+                day: row.day,
+                model: row.model,
+                answers: row.answers,
+                prompt_tokens: row.prompt_tokens,
+                completion_tokens: row.completion_tokens,
+                cached_tokens: row.cached_tokens,
+            })
+            .collect())
+    }
+
+    /// Trace d'une requête dont des secrets ont été masqués avant l'envoi au provider.
+    pub async fn record_scrub(
+        &self,
+        provider: &str,
+        model: Option<&str>,
+        rules: &[String],
+        replacements: i32,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO scrub_audit (provider, model, rules, replacements)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            provider,
+            model,
+            rules,
+            replacements
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Requête sur le point de partir vers un provider (`PROVIDER_DEBUG_LOG`).
+    pub async fn insert_debug_log(
+        &self,
+        provider: &str,
+        url: &str,
+        request: &serde_json::Value,
+    ) -> Result<Uuid, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            INSERT INTO provider_debug_logs (provider, url, request)
+            VALUES ($1, $2, $3)
+            RETURNING id
+            "#,
+            provider,
+            url,
+            request
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn complete_debug_log(
+        &self,
+        log_id: Uuid,
+        http_status: Option<i32>,
+        response: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE provider_debug_logs
+            SET http_status = $2, response = $3, completed_at = NOW()
+            WHERE id = $1
+            "#,
+            log_id,
+            http_status,
+            response
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Rattache les requêtes d'une génération au message produit, enregistré après coup.
+    pub async fn link_debug_logs(
+        &self,
+        log_ids: &[Uuid],
+        message_id: Uuid,
+    ) -> Result<(), sqlx::Error> {
+        if log_ids.is_empty() {
+            return Ok(());
+        }
+        sqlx::query!(
+            "UPDATE provider_debug_logs SET message_id = $2 WHERE id = ANY($1)",
+            log_ids,
+            message_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Échanges journalisés, d'un message ou de tout le déploiement, du plus récent au plus
+    /// ancien.
+    pub async fn debug_logs(
+        &self,
+        message_id: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<ProviderDebugLog>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, message_id, provider, url, request, http_status, response, created_at,
+                completed_at
+            FROM provider_debug_logs
+            WHERE $1::UUID IS NULL OR message_id = $1
+            ORDER BY created_at DESC
+            LIMIT $2
+            "#,
+            message_id,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| ProviderDebugLog {
+                id: row.id,
+                message_id: row.message_id,
+                provider: row.provider,
+                url: row.url,
+                request: row.request,
+                http_status: row.http_status,
+                response: row.response,
+                created_at: row.created_at,
+                completed_at: row.completed_at,
+            })
+            .collect())
+    }
+
+    /// Temps de génération d'une réponse ; une régénération remplace le précédent.
+    pub async fn record_latency(
+        &self,
+        message_id: Uuid,
+        model: &str,
+        latency: &LatencyBreakdown,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO message_latency
+                (message_id, model, first_token_ms, generation_ms, db_ms, attachments_ms)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (message_id) DO UPDATE SET
+                model = EXCLUDED.model,
+                first_token_ms = EXCLUDED.first_token_ms,
+                generation_ms = EXCLUDED.generation_ms,
+                db_ms = EXCLUDED.db_ms,
+                attachments_ms = EXCLUDED.attachments_ms,
+                recorded_at = NOW()
+            "#,
+            message_id,
+            model,
+            latency.first_token.map(millis),
+            millis(latency.generation),
+            millis(latency.db),
+            millis(latency.attachments)
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Moyennes et 95e centiles des temps de génération par modèle, entre `from` et `to`
+    /// inclus (jours UTC).
+    pub async fn latency_stats(
+        &self,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+        model: Option<&str>,
+    ) -> Result<Vec<LatencyStats>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                model,
+                COUNT(*) AS "answers!",
+                AVG(first_token_ms)::FLOAT8 AS first_token_ms_avg,
+                PERCENTILE_CONT(0.95) WITHIN GROUP (ORDER BY first_token_ms) AS first_token_ms_p95,
+                AVG(generation_ms)::FLOAT8 AS generation_ms_avg,
+                PERCENTILE_CONT(0.95) WITHIN GROUP (ORDER BY generation_ms) AS generation_ms_p95,
+                AVG(db_ms)::FLOAT8 AS db_ms_avg,
+                AVG(attachments_ms)::FLOAT8 AS attachments_ms_avg
+            FROM message_latency
+            WHERE ($1::DATE IS NULL OR (recorded_at AT TIME ZONE 'UTC')::DATE >= $1)
+              AND ($2::DATE IS NULL OR (recorded_at AT TIME ZONE 'UTC')::DATE <= $2)
+              AND ($3::TEXT IS NULL OR model = $3)
+            GROUP BY model
+            ORDER BY model
+            "#,
+            from,
+            to,
+            model
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| LatencyStats {
+                model: row.model,
+                answers: row.answers,
+                first_token_ms_avg: row.first_token_ms_avg,
+                first_token_ms_p95: row.first_token_ms_p95,
+                generation_ms_avg: row.generation_ms_avg,
+                generation_ms_p95: row.generation_ms_p95,
+                db_ms_avg: row.db_ms_avg,
+                attachments_ms_avg: row.attachments_ms_avg,
+            })
+            .collect())
+    }
+
+    /// Réponses les plus longues à générer sur la même période.
+    pub async fn slowest_messages(
+        &self,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+        model: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<MessageLatency>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT message_id, model, first_token_ms, generation_ms, db_ms, attachments_ms,
+                recorded_at
+            FROM message_latency
+            WHERE ($1::DATE IS NULL OR (recorded_at AT TIME ZONE 'UTC')::DATE >= $1)
+              AND ($2::DATE IS NULL OR (recorded_at AT TIME ZONE 'UTC')::DATE <= $2)
+              AND ($3::TEXT IS NULL OR model = $3)
+            ORDER BY generation_ms + db_ms + attachments_ms DESC
+            LIMIT $4
+            "#,
+            from,
+            to,
+            model,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| MessageLatency {
+                message_id: row.message_id,
+                model: row.model,
+                first_token_ms: row.first_token_ms,
+                generation_ms: row.generation_ms,
+                db_ms: row.db_ms,
+                attachments_ms: row.attachments_ms,
+                recorded_at: row.recorded_at,
+            })
+            .collect())
+    }
+
+    /// Dernières requêtes masquées, de la plus récente à la plus ancienne.
+    pub async fn scrub_audit(&self, limit: i64) -> Result<Vec<ScrubAuditEntry>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, provider, model, rules, replacements, created_at
+            FROM scrub_audit
+            ORDER BY created_at DESC
+            LIMIT $1
+            "#,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| ScrubAuditEntry {
+                id: row.id,
+                provider: row.provider,
+                model: row.model,
+                rules: row.rules,
+                replacements: row.replacements,
+                created_at: row.created_at,
+            })
+            .collect())
+    }
+
+    /// Requêtes de cette base les plus lentes en moyenne selon `pg_stat_statements` ; `None`
+    /// si l'extension n'est pas installée ou pas chargée (`shared_preload_libraries`).
+    pub async fn slow_queries(&self, limit: i64) -> Result<Option<Vec<SlowQuery>>, sqlx::Error> {
+        let installed = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM pg_extension WHERE extname = 'pg_stat_statements') AS "installed!""#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if !installed {
+            return Ok(None);
+        }
+        // Vue absente à la compilation : requête vérifiée seulement à l'exécution.
+        let rows = sqlx::query_as::<_, (String, i64, f64, f64, f64, i64, Option<f64>)>(
+            r#"
+            SELECT
+                query,
+                calls,
+                total_exec_time,
+                mean_exec_time,
+                max_exec_time,
+                rows,
+                shared_blks_hit::float8 / NULLIF(shared_blks_hit + shared_blks_read, 0)
+            FROM pg_stat_statements
+            WHERE dbid = (SELECT oid FROM pg_database WHERE datname = current_database())
+            ORDER BY mean_exec_time DESC
+            LIMIT $1
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await;
+        let rows = match rows {
+            Ok(rows) => rows,
+            // Extension créée sans être préchargée : la vue refuse d'être lue.
+            Err(sqlx::Error::Database(err)) if err.code().as_deref() == Some("55000") => {
+                return Ok(None);
+            }
+            Err(err) => return Err(err),
+        };
+        Ok(Some(
+            rows.into_iter()
+                .map(
+                    |(query, calls, total_ms, mean_ms, max_ms, rows, cache_hit_ratio)| SlowQuery {
+                        query,
+                        calls,
+                        total_ms,
+                        mean_ms,
+                        max_ms,
+                        rows,
+                        cache_hit_ratio,
+                    },
+                )
+                .collect(),
+        ))
+    }
+
+    /// Plans des lectures les plus fréquentes (liste des sessions, historique et pièces
+    /// jointes d'une session), pour vérifier qu'elles passent par leurs index.
+    pub async fn explain_hot_queries(&self) -> Result<Vec<QueryPlan>, sqlx::Error> {
+        let session_id = Uuid::nil();
+        let mut plans = Vec::new();
+        for (name, sql) in [
+            (
+                "list_sessions",
+                "EXPLAIN SELECT * FROM chat_sessions WHERE archived = FALSE ORDER BY updated_at DESC",
+            ),
+            (
+                "fetch_messages",
+                "EXPLAIN SELECT * FROM chat_messages WHERE session_id = $1 ORDER BY position ASC",
+            ),
+            (
+                "fetch_attachments",
+                "EXPLAIN SELECT a.*, e.status FROM chat_attachments a \
+                 LEFT JOIN attachment_extractions e ON e.storage_key = a.storage_key \
+                 WHERE a.message_id = ANY(ARRAY(SELECT id FROM chat_messages WHERE session_id = $1)) \
+                 ORDER BY a.created_at ASC",
+            ),
+        ] {
+            let mut query = sqlx::query_scalar::<_, String>(sql);
+            if sql.contains("$1") {
+                query = query.bind(session_id);
+            }
+            plans.push(QueryPlan {
+                name: name.to_string(),
+                plan: query.fetch_all(&self.pool).await?,
+            });
+        }
+        Ok(plans)
+    }
+
+    /// Sessions non archivées, de la plus récemment active à la plus ancienne.
+    pub async fn list_sessions(&self) -> Result<Vec<ChatSession>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                id,
+                title,
+                icon,
+                created_at as "created_at: chrono::DateTime<chrono::Utc>",
+                updated_at as "updated_at: chrono::DateTime<chrono::Utc>",
+                archived,
+                folder,
+                tags
+            FROM chat_sessions
+            WHERE archived = false
+            ORDER BY updated_at DESC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut sessions = Vec::with_capacity(rows.len());
+        for row in rows {
+            sessions.push(ChatSession {
+                id: row.id,
+                title: row.title,
+                icon: row.icon,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+                archived: row.archived,
+                folder: row.folder,
+                tags: row.tags,
+                messages: self.fetch_messages(row.id).await?,
+                draft: self.fetch_draft(row.id).await?,
+            });
+        }
+        Ok(sessions)
+    }
+
+    pub async fn create_session(&self, title: &str) -> Result<ChatSession, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            INSERT INTO chat_sessions (title)
+            VALUES ($1)
+            RETURNING
+                id,
+                title,
+                icon,
+                created_at as "created_at: chrono::DateTime<chrono::Utc>",
+                updated_at as "updated_at: chrono::DateTime<chrono::Utc>",
+                archived,
+                folder,
+                tags
+            "#,
+            title
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(ChatSession {
+            id: row.id,
+            title: row.title,
+            icon: row.icon,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            archived: row.archived,
+            folder: row.folder,
+            tags: row.tags,
+            messages: Vec::new(),
+            draft: None,
+        })
+    }
+
+    /// Crée une discussion à partir d'un gabarit : message système éventuel et première
+    /// question en brouillon, dans une seule transaction.
+    pub async fn create_session_from_template(
+        &self,
+        template: &ConversationTemplate,
+    ) -> Result<Uuid, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let session_id = sqlx::query_scalar!(
+            "INSERT INTO chat_sessions (title, icon) VALUES ($1, $2) RETURNING id",
+            template.title.trim(),
+            template.icon.as_deref()
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        if let Some(system_message) = template.system_message.as_deref() {
+            insert_message(
+                &mut tx,
+                session_id,
+                "system",
+                system_message,
+                MessageStatus::Complete,
+            )
+            .await?;
+        }
+        if let Some(user_message) = template.user_message.as_deref() {
+            sqlx::query!(
+                "INSERT INTO chat_drafts (session_id, content) VALUES ($1, $2)",
+                session_id,
+                user_message
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(session_id)
+    }
+
+    pub async fn fetch_session(&self, session_id: Uuid) -> Result<ChatSession, sqlx::Error> {
+        let Some(sessions) = &self.sessions else {
+            return self.load_session(session_id).await;
+        };
+        let mark = match sessions.get(session_id).await {
+            SessionLookup::Hit(session) => return Ok(*session),
+            SessionLookup::Miss(mark) => mark,
+        };
+        let session = self.load_session(session_id).await?;
+        if let Some(mark) = mark {
+            sessions.insert(mark, &session).await;
+        }
+        Ok(session)
+    }
+
+    async fn load_session(&self, session_id: Uuid) -> Result<ChatSession, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                id,
+                title,
+                icon,
+                created_at as "created_at: chrono::DateTime<chrono::Utc>",
+                updated_at as "updated_at: chrono::DateTime<chrono::Utc>",
+                archived,
+                folder,
+                tags
+            FROM chat_sessions
+            WHERE id = $1
+            "#,
+            session_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(ChatSession {
+            id: row.id,
+            title: row.title,
+            icon: row.icon,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            archived: row.archived,
+            folder: row.folder,
+            tags: row.tags,
+            messages: self.fetch_messages(session_id).await?,
+            draft: self.fetch_draft(session_id).await?,
+        })
+    }
+
+    /// Remplace le titre (et l'icône si fournie) sans toucher à `updated_at` : titre résumé
+    /// après coup.
+    pub async fn set_session_title(
+        &self,
+        session_id: Uuid,
+        title: &str,
+        icon: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"UPDATE chat_sessions SET title = $2, icon = COALESCE($3, icon) WHERE id = $1"#,
+            session_id,
+            title,
+            icon
+        )
+        .execute(&self.pool)
+        .await?;
+        self.invalidate_session(session_id).await;
+        Ok(())
+    }
+
+    /// `None` si la session n'existe pas, sinon son état d'archivage.
+    pub async fn session_archived(&self, session_id: Uuid) -> Result<Option<bool>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"SELECT archived FROM chat_sessions WHERE id = $1"#,
+            session_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn session_exists(&self, session_id: Uuid) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM chat_sessions WHERE id = $1) AS "exists!""#,
+            session_id
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Renvoie `false` si la session n'existe pas ou était déjà archivée.
+    pub async fn archive_session(&self, session_id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE chat_sessions
+            SET archived = TRUE, updated_at = NOW()
+            WHERE id = $1 AND archived = FALSE
+            "#,
+            session_id
+        )
+        .execute(&self.pool)
+        .await?;
+        self.invalidate_session(session_id).await;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Archive les sessions sans activité (message ou brouillon) depuis `idle_days` jours,
+    /// sauf pendant une génération.
+    pub async fn archive_idle_sessions(&self, idle_days: i32) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE chat_sessions s
+            SET archived = TRUE, updated_at = NOW()
+            WHERE s.archived = FALSE
+              AND s.updated_at < NOW() - make_interval(days => $1)
+              AND NOT EXISTS (
+                  SELECT 1 FROM chat_drafts d
+                  WHERE d.session_id = s.id AND d.updated_at >= NOW() - make_interval(days => $1)
+              )
+              AND NOT EXISTS (
+                  SELECT 1 FROM chat_messages m
+                  WHERE m.session_id = s.id AND m.status IN ('pending', 'streaming')
+              )
+            "#,
+            idle_days
+        )
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() > 0 {
+            self.invalidate_sessions().await;
+        }
+        Ok(result.rows_affected())
+    }
+
+    /// Supprime les sessions archivées depuis plus de `archived_days` jours (l'archivage
+    /// met `updated_at` à jour).
+    pub async fn purge_archived_sessions(&self, archived_days: i32) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM chat_sessions
+            WHERE archived = TRUE AND updated_at < NOW() - make_interval(days => $1)
+            "#,
+            archived_days
+        )
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() > 0 {
+            self.invalidate_sessions().await;
+        }
+        Ok(result.rows_affected())
+    }
+
+    /// Ramène chaque session à ses `max_messages` derniers messages au plus. La coupe se fait
+    /// avant une question, pour que l'historique ne commence pas par une réponse orpheline.
+    pub async fn trim_session_messages(&self, max_messages: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM chat_messages m
+            USING (
+                SELECT session_id, MIN(position) FILTER (WHERE role = 'user') AS first_kept
+                FROM (
+                    SELECT
+                        session_id,
+                        position,
+                        role,
+                        ROW_NUMBER() OVER (PARTITION BY session_id ORDER BY position DESC) AS rank
+                    FROM chat_messages
+                ) recent
+                WHERE rank <= $1
+                GROUP BY session_id
+            ) cut
+            WHERE m.session_id = cut.session_id AND m.position < cut.first_kept
+            "#,
+            max_messages
+        )
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() > 0 {
+            self.invalidate_sessions().await;
+        }
+        Ok(result.rows_affected())
+    }
+
+    /// Applique `action` aux sessions `ids` dans une seule transaction et renvoie le nombre
+    /// de sessions modifiées. Si certaines n'existent pas, rien n'est modifié et leurs
+    /// identifiants sont renvoyés. Ranger ou étiqueter une session ne change pas son
+    /// `updated_at`, pour ne pas la remonter dans la liste.
+    pub async fn bulk_update_sessions(
+        &self,
+        ids: &[Uuid],
+        action: &BulkSessionAction,
+    ) -> Result<Result<u64, Vec<Uuid>>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let found: HashSet<Uuid> = sqlx::query_scalar!(
+            r#"SELECT id FROM chat_sessions WHERE id = ANY($1) FOR UPDATE"#,
+            ids
+        )
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .collect();
+        let missing: Vec<Uuid> = ids
+            .iter()
+            .filter(|id| !found.contains(id))
+            .copied()
+            .collect();
+        if !missing.is_empty() {
+            return Ok(Err(missing));
+        }
+
+        let result = match action {
+            BulkSessionAction::Archive => {
+                sqlx::query!(
+                    r#"
+                    UPDATE chat_sessions
+                    SET archived = TRUE, updated_at = NOW()
+                    WHERE id = ANY($1) AND archived = FALSE
+                    "#,
+                    ids
+                )
+                .execute(&mut *tx)
+                .await?
+            }
+            BulkSessionAction::Delete => {
+                sqlx::query!(r#"DELETE FROM chat_sessions WHERE id = ANY($1)"#, ids)
+                    .execute(&mut *tx)
+                    .await?
+            }
+            BulkSessionAction::Tag { add, remove } => {
+                sqlx::query!(
+                    r#"
+                    UPDATE chat_sessions s
+                    SET tags = t.tags
+                    FROM (
+                        SELECT
+                            id,
+                            ARRAY(
+                                SELECT DISTINCT tag
+                                FROM unnest(tags || $2::TEXT[]) tag
+                                WHERE tag <> ALL($3::TEXT[])
+                                ORDER BY tag
+                            ) AS tags
+                        FROM chat_sessions
+                        WHERE id = ANY($1)
+                    ) t
+                    WHERE s.id = t.id AND s.tags <> t.tags
+                    "#,
+                    ids,
+                    add,
+                    remove
+                )
+                .execute(&mut *tx)
+                .await?
+            }
+            BulkSessionAction::Move { folder } => {
+                sqlx::query!(
+                    r#"
+                    UPDATE chat_sessions
+                    SET folder = $2
+                    WHERE id = ANY($1) AND folder IS DISTINCT FROM $2
+                    "#,
+                    ids,
+                    folder.as_deref()
+                )
+                .execute(&mut *tx)
+                .await?
+            }
+        };
+        tx.commit().await?;
+        if result.rows_affected() > 0 {
+            self.invalidate_sessions().await;
+        }
+        Ok(Ok(result.rows_affected()))
+    }
+
+    pub async fn delete_session(&self, session_id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(r#"DELETE FROM chat_sessions WHERE id = $1"#, session_id)
+            .execute(&self.pool)
+            .await?;
+        self.invalidate_session(session_id).await;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn fetch_messages(&self, session_id: Uuid) -> Result<Vec<ChatMessage>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                id,
+                session_id,
+                role,
+                content,
+                position,
+                status,
+                prompt_tokens,
+                completion_tokens,
+                cached_tokens,
+                model,
+                route,
+                finish_reason,
+                refused,
+                response_id,
+                excluded_from_context,
+                reply_to_message_id,
+                bookmarked_at as "bookmarked_at: chrono::DateTime<chrono::Utc>",
+                reaction,
+                created_at as "created_at: chrono::DateTime<chrono::Utc>"
+            FROM chat_messages
+            WHERE session_id = $1
+            ORDER BY position ASC
+            "#,
+            session_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let message_ids: Vec<Uuid> = rows.iter().map(|row| row.id).collect();
+        let mut attachments_by_message: HashMap<Uuid, Vec<ChatAttachment>> = HashMap::new();
+        let mut candidates_by_message: HashMap<Uuid, Vec<MessageCandidate>> = HashMap::new();
+
+        if !message_ids.is_empty() {
+            let attachment_rows = sqlx::query!(
+                r#"
+                SELECT
+                    a.id,
+                    a.message_id,
+                    a.file_name,
+                    a.mime_type,
+                    a.size_bytes,
+                    a.storage_key,
+                    a.pages,
+                    a.regions as "regions: sqlx::types::Json<Vec<ImageRegion>>",
+                    a.width,
+                    a.height,
+                    a.page_count,
+                    a.duration_ms,
+                    COALESCE(e.status, 'ready') as "processing_status!",
+                    a.created_at as "created_at: chrono::DateTime<chrono::Utc>"
+                FROM chat_attachments a
+                LEFT JOIN attachment_extractions e ON e.storage_key = a.storage_key
+                WHERE a.message_id = ANY($1)
+                ORDER BY a.created_at ASC
+                "#,
+                &message_ids
+            )
+            .fetch_all(&self.pool)
+            .await?;
+
+            for row in attachment_rows {
+                attachments_by_message
+                    .entry(row.message_id)
+                    .or_default()
+                    .push(ChatAttachment {
+                        id: row.id,
+                        message_id: row.message_id,
+                        file_name: row.file_name,
+                        mime_type: row.mime_type,
+                        size_bytes: row.size_bytes,
+                        url: self.urls.upload(None, &row.storage_key),
+                        storage_key: row.storage_key,
+                        pages: row.pages,
+                        regions: row.regions.map(|regions| regions.0),
+                        metadata: AttachmentMetadata {
+                            width: row.width,
+                            height: row.height,
+                            page_count: row.page_count,
+                            duration_ms: row.duration_ms,
+                        },
+                        processing_status: AttachmentStatus::from_db(&row.processing_status),
+                        created_at: row.created_at,
+                    });
+            }
+
+            let candidate_rows = sqlx::query!(
+                r#"
+                SELECT
+                    id,
+                    message_id,
+                    position,
+                    content,
+                    status,
+                    finish_reason,
+                    refused,
+                    response_id,
+                    error,
+                    selected,
+                    created_at as "created_at: chrono::DateTime<chrono::Utc>"
+                FROM message_candidates
+                WHERE message_id = ANY($1)
+                ORDER BY position ASC
+                "#,
+                &message_ids
+            )
+            .fetch_all(&self.pool)
+            .await?;
+            for row in candidate_rows {
+                candidates_by_message
+                    .entry(row.message_id)
+                    .or_default()
+                    .push(MessageCandidate {
+                        id: row.id,
+                        position: row.position,
+                        content: row.content,
+                        status: MessageStatus::from_db(&row.status),
+                        finish_reason: row.finish_reason.as_deref().and_then(FinishReason::parse),
+                        refused: row.refused,
+                        response_id: row.response_id,
+                        error: row.error,
+                        selected: row.selected,
+                        created_at: row.created_at,
+                    });
+            }
+        }
+
+        Ok(rows
+            .into_iter()
+            .map(|row| ChatMessage {
+                id: row.id,
+                session_id: row.session_id,
+                role: row.role,
+                content: row.content,
+                position: row.position,
+                status: MessageStatus::from_db(&row.status),
+                usage: TokenUsage::from_db(
+                    row.prompt_tokens,
+                    row.completion_tokens,
+                    row.cached_tokens,
+                ),
+                model: row.model,
+                route: row.route,
+                finish_reason: row.finish_reason.as_deref().and_then(FinishReason::parse),
+                refused: row.refused,
+                response_id: row.response_id,
+                excluded_from_context: row.excluded_from_context,
+                reply_to_message_id: row.reply_to_message_id,
+                bookmarked_at: row.bookmarked_at,
+                reaction: row.reaction,
+                created_at: row.created_at,
+                attachments: attachments_by_message.remove(&row.id).unwrap_or_default(),
+                candidates: candidates_by_message.remove(&row.id).unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    pub async fn fetch_draft(&self, session_id: Uuid) -> Result<Option<ChatDraft>, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                content,
+                attachments as "attachments: sqlx::types::Json<Vec<AttachmentPayload>>",
+                updated_at as "updated_at: chrono::DateTime<chrono::Utc>"
+            FROM chat_drafts
+            WHERE session_id = $1
+            "#,
+            session_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| ChatDraft {
+            content: row.content,
+            attachments: row
+                .attachments
+                .0
+                .into_iter()
+                .map(|mut attachment| {
+                    if let Some(storage_key) = &attachment.storage_key {
+                        attachment.url = self.urls.upload(None, storage_key);
+                    }
+                    attachment
+                })
+                .collect(),
+            updated_at: row.updated_at,
+        }))
+    }
+
+    /// Crée ou remplace le brouillon de la session, renvoie sa date de mise à jour.
+    pub async fn save_draft(
+        &self,
+        session_id: Uuid,
+        content: &str,
+        attachments: &[AttachmentPayload],
+    ) -> Result<DateTime<Utc>, sqlx::Error> {
+        let updated_at = sqlx::query_scalar!(
+            r#"
+            INSERT INTO chat_drafts (session_id, content, attachments)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (session_id)
+            DO UPDATE SET content = EXCLUDED.content, attachments = EXCLUDED.attachments, updated_at = NOW()
+            RETURNING updated_at as "updated_at: chrono::DateTime<chrono::Utc>"
+            "#,
+            session_id,
+            content,
+            sqlx::types::Json(attachments) as _
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        self.invalidate_session(session_id).await;
+        Ok(updated_at)
+    }
+
+    pub async fn delete_draft(&self, session_id: Uuid) -> Result<(), sqlx::Error> {
+        clear_draft(&mut *self.pool.acquire().await?, session_id).await?;
+        self.invalidate_session(session_id).await;
+        Ok(())
+    }
+
+    /// Enregistre question et réponse dans une seule transaction (le brouillon est vidé)
+    /// et renvoie l'id du message assistant.
+    pub async fn insert_exchange(&self, exchange: NewExchange<'_>) -> Result<Uuid, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        for message in exchange.preceding {
+            insert_message(
+                &mut tx,
+                exchange.session_id,
+                &message.role,
+                &message.content,
+                MessageStatus::Complete,
+            )
+            .await?;
+        }
+        let user_message_id = insert_message(
+            &mut tx,
+            exchange.session_id,
+            "user",
+            exchange.user_content,
+            MessageStatus::Complete,
+        )
+        .await?;
+        insert_attachments(&mut tx, user_message_id, exchange.attachments).await?;
+        if let Some(reply_to_message_id) = exchange.reply_to_message_id {
+            sqlx::query!(
+                r#"UPDATE chat_messages SET reply_to_message_id = $2 WHERE id = $1"#,
+                user_message_id,
+                reply_to_message_id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        let assistant_message_id = insert_message(
+            &mut tx,
+            exchange.session_id,
+            "assistant",
+            exchange.answer,
+            exchange.status,
+        )
+        .await?;
+        set_usage(&mut tx, assistant_message_id, exchange.usage).await?;
+        sqlx::query!(
+            r#"
+            UPDATE chat_messages SET finish_reason = $2, refused = $3, response_id = $4
+            WHERE id = $1
+            "#,
+            assistant_message_id,
+            exchange.finish_reason.map(|reason| reason.as_str()),
+            exchange.refused,
+            exchange.response_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        set_model(
+            &mut tx,
+            assistant_message_id,
+            exchange.model,
+            exchange.route,
+        )
+        .await?;
+        touch_session(&mut tx, exchange.session_id, exchange.title, exchange.icon).await?;
+        clear_draft(&mut tx, exchange.session_id).await?;
+
+        tx.commit().await?;
+        self.invalidate_session(exchange.session_id).await;
+        Ok(assistant_message_id)
+    }
+
+    /// Remplace le contenu d'une réponse existante (fin de stream, régénération). Les
+    /// candidates de l'ancienne réponse sont supprimées.
+    pub async fn persist_answer(
+        &self,
+        session_id: Uuid,
+        message_id: Uuid,
+        content: &str,
+        status: MessageStatus,
+        usage: Option<TokenUsage>,
+        finish_reason: Option<FinishReason>,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+            UPDATE chat_messages
+            SET content = $2, status = $3, finish_reason = $4
+            WHERE id = $1
+            "#,
+            message_id,
+            content,
+            status.as_str(),
+            finish_reason.map(|reason| reason.as_str())
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"DELETE FROM message_candidates WHERE message_id = $1"#,
+            message_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        set_usage(&mut tx, message_id, usage).await?;
+        touch_session(&mut tx, session_id, None, None).await?;
+        tx.commit().await?;
+        self.invalidate_session(session_id).await;
+        Ok(())
+    }
+
+    /// Joint à une réponse les fichiers produits par les outils (captures de
+    /// `screenshot_page`) ; `replace` retire d'abord ceux d'une génération précédente.
+    pub async fn attach_to_answer(
+        &self,
+        message_id: Uuid,
+        attachments: &[AttachmentPayload],
+        replace: bool,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        if replace {
+            sqlx::query!(
+                r#"DELETE FROM chat_attachments WHERE message_id = $1"#,
+                message_id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        insert_attachments(&mut tx, message_id, attachments).await?;
+        tx.commit().await?;
+        self.invalidate_message_session(message_id).await
+    }
+
+    /// Enregistre les candidates d'une réponse, dans l'ordre ; la première est retenue.
+    pub async fn insert_candidates(
+        &self,
+        message_id: Uuid,
+        candidates: &[NewCandidate<'_>],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for (position, candidate) in candidates.iter().enumerate() {
+            sqlx::query!(
+                r#"
+                INSERT INTO message_candidates
+                    (message_id, position, content, status, finish_reason, refused, response_id,
+                     error, selected)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                "#,
+                message_id,
+                position as i32,
+                candidate.content,
+                candidate.status.as_str(),
+                candidate.finish_reason.map(|reason| reason.as_str()),
+                candidate.refused,
+                candidate.response_id,
+                candidate.error,
+                position == 0
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        self.invalidate_message_session(message_id).await
+    }
+
+    /// Fait de la candidate le contenu du message ; `false` si elle n'appartient pas à ce
+    /// message de la session ou si sa requête a échoué.
+    pub async fn select_candidate(
+        &self,
+        session_id: Uuid,
+        message_id: Uuid,
+        candidate_id: Uuid,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query!(
+            r#"
+            UPDATE chat_messages m
+            SET content = c.content, status = c.status, finish_reason = c.finish_reason,
+                refused = c.refused, response_id = c.response_id
+            FROM message_candidates c
+            WHERE c.id = $3 AND c.message_id = m.id AND m.id = $2 AND m.session_id = $1
+              AND c.error IS NULL
+            "#,
+            session_id,
+            message_id,
+            candidate_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        sqlx::query!(
+            r#"UPDATE message_candidates SET selected = (id = $2) WHERE message_id = $1"#,
+            message_id,
+            candidate_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        touch_session(&mut tx, session_id, None, None).await?;
+        tx.commit().await?;
+        self.invalidate_session(session_id).await;
+        Ok(true)
+    }
+
+    /// Modèle (et route `auto`) d'une réponse régénérée ou continuée.
+    pub async fn set_message_model(
+        &self,
+        message_id: Uuid,
+        model: &str,
+        route: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let mut conn = self.pool.acquire().await?;
+        set_model(&mut conn, message_id, Some(model), route).await?;
+        self.invalidate_message_session(message_id).await
+    }
+
+    /// Contenu d'un message, quelle que soit sa session.
+    pub async fn fetch_message_content(
+        &self,
+        message_id: Uuid,
+    ) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"SELECT content FROM chat_messages WHERE id = $1"#,
+            message_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Renvoie `false` si le message n'existe pas dans cette session.
+    pub async fn set_excluded_from_context(
+        &self,
+        session_id: Uuid,
+        message_id: Uuid,
+        excluded: bool,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE chat_messages
+            SET excluded_from_context = $3
+            WHERE id = $2 AND session_id = $1
+            "#,
+            session_id,
+            message_id,
+            excluded
+        )
+        .execute(&self.pool)
+        .await?;
+        self.invalidate_session(session_id).await;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Met le message en signet ou l'en retire ; renvoie `false` s'il n'existe pas dans
+    /// cette session. Un message déjà en signet garde sa date.
+    pub async fn set_bookmarked(
+        &self,
+        session_id: Uuid,
+        message_id: Uuid,
+        bookmarked: bool,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE chat_messages
+            SET bookmarked_at = CASE WHEN $3 THEN COALESCE(bookmarked_at, NOW()) END
+            WHERE id = $2 AND session_id = $1
+            "#,
+            session_id,
+            message_id,
+            bookmarked
+        )
+        .execute(&self.pool)
+        .await?;
+        self.invalidate_session(session_id).await;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Renvoie `false` si le message n'existe pas dans cette session.
+    pub async fn set_reaction(
+        &self,
+        session_id: Uuid,
+        message_id: Uuid,
+        reaction: Option<&str>,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE chat_messages
+            SET reaction = $3
+            WHERE id = $2 AND session_id = $1
+            "#,
+            session_id,
+            message_id,
+            reaction
+        )
+        .execute(&self.pool)
+        .await?;
+        self.invalidate_session(session_id).await;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Messages en signet de toutes les discussions, archivées comprises, du plus récent au
+    /// plus ancien.
+    pub async fn list_bookmarks(&self) -> Result<Vec<Bookmark>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                m.id,
+                m.session_id,
+                s.title,
+                s.archived,
+                m.role,
+                m.content,
+                m.model,
+                m.reaction,
+                m.created_at as "created_at: chrono::DateTime<chrono::Utc>",
+                m.bookmarked_at as "bookmarked_at!: chrono::DateTime<chrono::Utc>"
+            FROM chat_messages m
+            JOIN chat_sessions s ON s.id = m.session_id
+            WHERE m.bookmarked_at IS NOT NULL
+            ORDER BY m.bookmarked_at DESC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Bookmark {
+                message_id: row.id,
+                session_id: row.session_id,
+                session_title: row.title,
+                session_archived: row.archived,
+                role: row.role,
+                content: row.content,
+                model: row.model,
+                reaction: row.reaction,
+                created_at: row.created_at,
+                bookmarked_at: row.bookmarked_at,
+            })
+            .collect())
+    }
+
+    /// Identifiant OpenAI de la réponse (fin de stream, régénération) ; `None` l'efface.
+    pub async fn set_response_id(
+        &self,
+        message_id: Uuid,
+        response_id: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"UPDATE chat_messages SET response_id = $2 WHERE id = $1"#,
+            message_id,
+            response_id
+        )
+        .execute(&self.pool)
+        .await?;
+        self.invalidate_message_session(message_id).await
+    }
+
+    /// Réponse refusée par le modèle ou filtrée (fin de stream, régénération).
+    pub async fn set_refused(&self, message_id: Uuid, refused: bool) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"UPDATE chat_messages SET refused = $2 WHERE id = $1"#,
+            message_id,
+            refused
+        )
+        .execute(&self.pool)
+        .await?;
+        self.invalidate_message_session(message_id).await
+    }
+
+    pub async fn set_message_status(
+        &self,
+        message_id: Uuid,
+        status: MessageStatus,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"UPDATE chat_messages SET status = $2 WHERE id = $1"#,
+            message_id,
+            status.as_str()
+        )
+        .execute(&self.pool)
+        .await?;
+        self.invalidate_message_session(message_id).await
+    }
+
+    pub async fn list_presets(&self) -> Result<Vec<CompletionPreset>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                id,
+                name,
+                params as "params: sqlx::types::Json<CompletionParams>",
+                created_at as "created_at: chrono::DateTime<chrono::Utc>",
+                updated_at as "updated_at: chrono::DateTime<chrono::Utc>"
+            FROM completion_presets
+            ORDER BY name ASC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| CompletionPreset {
+                id: row.id,
+                name: row.name,
+                params: row.params.0,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            })
+            .collect())
+    }
+
+    pub async fn fetch_preset_params(
+        &self,
+        preset_id: Uuid,
+    ) -> Result<Option<CompletionParams>, sqlx::Error> {
+        let params = sqlx::query_scalar!(
+            r#"
+            SELECT params as "params: sqlx::types::Json<CompletionParams>"
+            FROM completion_presets
+            WHERE id = $1
+            "#,
+            preset_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(params.map(|params| params.0))
+    }
+
+    /// Préférences enregistrées, avec la date du dernier enregistrement.
+    pub async fn fetch_preferences(&self) -> Result<Option<UserPreferences>, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            SELECT preferences as "preferences: sqlx::types::Json<UserPreferences>", updated_at
+            FROM user_preferences
+            "#
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|row| UserPreferences {
+            updated_at: Some(row.updated_at),
+            ..row.preferences.0
+        }))
+    }
+
+    /// Remplace les préférences ; renvoie la date d'enregistrement.
+    pub async fn save_preferences(
+        &self,
+        preferences: &UserPreferences,
+    ) -> Result<DateTime<Utc>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            INSERT INTO user_preferences (preferences)
+            VALUES ($1)
+            ON CONFLICT (id) DO UPDATE SET preferences = EXCLUDED.preferences, updated_at = NOW()
+            RETURNING updated_at
+            "#,
+            sqlx::types::Json(preferences) as _
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn insert_calendar_item(
+        &self,
+        item: &NewCalendarItem,
+    ) -> Result<CalendarItem, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            INSERT INTO calendar_items (kind, title, description, starts_at, ends_at, all_day)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, created_at as "created_at: chrono::DateTime<chrono::Utc>"
+            "#,
+            item.kind.as_str(),
+            item.title,
+            item.description,
+            item.starts_at,
+            item.ends_at,
+            item.all_day
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(CalendarItem {
+            id: row.id,
+            kind: item.kind,
+            title: item.title.clone(),
+            description: item.description.clone(),
+            starts_at: item.starts_at,
+            ends_at: item.ends_at,
+            all_day: item.all_day,
+            created_at: row.created_at,
+        })
+    }
+
+    /// Éléments du calendrier, par date ; les tâches sans échéance à la fin.
+    pub async fn calendar_items(&self) -> Result<Vec<CalendarItem>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                id,
+                kind,
+                title,
+                description,
+                starts_at as "starts_at: chrono::DateTime<chrono::Utc>",
+                ends_at as "ends_at: chrono::DateTime<chrono::Utc>",
+                all_day,
+                created_at as "created_at: chrono::DateTime<chrono::Utc>"
+            FROM calendar_items
+            ORDER BY starts_at ASC NULLS LAST, created_at ASC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| CalendarItem {
+                id: row.id,
+                kind: CalendarItemKind::from_db(&row.kind),
+                title: row.title,
+                description: row.description,
+                starts_at: row.starts_at,
+                ends_at: row.ends_at,
+                all_day: row.all_day,
+                created_at: row.created_at,
+            })
+            .collect())
+    }
+
+    /// `false` si l'élément n'existe pas.
+    pub async fn delete_calendar_item(&self, item_id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!("DELETE FROM calendar_items WHERE id = $1", item_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Jeton du lien d'abonnement au calendrier, créé au premier appel.
+    pub async fn calendar_feed_token(&self) -> Result<Uuid, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            INSERT INTO calendar_feed DEFAULT VALUES
+            ON CONFLICT (id) DO UPDATE SET token = calendar_feed.token
+            RETURNING token
+            "#
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Remplace le jeton : l'ancien lien d'abonnement cesse de fonctionner.
+    pub async fn rotate_calendar_token(&self) -> Result<Uuid, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            INSERT INTO calendar_feed DEFAULT VALUES
+            ON CONFLICT (id) DO UPDATE SET token = gen_random_uuid()
+            RETURNING token
+            "#
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Un nom déjà pris renvoie une erreur de contrainte d'unicité.
+    pub async fn insert_preset(
+        &self,
+        name: &str,
+        params: &CompletionParams,
+    ) -> Result<CompletionPreset, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            INSERT INTO completion_presets (name, params)
+            VALUES ($1, $2)
+            RETURNING
+                id,
+                created_at as "created_at: chrono::DateTime<chrono::Utc>",
+                updated_at as "updated_at: chrono::DateTime<chrono::Utc>"
+            "#,
+            name,
+            sqlx::types::Json(params) as _
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(CompletionPreset {
+            id: row.id,
+            name: name.to_string(),
+            params: params.clone(),
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        })
+    }
+
+    /// Renvoie `None` si le preset n'existe pas.
+    pub async fn update_preset(
+        &self,
+        preset_id: Uuid,
+        name: &str,
+        params: &CompletionParams,
+    ) -> Result<Option<CompletionPreset>, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            UPDATE completion_presets
+            SET name = $2, params = $3, updated_at = NOW()
+            WHERE id = $1
+            RETURNING
+                created_at as "created_at: chrono::DateTime<chrono::Utc>",
+                updated_at as "updated_at: chrono::DateTime<chrono::Utc>"
+            "#,
+            preset_id,
+            name,
+            sqlx::types::Json(params) as _
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| CompletionPreset {
+            id: preset_id,
+            name: name.to_string(),
+            params: params.clone(),
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }))
+    }
+
+    pub async fn delete_preset(&self, preset_id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(r#"DELETE FROM completion_presets WHERE id = $1"#, preset_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn fetch_feature_flags(&self) -> Result<Vec<FeatureFlag>, sqlx::Error> {
+        sqlx::query_as!(
+            FeatureFlag,
+            r#"
+            SELECT
+                name,
+                enabled,
+                rollout_percent,
+                clients,
+                description,
+                updated_at as "updated_at: chrono::DateTime<chrono::Utc>"
+            FROM feature_flags
+            ORDER BY name
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Crée ou remplace le drapeau `name`.
+    pub async fn upsert_feature_flag(
+        &self,
+        name: &str,
+        enabled: bool,
+        rollout_percent: i16,
+        clients: &[String],
+        description: Option<&str>,
+    ) -> Result<FeatureFlag, sqlx::Error> {
+        sqlx::query_as!(
+            FeatureFlag,
+            r#"
+            INSERT INTO feature_flags (name, enabled, rollout_percent, clients, description)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (name) DO UPDATE SET
+                enabled = EXCLUDED.enabled,
+                rollout_percent = EXCLUDED.rollout_percent,
+                clients = EXCLUDED.clients,
+                description = EXCLUDED.description,
+                updated_at = NOW()
+            RETURNING
+                name,
+                enabled,
+                rollout_percent,
+                clients,
+                description,
+                updated_at as "updated_at: chrono::DateTime<chrono::Utc>"
+            "#,
+            name,
+            enabled,
+            rollout_percent,
+            clients,
+            description
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn delete_feature_flag(&self, name: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(r#"DELETE FROM feature_flags WHERE name = $1"#, name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Mode maintenance en cours : son message et sa date de début ; `None` hors maintenance.
+    pub async fn fetch_maintenance(
+        &self,
+    ) -> Result<Option<(Option<String>, DateTime<Utc>)>, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"SELECT message, since as "since: DateTime<Utc>" FROM maintenance_mode"#
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|row| (row.message, row.since)))
+    }
+
+    /// Active le mode maintenance ou change son message ; un mode déjà actif garde sa date
+    /// de début.
+    pub async fn start_maintenance(
+        &self,
+        message: Option<&str>,
+    ) -> Result<DateTime<Utc>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            INSERT INTO maintenance_mode (message) VALUES ($1)
+            ON CONFLICT (id) DO UPDATE SET message = EXCLUDED.message
+            RETURNING since as "since: DateTime<Utc>"
+            "#,
+            message
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn end_maintenance(&self) -> Result<(), sqlx::Error> {
+        sqlx::query!(r#"DELETE FROM maintenance_mode"#)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Toutes les sessions, archivées comprises, de la plus ancienne à la plus récente.
+    pub async fn all_session_ids(&self) -> Result<Vec<Uuid>, sqlx::Error> {
+        sqlx::query_scalar!(r#"SELECT id FROM chat_sessions ORDER BY created_at"#)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Recrée une session sauvegardée avec ses identifiants, ses messages, les métadonnées de
+    /// ses pièces jointes et son brouillon. Renvoie `false` sans rien modifier si la session
+    /// existe déjà.
+    pub async fn restore_session(&self, session: &ChatSession) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let inserted = sqlx::query_scalar!(
+            r#"
+            INSERT INTO chat_sessions
+                (id, title, icon, created_at, updated_at, archived, folder, tags)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (id) DO NOTHING
+            RETURNING id
+            "#,
+            session.id,
+            session.title,
+            session.icon,
+            session.created_at,
+            session.updated_at,
+            session.archived,
+            session.folder,
+            &session.tags
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        if inserted.is_none() {
+            return Ok(false);
+        }
+
+        for message in &session.messages {
+            sqlx::query!(
+                r#"
+                INSERT INTO chat_messages
+                    (id, session_id, role, content, position, status, model, route,
+                     finish_reason, refused, excluded_from_context, bookmarked_at, reaction,
+                     created_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+                "#,
+                message.id,
+                session.id,
+                message.role,
+                message.content,
+                message.position,
+                message.status.as_str(),
+                message.model,
+                message.route,
+                message.finish_reason.map(|reason| reason.as_str()),
+                message.refused,
+                message.excluded_from_context,
+                message.bookmarked_at,
+                message.reaction,
+                message.created_at
+            )
+            .execute(&mut *tx)
+            .await?;
+            set_usage(&mut tx, message.id, message.usage).await?;
+            for attachment in &message.attachments {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO chat_attachments
+                        (id, message_id, file_name, mime_type, size_bytes, storage_key, pages,
+                         regions, width, height, page_count, duration_ms, created_at)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+                    "#,
+                    attachment.id,
+                    message.id,
+                    attachment.file_name,
+                    attachment.mime_type,
+                    attachment.size_bytes,
+                    attachment.storage_key,
+                    attachment.pages,
+                    attachment.regions.as_ref().map(sqlx::types::Json) as _,
+                    attachment.metadata.width,
+                    attachment.metadata.height,
+                    attachment.metadata.page_count,
+                    attachment.metadata.duration_ms,
+                    attachment.created_at
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+        // Une fois tous les messages insérés : la citation peut viser n'importe lequel.
+        for message in &session.messages {
+            if let Some(reply_to_message_id) = message.reply_to_message_id {
+                sqlx::query!(
+                    r#"
+                    UPDATE chat_messages SET reply_to_message_id = $2
+                    WHERE id = $1 AND EXISTS (SELECT 1 FROM chat_messages WHERE id = $2)
+                    "#,
+                    message.id,
+                    reply_to_message_id
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+        if let Some(draft) = &session.draft {
+            sqlx::query!(
+                r#"
+                INSERT INTO chat_drafts (session_id, content, attachments, updated_at)
+                VALUES ($1, $2, $3, $4)
+                "#,
+                session.id,
+                draft.content,
+                sqlx::types::Json(&draft.attachments) as _,
+                draft.updated_at
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        self.invalidate_session(session.id).await;
+        Ok(true)
+    }
+
+    /// Les exports en cours lors d'un arrêt du serveur ne se termineront pas.
+    pub async fn close_interrupted_exports(&self) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE chat_exports
+            SET status = 'failed', error = 'export interrompu'
+            WHERE status IN ('pending', 'running')
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn insert_export(
+        &self,
+        format: ExportFormat,
+        session_id: Option<Uuid>,
+    ) -> Result<Uuid, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"INSERT INTO chat_exports (format, session_id) VALUES ($1, $2) RETURNING id"#,
+            format.as_str(),
+            session_id
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn set_export_progress(
+        &self,
+        export_id: Uuid,
+        sessions_done: i32,
+        sessions_total: i32,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE chat_exports
+            SET status = 'running', sessions_done = $2, sessions_total = $3
+            WHERE id = $1
+            "#,
+            export_id,
+            sessions_done,
+            sessions_total
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Enregistre l'archive produite et la fin de validité de son lien, ou l'erreur qui a
+    /// fait échouer l'export.
+    pub async fn finish_export(
+        &self,
+        export_id: Uuid,
+        result: Result<(&str, DateTime<Utc>), &str>,
+    ) -> Result<(), sqlx::Error> {
+        let (status, file_name, expires_at, error) = match result {
+            Ok((file_name, expires_at)) => {
+                (ExportStatus::Ready, Some(file_name), Some(expires_at), None)
+            }
+            Err(error) => (ExportStatus::Failed, None, None, Some(error)),
+        };
+        sqlx::query!(
+            r#"
+            UPDATE chat_exports
+            SET status = $2, file_name = $3, expires_at = $4, error = $5
+            WHERE id = $1
+            "#,
+            export_id,
+            status.as_str(),
+            file_name,
+            expires_at,
+            error
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Archives des exports dont le lien a expiré, encore présentes sur disque.
+    pub async fn expired_export_files(&self) -> Result<Vec<(Uuid, String)>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, file_name as "file_name!"
+            FROM chat_exports
+            WHERE status = 'ready' AND expires_at <= NOW() AND file_name IS NOT NULL
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.id, row.file_name))
+            .collect())
+    }
+
+    pub async fn clear_export_file(&self, export_id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE chat_exports SET file_name = NULL WHERE id = $1",
+            export_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Export, `expired` passé `expires_at` ; le lien de téléchargement est laissé au
+    /// handler, qui connaît l'URL publique.
+    pub async fn fetch_export(&self, export_id: Uuid) -> Result<Option<ChatExport>, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                id,
+                status,
+                format,
+                session_id,
+                sessions_done,
+                sessions_total,
+                file_name,
+                download_token,
+                error,
+                created_at as "created_at: chrono::DateTime<chrono::Utc>",
+                expires_at as "expires_at: chrono::DateTime<chrono::Utc>"
+            FROM chat_exports
+            WHERE id = $1
+            "#,
+            export_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| {
+            let mut status = ExportStatus::from_db(&row.status);
+            if status == ExportStatus::Ready
+                && row
+                    .expires_at
+                    .is_some_and(|expires_at| expires_at <= Utc::now())
+            {
+                status = ExportStatus::Expired;
+            }
+            ChatExport {
+                id: row.id,
+                status,
+                format: ExportFormat::from_db(&row.format),
+                session_id: row.session_id,
+                sessions_done: row.sessions_done,
+                sessions_total: row.sessions_total,
+                download_url: None,
+                error: row.error,
+                created_at: row.created_at,
+                expires_at: row.expires_at,
+                file_name: row.file_name,
+                download_token: row.download_token,
+            }
+        }))
+    }
+}
+
+async fn insert_message(
+    conn: &mut PgConnection,
+    session_id: Uuid,
+    role: &str,
+    content: &str,
+    status: MessageStatus,
+) -> Result<Uuid, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        INSERT INTO chat_messages (session_id, role, content, status, position)
+        VALUES (
+            $1,
+            $2,
+            $3,
+            $4,
+            COALESCE((SELECT MAX(position) FROM chat_messages WHERE session_id = $1), 0) + 1
+        )
+        RETURNING id
+        "#,
+        session_id,
+        role,
+        content,
+        status.as_str()
+    )
+    .fetch_one(conn)
+    .await
+}
+
+async fn insert_attachments(
+    conn: &mut PgConnection,
+    message_id: Uuid,
+    attachments: &[AttachmentPayload],
+) -> Result<(), sqlx::Error> {
+    for attachment in attachments {
+        let storage_key = attachment
+            .storage_key
+            .clone()
+            .or_else(|| storage_key_from_url(&attachment.url))
+            .unwrap_or_default();
+        if storage_key.is_empty() {
+            continue;
+        }
+        sqlx::query!(
+            r#"
+            INSERT INTO chat_attachments
+                (message_id, file_name, mime_type, size_bytes, storage_key, pages,
+                 regions, width, height, page_count, duration_ms)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#,
+            message_id,
+            attachment.file_name,
+            attachment.mime_type,
+            attachment.size_bytes,
+            storage_key,
+            attachment.pages,
+            attachment.regions.as_ref().map(sqlx::types::Json) as _,
+            attachment.metadata.width,
+            attachment.metadata.height,
+            attachment.metadata.page_count,
+            attachment.metadata.duration_ms
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+async fn set_usage(
+    conn: &mut PgConnection,
+    message_id: Uuid,
+    usage: Option<TokenUsage>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE chat_messages
+        SET prompt_tokens = $2, completion_tokens = $3, cached_tokens = $4
+        WHERE id = $1
+        "#,
+        message_id,
+        usage.map(|usage| usage.prompt_tokens),
+        usage.map(|usage| usage.completion_tokens),
+        usage.map(|usage| usage.cached_tokens)
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn set_model(
+    conn: &mut PgConnection,
+    message_id: Uuid,
+    model: Option<&str>,
+    route: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE chat_messages SET model = $2, route = $3 WHERE id = $1"#,
+        message_id,
+        model,
+        route
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Met à jour `updated_at` (et le titre ou l'icône s'ils sont fournis) d'une session.
+async fn touch_session(
+    conn: &mut PgConnection,
+    session_id: Uuid,
+    title: Option<&str>,
+    icon: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE chat_sessions
+        SET title = COALESCE($2, title), icon = COALESCE($3, icon), updated_at = NOW()
+        WHERE id = $1
+        "#,
+        session_id,
+        title,
+        icon
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn clear_draft(conn: &mut PgConnection, session_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"DELETE FROM chat_drafts WHERE session_id = $1"#,
+        session_id
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Durée en millisecondes pour une colonne `INTEGER`.
+fn millis(duration: Duration) -> i32 {
+    i32::try_from(duration.as_millis()).unwrap_or(i32::MAX)
+}
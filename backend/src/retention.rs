@@ -0,0 +1,79 @@
+//! Règles de rétention des discussions : archivage des sessions inactives, purge des
+//! archives anciennes et plafond de messages par session, appliqués par la tâche planifiée
+//! `retention` pour que la base ne grossisse pas indéfiniment.
+
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::repository::ChatRepository;
+
+/// Règles configurées par `RETENTION_*` ; chacune est désactivée si absente.
+#[derive(Clone, Copy, Debug)]
+pub struct RetentionPolicy {
+    /// Archive les sessions sans activité depuis ce nombre de jours
+    pub archive_idle_days: Option<u32>,
+    /// Supprime les sessions archivées depuis ce nombre de jours
+    pub purge_archived_days: Option<u32>,
+    /// Ne garde que les derniers messages de chaque session
+    pub max_messages_per_session: Option<u32>,
+    /// Intervalle entre deux applications des règles
+    pub interval: Duration,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy {
+            archive_idle_days: None,
+            purge_archived_days: None,
+            max_messages_per_session: None,
+            interval: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl RetentionPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.archive_idle_days.is_some()
+            || self.purge_archived_days.is_some()
+            || self.max_messages_per_session.is_some()
+    }
+}
+
+/// Ce qu'une application des règles a changé.
+#[derive(Serialize, Clone, Copy, Debug, Default)]
+pub struct RetentionReport {
+    pub archived_sessions: u64,
+    pub purged_sessions: u64,
+    pub trimmed_messages: u64,
+}
+
+impl std::fmt::Display for RetentionReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} sessions archivées, {} supprimées, {} messages retirés",
+            self.archived_sessions, self.purged_sessions, self.trimmed_messages
+        )
+    }
+}
+
+/// Applique les règles dans l'ordre : archivage, purge puis plafond de messages. Les
+/// fichiers joints des sessions purgées restent sur disque jusqu'au passage de la
+/// tâche `upload_gc` ou de `carlgpt-admin purge-orphan-uploads`.
+pub async fn apply_retention(
+    repo: &ChatRepository,
+    policy: &RetentionPolicy,
+) -> Result<RetentionReport, sqlx::Error> {
+    let mut report = RetentionReport::default();
+    if let Some(days) = policy.archive_idle_days {
+        report.archived_sessions = repo.archive_idle_sessions(days as i32).await?;
+    }
+    if let Some(days) = policy.purge_archived_days {
+        report.purged_sessions = repo.purge_archived_sessions(days as i32).await?;
+    }
+    if let Some(max_messages) = policy.max_messages_per_session {
+        report.trimmed_messages = repo.trim_session_messages(max_messages.into()).await?;
+    }
+    Ok(report)
+}
@@ -0,0 +1,171 @@
+//! Modèle `auto` : choisit un modèle économique ou puissant d'après la conversation (longueur,
+//! pièces jointes, code, maths, coût estimé). La route retenue est enregistrée sur la réponse.
+
+use crate::{
+    access::{Caller, ModelPolicy},
+    models::ChatMessagePayload,
+    providers::AiModelChoice,
+    service::estimate_tokens,
+};
+
+/// Identifiant à passer dans `model` pour laisser le serveur choisir.
+pub(crate) const AUTO_MODEL: &str = "auto";
+
+/// Au-delà, la conversation part sur un modèle à grande fenêtre de contexte et bon marché.
+const LONG_PROMPT_TOKENS: u64 = 8_000;
+
+/// Coût maximal du prompt sur un modèle puissant ; au-delà, on repasse sur un modèle économique.
+const MAX_POWERFUL_PROMPT_COST_USD: f64 = 0.05;
+
+const SIMPLE_MODELS: &[AiModelChoice] = &[
+    AiModelChoice::GroqLlama31,
+    AiModelChoice::OpenAIGpt5Nano,
+    AiModelChoice::OpenAIGpt5Mini,
+];
+const ATTACHMENT_MODELS: &[AiModelChoice] = &[
+    AiModelChoice::OpenAIGpt5Mini,
+    AiModelChoice::OpenAIGpt5Nano,
+    AiModelChoice::OpenAIGpt51,
+];
+const LONG_MODELS: &[AiModelChoice] = &[
+    AiModelChoice::OpenAIGpt5Mini,
+    AiModelChoice::OpenAIGpt5Nano,
+    AiModelChoice::OpenAIGpt41,
+];
+const POWERFUL_MODELS: &[AiModelChoice] = &[
+    AiModelChoice::OpenAIGpt51,
+    AiModelChoice::OpenAIGpt5,
+    AiModelChoice::OpenAIGpt41,
+    AiModelChoice::OpenAIGpt5Mini,
+];
+
+const MATH_MARKERS: &[&str] = &[
+    "\\frac",
+    "\\int",
+    "\\sum",
+    "\\sqrt",
+    "$$",
+    "∫",
+    "∑",
+    "√",
+    "intégrale",
+    "dérivée",
+    "équation",
+    "démontre",
+    "théorème",
+    "integral",
+    "derivative",
+    "equation",
+    "theorem",
+];
+
+/// Raison du choix fait par le modèle `auto`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Route {
+    Simple,
+    Long,
+    Attachments,
+    Code,
+    Math,
+    /// Code ou maths, mais le prompt coûterait trop cher sur un modèle puissant
+    Cost,
+}
+
+impl Route {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Route::Simple => "simple",
+            Route::Long => "long",
+            Route::Attachments => "attachments",
+            Route::Code => "code",
+            Route::Math => "math",
+            Route::Cost => "cost",
+        }
+    }
+}
+
+/// Modèle retenu pour une requête, et la route suivie si le client a demandé `auto`.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ModelSelection {
+    pub(crate) model: AiModelChoice,
+    pub(crate) route: Option<Route>,
+}
+
+/// Choisit le modèle de `payload` parmi ceux autorisés à l'appelant. Si aucun candidat de la
+/// route ne convient, on retombe sur le modèle par défaut du déploiement.
+pub(crate) fn route(
+    payload: &[ChatMessagePayload],
+    policy: &ModelPolicy,
+    caller: Caller,
+) -> ModelSelection {
+    let prompt_tokens: u64 = payload
+        .iter()
+        .map(|message| estimate_tokens(&message.content))
+        .sum();
+    let has_attachments = payload
+        .iter()
+        .any(|message| !message.attachments.is_empty());
+    let question = payload
+        .iter()
+        .rev()
+        .find(|message| message.role == "user")
+        .map(|message| message.content.as_str())
+        .unwrap_or_default();
+
+    let demanding = if looks_like_code(question) {
+        Some(Route::Code)
+    } else if looks_like_math(question) {
+        Some(Route::Math)
+    } else {
+        None
+    };
+    let route = match demanding {
+        Some(_)
+            if AiModelChoice::OpenAIGpt51.cost_usd(prompt_tokens, 0)
+                > MAX_POWERFUL_PROMPT_COST_USD =>
+        {
+            Route::Cost
+        }
+        Some(route) => route,
+        None if has_attachments => Route::Attachments,
+        None if prompt_tokens > LONG_PROMPT_TOKENS => Route::Long,
+        None => Route::Simple,
+    };
+    let candidates = match route {
+        Route::Simple => SIMPLE_MODELS,
+        Route::Attachments => ATTACHMENT_MODELS,
+        Route::Long | Route::Cost => LONG_MODELS,
+        Route::Code | Route::Math => POWERFUL_MODELS,
+    };
+
+    let model = candidates
+        .iter()
+        .copied()
+        .find(|model| {
+            policy.allows(*model, caller)
+                && (!has_attachments || model.supports_attachments())
+                && prompt_tokens < model.context_window()
+        })
+        .unwrap_or(policy.default);
+    ModelSelection {
+        model,
+        route: Some(route),
+    }
+}
+
+fn looks_like_code(text: &str) -> bool {
+    if text.contains("```") {
+        return true;
+    }
+    let code_lines = text
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| line.ends_with(';') || line.ends_with('{') || line.ends_with('}'))
+        .count();
+    code_lines >= 2
+}
+
+fn looks_like_math(text: &str) -> bool {
+    let text = text.to_lowercase();
+    MATH_MARKERS.iter().any(|marker| text.contains(marker))
+}
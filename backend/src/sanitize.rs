@@ -0,0 +1,146 @@
+//! Réparation des réponses avant persistance (blocs de code, diagrammes mermaid).
+
+use crate::AppState;
+
+const MERMAID_DIAGRAM_TYPES: &[&str] = &[
+    "graph",
+    "flowchart",
+    "sequenceDiagram",
+    "classDiagram",
+    "classDiagram-v2",
+    "stateDiagram",
+    "stateDiagram-v2",
+    "erDiagram",
+    "journey",
+    "gantt",
+    "pie",
+    "quadrantChart",
+    "requirementDiagram",
+    "gitGraph",
+    "C4Context",
+    "mindmap",
+    "timeline",
+    "sankey-beta",
+    "xychart-beta",
+    "block-beta",
+];
+
+/// Post-traitement appliqué à une réponse complète avant de la persister.
+pub(crate) fn finalize_answer(state: &AppState, answer: String) -> String {
+    if !state.validate_code_blocks {
+        return answer;
+    }
+    let (repaired, issues) = repair_code_blocks(&answer);
+    for issue in &issues {
+        eprintln!("Bloc de code réparé: {issue}");
+    }
+    repaired
+}
+
+/// Vérifie les blocs de code délimités (```lang / ~~~lang) d'une réponse :
+/// - ferme les blocs laissés ouverts (réponse coupée, fence oubliée) ;
+/// - ajoute `text` aux blocs sans langage ;
+/// - retague en `text` les diagrammes mermaid sans type de diagramme reconnu,
+///   qui font planter le rendu côté frontend.
+///
+/// Retourne le texte réparé et la liste des problèmes rencontrés.
+fn repair_code_blocks(text: &str) -> (String, Vec<String>) {
+    let mut result = String::with_capacity(text.len() + 16);
+    let mut issues = Vec::new();
+    // (caractère de fence, longueur, indentation, langage, lignes du bloc)
+    let mut open: Option<(char, usize, String, String, Vec<&str>)> = None;
+
+    for line in text.split('\n') {
+        let trimmed = line.trim_start();
+        let indent = &line[..line.len() - trimmed.len()];
+        let fence_char = trimmed.chars().next().filter(|c| *c == '`' || *c == '~');
+        let fence_len = fence_char
+            .map(|c| trimmed.chars().take_while(|ch| *ch == c).count())
+            .unwrap_or(0);
+
+        let closes_block = open.as_ref().is_some_and(|(c, len, ..)| {
+            fence_char == Some(*c) && fence_len >= *len && trimmed[fence_len..].trim().is_empty()
+        });
+
+        if closes_block {
+            if let Some((c, len, indent, lang, body)) = open.take() {
+                push_code_block(&mut result, &mut issues, c, len, &indent, &lang, &body);
+            }
+            result.push_str(line);
+            result.push('\n');
+        } else if let Some((.., body)) = open.as_mut() {
+            body.push(line);
+        } else if let Some(c) = fence_char.filter(|_| fence_len >= 3) {
+            let lang = trimmed[fence_len..].trim().to_string();
+            open = Some((c, fence_len, indent.to_string(), lang, Vec::new()));
+        } else {
+            result.push_str(line);
+            result.push('\n');
+        }
+    }
+
+    if let Some((c, len, indent, lang, body)) = open.take() {
+        issues.push(format!("bloc `{}` non fermé", display_lang(&lang)));
+        push_code_block(&mut result, &mut issues, c, len, &indent, &lang, &body);
+        result.push_str(&indent);
+        result.push_str(&c.to_string().repeat(len));
+        result.push('\n');
+    }
+
+    // `split` produit un segment final : on retire le saut de ligne ajouté en trop.
+    result.pop();
+    (result, issues)
+}
+
+fn push_code_block(
+    result: &mut String,
+    issues: &mut Vec<String>,
+    fence_char: char,
+    fence_len: usize,
+    indent: &str,
+    lang: &str,
+    body: &[&str],
+) {
+    let mut lang = lang.to_string();
+    if lang.is_empty() {
+        issues.push("bloc de code sans langage".to_string());
+        lang = "text".to_string();
+    } else if lang.eq_ignore_ascii_case("mermaid") && !is_valid_mermaid(body) {
+        issues.push("diagramme mermaid sans type reconnu".to_string());
+        lang = "text".to_string();
+    }
+
+    result.push_str(indent);
+    result.push_str(&fence_char.to_string().repeat(fence_len));
+    result.push_str(&lang);
+    result.push('\n');
+    for line in body {
+        result.push_str(line);
+        result.push('\n');
+    }
+}
+
+fn is_valid_mermaid(body: &[&str]) -> bool {
+    let mut in_front_matter = false;
+    for line in body {
+        let line = line.trim();
+        if line == "---" {
+            in_front_matter = !in_front_matter;
+            continue;
+        }
+        if in_front_matter || line.is_empty() || line.starts_with("%%") {
+            continue;
+        }
+        let keyword = line.split_whitespace().next().unwrap_or("");
+        return MERMAID_DIAGRAM_TYPES.contains(&keyword);
+    }
+    false
+}
+
+fn display_lang(lang: &str) -> &str {
+    if lang.is_empty() {
+        "sans langage"
+    } else {
+        lang
+    }
+}
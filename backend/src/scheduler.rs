@@ -0,0 +1,110 @@
+//! Tâches de maintenance planifiées : chaque tâche nommée tourne à intervalle fixe dans le
+//! processus, et l'issue de sa dernière exécution est exposée par `GET /api/admin/jobs`.
+
+use std::{future::Future, sync::Mutex, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::time::MissedTickBehavior;
+
+use crate::AppState;
+
+/// Tâches enregistrées et état de leur dernière exécution.
+#[derive(Default)]
+pub(crate) struct Scheduler {
+    jobs: Mutex<Vec<JobStatus>>,
+}
+
+/// État d'une tâche planifiée.
+#[derive(Serialize, Clone, Debug)]
+pub struct JobStatus {
+    pub name: &'static str,
+    pub interval_secs: u64,
+    pub running: bool,
+    pub last_started_at: Option<DateTime<Utc>>,
+    pub last_finished_at: Option<DateTime<Utc>>,
+    pub last_result: Option<JobResult>,
+    /// Résumé renvoyé par la tâche, ou son erreur
+    pub last_message: Option<String>,
+    pub next_run_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobResult {
+    Ok,
+    Failed,
+}
+
+impl Scheduler {
+    /// Enregistre la tâche `name` et la lance : une première fois tout de suite, puis toutes
+    /// les `interval`. Une exécution qui dépasse l'intervalle décale la suivante au lieu de
+    /// se chevaucher avec elle.
+    pub(crate) fn spawn<F, Fut>(
+        &self,
+        state: &AppState,
+        name: &'static str,
+        interval: Duration,
+        job: F,
+    ) where
+        F: Fn(AppState) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<String, String>> + Send,
+    {
+        self.jobs.lock().unwrap().push(JobStatus {
+            name,
+            interval_secs: interval.as_secs(),
+            running: false,
+            last_started_at: None,
+            last_finished_at: None,
+            last_result: None,
+            last_message: None,
+            next_run_at: Some(Utc::now()),
+        });
+
+        let state = state.clone();
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticks.tick().await;
+                state.scheduler.update(name, |status| {
+                    status.running = true;
+                    status.last_started_at = Some(Utc::now());
+                });
+                let outcome = job(state.clone()).await;
+                if let Err(err) = &outcome {
+                    eprintln!("Tâche planifiée {name} en échec: {err}");
+                }
+                state.scheduler.update(name, |status| {
+                    let finished_at = Utc::now();
+                    status.running = false;
+                    status.last_finished_at = Some(finished_at);
+                    status.next_run_at = Some(finished_at + interval);
+                    let (result, message) = match outcome {
+                        Ok(summary) => (JobResult::Ok, summary),
+                        Err(err) => (JobResult::Failed, err),
+                    };
+                    status.last_result = Some(result);
+                    status.last_message = Some(message);
+                });
+            }
+        });
+    }
+
+    /// État de toutes les tâches, dans l'ordre d'enregistrement.
+    pub(crate) fn statuses(&self) -> Vec<JobStatus> {
+        self.jobs.lock().unwrap().clone()
+    }
+
+    fn update(&self, name: &str, change: impl FnOnce(&mut JobStatus)) {
+        if let Some(status) = self
+            .jobs
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .find(|status| status.name == name)
+        {
+            change(status);
+        }
+    }
+}
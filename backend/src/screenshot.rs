@@ -0,0 +1,192 @@
+//! Outil `screenshot_page` (`completion_params.screenshot`) : le modèle fait capturer une
+//! page web par un chromium headless (`SCREENSHOT_SERVICE_URL`, API `/screenshot` de
+//! browserless), puis la regarde pour la décrire ou la critiquer (« à quoi ressemble ce
+//! site ? », « critique cette landing page »). La capture est jointe à la réponse.
+//!
+//! Le navigateur tourne dans le déploiement : une page de la machine ou du réseau privé
+//! n'est capturée que si son hôte figure dans `SCREENSHOT_ALLOWED_HOSTS`, et l'outil est
+//! fermé en mode `LOCAL_ONLY`.
+
+use image::ImageFormat;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::{
+    AppState,
+    models::AttachmentPayload,
+    outbound::{is_private, resolve},
+    storage::store_file,
+};
+
+pub(crate) const SCREENSHOT_TOOL: &str = "screenshot_page";
+
+pub(crate) const SCREENSHOT_DESCRIPTION: &str = "Capture une page web publique telle qu'un \
+navigateur l'affiche (fenêtre de 1280 × 800 px, ou page entière) ; l'image t'est montrée \
+juste après le résultat. À utiliser quand l'utilisateur demande à quoi ressemble une page ou \
+une critique de sa mise en page, pas pour en lire le texte.";
+
+/// Largeur et hauteur de la fenêtre du navigateur, en pixels
+const VIEWPORT: (u32, u32) = (1280, 800);
+/// Taille maximale d'une capture acceptée du service
+const MAX_SCREENSHOT_BYTES: usize = 10 * 1024 * 1024;
+
+#[derive(Deserialize)]
+struct ScreenshotArgs {
+    url: String,
+    #[serde(default)]
+    full_page: bool,
+}
+
+pub(crate) fn screenshot_parameters() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "url": {
+                "type": "string",
+                "description": "Adresse complète de la page, en http ou https (ex. https://example.com/tarifs)"
+            },
+            "full_page": {
+                "type": "boolean",
+                "description": "Capture toute la hauteur de la page au lieu de la seule partie visible sans défiler"
+            }
+        },
+        "required": ["url"],
+        "additionalProperties": false
+    })
+}
+
+/// Exécute un appel de l'outil : le résultat, renvoyé au modèle, et la capture enregistrée
+/// dans les uploads, à lui montrer et à joindre à la réponse.
+pub(crate) async fn screenshot_page(
+    state: &AppState,
+    arguments: &str,
+) -> (String, Option<AttachmentPayload>) {
+    let args: ScreenshotArgs = match serde_json::from_str(arguments) {
+        Ok(args) => args,
+        Err(err) => {
+            return (
+                format!("Arguments invalides pour {SCREENSHOT_TOOL} : {err}."),
+                None,
+            );
+        }
+    };
+    let url = match reqwest::Url::parse(args.url.trim()) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => url,
+        _ => {
+            return (
+                format!(
+                    "Adresse invalide : {}. Donne une URL complète en http ou https.",
+                    args.url
+                ),
+                None,
+            );
+        }
+    };
+    if let Err(err) = check_target(state, &url).await {
+        return (format!("Capture de {url} refusée : {err}."), None);
+    }
+    let data = match capture(state, &url, args.full_page).await {
+        Ok(data) => data,
+        Err(err) => return (format!("Capture de {url} impossible : {err}."), None),
+    };
+    let host = url.host_str().unwrap_or("page").replace('.', "-");
+    match store_file(state, &format!("capture-{host}.png"), "image/png", &data).await {
+        Ok(attachment) => {
+            let (width, height) = (
+                attachment.metadata.width.unwrap_or_default(),
+                attachment.metadata.height.unwrap_or_default(),
+            );
+            (
+                format!(
+                    "Capture de {url} réalisée ({width} × {height} px) ; elle t'est montrée \
+                     dans le message suivant et jointe à ta réponse."
+                ),
+                Some(attachment),
+            )
+        }
+        Err((_, err)) => (
+            format!("Capture de {url} impossible : enregistrement échoué ({err})."),
+            None,
+        ),
+    }
+}
+
+/// Refuse une page dont l'hôte résout vers une adresse interne (boucle locale, réseau privé,
+/// lien local dont les métadonnées des clouds), sauf hôte de `SCREENSHOT_ALLOWED_HOSTS`.
+async fn check_target(state: &AppState, url: &reqwest::Url) -> Result<(), String> {
+    let host = url.host_str().unwrap_or_default();
+    if state
+        .screenshot_allowed_hosts
+        .iter()
+        .any(|allowed| allowed == host)
+    {
+        return Ok(());
+    }
+    let addresses = resolve(url).await?;
+    if addresses.is_empty() {
+        return Err(format!("{host} introuvable"));
+    }
+    if addresses.into_iter().any(is_private) {
+        return Err(format!(
+            "{host} est une adresse interne, hors de SCREENSHOT_ALLOWED_HOSTS"
+        ));
+    }
+    Ok(())
+}
+
+/// Demande la capture au service : un PNG, vérifié avant d'être gardé.
+async fn capture(state: &AppState, url: &reqwest::Url, full_page: bool) -> Result<Vec<u8>, String> {
+    let Some(service_url) = &state.screenshot_service_url else {
+        return Err("aucun service de capture configuré (SCREENSHOT_SERVICE_URL)".to_string());
+    };
+    let response = state
+        .outbound_client(service_url)
+        .await?
+        .post(service_url)
+        .timeout(state.screenshot_timeout)
+        .json(&json!({
+            "url": url.as_str(),
+            "options": { "type": "png", "fullPage": full_page },
+            "viewport": { "width": VIEWPORT.0, "height": VIEWPORT.1 }
+        }))
+        .send()
+        .await
+        .map_err(|err| {
+            if err.is_timeout() {
+                format!(
+                    "pas de réponse du service en {} s",
+                    state.screenshot_timeout.as_secs()
+                )
+            } else {
+                format!("service injoignable ({err})")
+            }
+        })?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!(
+            "le service a répondu {status} ({})",
+            body.trim().chars().take(200).collect::<String>()
+        ));
+    }
+    // Lue par morceaux : une réponse trop lourde est abandonnée sans être gardée en entier.
+    let mut response = response;
+    let mut data = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|err| format!("réponse du service illisible ({err})"))?
+    {
+        if data.len() + chunk.len() > MAX_SCREENSHOT_BYTES {
+            return Err(format!(
+                "capture trop lourde ({} Mo au plus)",
+                MAX_SCREENSHOT_BYTES / (1024 * 1024)
+            ));
+        }
+        data.extend_from_slice(&chunk);
+    }
+    if !matches!(image::guess_format(&data), Ok(ImageFormat::Png)) {
+        return Err("le service n'a pas renvoyé une image PNG".to_string());
+    }
+    Ok(data)
+}
@@ -0,0 +1,203 @@
+//! Masquage des secrets dans ce qui part vers les providers externes : clés d'API, noms
+//! d'hôtes internes et motifs configurés sont remplacés par `[masqué:<règle>]` dans les
+//! messages, le texte des pièces jointes et les résultats d'outils. Chaque requête masquée
+//! laisse une entrée dans `scrub_audit`, sans le texte masqué.
+
+use std::collections::BTreeMap;
+
+use regex::Regex;
+use serde_json::Value;
+
+use crate::{AppState, models::ChatMessagePayload};
+
+/// Règles configurées par `SCRUB_SECRETS`, `SCRUB_HOSTNAMES` et `SCRUB_PATTERNS_FILE`.
+#[derive(Clone, Debug, Default)]
+pub struct ScrubPolicy {
+    /// Motifs intégrés des clés d'API et clés privées courantes
+    pub builtin_secrets: bool,
+    /// Noms d'hôtes internes, masqués avec leurs sous-domaines
+    pub hostnames: Vec<String>,
+    /// Expressions régulières supplémentaires, une par règle
+    pub patterns: Vec<String>,
+}
+
+/// Motifs intégrés : (nom de la règle, expression).
+const BUILTIN_SECRETS: &[(&str, &str)] = &[
+    (
+        "openai_key",
+        r"\bsk-(?:proj-|svcacct-|admin-)?[A-Za-z0-9_-]{20,}",
+    ),
+    ("groq_key", r"\bgsk_[A-Za-z0-9]{20,}"),
+    ("anthropic_key", r"\bsk-ant-[A-Za-z0-9_-]{20,}"),
+    ("aws_access_key", r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b"),
+    (
+        "github_token",
+        r"\b(?:gh[pousr]_[A-Za-z0-9]{36,}|github_pat_[A-Za-z0-9_]{22,})",
+    ),
+    ("slack_token", r"\bxox[abprs]-[A-Za-z0-9-]{10,}"),
+    ("google_api_key", r"\bAIza[0-9A-Za-z_-]{35}"),
+    ("stripe_key", r"\b[rs]k_live_[A-Za-z0-9]{20,}"),
+    (
+        "jwt",
+        r"\beyJ[A-Za-z0-9_-]{10,}\.eyJ[A-Za-z0-9_-]{10,}\.[A-Za-z0-9_-]{10,}",
+    ),
+    (
+        "private_key",
+        r"-----BEGIN [A-Z ]*PRIVATE KEY-----[\s\S]*?-----END [A-Z ]*PRIVATE KEY-----",
+    ),
+];
+
+struct ScrubRule {
+    name: String,
+    regex: Regex,
+}
+
+/// Règles compilées au démarrage ; sans règle, le masquage ne coûte rien.
+pub(crate) struct Scrubber {
+    rules: Vec<ScrubRule>,
+}
+
+/// Remplacements faits dans une requête, par règle.
+#[derive(Debug, Default)]
+pub(crate) struct ScrubReport {
+    pub(crate) replacements: BTreeMap<String, usize>,
+}
+
+impl ScrubReport {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.replacements.is_empty()
+    }
+
+    pub(crate) fn total(&self) -> usize {
+        self.replacements.values().sum()
+    }
+}
+
+impl Scrubber {
+    /// Compile les règles ; une expression invalide est une erreur de configuration.
+    pub(crate) fn new(policy: &ScrubPolicy) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        if policy.builtin_secrets {
+            for (name, pattern) in BUILTIN_SECRETS {
+                rules.push(ScrubRule {
+                    name: name.to_string(),
+                    regex: Regex::new(pattern).map_err(|err| err.to_string())?,
+                });
+            }
+        }
+        for hostname in &policy.hostnames {
+            let hostname = hostname.trim().trim_start_matches("*.").to_lowercase();
+            if hostname.is_empty() {
+                continue;
+            }
+            rules.push(ScrubRule {
+                name: "hostname".to_string(),
+                regex: Regex::new(&format!(
+                    r"(?i)\b(?:[a-z0-9-]+\.)*{}\b",
+                    regex::escape(&hostname)
+                ))
+                .map_err(|err| format!("SCRUB_HOSTNAMES ({hostname}): {err}"))?,
+            });
+        }
+        for (index, pattern) in policy.patterns.iter().enumerate() {
+            rules.push(ScrubRule {
+                name: format!("custom-{}", index + 1),
+                regex: Regex::new(pattern)
+                    .map_err(|err| format!("motif de masquage {}: {err}", index + 1))?,
+            });
+        }
+        Ok(Scrubber { rules })
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        !self.rules.is_empty()
+    }
+
+    /// Masque `text` en place et compte les remplacements dans `report`.
+    pub(crate) fn scrub_text(&self, text: &mut String, report: &mut ScrubReport) {
+        for rule in &self.rules {
+            let count = rule.regex.find_iter(text).count();
+            if count == 0 {
+                continue;
+            }
+            let replacement = format!("[masqué:{}]", rule.name);
+            *text = rule
+                .regex
+                .replace_all(text, regex::NoExpand(&replacement))
+                .into_owned();
+            *report.replacements.entry(rule.name.clone()).or_default() += count;
+        }
+    }
+
+    /// Masque toutes les chaînes d'un corps de requête JSON, sauf les data URLs (images et
+    /// fichiers encodés en base64).
+    pub(crate) fn scrub_json(&self, value: &mut Value, report: &mut ScrubReport) {
+        match value {
+            Value::String(text) if !text.starts_with("data:") => self.scrub_text(text, report),
+            Value::Array(items) => {
+                for item in items {
+                    self.scrub_json(item, report);
+                }
+            }
+            Value::Object(fields) => {
+                for field in fields.values_mut() {
+                    self.scrub_json(field, report);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Corps de requête masqué, ou `None` s'il n'y avait rien à masquer. Le masquage est tracé
+/// dans `scrub_audit`.
+pub(crate) async fn scrub_request(state: &AppState, provider: &str, body: &Value) -> Option<Value> {
+    if !state.scrubber.is_enabled() {
+        return None;
+    }
+    let mut scrubbed = body.clone();
+    let mut report = ScrubReport::default();
+    state.scrubber.scrub_json(&mut scrubbed, &mut report);
+    if report.is_empty() {
+        return None;
+    }
+    record(state, provider, body["model"].as_str(), &report).await;
+    Some(scrubbed)
+}
+
+/// Même masquage pour les messages remis au provider simulé.
+pub(crate) async fn scrub_messages(
+    state: &AppState,
+    provider: &str,
+    model: &str,
+    messages: &[ChatMessagePayload],
+) -> Vec<ChatMessagePayload> {
+    let mut scrubbed = messages.to_vec();
+    if !state.scrubber.is_enabled() {
+        return scrubbed;
+    }
+    let mut report = ScrubReport::default();
+    for message in &mut scrubbed {
+        state.scrubber.scrub_text(&mut message.content, &mut report);
+    }
+    if !report.is_empty() {
+        record(state, provider, Some(model), &report).await;
+    }
+    scrubbed
+}
+
+/// Une trace manquante ne bloque pas la requête, déjà masquée.
+async fn record(state: &AppState, provider: &str, model: Option<&str>, report: &ScrubReport) {
+    let rules: Vec<String> = report.replacements.keys().cloned().collect();
+    let replacements = i32::try_from(report.total()).unwrap_or(i32::MAX);
+    if let Err(err) = state
+        .repo
+        .record_scrub(provider, model, &rules, replacements)
+        .await
+    {
+        eprintln!(
+            "Impossible de tracer le masquage ({}): {err}",
+            rules.join(", ")
+        );
+    }
+}
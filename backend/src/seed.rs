@@ -0,0 +1,183 @@
+//! Données de démonstration (`backend --seed`) : quelques discussions avec réponses, pièces
+//! jointes, brouillon et discussion archivée, pour avoir un frontend réaliste sur une base
+//! fraîchement migrée.
+
+use axum::http::StatusCode;
+use uuid::Uuid;
+
+use crate::{
+    AppState, internal_error,
+    models::{AttachmentPayload, FinishReason, MessageStatus, TokenUsage},
+    repository::NewExchange,
+    storage::store_file,
+};
+
+/// PNG 1×1 transparent, assez pour tester l'affichage et l'envoi d'une image.
+const SAMPLE_PNG: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f, 0x15, 0xc4,
+    0x89, 0x00, 0x00, 0x00, 0x0a, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x00, 0x01, 0x00, 0x00,
+    0x05, 0x00, 0x01, 0x0d, 0x0a, 0x2d, 0xb4, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae,
+    0x42, 0x60, 0x82,
+];
+
+const SAMPLE_NOTES: &str = "# Rapport trimestriel\n\n\
+- Chiffre d'affaires : 1,2 M€ (+8 %)\n\
+- Nouveaux clients : 42\n\
+- Taux de churn : 3,1 %\n\n\
+Priorités du prochain trimestre : onboarding, facturation annuelle, support en anglais.\n";
+
+struct SampleExchange {
+    question: &'static str,
+    answer: &'static str,
+    status: MessageStatus,
+}
+
+/// Crée les données de démonstration si aucune discussion n'existe encore et renvoie le
+/// nombre de discussions créées (0 si la base contenait déjà des données).
+pub async fn seed(state: &AppState) -> Result<usize, (StatusCode, String)> {
+    let repo = &state.repo;
+    if !repo
+        .list_sessions()
+        .await
+        .map_err(internal_error)?
+        .is_empty()
+    {
+        return Ok(0);
+    }
+
+    let notes = store_file(
+        state,
+        "rapport-trimestriel.md",
+        "text/markdown",
+        SAMPLE_NOTES.as_bytes(),
+    )
+    .await?;
+    let image = store_file(state, "schema.png", "image/png", SAMPLE_PNG).await?;
+
+    let rust = create_sample_session(
+        state,
+        "Découverte de Rust",
+        &[
+            SampleExchange {
+                question: "Comment lire un fichier ligne par ligne en Rust ?",
+                answer: "Avec `BufReader` :\n\n```rust\nuse std::fs::File;\nuse std::io::{BufRead, BufReader};\n\nfn main() -> std::io::Result<()> {\n    let file = File::open(\"notes.txt\")?;\n    for line in BufReader::new(file).lines() {\n        println!(\"{}\", line?);\n    }\n    Ok(())\n}\n```\n\n`lines()` renvoie des `Result<String>` : chaque ligne peut échouer (encodage invalide, erreur disque).",
+                status: MessageStatus::Complete,
+            },
+            SampleExchange {
+                question: "Et pour compter les mots ?",
+                answer: "Il suffit de découper chaque ligne :\n\n```rust\nlet words: usize = BufReader::new(file)\n    .lines()\n    .map_while(Result::ok)\n    .map(|line| line.split_whitespace().count())\n    .sum();\n```",
+                status: MessageStatus::Complete,
+            },
+        ],
+        &[],
+    )
+    .await?;
+
+    create_sample_session(
+        state,
+        "Résumé du rapport trimestriel",
+        &[SampleExchange {
+            question: "Peux-tu résumer ce rapport en trois points ?",
+            answer: "1. **Croissance** : 1,2 M€ de chiffre d'affaires, en hausse de 8 %.\n2. **Acquisition** : 42 nouveaux clients, pour un churn contenu à 3,1 %.\n3. **Priorités** : améliorer l'onboarding, proposer la facturation annuelle et un support en anglais.",
+            status: MessageStatus::Complete,
+        }],
+        std::slice::from_ref(&notes),
+    )
+    .await?;
+
+    create_sample_session(
+        state,
+        "Mathématiques et schémas",
+        &[
+            SampleExchange {
+                question: "Que représente ce schéma ?",
+                answer: "L'image jointe est un pixel transparent : il n'y a rien à analyser, mais l'envoi d'images fonctionne.",
+                status: MessageStatus::Complete,
+            },
+            SampleExchange {
+                question: "Donne-moi la formule de la variance.",
+                answer: "Pour une variable aléatoire $X$ d'espérance $\\mu$ :\n\n$$\n\\operatorname{Var}(X) = \\mathbb{E}\\left[(X - \\mu)^2\\right] = \\mathbb{E}[X^2] - \\mu^2\n$$\n\nSur un échantillon de taille $n$, on divise par",
+                status: MessageStatus::Incomplete,
+            },
+        ],
+        std::slice::from_ref(&image),
+    )
+    .await?;
+
+    let archived = create_sample_session(
+        state,
+        "Ancienne discussion",
+        &[SampleExchange {
+            question: "Quelle heure est-il à Tokyo quand il est midi à Paris ?",
+            answer: "Il est **20 h** à Tokyo quand Paris est à l'heure d'hiver (UTC+1 contre UTC+9), et **19 h** en été (UTC+2). Le Japon ne change pas d'heure.",
+            status: MessageStatus::Complete,
+        }],
+        &[],
+    )
+    .await?;
+    repo.archive_session(archived)
+        .await
+        .map_err(internal_error)?;
+
+    repo.save_draft(
+        rust,
+        "Quelle est la différence entre `String` et `&str` ?",
+        &[],
+    )
+    .await
+    .map_err(internal_error)?;
+
+    repo.create_board_message(
+        "CarlGPT",
+        "Bienvenue ! Ces discussions ont été créées par --seed.",
+    )
+    .await
+    .map_err(internal_error)?;
+
+    Ok(4)
+}
+
+/// Crée une discussion et ses échanges ; les pièces jointes vont sur la première question.
+async fn create_sample_session(
+    state: &AppState,
+    title: &str,
+    exchanges: &[SampleExchange],
+    attachments: &[AttachmentPayload],
+) -> Result<Uuid, (StatusCode, String)> {
+    let session = state
+        .repo
+        .create_session(title)
+        .await
+        .map_err(internal_error)?;
+    for (index, exchange) in exchanges.iter().enumerate() {
+        let usage = TokenUsage {
+            prompt_tokens: 400 + 150 * index as i32,
+            completion_tokens: (exchange.answer.chars().count() / 4) as i32,
+            cached_tokens: if index == 0 { 0 } else { 384 },
+        };
+        state
+            .repo
+            .insert_exchange(NewExchange {
+                session_id: session.id,
+                user_content: exchange.question,
+                attachments: if index == 0 { attachments } else { &[] },
+                reply_to_message_id: None,
+                preceding: &[],
+                answer: exchange.answer,
+                status: exchange.status,
+                usage: Some(usage),
+                finish_reason: (exchange.status == MessageStatus::Complete)
+                    .then_some(FinishReason::Stop),
+                refused: false,
+                response_id: None,
+                model: None,
+                route: None,
+                title: None,
+                icon: None,
+            })
+            .await
+            .map_err(internal_error)?;
+    }
+    Ok(session.id)
+}
@@ -0,0 +1,2312 @@
+//! Logique métier des discussions, indépendante d'HTTP : validations, appel du provider,
+//! persistance des échanges et diffusion des évènements de génération. Les handlers ne
+//! font que désérialiser la requête et mettre en forme la réponse (JSON ou SSE).
+
+use axum::http::StatusCode;
+use futures::{StreamExt, future::join_all};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    access::Caller,
+    archive::archive_exchange,
+    crop::validate_regions,
+    events::AppEvent,
+    features::Features,
+    internal_error,
+    latency::{self, DbTimer, LatencyClock},
+    models::{
+        AttachmentMetadata, AttachmentPayload, BulkSessionAction, BulkSessionRequest,
+        BulkSessionResult, ChatDraft, ChatMessage, ChatMessagePayload, ChatSession,
+        CompletionParams, CompletionPreset, CompletionPresetRequest, ContinueRequest,
+        ConversationTemplate, CostEstimate, CreateChatMessageRequest, FinishReason, MessageStatus,
+        ModelEstimate, RegenerateRequest, SaveDraftRequest, SelectCandidateRequest, TokenUsage,
+        UserPreferences,
+    },
+    notify::{FinishedGeneration, notify_generation},
+    prompt,
+    providers::{
+        AiModelChoice, CompletionStream, ProviderChunk, SessionTitle, generate_concise_title,
+        request_ai_completion,
+    },
+    repository::{NewCandidate, NewExchange},
+    routing::{AUTO_MODEL, ModelSelection, Route},
+    sanitize::finalize_answer,
+    storage::{AttachmentContent, PageSelection, load_attachment_content},
+    stream::{
+        ClientEvents, ClientSink, EventKind, StreamSegment, ThinkingSplitter, coalesce,
+        refusal_data, usage_data,
+    },
+};
+
+/// Approximation utilisée faute de tokenizer : ~4 caractères par token.
+const CHARS_PER_TOKEN: u64 = 4;
+/// Surcoût fixe par message (rôle, séparateurs) dans le format chat.
+const TOKENS_PER_MESSAGE: u64 = 4;
+/// Coût forfaitaire d'une image en haute définition dont les dimensions sont inconnues.
+const TOKENS_PER_IMAGE: u64 = 765;
+/// Coût d'une image en haute définition : forfait, puis par tuile de 512 px.
+const IMAGE_BASE_TOKENS: u64 = 85;
+const IMAGE_TILE_TOKENS: u64 = 170;
+/// Place gardée pour la réponse quand la requête ne fixe pas `max_tokens`.
+const ANSWER_RESERVE_TOKENS: u64 = 16_000;
+/// Budget minimal d'une pièce jointe, pour que le modèle en voie au moins le début.
+const MIN_ATTACHMENT_TOKENS: u64 = 256;
+/// Longueur maximale d'une réaction : un emoji, éventuellement composé, ou un mot court.
+const MAX_REACTION_CHARS: usize = 16;
+/// Messages envoyés au plus avec une question (`messages`).
+const MAX_PRECEDING_MESSAGES: usize = 20;
+/// Réponses candidates générées au plus par un appel (`candidates`).
+const MAX_CANDIDATES: u8 = 4;
+/// Longueur maximale de la persona et des instructions personnalisées des préférences.
+const MAX_INSTRUCTIONS_CHARS: usize = 4000;
+/// Longueur maximale du nom de l'utilisateur dans les préférences.
+const MAX_DISPLAY_NAME_CHARS: usize = 100;
+/// Longueur maximale de la citation d'un message auquel l'utilisateur répond.
+const QUOTE_MAX_CHARS: usize = 500;
+/// Sessions modifiées au plus par `POST /api/chat/sessions/bulk`.
+const MAX_BULK_SESSIONS: usize = 500;
+/// Longueur maximale d'un nom de dossier ou d'étiquette.
+const MAX_LABEL_CHARS: usize = 64;
+const CONTINUE_PROMPT: &str = "Continue ta réponse précédente exactement là où elle s'est arrêtée, sans répéter ce qui a déjà été écrit.";
+
+type ServiceResult<T> = Result<T, (StatusCode, String)>;
+
+pub struct ChatService<'a> {
+    state: &'a AppState,
+    caller: Caller,
+    /// Fonctionnalités expérimentales ouvertes au client (outils)
+    features: Features,
+    /// Requêtes à la base de la génération en cours, pour `message_latency`
+    db: DbTimer,
+}
+
+/// Génération acceptée par le provider et dont le placeholder est enregistré : il ne reste
+/// qu'à relayer le flux au client (`spawn`).
+pub struct Generation {
+    pub session: ChatSession,
+    pub message_id: Uuid,
+    stream: CompletionStream,
+    model: AiModelChoice,
+    /// Chronomètre démarré à l'appel au provider, pour l'évènement `usage`
+    clock: LatencyClock,
+    /// Contenu et consommation déjà enregistrés (continuation d'une réponse incomplète)
+    prefix: String,
+    prefix_usage: Option<TokenUsage>,
+    /// Question dont le titre est à résumer en parallèle de la réponse (premier message)
+    title_question: Option<String>,
+    /// Brouillon streamé au client pendant que `stream` est lu sans être relayé
+    draft: Option<Draft>,
+}
+
+/// Réponse du modèle rapide, affichée le temps que le modèle demandé rédige la sienne.
+struct Draft {
+    model: AiModelChoice,
+    stream: CompletionStream,
+}
+
+/// Message utilisateur validé, avec l'historique à envoyer au provider.
+struct PreparedExchange {
+    content: String,
+    attachments: Vec<AttachmentPayload>,
+    reply_to_message_id: Option<Uuid>,
+    /// Messages envoyés avec la question, à enregistrer avant elle
+    preceding: Vec<ChatMessagePayload>,
+    ai_model: AiModelChoice,
+    route: Option<Route>,
+    payload: Vec<ChatMessagePayload>,
+    /// Paramètres de la requête et du preset, avec la réponse précédente à reprendre si le
+    /// modèle passe par l'API Responses
+    completion_params: Option<CompletionParams>,
+    first_message: bool,
+}
+
+/// Réponse complète d'un endpoint non-stream.
+struct CollectedAnswer {
+    content: String,
+    status: MessageStatus,
+    usage: Option<TokenUsage>,
+    finish_reason: Option<FinishReason>,
+    /// Texte du refus du modèle, ajouté à la fin de la réponse
+    refusal: String,
+    response_id: Option<String>,
+    /// Requêtes journalisées (`PROVIDER_DEBUG_LOG`)
+    debug_log_ids: Vec<Uuid>,
+    /// Fichiers produits par les outils, joints à la réponse
+    attachments: Vec<AttachmentPayload>,
+    /// Erreur de la requête, pour une candidate qui n'a pas abouti
+    error: Option<String>,
+    clock: LatencyClock,
+}
+
+impl<'a> ChatService<'a> {
+    pub fn new(state: &'a AppState) -> Self {
+        ChatService {
+            state,
+            caller: Caller::default(),
+            features: Features::default(),
+            db: DbTimer::default(),
+        }
+    }
+
+    /// Appelant de la requête : détermine les modèles auxquels il a accès.
+    pub(crate) fn with_caller(mut self, caller: Caller) -> Self {
+        self.caller = caller;
+        self
+    }
+
+    /// Fonctionnalités du client : les outils qui lui sont fermés sont retirés des
+    /// paramètres de completion.
+    pub(crate) fn with_features(mut self, features: Features) -> Self {
+        self.features = features;
+        self
+    }
+
+    pub async fn create_session(&self, title: Option<String>) -> ServiceResult<ChatSession> {
+        let title = title
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .unwrap_or_else(|| "Nouvelle discussion".to_string());
+        self.state
+            .repo
+            .create_session(&title)
+            .await
+            .map_err(internal_error)
+    }
+
+    /// Gabarits de discussion ; un modèle suggéré auquel l'appelant n'a pas accès est retiré.
+    pub(crate) fn templates(&self) -> Vec<ConversationTemplate> {
+        self.state
+            .templates
+            .iter()
+            .cloned()
+            .map(|mut template| {
+                if template.model.as_deref().is_some_and(|model| {
+                    model != AUTO_MODEL
+                        && self.state.models.resolve(Some(model), self.caller).is_err()
+                }) {
+                    template.model = None;
+                }
+                template
+            })
+            .collect()
+    }
+
+    /// Discussion créée d'après le gabarit `template_id` ; rien n'est envoyé au modèle.
+    pub async fn create_session_from_template(
+        &self,
+        template_id: &str,
+    ) -> ServiceResult<ChatSession> {
+        let template = self
+            .state
+            .templates
+            .iter()
+            .find(|template| template.id == template_id)
+            .ok_or_else(|| {
+                (
+                    StatusCode::NOT_FOUND,
+                    format!("Gabarit de discussion introuvable : {template_id}."),
+                )
+            })?;
+        let repo = &self.state.repo;
+        let session_id = repo
+            .create_session_from_template(template)
+            .await
+            .map_err(internal_error)?;
+        repo.fetch_session(session_id).await.map_err(internal_error)
+    }
+
+    pub async fn archive(&self, session_id: Uuid) -> ServiceResult<()> {
+        let repo = &self.state.repo;
+        if repo
+            .archive_session(session_id)
+            .await
+            .map_err(internal_error)?
+        {
+            return Ok(());
+        }
+        if repo
+            .session_exists(session_id)
+            .await
+            .map_err(internal_error)?
+        {
+            Err((
+                StatusCode::BAD_REQUEST,
+                "Cette discussion est déjà archivée.".to_string(),
+            ))
+        } else {
+            Err(session_not_found())
+        }
+    }
+
+    /// Retire un message du contexte envoyé au modèle, ou l'y remet ; il reste affiché.
+    pub async fn set_excluded_from_context(
+        &self,
+        session_id: Uuid,
+        message_id: Uuid,
+        excluded: bool,
+    ) -> ServiceResult<()> {
+        if self
+            .state
+            .repo
+            .set_excluded_from_context(session_id, message_id, excluded)
+            .await
+            .map_err(internal_error)?
+        {
+            Ok(())
+        } else {
+            Err(message_not_found())
+        }
+    }
+
+    pub async fn set_bookmarked(
+        &self,
+        session_id: Uuid,
+        message_id: Uuid,
+        bookmarked: bool,
+    ) -> ServiceResult<()> {
+        if self
+            .state
+            .repo
+            .set_bookmarked(session_id, message_id, bookmarked)
+            .await
+            .map_err(internal_error)?
+        {
+            Ok(())
+        } else {
+            Err(message_not_found())
+        }
+    }
+
+    /// Réaction d'un message : un emoji ou un mot court, `None` pour la retirer.
+    pub async fn set_reaction(
+        &self,
+        session_id: Uuid,
+        message_id: Uuid,
+        reaction: Option<String>,
+    ) -> ServiceResult<()> {
+        let reaction = reaction
+            .as_deref()
+            .map(str::trim)
+            .filter(|reaction| !reaction.is_empty());
+        if reaction.is_some_and(|reaction| reaction.chars().count() > MAX_REACTION_CHARS) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Une réaction fait au plus {MAX_REACTION_CHARS} caractères."),
+            ));
+        }
+        if self
+            .state
+            .repo
+            .set_reaction(session_id, message_id, reaction)
+            .await
+            .map_err(internal_error)?
+        {
+            Ok(())
+        } else {
+            Err(message_not_found())
+        }
+    }
+
+    /// Retient une des réponses candidates comme contenu du message.
+    pub async fn select_candidate(
+        &self,
+        session_id: Uuid,
+        message_id: Uuid,
+        request: SelectCandidateRequest,
+    ) -> ServiceResult<ChatSession> {
+        let repo = &self.state.repo;
+        if !repo
+            .select_candidate(session_id, message_id, request.candidate_id)
+            .await
+            .map_err(internal_error)?
+        {
+            return Err((
+                StatusCode::NOT_FOUND,
+                "Réponse candidate introuvable.".to_string(),
+            ));
+        }
+        repo.fetch_session(session_id).await.map_err(internal_error)
+    }
+
+    /// Enregistre toutes les candidates d'une réponse générée en plusieurs exemplaires.
+    async fn record_candidates(
+        &self,
+        message_id: Uuid,
+        answer: &CollectedAnswer,
+        others: &[CollectedAnswer],
+    ) -> ServiceResult<()> {
+        if others.is_empty() {
+            return Ok(());
+        }
+        let candidates: Vec<_> = std::iter::once(answer)
+            .chain(others)
+            .map(CollectedAnswer::candidate)
+            .collect();
+        self.db
+            .time(self.state.repo.insert_candidates(message_id, &candidates))
+            .await
+            .map_err(internal_error)
+    }
+
+    pub async fn delete(&self, session_id: Uuid) -> ServiceResult<()> {
+        if self
+            .state
+            .repo
+            .delete_session(session_id)
+            .await
+            .map_err(internal_error)?
+        {
+            Ok(())
+        } else {
+            Err(session_not_found())
+        }
+    }
+
+    /// Archive, supprime, étiquette ou range plusieurs sessions d'un coup, toutes ou aucune :
+    /// une seule session inconnue fait échouer la requête (404).
+    pub async fn bulk_update(
+        &self,
+        request: BulkSessionRequest,
+    ) -> ServiceResult<BulkSessionResult> {
+        let mut ids = request.session_ids;
+        ids.sort_unstable();
+        ids.dedup();
+        if ids.is_empty() || ids.len() > MAX_BULK_SESSIONS {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("session_ids doit contenir de 1 à {MAX_BULK_SESSIONS} discussions."),
+            ));
+        }
+        let action = match request.action {
+            BulkSessionAction::Tag { add, remove } => {
+                let add = labels(add)?;
+                let remove = labels(remove)?;
+                if add.is_empty() && remove.is_empty() {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        "Indiquez les étiquettes à ajouter (add) ou à retirer (remove)."
+                            .to_string(),
+                    ));
+                }
+                BulkSessionAction::Tag { add, remove }
+            }
+            BulkSessionAction::Move { folder } => BulkSessionAction::Move {
+                folder: labels(folder)?.pop(),
+            },
+            action => action,
+        };
+        match self
+            .state
+            .repo
+            .bulk_update_sessions(&ids, &action)
+            .await
+            .map_err(internal_error)?
+        {
+            Ok(updated) => Ok(BulkSessionResult {
+                action: action.as_str(),
+                updated,
+            }),
+            Err(missing) => Err((
+                StatusCode::NOT_FOUND,
+                format!(
+                    "Discussions introuvables, aucune n'a été modifiée : {}.",
+                    missing
+                        .iter()
+                        .map(Uuid::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            )),
+        }
+    }
+
+    /// Un brouillon vide (ni texte ni fichier) est supprimé.
+    pub async fn save_draft(
+        &self,
+        session_id: Uuid,
+        draft: SaveDraftRequest,
+    ) -> ServiceResult<Option<ChatDraft>> {
+        let repo = &self.state.repo;
+        self.ensure_session_exists(session_id).await?;
+
+        if draft.content.trim().is_empty() && draft.attachments.is_empty() {
+            repo.delete_draft(session_id)
+                .await
+                .map_err(internal_error)?;
+            return Ok(None);
+        }
+
+        let updated_at = repo
+            .save_draft(session_id, &draft.content, &draft.attachments)
+            .await
+            .map_err(internal_error)?;
+        Ok(Some(ChatDraft {
+            content: draft.content,
+            attachments: draft.attachments,
+            updated_at,
+        }))
+    }
+
+    /// Envoie un message et attend la réponse complète. Rien n'est écrit avant d'avoir la
+    /// réponse : l'échange est persisté d'un bloc.
+    pub async fn create_exchange(
+        &self,
+        session_id: Uuid,
+        request: CreateChatMessageRequest,
+    ) -> ServiceResult<ChatSession> {
+        let count = candidate_count(request.candidates)?;
+        if request.draft == Some(true) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Le brouillon n'est disponible qu'en streaming.".to_string(),
+            ));
+        }
+        let prepared = self.prepare_exchange(session_id, request).await?;
+        ensure_candidates_without_tools(count, &prepared.completion_params)?;
+
+        self.state.publish(AppEvent::GenerationStarted {
+            chat_id: session_id,
+            message_id: None,
+        });
+        // Le titre est résumé pendant que la réponse est générée.
+        let answer = collect_candidates(
+            self.state,
+            &prepared.payload,
+            prepared.ai_model,
+            &prepared.completion_params,
+            count,
+        );
+        let title = async {
+            if prepared.first_message {
+                Some(summarize_title(self.state, &prepared.content).await)
+            } else {
+                None
+            }
+        };
+        let (answer, title) = tokio::join!(answer, title);
+        let (mut answer, others) = match answer {
+            Ok(collected) => collected,
+            Err(err) => {
+                self.state.publish(AppEvent::GenerationFinished {
+                    chat_id: session_id,
+                    message_id: None,
+                    status: MessageStatus::Failed,
+                });
+                return Err(err);
+            }
+        };
+
+        let assistant_message_id = self
+            .db
+            .time(self.state.repo.insert_exchange(NewExchange {
+                session_id,
+                user_content: &prepared.content,
+                attachments: &prepared.attachments,
+                reply_to_message_id: prepared.reply_to_message_id,
+                preceding: &prepared.preceding,
+                answer: &answer.content,
+                status: answer.status,
+                usage: answer.usage,
+                finish_reason: answer.finish_reason,
+                refused: answer.refused(),
+                response_id: answer.response_id.as_deref(),
+                model: Some(prepared.ai_model.model_id()),
+                route: prepared.route.map(|route| route.as_str()),
+                title: title.as_ref().map(|title| title.title.as_str()),
+                icon: title.as_ref().and_then(|title| title.icon.as_deref()),
+            }))
+            .await
+            .map_err(internal_error)?;
+        if !answer.attachments.is_empty() {
+            self.db
+                .time(self.state.repo.attach_to_answer(
+                    assistant_message_id,
+                    &answer.attachments,
+                    false,
+                ))
+                .await
+                .map_err(internal_error)?;
+        }
+        self.record_candidates(assistant_message_id, &answer, &others)
+            .await?;
+        self.db
+            .time(link_debug_logs(
+                self.state,
+                &answer.debug_log_ids,
+                assistant_message_id,
+            ))
+            .await;
+        answer.clock.add_db(self.db.elapsed());
+        latency::record(
+            self.state,
+            assistant_message_id,
+            prepared.ai_model,
+            &answer.clock.breakdown(),
+        )
+        .await;
+        archive_exchange(self.state, session_id, assistant_message_id);
+
+        self.state.publish(AppEvent::GenerationFinished {
+            chat_id: session_id,
+            message_id: Some(assistant_message_id),
+            status: answer.status,
+        });
+
+        self.state
+            .repo
+            .fetch_session(session_id)
+            .await
+            .map_err(internal_error)
+    }
+
+    /// Envoie un message en streaming : le provider doit accepter la requête avant qu'on
+    /// écrive quoi que ce soit, puis la question et un placeholder `pending` sont enregistrés.
+    pub async fn start_exchange(
+        &self,
+        session_id: Uuid,
+        request: CreateChatMessageRequest,
+    ) -> ServiceResult<Generation> {
+        ensure_single_candidate(request.candidates)?;
+        let with_draft = request.draft.unwrap_or(false);
+        let prepared = self.prepare_exchange(session_id, request).await?;
+        // Inutile si le modèle demandé est déjà le modèle rapide. Un modèle rapide fermé à
+        // l'appelant revient à se passer de brouillon.
+        let fast_model = self.state.draft_model;
+        let draft_model = (with_draft && prepared.ai_model != fast_model)
+            .then(|| {
+                self.state
+                    .models
+                    .resolve(Some(fast_model.model_id()), self.caller)
+                    .ok()
+            })
+            .flatten();
+
+        let mut clock = LatencyClock::start();
+        // Le brouillon est demandé d'abord : le modèle rapide répond avant le modèle demandé.
+        let draft = match draft_model {
+            Some(model) => self.request_draft(&prepared, model).await,
+            None => None,
+        };
+        let stream = request_ai_completion(
+            self.state,
+            &prepared.payload,
+            prepared.ai_model,
+            prepared.completion_params.clone(),
+        )
+        .await?;
+
+        // Titre provisoire : le résumé de l'IA arrive ensuite en évènement `title`.
+        let title = prepared
+            .first_message
+            .then(|| preview_chat_title(&prepared.content));
+        let message_id = self
+            .db
+            .time(self.state.repo.insert_exchange(NewExchange {
+                session_id,
+                user_content: &prepared.content,
+                attachments: &prepared.attachments,
+                reply_to_message_id: prepared.reply_to_message_id,
+                preceding: &prepared.preceding,
+                answer: "",
+                status: MessageStatus::Pending,
+                usage: None,
+                finish_reason: None,
+                refused: false,
+                response_id: None,
+                model: Some(prepared.ai_model.model_id()),
+                route: prepared.route.map(|route| route.as_str()),
+                title: title.as_deref(),
+                icon: None,
+            }))
+            .await
+            .map_err(internal_error)?;
+
+        let session = self
+            .db
+            .time(self.state.repo.fetch_session(session_id))
+            .await
+            .map_err(internal_error)?;
+
+        clock.add_db(self.db.elapsed());
+        Ok(Generation {
+            session,
+            message_id,
+            stream,
+            model: prepared.ai_model,
+            clock,
+            prefix: String::new(),
+            prefix_usage: None,
+            title_question: prepared.first_message.then_some(prepared.content),
+            draft,
+        })
+    }
+
+    /// Brouillon du modèle rapide ; la réponse est générée sans lui s'il échoue.
+    async fn request_draft(
+        &self,
+        prepared: &PreparedExchange,
+        model: AiModelChoice,
+    ) -> Option<Draft> {
+        // La réponse précédente gardée par OpenAI ne vaut que pour le modèle qui l'a produite,
+        // et seule la réponse enregistrée peut exécuter les outils (calendrier, capture...).
+        let params = prepared
+            .completion_params
+            .clone()
+            .map(|params| CompletionParams {
+                previous_response_id: None,
+                ..params.without_tools()
+            });
+        match request_ai_completion(self.state, &prepared.payload, model, params).await {
+            Ok(stream) => Some(Draft { model, stream }),
+            Err((_, err)) => {
+                eprintln!("Brouillon de {} indisponible: {err}", model.model_id());
+                None
+            }
+        }
+    }
+
+    /// Régénère la dernière réponse de l'IA et attend la nouvelle réponse complète.
+    pub async fn regenerate(
+        &self,
+        session_id: Uuid,
+        request: RegenerateRequest,
+    ) -> ServiceResult<ChatSession> {
+        let RegenerateRequest {
+            message_id,
+            model,
+            completion_params,
+            candidates,
+        } = request;
+        let completion_params = self.features.restrict(completion_params);
+        let count = candidate_count(candidates)?;
+        ensure_candidates_without_tools(count, &completion_params)?;
+        let (messages, target_index) = self.regeneration_target(session_id, message_id).await?;
+        let truncated = conversation_to_payload(&messages[..target_index]);
+        let selection = self
+            .state
+            .models
+            .select(model.as_deref(), &truncated, self.caller)?;
+        let ai_model = selection.model;
+        ensure_model_accepts(ai_model, &messages)?;
+
+        self.state.publish(AppEvent::GenerationStarted {
+            chat_id: session_id,
+            message_id: Some(message_id),
+        });
+        let (mut answer, others) =
+            match collect_candidates(self.state, &truncated, ai_model, &completion_params, count)
+                .await
+            {
+                Ok(answers) => answers,
+                Err(err) => {
+                    self.state.publish(AppEvent::GenerationFinished {
+                        chat_id: session_id,
+                        message_id: Some(message_id),
+                        status: MessageStatus::Failed,
+                    });
+                    return Err(err);
+                }
+            };
+
+        self.record_model(message_id, selection).await?;
+        self.db
+            .time(self.state.repo.persist_answer(
+                session_id,
+                message_id,
+                &answer.content,
+                answer.status,
+                answer.usage,
+                answer.finish_reason,
+            ))
+            .await
+            .map_err(internal_error)?;
+        self.db
+            .time(
+                self.state
+                    .repo
+                    .set_response_id(message_id, answer.response_id.as_deref()),
+            )
+            .await
+            .map_err(internal_error)?;
+        self.db
+            .time(self.state.repo.set_refused(message_id, answer.refused()))
+            .await
+            .map_err(internal_error)?;
+        if !answer.attachments.is_empty() || !messages[target_index].attachments.is_empty() {
+            self.db
+                .time(
+                    self.state
+                        .repo
+                        .attach_to_answer(message_id, &answer.attachments, true),
+                )
+                .await
+                .map_err(internal_error)?;
+        }
+        self.record_candidates(message_id, &answer, &others).await?;
+        self.db
+            .time(link_debug_logs(
+                self.state,
+                &answer.debug_log_ids,
+                message_id,
+            ))
+            .await;
+        answer.clock.add_db(self.db.elapsed());
+        latency::record(self.state, message_id, ai_model, &answer.clock.breakdown()).await;
+        archive_exchange(self.state, session_id, message_id);
+
+        self.state.publish(AppEvent::GenerationFinished {
+            chat_id: session_id,
+            message_id: Some(message_id),
+            status: answer.status,
+        });
+
+        self.state
+            .repo
+            .fetch_session(session_id)
+            .await
+            .map_err(internal_error)
+    }
+
+    pub async fn start_regeneration(
+        &self,
+        session_id: Uuid,
+        request: RegenerateRequest,
+    ) -> ServiceResult<Generation> {
+        let RegenerateRequest {
+            message_id,
+            model,
+            completion_params,
+            candidates,
+        } = request;
+        let completion_params = self.features.restrict(completion_params);
+        ensure_single_candidate(candidates)?;
+        let (messages, target_index) = self.regeneration_target(session_id, message_id).await?;
+        let truncated = conversation_to_payload(&messages[..target_index]);
+        let selection = self
+            .state
+            .models
+            .select(model.as_deref(), &truncated, self.caller)?;
+        let ai_model = selection.model;
+        ensure_model_accepts(ai_model, &messages)?;
+
+        let mut clock = LatencyClock::start();
+        let stream =
+            request_ai_completion(self.state, &truncated, ai_model, completion_params).await?;
+
+        self.record_model(message_id, selection).await?;
+        self.db
+            .time(
+                self.state
+                    .repo
+                    .set_message_status(message_id, MessageStatus::Pending),
+            )
+            .await
+            .map_err(internal_error)?;
+
+        let mut session = self
+            .db
+            .time(self.state.repo.fetch_session(session_id))
+            .await
+            .map_err(internal_error)?;
+        if let Some(msg) = session.messages.iter_mut().find(|m| m.id == message_id) {
+            msg.content.clear();
+        }
+
+        clock.add_db(self.db.elapsed());
+        Ok(Generation {
+            session,
+            message_id,
+            stream,
+            model: ai_model,
+            clock,
+            prefix: String::new(),
+            prefix_usage: None,
+            title_question: None,
+            draft: None,
+        })
+    }
+
+    /// Termine une réponse `incomplete` : la réponse partielle est renvoyée telle quelle au
+    /// provider, suivie d'une consigne de reprise.
+    pub async fn start_continuation(
+        &self,
+        session_id: Uuid,
+        request: ContinueRequest,
+    ) -> ServiceResult<Generation> {
+        let ContinueRequest {
+            message_id,
+            model,
+            completion_params,
+        } = request;
+        // La réponse continuée a déjà son début.
+        let completion_params =
+            self.features
+                .restrict(completion_params)
+                .map(|params| CompletionParams {
+                    assistant_prefix: None,
+                    ..params
+                });
+        let messages = self
+            .db
+            .time(self.state.repo.fetch_messages(session_id))
+            .await
+            .map_err(internal_error)?;
+
+        let target_index = messages
+            .iter()
+            .position(|msg| msg.id == message_id)
+            .ok_or((
+                StatusCode::NOT_FOUND,
+                "Message à continuer introuvable.".to_string(),
+            ))?;
+
+        let target = &messages[target_index];
+        if target.role != "assistant" || target.status != MessageStatus::Incomplete {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Seule une réponse incomplète de l'IA peut être continuée.".to_string(),
+            ));
+        }
+
+        if target_index != messages.len() - 1 {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "La continuation n'est possible que sur la dernière réponse.".to_string(),
+            ));
+        }
+
+        let selection = self.state.models.select(
+            model.as_deref(),
+            &conversation_to_payload(&messages[..target_index]),
+            self.caller,
+        )?;
+        let ai_model = selection.model;
+        ensure_model_accepts(ai_model, &messages)?;
+
+        let mut payload = conversation_to_payload(&messages);
+        payload.push(ChatMessagePayload {
+            role: "user".to_string(),
+            content: CONTINUE_PROMPT.to_string(),
+            attachments: Vec::new(),
+        });
+
+        let mut clock = LatencyClock::start();
+        let stream =
+            request_ai_completion(self.state, &payload, ai_model, completion_params).await?;
+
+        self.record_model(message_id, selection).await?;
+
+        self.db
+            .time(
+                self.state
+                    .repo
+                    .set_message_status(message_id, MessageStatus::Pending),
+            )
+            .await
+            .map_err(internal_error)?;
+
+        let session = self
+            .db
+            .time(self.state.repo.fetch_session(session_id))
+            .await
+            .map_err(internal_error)?;
+
+        clock.add_db(self.db.elapsed());
+        Ok(Generation {
+            session,
+            message_id,
+            stream,
+            model: ai_model,
+            clock,
+            prefix: target.content.clone(),
+            prefix_usage: target.usage,
+            title_question: None,
+            draft: None,
+        })
+    }
+
+    /// Coût estimé d'un message avant envoi, pour le modèle choisi et les autres modèles
+    /// compatibles.
+    pub async fn estimate(
+        &self,
+        session_id: Uuid,
+        request: CreateChatMessageRequest,
+    ) -> ServiceResult<CostEstimate> {
+        let completion_params = self.completion_params(&request).await?;
+        let CreateChatMessageRequest {
+            content,
+            model,
+            attachments,
+            context_message_ids,
+            reply_to_message_id,
+            messages: preceding,
+            ..
+        } = request;
+        let attachments = attachments.unwrap_or_default();
+        validate_attachment_selections(&attachments)?;
+        let preceding = validate_preceding(preceding)?;
+
+        self.ensure_session_exists(session_id).await?;
+        let messages = self
+            .state
+            .repo
+            .fetch_messages(session_id)
+            .await
+            .map_err(internal_error)?;
+        let content = prompt_with_quote(&messages, reply_to_message_id, content.trim())?;
+        let mut history =
+            conversation_to_payload(&select_context(messages, context_message_ids.as_deref())?);
+        history.extend(preceding);
+
+        let system_tokens =
+            estimate_tokens(&prompt::system_prompt(self.state).await) + TOKENS_PER_MESSAGE;
+        let mut history_tokens = 0;
+        let mut attachment_tokens = 0;
+        for message in &history {
+            history_tokens += estimate_tokens(&message.content) + TOKENS_PER_MESSAGE;
+            attachment_tokens +=
+                estimate_attachment_tokens(self.state, &message.attachments).await?;
+        }
+        let message_tokens = estimate_tokens(&content) + TOKENS_PER_MESSAGE;
+        attachment_tokens += estimate_attachment_tokens(self.state, &attachments).await?;
+
+        let prompt_tokens = system_tokens + history_tokens + message_tokens + attachment_tokens;
+        let has_attachments = attachment_tokens > 0;
+        let requested_max = completion_params
+            .and_then(|params| params.max_tokens)
+            .map(u64::from);
+
+        let estimate_for = |choice: AiModelChoice| {
+            let max_output_tokens = requested_max
+                .unwrap_or(choice.max_output_tokens())
+                .min(choice.max_output_tokens());
+            ModelEstimate {
+                model: choice.model_id(),
+                prompt_tokens,
+                max_output_tokens,
+                fits_in_context: prompt_tokens < choice.context_window(),
+                prompt_cost_usd: choice.cost_usd(prompt_tokens, 0),
+                worst_case_cost_usd: choice.cost_usd(prompt_tokens, max_output_tokens),
+            }
+        };
+
+        history.push(ChatMessagePayload {
+            role: "user".to_string(),
+            content,
+            attachments,
+        });
+        let ai_model = self
+            .state
+            .models
+            .select(model.as_deref(), &history, self.caller)?
+            .model;
+        let mut alternatives: Vec<ModelEstimate> = AiModelChoice::ALL
+            .into_iter()
+            .filter(|choice| *choice != ai_model && self.state.models.allows(*choice, self.caller))
+            .filter(|choice| !has_attachments || choice.supports_attachments())
+            .map(estimate_for)
+            .collect();
+        alternatives.sort_by(|a, b| a.worst_case_cost_usd.total_cmp(&b.worst_case_cost_usd));
+
+        Ok(CostEstimate {
+            system_tokens,
+            history_tokens,
+            message_tokens,
+            attachment_tokens,
+            context_window: ai_model.context_window(),
+            selected: estimate_for(ai_model),
+            alternatives,
+        })
+    }
+
+    pub async fn create_preset(
+        &self,
+        request: CompletionPresetRequest,
+    ) -> ServiceResult<CompletionPreset> {
+        let name = preset_name(&request.name)?;
+        self.state
+            .repo
+            .insert_preset(name, &request.params)
+            .await
+            .map_err(preset_error)
+    }
+
+    pub async fn update_preset(
+        &self,
+        preset_id: Uuid,
+        request: CompletionPresetRequest,
+    ) -> ServiceResult<CompletionPreset> {
+        let name = preset_name(&request.name)?;
+        self.state
+            .repo
+            .update_preset(preset_id, name, &request.params)
+            .await
+            .map_err(preset_error)?
+            .ok_or_else(preset_not_found)
+    }
+
+    pub async fn delete_preset(&self, preset_id: Uuid) -> ServiceResult<()> {
+        if self
+            .state
+            .repo
+            .delete_preset(preset_id)
+            .await
+            .map_err(internal_error)?
+        {
+            Ok(())
+        } else {
+            Err(preset_not_found())
+        }
+    }
+
+    /// Préférences enregistrées, ou toutes vides.
+    pub async fn preferences(&self) -> ServiceResult<UserPreferences> {
+        let preferences = self
+            .state
+            .repo
+            .fetch_preferences()
+            .await
+            .map_err(internal_error)?;
+        Ok(preferences.unwrap_or_default())
+    }
+
+    /// Remplace les préférences après validation ; les textes vides valent absence.
+    pub async fn save_preferences(
+        &self,
+        mut preferences: UserPreferences,
+    ) -> ServiceResult<UserPreferences> {
+        preferences.default_model = non_empty(preferences.default_model);
+        if let Some(model) = preferences.default_model.as_deref()
+            && model != AUTO_MODEL
+        {
+            if AiModelChoice::from_id(model).is_none() {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("Modèle inconnu : {model}."),
+                ));
+            }
+            self.state.models.resolve(Some(model), self.caller)?;
+        }
+        if preferences
+            .default_params
+            .as_ref()
+            .is_some_and(|params| params.previous_response_id.is_some())
+        {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "previous_response_id ne peut pas faire partie des paramètres par défaut."
+                    .to_string(),
+            ));
+        }
+        preferences.persona = non_empty(preferences.persona);
+        preferences.custom_instructions = non_empty(preferences.custom_instructions);
+        preferences.display_name = non_empty(preferences.display_name);
+        for (label, text, max_chars) in [
+            ("La persona", &preferences.persona, MAX_INSTRUCTIONS_CHARS),
+            (
+                "Les instructions personnalisées",
+                &preferences.custom_instructions,
+                MAX_INSTRUCTIONS_CHARS,
+            ),
+            ("Le nom", &preferences.display_name, MAX_DISPLAY_NAME_CHARS),
+        ] {
+            if text
+                .as_ref()
+                .is_some_and(|text| text.chars().count() > max_chars)
+            {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("{label} ne peut pas dépasser {max_chars} caractères."),
+                ));
+            }
+        }
+        preferences.language = non_empty(preferences.language);
+        if let Some(language) = preferences.language.as_deref()
+            && !is_language_tag(language)
+        {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Langue invalide : {language} (attendu : fr, en-US...)."),
+            ));
+        }
+        preferences.timezone = non_empty(preferences.timezone);
+        if let Some(timezone) = preferences.timezone.as_deref() {
+            let Some(parsed) = prompt::parse_timezone(timezone) else {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("Fuseau horaire inconnu : {timezone} (attendu : Europe/Paris...)."),
+                ));
+            };
+            preferences.timezone = Some(parsed.name().to_string());
+        }
+
+        preferences.updated_at = None;
+        let updated_at = self
+            .state
+            .repo
+            .save_preferences(&preferences)
+            .await
+            .map_err(internal_error)?;
+        preferences.updated_at = Some(updated_at);
+        Ok(preferences)
+    }
+
+    /// Paramètres du message : ceux de la requête, complétés par le preset `preset_id`, sans
+    /// les outils fermés au client.
+    async fn completion_params(
+        &self,
+        request: &CreateChatMessageRequest,
+    ) -> ServiceResult<Option<CompletionParams>> {
+        let Some(preset_id) = request.preset_id else {
+            return Ok(self.features.restrict(request.completion_params.clone()));
+        };
+        let preset = self
+            .db
+            .time(self.state.repo.fetch_preset_params(preset_id))
+            .await
+            .map_err(internal_error)?
+            .ok_or_else(preset_not_found)?;
+        Ok(self
+            .features
+            .restrict(Some(match request.completion_params.clone() {
+                Some(params) => params.or(preset),
+                None => preset,
+            })))
+    }
+
+    async fn ensure_session_exists(&self, session_id: Uuid) -> ServiceResult<()> {
+        if self
+            .state
+            .repo
+            .session_exists(session_id)
+            .await
+            .map_err(internal_error)?
+        {
+            Ok(())
+        } else {
+            Err(session_not_found())
+        }
+    }
+
+    async fn prepare_exchange(
+        &self,
+        session_id: Uuid,
+        request: CreateChatMessageRequest,
+    ) -> ServiceResult<PreparedExchange> {
+        let mut completion_params = self.completion_params(&request).await?;
+        let CreateChatMessageRequest {
+            content,
+            model,
+            attachments,
+            context_message_ids,
+            reply_to_message_id,
+            messages: preceding,
+            ..
+        } = request;
+        let content = content.trim().to_string();
+        let attachments = attachments.unwrap_or_default();
+        validate_attachment_selections(&attachments)?;
+        let preceding = validate_preceding(preceding)?;
+        if content.is_empty() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Le message ne peut pas être vide.".to_string(),
+            ));
+        }
+
+        match self
+            .db
+            .time(self.state.repo.session_archived(session_id))
+            .await
+            .map_err(internal_error)?
+        {
+            None => return Err(session_not_found()),
+            Some(true) => {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    "Impossible de poster dans une discussion archivée.".to_string(),
+                ));
+            }
+            Some(false) => {}
+        }
+
+        let history = self
+            .db
+            .time(self.state.repo.fetch_messages(session_id))
+            .await
+            .map_err(internal_error)?;
+        let first_message = history.is_empty();
+        let prompt = prompt_with_quote(&history, reply_to_message_id, &content)?;
+        let history = select_context(history, context_message_ids.as_deref())?;
+        let mut payload = conversation_to_payload(&history);
+        payload.extend(preceding.iter().cloned());
+        payload.push(ChatMessagePayload {
+            role: "user".to_string(),
+            content: prompt,
+            attachments: attachments.clone(),
+        });
+
+        let ModelSelection {
+            model: ai_model,
+            route,
+        } = self
+            .state
+            .models
+            .select(model.as_deref(), &payload, self.caller)?;
+        if !ai_model.supports_attachments() && !attachments.is_empty() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Ce modèle n'accepte pas les fichiers ni les images.".to_string(),
+            ));
+        }
+        ensure_model_accepts(ai_model, &history)?;
+
+        // OpenAI a gardé la réponse précédente si elle vient du même modèle (API Responses),
+        // mais avec tout ce qui la précède : inutilisable quand le contexte est restreint, ou
+        // quand des messages envoyés avec la question s'intercalent (une réponse `assistant`
+        // y passerait pour la dernière).
+        let previous_response_id = history
+            .last()
+            .filter(|_| self.state.uses_responses_api(ai_model))
+            .filter(|_| {
+                preceding.is_empty()
+                    && context_message_ids.is_none()
+                    && !history.iter().any(|message| message.excluded_from_context)
+            })
+            .filter(|message| {
+                message.role == "assistant"
+                    && message.status == MessageStatus::Complete
+                    && message.model.as_deref() == Some(ai_model.model_id())
+            })
+            .and_then(|message| message.response_id.clone());
+        if let Some(previous_response_id) = previous_response_id {
+            completion_params
+                .get_or_insert_with(CompletionParams::default)
+                .previous_response_id
+                .get_or_insert(previous_response_id);
+        }
+
+        Ok(PreparedExchange {
+            content,
+            attachments,
+            reply_to_message_id,
+            preceding,
+            ai_model,
+            route,
+            payload,
+            completion_params,
+            first_message,
+        })
+    }
+
+    async fn record_model(&self, message_id: Uuid, selection: ModelSelection) -> ServiceResult<()> {
+        self.db
+            .time(self.state.repo.set_message_model(
+                message_id,
+                selection.model.model_id(),
+                selection.route.map(|route| route.as_str()),
+            ))
+            .await
+            .map_err(internal_error)
+    }
+
+    /// Messages de la session et position de la réponse à régénérer (la dernière, précédée
+    /// d'au moins une question).
+    async fn regeneration_target(
+        &self,
+        session_id: Uuid,
+        message_id: Uuid,
+    ) -> ServiceResult<(Vec<ChatMessage>, usize)> {
+        let messages = self
+            .db
+            .time(self.state.repo.fetch_messages(session_id))
+            .await
+            .map_err(internal_error)?;
+
+        if messages.is_empty() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Il n'y a aucune réponse à régénérer pour cette discussion.".to_string(),
+            ));
+        }
+
+        let target_index = messages
+            .iter()
+            .position(|msg| msg.id == message_id)
+            .ok_or((
+                StatusCode::NOT_FOUND,
+                "Message à régénérer introuvable.".to_string(),
+            ))?;
+
+        if messages[target_index].role != "assistant" {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Seules les réponses de l'IA peuvent être régénérées.".to_string(),
+            ));
+        }
+
+        if target_index != messages.len() - 1 {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "La régénération n'est possible que sur la dernière réponse.".to_string(),
+            ));
+        }
+
+        if target_index == 0 {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Impossible de régénérer sans question utilisateur.".to_string(),
+            ));
+        }
+
+        Ok((messages, target_index))
+    }
+}
+
+impl Generation {
+    /// Envoie la session (avec le placeholder) en premier évènement puis relaie la génération
+    /// en tâche de fond ; les évènements renvoyés alimentent la réponse SSE.
+    pub(crate) fn spawn(mut self, state: AppState) -> ClientEvents {
+        let session_id = self.session.id;
+        let (mut client, events) = ClientSink::channel(Some(session_id), Some(self.message_id));
+        client.send(EventKind::Session, json!({ "session": self.session }));
+
+        if let Some(question) = self.title_question.take() {
+            tokio::spawn(send_title(
+                state.clone(),
+                client.fork(),
+                session_id,
+                question,
+            ));
+        }
+        tokio::spawn(run_answer_stream(state, client, self));
+        events
+    }
+}
+
+/// Titre et icône résumés par l'IA, ou début de la question (sans icône) si le provider
+/// échoue.
+async fn summarize_title(state: &AppState, question: &str) -> SessionTitle {
+    match generate_concise_title(state, question).await {
+        Ok(title) => title,
+        Err(err) => {
+            eprintln!("Failed to summarize title: {err:?}");
+            SessionTitle {
+                title: preview_chat_title(question),
+                icon: None,
+            }
+        }
+    }
+}
+
+/// Enregistre le titre résumé pendant que la réponse streame et l'envoie en évènement `title`.
+/// Le flux SSE reste ouvert tant que cette tâche n'a pas terminé.
+async fn send_title(state: AppState, mut client: ClientSink, session_id: Uuid, question: String) {
+    let SessionTitle { title, icon } = summarize_title(&state, &question).await;
+    if let Err(err) = state
+        .repo
+        .set_session_title(session_id, &title, icon.as_deref())
+        .await
+    {
+        eprintln!("Impossible d'enregistrer le titre: {err}");
+        return;
+    }
+    client.send(EventKind::Title, json!({ "title": title, "icon": icon }));
+}
+
+/// Noms de dossier ou d'étiquettes sans espaces autour ; les noms vides sont ignorés.
+fn labels(names: impl IntoIterator<Item = String>) -> ServiceResult<Vec<String>> {
+    let mut labels = Vec::new();
+    for name in names {
+        let name = name.trim();
+        if name.chars().count() > MAX_LABEL_CHARS {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Un dossier ou une étiquette fait au plus {MAX_LABEL_CHARS} caractères."),
+            ));
+        }
+        if !name.is_empty() {
+            labels.push(name.to_string());
+        }
+    }
+    Ok(labels)
+}
+
+fn session_not_found() -> (StatusCode, String) {
+    (StatusCode::NOT_FOUND, "Discussion introuvable.".to_string())
+}
+
+fn message_not_found() -> (StatusCode, String) {
+    (StatusCode::NOT_FOUND, "Message introuvable.".to_string())
+}
+
+fn preset_not_found() -> (StatusCode, String) {
+    (StatusCode::NOT_FOUND, "Preset introuvable.".to_string())
+}
+
+fn preset_name(name: &str) -> ServiceResult<&str> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Le nom du preset ne peut pas être vide.".to_string(),
+        ));
+    }
+    Ok(name)
+}
+
+/// Texte sans les espaces autour, `None` s'il est vide.
+fn non_empty(text: Option<String>) -> Option<String> {
+    text.map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+/// Étiquette de langue BCP 47 simplifiée : langue de 2 ou 3 lettres, puis sous-étiquettes
+/// alphanumériques (`fr`, `en-US`, `zh-Hant-TW`).
+fn is_language_tag(tag: &str) -> bool {
+    let mut subtags = tag.split('-');
+    let language = subtags.next().unwrap_or_default();
+    (2..=3).contains(&language.len())
+        && language.chars().all(|ch| ch.is_ascii_alphabetic())
+        && subtags.all(|subtag| {
+            (1..=8).contains(&subtag.len()) && subtag.chars().all(|ch| ch.is_ascii_alphanumeric())
+        })
+}
+
+fn preset_error(err: sqlx::Error) -> (StatusCode, String) {
+    match &err {
+        sqlx::Error::Database(db_err) if db_err.is_unique_violation() => (
+            StatusCode::CONFLICT,
+            "Un preset porte déjà ce nom.".to_string(),
+        ),
+        _ => internal_error(err),
+    }
+}
+
+/// Une discussion qui contient des fichiers doit rester sur un modèle qui les accepte.
+fn ensure_model_accepts(ai_model: AiModelChoice, messages: &[ChatMessage]) -> ServiceResult<()> {
+    if !ai_model.supports_attachments()
+        && messages
+            .iter()
+            .any(|msg| !msg.excluded_from_context && !msg.attachments.is_empty())
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Cette discussion contient des fichiers. Choisis un modèle qui les accepte pour continuer."
+                .to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Garde de l'historique les messages choisis par le client (`context_message_ids`), dans
+/// l'ordre de la discussion. Un identifiant qui n'appartient pas à la discussion est refusé.
+fn select_context(
+    history: Vec<ChatMessage>,
+    context_message_ids: Option<&[Uuid]>,
+) -> ServiceResult<Vec<ChatMessage>> {
+    let Some(ids) = context_message_ids else {
+        return Ok(history);
+    };
+    if let Some(unknown) = ids
+        .iter()
+        .find(|id| !history.iter().any(|message| message.id == **id))
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Le message {unknown} n'appartient pas à cette discussion."),
+        ));
+    }
+    Ok(history
+        .into_iter()
+        .filter(|message| ids.contains(&message.id))
+        .collect())
+}
+
+/// Messages envoyés avec une question : rôle connu, texte non vide et sans pièce jointe
+/// (elles vont avec la question).
+fn validate_preceding(
+    messages: Option<Vec<ChatMessagePayload>>,
+) -> ServiceResult<Vec<ChatMessagePayload>> {
+    let mut messages = messages.unwrap_or_default();
+    if messages.len() > MAX_PRECEDING_MESSAGES {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("{MAX_PRECEDING_MESSAGES} messages au plus peuvent accompagner une question."),
+        ));
+    }
+    for message in &mut messages {
+        if !matches!(message.role.as_str(), "system" | "user" | "assistant") {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Rôle inconnu : {} (system, user ou assistant).",
+                    message.role
+                ),
+            ));
+        }
+        message.content = message.content.trim().to_string();
+        if message.content.is_empty() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Les messages envoyés avec la question ne peuvent pas être vides.".to_string(),
+            ));
+        }
+        if !message.attachments.is_empty() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Les pièces jointes vont dans `attachments`, avec la question.".to_string(),
+            ));
+        }
+    }
+    Ok(messages)
+}
+
+/// Une sélection de pages ne s'applique qu'à un PDF et doit être lisible.
+/// Pages d'un PDF et zones d'une image demandées avec les pièces jointes.
+fn validate_attachment_selections(attachments: &[AttachmentPayload]) -> ServiceResult<()> {
+    for attachment in attachments {
+        validate_regions(attachment).map_err(|err| (StatusCode::BAD_REQUEST, err))?;
+        let Some(pages) = &attachment.pages else {
+            continue;
+        };
+        if attachment.mime_type != "application/pdf" {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "{} n'est pas un PDF : impossible d'en choisir les pages.",
+                    attachment.file_name
+                ),
+            ));
+        }
+        PageSelection::parse(pages).map_err(|err| (StatusCode::BAD_REQUEST, err))?;
+    }
+    Ok(())
+}
+
+/// Rattache au message les requêtes journalisées pendant sa génération ; un échec ne touche
+/// que le journal.
+async fn link_debug_logs(state: &AppState, log_ids: &[Uuid], message_id: Uuid) {
+    if let Err(err) = state.repo.link_debug_logs(log_ids, message_id).await {
+        eprintln!(
+            "Impossible de rattacher le journal des providers au message {message_id}: {err}"
+        );
+    }
+}
+
+/// Récupère une réponse complète (endpoints non-stream). En cas d'erreur du provider
+/// en cours de route, le texte déjà reçu est gardé et marqué `incomplete`.
+async fn collect_answer(
+    state: &AppState,
+    mut stream: CompletionStream,
+    clock: LatencyClock,
+) -> CollectedAnswer {
+    let mut answer = CollectedAnswer {
+        content: String::new(),
+        status: MessageStatus::Complete,
+        usage: None,
+        finish_reason: None,
+        refusal: String::new(),
+        response_id: None,
+        debug_log_ids: Vec::new(),
+        attachments: Vec::new(),
+        error: None,
+        clock,
+    };
+    while let Some(chunk_res) = stream.next().await {
+        if let Ok(chunk) = &chunk_res {
+            answer.clock.observe(chunk);
+        }
+        match chunk_res {
+            Ok(ProviderChunk::Text(chunk)) => answer.content.push_str(&chunk),
+            Ok(ProviderChunk::Usage(reported)) => answer.usage = Some(reported),
+            Ok(ProviderChunk::Finish(reason)) => answer.finish_reason = Some(reason),
+            Ok(ProviderChunk::ResponseId(id)) => answer.response_id = Some(id),
+            Ok(ProviderChunk::DebugLogId(id)) => answer.debug_log_ids.push(id),
+            Ok(ProviderChunk::Refusal(text)) => answer.refusal.push_str(&text),
+            Ok(ProviderChunk::Attachment(attachment)) => answer.attachments.push(attachment),
+            Ok(ProviderChunk::AttachmentTime(_) | ProviderChunk::Notice(_)) => {}
+            Err(err) => {
+                eprintln!("Erreur stream: {err}");
+                answer.clock.end_of_stream();
+                answer.content.push_str(&answer.refusal);
+                answer.status = MessageStatus::interrupted(&answer.content);
+                return answer;
+            }
+        }
+    }
+    answer.clock.end_of_stream();
+    answer.content = finalize_answer(state, answer.content);
+    answer.content.push_str(&answer.refusal);
+    answer
+}
+
+impl CollectedAnswer {
+    /// Candidate dont la requête au provider a échoué.
+    fn failed(error: String) -> Self {
+        CollectedAnswer {
+            content: String::new(),
+            status: MessageStatus::Failed,
+            usage: None,
+            finish_reason: None,
+            refusal: String::new(),
+            response_id: None,
+            debug_log_ids: Vec::new(),
+            attachments: Vec::new(),
+            error: Some(error),
+            clock: LatencyClock::start(),
+        }
+    }
+
+    fn refused(&self) -> bool {
+        refusal_data(&self.refusal, self.finish_reason).is_some()
+    }
+
+    fn candidate(&self) -> NewCandidate<'_> {
+        NewCandidate {
+            content: &self.content,
+            status: self.status,
+            finish_reason: self.finish_reason,
+            refused: self.refused(),
+            response_id: self.response_id.as_deref(),
+            error: self.error.as_deref(),
+        }
+    }
+}
+
+/// Nombre de réponses candidates demandé (`candidates`), 1 par défaut.
+fn candidate_count(candidates: Option<u8>) -> ServiceResult<usize> {
+    match candidates.unwrap_or(1) {
+        count @ 1..=MAX_CANDIDATES => Ok(count as usize),
+        _ => Err((
+            StatusCode::BAD_REQUEST,
+            format!("`candidates` doit être compris entre 1 et {MAX_CANDIDATES}."),
+        )),
+    }
+}
+
+/// Les endpoints de streaming ne produisent qu'une réponse.
+fn ensure_single_candidate(candidates: Option<u8>) -> ServiceResult<()> {
+    if candidate_count(candidates)? > 1 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Plusieurs réponses candidates ne sont disponibles que sans streaming.".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Chaque candidate exécuterait les outils du modèle (entrée du calendrier, capture, fichier
+/// modifié, plugin) : plusieurs réponses ne se génèrent que sans outil.
+fn ensure_candidates_without_tools(
+    count: usize,
+    completion_params: &Option<CompletionParams>,
+) -> ServiceResult<()> {
+    if count > 1
+        && completion_params
+            .as_ref()
+            .is_some_and(CompletionParams::uses_tools)
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Plusieurs réponses candidates ne sont pas disponibles avec les outils \
+             (`code_edit`, `calendar`, `screenshot`, `plugins`)."
+                .to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Génère `count` réponses complètes en parallèle, chacune par sa propre requête (Groq et
+/// l'API Responses n'acceptent pas `n`). La première qui aboutit est la réponse retenue ;
+/// elle reprend la consommation et le journal de toutes les candidates, renvoyées à part,
+/// celles dont la requête a échoué en dernier avec leur erreur. L'appel n'échoue que si
+/// aucune requête n'aboutit.
+async fn collect_candidates(
+    state: &AppState,
+    payload: &[ChatMessagePayload],
+    model: AiModelChoice,
+    completion_params: &Option<CompletionParams>,
+    count: usize,
+) -> ServiceResult<(CollectedAnswer, Vec<CollectedAnswer>)> {
+    let results = join_all((0..count).map(|_| async {
+        let clock = LatencyClock::start();
+        let stream =
+            request_ai_completion(state, payload, model, completion_params.clone()).await?;
+        Ok::<_, (StatusCode, String)>(collect_answer(state, stream, clock).await)
+    }))
+    .await;
+    let mut answers = Vec::with_capacity(count);
+    let mut errors = Vec::new();
+    for result in results {
+        match result {
+            Ok(answer) => answers.push(answer),
+            Err(err) => errors.push(err),
+        }
+    }
+    if answers.is_empty() {
+        return Err(candidates_failed(errors));
+    }
+    let mut answer = answers.remove(0);
+    for other in &answers {
+        if let Some(usage) = other.usage {
+            answer.usage = Some(answer.usage.map_or(usage, |total| total + usage));
+        }
+        answer.debug_log_ids.extend(&other.debug_log_ids);
+    }
+    for (_, error) in errors {
+        eprintln!("Réponse candidate en échec: {error}");
+        answers.push(CollectedAnswer::failed(error));
+    }
+    Ok((answer, answers))
+}
+
+/// Erreur d'une génération dont aucune candidate n'a abouti : celle de l'unique requête, ou
+/// le statut de la première et la cause de chacune.
+fn candidates_failed(mut errors: Vec<(StatusCode, String)>) -> (StatusCode, String) {
+    if errors.len() == 1 {
+        return errors.remove(0);
+    }
+    let causes: Vec<_> = errors
+        .iter()
+        .enumerate()
+        .map(|(index, (_, message))| format!("candidate {} : {message}", index + 1))
+        .collect();
+    (
+        errors[0].0,
+        format!(
+            "Aucune des {} réponses candidates n'a abouti ({}).",
+            errors.len(),
+            causes.join(" ; ")
+        ),
+    )
+}
+
+/// Tâche de fond des endpoints SSE : relaie les tokens du provider au client, persiste la
+/// réponse puis envoie `final` (ou `error`) et `usage`.
+async fn run_answer_stream(state: AppState, mut client: ClientSink, generation: Generation) {
+    let Generation {
+        session,
+        message_id,
+        stream,
+        model,
+        mut clock,
+        prefix,
+        prefix_usage,
+        draft,
+        ..
+    } = generation;
+    let session_id = session.id;
+    state.publish(AppEvent::GenerationStarted {
+        chat_id: session_id,
+        message_id: Some(message_id),
+    });
+
+    let mut stream = coalesce(
+        stream,
+        state.stream_coalesce_interval,
+        state.stream_coalesce_chars,
+    );
+    let draft_model = draft.as_ref().map(|draft| draft.model);
+    let draft_task = draft.map(|draft| {
+        let stream = coalesce(
+            draft.stream,
+            state.stream_coalesce_interval,
+            state.stream_coalesce_chars,
+        );
+        tokio::spawn(relay_draft(stream, client.fork()))
+    });
+    // Une régénération remplace les fichiers joints à la réponse précédente, une continuation
+    // les garde.
+    let replace_attachments = prefix.is_empty()
+        && session
+            .messages
+            .iter()
+            .any(|message| message.id == message_id && !message.attachments.is_empty());
+    let mut full_answer = prefix;
+    let mut attachments = Vec::new();
+    let mut splitter = ThinkingSplitter::default();
+    let mut stream_error = None;
+    let mut streaming = false;
+    let mut usage = prefix_usage;
+    let mut finish_reason = None;
+    let mut refusal = String::new();
+    let mut response_id = None;
+    let mut debug_log_ids = Vec::new();
+    let mut disconnected = false;
+    let db = DbTimer::default();
+
+    loop {
+        let chunk_res = tokio::select! {
+            chunk_res = stream.next() => match chunk_res {
+                Some(chunk_res) => chunk_res,
+                None => break,
+            },
+            () = client.closed() => {
+                disconnected = true;
+                break;
+            }
+        };
+        if let Ok(chunk) = &chunk_res {
+            clock.observe(chunk);
+        }
+        match chunk_res {
+            Ok(ProviderChunk::Usage(reported)) => {
+                usage = Some(usage.map_or(reported, |previous| previous + reported));
+            }
+            Ok(ProviderChunk::Finish(reason)) => finish_reason = Some(reason),
+            Ok(ProviderChunk::ResponseId(id)) => response_id = Some(id),
+            Ok(ProviderChunk::DebugLogId(id)) => debug_log_ids.push(id),
+            Ok(ProviderChunk::AttachmentTime(_)) => {}
+            Ok(ProviderChunk::Notice(message)) => {
+                client.send(EventKind::Notice, json!({ "message": message }));
+            }
+            Ok(ProviderChunk::Refusal(text)) => refusal.push_str(&text),
+            Ok(ProviderChunk::Attachment(attachment)) => attachments.push(attachment),
+            Ok(ProviderChunk::Text(chunk)) => {
+                if !streaming {
+                    streaming = true;
+                    if let Err(err) = db
+                        .time(
+                            state
+                                .repo
+                                .set_message_status(message_id, MessageStatus::Streaming),
+                        )
+                        .await
+                    {
+                        eprintln!("Impossible de passer le message en streaming: {err}");
+                    }
+                }
+                for segment in splitter.push(&chunk) {
+                    if let StreamSegment::Token(content) = &segment {
+                        full_answer.push_str(content);
+                    }
+                    if draft_model.is_none() {
+                        client.send_segment(segment);
+                    }
+                }
+            }
+            Err(err) => {
+                eprintln!("Erreur stream: {err}");
+                stream_error = Some(err);
+                break;
+            }
+        }
+    }
+
+    // Client parti : lâcher le stream ferme la requête au provider, la réponse partielle est
+    // gardée comme une coupure du provider.
+    clock.end_of_stream();
+    drop(stream);
+    if disconnected {
+        eprintln!("Client SSE déconnecté, génération {message_id} interrompue");
+    }
+    // La réponse est prête : le brouillon s'arrête là, ses derniers tokens envoyés.
+    if let Some(task) = draft_task {
+        task.abort();
+        let _ = task.await;
+    }
+
+    // Flush remaining buffer (le raisonnement non fermé n'est pas ajouté à la réponse)
+    if let Some(segment) = splitter.finish() {
+        if let StreamSegment::Token(content) = &segment {
+            full_answer.push_str(content);
+        }
+        if draft_model.is_none() {
+            client.send_segment(segment);
+        }
+    }
+
+    // Une réponse interrompue n'est pas réparée : la continuation reprendra le texte brut.
+    let (full_answer, status) = if stream_error.is_some() || disconnected {
+        full_answer.push_str(&refusal);
+        let status = MessageStatus::interrupted(&full_answer);
+        (full_answer, status)
+    } else {
+        let mut full_answer = finalize_answer(&state, full_answer);
+        full_answer.push_str(&refusal);
+        (full_answer, MessageStatus::Complete)
+    };
+    let refused = refusal_data(&refusal, finish_reason);
+
+    let persisted = db
+        .time(state.repo.persist_answer(
+            session_id,
+            message_id,
+            &full_answer,
+            status,
+            usage,
+            finish_reason,
+        ))
+        .await;
+    if persisted.is_ok()
+        && let Err(err) = db
+            .time(
+                state
+                    .repo
+                    .set_response_id(message_id, response_id.as_deref()),
+            )
+            .await
+    {
+        eprintln!("Impossible d'enregistrer l'identifiant de réponse: {err}");
+    }
+    if persisted.is_ok()
+        && let Err(err) = db
+            .time(state.repo.set_refused(message_id, refused.is_some()))
+            .await
+    {
+        eprintln!("Impossible d'enregistrer le refus de la réponse: {err}");
+    }
+    if persisted.is_ok()
+        && (replace_attachments || !attachments.is_empty())
+        && let Err(err) = db
+            .time(
+                state
+                    .repo
+                    .attach_to_answer(message_id, &attachments, replace_attachments),
+            )
+            .await
+    {
+        eprintln!("Impossible de joindre les fichiers des outils à la réponse: {err}");
+    }
+    db.time(link_debug_logs(&state, &debug_log_ids, message_id))
+        .await;
+    clock.add_db(db.elapsed());
+    if persisted.is_ok() {
+        latency::record(&state, message_id, model, &clock.breakdown()).await;
+        archive_exchange(&state, session_id, message_id);
+    }
+    let final_status = if persisted.is_ok() {
+        status
+    } else {
+        MessageStatus::Failed
+    };
+    state.publish(AppEvent::GenerationFinished {
+        chat_id: session_id,
+        message_id: Some(message_id),
+        status: final_status,
+    });
+    notify_generation(
+        &state,
+        FinishedGeneration {
+            session_id,
+            message_id,
+            title: session.title.clone(),
+            model: model.model_id(),
+            status: final_status,
+            elapsed: clock.elapsed(),
+            error: match (&persisted, &stream_error) {
+                (Err(err), _) => Some(err.to_string()),
+                (Ok(_), Some(err)) => Some(err.to_string()),
+                (Ok(_), None) => None,
+            },
+        },
+    );
+    if let Err(err) = persisted {
+        eprintln!("Impossible de mettre à jour la réponse IA: {err}");
+        client.send(
+            EventKind::Error,
+            json!({
+                "message": format!("La réponse n'a pas pu être enregistrée: {err}"),
+                "partial": true,
+                "stage": "persist"
+            }),
+        );
+        return;
+    }
+    if disconnected {
+        return;
+    }
+    if let Some(draft_model) = draft_model {
+        client.send(
+            EventKind::Revision,
+            json!({ "content": full_answer, "draftModel": draft_model.model_id() }),
+        );
+    }
+
+    if let Some(err) = &stream_error {
+        client.send(
+            EventKind::Error,
+            json!({
+                "message": format!("La génération a été interrompue: {err}"),
+                "partial": true,
+                "stage": "stream",
+                "resume": {
+                    "endpoint": format!("/api/chat/sessions/{session_id}/continue/stream"),
+                    "messageId": message_id
+                }
+            }),
+        );
+    } else {
+        if let Some(refused) = refused {
+            client.send(EventKind::Refusal, refused);
+        }
+        match state.repo.fetch_session(session_id).await {
+            Ok(final_session) => {
+                client.send(
+                    EventKind::Final,
+                    json!({
+                        "session": final_session,
+                        "finishReason": finish_reason.map(|reason| reason.as_str())
+                    }),
+                );
+            }
+            Err(err) => {
+                client.send(EventKind::Error, json!({ "message": format!("{err}") }));
+            }
+        }
+    }
+
+    client.send(
+        EventKind::Usage,
+        usage_data(model, usage, &clock, finish_reason),
+    );
+}
+
+/// Relaie les tokens du brouillon au client jusqu'à la fin de son stream, ou jusqu'à ce que
+/// la réponse du modèle demandé soit prête (la tâche est alors interrompue). Le brouillon
+/// n'est pas enregistré : son raisonnement, son refus et sa consommation sont ignorés.
+async fn relay_draft(mut stream: CompletionStream, mut client: ClientSink) {
+    let mut splitter = ThinkingSplitter::default();
+    while let Some(Ok(chunk)) = stream.next().await {
+        if let ProviderChunk::Text(chunk) = chunk {
+            for segment in splitter.push(&chunk) {
+                if let StreamSegment::Token(_) = segment {
+                    client.send_segment(segment);
+                }
+            }
+        }
+    }
+    if let Some(segment @ StreamSegment::Token(_)) = splitter.finish() {
+        client.send_segment(segment);
+    }
+}
+
+pub(crate) fn estimate_tokens(text: &str) -> u64 {
+    (text.chars().count() as u64).div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Taille maximale (en caractères) du texte de chaque pièce jointe pour `model` : la place
+/// laissée dans la fenêtre de contexte par l'historique, les images et la réponse est
+/// partagée entre les fichiers texte de la conversation, sans dépasser `ATTACHMENT_MAX_TOKENS`.
+pub(crate) fn attachment_char_budget(
+    state: &AppState,
+    model: AiModelChoice,
+    messages: &[ChatMessagePayload],
+    params: Option<&CompletionParams>,
+) -> usize {
+    let attachments = messages.iter().flat_map(|message| &message.attachments);
+    let (images, documents): (Vec<_>, Vec<_>) =
+        attachments.partition(|attachment| attachment.mime_type.starts_with("image/"));
+    let image_tokens: u64 = images
+        .iter()
+        .map(|image| image_tokens(&image.metadata))
+        .sum();
+    let documents = documents.len() as u64;
+    let answer_tokens = params
+        .and_then(|params| params.max_tokens)
+        .map_or(ANSWER_RESERVE_TOKENS, u64::from)
+        .min(model.max_output_tokens());
+    let used: u64 = messages
+        .iter()
+        .map(|message| estimate_tokens(&message.content) + TOKENS_PER_MESSAGE)
+        .sum::<u64>()
+        + image_tokens
+        + answer_tokens;
+    let share = model.context_window().saturating_sub(used) / documents.max(1);
+    let tokens = share
+        .min(state.attachment_max_tokens)
+        .max(MIN_ATTACHMENT_TOKENS);
+    (tokens * CHARS_PER_TOKEN) as usize
+}
+
+/// Ramène `max_tokens` au maximum du modèle et à la place laissée par le prompt dans sa
+/// fenêtre de contexte (les documents joints y comptent pour leur budget minimal, le reste
+/// étant coupé à la place restante), au lieu de laisser le provider refuser la requête.
+/// Renvoie aussi le message de l'évènement `notice` si la valeur a été réduite.
+pub(crate) fn clamp_max_tokens(
+    model: AiModelChoice,
+    messages: &[ChatMessagePayload],
+    params: Option<CompletionParams>,
+) -> (Option<CompletionParams>, Option<String>) {
+    let Some(mut params) = params else {
+        return (None, None);
+    };
+    let Some(requested) = params.max_tokens else {
+        return (Some(params), None);
+    };
+    let prompt_tokens: u64 = messages
+        .iter()
+        .map(|message| {
+            let attachments: u64 = message
+                .attachments
+                .iter()
+                .map(
+                    |attachment| match attachment.mime_type.starts_with("image/") {
+                        true => image_tokens(&attachment.metadata),
+                        false => MIN_ATTACHMENT_TOKENS,
+                    },
+                )
+                .sum();
+            estimate_tokens(&message.content) + TOKENS_PER_MESSAGE + attachments
+        })
+        .sum();
+    let room = model.context_window().saturating_sub(prompt_tokens);
+    // Un prompt qui remplit déjà la fenêtre est refusé par le provider, quel que soit
+    // `max_tokens` : seul le maximum du modèle s'applique alors.
+    let (limit, reason) = if room > 0 && room < model.max_output_tokens() {
+        (room, "la place restante dans sa fenêtre de contexte")
+    } else {
+        (model.max_output_tokens(), "son maximum")
+    };
+    if u64::from(requested) <= limit {
+        return (Some(params), None);
+    }
+    let limit = limit as u32;
+    params.max_tokens = Some(limit);
+    let notice = format!(
+        "max_tokens ramené de {requested} à {limit} pour {} : {reason}.",
+        model.model_id()
+    );
+    (Some(params), Some(notice))
+}
+
+async fn estimate_attachment_tokens(
+    state: &AppState,
+    attachments: &[AttachmentPayload],
+) -> Result<u64, (axum::http::StatusCode, String)> {
+    let mut tokens = 0;
+    for attachment in attachments {
+        let max_chars = (state.attachment_max_tokens * CHARS_PER_TOKEN) as usize;
+        tokens += match load_attachment_content(attachment, state, max_chars).await? {
+            // Chaque zone est une image à part, de la taille de la découpe.
+            AttachmentContent::Image(_) => match &attachment.regions {
+                Some(regions) if !regions.is_empty() => regions
+                    .iter()
+                    .map(|region| {
+                        image_tokens(&AttachmentMetadata {
+                            width: Some(region.width as i32),
+                            height: Some(region.height as i32),
+                            ..AttachmentMetadata::default()
+                        })
+                    })
+                    .sum(),
+                _ => image_tokens(&attachment.metadata),
+            },
+            AttachmentContent::Text(text) => estimate_tokens(&text),
+        };
+    }
+    Ok(tokens)
+}
+
+/// Tokens d'une image en haute définition selon ses dimensions : ramenée dans un carré de
+/// 2048 px, puis à 768 px sur son petit côté, et comptée par tuiles de 512 px.
+fn image_tokens(metadata: &AttachmentMetadata) -> u64 {
+    let (Some(width), Some(height)) = (metadata.width, metadata.height) else {
+        return TOKENS_PER_IMAGE;
+    };
+    if width <= 0 || height <= 0 {
+        return TOKENS_PER_IMAGE;
+    }
+    let (mut width, mut height) = (f64::from(width), f64::from(height));
+    let fit = (2048.0 / width.max(height)).min(1.0);
+    width *= fit;
+    height *= fit;
+    let shrink = (768.0 / width.min(height)).min(1.0);
+    width *= shrink;
+    height *= shrink;
+    let tiles = (width / 512.0).ceil() as u64 * (height / 512.0).ceil() as u64;
+    IMAGE_BASE_TOKENS + tiles * IMAGE_TILE_TOKENS
+}
+
+fn preview_chat_title(message: &str) -> String {
+    const MAX_CHARS: usize = 60;
+    let mut preview = String::new();
+    let mut truncated = false;
+
+    for (idx, ch) in message.chars().enumerate() {
+        if idx >= MAX_CHARS {
+            truncated = true;
+            break;
+        }
+        preview.push(ch);
+    }
+
+    if truncated {
+        preview.push('…');
+    }
+
+    preview
+}
+
+/// Question envoyée au modèle : `content`, précédé de la citation du message auquel il
+/// répond. Un message cité absent de la discussion est refusé.
+fn prompt_with_quote(
+    history: &[ChatMessage],
+    reply_to_message_id: Option<Uuid>,
+    content: &str,
+) -> ServiceResult<String> {
+    let Some(reply_to) = reply_to_message_id else {
+        return Ok(content.to_string());
+    };
+    let quoted = history
+        .iter()
+        .find(|message| message.id == reply_to)
+        .ok_or((
+            StatusCode::BAD_REQUEST,
+            "Le message cité n'appartient pas à cette discussion.".to_string(),
+        ))?;
+    Ok(quote(&quoted.content, content))
+}
+
+/// Citation Markdown (`> `) du début de `quoted`, suivie de `content`.
+fn quote(quoted: &str, content: &str) -> String {
+    let mut excerpt: String = quoted.chars().take(QUOTE_MAX_CHARS).collect();
+    if quoted.chars().nth(QUOTE_MAX_CHARS).is_some() {
+        excerpt.push('…');
+    }
+    let mut prompt = String::new();
+    for line in excerpt.lines() {
+        prompt.push_str("> ");
+        prompt.push_str(line);
+        prompt.push('\n');
+    }
+    prompt.push('\n');
+    prompt.push_str(content);
+    prompt
+}
+
+/// Messages envoyés au modèle, hors ceux exclus du contexte par l'utilisateur. Une question
+/// qui répond à un message le cite, comme lors de son envoi.
+fn conversation_to_payload(messages: &[ChatMessage]) -> Vec<ChatMessagePayload> {
+    messages
+        .iter()
+        .filter(|msg| !msg.excluded_from_context)
+        .map(|msg| ChatMessagePayload {
+            role: msg.role.clone(),
+            content: match msg
+                .reply_to_message_id
+                .and_then(|id| messages.iter().find(|quoted| quoted.id == id))
+            {
+                Some(quoted) => quote(&quoted.content, &msg.content),
+                None => msg.content.clone(),
+            },
+            attachments: msg
+                .attachments
+                .iter()
+                .map(|attachment| AttachmentPayload {
+                    file_name: attachment.file_name.clone(),
+                    mime_type: attachment.mime_type.clone(),
+                    size_bytes: attachment.size_bytes,
+                    url: attachment.url.clone(),
+                    storage_key: Some(attachment.storage_key.clone()),
+                    pages: attachment.pages.clone(),
+                    regions: attachment.regions.clone(),
+                    metadata: attachment.metadata,
+                })
+                .collect(),
+        })
+        .collect()
+}
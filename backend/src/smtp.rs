@@ -0,0 +1,187 @@
+//! Client SMTP minimal des notifications par email : TLS implicite (`smtps://`, port 465) ou
+//! STARTTLS (`smtp://`, port 587). Sans STARTTLS proposé par le serveur, seul un relais sans
+//! identifiants (Postfix local...) est utilisé en clair.
+
+use std::{sync::Arc, time::Duration};
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use percent_encoding::percent_decode_str;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+use tokio_rustls::{
+    TlsConnector,
+    rustls::{self, ClientConfig, RootCertStore, pki_types::ServerName},
+};
+use url::Url;
+
+/// Durée maximale d'un envoi, connexion comprise
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+/// Nom annoncé au serveur par `EHLO`
+const EHLO_NAME: &str = "carlgpt";
+
+trait Io: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Io for T {}
+
+type Connection = BufReader<Box<dyn Io>>;
+
+/// Serveur d'envoi lu dans `NOTIFY_SMTP_URL` (`smtps://utilisateur:mot-de-passe@hôte`).
+#[derive(Clone, Debug)]
+pub(crate) struct SmtpTransport {
+    host: String,
+    port: u16,
+    implicit_tls: bool,
+    credentials: Option<(String, String)>,
+}
+
+impl SmtpTransport {
+    pub(crate) fn from_url(url: &str) -> Result<Self, String> {
+        let url = Url::parse(url).map_err(|err| err.to_string())?;
+        let implicit_tls = match url.scheme() {
+            "smtps" => true,
+            "smtp" => false,
+            scheme => return Err(format!("schéma {scheme} (attendu : smtp ou smtps)")),
+        };
+        let host = url.host_str().ok_or("hôte manquant")?.to_string();
+        let decode = |value: &str| {
+            percent_decode_str(value)
+                .decode_utf8()
+                .map(|value| value.into_owned())
+                .map_err(|_| "identifiants mal encodés".to_string())
+        };
+        let credentials = match (url.username(), url.password()) {
+            ("", _) => None,
+            (user, password) => Some((decode(user)?, decode(password.unwrap_or_default())?)),
+        };
+        Ok(SmtpTransport {
+            host,
+            port: url.port().unwrap_or(if implicit_tls { 465 } else { 587 }),
+            implicit_tls,
+            credentials,
+        })
+    }
+
+    /// Envoie `message` (en-têtes et corps, lignes séparées par `\n` ou `\r\n`).
+    pub(crate) async fn send(
+        &self,
+        from: &str,
+        to: &[String],
+        message: &str,
+    ) -> Result<(), String> {
+        tokio::time::timeout(SMTP_TIMEOUT, self.deliver(from, to, message))
+            .await
+            .map_err(|_| format!("{}:{} ne répond pas", self.host, self.port))?
+    }
+
+    async fn deliver(&self, from: &str, to: &[String], message: &str) -> Result<(), String> {
+        let tcp = TcpStream::connect((self.host.as_str(), self.port))
+            .await
+            .map_err(|err| format!("connexion à {}:{}: {err}", self.host, self.port))?;
+        let mut conn: Connection = if self.implicit_tls {
+            BufReader::new(self.tls(Box::new(tcp)).await?)
+        } else {
+            BufReader::new(Box::new(tcp))
+        };
+        expect(&mut conn, 220).await?;
+        let capabilities = command(&mut conn, &format!("EHLO {EHLO_NAME}"), 250).await?;
+
+        if !self.implicit_tls {
+            if capabilities
+                .iter()
+                .any(|line| line.eq_ignore_ascii_case("STARTTLS"))
+            {
+                command(&mut conn, "STARTTLS", 220).await?;
+                conn = BufReader::new(self.tls(conn.into_inner()).await?);
+                command(&mut conn, &format!("EHLO {EHLO_NAME}"), 250).await?;
+            } else if self.credentials.is_some() {
+                return Err("le serveur ne propose pas STARTTLS : identifiants non envoyés".into());
+            }
+        }
+
+        if let Some((user, password)) = &self.credentials {
+            let token = BASE64.encode(format!("\0{user}\0{password}"));
+            command(&mut conn, &format!("AUTH PLAIN {token}"), 235).await?;
+        }
+        command(&mut conn, &format!("MAIL FROM:<{from}>"), 250).await?;
+        for recipient in to {
+            command(&mut conn, &format!("RCPT TO:<{recipient}>"), 250).await?;
+        }
+        command(&mut conn, "DATA", 354).await?;
+        let mut data = String::with_capacity(message.len() + 64);
+        for line in message.lines() {
+            // Un point en début de ligne est doublé : seul `.` seul clôt le message.
+            if line.starts_with('.') {
+                data.push('.');
+            }
+            data.push_str(line);
+            data.push_str("\r\n");
+        }
+        data.push_str(".\r\n");
+        conn.get_mut()
+            .write_all(data.as_bytes())
+            .await
+            .map_err(|err| err.to_string())?;
+        expect(&mut conn, 250).await?;
+        let _ = command(&mut conn, "QUIT", 221).await;
+        Ok(())
+    }
+
+    async fn tls(&self, stream: Box<dyn Io>) -> Result<Box<dyn Io>, String> {
+        let roots = RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        let config =
+            ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .map_err(|err| err.to_string())?
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+        let name = ServerName::try_from(self.host.clone()).map_err(|err| err.to_string())?;
+        let tls = TlsConnector::from(Arc::new(config))
+            .connect(name, stream)
+            .await
+            .map_err(|err| format!("TLS avec {}: {err}", self.host))?;
+        Ok(Box::new(tls))
+    }
+}
+
+/// Envoie une commande et renvoie les lignes de la réponse (sans leur code) si son code est
+/// celui attendu.
+async fn command(conn: &mut Connection, line: &str, code: u16) -> Result<Vec<String>, String> {
+    conn.get_mut()
+        .write_all(format!("{line}\r\n").as_bytes())
+        .await
+        .map_err(|err| err.to_string())?;
+    expect(conn, code).await.map_err(|err| {
+        // Deux premiers mots seulement : le jeton d'`AUTH PLAIN` n'est pas journalisé.
+        let verb = line.split(' ').take(2).collect::<Vec<_>>().join(" ");
+        format!("{verb}: {err}")
+    })
+}
+
+/// Lit une réponse, éventuellement sur plusieurs lignes (`250-...` puis `250 ...`).
+async fn expect(conn: &mut Connection, code: u16) -> Result<Vec<String>, String> {
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        let read = conn
+            .read_line(&mut line)
+            .await
+            .map_err(|err| err.to_string())?;
+        if read == 0 {
+            return Err("connexion fermée par le serveur".into());
+        }
+        let line = line.trim_end();
+        let received: u16 = line
+            .get(..3)
+            .and_then(|digits| digits.parse().ok())
+            .ok_or_else(|| format!("réponse illisible : {line}"))?;
+        let last = line.as_bytes().get(3) != Some(&b'-');
+        if received != code {
+            return Err(format!("réponse {line}"));
+        }
+        lines.push(line.get(4..).unwrap_or_default().to_string());
+        if last {
+            return Ok(lines);
+        }
+    }
+}
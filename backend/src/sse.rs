@@ -0,0 +1,136 @@
+//! Lecture des flux SSE (Server-Sent Events) des providers, selon le format du standard
+//! HTML : lignes terminées par LF, CRLF ou CR, commentaires `:`, champ `event:`, plusieurs
+//! lignes `data:` par évènement, espace facultative après les deux-points. Les octets sont
+//! découpés en lignes avant d'être décodés : un caractère UTF-8 à cheval sur deux morceaux
+//! du flux n'est jamais coupé. Le même découpage lit les flux JSON par ligne (NDJSON)
+//! d'Ollama.
+
+/// Marque d'ordre des octets UTF-8, ignorée en début de flux
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Évènement reçu : son nom (`event:`) s'il en a un, et ses lignes `data:` jointes par `\n`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    pub event: Option<String>,
+    pub data: String,
+}
+
+/// Découpe un flux SSE reçu par morceaux en évènements.
+#[derive(Default)]
+pub struct SseParser {
+    /// Octets reçus après la dernière fin de ligne
+    buffer: Vec<u8>,
+    event: Option<String>,
+    data: Option<String>,
+    /// Début du flux passé (BOM éventuel retiré)
+    started: bool,
+    /// Flux NDJSON : chaque ligne non vide est un évènement
+    json_lines: bool,
+}
+
+impl SseParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parser d'un flux NDJSON : chaque ligne non vide est rendue telle quelle dans `data`.
+    pub fn json_lines() -> Self {
+        Self {
+            json_lines: true,
+            ..Self::default()
+        }
+    }
+
+    /// Ajoute un morceau du flux ; renvoie les évènements qu'il complète.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(bytes);
+        if !self.started {
+            if self.buffer.len() < BOM.len() && BOM.starts_with(&self.buffer) {
+                return Vec::new();
+            }
+            self.started = true;
+            if self.buffer.starts_with(BOM) {
+                self.buffer.drain(..BOM.len());
+            }
+        }
+        self.drain_lines(false)
+    }
+
+    /// Fin du flux : la dernière ligne et le dernier évènement sont rendus même sans ligne
+    /// vide finale, que certains providers omettent.
+    pub fn finish(&mut self) -> Vec<SseEvent> {
+        let mut events = self.drain_lines(true);
+        if !self.buffer.is_empty() {
+            let line = std::mem::take(&mut self.buffer);
+            events.extend(self.line(&line));
+        }
+        events.extend(self.dispatch());
+        events
+    }
+
+    fn drain_lines(&mut self, at_end: bool) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        let mut start = 0;
+        while let Some(offset) = self.buffer[start..]
+            .iter()
+            .position(|byte| *byte == b'\n' || *byte == b'\r')
+        {
+            let end = start + offset;
+            let next = match self.buffer[end] {
+                b'\r' => match self.buffer.get(end + 1) {
+                    Some(b'\n') => end + 2,
+                    // CR en fin de morceau : un LF peut suivre dans le prochain.
+                    None if !at_end => break,
+                    _ => end + 1,
+                },
+                _ => end + 1,
+            };
+            let line = self.buffer[start..end].to_vec();
+            events.extend(self.line(&line));
+            start = next;
+        }
+        self.buffer.drain(..start);
+        events
+    }
+
+    /// Traite une ligne complète ; une ligne vide termine l'évènement en cours.
+    fn line(&mut self, line: &[u8]) -> Option<SseEvent> {
+        if self.json_lines {
+            let data = String::from_utf8_lossy(line);
+            return (!data.trim().is_empty()).then(|| SseEvent {
+                event: None,
+                data: data.into_owned(),
+            });
+        }
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line[0] == b':' {
+            return None;
+        }
+        let line = String::from_utf8_lossy(line);
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line.as_ref(), ""),
+        };
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => match &mut self.data {
+                Some(data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                }
+                None => self.data = Some(value.to_string()),
+            },
+            // `id` et `retry` ne servent qu'à la reconnexion d'un navigateur.
+            _ => {}
+        }
+        None
+    }
+
+    /// Évènement en cours ; un évènement sans `data` est ignoré.
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take().filter(|event| !event.is_empty());
+        self.data.take().map(|data| SseEvent { event, data })
+    }
+}
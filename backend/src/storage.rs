@@ -0,0 +1,493 @@
+//! Fichiers uploadés : noms, clés de stockage et lecture du contenu des pièces jointes.
+
+use std::{
+    cell::RefCell,
+    ops::RangeInclusive,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use base64::{Engine as _, engine::general_purpose};
+use pdf_extract::extract_text_from_mem_by_pages;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    AppState, internal_error,
+    media::probe_metadata,
+    models::{AttachmentDerivative, AttachmentExtraction, AttachmentPayload, AttachmentStatus},
+};
+
+/// Intervalle de relecture d'une extraction en cours quand une réponse l'attend
+const EXTRACTION_POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// Sépare les pages dans le texte extrait d'un PDF (saut de page)
+const PAGE_BREAK: char = '\u{c}';
+
+pub(crate) fn sanitize_file_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|ch| match ch {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => ch,
+            _ => '-',
+        })
+        .collect();
+    let trimmed = cleaned.trim_matches('-');
+    if trimmed.is_empty() {
+        "fichier".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+pub(crate) fn storage_key_from_url(url: &str) -> Option<String> {
+    let segment = url.rsplit('/').next()?.split('?').next()?.trim();
+    if segment.is_empty() {
+        None
+    } else {
+        Some(segment.to_string())
+    }
+}
+
+pub(crate) fn attachment_local_path(upload_dir: &str, storage_key: &str) -> PathBuf {
+    let mut path = PathBuf::from(upload_dir);
+    path.push(storage_key);
+    path
+}
+
+/// Écrit un fichier dans le dossier des uploads, comme `POST /api/uploads` (données de
+/// démonstration, captures de l'outil `screenshot_page`).
+pub(crate) async fn store_file(
+    state: &AppState,
+    file_name: &str,
+    mime_type: &str,
+    data: &[u8],
+) -> Result<AttachmentPayload, (axum::http::StatusCode, String)> {
+    let extension = file_name.rsplit('.').next().unwrap_or("bin");
+    let stored_name = format!("{}.{extension}", Uuid::new_v4());
+    let path = std::path::Path::new(&state.upload_dir).join(&stored_name);
+    tokio::fs::write(&path, data)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(AttachmentPayload {
+        file_name: file_name.to_string(),
+        mime_type: mime_type.to_string(),
+        size_bytes: data.len() as i64,
+        url: state.urls.upload(None, &stored_name),
+        storage_key: Some(stored_name),
+        pages: None,
+        regions: None,
+        metadata: probe_metadata(mime_type, data, state.pdf_extract_timeout).await,
+    })
+}
+
+#[derive(Clone)]
+pub(crate) enum AttachmentContent {
+    Image(String),
+    Text(String),
+}
+
+impl AttachmentContent {
+    /// Taille en octets, pour borner le cache des pièces jointes.
+    pub(crate) fn byte_len(&self) -> usize {
+        match self {
+            AttachmentContent::Image(url) => url.len(),
+            AttachmentContent::Text(text) => text.len(),
+        }
+    }
+}
+
+/// Contenu d'une pièce jointe à envoyer au modèle ; le texte est limité à `max_chars`
+/// (budget de la pièce jointe, voir `service::attachment_char_budget`).
+pub(crate) async fn load_attachment_content(
+    attachment: &AttachmentPayload,
+    state: &AppState,
+    max_chars: usize,
+) -> Result<AttachmentContent, (axum::http::StatusCode, String)> {
+    let storage_key = attachment
+        .storage_key
+        .clone()
+        .or_else(|| storage_key_from_url(&attachment.url));
+    if storage_key.is_none() {
+        if attachment.mime_type.starts_with("image/") {
+            return Ok(AttachmentContent::Image(attachment.url.clone()));
+        }
+        return Ok(AttachmentContent::Text(format!(
+            "Fichier attaché: {} ({}).\n{}",
+            attachment.file_name, attachment.mime_type, attachment.url
+        )));
+    }
+    let key = storage_key.unwrap();
+
+    let pages = attachment
+        .pages
+        .as_deref()
+        .map(PageSelection::parse)
+        .transpose()
+        .map_err(|err| (axum::http::StatusCode::BAD_REQUEST, err))?;
+
+    let cached = state
+        .attachment_cache
+        .as_ref()
+        .and_then(|cache| cache.get(&key, &attachment.mime_type));
+    let content = match cached {
+        Some(content) => content,
+        None => {
+            let extraction = if attachment.mime_type.starts_with("image/") {
+                None
+            } else {
+                wait_for_extraction(state, &key)
+                    .await
+                    .map_err(internal_error)?
+            };
+            let content = match extraction {
+                // Image, ou fichier sans extraction en tâche de fond (données de démonstration)
+                None => read_attachment(attachment, state, &key).await?,
+                Some(extraction) => match extraction.status {
+                    AttachmentStatus::Ready => {
+                        AttachmentContent::Text(extraction.content.unwrap_or_default())
+                    }
+                    AttachmentStatus::Failed => {
+                        return Ok(AttachmentContent::Text(format!(
+                            "Fichier attaché: {} ({}). Son contenu n'a pas pu être lu.",
+                            attachment.file_name, attachment.mime_type
+                        )));
+                    }
+                    // Le contenu sera disponible pour les prochaines réponses.
+                    AttachmentStatus::Pending => {
+                        return Ok(AttachmentContent::Text(format!(
+                            "Fichier attaché: {} ({}). Son contenu est encore en cours \
+                             d'analyse et n'est pas disponible pour cette réponse.",
+                            attachment.file_name, attachment.mime_type
+                        )));
+                    }
+                },
+            };
+            if let Some(cache) = &state.attachment_cache {
+                cache.insert(&key, &attachment.mime_type, content.clone());
+            }
+            content
+        }
+    };
+
+    // Le cache garde le texte complet : la sélection de pages et la limite de taille
+    // s'appliquent à chaque lecture.
+    Ok(match content {
+        AttachmentContent::Text(text) if attachment.mime_type == "application/pdf" => {
+            AttachmentContent::Text(render_pdf_pages(&text, pages.as_ref(), max_chars))
+        }
+        AttachmentContent::Text(text) => AttachmentContent::Text(truncate_text(&text, max_chars)),
+        image => image,
+    })
+}
+
+/// Pages d'un PDF à envoyer au modèle, numérotées à partir de 1 : `10-25`, `3`, `1-3, 7`.
+pub(crate) struct PageSelection(Vec<RangeInclusive<usize>>);
+
+impl PageSelection {
+    pub(crate) fn parse(spec: &str) -> Result<Self, String> {
+        let invalid = || format!("Pages invalides « {spec} » : attendu par exemple 10-25 ou 1-3,7");
+        let mut ranges = Vec::new();
+        for part in spec.split(',') {
+            let (start, end) = part.split_once('-').unwrap_or((part, part));
+            let start: usize = start.trim().parse().map_err(|_| invalid())?;
+            let end: usize = end.trim().parse().map_err(|_| invalid())?;
+            if start == 0 || start > end {
+                return Err(invalid());
+            }
+            ranges.push(start..=end);
+        }
+        Ok(PageSelection(ranges))
+    }
+
+    fn contains(&self, page: usize) -> bool {
+        self.0.iter().any(|range| range.contains(&page))
+    }
+}
+
+/// Texte d'un PDF avec un repère par page, réduit aux pages demandées. Au-delà de
+/// `max_chars`, les pages suivantes sont omises plutôt que coupées au milieu (seule une
+/// première page trop longue est tronquée), et le modèle est prévenu de celles qui manquent.
+fn render_pdf_pages(text: &str, selection: Option<&PageSelection>, max_chars: usize) -> String {
+    let pages: Vec<&str> = text.split(PAGE_BREAK).collect();
+    let total = pages.len();
+    let mut rendered = String::new();
+    let mut rendered_chars = 0;
+    let mut limit_reached = false;
+    let mut omitted = Vec::new();
+    for (index, page) in pages.iter().enumerate() {
+        let number = index + 1;
+        if selection.is_some_and(|selection| !selection.contains(number)) {
+            continue;
+        }
+        if limit_reached {
+            omitted.push(number);
+            continue;
+        }
+        let page = page.trim();
+        let marker = format!("[Page {number}/{total}]\n");
+        let room = max_chars.saturating_sub(rendered_chars + marker.len());
+        let page_chars = page.chars().count();
+        if page_chars > room {
+            limit_reached = true;
+            if !rendered.is_empty() {
+                omitted.push(number);
+                continue;
+            }
+        }
+        let page = char_prefix(page, room);
+        rendered_chars += marker.len() + page_chars.min(room) + 2;
+        rendered.push_str(&marker);
+        rendered.push_str(page);
+        rendered.push_str("\n\n");
+    }
+    if rendered.is_empty() {
+        return format!("[Aucune des pages demandées n'existe : le document compte {total} pages]");
+    }
+    if limit_reached {
+        rendered.push_str(&format!("[Texte limité à {max_chars} caractères"));
+        if let (Some(first), Some(last)) = (omitted.first(), omitted.last()) {
+            rendered.push_str(&format!(
+                " : pages {first} à {last} non incluses, à demander avec une sélection de pages"
+            ));
+        }
+        rendered.push(']');
+    }
+    rendered
+}
+
+/// Lit les métadonnées d'un fichier uploadé et lance l'extraction de son contenu. Un contenu
+/// déjà traité (même empreinte SHA-256, même type MIME) reprend les résultats enregistrés dans
+/// `attachment_derivatives` sans être relu.
+pub(crate) async fn process_upload(
+    state: &AppState,
+    attachment: &mut AttachmentPayload,
+    data: &[u8],
+) -> Result<AttachmentStatus, sqlx::Error> {
+    let content_hash = format!("{:x}", Sha256::digest(data));
+    let derivative = state
+        .repo
+        .fetch_attachment_derivative(&content_hash, &attachment.mime_type)
+        .await?;
+    if let Some(derivative) = derivative {
+        attachment.metadata = derivative.metadata;
+        if let (Some(key), Some(text)) = (&attachment.storage_key, &derivative.extracted_text) {
+            state.repo.insert_pending_extraction(key).await?;
+            state.repo.finish_extraction(key, Ok(text)).await?;
+        }
+        return Ok(AttachmentStatus::Ready);
+    }
+
+    attachment.metadata =
+        probe_metadata(&attachment.mime_type, data, state.pdf_extract_timeout).await;
+    start_extraction(state, attachment, content_hash).await
+}
+
+/// Lance l'extraction du contenu d'un fichier uploadé en tâche de fond, et enregistre son
+/// résultat sous `content_hash` si elle réussit. Les images, envoyées telles quelles au
+/// modèle, n'en ont pas besoin.
+async fn start_extraction(
+    state: &AppState,
+    attachment: &AttachmentPayload,
+    content_hash: String,
+) -> Result<AttachmentStatus, sqlx::Error> {
+    let Some(key) = attachment.storage_key.clone() else {
+        return Ok(AttachmentStatus::Ready);
+    };
+    if attachment.mime_type.starts_with("image/") {
+        let derivative = AttachmentDerivative {
+            extracted_text: None,
+            metadata: attachment.metadata,
+        };
+        state
+            .repo
+            .insert_attachment_derivative(&content_hash, &attachment.mime_type, &derivative)
+            .await?;
+        return Ok(AttachmentStatus::Ready);
+    }
+    state.repo.insert_pending_extraction(&key).await?;
+
+    let state = state.clone();
+    let attachment = attachment.clone();
+    tokio::spawn(async move {
+        let started_at = Instant::now();
+        let read = read_attachment(&attachment, &state, &key).await;
+        state.upload_metrics.record_extraction(
+            &attachment.mime_type,
+            started_at.elapsed(),
+            read.as_ref().err().map(|(status, _)| *status),
+        );
+        let result = match read {
+            Ok(AttachmentContent::Text(text) | AttachmentContent::Image(text)) => Ok(text),
+            Err((_, err)) => Err(err),
+        };
+        if let Err(err) = &result {
+            eprintln!("Extraction de {} impossible: {err}", attachment.file_name);
+        }
+        // Enregistré avant la fin de l'extraction : un upload du même fichier qui suit une
+        // réponse ayant attendu cette extraction le reprend. Un échec (PDF trop long à
+        // lire...) sera retenté au prochain upload du fichier.
+        if let Ok(text) = &result {
+            let derivative = AttachmentDerivative {
+                extracted_text: Some(text.clone()),
+                metadata: attachment.metadata,
+            };
+            let stored = state
+                .repo
+                .insert_attachment_derivative(&content_hash, &attachment.mime_type, &derivative)
+                .await;
+            if let Err(err) = stored {
+                eprintln!("Impossible d'enregistrer le traitement de {key}: {err}");
+            }
+        }
+        let stored = state
+            .repo
+            .finish_extraction(&key, result.as_deref().map_err(String::as_str))
+            .await;
+        if let Err(err) = stored {
+            eprintln!("Impossible d'enregistrer l'extraction de {key}: {err}");
+        }
+    });
+    Ok(AttachmentStatus::Pending)
+}
+
+/// Extraction enregistrée pour `key`, en attendant au plus `ATTACHMENT_EXTRACTION_WAIT_SECS`
+/// qu'elle se termine ; `None` si le fichier n'en a pas.
+async fn wait_for_extraction(
+    state: &AppState,
+    key: &str,
+) -> Result<Option<AttachmentExtraction>, sqlx::Error> {
+    let deadline = Instant::now() + state.attachment_extraction_wait;
+    loop {
+        let extraction = state.repo.fetch_extraction(key).await?;
+        match &extraction {
+            Some(pending)
+                if pending.status == AttachmentStatus::Pending && Instant::now() < deadline =>
+            {
+                tokio::time::sleep(EXTRACTION_POLL_INTERVAL).await;
+            }
+            _ => return Ok(extraction),
+        }
+    }
+}
+
+/// Lit le fichier stocké sous `key` : data URL pour une image, texte extrait sinon.
+async fn read_attachment(
+    attachment: &AttachmentPayload,
+    state: &AppState,
+    key: &str,
+) -> Result<AttachmentContent, (axum::http::StatusCode, String)> {
+    let path = attachment_local_path(&state.upload_dir, key);
+    let data = tokio::fs::read(&path).await.map_err(internal_error)?;
+
+    if attachment.mime_type.starts_with("image/") {
+        let data_url = format!(
+            "data:{};base64,{}",
+            attachment.mime_type,
+            general_purpose::STANDARD.encode(data)
+        );
+        Ok(AttachmentContent::Image(data_url))
+    } else if attachment.mime_type == "application/pdf" {
+        let text = extract_pdf_text(&attachment.file_name, data, state.pdf_extract_timeout).await?;
+        Ok(AttachmentContent::Text(text))
+    } else if let Ok(text) = String::from_utf8(data.clone()) {
+        Ok(AttachmentContent::Text(text))
+    } else {
+        Ok(AttachmentContent::Text(format!(
+            "Fichier attaché (encodé en base64) {}:\n{}",
+            attachment.file_name,
+            general_purpose::STANDARD.encode(data)
+        )))
+    }
+}
+
+fn truncate_text(text: &str, max_chars: usize) -> String {
+    let total = text.chars().count();
+    if total <= max_chars {
+        text.to_string()
+    } else {
+        format!(
+            "{}\n\n[Texte tronqué, {max_chars} premiers caractères sur {total}]",
+            char_prefix(text, max_chars)
+        )
+    }
+}
+
+/// Les `max_chars` premiers caractères (et non octets) de `text`.
+fn char_prefix(text: &str, max_chars: usize) -> &str {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+/// Extrait le texte d'un PDF sur un thread bloquant, pages séparées par `PAGE_BREAK` : un
+/// fichier malformé peut faire tourner `pdf-extract` très longtemps, la requête abandonne
+/// alors au bout de `timeout`.
+async fn extract_pdf_text(
+    file_name: &str,
+    data: Vec<u8>,
+    timeout: Duration,
+) -> Result<String, (axum::http::StatusCode, String)> {
+    let extraction = tokio::task::spawn_blocking(move || {
+        PDF_WARNINGS.with_borrow_mut(|warnings| warnings.clear());
+        let text =
+            extract_text_from_mem_by_pages(&data).map(|pages| pages.join(&PAGE_BREAK.to_string()));
+        (text, PDF_WARNINGS.take())
+    });
+    let (text, warnings) = match tokio::time::timeout(timeout, extraction).await {
+        Ok(joined) => joined.map_err(internal_error)?,
+        Err(_) => {
+            return Err((
+                axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+                format!(
+                    "Extraction du texte de {file_name} trop longue (plus de {}s)",
+                    timeout.as_secs()
+                ),
+            ));
+        }
+    };
+    if let Some(first) = warnings.first() {
+        eprintln!(
+            "pdf-extract: {} avertissement(s) pour {file_name}, dont « {first} »",
+            warnings.len()
+        );
+    }
+    text.map_err(internal_error)
+}
+
+thread_local! {
+    /// Avertissements de `pdf-extract` émis par l'extraction en cours sur ce thread
+    static PDF_WARNINGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Logger qui range les avertissements de `pdf-extract` avec l'extraction qui les a produits,
+/// au lieu de les laisser se mélanger entre requêtes concurrentes.
+struct PdfLogCapture;
+
+impl log::Log for PdfLogCapture {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.target().starts_with("pdf_extract") && metadata.level() <= log::Level::Warn
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            let mut message = record.args().to_string();
+            if message.len() > 200 {
+                message.truncate(message.floor_char_boundary(200));
+                message.push('…');
+            }
+            PDF_WARNINGS.with_borrow_mut(|warnings| warnings.push(message));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Installe la capture des logs de `pdf-extract` ; sans effet si un logger est déjà en place.
+pub(crate) fn install_pdf_log_capture() {
+    static LOGGER: PdfLogCapture = PdfLogCapture;
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(log::LevelFilter::Warn);
+    }
+}
@@ -0,0 +1,413 @@
+//! Découpage du flux du provider en segments SSE (`token` / `reasoning`), regroupement des
+//! chunks pour limiter le nombre d'évènements, et envoi au client sans dépendre de sa vitesse
+//! de lecture.
+//!
+//! Chaque évènement d'une génération porte un nom SSE (`event:`), repris dans le champ `type`
+//! de ses données, et un numéro `seq` croissant (aussi envoyé comme `id:`) qui permet au client
+//! de détecter un trou ou un doublon. Le schéma est décrit dans `openapi.yaml`.
+
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use axum::response::sse::Event;
+use futures::stream::{self, BoxStream, StreamExt};
+use serde_json::{Value, json};
+use tokio::{sync::mpsc, time::Instant};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use uuid::Uuid;
+
+use crate::{
+    latency::LatencyClock,
+    models::{FinishReason, TokenUsage},
+    providers::{AiModelChoice, CompletionStream, ProviderChunk},
+};
+
+/// Évènements en attente de lecture par le client au-delà desquels les tokens sont retenus
+/// et fusionnés au lieu d'être envoyés un par un.
+const MAX_QUEUED_EVENTS: usize = 256;
+
+/// Types d'évènements SSE d'une génération.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum EventKind {
+    /// Session avec le placeholder de la réponse (premier évènement)
+    Session,
+    Token,
+    Reasoning,
+    /// Requête ajustée avant l'envoi au provider (`max_tokens` réduit...)
+    Notice,
+    /// Titre résumé en parallèle de la réponse
+    Title,
+    /// Le modèle a refusé de répondre ou le provider a filtré la réponse (avant `final`)
+    Refusal,
+    /// Réponse du modèle demandé, qui remplace le brouillon streamé (`draft`, avant `final`)
+    Revision,
+    Final,
+    /// Consommation, coût et durée de la génération (dernier évènement)
+    Usage,
+    Error,
+}
+
+impl EventKind {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Session => "session",
+            EventKind::Token => "token",
+            EventKind::Reasoning => "reasoning",
+            EventKind::Notice => "notice",
+            EventKind::Title => "title",
+            EventKind::Refusal => "refusal",
+            EventKind::Revision => "revision",
+            EventKind::Final => "final",
+            EventKind::Usage => "usage",
+            EventKind::Error => "error",
+        }
+    }
+}
+
+/// Données de l'évènement `usage` : tokens et coût (`null` si le provider n'a pas communiqué
+/// sa consommation), modèle, durée depuis l'appel au provider et sa décomposition, raison de
+/// fin de la réponse.
+pub(crate) fn usage_data(
+    model: AiModelChoice,
+    usage: Option<TokenUsage>,
+    clock: &LatencyClock,
+    finish_reason: Option<FinishReason>,
+) -> Value {
+    let breakdown = clock.breakdown();
+    json!({
+        "model": model.model_id(),
+        "promptTokens": usage.map(|usage| usage.prompt_tokens),
+        "completionTokens": usage.map(|usage| usage.completion_tokens),
+        "cachedTokens": usage.map(|usage| usage.cached_tokens),
+        "costUsd": usage.map(|usage| {
+            model.cost_usd(usage.prompt_tokens as u64, usage.completion_tokens as u64)
+        }),
+        "latencyMs": clock.elapsed().as_millis() as u64,
+        "firstTokenMs": breakdown.first_token.map(|elapsed| elapsed.as_millis() as u64),
+        "generationMs": breakdown.generation.as_millis() as u64,
+        "dbMs": breakdown.db.as_millis() as u64,
+        "attachmentsMs": breakdown.attachments.as_millis() as u64,
+        "finishReason": finish_reason.map(|reason| reason.as_str()),
+    })
+}
+
+/// Données de l'évènement `refusal` : `reason` vaut `refusal` quand le modèle a refusé de
+/// répondre (`message` : son texte) ou `content_filter` quand le provider a filtré la
+/// réponse ; `None` si la réponse n'a été ni refusée ni filtrée.
+pub(crate) fn refusal_data(refusal: &str, finish_reason: Option<FinishReason>) -> Option<Value> {
+    if !refusal.is_empty() {
+        Some(json!({ "reason": "refusal", "message": refusal }))
+    } else if finish_reason == Some(FinishReason::ContentFilter) {
+        Some(json!({ "reason": "content_filter", "message": null }))
+    } else {
+        None
+    }
+}
+
+/// Morceau de réponse prêt à être envoyé au client.
+pub(crate) enum StreamSegment {
+    Token(String),
+    Reasoning(String),
+}
+
+impl StreamSegment {
+    fn into_event(self) -> (EventKind, Value) {
+        match self {
+            StreamSegment::Token(content) => (EventKind::Token, json!({ "content": content })),
+            StreamSegment::Reasoning(content) => {
+                (EventKind::Reasoning, json!({ "content": content }))
+            }
+        }
+    }
+}
+
+/// Sépare le flux de tokens en contenu visible et en raisonnement (`<thinking>…</thinking>`),
+/// en gardant en tampon les balises coupées entre deux chunks.
+#[derive(Default)]
+pub(crate) struct ThinkingSplitter {
+    buffer: String,
+    in_thinking_block: bool,
+}
+
+impl ThinkingSplitter {
+    const OPEN_TAG: &'static str = "<thinking>";
+    const CLOSE_TAG: &'static str = "</thinking>";
+
+    pub(crate) fn push(&mut self, chunk: &str) -> Vec<StreamSegment> {
+        self.buffer.push_str(chunk);
+        let mut segments = Vec::new();
+
+        loop {
+            let tag = if self.in_thinking_block {
+                Self::CLOSE_TAG
+            } else {
+                Self::OPEN_TAG
+            };
+
+            if let Some(idx) = self.buffer.find(tag) {
+                // Contenu avant la balise, puis on bascule de mode
+                self.emit(&mut segments, idx);
+                self.buffer.drain(..tag.len());
+                self.in_thinking_block = !self.in_thinking_block;
+                continue;
+            }
+
+            // Pas de balise complète : on garde une éventuelle balise partielle en fin de tampon
+            let split_idx = partial_tag_start(&self.buffer, tag);
+            self.emit(&mut segments, split_idx);
+            break;
+        }
+
+        segments
+    }
+
+    pub(crate) fn finish(&mut self) -> Option<StreamSegment> {
+        let rest = std::mem::take(&mut self.buffer);
+        if rest.is_empty() {
+            None
+        } else if self.in_thinking_block {
+            Some(StreamSegment::Reasoning(rest))
+        } else {
+            Some(StreamSegment::Token(rest))
+        }
+    }
+
+    fn emit(&mut self, segments: &mut Vec<StreamSegment>, end: usize) {
+        if end == 0 {
+            return;
+        }
+        let content: String = self.buffer.drain(..end).collect();
+        segments.push(if self.in_thinking_block {
+            StreamSegment::Reasoning(content)
+        } else {
+            StreamSegment::Token(content)
+        });
+    }
+}
+
+/// Position du début d'une balise `tag` tronquée en fin de `buffer` (ou `buffer.len()`).
+fn partial_tag_start(buffer: &str, tag: &str) -> usize {
+    (1..tag.len())
+        .rev()
+        .find(|&len| buffer.ends_with(&tag[..len]))
+        .map(|len| buffer.len() - len)
+        .unwrap_or(buffer.len())
+}
+
+/// Regroupe les chunks de texte consécutifs du provider : le texte en attente part dès qu'il
+/// atteint `max_chars` caractères ou que `interval` s'est écoulé depuis son premier chunk
+/// (0 / `None` désactive le critère). La consommation et les erreurs vident d'abord le tampon.
+pub(crate) fn coalesce(
+    stream: CompletionStream,
+    interval: Option<Duration>,
+    max_chars: usize,
+) -> CompletionStream {
+    if interval.is_none() && max_chars == 0 {
+        return stream;
+    }
+
+    let coalescer = Coalescer {
+        inner: stream,
+        buffer: String::new(),
+        deadline: None,
+        pending: None,
+        done: false,
+    };
+    Box::pin(stream::unfold(coalescer, move |mut c| async move {
+        if let Some(item) = c.pending.take() {
+            return Some((item, c));
+        }
+        while !c.done {
+            let next = match c.deadline {
+                Some(deadline) => tokio::select! {
+                    next = c.inner.next() => next,
+                    _ = tokio::time::sleep_until(deadline) => return Some((c.flush(), c)),
+                },
+                None => c.inner.next().await,
+            };
+            match next {
+                Some(Ok(ProviderChunk::Text(text))) => {
+                    if c.buffer.is_empty() {
+                        c.deadline = interval.map(|interval| Instant::now() + interval);
+                    }
+                    c.buffer.push_str(&text);
+                    if max_chars > 0 && c.buffer.chars().count() >= max_chars {
+                        return Some((c.flush(), c));
+                    }
+                }
+                Some(other) if c.buffer.is_empty() => return Some((other, c)),
+                Some(other) => {
+                    c.pending = Some(other);
+                    return Some((c.flush(), c));
+                }
+                None => c.done = true,
+            }
+        }
+        (!c.buffer.is_empty()).then(|| (c.flush(), c))
+    }))
+}
+
+struct Coalescer {
+    inner: CompletionStream,
+    buffer: String,
+    /// Échéance du texte en attente (mode `interval`)
+    deadline: Option<Instant>,
+    /// Élément reçu pendant qu'un texte attendait, envoyé juste après lui
+    pending: Option<Result<ProviderChunk, String>>,
+    /// Le flux du provider est terminé : il ne doit plus être interrogé
+    done: bool,
+}
+
+impl Coalescer {
+    fn flush(&mut self) -> Result<ProviderChunk, String> {
+        self.deadline = None;
+        Ok(ProviderChunk::Text(std::mem::take(&mut self.buffer)))
+    }
+}
+
+/// Évènement d'une génération, avant sa mise en forme (SSE ou gRPC).
+pub(crate) struct ClientEvent {
+    pub(crate) kind: EventKind,
+    pub(crate) seq: u64,
+    /// Données complétées par `type`, `seq`, `chatId` et `messageId`
+    pub(crate) data: Value,
+}
+
+impl ClientEvent {
+    pub(crate) fn into_sse(self) -> Event {
+        Event::default()
+            .event(self.kind.as_str())
+            .id(self.seq.to_string())
+            .json_data(self.data)
+            .unwrap_or_else(|_| Event::default().event(self.kind.as_str()))
+    }
+}
+
+/// Évènements destinés au client, dans l'ordre d'envoi.
+pub(crate) type ClientEvents = BoxStream<'static, ClientEvent>;
+
+/// Envoi des évènements d'une génération au client. L'envoi n'attend jamais le client : la
+/// lecture du provider (et donc la réponse enregistrée) avance à son rythme. Quand le client
+/// a plus de `MAX_QUEUED_EVENTS` évènements de retard, les tokens suivants sont retenus et
+/// fusionnés, puis envoyés en un seul évènement dès que la file redescend ou avant tout
+/// évènement d'un autre type : le texte reçu par le client reste complet.
+pub(crate) struct ClientSink {
+    tx: mpsc::UnboundedSender<ClientEvent>,
+    queued: Arc<AtomicUsize>,
+    /// Dernier `seq` envoyé, partagé avec les tâches annexes ; le verrou est gardé pendant
+    /// l'envoi pour que l'ordre du canal suive celui des numéros.
+    seq: Arc<Mutex<u64>>,
+    chat_id: Option<Uuid>,
+    message_id: Option<Uuid>,
+    held: Option<StreamSegment>,
+}
+
+impl ClientSink {
+    pub(crate) fn channel(
+        chat_id: Option<Uuid>,
+        message_id: Option<Uuid>,
+    ) -> (ClientSink, ClientEvents) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let queued = Arc::new(AtomicUsize::new(0));
+        let read = queued.clone();
+        let events = UnboundedReceiverStream::new(rx).inspect(move |_| {
+            read.fetch_sub(1, Ordering::Relaxed);
+        });
+        let sink = ClientSink {
+            tx,
+            queued,
+            seq: Arc::new(Mutex::new(0)),
+            chat_id,
+            message_id,
+            held: None,
+        };
+        (sink, Box::pin(events))
+    }
+
+    /// Autre émetteur vers le même client (tâche annexe), sans les tokens retenus.
+    pub(crate) fn fork(&self) -> ClientSink {
+        ClientSink {
+            tx: self.tx.clone(),
+            queued: self.queued.clone(),
+            seq: self.seq.clone(),
+            chat_id: self.chat_id,
+            message_id: self.message_id,
+            held: None,
+        }
+    }
+
+    /// Envoie un évènement (après les tokens retenus). `data` est complété par `type`, `seq`,
+    /// et `chatId` / `messageId` s'ils sont connus. `false` si le client est parti.
+    pub(crate) fn send(&mut self, kind: EventKind, data: Value) -> bool {
+        self.release_held();
+        self.push(kind, data)
+    }
+
+    /// Envoie un token ou du raisonnement, ou le retient si le client est en retard.
+    pub(crate) fn send_segment(&mut self, segment: StreamSegment) -> bool {
+        match (&mut self.held, segment) {
+            (Some(StreamSegment::Token(held)), StreamSegment::Token(content))
+            | (Some(StreamSegment::Reasoning(held)), StreamSegment::Reasoning(content)) => {
+                held.push_str(&content);
+            }
+            (_, segment) => {
+                self.release_held();
+                self.held = Some(segment);
+            }
+        }
+        if !self.lagging() {
+            self.release_held();
+        }
+        !self.tx.is_closed()
+    }
+
+    /// Se termine quand le client a fermé la connexion.
+    pub(crate) async fn closed(&self) {
+        self.tx.closed().await
+    }
+
+    fn lagging(&self) -> bool {
+        self.queued.load(Ordering::Relaxed) >= MAX_QUEUED_EVENTS
+    }
+
+    fn release_held(&mut self) {
+        if let Some(segment) = self.held.take() {
+            let (kind, data) = segment.into_event();
+            self.push(kind, data);
+        }
+    }
+
+    fn push(&self, kind: EventKind, mut data: Value) -> bool {
+        let mut seq = self.seq.lock().unwrap();
+        *seq += 1;
+        if let Some(fields) = data.as_object_mut() {
+            fields.insert("type".to_string(), json!(kind.as_str()));
+            fields.insert("seq".to_string(), json!(*seq));
+            if let Some(chat_id) = self.chat_id {
+                fields.entry("chatId").or_insert(json!(chat_id));
+            }
+            if let Some(message_id) = self.message_id {
+                fields.entry("messageId").or_insert(json!(message_id));
+            }
+        }
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        self.tx
+            .send(ClientEvent {
+                kind,
+                seq: *seq,
+                data,
+            })
+            .is_ok()
+    }
+}
+
+impl Drop for ClientSink {
+    fn drop(&mut self) {
+        self.release_held();
+    }
+}
@@ -0,0 +1,434 @@
+//! Outils appelables par le modèle. `edit_file` applique un diff unifié à un fichier déjà
+//! présent dans la conversation (bloc de code nommé d'un message, pièce jointe texte ou
+//! fichier modifié plus tôt dans la réponse) : le fichier modifié complet est ajouté à la
+//! réponse par le serveur, sans que le modèle le recopie. `add_to_calendar` enregistre un
+//! évènement ou une tâche dans le calendrier de l'utilisateur (`calendar.rs`).
+//! `screenshot_page` capture une page web (`screenshot.rs`) : l'image est montrée au modèle
+//! au tour suivant et jointe à la réponse. Les plugins WASM (`plugins.rs`) ajoutent les leurs.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use futures::stream::{self, StreamExt};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::{
+    AppState,
+    artifacts::{extract_artifacts, fenced_file},
+    calendar::{CALENDAR_DESCRIPTION, CALENDAR_TOOL, add_to_calendar, calendar_parameters},
+    models::{AttachmentPayload, ChatMessagePayload, CompletionParams, FinishReason, TokenUsage},
+    patch::apply_unified_diff,
+    providers::{
+        AiModelChoice, CompletionStream, ProviderChunk, ProviderStream, StreamItem,
+        request_model_completion,
+    },
+    screenshot::{SCREENSHOT_DESCRIPTION, SCREENSHOT_TOOL, screenshot_page, screenshot_parameters},
+    storage::{AttachmentContent, load_attachment_content},
+};
+
+pub(crate) const EDIT_FILE_TOOL: &str = "edit_file";
+/// Allers-retours maximum entre le modèle et les outils pour une même réponse.
+const MAX_TOOL_ROUNDS: usize = 4;
+
+const EDIT_FILE_DESCRIPTION: &str = "Modifie un fichier déjà présent dans la conversation \
+(bloc de code nommé, pièce jointe texte) en lui appliquant un diff unifié. Le serveur affiche \
+à l'utilisateur le fichier modifié complet : ne le recopie pas dans ta réponse.";
+
+/// Appel d'outil en cours de réception : Chat Completions l'envoie par morceaux repérés par
+/// `index`, l'API Responses d'un bloc.
+pub(crate) struct ToolCallDelta {
+    pub(crate) index: usize,
+    pub(crate) id: Option<String>,
+    pub(crate) name: Option<String>,
+    pub(crate) arguments: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ToolCall {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) arguments: String,
+}
+
+/// Tour terminé par des appels d'outils, à renvoyer au provider avec leurs résultats.
+#[derive(Clone, Debug)]
+pub(crate) struct ToolRound {
+    /// Texte écrit par le modèle avant ses appels
+    pub(crate) text: String,
+    pub(crate) calls: Vec<ToolCall>,
+    /// Résultat de chaque appel, dans l'ordre de `calls`
+    pub(crate) outputs: Vec<String>,
+    /// Images produites par les appels, montrées au modèle après leurs résultats
+    pub(crate) images: Vec<AttachmentPayload>,
+    /// Réponse à reprendre (API Responses)
+    pub(crate) response_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct EditFileArgs {
+    file_name: String,
+    diff: String,
+}
+
+fn edit_file_parameters() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "file_name": {
+                "type": "string",
+                "description": "Nom du fichier tel qu'il apparaît dans la conversation (ex. src/main.rs)"
+            },
+            "diff": {
+                "type": "string",
+                "description": "Diff unifié avec des hunks @@ -a,b +c,d @@ ; les lignes de contexte et supprimées doivent être identiques au fichier"
+            }
+        },
+        "required": ["file_name", "diff"],
+        "additionalProperties": false
+    })
+}
+
+/// Outils activés par les paramètres de la requête : nom, description et paramètres.
+fn enabled_tools(state: &AppState, params: &CompletionParams) -> Vec<(String, String, Value)> {
+    let mut tools = Vec::new();
+    if params.code_edit == Some(true) {
+        tools.push((
+            EDIT_FILE_TOOL.to_string(),
+            EDIT_FILE_DESCRIPTION.to_string(),
+            edit_file_parameters(),
+        ));
+    }
+    if params.calendar == Some(true) {
+        tools.push((
+            CALENDAR_TOOL.to_string(),
+            CALENDAR_DESCRIPTION.to_string(),
+            calendar_parameters(),
+        ));
+    }
+    if params.screenshot == Some(true) {
+        tools.push((
+            SCREENSHOT_TOOL.to_string(),
+            SCREENSHOT_DESCRIPTION.to_string(),
+            screenshot_parameters(),
+        ));
+    }
+    for name in params.plugins.iter().flatten() {
+        if let Some(plugin) = state.plugins.tool(name) {
+            tools.push((
+                plugin.name,
+                plugin.description,
+                plugin.parameters.unwrap_or_default(),
+            ));
+        }
+    }
+    tools
+}
+
+/// Définitions des outils activés pour Chat Completions.
+pub(crate) fn chat_tool_definitions(state: &AppState, params: &CompletionParams) -> Vec<Value> {
+    enabled_tools(state, params)
+        .into_iter()
+        .map(|(name, description, parameters)| {
+            json!({
+                "type": "function",
+                "function": {
+                    "name": name,
+                    "description": description,
+                    "parameters": parameters
+                }
+            })
+        })
+        .collect()
+}
+
+/// Définitions des outils activés pour l'API Responses.
+pub(crate) fn responses_tool_definitions(
+    state: &AppState,
+    params: &CompletionParams,
+) -> Vec<Value> {
+    enabled_tools(state, params)
+        .into_iter()
+        .map(|(name, description, parameters)| {
+            json!({
+                "type": "function",
+                "name": name,
+                "description": description,
+                "parameters": parameters
+            })
+        })
+        .collect()
+}
+
+/// Flux renvoyé aux appelants : le texte du modèle passe tel quel ; quand le modèle appelle
+/// des outils, ils sont exécutés et la conversation est relancée avec leurs résultats. La
+/// consommation de tous les tours est additionnée et envoyée à la fin.
+pub(crate) fn run_tools(
+    state: AppState,
+    messages: Vec<ChatMessagePayload>,
+    model: AiModelChoice,
+    params: Option<CompletionParams>,
+    stream: ProviderStream,
+) -> CompletionStream {
+    let tool_loop = ToolLoop {
+        state,
+        messages,
+        model,
+        params,
+        stream: Some(stream),
+        pending: VecDeque::new(),
+        calls: BTreeMap::new(),
+        rounds: Vec::new(),
+        edited: Vec::new(),
+        round_text: String::new(),
+        round_images: Vec::new(),
+        finish: None,
+        usage: None,
+        response_id: None,
+    };
+    Box::pin(stream::unfold(tool_loop, |mut tool_loop| async move {
+        tool_loop.next().await.map(|chunk| (chunk, tool_loop))
+    }))
+}
+
+struct ToolLoop {
+    state: AppState,
+    messages: Vec<ChatMessagePayload>,
+    model: AiModelChoice,
+    params: Option<CompletionParams>,
+    /// Flux du tour en cours ; `None` une fois la réponse terminée
+    stream: Option<ProviderStream>,
+    pending: VecDeque<Result<ProviderChunk, String>>,
+    calls: BTreeMap<usize, ToolCall>,
+    rounds: Vec<ToolRound>,
+    /// Fichiers modifiés pendant la réponse, du plus ancien au plus récent
+    edited: Vec<(String, String)>,
+    round_text: String,
+    /// Images produites par les outils du tour en cours
+    round_images: Vec<AttachmentPayload>,
+    finish: Option<FinishReason>,
+    usage: Option<TokenUsage>,
+    response_id: Option<String>,
+}
+
+impl ToolLoop {
+    async fn next(&mut self) -> Option<Result<ProviderChunk, String>> {
+        loop {
+            if let Some(chunk) = self.pending.pop_front() {
+                return Some(chunk);
+            }
+            let item = self.stream.as_mut()?.next().await;
+            match item {
+                Some(Ok(StreamItem::Chunk(ProviderChunk::Text(text)))) => {
+                    self.round_text.push_str(&text);
+                    return Some(Ok(ProviderChunk::Text(text)));
+                }
+                Some(Ok(StreamItem::Chunk(ProviderChunk::ResponseId(id)))) => {
+                    self.response_id = Some(id.clone());
+                    return Some(Ok(ProviderChunk::ResponseId(id)));
+                }
+                Some(Ok(StreamItem::Chunk(
+                    chunk @ (ProviderChunk::DebugLogId(_)
+                    | ProviderChunk::AttachmentTime(_)
+                    | ProviderChunk::Notice(_)
+                    | ProviderChunk::Refusal(_)
+                    | ProviderChunk::Attachment(_)),
+                ))) => {
+                    return Some(Ok(chunk));
+                }
+                Some(Ok(StreamItem::Chunk(ProviderChunk::Finish(reason)))) => {
+                    self.finish = Some(reason);
+                }
+                Some(Ok(StreamItem::Chunk(ProviderChunk::Usage(reported)))) => {
+                    self.usage = Some(self.usage.map_or(reported, |usage| usage + reported));
+                }
+                Some(Ok(StreamItem::ToolCall(delta))) => {
+                    let call = self.calls.entry(delta.index).or_default();
+                    if let Some(id) = delta.id {
+                        call.id = id;
+                    }
+                    if let Some(name) = delta.name {
+                        call.name = name;
+                    }
+                    call.arguments.push_str(&delta.arguments);
+                }
+                Some(Err(err)) => {
+                    self.stream = None;
+                    return Some(Err(err));
+                }
+                None => self.end_round().await,
+            }
+        }
+    }
+
+    /// Fin du flux d'un tour : exécute les appels d'outils et relance le modèle, ou termine
+    /// la réponse.
+    async fn end_round(&mut self) {
+        let calls: Vec<ToolCall> = std::mem::take(&mut self.calls).into_values().collect();
+        if calls.is_empty() || self.rounds.len() >= MAX_TOOL_ROUNDS {
+            self.stream = None;
+            if let Some(reason) = self.finish {
+                self.pending.push_back(Ok(ProviderChunk::Finish(reason)));
+            }
+            if let Some(usage) = self.usage {
+                self.pending.push_back(Ok(ProviderChunk::Usage(usage)));
+            }
+            return;
+        }
+
+        let mut outputs = Vec::with_capacity(calls.len());
+        for call in &calls {
+            outputs.push(self.execute(call).await);
+        }
+        self.rounds.push(ToolRound {
+            text: std::mem::take(&mut self.round_text),
+            calls,
+            outputs,
+            images: std::mem::take(&mut self.round_images),
+            response_id: self.response_id.clone(),
+        });
+        self.finish = None;
+        self.stream = match request_model_completion(
+            &self.state,
+            &self.messages,
+            self.model,
+            self.params.clone(),
+            &self.rounds,
+        )
+        .await
+        {
+            Ok(stream) => Some(stream),
+            Err((_, message)) => {
+                self.pending.push_back(Err(message));
+                None
+            }
+        };
+    }
+
+    /// Résultat d'un appel, renvoyé au modèle.
+    async fn execute(&mut self, call: &ToolCall) -> String {
+        match call.name.as_str() {
+            EDIT_FILE_TOOL => self.edit_file(&call.arguments).await,
+            CALENDAR_TOOL => add_to_calendar(&self.state, &call.arguments).await,
+            SCREENSHOT_TOOL => {
+                let (output, capture) = screenshot_page(&self.state, &call.arguments).await;
+                if let Some(capture) = capture {
+                    self.pending
+                        .push_back(Ok(ProviderChunk::Attachment(capture.clone())));
+                    self.round_images.push(capture);
+                }
+                output
+            }
+            name if self.requested_plugin(name) => {
+                self.state.plugins.call_tool(name, &call.arguments).await
+            }
+            name => format!("Outil inconnu : {name}."),
+        }
+    }
+
+    /// Outil de plugin proposé au modèle par la requête.
+    fn requested_plugin(&self, name: &str) -> bool {
+        self.params
+            .as_ref()
+            .and_then(|params| params.plugins.as_ref())
+            .is_some_and(|plugins| plugins.iter().any(|plugin| plugin == name))
+    }
+
+    /// Applique le diff ; le fichier modifié est aussi ajouté à la réponse.
+    async fn edit_file(&mut self, arguments: &str) -> String {
+        let args: EditFileArgs = match serde_json::from_str(arguments) {
+            Ok(args) => args,
+            Err(err) => return format!("Arguments invalides pour {EDIT_FILE_TOOL} : {err}."),
+        };
+        let original = match self.find_in_texts(&args.file_name) {
+            Some(content) => Some(content),
+            None => find_attachment(&self.state, &self.messages, &args.file_name).await,
+        };
+        let Some(original) = original else {
+            return format!(
+                "Fichier introuvable dans la conversation : {}. Donne le nom d'un bloc de code \
+                 ou d'une pièce jointe texte.",
+                args.file_name
+            );
+        };
+        match apply_unified_diff(&original, &args.diff) {
+            Ok(patched) => {
+                let block = fenced_file(&args.file_name, &patched);
+                self.pending
+                    .push_back(Ok(ProviderChunk::Text(format!("\n\n{block}\n"))));
+                let lines = patched.lines().count();
+                self.edited.push((args.file_name.clone(), patched));
+                format!(
+                    "Diff appliqué à {} ({lines} lignes). Le fichier modifié complet a été \
+                     affiché à l'utilisateur : ne le recopie pas.",
+                    args.file_name
+                )
+            }
+            Err(err) => format!(
+                "Échec de l'édition de {} : {err}. Corrige le diff (lignes de contexte \
+                 identiques au fichier) ou réécris le fichier.",
+                args.file_name
+            ),
+        }
+    }
+
+    /// Dernière version connue d'un fichier dans les textes : modifiée pendant la réponse,
+    /// écrite par le modèle dans cette réponse, puis blocs de code des messages, du plus récent
+    /// au plus ancien.
+    fn find_in_texts(&self, file_name: &str) -> Option<String> {
+        if let Some((_, content)) = self
+            .edited
+            .iter()
+            .rev()
+            .find(|(name, _)| same_file(name, file_name))
+        {
+            return Some(content.clone());
+        }
+        let answer_so_far = self
+            .rounds
+            .iter()
+            .map(|round| round.text.as_str())
+            .chain([self.round_text.as_str()]);
+        let message_contents = self
+            .messages
+            .iter()
+            .rev()
+            .map(|message| message.content.as_str());
+        answer_so_far
+            .rev()
+            .chain(message_contents)
+            .find_map(|text| {
+                extract_artifacts(text)
+                    .into_iter()
+                    .rev()
+                    .find(|artifact| same_file(&artifact.file_name, file_name))
+            })
+            .map(|artifact| artifact.content)
+    }
+}
+
+/// Contenu texte de la pièce jointe la plus récente portant ce nom.
+async fn find_attachment(
+    state: &AppState,
+    messages: &[ChatMessagePayload],
+    file_name: &str,
+) -> Option<String> {
+    for attachment in messages
+        .iter()
+        .rev()
+        .flat_map(|message| message.attachments.iter().rev())
+        .filter(|attachment| same_file(&attachment.file_name, file_name))
+    {
+        if let Ok(AttachmentContent::Text(text)) =
+            load_attachment_content(attachment, state, usize::MAX).await
+        {
+            return Some(text);
+        }
+    }
+    None
+}
+
+/// Même fichier : chemins identiques, ou même nom quand l'un des deux n'a pas de dossier.
+fn same_file(a: &str, b: &str) -> bool {
+    let base = |path: &str| path.rsplit('/').next().unwrap_or(path).to_string();
+    a == b || ((!a.contains('/') || !b.contains('/')) && base(a) == base(b))
+}
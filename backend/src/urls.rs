@@ -0,0 +1,69 @@
+//! URLs publiques renvoyées aux clients (pièces jointes, téléchargement des exports, flux
+//! du calendrier) :
+//! construites à un seul endroit, à partir de `PUBLIC_BASE_URL` ou, à défaut, du schéma et
+//! de l'hôte de la requête vus derrière un proxy de confiance.
+
+use uuid::Uuid;
+
+use crate::{config::Config, proxy::ClientOrigin};
+
+/// Construction des URLs absolues du déploiement.
+#[derive(Clone, Debug)]
+pub struct PublicUrls {
+    /// `PUBLIC_BASE_URL`, sans `/` final
+    base_url: Option<String>,
+    /// `UPLOAD_BASE_URL`, sans `/` final : chemin (`/uploads`) ou URL absolue
+    upload_base_url: String,
+}
+
+impl PublicUrls {
+    /// `PUBLIC_BASE_URL` et `UPLOAD_BASE_URL`.
+    pub fn from_config(config: &Config) -> Self {
+        PublicUrls {
+            base_url: config
+                .public_base_url
+                .as_deref()
+                .map(|url| url.trim_end_matches('/').to_string()),
+            upload_base_url: config.upload_base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// URL absolue d'un chemin du serveur (`/api/...`). Sans `PUBLIC_BASE_URL` ni requête
+    /// dont l'hôte est connu (tâches de fond, CLI), le chemin reste relatif.
+    pub(crate) fn absolute(&self, origin: Option<&ClientOrigin>, path: &str) -> String {
+        if path.starts_with("http://") || path.starts_with("https://") {
+            return path.to_string();
+        }
+        match self
+            .base_url
+            .clone()
+            .or_else(|| origin.and_then(ClientOrigin::base_url))
+        {
+            Some(root) => format!("{root}{path}"),
+            None => path.to_string(),
+        }
+    }
+
+    /// URL d'un fichier du dossier des uploads.
+    pub(crate) fn upload(&self, origin: Option<&ClientOrigin>, storage_key: &str) -> String {
+        self.absolute(origin, &format!("{}/{storage_key}", self.upload_base_url))
+    }
+
+    /// Lien de téléchargement de l'archive d'un export.
+    pub(crate) fn export_download(
+        &self,
+        origin: Option<&ClientOrigin>,
+        export_id: Uuid,
+        token: Uuid,
+    ) -> String {
+        self.absolute(
+            origin,
+            &format!("/api/export/{export_id}/download?token={token}"),
+        )
+    }
+
+    /// Lien d'abonnement au flux ICS du calendrier.
+    pub(crate) fn calendar_feed(&self, origin: Option<&ClientOrigin>, token: Uuid) -> String {
+        self.absolute(origin, &format!("/api/me/calendar.ics?token={token}"))
+    }
+}
@@ -0,0 +1,150 @@
+//! Widgets de chat intégrables (`WIDGETS_FILE`) : jeton propre à chaque widget, persona et
+//! modèle imposés, limite de requêtes par visiteur. La conversation reste dans la page du
+//! visiteur et n'est jamais enregistrée ; le jeton n'ouvre aucun autre endpoint.
+
+use std::sync::Arc;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{StatusCode, header, request::Parts},
+};
+use redis::aio::ConnectionManager;
+
+use crate::{
+    AppState,
+    access::constant_time_eq,
+    limits::RateLimiter,
+    models::{ChatMessagePayload, Widget, WidgetMessage},
+};
+
+/// Widgets du déploiement, chacun avec son décompte de requêtes.
+#[derive(Default)]
+pub(crate) struct Widgets {
+    widgets: Vec<Arc<RegisteredWidget>>,
+}
+
+pub(crate) struct RegisteredWidget {
+    pub(crate) widget: Widget,
+    pub(crate) limiter: RateLimiter,
+}
+
+impl Widgets {
+    pub(crate) fn new(widgets: &[Widget], redis: Option<ConnectionManager>) -> Self {
+        Widgets {
+            widgets: widgets
+                .iter()
+                .map(|widget| {
+                    Arc::new(RegisteredWidget {
+                        limiter: RateLimiter::new(widget.rate_limit_per_minute, redis.clone()),
+                        widget: widget.clone(),
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// Widget du jeton ; tous les jetons sont comparés, en temps constant.
+    fn find(&self, token: &str) -> Option<&Arc<RegisteredWidget>> {
+        self.widgets.iter().fold(None, |found, registered| {
+            let matches = constant_time_eq(token.as_bytes(), registered.widget.token.as_bytes());
+            if matches { Some(registered) } else { found }
+        })
+    }
+}
+
+/// Widget authentifié par `Authorization: Bearer <jeton>`, appelé depuis une origine qu'il
+/// autorise.
+#[derive(Clone)]
+pub(crate) struct WidgetAccess(pub(crate) Arc<RegisteredWidget>);
+
+#[async_trait]
+impl FromRequestParts<AppState> for WidgetAccess {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim);
+        let Some(registered) = token.and_then(|token| state.widgets.find(token)) else {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                "Jeton de widget invalide.".to_string(),
+            ));
+        };
+        let allowed = &registered.widget.allowed_origins;
+        let origin = parts
+            .headers
+            .get(header::ORIGIN)
+            .and_then(|value| value.to_str().ok());
+        if !allowed.is_empty()
+            && !origin.is_some_and(|origin| {
+                allowed
+                    .iter()
+                    .any(|allowed| allowed.trim_end_matches('/') == origin)
+            })
+        {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "Ce widget n'est pas autorisé sur ce site.".to_string(),
+            ));
+        }
+        Ok(WidgetAccess(registered.clone()))
+    }
+}
+
+/// Messages envoyés au modèle : le persona, puis les derniers messages de la conversation,
+/// qui doit se terminer par une question du visiteur.
+pub(crate) fn conversation(
+    widget: &Widget,
+    messages: Vec<WidgetMessage>,
+) -> Result<Vec<ChatMessagePayload>, (StatusCode, String)> {
+    let bad_request = |message: String| Err((StatusCode::BAD_REQUEST, message));
+    match messages.last() {
+        None => return bad_request("La conversation doit contenir au moins un message.".into()),
+        Some(last) if last.role != "user" => {
+            return bad_request("Le dernier message doit venir du visiteur (`user`).".into());
+        }
+        Some(_) => {}
+    }
+    if let Some(message) = messages
+        .iter()
+        .find(|message| message.role != "user" && message.role != "assistant")
+    {
+        return bad_request(format!(
+            "Rôle non autorisé : {} (`user` ou `assistant`).",
+            message.role
+        ));
+    }
+    if messages
+        .iter()
+        .any(|message| message.content.chars().count() > widget.max_message_chars)
+    {
+        return bad_request(format!(
+            "Message trop long : {} caractères au plus.",
+            widget.max_message_chars
+        ));
+    }
+
+    let skipped = messages.len().saturating_sub(widget.max_messages);
+    let persona = widget.persona.iter().map(|persona| ChatMessagePayload {
+        role: "system".to_string(),
+        content: persona.clone(),
+        attachments: Vec::new(),
+    });
+    let history = messages
+        .into_iter()
+        .skip(skipped)
+        .map(|message| ChatMessagePayload {
+            role: message.role,
+            content: message.content,
+            attachments: Vec::new(),
+        });
+    Ok(persona.chain(history).collect())
+}
@@ -0,0 +1,135 @@
+mod common;
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use axum::{Json, Router, http::StatusCode, routing::post};
+use common::TestApp;
+use serde_json::{Value, json};
+use tokio::{net::TcpListener, sync::mpsc};
+use uuid::Uuid;
+
+/// Webhook d'archivage qui répond 503 à ses `failures` premiers appels.
+async fn fake_archive(failures: usize) -> (String, mpsc::UnboundedReceiver<Value>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/archive", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::unbounded_channel();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let app = Router::new().route(
+        "/archive",
+        post(move |Json(body): Json<Value>| async move {
+            if calls.fetch_add(1, Ordering::SeqCst) < failures {
+                return StatusCode::SERVICE_UNAVAILABLE;
+            }
+            tx.send(body).unwrap();
+            StatusCode::OK
+        }),
+    );
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (url, rx)
+}
+
+#[tokio::test]
+async fn completed_exchanges_are_archived() {
+    let (archive_url, mut archived) = fake_archive(1).await;
+    let app = TestApp::spawn_with(|config| config.archive.url = Some(archive_url)).await;
+    let session_id = app.create_session().await;
+    let (status, _) = app
+        .request(
+            axum::http::Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({ "content": "Bonjour" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Le premier envoi échoue : l'échange arrive à la deuxième tentative.
+    let exchange = tokio::time::timeout(Duration::from_secs(10), archived.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(exchange["version"], 1);
+    assert_eq!(exchange["session_id"], session_id.to_string());
+    assert_eq!(exchange["question"]["role"], "user");
+    assert_eq!(exchange["question"]["content"], "Bonjour");
+    assert_eq!(exchange["answer"]["role"], "assistant");
+    assert_eq!(exchange["answer"]["status"], "complete");
+
+    app.stream(
+        &format!("/api/chat/sessions/{session_id}/messages/stream"),
+        json!({ "content": "Et ensuite ?" }),
+    )
+    .await;
+    let exchange = tokio::time::timeout(Duration::from_secs(10), archived.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(exchange["question"]["content"], "Et ensuite ?");
+}
+
+#[tokio::test]
+async fn failed_archives_are_kept_and_sent_again() {
+    let (archive_url, mut archived) = fake_archive(1).await;
+    let dead_letter_dir = std::env::temp_dir().join(format!("carlgpt-archive-{}", Uuid::new_v4()));
+    let dir = dead_letter_dir.to_string_lossy().into_owned();
+    let app = TestApp::spawn_with(|config| {
+        config.archive.url = Some(archive_url);
+        config.archive.max_attempts = 1;
+        config.archive.dead_letter_dir = dir;
+        config.archive.retry_interval = Duration::from_secs(1);
+    })
+    .await;
+    let session_id = app.create_session().await;
+    app.stream(
+        &format!("/api/chat/sessions/{session_id}/messages/stream"),
+        json!({ "content": "Bonjour" }),
+    )
+    .await;
+
+    // Refusé une fois, l'échange attend dans les lettres mortes puis part avec `archive_retry`.
+    let exchange = tokio::time::timeout(Duration::from_secs(10), archived.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(exchange["question"]["content"], "Bonjour");
+    for _ in 0..50 {
+        let remaining = std::fs::read_dir(&dead_letter_dir).unwrap().count();
+        if remaining == 0 {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("la lettre morte n'a pas été supprimée");
+}
+
+#[tokio::test]
+async fn exchanges_can_be_archived_to_a_directory() {
+    let dir = std::env::temp_dir().join(format!("carlgpt-archive-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let url = format!("file://{}", dir.display());
+    let app = TestApp::spawn_with(|config| config.archive.url = Some(url)).await;
+    let session_id = app.create_session().await;
+    app.stream(
+        &format!("/api/chat/sessions/{session_id}/messages/stream"),
+        json!({ "content": "Bonjour" }),
+    )
+    .await;
+
+    for _ in 0..100 {
+        let files: Vec<_> = std::fs::read_dir(&dir).unwrap().flatten().collect();
+        if let Some(file) = files.first() {
+            let exchange: Value =
+                serde_json::from_slice(&std::fs::read(file.path()).unwrap()).unwrap();
+            assert_eq!(exchange["session_id"], session_id.to_string());
+            assert!(file.file_name().to_string_lossy().ends_with(".json"));
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("aucun échange archivé dans {}", dir.display());
+}
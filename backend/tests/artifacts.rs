@@ -0,0 +1,147 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use backend::mock::MockReply;
+use serde_json::json;
+
+use common::{TestApp, zip_entries};
+
+#[tokio::test]
+async fn code_blocks_can_be_listed_and_downloaded() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    app.provider().push_reply(MockReply::Text(
+        "Voici le projet.\n\nFichier `src/main.rs` :\n\n```rust\nfn main() {\n    println!(\"Bonjour\");\n}\n```\n\n\
+         ```toml title=\"Cargo.toml\"\n[package]\nname = \"bonjour\"\n```\n\n\
+         Pour lancer :\n\n```bash\ncargo run\n```\n"
+            .to_string(),
+    ));
+    let (_, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({ "content": "Un hello world en Rust ?" })),
+        )
+        .await;
+    let question_id = session["messages"][0]["id"].as_str().unwrap();
+    let answer_id = session["messages"][1]["id"].as_str().unwrap();
+    let artifacts_uri = format!("/api/chat/messages/{answer_id}/artifacts");
+
+    let (status, artifacts) = app.request(Method::GET, &artifacts_uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let names: Vec<&str> = artifacts
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|artifact| artifact["file_name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["src/main.rs", "Cargo.toml", "extrait-3.sh"]);
+    assert_eq!(artifacts[0]["language"], "rust");
+    assert_eq!(artifacts[2]["filename_hint"], serde_json::Value::Null);
+    assert_eq!(artifacts[2]["content"], "cargo run\n");
+
+    let (status, content_type, file) = app.download(&format!("{artifacts_uri}/1")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type.as_deref(), Some("text/plain; charset=utf-8"));
+    assert_eq!(file, b"[package]\nname = \"bonjour\"\n");
+
+    let (status, content_type, archive) = app.download(&format!("{artifacts_uri}/download")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type.as_deref(), Some("application/zip"));
+    let entries = zip_entries(&archive);
+    assert_eq!(entries[0].0, "src/main.rs");
+    assert!(String::from_utf8_lossy(&entries[0].1).contains("println!"));
+    assert_eq!(entries.len(), 3);
+
+    let (status, _, _) = app.download(&format!("{artifacts_uri}/3")).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _, _) = app
+        .download(&format!(
+            "/api/chat/messages/{question_id}/artifacts/download"
+        ))
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn the_model_can_patch_a_shared_file_with_a_diff() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    let messages_uri = format!("/api/chat/sessions/{session_id}/messages");
+    app.provider().push_reply(MockReply::Text(
+        "```rust:src/lib.rs\npub fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n```\n".to_string(),
+    ));
+    app.request(
+        Method::POST,
+        &messages_uri,
+        Some(json!({ "content": "Une fonction d'addition ?", "model": "gpt-5-mini" })),
+    )
+    .await;
+
+    let edit = |diff: &str| MockReply::ToolCall {
+        name: "edit_file".to_string(),
+        arguments: json!({ "file_name": "src/lib.rs", "diff": diff }).to_string(),
+    };
+    app.provider()
+        .push_reply(edit("@@ -2,1 +2,1 @@\n-    a - b\n+    a * b\n"));
+    app.provider().push_reply(edit(
+        "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,3 +1,3 @@\n-pub fn add(a: i32, b: i32) -> i32 {\n+pub fn add(a: i64, b: i64) -> i64 {\n     a + b\n }\n",
+    ));
+    app.provider()
+        .push_reply(MockReply::Text("Les types passent en i64.".to_string()));
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &messages_uri,
+            Some(json!({
+                "content": "Passe en i64",
+                "model": "gpt-5-mini",
+                "completion_params": { "code_edit": true }
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let requests = app.provider().requests();
+    let outputs: Vec<&Vec<String>> = requests[1..]
+        .iter()
+        .map(|request| &request.tool_outputs)
+        .collect();
+    assert!(outputs[0].is_empty());
+    assert!(outputs[1][0].starts_with("Échec de l'édition de src/lib.rs"));
+    assert!(outputs[2][0].starts_with("Diff appliqué à src/lib.rs (3 lignes)"));
+
+    let answer = &session["messages"][3];
+    assert!(
+        answer["content"]
+            .as_str()
+            .unwrap()
+            .ends_with("Les types passent en i64.")
+    );
+    let (_, artifacts) = app
+        .request(
+            Method::GET,
+            &format!(
+                "/api/chat/messages/{}/artifacts",
+                answer["id"].as_str().unwrap()
+            ),
+            None,
+        )
+        .await;
+    assert_eq!(artifacts[0]["file_name"], "src/lib.rs");
+    assert_eq!(
+        artifacts[0]["content"],
+        "pub fn add(a: i64, b: i64) -> i64 {\n    a + b\n}\n"
+    );
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            &messages_uri,
+            Some(
+                json!({ "content": "Et avec Llama ?", "completion_params": { "code_edit": true } }),
+            ),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
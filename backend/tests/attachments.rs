@@ -0,0 +1,700 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use backend::cassette::CassetteMode;
+use base64::Engine as _;
+use lopdf::{
+    Document, Object, Stream,
+    content::{Content, Operation},
+    dictionary,
+};
+use serde_json::json;
+use uuid::Uuid;
+
+use common::TestApp;
+
+#[tokio::test]
+async fn attachment_content_is_read_once() {
+    let mut upload_dir = String::new();
+    let app = TestApp::spawn_with(|config| upload_dir = config.upload_dir.clone()).await;
+    let path = std::path::Path::new(&upload_dir).join("notes.txt");
+    tokio::fs::write(&path, "Compte rendu de la réunion ".repeat(40))
+        .await
+        .unwrap();
+    let session_id = app.create_session().await;
+    let body = json!({
+        "content": "Résume ce fichier",
+        "attachments": [{
+            "file_name": "notes.txt",
+            "mime_type": "text/plain",
+            "size_bytes": 1080,
+            "url": "http://127.0.0.1:4000/uploads/notes.txt",
+            "storage_key": "notes.txt"
+        }]
+    });
+    let uri = format!("/api/chat/sessions/{session_id}/estimate");
+
+    let (status, first) = app.request(Method::POST, &uri, Some(body.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(first["attachment_tokens"].as_u64().unwrap() > 0);
+
+    // Le fichier n'est plus relu : l'estimation ne dépend plus du disque.
+    tokio::fs::remove_file(&path).await.unwrap();
+    let (status, second) = app.request(Method::POST, &uri, Some(body)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(second["attachment_tokens"], first["attachment_tokens"]);
+}
+
+#[tokio::test]
+async fn unreadable_pdf_fails_the_request() {
+    let mut upload_dir = String::new();
+    let app = TestApp::spawn_with(|config| upload_dir = config.upload_dir.clone()).await;
+    tokio::fs::write(
+        std::path::Path::new(&upload_dir).join("casse.pdf"),
+        b"%PDF-1.4 pas vraiment un PDF",
+    )
+    .await
+    .unwrap();
+    let session_id = app.create_session().await;
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/estimate"),
+            Some(json!({
+                "content": "Résume ce fichier",
+                "attachments": [{
+                    "file_name": "casse.pdf",
+                    "mime_type": "application/pdf",
+                    "size_bytes": 28,
+                    "url": "http://127.0.0.1:4000/uploads/casse.pdf",
+                    "storage_key": "casse.pdf"
+                }]
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[tokio::test]
+async fn uploads_are_extracted_in_the_background() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+
+    let (status, notes) = app
+        .upload(
+            "notes.txt",
+            "text/plain",
+            "Ordre du jour ".repeat(50).as_bytes(),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(["pending", "ready"].contains(&notes["processing_status"].as_str().unwrap()));
+    let (status, broken) = app
+        .upload(
+            "casse.pdf",
+            "application/pdf",
+            b"%PDF-1.4 pas vraiment un PDF",
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // L'estimation attend la fin des extractions ; le PDF illisible ne la fait pas échouer.
+    let (status, estimate) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/estimate"),
+            Some(json!({ "content": "Résume", "attachments": [notes, broken] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(estimate["attachment_tokens"].as_u64().unwrap() > 150);
+
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({
+                "content": "Résume",
+                "model": "gpt-5-mini",
+                "attachments": [notes, broken]
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    // Les deux pièces jointes sont insérées dans la même transaction : leur ordre n'est pas
+    // garanti.
+    let status_of = |file_name: &str| {
+        session["messages"][0]["attachments"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|attachment| attachment["file_name"] == file_name)
+            .map(|attachment| attachment["processing_status"].clone())
+            .unwrap()
+    };
+    assert_eq!(status_of("notes.txt"), "ready");
+    assert_eq!(status_of("casse.pdf"), "failed");
+}
+
+/// PDF minimal d'une page par texte, en Courier.
+fn sample_pdf(pages: &[String]) -> Vec<u8> {
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Courier",
+    });
+    let resources_id = doc.add_object(dictionary! {
+        "Font" => dictionary! { "F1" => font_id },
+    });
+    let kids: Vec<Object> = pages
+        .iter()
+        .map(|text| {
+            let content = Content {
+                operations: vec![
+                    Operation::new("BT", vec![]),
+                    Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                    Operation::new("Td", vec![50.into(), 700.into()]),
+                    Operation::new("Tj", vec![Object::string_literal(text.as_str())]),
+                    Operation::new("ET", vec![]),
+                ],
+            };
+            let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+            doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "Contents" => content_id,
+            })
+            .into()
+        })
+        .collect();
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Count" => kids.len() as i64,
+            "Kids" => kids,
+            "Resources" => resources_id,
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        }),
+    );
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    doc.trailer.set("Root", catalog_id);
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes).unwrap();
+    bytes
+}
+
+#[tokio::test]
+async fn pdf_pages_can_be_selected() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    let pages: Vec<String> = (1..=5)
+        .map(|page| format!("Chapitre {page} ").repeat(20))
+        .collect();
+    let (_, report) = app
+        .upload("rapport.pdf", "application/pdf", &sample_pdf(&pages))
+        .await;
+    let (_, notes) = app.upload("notes.txt", "text/plain", b"Notes").await;
+    let estimate = |attachment: serde_json::Value| {
+        let uri = format!("/api/chat/sessions/{session_id}/estimate");
+        let app = &app;
+        async move {
+            app.request(
+                Method::POST,
+                &uri,
+                Some(json!({ "content": "Résume", "attachments": [attachment] })),
+            )
+            .await
+        }
+    };
+
+    let (status, full) = estimate(report.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    let mut selected = report.clone();
+    selected["pages"] = json!("2-3");
+    let (status, partial) = estimate(selected).await;
+    assert_eq!(status, StatusCode::OK);
+    let full_tokens = full["attachment_tokens"].as_u64().unwrap();
+    let partial_tokens = partial["attachment_tokens"].as_u64().unwrap();
+    assert!(
+        partial_tokens * 2 < full_tokens,
+        "{partial_tokens} / {full_tokens}"
+    );
+
+    let mut reversed = report;
+    reversed["pages"] = json!("3-1");
+    let (status, _) = estimate(reversed).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let mut not_a_pdf = notes;
+    not_a_pdf["pages"] = json!("1");
+    let (status, _) = estimate(not_a_pdf).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn attachment_text_is_capped_in_tokens() {
+    let mut upload_dir = String::new();
+    let app = TestApp::spawn_with(|config| {
+        upload_dir = config.upload_dir.clone();
+        config.attachment_max_tokens = 100;
+    })
+    .await;
+    tokio::fs::write(
+        std::path::Path::new(&upload_dir).join("long.txt"),
+        "é".repeat(10_000),
+    )
+    .await
+    .unwrap();
+    let session_id = app.create_session().await;
+
+    let (status, estimate) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/estimate"),
+            Some(json!({
+                "content": "Résume",
+                "attachments": [{
+                    "file_name": "long.txt",
+                    "mime_type": "text/plain",
+                    "size_bytes": 20_000,
+                    "url": "http://127.0.0.1:4000/uploads/long.txt",
+                    "storage_key": "long.txt"
+                }]
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let tokens = estimate["attachment_tokens"].as_u64().unwrap();
+    assert!((100..150).contains(&tokens), "{tokens}");
+}
+
+#[tokio::test]
+async fn pasted_text_becomes_an_attachment() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    let text = "ligne de log\n".repeat(2_000);
+
+    let (status, pasted) = app
+        .request(
+            Method::POST,
+            "/api/uploads/text",
+            Some(json!({ "text": text })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(pasted["file_name"], "texte-colle.txt");
+    assert_eq!(pasted["mime_type"], "text/plain");
+    assert_eq!(pasted["size_bytes"], text.len());
+    let preview = pasted["preview"].as_str().unwrap();
+    assert!(preview.starts_with("ligne de log ligne de log"));
+    assert!(preview.chars().count() <= 201);
+
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({
+                "content": "Que disent ces logs ?",
+                "model": "gpt-5-mini",
+                "attachments": [pasted]
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(session["messages"][0]["content"], "Que disent ces logs ?");
+    assert_eq!(
+        session["messages"][0]["attachments"][0]["file_name"],
+        "texte-colle.txt"
+    );
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            "/api/uploads/text",
+            Some(json!({ "text": " " })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn attachment_urls_are_resolved_from_the_storage_key() {
+    let app = TestApp::spawn_with(|config| {
+        config.public_base_url = Some("https://chat.example.com".to_string());
+        config.upload_base_url = "/uploads".to_string();
+    })
+    .await;
+    let session_id = app.create_session().await;
+    let attachment = |storage_key: &str| {
+        json!({
+            "file_name": "photo.png",
+            "mime_type": "image/png",
+            "size_bytes": 4,
+            "storage_key": storage_key
+        })
+    };
+    let mut stale = attachment("photo.png");
+    stale["url"] = json!("http://ancien-domaine:4000/uploads/photo.png");
+
+    let (status, _) = app
+        .request(
+            Method::PUT,
+            &format!("/api/chat/sessions/{session_id}/draft"),
+            Some(json!({ "content": "Brouillon", "attachments": [attachment("brouillon.png")] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, sessions) = app.request(Method::GET, "/api/chat/sessions", None).await;
+    assert_eq!(
+        sessions[0]["draft"]["attachments"][0]["url"],
+        "https://chat.example.com/uploads/brouillon.png"
+    );
+
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({
+                "content": "Que vois-tu ?",
+                "model": "gpt-5-mini",
+                "attachments": [stale]
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+
+    // L'URL envoyée par le client n'est pas conservée.
+    assert_eq!(
+        session["messages"][0]["attachments"][0]["url"],
+        "https://chat.example.com/uploads/photo.png"
+    );
+}
+
+/// En-tête WAV PCM mono 8 kHz sur 16 bits suivi de `seconds` de silence.
+fn sample_wav(seconds: u32) -> Vec<u8> {
+    let byte_rate: u32 = 8000 * 2;
+    let data_len = byte_rate * seconds;
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&8000u32.to_le_bytes());
+    wav.extend_from_slice(&byte_rate.to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav.resize(wav.len() + data_len as usize, 0);
+    wav
+}
+
+/// En-tête PNG (signature et chunk IHDR) d'une image `width`×`height`.
+fn sample_png_header(width: u32, height: u32) -> Vec<u8> {
+    let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+    png.extend_from_slice(&width.to_be_bytes());
+    png.extend_from_slice(&height.to_be_bytes());
+    png.extend_from_slice(&[8, 6, 0, 0, 0]);
+    png
+}
+
+#[tokio::test]
+async fn upload_metadata_is_stored_with_the_attachment() {
+    let app = TestApp::spawn().await;
+    let pages: Vec<String> = (1..=3).map(|page| format!("Page {page}")).collect();
+    let (_, pdf) = app
+        .upload("rapport.pdf", "application/pdf", &sample_pdf(&pages))
+        .await;
+    let (_, image) = app
+        .upload("schema.png", "image/png", &sample_png_header(1920, 1080))
+        .await;
+    let (_, audio) = app.upload("memo.wav", "audio/wav", &sample_wav(2)).await;
+    assert_eq!(pdf["page_count"], 3);
+    assert_eq!(image["width"], 1920);
+    assert_eq!(image["height"], 1080);
+    assert_eq!(audio["duration_ms"], 2000);
+
+    let session_id = app.create_session().await;
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/estimate"),
+            Some(json!({ "content": "Décris l'image", "model": "gpt-5-mini", "attachments": [image] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    // 1920×1080 ramenée à 1365×768 : 3×2 tuiles
+    assert_eq!(session["attachment_tokens"], 85 + 6 * 170);
+
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({
+                "content": "Résume ces fichiers",
+                "model": "gpt-5-mini",
+                "attachments": [pdf, audio]
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    let attachments = session["messages"][0]["attachments"].as_array().unwrap();
+    let stored = |file_name: &str| {
+        attachments
+            .iter()
+            .find(|attachment| attachment["file_name"] == file_name)
+            .unwrap()
+            .clone()
+    };
+    assert_eq!(stored("rapport.pdf")["page_count"], 3);
+    assert_eq!(stored("memo.wav")["duration_ms"], 2000);
+    assert!(stored("memo.wav").get("width").is_none());
+}
+
+#[tokio::test]
+async fn reuploaded_files_reuse_their_processing() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    let pdf = sample_pdf(&["Budget 2025".to_string(), "Annexes".to_string()]);
+
+    let (status, first) = app.upload("budget.pdf", "application/pdf", &pdf).await;
+    assert_eq!(status, StatusCode::OK);
+    let estimate_uri = format!("/api/chat/sessions/{session_id}/estimate");
+    let (status, first_estimate) = app
+        .request(
+            Method::POST,
+            &estimate_uri,
+            Some(json!({ "content": "Résume", "attachments": [first] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Même contenu sous un autre nom : ni relu ni réanalysé, son texte est disponible aussitôt.
+    let (status, second) = app.upload("copie.pdf", "application/pdf", &pdf).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(second["processing_status"], "ready");
+    assert_eq!(second["page_count"], 2);
+    assert_ne!(second["storage_key"], first["storage_key"]);
+    let (status, second_estimate) = app
+        .request(
+            Method::POST,
+            &estimate_uri,
+            Some(json!({ "content": "Résume", "attachments": [second] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        second_estimate["attachment_tokens"],
+        first_estimate["attachment_tokens"]
+    );
+
+    // Le même contenu envoyé comme texte est traité à part.
+    let (_, as_text) = app.upload("budget.txt", "text/plain", &pdf).await;
+    assert!(as_text.get("page_count").is_none());
+}
+
+/// PNG de 40×20 px : moitié gauche rouge, moitié droite bleue.
+fn two_color_png() -> Vec<u8> {
+    let image = image::RgbImage::from_fn(40, 20, |x, _| {
+        if x < 20 {
+            image::Rgb([255, 0, 0])
+        } else {
+            image::Rgb([0, 0, 255])
+        }
+    });
+    let mut png = Vec::new();
+    image
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+    png
+}
+
+#[tokio::test]
+async fn image_regions_are_validated_and_stored() {
+    let app = TestApp::spawn().await;
+    let (_, mut image) = app
+        .upload("capture.png", "image/png", &two_color_png())
+        .await;
+    let (_, mut pdf) = app
+        .upload(
+            "rapport.pdf",
+            "application/pdf",
+            &sample_pdf(&["Page 1".to_string()]),
+        )
+        .await;
+    let session_id = app.create_session().await;
+    let region = json!({ "x": 0, "y": 0, "width": 10, "height": 10, "label": "le carré rouge" });
+    image["regions"] = json!([region, { "x": 30, "y": 5, "width": 100, "height": 10 }]);
+
+    // Chaque zone compte comme une petite image.
+    let (status, estimate) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/estimate"),
+            Some(json!({ "content": "Et ça ?", "model": "gpt-5-mini", "attachments": [image] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{estimate}");
+    assert_eq!(estimate["attachment_tokens"], 2 * (85 + 170));
+
+    let mut outside = image.clone();
+    outside["regions"] = json!([{ "x": 40, "y": 0, "width": 5, "height": 5 }]);
+    let mut empty = image.clone();
+    empty["regions"] = json!([{ "x": 0, "y": 0, "width": 0, "height": 5 }]);
+    pdf["regions"] = json!([region]);
+    for attachment in [outside, empty, pdf] {
+        let (status, body) = app
+            .request(
+                Method::POST,
+                &format!("/api/chat/sessions/{session_id}/messages"),
+                Some(json!({ "content": "Et ça ?", "model": "gpt-5-mini", "attachments": [attachment] })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+    }
+
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({ "content": "Et ça ?", "model": "gpt-5-mini", "attachments": [image] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    let stored = &session["messages"][0]["attachments"][0];
+    assert_eq!(stored["regions"], image["regions"]);
+}
+
+#[tokio::test]
+async fn only_the_annotated_regions_are_sent_to_the_model() {
+    let dir = std::env::temp_dir().join(format!("carlgpt-cassettes-{}", Uuid::new_v4()));
+    let cassette_dir = dir.to_string_lossy().into_owned();
+    let app = TestApp::spawn_with(|config| {
+        config.mock_provider = false;
+        config.cassette_mode = Some(CassetteMode::Replay);
+        config.cassette_dir = cassette_dir;
+        config.provider_debug_log = true;
+        config.admin_token = Some("secret".to_string());
+    })
+    .await;
+    let (_, mut image) = app
+        .upload("capture.png", "image/png", &two_color_png())
+        .await;
+    image["regions"] =
+        json!([{ "x": 25, "y": 0, "width": 10, "height": 40, "label": "cette partie" }]);
+    let session_id = app.create_session().await;
+    let uri = format!("/api/chat/sessions/{session_id}/messages");
+    let request =
+        json!({ "content": "Quelle couleur ?", "model": "gpt-5-mini", "attachments": [image] });
+
+    // Pas encore de cassette : l'erreur donne le fichier attendu pour cette requête.
+    let (status, body) = app.request(Method::POST, &uri, Some(request.clone())).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    let path = body
+        .as_str()
+        .unwrap()
+        .split_once('(')
+        .and_then(|(_, rest)| rest.split_once(')'))
+        .map(|(path, _)| path.to_string())
+        .unwrap();
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        &path,
+        "data: {\"choices\":[{\"delta\":{\"content\":\"Bleu\"}}]}\n\ndata: [DONE]\n\n",
+    )
+    .unwrap();
+    let (status, session) = app.request(Method::POST, &uri, Some(request)).await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    let answer_id = session["messages"][1]["id"].as_str().unwrap();
+
+    let logs_uri = format!("/api/admin/provider-logs?message_id={answer_id}");
+    for _ in 0..50 {
+        let (_, logs) = app
+            .request_with_headers(Method::GET, &logs_uri, None, &[("x-admin-token", "secret")])
+            .await;
+        let Some(log) = logs.as_array().unwrap().first() else {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            continue;
+        };
+        let sent = log["request"].to_string();
+        assert!(
+            sent.contains(
+                "Zone 1/1 de capture.png (x 25, y 0, 10 × 20 px sur 40 × 20) : « cette partie »"
+            ),
+            "{sent}"
+        );
+        let images: Vec<_> = sent.split("data:image/png;base64,").skip(1).collect();
+        assert_eq!(images.len(), 1);
+        let encoded = images[0].split('"').next().unwrap();
+        let crop = image::load_from_memory(
+            &base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .unwrap(),
+        )
+        .unwrap()
+        .to_rgb8();
+        assert_eq!(crop.dimensions(), (10, 20));
+        assert!(crop.pixels().all(|pixel| *pixel == image::Rgb([0, 0, 255])));
+        std::fs::remove_dir_all(&dir).unwrap();
+        return;
+    }
+    panic!("la requête n'a pas été journalisée");
+}
+
+#[tokio::test]
+async fn upload_metrics_are_exposed_to_admins() {
+    let app = TestApp::spawn_with(|config| config.admin_token = Some("secret".to_string())).await;
+    let (status, _) = app
+        .upload("notes.txt", "text/plain; charset=utf-8", b"Compte rendu")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app
+        .request(
+            Method::POST,
+            "/api/uploads/text",
+            Some(json!({ "text": " " })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = app.request(Method::GET, "/api/admin/metrics", None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    for _ in 0..50 {
+        let (status, metrics) = app
+            .request_with_headers(
+                Method::GET,
+                "/api/admin/metrics",
+                None,
+                &[("x-admin-token", "secret")],
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        let metrics = metrics.as_str().unwrap();
+        assert!(
+            metrics.contains("carlgpt_uploads_total{endpoint=\"file\",mime_type=\"text/plain\"} 1")
+        );
+        assert!(
+            metrics.contains("carlgpt_upload_size_bytes_bucket{endpoint=\"file\",le=\"1024\"} 1")
+        );
+        assert!(
+            metrics
+                .contains("carlgpt_upload_failures_total{endpoint=\"text\",reason=\"invalid\"} 1")
+        );
+        // L'extraction se termine en arrière-plan.
+        if metrics.contains(
+            "carlgpt_attachment_extraction_seconds_count{kind=\"text\",outcome=\"ready\"} 1",
+        ) {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    panic!("l'extraction n'a pas été mesurée");
+}
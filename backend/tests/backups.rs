@@ -0,0 +1,155 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+use uuid::Uuid;
+
+use common::TestApp;
+
+const ADMIN: &[(&str, &str)] = &[("x-admin-token", "secret")];
+
+#[tokio::test]
+async fn deleted_session_is_restored_from_backup() {
+    let dir = std::env::temp_dir().join(format!("carlgpt-backups-{}", Uuid::new_v4()));
+    let backup_url = format!("file://{}", dir.display());
+    let app = TestApp::spawn_with(|config| {
+        config.admin_token = Some("secret".to_string());
+        config.backup_url = Some(backup_url);
+    })
+    .await;
+    let session_id = app.create_session().await;
+    let (status, _) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({ "content": "Bonjour" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, backup) = app
+        .request_with_headers(Method::POST, "/api/admin/backups", None, ADMIN)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{backup}");
+    assert_eq!(backup["sessions"], 1);
+    let name = backup["name"].as_str().unwrap();
+
+    let (status, backups) = app
+        .request_with_headers(Method::GET, "/api/admin/backups", None, ADMIN)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(backups[0]["name"], name);
+
+    let (status, _) = app
+        .request(
+            Method::DELETE,
+            &format!("/api/chat/sessions/{session_id}"),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let restore_uri = format!("/api/admin/backups/{name}/restore");
+    let (status, report) = app
+        .request_with_headers(Method::POST, &restore_uri, None, ADMIN)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{report}");
+    assert_eq!(report["sessions_restored"], 1);
+    let (_, sessions) = app.request(Method::GET, "/api/chat/sessions", None).await;
+    assert_eq!(sessions[0]["id"], session_id.to_string());
+    assert_eq!(sessions[0]["messages"][0]["content"], "Bonjour");
+    assert_eq!(sessions[0]["messages"].as_array().unwrap().len(), 2);
+
+    // Une deuxième restauration ne duplique rien.
+    let (_, report) = app
+        .request_with_headers(Method::POST, &restore_uri, None, ADMIN)
+        .await;
+    assert_eq!(report["sessions_restored"], 0);
+    assert_eq!(report["sessions_skipped"], 1);
+
+    let (status, _) = app
+        .request_with_headers(
+            Method::POST,
+            "/api/admin/backups/backup-inconnue.json.gz/restore",
+            None,
+            ADMIN,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn backups_are_disabled_without_url() {
+    let app = TestApp::spawn_with(|config| {
+        config.admin_token = Some("secret".to_string());
+    })
+    .await;
+    let (status, _) = app
+        .request_with_headers(Method::GET, "/api/admin/backups", None, ADMIN)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn deleted_session_is_restored_as_of_a_date() {
+    let dir = std::env::temp_dir().join(format!("carlgpt-backups-{}", Uuid::new_v4()));
+    let backup_url = format!("file://{}", dir.display());
+    let app = TestApp::spawn_with(|config| {
+        config.admin_token = Some("secret".to_string());
+        config.backup_url = Some(backup_url);
+    })
+    .await;
+    let session_id = app.create_session().await;
+    let messages_uri = format!("/api/chat/sessions/{session_id}/messages");
+    app.request(
+        Method::POST,
+        &messages_uri,
+        Some(json!({ "content": "Premier" })),
+    )
+    .await;
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    let before_second = chrono::Utc::now();
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    app.request(
+        Method::POST,
+        &messages_uri,
+        Some(json!({ "content": "Second" })),
+    )
+    .await;
+    app.request_with_headers(Method::POST, "/api/admin/backups", None, ADMIN)
+        .await;
+
+    let restore_uri = format!("/api/admin/restore/session/{session_id}");
+    let body = json!({ "at": before_second });
+    let (status, _) = app
+        .request_with_headers(Method::POST, &restore_uri, Some(body.clone()), ADMIN)
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    app.request(
+        Method::DELETE,
+        &format!("/api/chat/sessions/{session_id}"),
+        None,
+    )
+    .await;
+    let (status, restored) = app
+        .request_with_headers(Method::POST, &restore_uri, Some(body), ADMIN)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{restored}");
+    let messages = restored["session"]["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0]["content"], "Premier");
+
+    // Session jamais sauvegardée
+    let (status, _) = app
+        .request_with_headers(
+            Method::POST,
+            &format!("/api/admin/restore/session/{}", Uuid::new_v4()),
+            Some(json!({})),
+            ADMIN,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use serde_json::{Value, json};
+
+use common::TestApp;
+
+const ADMIN: &[(&str, &str)] = &[("x-admin-token", "secret")];
+
+fn ids(messages: &Value) -> Vec<i64> {
+    messages
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|message| message["id"].as_i64().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn board_messages_are_paginated_filtered_edited_and_deleted() {
+    let app = TestApp::spawn_with(|config| config.admin_token = Some("secret".to_string())).await;
+    let mut created = Vec::new();
+    for (author, content) in [
+        ("Alice", "Premier"),
+        ("Bob", "Deuxième"),
+        ("Alice", "Troisième"),
+        ("Bob", "Quatrième"),
+        ("Alice", "Cinquième"),
+    ] {
+        let (status, message) = app
+            .request(
+                Method::POST,
+                "/api/messages",
+                Some(json!({ "author": author, "content": content })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{message}");
+        assert!(message.get("edited_at").is_none());
+        created.push(message["id"].as_i64().unwrap());
+    }
+
+    let (status, page) = app
+        .request(Method::GET, "/api/messages?limit=2", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(ids(&page), [created[4], created[3]]);
+    let (_, page) = app
+        .request(
+            Method::GET,
+            &format!("/api/messages?limit=2&before={}", created[3]),
+            None,
+        )
+        .await;
+    assert_eq!(ids(&page), [created[2], created[1]]);
+    let (_, page) = app
+        .request(Method::GET, "/api/messages?author=Alice", None)
+        .await;
+    assert_eq!(ids(&page), [created[4], created[2], created[0]]);
+
+    // Le livre d'or est anonyme : seule la modération retouche ou supprime un message.
+    let uri = format!("/api/messages/{}", created[1]);
+    let (status, _) = app
+        .request(Method::PATCH, &uri, Some(json!({ "content": "Piraté" })))
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app.request(Method::DELETE, &uri, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, message) = app
+        .request_with_headers(
+            Method::PATCH,
+            &uri,
+            Some(json!({ "content": "  Corrigé " })),
+            ADMIN,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{message}");
+    assert_eq!(message["author"], "Bob");
+    assert_eq!(message["content"], "Corrigé");
+    assert!(message["edited_at"].is_string());
+    let (status, _) = app
+        .request_with_headers(Method::PATCH, &uri, Some(json!({ "author": " " })), ADMIN)
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app
+        .request_with_headers(Method::PATCH, &uri, Some(json!({})), ADMIN)
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app
+        .request(
+            Method::POST,
+            "/api/messages",
+            Some(json!({ "author": "Eve", "content": "" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = app
+        .request_with_headers(Method::DELETE, &uri, None, ADMIN)
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = app
+        .request_with_headers(Method::DELETE, &uri, None, ADMIN)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = app
+        .request_with_headers(
+            Method::PATCH,
+            &uri,
+            Some(json!({ "content": "Fantôme" })),
+            ADMIN,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, page) = app
+        .request(Method::GET, "/api/messages?author=Bob", None)
+        .await;
+    assert_eq!(ids(&page), [created[3]]);
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use serde_json::{Value, json};
+
+use common::TestApp;
+
+#[tokio::test]
+async fn bookmarked_answers_are_listed_across_sessions() {
+    let app = TestApp::spawn().await;
+    let mut answers = Vec::new();
+    for question in ["Une recette de crêpes ?", "Un poème sur la mer ?"] {
+        let session_id = app.create_session().await;
+        let (_, session) = app
+            .request(
+                Method::POST,
+                &format!("/api/chat/sessions/{session_id}/messages"),
+                Some(json!({ "content": question })),
+            )
+            .await;
+        let answer_id = session["messages"][1]["id"].as_str().unwrap().to_string();
+        answers.push((session_id, answer_id));
+    }
+    let message = |(session_id, answer_id): &(uuid::Uuid, String), action: &str| {
+        format!("/api/chat/sessions/{session_id}/messages/{answer_id}/{action}")
+    };
+
+    for answer in &answers {
+        let (status, _) = app
+            .request(
+                Method::PUT,
+                &message(answer, "bookmark"),
+                Some(json!({ "bookmarked": true })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+    }
+    let (status, _) = app
+        .request(
+            Method::PUT,
+            &message(&answers[0], "reaction"),
+            Some(json!({ "reaction": "👍" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, bookmarks) = app.request(Method::GET, "/api/bookmarks", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let ours: Vec<&Value> = bookmarks
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|bookmark| answers.iter().any(|(_, id)| bookmark["message_id"] == *id))
+        .collect();
+    // Le plus récent d'abord
+    assert_eq!(ours.len(), 2);
+    assert_eq!(ours[0]["message_id"], answers[1].1);
+    assert_eq!(ours[0]["session_title"], "Un poème sur la mer ?");
+    assert_eq!(ours[1]["reaction"], "👍");
+    assert_eq!(
+        ours[1]["content"],
+        "Réponse simulée : Une recette de crêpes ?"
+    );
+
+    let (status, _) = app
+        .request(
+            Method::PUT,
+            &message(&answers[1], "bookmark"),
+            Some(json!({ "bookmarked": false })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (_, bookmarks) = app.request(Method::GET, "/api/bookmarks", None).await;
+    assert!(
+        bookmarks
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|bookmark| bookmark["message_id"] != answers[1].1)
+    );
+
+    let (status, _) = app
+        .request(
+            Method::PUT,
+            &message(&answers[0], "reaction"),
+            Some(json!({ "reaction": "une réaction beaucoup trop longue" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let other_session = (answers[1].0, answers[0].1.clone());
+    let (status, _) = app
+        .request(
+            Method::PUT,
+            &message(&other_session, "bookmark"),
+            Some(json!({ "bookmarked": true })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,141 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+use common::TestApp;
+
+/// Session active de la liste, `None` si elle est archivée ou supprimée.
+async fn listed(app: &TestApp, session_id: Uuid) -> Option<Value> {
+    let (_, sessions) = app.request(Method::GET, "/api/chat/sessions", None).await;
+    sessions
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|session| session["id"] == session_id.to_string())
+        .cloned()
+}
+
+#[tokio::test]
+async fn sessions_are_tagged_moved_and_archived_together() {
+    let app = TestApp::spawn().await;
+    let ids = [app.create_session().await, app.create_session().await];
+
+    let (status, result) = app
+        .request(
+            Method::POST,
+            "/api/chat/sessions/bulk",
+            Some(json!({ "action": "tag", "session_ids": ids, "add": ["rust", " travail ", ""] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{result}");
+    assert_eq!(result, json!({ "action": "tag", "updated": 2 }));
+    let (_, result) = app
+        .request(
+            Method::POST,
+            "/api/chat/sessions/bulk",
+            Some(json!({
+                "action": "tag",
+                "session_ids": [ids[0]],
+                "add": ["anki", "rust"],
+                "remove": ["travail"]
+            })),
+        )
+        .await;
+    assert_eq!(result["updated"], 1);
+
+    let (_, result) = app
+        .request(
+            Method::POST,
+            "/api/chat/sessions/bulk",
+            Some(json!({ "action": "move", "session_ids": ids, "folder": "Projets" })),
+        )
+        .await;
+    assert_eq!(result["updated"], 2);
+    let first = listed(&app, ids[0]).await.unwrap();
+    assert_eq!(first["tags"], json!(["anki", "rust"]));
+    assert_eq!(first["folder"], "Projets");
+    let second = listed(&app, ids[1]).await.unwrap();
+    assert_eq!(second["tags"], json!(["rust", "travail"]));
+
+    let (_, result) = app
+        .request(
+            Method::POST,
+            "/api/chat/sessions/bulk",
+            Some(json!({ "action": "move", "session_ids": [ids[1]], "folder": null })),
+        )
+        .await;
+    assert_eq!(result["updated"], 1);
+    assert_eq!(listed(&app, ids[1]).await.unwrap()["folder"], Value::Null);
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{}/archive", ids[0]),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, result) = app
+        .request(
+            Method::POST,
+            "/api/chat/sessions/bulk",
+            Some(json!({ "action": "archive", "session_ids": ids })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    // La première était déjà archivée.
+    assert_eq!(result["updated"], 1);
+    assert!(listed(&app, ids[1]).await.is_none());
+}
+
+#[tokio::test]
+async fn unknown_session_cancels_the_whole_batch() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    let unknown = Uuid::new_v4();
+
+    let (status, body) = app
+        .request(
+            Method::POST,
+            "/api/chat/sessions/bulk",
+            Some(json!({ "action": "delete", "session_ids": [session_id, unknown] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(
+        body.as_str().unwrap().contains(&unknown.to_string()),
+        "{body}"
+    );
+    assert!(listed(&app, session_id).await.is_some());
+
+    for request in [
+        json!({ "action": "delete", "session_ids": [] }),
+        json!({ "action": "tag", "session_ids": [session_id] }),
+        json!({ "action": "move", "session_ids": [session_id], "folder": "x".repeat(65) }),
+    ] {
+        let (status, _) = app
+            .request(Method::POST, "/api/chat/sessions/bulk", Some(request))
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    let (status, result) = app
+        .request(
+            Method::POST,
+            "/api/chat/sessions/bulk",
+            Some(json!({ "action": "delete", "session_ids": [session_id, session_id] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(result["updated"], 1);
+    let (status, _) = app
+        .request(
+            Method::DELETE,
+            &format!("/api/chat/sessions/{session_id}"),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
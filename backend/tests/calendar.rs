@@ -0,0 +1,178 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use backend::mock::MockReply;
+use serde_json::json;
+
+use common::TestApp;
+
+#[tokio::test]
+async fn calendar_tool_feeds_an_ics_calendar() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    let tool_call = |arguments: serde_json::Value| MockReply::ToolCall {
+        name: "add_to_calendar".to_string(),
+        arguments: arguments.to_string(),
+    };
+    app.provider().push_reply(tool_call(json!({
+        "kind": "event",
+        "title": "Dentiste",
+        "description": "Cabinet du Dr Martin, 12 rue des Lilas",
+        "start": "2026-10-20T14:00:00+02:00",
+        "end": "2026-10-20T14:30:00+02:00"
+    })));
+    // Heure sans fuseau : refusée, le modèle corrige.
+    app.provider().push_reply(tool_call(json!({
+        "kind": "todo",
+        "title": "Envoyer le rapport",
+        "start": "2026-10-23T09:00"
+    })));
+    app.provider().push_reply(tool_call(json!({
+        "kind": "todo",
+        "title": "Envoyer le rapport",
+        "start": "2026-10-23"
+    })));
+    app.provider().push_reply(MockReply::Text(
+        "C'est noté dans ton calendrier.".to_string(),
+    ));
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({
+                "content": "Rappelle-moi le dentiste mardi à 14h et d'envoyer le rapport vendredi",
+                "model": "gpt-5-mini",
+                "completion_params": { "calendar": true }
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+
+    let outputs: Vec<String> = app
+        .provider()
+        .requests()
+        .iter()
+        .rev()
+        .take(3)
+        .rev()
+        .map(|request| request.tool_outputs[0].clone())
+        .collect();
+    assert_eq!(
+        outputs[0],
+        "Ajouté au calendrier de l'utilisateur : Dentiste (2026-10-20T12:00:00+00:00)."
+    );
+    assert!(
+        outputs[1].contains("n'a pas de fuseau horaire"),
+        "{outputs:?}"
+    );
+    assert_eq!(
+        outputs[2],
+        "Ajouté au calendrier de l'utilisateur : Envoyer le rapport (2026-10-23)."
+    );
+
+    let (status, calendar) = app.request(Method::GET, "/api/me/calendar", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let items = calendar["items"].as_array().unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0]["kind"], "event");
+    assert_eq!(items[1]["kind"], "todo");
+    assert_eq!(items[1]["all_day"], true);
+
+    let feed_url = calendar["feed_url"].as_str().unwrap().to_string();
+    let (status, content_type, ics) = app.download(&feed_url).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(content_type.unwrap().starts_with("text/calendar"));
+    let ics = String::from_utf8(ics).unwrap();
+    assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"), "{ics}");
+    for line in [
+        "BEGIN:VEVENT",
+        "SUMMARY:Dentiste",
+        "DESCRIPTION:Cabinet du Dr Martin\\, 12 rue des Lilas",
+        "DTSTART:20261020T120000Z",
+        "DTEND:20261020T123000Z",
+        "BEGIN:VTODO",
+        "DUE;VALUE=DATE:20261023",
+        "STATUS:NEEDS-ACTION",
+    ] {
+        assert!(ics.contains(&format!("\r\n{line}\r\n")), "{line} : {ics}");
+    }
+
+    // Le flux exige le jeton, qui peut être remplacé.
+    let (status, _, _) = app
+        .download("/api/me/calendar.ics?token=00000000-0000-0000-0000-000000000000")
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, rotated) = app
+        .request(Method::POST, "/api/me/calendar/token", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_ne!(rotated["feed_url"], calendar["feed_url"]);
+    let (status, _, _) = app.download(&feed_url).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let item_uri = format!(
+        "/api/me/calendar/items/{}",
+        items[0]["id"].as_str().unwrap()
+    );
+    let (status, _) = app.request(Method::DELETE, &item_uri, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = app.request(Method::DELETE, &item_uri, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn candidates_do_not_run_the_calendar_tool_several_times() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    app.provider().push_reply(MockReply::ToolCall {
+        name: "add_to_calendar".to_string(),
+        arguments: json!({ "kind": "todo", "title": "Dentiste", "start": "2026-10-20" })
+            .to_string(),
+    });
+    let message = |candidates: u8| {
+        json!({
+            "content": "Rappelle-moi le dentiste mardi",
+            "model": "gpt-5-mini",
+            "candidates": candidates,
+            "completion_params": { "calendar": true }
+        })
+    };
+
+    // Chaque candidate ajouterait sa propre entrée : la requête est refusée avant le modèle.
+    let (status, body) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(message(3)),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+    assert!(app.provider().requests().is_empty());
+
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(message(1)),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    let (_, calendar) = app.request(Method::GET, "/api/me/calendar", None).await;
+    assert_eq!(calendar["items"].as_array().unwrap().len(), 1);
+
+    let message_id = session["messages"][1]["id"].as_str().unwrap();
+    let (status, body) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/regenerate"),
+            Some(json!({
+                "message_id": message_id,
+                "candidates": 2,
+                "completion_params": { "calendar": true }
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+    let (_, calendar) = app.request(Method::GET, "/api/me/calendar", None).await;
+    assert_eq!(calendar["items"].as_array().unwrap().len(), 1);
+}
@@ -0,0 +1,348 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use backend::cassette::CassetteMode;
+use serde_json::json;
+use uuid::Uuid;
+
+use common::TestApp;
+
+#[tokio::test]
+async fn replay_serves_recorded_stream_without_api_key() {
+    let dir = std::env::temp_dir().join(format!("carlgpt-cassettes-{}", Uuid::new_v4()));
+    let cassette_dir = dir.to_string_lossy().into_owned();
+    let app = TestApp::spawn_with(|config| {
+        config.mock_provider = false;
+        config.cassette_mode = Some(CassetteMode::Replay);
+        config.cassette_dir = cassette_dir;
+    })
+    .await;
+    let request = json!({ "messages": [{ "role": "user", "content": "Bonjour" }] });
+
+    // Sans cassette, l'erreur indique le fichier attendu.
+    let (status, body) = app
+        .request(Method::POST, "/api/ai", Some(request.clone()))
+        .await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    let message = body.as_str().unwrap();
+    let path = message
+        .split_once('(')
+        .and_then(|(_, rest)| rest.split_once(')'))
+        .map(|(path, _)| path)
+        .unwrap();
+    assert!(path.starts_with(dir.to_str().unwrap()));
+
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        path,
+        concat!(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Salut \"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"enregistré\"}}]}\n\n",
+            "data: [DONE]\n\n",
+        ),
+    )
+    .unwrap();
+
+    let (status, body) = app.request(Method::POST, "/api/ai", Some(request)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["response"], "Salut enregistré");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn debug_log_keeps_the_exchange_of_each_message() {
+    let dir = std::env::temp_dir().join(format!("carlgpt-cassettes-{}", Uuid::new_v4()));
+    let cassette_dir = dir.to_string_lossy().into_owned();
+    let app = TestApp::spawn_with(|config| {
+        config.mock_provider = false;
+        config.cassette_mode = Some(CassetteMode::Replay);
+        config.cassette_dir = cassette_dir;
+        config.provider_debug_log = true;
+        config.admin_token = Some("secret".to_string());
+        config.scrub.builtin_secrets = true;
+    })
+    .await;
+    let session_id = app.create_session().await;
+    let uri = format!("/api/chat/sessions/{session_id}/messages");
+    let request =
+        json!({ "content": "Ma clé est sk-abcdefghijklmnopqrstuvwxyz", "model": "gpt-5-mini" });
+
+    let (status, body) = app.request(Method::POST, &uri, Some(request.clone())).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    let path = body
+        .as_str()
+        .unwrap()
+        .split_once('(')
+        .and_then(|(_, rest)| rest.split_once(')'))
+        .map(|(path, _)| path.to_string())
+        .unwrap();
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        &path,
+        concat!(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Ne partage pas sk-abcdefghijklmnopqrstuvwxyz\"}}]}\n\n",
+            "data: [DONE]\n\n",
+        ),
+    )
+    .unwrap();
+
+    let (status, session) = app.request(Method::POST, &uri, Some(request)).await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    let answer_id = session["messages"][1]["id"].as_str().unwrap();
+
+    let logs_uri = format!("/api/admin/provider-logs?message_id={answer_id}");
+    for _ in 0..50 {
+        let (status, logs) = app
+            .request_with_headers(Method::GET, &logs_uri, None, &[("x-admin-token", "secret")])
+            .await;
+        assert_eq!(status, StatusCode::OK, "{logs}");
+        let Some(log) = logs
+            .as_array()
+            .unwrap()
+            .first()
+            .filter(|log| !log["response"].is_null())
+        else {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            continue;
+        };
+        assert_eq!(log["provider"], "openai");
+        assert_eq!(log["http_status"], 200);
+        assert_eq!(log["request"]["model"], "gpt-5-mini");
+        let request = log["request"].to_string();
+        assert!(request.contains("[masqué:openai_key]"), "{request}");
+        assert!(!request.contains("abcdefghij"));
+        let response = log["response"].as_str().unwrap();
+        assert!(
+            response.contains("Ne partage pas [masqué:openai_key]"),
+            "{response}"
+        );
+        assert!(response.ends_with("data: [DONE]\n\n"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        return;
+    }
+    panic!("l'échange n'a pas été journalisé");
+}
+
+#[tokio::test]
+async fn replay_reads_crlf_streams_with_refusals_and_extra_choices() {
+    let dir = std::env::temp_dir().join(format!("carlgpt-cassettes-{}", Uuid::new_v4()));
+    let cassette_dir = dir.to_string_lossy().into_owned();
+    let app = TestApp::spawn_with(|config| {
+        config.mock_provider = false;
+        config.cassette_mode = Some(CassetteMode::Replay);
+        config.cassette_dir = cassette_dir;
+    })
+    .await;
+    let request = json!({ "messages": [{ "role": "user", "content": "Aide-moi à tricher" }] });
+
+    let (_, body) = app
+        .request(Method::POST, "/api/ai", Some(request.clone()))
+        .await;
+    let path = body
+        .as_str()
+        .unwrap()
+        .split_once('(')
+        .and_then(|(_, rest)| rest.split_once(')'))
+        .map(|(path, _)| path.to_string())
+        .unwrap();
+    std::fs::create_dir_all(&dir).unwrap();
+    // CRLF, `data:` sans espace, commentaire, refus et second choix à ignorer
+    std::fs::write(
+        &path,
+        concat!(
+            ": connexion ouverte\r\n\r\n",
+            "data:{\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Désolé, \"}}]}\r\n\r\n",
+            "data:{\"choices\":[{\"index\":1,\"delta\":{\"content\":\"autre réponse\"}}]}\r\n\r\n",
+            "data: {\"choices\":[{\"index\":0,\"delta\":{\"refusal\":\"je ne peux pas aider à ça.\"}}]}\r\n\r\n",
+            "data: {\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"stop\"}],\r\n",
+            "data: \"usage\":{\"prompt_tokens\":12,\"completion_tokens\":7}}\r\n\r\n",
+            "data: [DONE]\r\n\r\n",
+        ),
+    )
+    .unwrap();
+
+    let (status, body) = app.request(Method::POST, "/api/ai", Some(request)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["response"], "Désolé, je ne peux pas aider à ça.");
+    assert_eq!(body["refused"], true);
+    assert_eq!(body["usage"]["completion_tokens"], 7);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn replay_reads_anthropic_message_stream() {
+    let dir = std::env::temp_dir().join(format!("carlgpt-cassettes-{}", Uuid::new_v4()));
+    let cassette_dir = dir.to_string_lossy().into_owned();
+    let app = TestApp::spawn_with(|config| {
+        config.mock_provider = false;
+        config.cassette_mode = Some(CassetteMode::Replay);
+        config.cassette_dir = cassette_dir;
+        config.provider_debug_log = true;
+        config.admin_token = Some("secret".to_string());
+    })
+    .await;
+    let session_id = app.create_session().await;
+    let uri = format!("/api/chat/sessions/{session_id}/messages");
+    let request = json!({ "content": "Tu m'entends ?", "model": "claude-sonnet-4-5" });
+
+    let (status, body) = app.request(Method::POST, &uri, Some(request.clone())).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    let path = body
+        .as_str()
+        .unwrap()
+        .split_once('(')
+        .and_then(|(_, rest)| rest.split_once(')'))
+        .map(|(path, _)| path.to_string())
+        .unwrap();
+    assert!(path.contains("anthropic-"), "{path}");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::copy(
+        format!(
+            "{}/tests/fixtures/sse/anthropic_messages.sse",
+            env!("CARGO_MANIFEST_DIR")
+        ),
+        &path,
+    )
+    .unwrap();
+
+    let (status, session) = app.request(Method::POST, &uri, Some(request)).await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    let answer = &session["messages"][1];
+    assert_eq!(answer["content"], "Ça marche !");
+    assert_eq!(answer["model"], "claude-sonnet-4-5");
+    // Prompt annoncé par `message_start`, tokens générés par `message_delta`
+    assert_eq!(answer["usage"]["prompt_tokens"], 25);
+    assert_eq!(answer["usage"]["completion_tokens"], 6);
+
+    // Points de cache : fin du prompt système et dernier message de la conversation.
+    let logs_uri = format!(
+        "/api/admin/provider-logs?message_id={}",
+        answer["id"].as_str().unwrap()
+    );
+    for _ in 0..50 {
+        let (_, logs) = app
+            .request_with_headers(Method::GET, &logs_uri, None, &[("x-admin-token", "secret")])
+            .await;
+        let Some(log) = logs.as_array().unwrap().first() else {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            continue;
+        };
+        let ephemeral = json!({ "type": "ephemeral" });
+        let system = log["request"]["system"].as_array().unwrap();
+        assert_eq!(system.last().unwrap()["cache_control"], ephemeral);
+        assert!(
+            system[..system.len() - 1]
+                .iter()
+                .all(|block| block.get("cache_control").is_none())
+        );
+        let messages = log["request"]["messages"].as_array().unwrap();
+        let last = messages.last().unwrap();
+        assert_eq!(last["role"], "user");
+        assert_eq!(last["content"][0]["text"], "Tu m'entends ?");
+        assert_eq!(last["content"][0]["cache_control"], ephemeral);
+        std::fs::remove_dir_all(&dir).unwrap();
+        return;
+    }
+    panic!("l'échange n'a pas été journalisé");
+}
+
+#[tokio::test]
+async fn replay_reads_gemini_stream() {
+    let dir = std::env::temp_dir().join(format!("carlgpt-cassettes-{}", Uuid::new_v4()));
+    let cassette_dir = dir.to_string_lossy().into_owned();
+    let app = TestApp::spawn_with(|config| {
+        config.mock_provider = false;
+        config.cassette_mode = Some(CassetteMode::Replay);
+        config.cassette_dir = cassette_dir;
+    })
+    .await;
+    let session_id = app.create_session().await;
+    let uri = format!("/api/chat/sessions/{session_id}/messages");
+    let request = json!({ "content": "Tu m'entends ?", "model": "gemini-2.5-flash" });
+
+    let (status, body) = app.request(Method::POST, &uri, Some(request.clone())).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    let path = body
+        .as_str()
+        .unwrap()
+        .split_once('(')
+        .and_then(|(_, rest)| rest.split_once(')'))
+        .map(|(path, _)| path.to_string())
+        .unwrap();
+    assert!(path.contains("gemini-"), "{path}");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::copy(
+        format!(
+            "{}/tests/fixtures/sse/gemini_stream.sse",
+            env!("CARGO_MANIFEST_DIR")
+        ),
+        &path,
+    )
+    .unwrap();
+
+    let (status, session) = app.request(Method::POST, &uri, Some(request)).await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    let answer = &session["messages"][1];
+    // La réflexion du modèle n'est pas reprise dans la réponse, mais facturée avec elle.
+    assert_eq!(answer["content"], "Ça marche ✨");
+    assert_eq!(answer["model"], "gemini-2.5-flash");
+    assert_eq!(answer["usage"]["prompt_tokens"], 9);
+    assert_eq!(answer["usage"]["completion_tokens"], 16);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn local_only_mode_reaches_ollama() {
+    let dir = std::env::temp_dir().join(format!("carlgpt-cassettes-{}", Uuid::new_v4()));
+    let cassette_dir = dir.to_string_lossy().into_owned();
+    let app = TestApp::spawn_with(|config| {
+        config.mock_provider = false;
+        config.local_only = true;
+        config.ollama_base_url = Some("http://ollama.local:11434".to_string());
+        config.cassette_mode = Some(CassetteMode::Replay);
+        config.cassette_dir = cassette_dir;
+    })
+    .await;
+
+    let (_, body) = app.request(Method::GET, "/api/models", None).await;
+    let available: Vec<_> = body["models"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|model| model["available"] == true)
+        .map(|model| model["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(available, ["ollama"]);
+
+    let session_id = app.create_session().await;
+    let uri = format!("/api/chat/sessions/{session_id}/messages");
+    let request = json!({ "content": "Tu m'entends ?", "model": "ollama" });
+    let (status, body) = app.request(Method::POST, &uri, Some(request.clone())).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    let path = body
+        .as_str()
+        .unwrap()
+        .split_once('(')
+        .and_then(|(_, rest)| rest.split_once(')'))
+        .map(|(path, _)| path.to_string())
+        .unwrap();
+    assert!(path.contains("ollama-"), "{path}");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::copy(
+        format!(
+            "{}/tests/fixtures/sse/ollama_chat.ndjson",
+            env!("CARGO_MANIFEST_DIR")
+        ),
+        &path,
+    )
+    .unwrap();
+
+    let (status, session) = app.request(Method::POST, &uri, Some(request)).await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    let answer = &session["messages"][1];
+    assert_eq!(answer["content"], "Ça marche 🦙");
+    assert_eq!(answer["model"], "ollama");
+    assert_eq!(answer["usage"]["prompt_tokens"], 31);
+    assert_eq!(answer["usage"]["completion_tokens"], 5);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,1163 @@
+mod common;
+
+use std::time::Duration;
+
+use axum::http::{Method, StatusCode};
+use backend::{access::ModelPolicy, mock::MockReply, providers::AiModelChoice};
+use http_body_util::BodyExt;
+use serde_json::{Value, json};
+
+use common::{TestApp, events_of, streamed_text};
+
+#[tokio::test]
+async fn session_lifecycle() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+
+    let (status, sessions) = app.request(Method::GET, "/api/chat/sessions", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(
+        sessions
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|session| session["id"] == session_id.to_string())
+    );
+
+    let (status, draft) = app
+        .request(
+            Method::PUT,
+            &format!("/api/chat/sessions/{session_id}/draft"),
+            Some(json!({ "content": "brouillon" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(draft["content"], "brouillon");
+
+    let archive = format!("/api/chat/sessions/{session_id}/archive");
+    let (status, _) = app.request(Method::POST, &archive, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = app.request(Method::POST, &archive, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let session = format!("/api/chat/sessions/{session_id}");
+    let (status, _) = app.request(Method::DELETE, &session, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = app.request(Method::DELETE, &session, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn message_is_answered_and_titled() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    app.provider()
+        .push_reply(MockReply::Text("Bonjour, que puis-je faire ?".to_string()));
+
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({ "content": "Salut, comment vas-tu aujourd'hui ?" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(session["title"], "Salut, comment vas-tu aujourd'hui ?");
+    assert_eq!(session["icon"], "💬");
+    let messages = session["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0]["role"], "user");
+    assert_eq!(messages[1]["content"], "Bonjour, que puis-je faire ?");
+    assert_eq!(messages[1]["status"], "complete");
+    assert!(messages[1]["usage"]["completion_tokens"].as_i64().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn streaming_emits_session_tokens_and_final() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    app.provider().push_reply(MockReply::Text(
+        "Une réponse en plusieurs morceaux".to_string(),
+    ));
+
+    let events = app
+        .stream(
+            &format!("/api/chat/sessions/{session_id}/messages/stream"),
+            json!({ "content": "Explique-moi le streaming" }),
+        )
+        .await;
+
+    assert_eq!(events[0]["type"], "session");
+    let message_id = events[0]["messageId"].clone();
+    assert!(events_of(&events, "token").len() > 1);
+    assert_eq!(streamed_text(&events), "Une réponse en plusieurs morceaux");
+
+    // Le titre est résumé en parallèle : son évènement peut arriver après `final`.
+    let finals = events_of(&events, "final");
+    assert_eq!(finals.len(), 1);
+    assert_eq!(finals[0]["messageId"], message_id);
+    let answer = &finals[0]["session"]["messages"][1];
+    assert_eq!(answer["content"], "Une réponse en plusieurs morceaux");
+    assert_eq!(answer["status"], "complete");
+    assert_eq!(answer["finish_reason"], "stop");
+    assert_eq!(finals[0]["finishReason"], "stop");
+
+    // Numérotation continue, titre compris (envoyé par une autre tâche).
+    let seqs: Vec<u64> = events
+        .iter()
+        .map(|event| event["seq"].as_u64().unwrap())
+        .collect();
+    assert_eq!(seqs, (1..=events.len() as u64).collect::<Vec<_>>());
+
+    // `usage` suit la réponse enregistrée.
+    let usages = events_of(&events, "usage");
+    assert_eq!(usages.len(), 1);
+    assert!(usages[0]["seq"].as_u64() > finals[0]["seq"].as_u64());
+    assert_eq!(usages[0]["model"], "llama-3.1-8b-instant");
+    assert_eq!(usages[0]["completionTokens"], 5);
+    assert_eq!(usages[0]["finishReason"], "stop");
+    assert!(usages[0]["costUsd"].as_f64().unwrap() > 0.0);
+    assert!(usages[0]["latencyMs"].is_u64());
+
+    let titles = events_of(&events, "title");
+    assert_eq!(titles.len(), 1);
+    assert_eq!(titles[0]["title"], "Explique-moi le streaming");
+    assert_eq!(titles[0]["icon"], "💬");
+    let (_, sessions) = app.request(Method::GET, "/api/chat/sessions", None).await;
+    assert_eq!(sessions[0]["title"], "Explique-moi le streaming");
+}
+
+#[tokio::test]
+async fn latency_breakdown_is_reported_and_recorded() {
+    let app = TestApp::spawn_with(|config| {
+        config.admin_token = Some("secret".to_string());
+    })
+    .await;
+    let session_id = app.create_session().await;
+    app.provider()
+        .push_reply(MockReply::Text("Réponse chronométrée".to_string()));
+
+    let events = app
+        .stream(
+            &format!("/api/chat/sessions/{session_id}/messages/stream"),
+            json!({ "content": "Combien de temps ?" }),
+        )
+        .await;
+    let message_id = events[0]["messageId"].clone();
+    let usage = &events_of(&events, "usage")[0];
+    let first_token = usage["firstTokenMs"].as_u64().unwrap();
+    let generation = usage["generationMs"].as_u64().unwrap();
+    assert!(first_token <= generation);
+    assert!(generation <= usage["latencyMs"].as_u64().unwrap());
+    assert!(usage["dbMs"].is_u64());
+    assert_eq!(usage["attachmentsMs"], 0);
+
+    let (status, _) = app.request(Method::GET, "/api/admin/latency", None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, report) = app
+        .request_with_headers(
+            Method::GET,
+            "/api/admin/latency?model=llama-3.1-8b-instant",
+            None,
+            &[("x-admin-token", "secret")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{report}");
+    assert_eq!(report["models"][0]["model"], "llama-3.1-8b-instant");
+    assert_eq!(report["models"][0]["answers"], 1);
+    assert!(report["models"][0]["generation_ms_p95"].is_f64());
+    let slowest = &report["slowest"][0];
+    assert_eq!(slowest["message_id"], message_id);
+    assert_eq!(slowest["first_token_ms"], first_token);
+    assert_eq!(slowest["generation_ms"], generation);
+}
+
+#[tokio::test]
+async fn truncated_answers_are_marked() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    app.provider()
+        .push_reply(MockReply::Truncated("Une réponse trop".to_string()));
+
+    let events = app
+        .stream(
+            &format!("/api/chat/sessions/{session_id}/messages/stream"),
+            json!({ "content": "Écris un roman" }),
+        )
+        .await;
+    let last = events_of(&events, "final")[0];
+    assert_eq!(last["finishReason"], "length");
+    assert_eq!(last["session"]["messages"][1]["finish_reason"], "length");
+
+    // La raison de fin est enregistrée avec la réponse.
+    let (_, sessions) = app.request(Method::GET, "/api/chat/sessions", None).await;
+    assert_eq!(sessions[0]["messages"][1]["finish_reason"], "length");
+}
+
+#[tokio::test]
+async fn refusals_are_signalled_and_stored() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    app.provider().push_reply(MockReply::Refusal(
+        "Je ne peux pas vous aider à contourner cette protection.".to_string(),
+    ));
+
+    let events = app
+        .stream(
+            &format!("/api/chat/sessions/{session_id}/messages/stream"),
+            json!({ "content": "Comment craquer ce logiciel ?" }),
+        )
+        .await;
+    assert_eq!(streamed_text(&events), "");
+    let refusal = events_of(&events, "refusal")[0];
+    assert_eq!(refusal["reason"], "refusal");
+    assert_eq!(
+        refusal["message"],
+        "Je ne peux pas vous aider à contourner cette protection."
+    );
+    let answer = &events_of(&events, "final")[0]["session"]["messages"][1];
+    assert_eq!(answer["refused"], true);
+    assert_eq!(
+        answer["content"],
+        "Je ne peux pas vous aider à contourner cette protection."
+    );
+
+    // Le drapeau est enregistré avec la réponse ; les autres messages ne l'ont pas.
+    let (_, sessions) = app.request(Method::GET, "/api/chat/sessions", None).await;
+    assert_eq!(sessions[0]["messages"][0]["refused"], false);
+    assert_eq!(sessions[0]["messages"][1]["refused"], true);
+}
+
+#[tokio::test]
+async fn draft_is_streamed_then_revised_by_the_requested_model() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    app.provider()
+        .push_reply(MockReply::Text("Brouillon rapide".to_string()));
+    app.provider().push_reply(MockReply::Delayed {
+        delay: Duration::from_millis(200),
+        text: "Réponse détaillée et vérifiée".to_string(),
+    });
+
+    let events = app
+        .stream(
+            &format!("/api/chat/sessions/{session_id}/messages/stream"),
+            json!({ "content": "Explique les closures", "model": "gpt-5-mini", "draft": true }),
+        )
+        .await;
+    let models: Vec<_> = app
+        .provider()
+        .requests()
+        .iter()
+        .map(|request| request.model)
+        .collect();
+    assert_eq!(
+        models,
+        [AiModelChoice::GroqLlama31, AiModelChoice::OpenAIGpt5Mini]
+    );
+    assert_eq!(streamed_text(&events), "Brouillon rapide");
+    let revision = events_of(&events, "revision")[0];
+    assert_eq!(revision["content"], "Réponse détaillée et vérifiée");
+    assert_eq!(revision["draftModel"], "llama-3.1-8b-instant");
+    let kinds: Vec<_> = events
+        .iter()
+        .map(|event| event["type"].as_str().unwrap())
+        .filter(|kind| *kind != "title")
+        .collect();
+    assert_eq!(kinds[kinds.len() - 3..], ["revision", "final", "usage"]);
+
+    // Seule la réponse du modèle demandé est enregistrée.
+    let answer = &events_of(&events, "final")[0]["session"]["messages"][1];
+    assert_eq!(answer["content"], "Réponse détaillée et vérifiée");
+    assert_eq!(answer["model"], "gpt-5-mini");
+    assert_eq!(events_of(&events, "usage")[0]["model"], "gpt-5-mini");
+}
+
+#[tokio::test]
+async fn draft_is_skipped_for_the_fast_model_and_refused_without_streaming() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+
+    let events = app
+        .stream(
+            &format!("/api/chat/sessions/{session_id}/messages/stream"),
+            json!({ "content": "Bonjour", "model": "llama-3.1-8b-instant", "draft": true }),
+        )
+        .await;
+    assert_eq!(app.provider().requests().len(), 1);
+    assert!(events_of(&events, "revision").is_empty());
+    assert_eq!(streamed_text(&events), "Réponse simulée : Bonjour");
+
+    let (status, body) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({ "content": "Bonjour", "draft": true })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+}
+
+#[tokio::test]
+async fn draft_model_is_configurable_and_skipped_when_not_allowed() {
+    let app =
+        TestApp::spawn_with(|config| config.draft_model = AiModelChoice::OpenAIGpt5Nano).await;
+    let session_id = app.create_session().await;
+    let events = app
+        .stream(
+            &format!("/api/chat/sessions/{session_id}/messages/stream"),
+            json!({ "content": "Bonjour", "model": "gpt-5-mini", "draft": true }),
+        )
+        .await;
+    let models: Vec<_> = app
+        .provider()
+        .requests()
+        .iter()
+        .map(|request| request.model)
+        .collect();
+    assert_eq!(
+        models,
+        [AiModelChoice::OpenAIGpt5Nano, AiModelChoice::OpenAIGpt5Mini]
+    );
+    assert_eq!(
+        events_of(&events, "revision")[0]["draftModel"],
+        "gpt-5-nano"
+    );
+
+    // Modèle rapide fermé à l'appelant : la réponse arrive sans brouillon.
+    let app = TestApp::spawn_with(|config| {
+        config.models = ModelPolicy {
+            default: AiModelChoice::OpenAIGpt5Mini,
+            allowed: Some(vec![AiModelChoice::OpenAIGpt5Mini]),
+        };
+    })
+    .await;
+    let session_id = app.create_session().await;
+    let events = app
+        .stream(
+            &format!("/api/chat/sessions/{session_id}/messages/stream"),
+            json!({ "content": "Bonjour", "model": "gpt-5-mini", "draft": true }),
+        )
+        .await;
+    assert_eq!(app.provider().requests().len(), 1);
+    assert!(events_of(&events, "revision").is_empty());
+    assert_eq!(streamed_text(&events), "Réponse simulée : Bonjour");
+}
+
+#[tokio::test]
+async fn draft_runs_without_the_tools_of_the_answer() {
+    let app =
+        TestApp::spawn_with(|config| config.draft_model = AiModelChoice::OpenAIGpt5Nano).await;
+    let session_id = app.create_session().await;
+    let events = app
+        .stream(
+            &format!("/api/chat/sessions/{session_id}/messages/stream"),
+            json!({
+                "content": "Note le dentiste mardi",
+                "model": "gpt-5-mini",
+                "draft": true,
+                "completion_params": { "calendar": true, "code_edit": true }
+            }),
+        )
+        .await;
+    assert_eq!(events_of(&events, "revision").len(), 1);
+
+    let requests = app.provider().requests();
+    let params = |model| {
+        requests
+            .iter()
+            .find(|request| request.model == model)
+            .and_then(|request| request.params.clone())
+            .unwrap()
+    };
+    let draft = params(AiModelChoice::OpenAIGpt5Nano);
+    assert_eq!(
+        (
+            draft.calendar,
+            draft.screenshot,
+            draft.code_edit,
+            draft.plugins
+        ),
+        (None, None, None, None)
+    );
+    let answer = params(AiModelChoice::OpenAIGpt5Mini);
+    assert_eq!(
+        (answer.calendar, answer.code_edit),
+        (Some(true), Some(true))
+    );
+}
+
+#[tokio::test]
+async fn filtered_answers_are_reported_as_refused() {
+    let app = TestApp::spawn().await;
+    let request = json!({ "messages": [{ "role": "user", "content": "Décris la scène" }] });
+
+    app.provider()
+        .push_reply(MockReply::Filtered("La scène commence".to_string()));
+    let events = app.stream("/api/ai/stream", request.clone()).await;
+    let refusal = events_of(&events, "refusal")[0];
+    assert_eq!(refusal["reason"], "content_filter");
+    assert_eq!(refusal["message"], Value::Null);
+    let last = events_of(&events, "final")[0];
+    assert_eq!(last["refused"], true);
+    assert_eq!(last["finishReason"], "content_filter");
+
+    app.provider()
+        .push_reply(MockReply::Filtered("La scène commence".to_string()));
+    let (status, body) = app.request(Method::POST, "/api/ai", Some(request)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["refused"], true);
+    assert_eq!(body["response"], "La scène commence");
+
+    app.provider()
+        .push_reply(MockReply::Text("Une réponse normale".to_string()));
+    let events = app
+        .stream(
+            "/api/ai/stream",
+            json!({ "messages": [{ "role": "user", "content": "Bonjour" }] }),
+        )
+        .await;
+    assert!(events_of(&events, "refusal").is_empty());
+    assert_eq!(events_of(&events, "final")[0]["refused"], false);
+}
+
+#[tokio::test]
+async fn candidates_are_stored_and_one_can_be_selected() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    for reply in ["Première piste", "Deuxième piste", "Troisième piste"] {
+        app.provider()
+            .push_reply(MockReply::Text(reply.to_string()));
+    }
+
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({ "content": "Propose un titre", "candidates": 3 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    assert_eq!(app.provider().requests().len(), 3);
+    let answer = &session["messages"][1];
+    let candidates = answer["candidates"].as_array().unwrap();
+    let mut contents: Vec<_> = candidates
+        .iter()
+        .map(|candidate| candidate["content"].as_str().unwrap())
+        .collect();
+    contents.sort();
+    assert_eq!(
+        contents,
+        ["Deuxième piste", "Première piste", "Troisième piste"]
+    );
+    assert_eq!(candidates[0]["selected"], true);
+    assert_eq!(answer["content"], candidates[0]["content"]);
+    // La consommation du message compte toutes les candidates.
+    assert_eq!(answer["usage"]["completion_tokens"], 6);
+    assert!(session["messages"][0].get("candidates").is_none());
+
+    let message_id = answer["id"].as_str().unwrap();
+    let uri = format!("/api/chat/sessions/{session_id}/messages/{message_id}/candidate");
+    let (status, session) = app
+        .request(
+            Method::PUT,
+            &uri,
+            Some(json!({ "candidate_id": candidates[2]["id"] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    let answer = &session["messages"][1];
+    assert_eq!(answer["content"], candidates[2]["content"]);
+    let selected: Vec<_> = answer["candidates"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|candidate| candidate["selected"].as_bool().unwrap())
+        .collect();
+    assert_eq!(selected, [false, false, true]);
+
+    let (status, _) = app
+        .request(
+            Method::PUT,
+            &uri,
+            Some(json!({ "candidate_id": uuid::Uuid::new_v4() })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Une régénération remplace la réponse et ses candidates.
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/regenerate"),
+            Some(json!({ "message_id": message_id })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    assert!(session["messages"][1].get("candidates").is_none());
+}
+
+#[tokio::test]
+async fn failed_candidates_are_kept_with_their_error() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    app.provider()
+        .push_reply(MockReply::Error("quota dépassé".to_string()));
+    app.provider()
+        .push_reply(MockReply::Text("Seule piste".to_string()));
+
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({ "content": "Propose un titre", "candidates": 2 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    let answer = &session["messages"][1];
+    assert_eq!(answer["content"], "Seule piste");
+    assert_eq!(answer["status"], "complete");
+    let candidates = answer["candidates"].as_array().unwrap();
+    assert_eq!(candidates.len(), 2);
+    assert_eq!(candidates[0]["selected"], true);
+    assert!(candidates[0].get("error").is_none());
+    assert_eq!(candidates[1]["status"], "failed");
+    assert_eq!(candidates[1]["error"], "quota dépassé");
+    assert_eq!(candidates[1]["selected"], false);
+
+    // Une candidate en échec ne peut pas devenir le contenu du message.
+    let message_id = answer["id"].as_str().unwrap();
+    let (status, _) = app
+        .request(
+            Method::PUT,
+            &format!("/api/chat/sessions/{session_id}/messages/{message_id}/candidate"),
+            Some(json!({ "candidate_id": candidates[1]["id"] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Sans aucune réponse, l'erreur rapporte la cause de chaque candidate.
+    for error in ["quota dépassé", "provider indisponible"] {
+        app.provider()
+            .push_reply(MockReply::Error(error.to_string()));
+    }
+    let (status, body) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/regenerate"),
+            Some(json!({ "message_id": message_id, "candidates": 2 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    let body = body.as_str().unwrap();
+    assert!(
+        body.starts_with("Aucune des 2 réponses candidates"),
+        "{body}"
+    );
+    assert!(body.contains("quota dépassé") && body.contains("provider indisponible"));
+}
+
+#[tokio::test]
+async fn candidates_are_limited_to_non_streaming_endpoints() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages/stream"),
+            Some(json!({ "content": "Bonjour", "candidates": 2 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, body) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({ "content": "Bonjour", "candidates": 5 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.as_str().unwrap().contains("entre 1 et 4"), "{body}");
+    assert!(app.provider().requests().is_empty());
+}
+
+#[tokio::test]
+async fn interrupted_stream_can_be_continued() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    app.provider()
+        .push_reply(MockReply::Interrupted("Début de ".to_string()));
+
+    let events = app
+        .stream(
+            &format!("/api/chat/sessions/{session_id}/messages/stream"),
+            json!({ "content": "Raconte une histoire" }),
+        )
+        .await;
+    let error = events_of(&events, "error")[0];
+    assert_eq!(error["partial"], true);
+    let usage = events.last().unwrap();
+    assert_eq!(usage["type"], "usage");
+    assert_eq!(usage["finishReason"], Value::Null);
+    let message_id = error["resume"]["messageId"].clone();
+
+    app.provider()
+        .push_reply(MockReply::Text("la suite".to_string()));
+    let events = app
+        .stream(
+            &format!("/api/chat/sessions/{session_id}/continue/stream"),
+            json!({ "message_id": message_id }),
+        )
+        .await;
+    let last = events_of(&events, "final")[0];
+    let answer = &last["session"]["messages"][1];
+    assert_eq!(answer["content"], "Début de la suite");
+    assert_eq!(answer["status"], "complete");
+}
+
+#[tokio::test]
+async fn regeneration_replaces_the_last_answer() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    app.provider()
+        .push_reply(MockReply::Text("Première version".to_string()));
+    let (_, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({ "content": "Question" })),
+        )
+        .await;
+    let message_id = session["messages"][1]["id"].clone();
+
+    app.provider()
+        .push_reply(MockReply::Text("Deuxième version".to_string()));
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/regenerate"),
+            Some(json!({ "message_id": message_id })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(session["messages"][1]["content"], "Deuxième version");
+
+    app.provider()
+        .push_reply(MockReply::Text("Troisième version".to_string()));
+    let events = app
+        .stream(
+            &format!("/api/chat/sessions/{session_id}/regenerate/stream"),
+            json!({ "message_id": session["messages"][1]["id"] }),
+        )
+        .await;
+    assert_eq!(streamed_text(&events), "Troisième version");
+    let last = events_of(&events, "final")[0];
+    let messages = last["session"]["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[1]["content"], "Troisième version");
+
+    // Le dernier appel au provider ne contient pas l'ancienne réponse.
+    let request = app.provider().requests().pop().unwrap();
+    assert!(
+        request
+            .messages
+            .iter()
+            .all(|message| message.role != "assistant")
+    );
+}
+
+#[tokio::test]
+async fn provider_error_is_reported() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    app.provider()
+        .push_reply(MockReply::Error("provider indisponible".to_string()));
+
+    let (status, body) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({ "content": "Bonjour" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(body, "provider indisponible");
+}
+
+#[tokio::test]
+async fn provider_error_bodies_are_mapped() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    let cases = [
+        (
+            400,
+            json!({ "error": {
+                "message": "This model's maximum context length is 8192 tokens.",
+                "type": "invalid_request_error",
+                "code": "context_length_exceeded"
+            }}),
+            StatusCode::BAD_REQUEST,
+            "trop longue",
+        ),
+        (
+            401,
+            json!({ "error": { "message": "Incorrect API key provided", "code": "invalid_api_key" }}),
+            StatusCode::SERVICE_UNAVAILABLE,
+            "clé API refusée",
+        ),
+        (
+            429,
+            json!({ "error": { "message": "Rate limit reached", "code": "rate_limit_exceeded" }}),
+            StatusCode::TOO_MANY_REQUESTS,
+            "trop de requêtes",
+        ),
+        (
+            400,
+            json!({ "error": { "message": "Rejected", "code": "content_policy_violation" }}),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "filtre de contenu",
+        ),
+        (
+            404,
+            json!({ "error": { "message": "The model does not exist", "code": "model_not_found" }}),
+            StatusCode::BAD_GATEWAY,
+            "The model does not exist",
+        ),
+    ];
+
+    for (upstream_status, upstream_body, expected_status, expected_message) in cases {
+        app.provider().push_reply(MockReply::ProviderError {
+            status: upstream_status,
+            body: upstream_body.to_string(),
+        });
+        let (status, body) = app
+            .request(
+                Method::POST,
+                &format!("/api/chat/sessions/{session_id}/messages"),
+                Some(json!({ "content": "Bonjour" })),
+            )
+            .await;
+        assert_eq!(status, expected_status, "HTTP {upstream_status}: {body}");
+        assert!(
+            body.as_str().unwrap().contains(expected_message),
+            "HTTP {upstream_status}: {body}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn streamed_tokens_can_be_coalesced() {
+    let app = TestApp::spawn_with(|config| config.stream_coalesce_chars = 15).await;
+    let session_id = app.create_session().await;
+    app.provider().push_reply(MockReply::Text(
+        "Une réponse en plusieurs morceaux".to_string(),
+    ));
+
+    let events = app
+        .stream(
+            &format!("/api/chat/sessions/{session_id}/messages/stream"),
+            json!({ "content": "Explique-moi le streaming" }),
+        )
+        .await;
+
+    let tokens: Vec<&str> = events_of(&events, "token")
+        .iter()
+        .map(|event| event["content"].as_str().unwrap())
+        .collect();
+    assert_eq!(tokens, ["Une réponse en ", "plusieurs morceaux"]);
+}
+
+#[tokio::test]
+async fn long_answers_reach_the_client_in_full() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    let reply: Vec<String> = (0..2000).map(|i| format!("mot{i}")).collect();
+    let reply = reply.join(" ");
+    app.provider().push_reply(MockReply::Text(reply.clone()));
+
+    let events = app
+        .stream(
+            &format!("/api/chat/sessions/{session_id}/messages/stream"),
+            json!({ "content": "Écris beaucoup" }),
+        )
+        .await;
+
+    // Les tokens peuvent être fusionnés si le client prend du retard, jamais perdus.
+    assert_eq!(streamed_text(&events), reply);
+    let finals = events_of(&events, "final");
+    assert_eq!(finals[0]["session"]["messages"][1]["content"], reply);
+}
+
+#[tokio::test]
+async fn client_disconnect_keeps_the_partial_answer() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    app.provider()
+        .push_reply(MockReply::Stalled("Début de réponse ".to_string()));
+
+    let mut body = app
+        .open_stream(
+            &format!("/api/chat/sessions/{session_id}/messages/stream"),
+            json!({ "content": "Raconte une longue histoire" }),
+        )
+        .await;
+    let mut received = String::new();
+    while !received.contains("réponse ") {
+        let frame = body.frame().await.unwrap().unwrap();
+        if let Some(data) = frame.data_ref() {
+            received.push_str(&String::from_utf8_lossy(data));
+        }
+    }
+    // Le provider ne répondra plus : seule la déconnexion peut terminer la génération.
+    drop(body);
+
+    for _ in 0..50 {
+        let (_, sessions) = app.request(Method::GET, "/api/chat/sessions", None).await;
+        let answer = &sessions[0]["messages"][1];
+        if answer["status"] == "incomplete" {
+            assert_eq!(answer["content"], "Début de réponse ");
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("la génération n'a pas été interrompue après la déconnexion du client");
+}
+
+#[tokio::test]
+async fn context_can_be_restricted_to_selected_messages() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    let uri = format!("/api/chat/sessions/{session_id}/messages");
+    let mut session = Value::Null;
+    for content in ["Voici un long journal d'erreurs", "Et la météo ?"] {
+        let (status, body) = app
+            .request(
+                Method::POST,
+                &uri,
+                Some(json!({ "content": content, "model": "gpt-5-mini" })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        session = body;
+    }
+    let kept: Vec<Value> = session["messages"].as_array().unwrap()[2..]
+        .iter()
+        .map(|message| message["id"].clone())
+        .collect();
+
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &uri,
+            Some(json!({
+                "content": "Et demain ?",
+                "model": "gpt-5-mini",
+                "context_message_ids": kept
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(session["messages"].as_array().unwrap().len(), 6);
+    let request = app.provider().requests().pop().unwrap();
+    let sent: Vec<&str> = request
+        .messages
+        .iter()
+        .filter(|message| message.role != "system")
+        .map(|message| message.content.as_str())
+        .collect();
+    assert_eq!(
+        sent,
+        [
+            "Et la météo ?",
+            "Réponse simulée : Et la météo ?",
+            "Et demain ?"
+        ]
+    );
+    // La réponse précédente d'OpenAI porte tout l'historique : elle n'est pas reprise.
+    assert_eq!(
+        request
+            .params
+            .and_then(|params| params.previous_response_id),
+        None
+    );
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            &uri,
+            Some(json!({
+                "content": "Encore ?",
+                "context_message_ids": [uuid::Uuid::new_v4()]
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn excluded_messages_stay_visible_but_leave_the_context() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    let uri = format!("/api/chat/sessions/{session_id}/messages");
+    let (_, session) = app
+        .request(
+            Method::POST,
+            &uri,
+            Some(json!({ "content": "Voici un long journal d'erreurs" })),
+        )
+        .await;
+    let log_id = session["messages"][0]["id"].as_str().unwrap().to_string();
+    assert_eq!(session["messages"][0]["excluded_from_context"], false);
+
+    let context = format!("/api/chat/sessions/{session_id}/messages/{log_id}/context");
+    let (status, _) = app
+        .request(
+            Method::PUT,
+            &context,
+            Some(json!({ "excluded_from_context": true })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &uri,
+            Some(json!({ "content": "Et ensuite ?" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(session["messages"].as_array().unwrap().len(), 4);
+    assert_eq!(session["messages"][0]["excluded_from_context"], true);
+    let request = app.provider().requests().pop().unwrap();
+    assert!(
+        request
+            .messages
+            .iter()
+            .all(|message| message.content != "Voici un long journal d'erreurs")
+    );
+
+    let unknown = format!(
+        "/api/chat/sessions/{session_id}/messages/{}/context",
+        uuid::Uuid::new_v4()
+    );
+    let (status, _) = app
+        .request(
+            Method::PUT,
+            &unknown,
+            Some(json!({ "excluded_from_context": false })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn replies_quote_the_referenced_message() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    let uri = format!("/api/chat/sessions/{session_id}/messages");
+    app.provider()
+        .push_reply(MockReply::Text("Paris\net Lyon".to_string()));
+    let (_, session) = app
+        .request(
+            Method::POST,
+            &uri,
+            Some(json!({ "content": "Deux grandes villes ?" })),
+        )
+        .await;
+    let answer_id = session["messages"][1]["id"].clone();
+
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &uri,
+            Some(json!({ "content": "Pourquoi Lyon ?", "reply_to_message_id": answer_id })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(session["messages"][2]["content"], "Pourquoi Lyon ?");
+    assert_eq!(session["messages"][2]["reply_to_message_id"], answer_id);
+    let quoted = "> Paris\n> et Lyon\n\nPourquoi Lyon ?";
+    let request = app.provider().requests().pop().unwrap();
+    assert_eq!(request.messages.last().unwrap().content, quoted);
+
+    // La citation est reprise dans l'historique des tours suivants.
+    app.request(Method::POST, &uri, Some(json!({ "content": "Merci" })))
+        .await;
+    let request = app.provider().requests().pop().unwrap();
+    assert!(
+        request
+            .messages
+            .iter()
+            .any(|message| message.content == quoted)
+    );
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            &uri,
+            Some(json!({ "content": "Et ?", "reply_to_message_id": uuid::Uuid::new_v4() })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn messages_can_be_sent_in_a_batch() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    let uri = format!("/api/chat/sessions/{session_id}/messages");
+
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &uri,
+            Some(json!({
+                "content": "Et 3 + 3 ?",
+                "messages": [
+                    { "role": "system", "content": "Réponds par un nombre." },
+                    { "role": "user", "content": "2 + 2 ?" },
+                    { "role": "assistant", "content": "4" }
+                ]
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    let stored: Vec<(&str, &str)> = session["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|message| {
+            (
+                message["role"].as_str().unwrap(),
+                message["content"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        stored,
+        [
+            ("system", "Réponds par un nombre."),
+            ("user", "2 + 2 ?"),
+            ("assistant", "4"),
+            ("user", "Et 3 + 3 ?"),
+            ("assistant", "Réponse simulée : Et 3 + 3 ?"),
+        ]
+    );
+    let request = app.provider().requests().pop().unwrap();
+    let sent: Vec<&str> = request.messages[1..]
+        .iter()
+        .map(|message| message.content.as_str())
+        .collect();
+    assert_eq!(
+        sent,
+        ["Réponds par un nombre.", "2 + 2 ?", "4", "Et 3 + 3 ?"]
+    );
+
+    // Un lot invalide n'enregistre rien.
+    for messages in [
+        json!([{ "role": "tool", "content": "x" }]),
+        json!([{ "role": "user", "content": "  " }]),
+    ] {
+        let (status, _) = app
+            .request(
+                Method::POST,
+                &uri,
+                Some(json!({ "content": "Encore ?", "messages": messages })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+    let (_, session) = app
+        .request(Method::POST, &uri, Some(json!({ "content": "Encore ?" })))
+        .await;
+    assert_eq!(session["messages"].as_array().unwrap().len(), 7);
+}
+
+#[tokio::test]
+async fn answers_start_with_the_assistant_prefix() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    let uri = format!("/api/chat/sessions/{session_id}/messages");
+    let params = json!({ "assistant_prefix": "```json" });
+
+    // Groq poursuit le message `assistant` prérempli.
+    app.provider()
+        .push_reply(MockReply::Text("\n{\"ok\": true}\n```".to_string()));
+    let events = app
+        .stream(
+            &format!("{uri}/stream"),
+            json!({
+                "content": "Statut ?",
+                "model": "llama-3.1-8b-instant",
+                "completion_params": params
+            }),
+        )
+        .await;
+    assert_eq!(streamed_text(&events), "```json\n{\"ok\": true}\n```");
+    let request = app.provider().requests().pop().unwrap();
+    let last = request.messages.last().unwrap();
+    assert_eq!(
+        (last.role.as_str(), last.content.as_str()),
+        ("assistant", "```json")
+    );
+
+    // OpenAI reçoit une consigne : le préfixe réécrit par le modèle n'est pas doublé.
+    app.provider()
+        .push_reply(MockReply::Text("```json\n{\"ok\": false}\n```".to_string()));
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &uri,
+            Some(json!({
+                "content": "Et maintenant ?",
+                "model": "gpt-5-mini",
+                "completion_params": params
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        session["messages"][3]["content"],
+        "```json\n{\"ok\": false}\n```"
+    );
+    let request = app.provider().requests().pop().unwrap();
+    assert_eq!(request.messages.last().unwrap().role, "system");
+}
+
+#[tokio::test]
+async fn attachments_are_refused_by_models_without_file_support() {
+    let app = TestApp::spawn().await;
+    let attachment = json!({
+        "file_name": "photo.png",
+        "mime_type": "image/png",
+        "size_bytes": 12,
+        "url": "http://127.0.0.1:4000/uploads/photo.png",
+    });
+    let request = json!({
+        "model": "llama-3.1-8b-instant",
+        "messages": [{ "role": "user", "content": "Décris", "attachments": [attachment] }],
+    });
+    for path in ["/api/ai", "/api/ai/stream"] {
+        let (status, body) = app.request(Method::POST, path, Some(request.clone())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, "Ce modèle n'accepte pas les fichiers ni les images.");
+    }
+
+    let session_id = app.create_session().await;
+    let (status, body) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({
+                "content": "Décris",
+                "model": "llama-3.1-8b-instant",
+                "attachments": [attachment],
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body, "Ce modèle n'accepte pas les fichiers ni les images.");
+    assert!(app.provider().requests().is_empty());
+}
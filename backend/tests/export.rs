@@ -0,0 +1,227 @@
+mod common;
+
+use std::time::Duration;
+
+use axum::http::{Method, StatusCode};
+use backend::{mock::MockReply, providers::AiModelChoice};
+use serde_json::{Value, json};
+
+use common::{TestApp, zip_entries};
+
+/// Attend la fin de la construction de l'archive et renvoie l'export.
+async fn wait_for_export(app: &TestApp, export_id: &str) -> Value {
+    for _ in 0..100 {
+        let (status, export) = app
+            .request(Method::GET, &format!("/api/export/{export_id}"), None)
+            .await;
+        assert_eq!(status, StatusCode::OK, "{export}");
+        if export["status"] != "pending" && export["status"] != "running" {
+            return export;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("l'export ne s'est pas terminé");
+}
+
+#[tokio::test]
+async fn export_builds_a_downloadable_archive() {
+    let app = TestApp::spawn().await;
+    let (status, notes) = app
+        .upload("notes.txt", "text/plain", "Ordre du jour".as_bytes())
+        .await;
+    assert_eq!(status, StatusCode::OK, "{notes}");
+    let session_id = app.create_session().await;
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({
+                "content": "Résume ces notes",
+                "model": "gpt-5-mini",
+                "attachments": [notes]
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    app.request(
+        Method::POST,
+        &format!("/api/chat/sessions/{session_id}/archive"),
+        None,
+    )
+    .await;
+
+    let (status, export) = app.request(Method::POST, "/api/export", None).await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{export}");
+    let export = wait_for_export(&app, export["id"].as_str().unwrap()).await;
+    assert_eq!(export["status"], "ready", "{export}");
+    assert_eq!(export["sessions_done"], 1);
+    assert_eq!(export["sessions_total"], 1);
+
+    let download_url = export["download_url"].as_str().unwrap();
+    let (status, content_type, archive) = app.download(download_url).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type.as_deref(), Some("application/zip"));
+
+    let entries = zip_entries(&archive);
+    let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+    let storage_key = notes["storage_key"].as_str().unwrap();
+    assert_eq!(names[0], "export.json");
+    assert!(names[1].starts_with("sessions/001-"), "{names:?}");
+    assert_eq!(names[2], format!("attachments/{storage_key}"));
+
+    // Les sessions archivées font partie de l'export.
+    let sessions: Value = serde_json::from_slice(&entries[0].1).unwrap();
+    assert_eq!(sessions[0]["archived"], true);
+    let page = String::from_utf8(entries[1].1.clone()).unwrap();
+    assert!(page.contains("Résume ces notes"), "{page}");
+    assert!(page.contains(&format!("](../attachments/{storage_key})")));
+    assert_eq!(entries[2].1, b"Ordre du jour");
+}
+
+#[tokio::test]
+async fn export_download_requires_a_valid_link() {
+    let app = TestApp::spawn_with(|config| config.export_link_ttl = Duration::ZERO).await;
+    let (_, export) = app.request(Method::POST, "/api/export", None).await;
+    let export_id = export["id"].as_str().unwrap();
+    wait_for_export(&app, export_id).await;
+
+    let (status, _, _) = app
+        .download(&format!(
+            "/api/export/{export_id}/download?token={}",
+            uuid::Uuid::new_v4()
+        ))
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Lien valable zéro seconde : l'export est aussitôt expiré.
+    let (_, export) = app
+        .request(Method::GET, &format!("/api/export/{export_id}"), None)
+        .await;
+    assert_eq!(export["status"], "expired");
+    assert!(export.get("download_url").is_none());
+}
+
+#[tokio::test]
+async fn export_renders_a_session_as_pdf() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({
+                "content": "## Théorème de Pythagore\n\nPour un triangle rectangle, \
+                            $a^2 + b^2 = c^2$ et $$\\frac{\\sqrt{x}}{\\alpha_{i}}$$\n\n\
+                            ```rust:src/main.rs\nfn main() {}\n```\n\n\
+                            | Côté | Longueur |\n|---|---|\n| a | 3 |",
+                "model": "gpt-5-mini"
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+
+    // Le PDF porte sur une session existante.
+    let (status, _) = app
+        .request(
+            Method::POST,
+            "/api/export",
+            Some(json!({ "format": "pdf" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app
+        .request(
+            Method::POST,
+            "/api/export",
+            Some(json!({ "format": "pdf", "session_id": uuid::Uuid::new_v4() })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, export) = app
+        .request(
+            Method::POST,
+            "/api/export",
+            Some(json!({ "format": "pdf", "session_id": session_id })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{export}");
+    assert_eq!(export["format"], "pdf");
+    let export = wait_for_export(&app, export["id"].as_str().unwrap()).await;
+    assert_eq!(export["status"], "ready", "{export}");
+
+    let download_url = export["download_url"].as_str().unwrap();
+    let (status, content_type, pdf) = app.download(download_url).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type.as_deref(), Some("application/pdf"));
+    assert!(pdf.starts_with(b"%PDF"));
+    let text = pdf_extract::extract_text_from_mem(&pdf).unwrap();
+    assert!(text.contains("Pythagore"), "{text}");
+    assert!(text.contains("main"), "{text}");
+    assert!(text.contains("Longueur"), "{text}");
+    // Formules mises en forme, pas laissées en LaTeX
+    assert!(!text.contains("\\frac"), "{text}");
+}
+
+#[tokio::test]
+async fn export_distills_a_session_into_anki_flashcards() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({
+                "content": "Explique la photosynthèse",
+                "model": "gpt-5-mini"
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+
+    app.provider().push_reply(MockReply::Text(
+        "```json\n[{\"question\": \"Où a lieu la photosynthèse ?\", \
+         \"answer\": \"Dans les \\\"chloroplastes\\\".\\nSurtout dans les feuilles.\"}, \
+         {\"question\": \"Que produit-elle ?\", \"answer\": \"Du glucose & de l'O2\"}]\n```"
+            .to_string(),
+    ));
+    let (status, export) = app
+        .request(
+            Method::POST,
+            "/api/export",
+            Some(json!({ "format": "anki", "session_id": session_id, "model": "gpt-4.1" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{export}");
+    let export = wait_for_export(&app, export["id"].as_str().unwrap()).await;
+    assert_eq!(export["status"], "ready", "{export}");
+
+    // Le modèle demandé reçoit la discussion.
+    let request = app.provider().requests().pop().unwrap();
+    assert_eq!(request.model, AiModelChoice::OpenAIGpt41);
+    assert!(
+        request.messages[1]
+            .content
+            .contains("Explique la photosynthèse")
+    );
+
+    let download_url = export["download_url"].as_str().unwrap();
+    let (status, content_type, csv) = app.download(download_url).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(content_type.unwrap().starts_with("text/csv"));
+    let csv = String::from_utf8(csv).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "#separator:Comma");
+    assert!(
+        lines.iter().any(|line| line.starts_with("#deck:CarlGPT::")),
+        "{csv}"
+    );
+    assert!(
+        lines.contains(
+            &"\"Où a lieu la photosynthèse ?\",\"Dans les \"\"chloroplastes\"\".<br>Surtout \
+              dans les feuilles.\",carlgpt"
+        ),
+        "{csv}"
+    );
+    assert!(lines.contains(&"\"Que produit-elle ?\",\"Du glucose &amp; de l'O2\",carlgpt"));
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+
+use common::TestApp;
+
+const ADMIN: &[(&str, &str)] = &[("x-admin-token", "secret")];
+
+#[tokio::test]
+async fn tools_can_be_opened_to_listed_clients_only() {
+    let app = TestApp::spawn_with(|config| config.admin_token = Some("secret".to_string())).await;
+    let session_id = app.create_session().await;
+
+    let (status, features) = app
+        .request_with_headers(
+            Method::GET,
+            "/api/me/features",
+            None,
+            &[("x-client-id", "alice")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        features,
+        json!({ "client": "alice", "features": { "tools": true } })
+    );
+
+    let flag = json!({ "enabled": true, "rollout_percent": 0, "clients": ["beta"] });
+    let (status, _) = app
+        .request(
+            Method::PUT,
+            "/api/admin/feature-flags/tools",
+            Some(flag.clone()),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, saved) = app
+        .request_with_headers(
+            Method::PUT,
+            "/api/admin/feature-flags/tools",
+            Some(flag),
+            ADMIN,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{saved}");
+    assert_eq!(saved["rollout_percent"], 0);
+    assert_eq!(saved["clients"], json!(["beta"]));
+
+    let message = json!({
+        "content": "Bonjour",
+        "model": "gpt-5-mini",
+        "completion_params": { "calendar": true }
+    });
+    for client in ["alice", "beta"] {
+        let (status, _) = app
+            .request_with_headers(
+                Method::POST,
+                &format!("/api/chat/sessions/{session_id}/messages"),
+                Some(message.clone()),
+                &[("x-client-id", client)],
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let calendar: Vec<_> = app
+        .provider()
+        .requests()
+        .iter()
+        .map(|request| request.params.as_ref().and_then(|params| params.calendar))
+        .collect();
+    assert_eq!(calendar, [None, Some(true)]);
+
+    let (status, features) = app
+        .request_with_headers(
+            Method::GET,
+            "/api/me/features",
+            None,
+            &[("x-client-id", "alice")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(features["features"]["tools"], false);
+
+    let (status, _) = app
+        .request_with_headers(
+            Method::DELETE,
+            "/api/admin/feature-flags/tools",
+            None,
+            ADMIN,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, flags) = app
+        .request_with_headers(Method::GET, "/api/admin/feature-flags", None, ADMIN)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(flags, json!([]));
+}
+
+#[tokio::test]
+async fn percentage_rollout_is_stable_per_client() {
+    let app = TestApp::spawn_with(|config| config.admin_token = Some("secret".to_string())).await;
+    let (status, _) = app
+        .request_with_headers(
+            Method::PUT,
+            "/api/admin/feature-flags/arena",
+            Some(json!({ "enabled": true, "rollout_percent": 50, "description": "Mode arène" })),
+            ADMIN,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app
+        .request_with_headers(
+            Method::PUT,
+            "/api/admin/feature-flags/rag",
+            Some(json!({ "enabled": true, "rollout_percent": 101 })),
+            ADMIN,
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let mut enabled = 0;
+    for client in 0..200 {
+        let client = format!("client-{client}");
+        let mut states = Vec::new();
+        for _ in 0..2 {
+            let (_, features) = app
+                .request_with_headers(
+                    Method::GET,
+                    "/api/me/features",
+                    None,
+                    &[("x-client-id", &client)],
+                )
+                .await;
+            states.push(features["features"]["arena"].as_bool().unwrap());
+        }
+        assert_eq!(states[0], states[1]);
+        enabled += usize::from(states[0]);
+    }
+    assert!((60..=140).contains(&enabled), "{enabled} clients sur 200");
+}
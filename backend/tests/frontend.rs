@@ -0,0 +1,47 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn frontend_is_served_with_spa_fallback_and_cache_headers() {
+    let dir = std::env::temp_dir().join(format!("carlgpt-frontend-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(dir.join("_next/static/chunks")).unwrap();
+    std::fs::write(dir.join("index.html"), "<html>CarlGPT</html>").unwrap();
+    std::fs::write(
+        dir.join("_next/static/chunks/app-1a2b.js"),
+        "console.log(1)",
+    )
+    .unwrap();
+    let frontend_dir = dir.to_string_lossy().into_owned();
+    let app = TestApp::spawn_with(|config| config.frontend_dir = Some(frontend_dir)).await;
+
+    for uri in ["/", "/chat/1234"] {
+        let (status, headers, body) = app
+            .request_with_response_headers(Method::GET, uri, None, &[])
+            .await;
+        assert_eq!(status, StatusCode::OK, "{uri}");
+        assert_eq!(body, "<html>CarlGPT</html>");
+        assert_eq!(headers["cache-control"], "no-cache");
+    }
+
+    let (status, headers, body) = app
+        .request_with_response_headers(Method::GET, "/_next/static/chunks/app-1a2b.js", None, &[])
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "console.log(1)");
+    assert_eq!(
+        headers["cache-control"],
+        "public, max-age=31536000, immutable"
+    );
+
+    // L'API garde la priorité, et un endpoint inconnu reste une 404.
+    let (status, models) = app.request(Method::GET, "/api/models", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(models["models"].is_array());
+    let (status, _) = app.request(Method::GET, "/api/nope", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,163 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use backend::grpc::proto::{
+    CreateSessionRequest, GetSessionRequest, ListSessionsRequest, SendMessageRequest,
+    chat_client::ChatClient, chat_event::Event,
+};
+use common::TestApp;
+use serde_json::json;
+use tonic::{Code, transport::Channel};
+
+async fn client(app: &TestApp) -> ChatClient<Channel> {
+    let addr = app.serve().await;
+    ChatClient::connect(format!("http://{addr}")).await.unwrap()
+}
+
+#[tokio::test]
+async fn send_message_streams_typed_events() {
+    let app = TestApp::spawn_with(|config| config.grpc_enabled = true).await;
+    let mut client = client(&app).await;
+
+    let session = client
+        .create_session(CreateSessionRequest {
+            title: Some("Depuis gRPC".into()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let mut stream = client
+        .send_message(SendMessageRequest {
+            session_id: session.id.clone(),
+            content: "Bonjour".into(),
+            model: None,
+            preset_id: None,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let mut events = Vec::new();
+    while let Some(event) = stream.message().await.unwrap() {
+        events.push(event);
+    }
+    let seqs: Vec<u64> = events.iter().map(|event| event.seq).collect();
+    assert!(seqs.windows(2).all(|pair| pair[1] == pair[0] + 1));
+    assert!(matches!(events[0].event, Some(Event::Session(_))));
+    assert!(matches!(
+        events.last().unwrap().event,
+        Some(Event::Usage(_))
+    ));
+
+    let streamed: String = events
+        .iter()
+        .filter_map(|event| match &event.event {
+            Some(Event::Token(delta)) => Some(delta.content.as_str()),
+            _ => None,
+        })
+        .collect();
+    let Some(Event::Final(last)) = events
+        .iter()
+        .find(|event| matches!(event.event, Some(Event::Final(_))))
+        .and_then(|event| event.event.clone())
+    else {
+        panic!("pas d'évènement final");
+    };
+    let reply = last.session.unwrap().messages.pop().unwrap();
+    assert_eq!(reply.role, "assistant");
+    assert_eq!(reply.status, "complete");
+    assert_eq!(reply.content, streamed);
+
+    let stored = client
+        .get_session(GetSessionRequest {
+            session_id: session.id.clone(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(stored.messages.len(), 2);
+    let listed = client
+        .list_sessions(ListSessionsRequest {})
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(listed.sessions.iter().any(|listed| listed.id == session.id));
+}
+
+#[tokio::test]
+async fn send_message_shares_the_http_rate_limit() {
+    let app = TestApp::spawn_with(|config| {
+        config.grpc_enabled = true;
+        config.rate_limit_per_minute = Some(1);
+    })
+    .await;
+    let mut client = client(&app).await;
+    let session = client
+        .create_session(CreateSessionRequest { title: None })
+        .await
+        .unwrap()
+        .into_inner();
+    let request = || SendMessageRequest {
+        session_id: session.id.clone(),
+        content: "Bonjour".into(),
+        model: None,
+        preset_id: None,
+    };
+
+    let mut stream = client.send_message(request()).await.unwrap().into_inner();
+    while stream.message().await.unwrap().is_some() {}
+    let refused = client.send_message(request()).await.unwrap_err();
+    assert_eq!(refused.code(), Code::ResourceExhausted);
+
+    let missing = client
+        .get_session(GetSessionRequest {
+            session_id: uuid::Uuid::new_v4().to_string(),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(missing.code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn session_reads_are_cached_until_the_backend_writes_it() {
+    let app = TestApp::spawn_with(|config| config.grpc_enabled = true).await;
+    let mut client = client(&app).await;
+    let session = client
+        .create_session(CreateSessionRequest {
+            title: Some("Avant".into()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let get = || GetSessionRequest {
+        session_id: session.id.clone(),
+    };
+    assert_eq!(
+        client.get_session(get()).await.unwrap().into_inner().title,
+        "Avant"
+    );
+
+    // Écriture hors du backend : la copie en cache reste servie jusqu'à son expiration.
+    app.execute_sql(&format!(
+        "UPDATE chat_sessions SET title = 'Après' WHERE id = '{}'",
+        session.id
+    ))
+    .await;
+    assert_eq!(
+        client.get_session(get()).await.unwrap().into_inner().title,
+        "Avant"
+    );
+
+    let (status, _) = app
+        .request(
+            Method::PUT,
+            &format!("/api/chat/sessions/{}/draft", session.id),
+            Some(json!({ "content": "brouillon" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        client.get_session(get()).await.unwrap().into_inner().title,
+        "Après"
+    );
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use backend::{
+    hooks::{HookContext, StreamFilter, StreamHook, StreamHookSettings},
+    mock::MockReply,
+};
+use serde_json::json;
+
+use common::{TestApp, streamed_text};
+
+#[tokio::test]
+async fn builtin_hooks_rewrite_the_stream_and_the_stored_answer() {
+    let app = TestApp::spawn_with(|config| {
+        config.stream_hooks = StreamHookSettings {
+            hooks: vec!["mask_words".to_string(), "rewrite_links".to_string()],
+            masked_words: vec!["zut".to_string()],
+            link_rewrites: vec![(
+                "http://wiki.internal/".to_string(),
+                "https://wiki.example.com/".to_string(),
+            )],
+        };
+    })
+    .await;
+    let session_id = app.create_session().await;
+    // Le mock découpe la réponse en mots : le lien et les mots arrivent en morceaux.
+    app.provider().push_reply(MockReply::Text(
+        "Zut, voir [la page](http://wiki.internal/page) ou zutique, zut".to_string(),
+    ));
+
+    let events = app
+        .stream(
+            &format!("/api/chat/sessions/{session_id}/messages/stream"),
+            json!({ "content": "Une source ?" }),
+        )
+        .await;
+    let expected = "***, voir [la page](https://wiki.example.com/page) ou zutique, ***";
+    assert_eq!(streamed_text(&events), expected);
+
+    let (status, sessions) = app.request(Method::GET, "/api/chat/sessions", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(sessions[0]["messages"][1]["content"], expected);
+}
+
+/// Met le texte en majuscules, ligne par ligne.
+struct Shout;
+
+struct ShoutFilter(String);
+
+impl StreamHook for Shout {
+    fn start(&self, _context: &HookContext) -> Box<dyn StreamFilter> {
+        Box::new(ShoutFilter(String::new()))
+    }
+}
+
+impl StreamFilter for ShoutFilter {
+    fn push(&mut self, text: &str) -> String {
+        self.0.push_str(text);
+        match self.0.rfind('\n') {
+            Some(index) => {
+                let rest = self.0.split_off(index + 1);
+                std::mem::replace(&mut self.0, rest).to_uppercase()
+            }
+            None => String::new(),
+        }
+    }
+
+    fn finish(&mut self) -> String {
+        std::mem::take(&mut self.0).to_uppercase()
+    }
+}
+
+#[tokio::test]
+async fn registered_hooks_apply_to_every_answer() {
+    let app = TestApp::spawn().await.with_stream_hook(Shout);
+    app.provider()
+        .push_reply(MockReply::Text("bonjour\nà tous".to_string()));
+
+    let (status, body) = app
+        .request(
+            Method::POST,
+            "/api/ai",
+            Some(json!({ "messages": [{ "role": "user", "content": "Salut" }] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["response"], "BONJOUR\nÀ TOUS");
+}
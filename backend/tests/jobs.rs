@@ -0,0 +1,103 @@
+mod common;
+
+use std::time::Duration;
+
+use axum::http::{Method, StatusCode};
+use backend::retention::RetentionPolicy;
+use serde_json::json;
+
+use common::TestApp;
+
+#[tokio::test]
+async fn admin_sees_scheduled_jobs() {
+    let app = TestApp::spawn_with(|config| {
+        config.admin_token = Some("secret".to_string());
+        config.retention = RetentionPolicy {
+            archive_idle_days: Some(30),
+            ..RetentionPolicy::default()
+        };
+    })
+    .await;
+
+    let (status, _) = app.request(Method::GET, "/api/admin/jobs", None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Chaque tâche tourne une première fois dès le démarrage.
+    for _ in 0..60 {
+        let (status, jobs) = app
+            .request_with_headers(
+                Method::GET,
+                "/api/admin/jobs",
+                None,
+                &[("x-admin-token", "secret")],
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{jobs}");
+        let names: Vec<&str> = jobs
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|job| job["name"].as_str())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "retention",
+                "usage_rollup",
+                "feature_flags",
+                "export_cleanup"
+            ]
+        );
+        if jobs[0]["last_result"] == "ok" {
+            assert_eq!(
+                jobs[0]["last_message"],
+                "0 sessions archivées, 0 supprimées, 0 messages retirés"
+            );
+            assert_eq!(jobs[0]["interval_secs"], 3600);
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("la tâche de rétention ne s'est pas exécutée");
+}
+
+#[tokio::test]
+async fn usage_is_rolled_up_per_day_and_model() {
+    let app = TestApp::spawn_with(|config| {
+        config.admin_token = Some("secret".to_string());
+        config.usage_rollup_interval = Duration::from_millis(50);
+    })
+    .await;
+    let session_id = app.create_session().await;
+    for content in ["Bonjour", "Encore"] {
+        let (status, _) = app
+            .request(
+                Method::POST,
+                &format!("/api/chat/sessions/{session_id}/messages"),
+                Some(json!({ "content": content, "model": "gpt-5-mini" })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let today = chrono::Utc::now().date_naive();
+    for _ in 0..60 {
+        let (status, usage) = app
+            .request_with_headers(
+                Method::GET,
+                &format!("/api/admin/usage?from={today}&to={today}"),
+                None,
+                &[("x-admin-token", "secret")],
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{usage}");
+        if usage[0]["answers"] == 2 {
+            assert_eq!(usage[0]["day"], today.to_string());
+            assert_eq!(usage[0]["model"], "gpt-5-mini");
+            assert!(usage[0]["completion_tokens"].as_i64().unwrap() > 0);
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("la consommation n'a pas été agrégée");
+}
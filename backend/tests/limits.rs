@@ -0,0 +1,213 @@
+mod common;
+
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    http::{HeaderMap, Method, StatusCode},
+};
+use http_body_util::BodyExt;
+use serde_json::{Value, json};
+
+use common::TestApp;
+
+fn header(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.parse().ok()
+}
+
+/// Premier évènement d'un flux SSE ouvert.
+async fn first_event(body: &mut Body) -> Value {
+    let frame = body.frame().await.unwrap().unwrap();
+    let text = String::from_utf8_lossy(frame.data_ref().unwrap()).into_owned();
+    let data = text.lines().find_map(|line| line.strip_prefix("data: "));
+    serde_json::from_str(data.unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn streamed_completions_over_the_limit_are_queued() {
+    let app = TestApp::spawn_with(|config| {
+        config.rate_limit_per_minute = Some(1);
+        config.rate_limit_queue = Some(1);
+    })
+    .await;
+    let body = json!({ "messages": [{ "role": "user", "content": "Bonjour" }] });
+    app.stream("/api/ai/stream", body.clone()).await;
+
+    // Au-delà de la limite : la réponse attend son tour au lieu d'être refusée.
+    let mut queued = app.open_stream("/api/ai/stream", body.clone()).await;
+    let event = first_event(&mut queued).await;
+    assert_eq!(event["type"], "queued");
+    assert_eq!(event["position"], 1);
+    let eta = event["etaSeconds"].as_u64().unwrap();
+    assert!(eta > 0 && eta <= 60, "{event}");
+
+    // File pleine, et les endpoints JSON ne sont jamais mis en file.
+    for uri in ["/api/ai/stream", "/api/ai"] {
+        let (status, _, _) = app
+            .request_with_response_headers(Method::POST, uri, Some(body.clone()), &[])
+            .await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS, "{uri}");
+    }
+
+    // Un client qui se déconnecte libère sa place.
+    drop(queued);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let mut queued = app.open_stream("/api/ai/stream", body).await;
+    assert_eq!(first_event(&mut queued).await["position"], 1);
+    assert_eq!(app.provider().requests().len(), 1);
+}
+
+#[tokio::test]
+async fn completions_are_rate_limited_per_minute() {
+    let app = TestApp::spawn_with(|config| {
+        config.rate_limit_per_minute = Some(2);
+        config.admin_token = Some("secret".to_string());
+    })
+    .await;
+    let body = json!({ "messages": [{ "role": "user", "content": "Bonjour" }] });
+
+    for remaining in [1, 0] {
+        let (status, headers, _) = app
+            .request_with_response_headers(Method::POST, "/api/ai", Some(body.clone()), &[])
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(header(&headers, "x-ratelimit-limit"), Some(2));
+        assert_eq!(header(&headers, "x-ratelimit-remaining"), Some(remaining));
+        assert!(header(&headers, "x-ratelimit-reset").unwrap() <= 60);
+    }
+
+    let (status, headers, message) = app
+        .request_with_response_headers(Method::POST, "/api/ai", Some(body.clone()), &[])
+        .await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert!(
+        message.as_str().unwrap().contains("2 par minute"),
+        "{message}"
+    );
+    assert_eq!(header(&headers, "x-ratelimit-remaining"), Some(0));
+    assert!(header(&headers, "retry-after").unwrap() > 0);
+
+    // Les autres endpoints et les admins ne sont pas limités.
+    let (status, _) = app.request(Method::GET, "/api/models", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, headers, _) = app
+        .request_with_response_headers(
+            Method::POST,
+            "/api/ai",
+            Some(body),
+            &[("x-admin-token", "secret")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers.get("x-ratelimit-limit").is_none());
+}
+
+#[tokio::test]
+async fn daily_token_quota_blocks_new_answers() {
+    let app = TestApp::spawn_with(|config| config.daily_token_quota = Some(1)).await;
+    let session_id = app.create_session().await;
+    let uri = format!("/api/chat/sessions/{session_id}/messages");
+
+    let (status, headers, _) = app
+        .request_with_response_headers(
+            Method::POST,
+            &uri,
+            Some(json!({ "content": "Bonjour" })),
+            &[],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(header(&headers, "x-quota-limit"), Some(1));
+    assert_eq!(header(&headers, "x-quota-remaining"), Some(1));
+
+    let (status, headers, _) = app
+        .request_with_response_headers(
+            Method::POST,
+            &uri,
+            Some(json!({ "content": "Encore" })),
+            &[],
+        )
+        .await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(header(&headers, "x-quota-remaining"), Some(0));
+    assert!(header(&headers, "retry-after").unwrap() <= 24 * 3600);
+}
+
+#[tokio::test]
+async fn daily_token_quota_counts_unsaved_answers_and_deleted_sessions() {
+    let app = TestApp::spawn_with(|config| config.daily_token_quota = Some(1)).await;
+    let body = json!({ "messages": [{ "role": "user", "content": "Bonjour" }] });
+
+    // `/api/ai` n'enregistre rien, mais sa consommation entre dans le quota.
+    let (status, _, _) = app
+        .request_with_response_headers(Method::POST, "/api/ai", Some(body.clone()), &[])
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, headers, _) = app
+        .request_with_response_headers(Method::POST, "/api/ai", Some(body), &[])
+        .await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(header(&headers, "x-quota-remaining"), Some(0));
+
+    // Supprimer la discussion ne rend pas les tokens consommés.
+    let app = TestApp::spawn_with(|config| config.daily_token_quota = Some(1)).await;
+    let session_id = app.create_session().await;
+    let uri = format!("/api/chat/sessions/{session_id}");
+    let (status, _) = app
+        .request(
+            Method::POST,
+            &format!("{uri}/messages"),
+            Some(json!({ "content": "Bonjour" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.request(Method::DELETE, &uri, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _, _) = app
+        .request_with_response_headers(
+            Method::POST,
+            "/api/ai/stream",
+            Some(json!({ "messages": [{ "role": "user", "content": "Encore" }] })),
+            &[],
+        )
+        .await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn oversize_bodies_are_refused_except_on_uploads() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    let text = "a".repeat(2 * 1024 * 1024);
+    let uri = format!("/api/chat/sessions/{session_id}/messages");
+    let body = json!({ "content": text });
+
+    let length = body.to_string().len().to_string();
+    let (status, _, message) = app
+        .request_with_response_headers(
+            Method::POST,
+            &uri,
+            Some(body.clone()),
+            &[("content-length", &length)],
+        )
+        .await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(
+        message.as_str().unwrap().contains("1 Mo au plus"),
+        "{message}"
+    );
+
+    // Sans `Content-Length`, le corps est coupé à la même limite.
+    let (status, _) = app.request(Method::POST, &uri, Some(body)).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(app.provider().requests().is_empty());
+
+    let (status, uploaded) = app
+        .request(
+            Method::POST,
+            "/api/uploads/text",
+            Some(json!({ "text": text })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{uploaded}");
+}
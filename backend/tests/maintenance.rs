@@ -0,0 +1,124 @@
+mod common;
+
+use std::time::Duration;
+
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+
+use common::TestApp;
+
+const ADMIN: &[(&str, &str)] = &[("x-admin-token", "secret")];
+
+#[tokio::test]
+async fn maintenance_mode_blocks_writes_but_not_reads() {
+    let app = TestApp::spawn_with(|config| config.admin_token = Some("secret".to_string())).await;
+    let session_id = app.create_session().await;
+
+    let (status, _) = app
+        .request(
+            Method::PUT,
+            "/api/admin/maintenance",
+            Some(json!({ "enabled": true })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, maintenance) = app
+        .request_with_headers(
+            Method::PUT,
+            "/api/admin/maintenance",
+            Some(json!({ "enabled": true, "message": "Migration en cours, retour vers 14 h" })),
+            ADMIN,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(maintenance["enabled"], true);
+    assert!(maintenance["since"].is_string());
+
+    let (status, sessions) = app.request(Method::GET, "/api/chat/sessions", None).await;
+    assert_eq!(status, StatusCode::OK, "{sessions}");
+    let (status, _) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/estimate"),
+            Some(json!({ "content": "Bonjour" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, headers, body) = app
+        .request_with_response_headers(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({ "content": "Bonjour" })),
+            &[],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body, "Migration en cours, retour vers 14 h");
+    assert_eq!(headers["retry-after"], "60");
+    let (status, _) = app
+        .request(
+            Method::DELETE,
+            &format!("/api/chat/sessions/{session_id}"),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(app.provider().requests().is_empty());
+
+    let (status, maintenance) = app
+        .request_with_headers(
+            Method::PUT,
+            "/api/admin/maintenance",
+            Some(json!({ "enabled": false })),
+            ADMIN,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(maintenance, json!({ "enabled": false }));
+    let (status, _) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({ "content": "Bonjour" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn maintenance_mode_is_shared_through_the_database() {
+    let app = TestApp::spawn_with(|config| config.admin_token = Some("secret".to_string())).await;
+    let session_id = app.create_session().await;
+    let (status, _) = app.request(Method::GET, "/api/chat/sessions", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Une autre instance active le mode : celle-ci le voit au plus tard à la relecture.
+    app.execute_sql("INSERT INTO maintenance_mode (message) VALUES ('Migration')")
+        .await;
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+    let (status, body) = app
+        .request(
+            Method::DELETE,
+            &format!("/api/chat/sessions/{session_id}"),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body, "Migration");
+    let (_, maintenance) = app
+        .request_with_headers(Method::GET, "/api/admin/maintenance", None, ADMIN)
+        .await;
+    assert_eq!(maintenance["enabled"], true);
+    assert_eq!(maintenance["message"], "Migration");
+
+    app.execute_sql("DELETE FROM maintenance_mode").await;
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+    let (status, _) = app
+        .request(
+            Method::DELETE,
+            &format!("/api/chat/sessions/{session_id}"),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+}
@@ -0,0 +1,203 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use backend::{access::ModelPolicy, providers::AiModelChoice};
+use serde_json::json;
+
+use common::{TestApp, events_of};
+
+async fn restricted_app() -> TestApp {
+    TestApp::spawn_with(|config| {
+        config.models = ModelPolicy {
+            default: AiModelChoice::OpenAIGpt5Mini,
+            allowed: Some(vec![
+                AiModelChoice::OpenAIGpt5Mini,
+                AiModelChoice::GroqLlama31,
+            ]),
+        };
+        config.admin_token = Some("secret".to_string());
+    })
+    .await
+}
+
+#[tokio::test]
+async fn models_endpoint_lists_allowed_models() {
+    let app = restricted_app().await;
+
+    let (status, body) = app.request(Method::GET, "/api/models", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["default"], "gpt-5-mini");
+    assert_eq!(body["models"].as_array().unwrap().len(), 2);
+
+    let (_, body) = app
+        .request_with_headers(
+            Method::GET,
+            "/api/models",
+            None,
+            &[("x-admin-token", "secret")],
+        )
+        .await;
+    assert_eq!(
+        body["models"].as_array().unwrap().len(),
+        AiModelChoice::ALL.len()
+    );
+}
+
+#[tokio::test]
+async fn restricted_model_is_reserved_to_admins() {
+    let app = restricted_app().await;
+    let session_id = app.create_session().await;
+    let uri = format!("/api/chat/sessions/{session_id}/messages");
+    let body = json!({ "content": "Bonjour", "model": "gpt-5-pro" });
+
+    let (status, _) = app.request(Method::POST, &uri, Some(body.clone())).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = app
+        .request_with_headers(
+            Method::POST,
+            &uri,
+            Some(body.clone()),
+            &[("x-admin-token", "mauvais")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = app
+        .request_with_headers(
+            Method::POST,
+            &uri,
+            Some(body),
+            &[("x-admin-token", "secret")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        app.provider().requests()[0].model,
+        AiModelChoice::OpenAIGpt5Pro
+    );
+}
+
+#[tokio::test]
+async fn configured_default_model_is_used() {
+    let app = restricted_app().await;
+    let session_id = app.create_session().await;
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({ "content": "Bonjour" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        app.provider().requests()[0].model,
+        AiModelChoice::OpenAIGpt5Mini
+    );
+}
+
+#[tokio::test]
+async fn local_only_mode_refuses_external_providers() {
+    let app = TestApp::spawn_with(|config| {
+        config.local_only = true;
+        config.mock_provider = false;
+    })
+    .await;
+
+    let (_, body) = app.request(Method::GET, "/api/models", None).await;
+    assert!(
+        body["models"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|model| model["available"] == false)
+    );
+
+    let session_id = app.create_session().await;
+    for model in ["llama-3.1-8b-instant", "gpt-5-mini"] {
+        let (status, body) = app
+            .request(
+                Method::POST,
+                &format!("/api/chat/sessions/{session_id}/messages"),
+                Some(json!({ "content": "Bonjour", "model": model })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(body.as_str().unwrap().contains("LOCAL_ONLY"), "{body}");
+    }
+}
+
+#[tokio::test]
+async fn unsupported_params_are_not_sent_to_the_model() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    let uri = format!("/api/chat/sessions/{session_id}/messages");
+    let params =
+        json!({ "temperature": 0.2, "max_tokens": 300, "seed": 7, "presence_penalty": 0.5 });
+
+    for model in ["gpt-5-mini", "llama-3.1-8b-instant", "gpt-4.1"] {
+        let (status, _) = app
+            .request(
+                Method::POST,
+                &uri,
+                Some(json!({ "content": "Bonjour", "model": model, "completion_params": params })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{model}");
+    }
+    let sent: Vec<_> = app
+        .provider()
+        .requests()
+        .into_iter()
+        .map(|request| request.params.unwrap())
+        .collect();
+    // Modèle de raisonnement : ni température ni pénalités.
+    assert_eq!(sent[0].temperature, None);
+    assert_eq!(sent[0].presence_penalty, None);
+    assert_eq!((sent[0].max_tokens, sent[0].seed), (Some(300), Some(7)));
+    // Groq ignore `seed`.
+    assert_eq!(sent[1].temperature, Some(0.2));
+    assert_eq!(sent[1].seed, None);
+    assert_eq!(sent[2].presence_penalty, Some(0.5));
+    assert_eq!(sent[2].seed, Some(7));
+
+    let (_, body) = app.request(Method::GET, "/api/models", None).await;
+    let gpt5_mini = body["models"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|model| model["id"] == "gpt-5-mini")
+        .unwrap();
+    assert_eq!(gpt5_mini["supported_params"], json!(["max_tokens", "seed"]));
+}
+
+#[tokio::test]
+async fn max_tokens_is_clamped_to_the_model_limit() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    let events = app
+        .stream(
+            &format!("/api/chat/sessions/{session_id}/messages/stream"),
+            json!({
+                "content": "Écris un roman",
+                "model": "gpt-4.1",
+                "completion_params": { "max_tokens": 100_000 }
+            }),
+        )
+        .await;
+
+    let notices = events_of(&events, "notice");
+    assert_eq!(notices.len(), 1);
+    assert!(
+        notices[0]["message"]
+            .as_str()
+            .unwrap()
+            .contains("de 100000 à 32768"),
+        "{}",
+        notices[0]
+    );
+    assert!(!events_of(&events, "final").is_empty());
+    let params = app.provider().requests()[0].params.clone().unwrap();
+    assert_eq!(params.max_tokens, Some(32_768));
+}
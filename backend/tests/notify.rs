@@ -0,0 +1,182 @@
+mod common;
+
+use std::time::Duration;
+
+use axum::{Json, Router, routing::post};
+use backend::outbound::Outbound;
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use common::TestApp;
+use serde_json::{Value, json};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpListener,
+    sync::mpsc,
+};
+
+/// Serveur SMTP de test, sans STARTTLS ni authentification : renvoie chaque message reçu.
+async fn fake_smtp() -> (String, mpsc::UnboundedReceiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("smtp://{}", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Ok((socket, _)) = listener.accept().await {
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let (read, mut write) = socket.into_split();
+                let mut lines = BufReader::new(read).lines();
+                write.write_all(b"220 test ESMTP\r\n").await.unwrap();
+                let mut data: Option<String> = None;
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if let Some(message) = data.as_mut() {
+                        if line == "." {
+                            tx.send(data.take().unwrap()).unwrap();
+                            write.write_all(b"250 OK\r\n").await.unwrap();
+                        } else {
+                            message.push_str(&line);
+                            message.push('\n');
+                        }
+                        continue;
+                    }
+                    let reply: &[u8] = match line.split(' ').next().unwrap() {
+                        "EHLO" => b"250-test\r\n250 8BITMIME\r\n",
+                        "DATA" => {
+                            data = Some(String::new());
+                            b"354 go\r\n"
+                        }
+                        "QUIT" => b"221 bye\r\n",
+                        _ => b"250 OK\r\n",
+                    };
+                    write.write_all(reply).await.unwrap();
+                }
+            });
+        }
+    });
+    (url, rx)
+}
+
+async fn fake_webhook() -> (String, mpsc::UnboundedReceiver<Value>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::unbounded_channel();
+    let app = Router::new().route(
+        "/hook",
+        post(move |Json(body): Json<Value>| async move {
+            tx.send(body).unwrap();
+        }),
+    );
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (url, rx)
+}
+
+#[tokio::test]
+async fn long_generation_is_notified_by_webhook_and_email() {
+    let (smtp_url, mut emails) = fake_smtp().await;
+    let (webhook_url, mut hooks) = fake_webhook().await;
+    let app = TestApp::spawn_with(|config| {
+        config.public_base_url = Some("https://chat.example.com".to_string());
+        let notifications = &mut config.notifications;
+        notifications.webhook_url = Some(webhook_url);
+        notifications.smtp_url = Some(smtp_url);
+        notifications.email_from = Some("carlgpt@example.com".to_string());
+        notifications.email_to = vec!["moi@example.com".to_string()];
+        notifications.min_duration = Duration::ZERO;
+    })
+    .await;
+    let session_id = app.create_session().await;
+    app.stream(
+        &format!("/api/chat/sessions/{session_id}/messages/stream"),
+        json!({ "content": "Un long raisonnement" }),
+    )
+    .await;
+
+    let hook = tokio::time::timeout(Duration::from_secs(10), hooks.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(hook["type"], "generation_finished");
+    assert_eq!(hook["chatId"], session_id.to_string());
+    assert_eq!(hook["status"], "complete");
+    assert_eq!(
+        hook["url"],
+        format!("https://chat.example.com/?session={session_id}")
+    );
+
+    let email = tokio::time::timeout(Duration::from_secs(10), emails.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(email.contains("To: moi@example.com"));
+    let subject = email
+        .lines()
+        .find_map(|line| line.strip_prefix("Subject: =?UTF-8?B?"))
+        .and_then(|encoded| encoded.strip_suffix("?="))
+        .unwrap();
+    let subject = String::from_utf8(BASE64.decode(subject).unwrap()).unwrap();
+    assert!(subject.starts_with("Réponse prête"));
+    let (_, body) = email.split_once("\n\n").unwrap();
+    let body = String::from_utf8(BASE64.decode(body.replace('\n', "")).unwrap()).unwrap();
+    assert!(body.contains(&format!("https://chat.example.com/?session={session_id}")));
+}
+
+#[tokio::test]
+async fn short_generation_is_not_notified() {
+    let (webhook_url, mut hooks) = fake_webhook().await;
+    let app = TestApp::spawn_with(|config| {
+        config.notifications.webhook_url = Some(webhook_url);
+    })
+    .await;
+    let session_id = app.create_session().await;
+    app.stream(
+        &format!("/api/chat/sessions/{session_id}/messages/stream"),
+        json!({ "content": "Bonjour" }),
+    )
+    .await;
+
+    let received = tokio::time::timeout(Duration::from_millis(500), hooks.recv()).await;
+    assert!(received.is_err());
+}
+
+#[tokio::test]
+async fn webhooks_stay_on_local_hosts_in_local_only_mode() {
+    // Le webhook de la machine reste notifié…
+    let (webhook_url, mut hooks) = fake_webhook().await;
+    let app = TestApp::spawn_with(|config| {
+        config.local_only = true;
+        config.notifications.webhook_url = Some(webhook_url.clone());
+        config.notifications.min_duration = Duration::ZERO;
+    })
+    .await;
+    let session_id = app.create_session().await;
+    app.stream(
+        &format!("/api/chat/sessions/{session_id}/messages/stream"),
+        json!({ "content": "Bonjour" }),
+    )
+    .await;
+    let hook = tokio::time::timeout(Duration::from_secs(10), hooks.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(hook["chatId"], session_id.to_string());
+
+    // … mais un hôte externe est refusé avant tout envoi.
+    let outbound = Outbound::new(true);
+    let err = outbound
+        .client("https://203.0.113.7/hook")
+        .await
+        .unwrap_err();
+    assert!(err.contains("LOCAL_ONLY"), "{err}");
+    for url in [
+        webhook_url.as_str(),
+        "http://localhost:8080/hook",
+        "http://10.1.2.3/hook",
+        "http://[::1]:9000/hook",
+    ] {
+        assert!(outbound.client(url).await.is_ok(), "{url}");
+    }
+    assert!(
+        Outbound::new(false)
+            .client("https://203.0.113.7/hook")
+            .await
+            .is_ok()
+    );
+}
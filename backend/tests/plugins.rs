@@ -0,0 +1,214 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use axum::http::{Method, StatusCode};
+use backend::mock::MockReply;
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+use common::TestApp;
+
+const ADMIN: &[(&str, &str)] = &[("x-admin-token", "secret")];
+
+/// Renvoie le texte reçu (adresse et longueur) tel quel.
+const RETURN_INPUT: &str = "(i64.or (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32)) \
+     (i64.extend_i32_u (local.get $len)))";
+
+/// Module WAT avec la mémoire, `alloc` (allocation linéaire) et un `describe()` qui renvoie
+/// `description` ; `body` ajoute les fonctions du plugin.
+fn plugin(description: Value, body: &str) -> String {
+    let description = description.to_string();
+    let escaped: String = description.bytes().map(|b| format!("\\{b:02x}")).collect();
+    format!(
+        r#"(module
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 4096))
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (global.get $next) (local.get $len)))
+    (local.get $ptr))
+  (data (i32.const 0) "{escaped}")
+  (func (export "describe") (result i64) (i64.const {len}))
+  {body})"#,
+        len = description.len()
+    )
+}
+
+/// Outil qui renvoie ses arguments au modèle.
+fn echo_tool() -> String {
+    plugin(
+        json!({
+            "kind": "tool",
+            "description": "Répète ses arguments",
+            "parameters": { "type": "object", "properties": { "texte": { "type": "string" } } }
+        }),
+        &format!(
+            r#"(func (export "call") (param $ptr i32) (param $len i32) (result i64) {RETURN_INPUT})"#
+        ),
+    )
+}
+
+/// Filtre qui met les lettres ASCII en majuscules.
+fn uppercase_filter() -> String {
+    plugin(
+        json!({ "kind": "filter", "description": "Majuscules" }),
+        &format!(
+            r#"(func (export "push") (param $ptr i32) (param $len i32) (result i64)
+    (local $i i32) (local $b i32)
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+        (local.set $b (i32.load8_u (i32.add (local.get $ptr) (local.get $i))))
+        (if (i32.and (i32.ge_u (local.get $b) (i32.const 97)) (i32.le_u (local.get $b) (i32.const 122)))
+          (then (i32.store8 (i32.add (local.get $ptr) (local.get $i)) (i32.sub (local.get $b) (i32.const 32)))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    {RETURN_INPUT})"#
+        ),
+    )
+}
+
+fn plugin_dir(files: &[(&str, String)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("carlgpt-plugins-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for (name, content) in files {
+        std::fs::write(dir.join(name), content).unwrap();
+    }
+    dir
+}
+
+async fn spawn_with_plugins(dir: &Path, fuel: Option<u64>) -> TestApp {
+    let dir = dir.to_string_lossy().into_owned();
+    TestApp::spawn_with(move |config| {
+        config.plugins.dir = Some(dir);
+        if let Some(fuel) = fuel {
+            config.plugins.fuel = fuel;
+        }
+        config.admin_token = Some("secret".to_string());
+    })
+    .await
+}
+
+#[tokio::test]
+async fn tool_plugin_answers_the_model() {
+    let dir = plugin_dir(&[("echo.wat", echo_tool())]);
+    let app = spawn_with_plugins(&dir, None).await;
+    let session_id = app.create_session().await;
+    let arguments = json!({ "texte": "salut" }).to_string();
+    app.provider().push_reply(MockReply::ToolCall {
+        name: "echo".to_string(),
+        arguments: arguments.clone(),
+    });
+    app.provider()
+        .push_reply(MockReply::Text("Le plugin a répondu.".to_string()));
+
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({
+                "content": "Répète salut",
+                "model": "gpt-5-mini",
+                "completion_params": { "plugins": ["echo"] }
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    assert_eq!(session["messages"][1]["content"], "Le plugin a répondu.");
+    let requests = app.provider().requests();
+    assert_eq!(requests[1].tool_outputs, [arguments]);
+
+    // Un outil qui n'est pas chargé est refusé avant tout appel au modèle.
+    let (status, body) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({
+                "content": "Bonjour",
+                "model": "gpt-5-mini",
+                "completion_params": { "plugins": ["meteo"] }
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+    assert_eq!(app.provider().requests().len(), 2);
+}
+
+#[tokio::test]
+async fn filter_plugins_rewrite_answers_within_their_limits() {
+    let dir = plugin_dir(&[
+        ("majuscules.wat", uppercase_filter()),
+        (
+            "boucle.wat",
+            plugin(
+                json!({ "kind": "filter" }),
+                r#"(func (export "push") (param i32 i32) (result i64) (loop $l (br $l)) (i64.const 0))"#,
+            ),
+        ),
+    ]);
+    let app = spawn_with_plugins(&dir, Some(100_000)).await;
+    let session_id = app.create_session().await;
+
+    // `boucle` épuise ses instructions : il est écarté et la réponse passe quand même.
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({ "content": "Bonjour" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    assert_eq!(
+        session["messages"][1]["content"],
+        "RéPONSE SIMULéE : BONJOUR"
+    );
+}
+
+#[tokio::test]
+async fn plugins_are_listed_and_reloaded_by_admins() {
+    let dir = plugin_dir(&[
+        ("echo.wat", echo_tool()),
+        (
+            "reseau.wat",
+            r#"(module (import "env" "fetch" (func)) (memory (export "memory") 1))"#.to_string(),
+        ),
+        ("edit_file.wat", echo_tool()),
+    ]);
+    let app = spawn_with_plugins(&dir, None).await;
+
+    let (status, _) = app.request(Method::GET, "/api/admin/plugins", None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, report) = app
+        .request_with_headers(Method::GET, "/api/admin/plugins", None, ADMIN)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["plugins"].as_array().unwrap().len(), 1);
+    assert_eq!(report["plugins"][0]["name"], "echo");
+    assert_eq!(report["plugins"][0]["kind"], "tool");
+    assert_eq!(report["plugins"][0]["description"], "Répète ses arguments");
+    let failed: Vec<_> = report["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["file"].as_str().unwrap())
+        .collect();
+    assert_eq!(failed, ["edit_file.wat", "reseau.wat"]);
+
+    // Un plugin déposé après le démarrage est chargé à la relecture, un plugin retiré oublié.
+    std::fs::write(dir.join("majuscules.wat"), uppercase_filter()).unwrap();
+    std::fs::remove_file(dir.join("echo.wat")).unwrap();
+    let (status, report) = app
+        .request_with_headers(Method::POST, "/api/admin/plugins/reload", None, ADMIN)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let names: Vec<_> = report["plugins"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|plugin| plugin["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["majuscules"]);
+    assert_eq!(report["plugins"][0]["kind"], "filter");
+}
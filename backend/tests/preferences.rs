@@ -0,0 +1,279 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use backend::{access::ModelPolicy, prompt::DatetimePrecision, providers::AiModelChoice};
+use serde_json::{Value, json};
+
+use common::TestApp;
+
+const ADMIN: &[(&str, &str)] = &[("x-admin-token", "secret")];
+
+/// Prompt système de la prochaine requête, vu par un admin.
+async fn prompt_preview(app: &TestApp) -> Value {
+    let (status, preview) = app
+        .request_with_headers(Method::GET, "/api/admin/system-prompt", None, ADMIN)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{preview}");
+    preview
+}
+
+#[tokio::test]
+async fn preferences_are_stored_server_side() {
+    let app = TestApp::spawn().await;
+
+    let (status, preferences) = app.request(Method::GET, "/api/me/preferences", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(preferences["default_model"], json!(null));
+    assert_eq!(preferences["updated_at"], json!(null));
+
+    let (status, saved) = app
+        .request(
+            Method::PUT,
+            "/api/me/preferences",
+            Some(json!({
+                "default_model": " gpt-5-mini ",
+                "default_params": { "temperature": 0.3 },
+                "persona": "Réponds en tutoyant.",
+                "language": "fr-CA",
+                "theme": "dark",
+                "streaming": { "enabled": true, "show_reasoning": false }
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{saved}");
+    assert_eq!(saved["default_model"], "gpt-5-mini");
+    assert!(saved["updated_at"].is_string());
+
+    // Un autre appareil retrouve les mêmes préférences.
+    let (_, preferences) = app.request(Method::GET, "/api/me/preferences", None).await;
+    assert_eq!(preferences, saved);
+    assert_eq!(preferences["default_params"]["temperature"], 0.3);
+    assert_eq!(preferences["theme"], "dark");
+    assert_eq!(preferences["streaming"]["enabled"], true);
+
+    // PUT remplace tout : les champs absents sont effacés.
+    let (status, saved) = app
+        .request(
+            Method::PUT,
+            "/api/me/preferences",
+            Some(json!({ "theme": "system", "persona": "  " })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(saved["default_model"], json!(null));
+    assert_eq!(saved["persona"], json!(null));
+    assert_eq!(saved["theme"], "system");
+}
+
+#[tokio::test]
+async fn invalid_preferences_are_rejected() {
+    let app = TestApp::spawn_with(|config| {
+        config.models = ModelPolicy {
+            default: AiModelChoice::GroqLlama31,
+            allowed: Some(vec![AiModelChoice::GroqLlama31]),
+        };
+    })
+    .await;
+
+    for (preferences, expected) in [
+        (json!({ "default_model": "gpt-2" }), StatusCode::BAD_REQUEST),
+        (json!({ "default_model": "gpt-5" }), StatusCode::FORBIDDEN),
+        (json!({ "language": "français" }), StatusCode::BAD_REQUEST),
+        (
+            json!({ "timezone": "Europe/Lyon" }),
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            json!({ "default_params": { "previous_response_id": "resp_1" } }),
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            json!({ "theme": "sepia" }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ),
+    ] {
+        let (status, body) = app
+            .request(Method::PUT, "/api/me/preferences", Some(preferences))
+            .await;
+        assert_eq!(status, expected, "{body}");
+    }
+
+    let (status, saved) = app
+        .request(
+            Method::PUT,
+            "/api/me/preferences",
+            Some(json!({ "default_model": "auto" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(saved["default_model"], "auto");
+}
+
+#[tokio::test]
+async fn system_prompt_is_assembled_from_preferences() {
+    let app = TestApp::spawn_with(|config| {
+        config.admin_token = Some("secret".to_string());
+        config.system_prompt.project_instructions =
+            Some("Le projet est un backend Rust.\n".to_string());
+    })
+    .await;
+    let (status, _) = app
+        .request(
+            Method::PUT,
+            "/api/me/preferences",
+            Some(json!({
+                "display_name": "Carl",
+                "custom_instructions": "Réponds en trois phrases au plus.",
+                "persona": "Un pirate courtois."
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let session_id = app.create_session().await;
+    let (status, _) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({ "content": "Bonjour", "model": "gpt-5-mini" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let request = app.provider().requests().pop().unwrap();
+    let system = &request.messages[0];
+    assert_eq!(system.role, "system");
+    for part in [
+        "react-markdown",
+        "Le projet est un backend Rust.",
+        "Un pirate courtois.",
+        "Réponds en trois phrases au plus.",
+        "Tu t'adresses à Carl.",
+        "Date et heure actuelles : ",
+    ] {
+        assert!(system.content.contains(part), "{part} absent du prompt");
+    }
+    assert!(!system.content.contains("{{"));
+    assert!(!system.content.contains("\n\n\n"));
+
+    let preview = prompt_preview(&app).await;
+    assert_eq!(preview["variables"]["user_name"], "Carl");
+    assert!(preview["variables"]["datetime"].is_string());
+    assert!(preview["estimated_tokens"].as_u64().unwrap() > 0);
+    assert!(
+        preview["template"]
+            .as_str()
+            .unwrap()
+            .contains("{{#persona}}")
+    );
+}
+
+#[tokio::test]
+async fn custom_template_skips_empty_sections() {
+    let app = TestApp::spawn_with(|config| {
+        config.admin_token = Some("secret".to_string());
+        config.system_prompt.template = Some(
+            "Assistant de {{user_name}}.\n{{#persona}}\nPersona : {{persona}}\n{{/persona}}\nFin."
+                .to_string(),
+        );
+    })
+    .await;
+
+    let preview = prompt_preview(&app).await;
+    assert_eq!(preview["prompt"], "Assistant de .\nFin.");
+    assert_eq!(preview["variables"]["persona"], json!(null));
+
+    app.request(
+        Method::PUT,
+        "/api/me/preferences",
+        Some(json!({ "display_name": "Ana", "persona": "Concise." })),
+    )
+    .await;
+    let preview = prompt_preview(&app).await;
+    assert_eq!(
+        preview["prompt"],
+        "Assistant de Ana.\nPersona : Concise.\nFin."
+    );
+}
+
+#[tokio::test]
+async fn prompt_datetime_follows_timezone_and_locale() {
+    let app = TestApp::spawn_with(|config| {
+        config.admin_token = Some("secret".to_string());
+        config.system_prompt.default_timezone = Some("Europe/Paris".to_string());
+        config.system_prompt.datetime = DatetimePrecision::Minute;
+    })
+    .await;
+
+    let preview = prompt_preview(&app).await;
+    assert_eq!(preview["variables"]["timezone"], "Europe/Paris");
+    assert_eq!(preview["variables"]["locale"], json!(null));
+
+    let (status, saved) = app
+        .request(
+            Method::PUT,
+            "/api/me/preferences",
+            Some(json!({ "timezone": " Asia/Tokyo ", "language": "fr-CA" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{saved}");
+    assert_eq!(saved["timezone"], "Asia/Tokyo");
+
+    let preview = prompt_preview(&app).await;
+    assert_eq!(preview["variables"]["timezone"], "Asia/Tokyo");
+    assert_eq!(preview["variables"]["locale"], "fr-CA");
+    let datetime = preview["variables"]["datetime"].as_str().unwrap();
+    assert!(datetime.ends_with("(UTC+09:00)"), "{datetime}");
+    let prompt = preview["prompt"].as_str().unwrap();
+    assert!(prompt.contains("fuseau horaire Asia/Tokyo"));
+    assert!(prompt.contains("la locale fr-CA"));
+}
+
+#[tokio::test]
+async fn prompt_datetime_precision_is_configurable() {
+    // Par défaut, la date seule : le prompt système ne change pas de la journée.
+    let app = TestApp::spawn_with(|config| config.admin_token = Some("secret".to_string())).await;
+    let preview = prompt_preview(&app).await;
+    let datetime = preview["variables"]["datetime"].as_str().unwrap();
+    assert!(!datetime.contains(':'), "{datetime}");
+
+    let app = TestApp::spawn_with(|config| {
+        config.admin_token = Some("secret".to_string());
+        config.system_prompt.datetime = DatetimePrecision::Minute;
+    })
+    .await;
+    let preview = prompt_preview(&app).await;
+    let datetime = preview["variables"]["datetime"].as_str().unwrap();
+    assert!(datetime.contains(':'), "{datetime}");
+
+    let app = TestApp::spawn_with(|config| {
+        config.admin_token = Some("secret".to_string());
+        config.system_prompt.datetime = DatetimePrecision::Off;
+    })
+    .await;
+    let preview = prompt_preview(&app).await;
+    assert_eq!(preview["variables"]["datetime"], json!(null));
+    assert_eq!(preview["variables"]["timezone"], json!(null));
+    assert!(!preview["prompt"].as_str().unwrap().contains("CONTEXTE"));
+}
+
+#[tokio::test]
+async fn system_prompt_preview_is_reserved_to_admins() {
+    let app = TestApp::spawn_with(|config| config.admin_token = Some("secret".to_string())).await;
+    let (status, _) = app
+        .request(Method::GET, "/api/admin/system-prompt", None)
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app
+        .request_with_headers(
+            Method::GET,
+            "/api/admin/system-prompt",
+            None,
+            &[("x-admin-token", "mauvais")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app
+        .request(Method::GET, "/api/debug/system-prompt", None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
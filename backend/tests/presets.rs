@@ -0,0 +1,103 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use backend::models::ServiceTier;
+use serde_json::json;
+
+use common::TestApp;
+
+#[tokio::test]
+async fn presets_can_be_managed() {
+    let app = TestApp::spawn().await;
+
+    let (status, presets) = app.request(Method::GET, "/api/presets", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(presets.as_array().unwrap().len(), 3);
+
+    let (status, preset) = app
+        .request(
+            Method::POST,
+            "/api/presets",
+            Some(json!({ "name": " Court ", "params": { "max_tokens": 200 } })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(preset["name"], "Court");
+    assert_eq!(preset["params"]["max_tokens"], 200);
+    let preset_id = preset["id"].as_str().unwrap().to_string();
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            "/api/presets",
+            Some(json!({ "name": "Court", "params": {} })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, preset) = app
+        .request(
+            Method::PUT,
+            &format!("/api/presets/{preset_id}"),
+            Some(json!({ "name": "Très court", "params": { "max_tokens": 50 } })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(preset["name"], "Très court");
+    assert_eq!(preset["params"]["max_tokens"], 50);
+
+    let (status, _) = app
+        .request(Method::DELETE, &format!("/api/presets/{preset_id}"), None)
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = app
+        .request(Method::DELETE, &format!("/api/presets/{preset_id}"), None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn preset_fills_in_missing_completion_params() {
+    let app = TestApp::spawn().await;
+    let (_, preset) = app
+        .request(
+            Method::POST,
+            "/api/presets",
+            Some(json!({
+                "name": "Graine",
+                "params": { "temperature": 0.0, "seed": 42, "service_tier": "flex" }
+            })),
+        )
+        .await;
+    let session_id = app.create_session().await;
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({
+                "content": "Bonjour",
+                "model": "gpt-4.1",
+                "preset_id": preset["id"],
+                "completion_params": { "temperature": 0.5 }
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let params = app.provider().requests()[0].params.clone().unwrap();
+    assert_eq!(params.temperature, Some(0.5));
+    assert_eq!(params.seed, Some(42));
+    assert_eq!(params.service_tier, Some(ServiceTier::Flex));
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({
+                "content": "Bonjour",
+                "preset_id": "00000000-0000-0000-0000-000000000000"
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use axum::{
+    Json, Router,
+    http::{Method, StatusCode},
+    routing::post,
+};
+use common::TestApp;
+use serde_json::{Value, json};
+use tokio::{net::TcpListener, sync::mpsc};
+
+/// Serveur Ollama qui répond toujours le flux de `tests/fixtures/sse/ollama_chat.ndjson`.
+async fn fake_ollama() -> (String, mpsc::UnboundedReceiver<Value>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::unbounded_channel();
+    let app = Router::new().route(
+        "/api/chat",
+        post(move |Json(body): Json<Value>| async move {
+            tx.send(body).unwrap();
+            include_str!("fixtures/sse/ollama_chat.ndjson")
+        }),
+    );
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (url, rx)
+}
+
+#[tokio::test]
+async fn registry_sends_the_upstream_model_id() {
+    let (ollama_url, mut requests) = fake_ollama().await;
+    let app = TestApp::spawn_with(|config| {
+        config.mock_provider = false;
+        config.ollama_base_url = Some(ollama_url);
+        config.ollama_model = "mistral:7b".to_string();
+    })
+    .await;
+    let session_id = app.create_session().await;
+
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({ "content": "Bonjour", "model": "ollama" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    assert_eq!(session["messages"][1]["content"], "Ça marche 🦙");
+    assert_eq!(session["messages"][1]["model"], "ollama");
+    let request = requests.recv().await.unwrap();
+    assert_eq!(request["model"], "mistral:7b");
+    assert_eq!(
+        request["messages"].as_array().unwrap().last().unwrap()["content"],
+        "Bonjour"
+    );
+}
+
+#[tokio::test]
+async fn unregistered_model_is_unavailable() {
+    let app = TestApp::spawn_with(|config| config.mock_provider = false).await;
+    let (_, body) = app.request(Method::GET, "/api/models", None).await;
+    let ollama = body["models"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|model| model["id"] == "ollama")
+        .unwrap();
+    assert_eq!(ollama["available"], false);
+
+    let session_id = app.create_session().await;
+    let (status, _) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({ "content": "Bonjour", "model": "ollama" })),
+        )
+        .await;
+    assert_ne!(status, StatusCode::OK);
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+
+use common::TestApp;
+
+#[tokio::test]
+async fn forwarded_headers_from_trusted_proxies_build_attachment_urls() {
+    let forwarded = [
+        ("x-forwarded-proto", "https"),
+        ("x-forwarded-host", "chat.example.com"),
+        ("host", "backend:4000"),
+    ];
+    let body = json!({ "text": "Un long texte collé" });
+
+    let app = TestApp::spawn_with(|config| {
+        config.upload_base_url = "/uploads".to_string();
+        config.trusted_proxies = vec!["127.0.0.0/8".parse().unwrap()];
+    })
+    .await;
+    let (status, uploaded) = app
+        .request_with_headers(
+            Method::POST,
+            "/api/uploads/text",
+            Some(body.clone()),
+            &forwarded,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{uploaded}");
+    let url = uploaded["url"].as_str().unwrap();
+    assert!(
+        url.starts_with("https://chat.example.com/uploads/"),
+        "{url}"
+    );
+
+    // Sans proxy de confiance, les en-têtes `X-Forwarded-*` sont ignorés.
+    let app = TestApp::spawn_with(|config| config.upload_base_url = "/uploads".to_string()).await;
+    let (_, uploaded) = app
+        .request_with_headers(Method::POST, "/api/uploads/text", Some(body), &forwarded)
+        .await;
+    let url = uploaded["url"].as_str().unwrap();
+    assert!(url.starts_with("http://backend:4000/uploads/"), "{url}");
+}
+
+async fn ask(app: &TestApp, forwarded_for: &str) -> StatusCode {
+    let body = json!({ "messages": [{ "role": "user", "content": "Bonjour" }] });
+    let (status, _) = app
+        .request_with_headers(
+            Method::POST,
+            "/api/ai",
+            Some(body),
+            &[("x-forwarded-for", forwarded_for)],
+        )
+        .await;
+    status
+}
+
+#[tokio::test]
+async fn rate_limit_counts_forwarded_client_addresses() {
+    let app = TestApp::spawn_with(|config| {
+        config.rate_limit_per_minute = Some(1);
+        config.trusted_proxies = vec![
+            "127.0.0.1/32".parse().unwrap(),
+            "10.0.0.0/8".parse().unwrap(),
+        ];
+    })
+    .await;
+    assert_eq!(ask(&app, "203.0.113.7").await, StatusCode::OK);
+    // Adresse ajoutée par le client devant la vraie, puis proxy interne : même client.
+    assert_eq!(
+        ask(&app, "198.51.100.1, 203.0.113.7, 10.1.2.3").await,
+        StatusCode::TOO_MANY_REQUESTS
+    );
+    assert_eq!(ask(&app, "203.0.113.8").await, StatusCode::OK);
+
+    // Sans proxy de confiance, tous les appels viennent de la même connexion.
+    let app = TestApp::spawn_with(|config| config.rate_limit_per_minute = Some(1)).await;
+    assert_eq!(ask(&app, "203.0.113.7").await, StatusCode::OK);
+    assert_eq!(
+        ask(&app, "203.0.113.8").await,
+        StatusCode::TOO_MANY_REQUESTS
+    );
+}
+
+#[tokio::test]
+async fn public_base_url_takes_precedence_for_generated_links() {
+    let app = TestApp::spawn_with(|config| {
+        config.upload_base_url = "/uploads".to_string();
+        config.public_base_url = Some("https://chat.example.com/".to_string());
+    })
+    .await;
+    let (status, uploaded) = app
+        .request_with_headers(
+            Method::POST,
+            "/api/uploads/text",
+            Some(json!({ "text": "Un long texte collé" })),
+            &[("host", "backend:4000")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{uploaded}");
+    let url = uploaded["url"].as_str().unwrap();
+    assert!(
+        url.starts_with("https://chat.example.com/uploads/"),
+        "{url}"
+    );
+
+    let (status, export) = app.request(Method::POST, "/api/export", None).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let export_id = export["id"].as_str().unwrap();
+    let mut download_url = None;
+    for _ in 0..60 {
+        let (_, export) = app
+            .request(Method::GET, &format!("/api/export/{export_id}"), None)
+            .await;
+        if export["status"] == "ready" {
+            download_url = export["download_url"].as_str().map(str::to_string);
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    let download_url = download_url.expect("export prêt");
+    assert!(
+        download_url.starts_with(&format!(
+            "https://chat.example.com/api/export/{export_id}/download?token="
+        )),
+        "{download_url}"
+    );
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+
+use common::TestApp;
+
+const ADMIN: &[(&str, &str)] = &[("x-admin-token", "secret")];
+
+#[tokio::test]
+async fn hot_reads_are_explained_and_use_their_indexes() {
+    let app = TestApp::spawn_with(|config| config.admin_token = Some("secret".to_string())).await;
+
+    let (status, _) = app.request(Method::GET, "/api/admin/queries", None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Extension créée mais pas préchargée : les statistiques sont indisponibles, pas en erreur.
+    app.execute_sql("CREATE EXTENSION IF NOT EXISTS pg_stat_statements")
+        .await;
+    let (status, report) = app
+        .request_with_headers(Method::GET, "/api/admin/queries?limit=5", None, ADMIN)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{report}");
+    if let Some(slow) = report["slow_queries"].as_array() {
+        assert!(slow.len() <= 5);
+    } else {
+        assert!(report["slow_queries"].is_null());
+    }
+    let plans = report["plans"].as_array().unwrap();
+    let names: Vec<_> = plans
+        .iter()
+        .map(|plan| plan["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        ["list_sessions", "fetch_messages", "fetch_attachments"]
+    );
+    // Sur une table vide, l'historique d'une session se lit par l'index de la migration.
+    let messages = plans[1]["plan"].as_array().unwrap();
+    assert!(
+        messages.iter().any(|line| line
+            .as_str()
+            .unwrap()
+            .contains("chat_messages_session_position_idx")),
+        "{messages:?}"
+    );
+}
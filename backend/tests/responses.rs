@@ -0,0 +1,62 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use backend::providers::AiModelChoice;
+use serde_json::json;
+
+use common::TestApp;
+
+#[tokio::test]
+async fn responses_api_chains_previous_response() {
+    let app = TestApp::spawn_with(|config| {
+        config.responses_api_models = vec![AiModelChoice::OpenAIGpt5Mini];
+    })
+    .await;
+    let session_id = app.create_session().await;
+    let uri = format!("/api/chat/sessions/{session_id}/messages");
+
+    for content in ["Bonjour", "Et ensuite ?"] {
+        let (status, _) = app
+            .request(
+                Method::POST,
+                &uri,
+                Some(json!({ "content": content, "model": "gpt-5-mini" })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let requests = app.provider().requests();
+    assert_eq!(
+        requests[0]
+            .params
+            .as_ref()
+            .and_then(|params| params.previous_response_id.clone()),
+        None
+    );
+    assert_eq!(
+        requests[1]
+            .params
+            .as_ref()
+            .unwrap()
+            .previous_response_id
+            .as_deref(),
+        Some("resp_mock_1")
+    );
+}
+
+#[tokio::test]
+async fn web_search_requires_the_responses_api() {
+    let app = TestApp::spawn().await;
+    let (status, _) = app
+        .request(
+            Method::POST,
+            "/api/ai",
+            Some(json!({
+                "messages": [{ "role": "user", "content": "Météo ?" }],
+                "completion_params": { "web_search": true }
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
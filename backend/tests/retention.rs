@@ -0,0 +1,110 @@
+mod common;
+
+use std::time::Duration;
+
+use axum::http::{Method, StatusCode};
+use backend::retention::RetentionPolicy;
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+use common::TestApp;
+
+async fn spawn_with_retention(policy: RetentionPolicy) -> TestApp {
+    TestApp::spawn_with(|config| {
+        config.retention = RetentionPolicy {
+            interval: Duration::from_millis(50),
+            ..policy
+        }
+    })
+    .await
+}
+
+/// Réessaie `POST .../archive` jusqu'au statut attendu : 400 une fois la session archivée,
+/// 404 une fois supprimée.
+async fn wait_for_archive_status(app: &TestApp, session_id: Uuid, expected: StatusCode) {
+    for _ in 0..60 {
+        let (status, _) = app
+            .request(
+                Method::POST,
+                &format!("/api/chat/sessions/{session_id}/archive"),
+                None,
+            )
+            .await;
+        if status == expected {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("la règle de rétention n'a pas été appliquée");
+}
+
+#[tokio::test]
+async fn idle_sessions_are_archived() {
+    let app = spawn_with_retention(RetentionPolicy {
+        archive_idle_days: Some(0),
+        ..RetentionPolicy::default()
+    })
+    .await;
+    let session_id = app.create_session().await;
+
+    wait_for_archive_status(&app, session_id, StatusCode::BAD_REQUEST).await;
+    let (_, sessions) = app.request(Method::GET, "/api/chat/sessions", None).await;
+    assert_eq!(sessions, json!([]));
+}
+
+#[tokio::test]
+async fn old_archives_are_purged() {
+    let app = spawn_with_retention(RetentionPolicy {
+        purge_archived_days: Some(0),
+        ..RetentionPolicy::default()
+    })
+    .await;
+    let kept = app.create_session().await;
+    let archived = app.create_session().await;
+    let (status, _) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{archived}/archive"),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    wait_for_archive_status(&app, archived, StatusCode::NOT_FOUND).await;
+    let (_, sessions) = app.request(Method::GET, "/api/chat/sessions", None).await;
+    assert_eq!(sessions[0]["id"], kept.to_string());
+}
+
+#[tokio::test]
+async fn sessions_keep_their_latest_messages() {
+    let app = spawn_with_retention(RetentionPolicy {
+        max_messages_per_session: Some(3),
+        ..RetentionPolicy::default()
+    })
+    .await;
+    let session_id = app.create_session().await;
+    for question in ["Première", "Deuxième", "Troisième"] {
+        let (status, _) = app
+            .request(
+                Method::POST,
+                &format!("/api/chat/sessions/{session_id}/messages"),
+                Some(json!({ "content": question })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    // Les 3 derniers messages commencent par une réponse : la coupe remonte à la question
+    // suivante, il reste le dernier échange.
+    for _ in 0..60 {
+        let (_, sessions) = app.request(Method::GET, "/api/chat/sessions", None).await;
+        let messages = sessions[0]["messages"].as_array().unwrap();
+        if messages.len() == 2 {
+            assert_eq!(messages[0]["content"], "Troisième");
+            assert_eq!(messages[1]["role"], Value::from("assistant"));
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("les anciens messages n'ont pas été retirés");
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use backend::{access::ModelPolicy, providers::AiModelChoice};
+use serde_json::{Value, json};
+
+use common::TestApp;
+
+async fn ask_auto(app: &TestApp, content: &str) -> Value {
+    let session_id = app.create_session().await;
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({ "content": content, "model": "auto" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    session["messages"][1].clone()
+}
+
+#[tokio::test]
+async fn auto_routes_by_question_and_records_the_route() {
+    let app = TestApp::spawn().await;
+
+    let answer = ask_auto(&app, "Quelle est la capitale du Portugal ?").await;
+    assert_eq!(answer["model"], "llama-3.1-8b-instant");
+    assert_eq!(answer["route"], "simple");
+
+    let answer = ask_auto(
+        &app,
+        "Pourquoi ce code ne compile pas ?\n```rust\nlet x: u8 = 300;\n```",
+    )
+    .await;
+    assert_eq!(answer["model"], "gpt-5.1");
+    assert_eq!(answer["route"], "code");
+
+    let answer = ask_auto(&app, "Calcule l'intégrale de x² entre 0 et 1.").await;
+    assert_eq!(answer["route"], "math");
+
+    let models: Vec<AiModelChoice> = app
+        .provider()
+        .requests()
+        .into_iter()
+        .map(|request| request.model)
+        .collect();
+    assert_eq!(
+        models,
+        [
+            AiModelChoice::GroqLlama31,
+            AiModelChoice::OpenAIGpt51,
+            AiModelChoice::OpenAIGpt51
+        ]
+    );
+}
+
+#[tokio::test]
+async fn auto_stays_within_allowed_models() {
+    let app = TestApp::spawn_with(|config| {
+        config.models = ModelPolicy {
+            default: AiModelChoice::GroqLlama31,
+            allowed: Some(vec![
+                AiModelChoice::GroqLlama31,
+                AiModelChoice::OpenAIGpt5Mini,
+            ]),
+        };
+    })
+    .await;
+
+    let answer = ask_auto(&app, "Démontre le théorème de Pythagore.").await;
+    assert_eq!(answer["model"], "gpt-5-mini");
+    assert_eq!(answer["route"], "math");
+}
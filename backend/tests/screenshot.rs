@@ -0,0 +1,262 @@
+mod common;
+
+use std::io::Cursor;
+
+use axum::{
+    Json, Router,
+    http::{Method, StatusCode, header},
+    routing::post,
+};
+use backend::mock::MockReply;
+use image::{ImageFormat, RgbImage};
+use serde_json::{Value, json};
+use tokio::{net::TcpListener, sync::mpsc};
+
+use common::TestApp;
+
+/// Service de capture simulé : renvoie un PNG aux dimensions de la fenêtre demandée.
+async fn fake_screenshot_service() -> (String, mpsc::UnboundedReceiver<Value>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/screenshot", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::unbounded_channel();
+    let app = Router::new().route(
+        "/screenshot",
+        post(move |Json(body): Json<Value>| async move {
+            let width = body["viewport"]["width"].as_u64().unwrap() as u32;
+            let height = body["viewport"]["height"].as_u64().unwrap() as u32;
+            tx.send(body).unwrap();
+            let mut png = Vec::new();
+            RgbImage::new(width, height)
+                .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+                .unwrap();
+            ([(header::CONTENT_TYPE, "image/png")], png)
+        }),
+    );
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (url, rx)
+}
+
+#[tokio::test]
+async fn screenshot_is_shown_to_the_model_and_attached_to_the_answer() {
+    let (service_url, mut captures) = fake_screenshot_service().await;
+    let app = TestApp::spawn_with(|config| config.screenshot_service_url = Some(service_url)).await;
+    let session_id = app.create_session().await;
+    let tool_call = |arguments: Value| MockReply::ToolCall {
+        name: "screenshot_page".to_string(),
+        arguments: arguments.to_string(),
+    };
+    app.provider()
+        .push_reply(tool_call(json!({ "url": "file:///etc/passwd" })));
+    app.provider().push_reply(tool_call(
+        json!({ "url": "https://93.184.215.14/", "full_page": true }),
+    ));
+    app.provider().push_reply(MockReply::Text(
+        "Le bouton d'appel à l'action manque de contraste.".to_string(),
+    ));
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({
+                "content": "Critique la landing page de https://93.184.215.14",
+                "model": "gpt-5-mini",
+                "completion_params": { "screenshot": true }
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+
+    let capture = captures.try_recv().unwrap();
+    assert_eq!(capture["url"], "https://93.184.215.14/");
+    assert_eq!(capture["options"]["fullPage"], true);
+    assert!(captures.try_recv().is_err());
+
+    let requests = app.provider().requests();
+    let [.., refused, captured] = &requests[..] else {
+        panic!("{requests:?}");
+    };
+    assert!(
+        refused.tool_outputs[0].starts_with("Adresse invalide"),
+        "{:?}",
+        refused.tool_outputs
+    );
+    assert!(refused.tool_images.is_empty());
+    assert!(
+        captured.tool_outputs[0].contains("(1280 × 800 px)"),
+        "{:?}",
+        captured.tool_outputs
+    );
+    assert_eq!(captured.tool_images.len(), 1);
+
+    let answer = &session["messages"][1];
+    assert_eq!(
+        answer["content"],
+        "Le bouton d'appel à l'action manque de contraste."
+    );
+    let attachments = answer["attachments"].as_array().unwrap();
+    assert_eq!(attachments.len(), 1);
+    assert_eq!(attachments[0]["file_name"], "capture-93-184-215-14.png");
+    assert_eq!(attachments[0]["mime_type"], "image/png");
+    assert_eq!(attachments[0]["width"], 1280);
+    assert_eq!(attachments[0]["height"], 800);
+
+    // Régénérée sans capture, la réponse perd celle de la génération précédente.
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/regenerate"),
+            Some(json!({ "message_id": answer["id"], "model": "gpt-5-mini" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    assert_eq!(session["messages"][1]["attachments"], json!([]));
+}
+
+#[tokio::test]
+async fn screenshot_tool_requires_a_service_and_a_vision_model() {
+    let app = TestApp::spawn().await;
+    let session_id = app.create_session().await;
+    let uri = format!("/api/chat/sessions/{session_id}/messages");
+    let (status, body) = app
+        .request(
+            Method::POST,
+            &uri,
+            Some(json!({
+                "content": "À quoi ressemble example.com ?",
+                "model": "gpt-5-mini",
+                "completion_params": { "screenshot": true }
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.as_str().unwrap().contains("SCREENSHOT_SERVICE_URL"));
+
+    let (service_url, _captures) = fake_screenshot_service().await;
+    let app = TestApp::spawn_with(|config| config.screenshot_service_url = Some(service_url)).await;
+    let session_id = app.create_session().await;
+    let (status, body) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({
+                "content": "À quoi ressemble example.com ?",
+                "model": "llama-3.1-8b-instant",
+                "completion_params": { "screenshot": true }
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(
+        body.as_str().unwrap().contains("modèle de vision"),
+        "{body}"
+    );
+}
+
+#[tokio::test]
+async fn screenshot_tool_is_closed_in_local_only_mode() {
+    let (service_url, _captures) = fake_screenshot_service().await;
+    let app = TestApp::spawn_with(|config| {
+        config.local_only = true;
+        config.screenshot_service_url = Some(service_url);
+    })
+    .await;
+    let session_id = app.create_session().await;
+    let (status, body) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({
+                "content": "À quoi ressemble example.com ?",
+                "model": "gpt-5-mini",
+                "completion_params": { "screenshot": true }
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.as_str().unwrap().contains("LOCAL_ONLY"), "{body}");
+    assert!(app.provider().requests().is_empty());
+}
+
+#[tokio::test]
+async fn internal_pages_are_captured_only_when_allowed() {
+    let (service_url, mut captures) = fake_screenshot_service().await;
+    let app = TestApp::spawn_with(|config| {
+        config.screenshot_service_url = Some(service_url);
+        config.screenshot_allowed_hosts = vec!["localhost".to_string()];
+    })
+    .await;
+    let session_id = app.create_session().await;
+    let refused = [
+        "http://127.0.0.1:8080/admin",
+        "http://169.254.169.254/latest/meta-data/",
+        "http://[::ffff:192.168.1.1]/",
+    ];
+    for url in refused.iter().chain(&["http://localhost:3000/"]) {
+        app.provider().push_reply(MockReply::ToolCall {
+            name: "screenshot_page".to_string(),
+            arguments: json!({ "url": url }).to_string(),
+        });
+    }
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({
+                "content": "À quoi ressemble l'interface d'administration ?",
+                "model": "gpt-5-mini",
+                "completion_params": { "screenshot": true }
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+
+    let requests = app.provider().requests();
+    for (url, request) in refused.iter().zip(&requests[1..]) {
+        let output = &request.tool_outputs[0];
+        assert!(output.contains("adresse interne"), "{url} : {output}");
+    }
+    // Seul l'hôte de SCREENSHOT_ALLOWED_HOSTS atteint le service.
+    assert_eq!(
+        captures.try_recv().unwrap()["url"],
+        "http://localhost:3000/"
+    );
+    assert!(captures.try_recv().is_err());
+    assert_eq!(requests.last().unwrap().tool_images.len(), 1);
+}
+
+#[tokio::test]
+async fn oversized_screenshots_are_dropped() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let service_url = format!("http://{}/screenshot", listener.local_addr().unwrap());
+    let app = Router::new().route(
+        "/screenshot",
+        post(|| async {
+            (
+                [(header::CONTENT_TYPE, "image/png")],
+                vec![0u8; 11 * 1024 * 1024],
+            )
+        }),
+    );
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let app = TestApp::spawn_with(|config| config.screenshot_service_url = Some(service_url)).await;
+    let session_id = app.create_session().await;
+    app.provider().push_reply(MockReply::ToolCall {
+        name: "screenshot_page".to_string(),
+        arguments: json!({ "url": "https://93.184.215.14/" }).to_string(),
+    });
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({
+                "content": "À quoi ressemble cette page ?",
+                "model": "gpt-5-mini",
+                "completion_params": { "screenshot": true }
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    let output = &app.provider().requests()[1].tool_outputs[0];
+    assert!(output.contains("capture trop lourde"), "{output}");
+    assert_eq!(session["messages"][1]["attachments"], json!([]));
+}
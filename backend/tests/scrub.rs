@@ -0,0 +1,83 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use backend::scrub::ScrubPolicy;
+use serde_json::json;
+
+use common::TestApp;
+
+#[tokio::test]
+async fn secrets_are_masked_before_reaching_the_provider() {
+    let app = TestApp::spawn_with(|config| {
+        config.admin_token = Some("secret".to_string());
+        config.scrub = ScrubPolicy {
+            builtin_secrets: true,
+            hostnames: vec!["corp.internal".to_string()],
+            patterns: vec![r"PROJET-[0-9]{4}".to_string()],
+        };
+    })
+    .await;
+    let session_id = app.create_session().await;
+    let question = "Ma clé sk-proj-abcdefghijklmnopqrstuvwx ne marche pas sur \
+                    db.corp.internal pour PROJET-1234, pourquoi ?";
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({ "content": question, "model": "gpt-5-mini" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    // L'historique garde le message tel quel, seul l'envoi est masqué.
+    assert_eq!(session["messages"][0]["content"], question);
+
+    let request = app.provider().requests().pop().unwrap();
+    let sent = &request.messages.last().unwrap().content;
+    assert_eq!(
+        sent,
+        "Ma clé [masqué:openai_key] ne marche pas sur [masqué:hostname] pour [masqué:custom-1], pourquoi ?"
+    );
+
+    let (status, entries) = app
+        .request_with_headers(
+            Method::GET,
+            "/api/admin/scrub-audit",
+            None,
+            &[("x-admin-token", "secret")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let entry = entries
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|entry| entry["model"] == "gpt-5-mini")
+        .expect("masquage tracé");
+    assert_eq!(entry["provider"], "mock");
+    assert_eq!(
+        entry["rules"],
+        json!(["custom-1", "hostname", "openai_key"])
+    );
+    assert_eq!(entry["replacements"], 3);
+    assert!(!entry.to_string().contains("abcdefghij"));
+
+    let (status, _) = app
+        .request(Method::GET, "/api/admin/scrub-audit", None)
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn messages_without_secrets_are_sent_unchanged() {
+    let app = TestApp::spawn_with(|config| config.scrub.builtin_secrets = true).await;
+    let session_id = app.create_session().await;
+    let question = "Le mot sk-court et task-runner restent intacts.";
+    app.request(
+        Method::POST,
+        &format!("/api/chat/sessions/{session_id}/messages"),
+        Some(json!({ "content": question })),
+    )
+    .await;
+    let request = app.provider().requests().pop().unwrap();
+    assert_eq!(request.messages.last().unwrap().content, question);
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use backend::seed::seed;
+
+use common::TestApp;
+
+#[tokio::test]
+async fn seed_fills_an_empty_database_once() {
+    let app = TestApp::spawn().await;
+
+    assert_eq!(seed(app.state()).await.unwrap(), 4);
+    assert_eq!(seed(app.state()).await.unwrap(), 0);
+
+    let (status, sessions) = app.request(Method::GET, "/api/chat/sessions", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let sessions = sessions.as_array().unwrap();
+    assert_eq!(sessions.len(), 3);
+    assert!(sessions.iter().any(|session| !session["draft"].is_null()));
+
+    let attachments: Vec<_> = sessions
+        .iter()
+        .flat_map(|session| session["messages"].as_array().unwrap())
+        .flat_map(|message| message["attachments"].as_array().unwrap())
+        .collect();
+    assert_eq!(attachments.len(), 2);
+    for attachment in attachments {
+        let (status, _) = app
+            .request(
+                Method::GET,
+                &format!("/uploads/{}", attachment["storage_key"].as_str().unwrap()),
+                None,
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
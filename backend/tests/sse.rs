@@ -0,0 +1,206 @@
+use backend::sse::{SseEvent, SseParser};
+use serde_json::Value;
+
+fn fixture(name: &str) -> String {
+    let path = format!(
+        "{}/tests/fixtures/sse/{name}.sse",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    std::fs::read_to_string(path).unwrap()
+}
+
+/// Évènements du flux reçu par morceaux de `size` octets.
+fn parse(stream: &[u8], size: usize) -> Vec<SseEvent> {
+    let mut parser = SseParser::new();
+    let mut events = Vec::new();
+    for chunk in stream.chunks(size) {
+        events.extend(parser.feed(chunk));
+    }
+    events.extend(parser.finish());
+    events
+}
+
+/// Mêmes évènements quels que soient le découpage et les fins de ligne, y compris au
+/// milieu d'un caractère UTF-8 ou entre le CR et le LF d'un CRLF.
+fn parse_fixture(name: &str) -> Vec<SseEvent> {
+    let stream = fixture(name);
+    let events = parse(stream.as_bytes(), stream.len());
+    for variant in [
+        stream.clone(),
+        stream.replace('\n', "\r\n"),
+        stream.replace('\n', "\r"),
+    ] {
+        for size in 1..=7 {
+            assert_eq!(
+                parse(variant.as_bytes(), size),
+                events,
+                "{name}, morceaux de {size} octets"
+            );
+        }
+    }
+    events
+}
+
+fn json(event: &SseEvent) -> Value {
+    serde_json::from_str(&event.data).unwrap()
+}
+
+#[test]
+fn openai_chat_stream_keeps_tool_call_fragments() {
+    let events = parse_fixture("openai_chat");
+    assert_eq!(events.len(), 8);
+    assert!(events.iter().all(|event| event.event.is_none()));
+    assert_eq!(
+        json(&events[1])["choices"][0]["delta"]["content"],
+        "Je regarde la météo à Zürich"
+    );
+    let arguments: String = events[2..5]
+        .iter()
+        .map(|event| {
+            json(event)["choices"][0]["delta"]["tool_calls"][0]["function"]["arguments"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    assert_eq!(arguments, r#"{"query":"météo Zürich"}"#);
+    assert_eq!(
+        json(&events[5])["choices"][0]["finish_reason"],
+        "tool_calls"
+    );
+    assert_eq!(json(&events[6])["usage"]["total_tokens"], 439);
+    assert_eq!(events[7].data, "[DONE]");
+}
+
+#[test]
+fn groq_chat_stream_reports_usage_in_x_groq() {
+    let events = parse_fixture("groq_chat");
+    assert_eq!(events.len(), 5);
+    assert_eq!(
+        json(&events[1])["choices"][0]["delta"]["content"],
+        "Bonjour 👋"
+    );
+    assert_eq!(json(&events[3])["x_groq"]["usage"]["completion_tokens"], 4);
+    assert_eq!(events[4].data, "[DONE]");
+}
+
+#[test]
+fn openai_responses_stream_names_each_event() {
+    let events = parse_fixture("openai_responses");
+    assert_eq!(events.len(), 7);
+    for event in &events {
+        assert_eq!(event.event.as_deref(), json(event)["type"].as_str());
+    }
+    assert_eq!(json(&events[4])["delta"], r" l'équation : $e^{i\pi} = -1$");
+    assert_eq!(events[5].event.as_deref(), Some("response.refusal.delta"));
+}
+
+#[test]
+fn anthropic_stream_skips_comments() {
+    let events = parse_fixture("anthropic_messages");
+    let names: Vec<_> = events
+        .iter()
+        .map(|event| event.event.as_deref().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "message_start",
+            "content_block_start",
+            "ping",
+            "content_block_delta",
+            "content_block_delta",
+            "content_block_stop",
+            "message_delta",
+            "message_stop",
+        ]
+    );
+    assert_eq!(json(&events[3])["delta"]["text"], "Ça marche");
+}
+
+#[test]
+fn ollama_stream_yields_one_event_per_line() {
+    let path = format!(
+        "{}/tests/fixtures/sse/ollama_chat.ndjson",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let stream = std::fs::read_to_string(path).unwrap();
+    for variant in [stream.clone(), stream.replace('\n', "\r\n")] {
+        for size in 1..=7 {
+            let mut parser = SseParser::json_lines();
+            let mut events = Vec::new();
+            for chunk in variant.as_bytes().chunks(size) {
+                events.extend(parser.feed(chunk));
+            }
+            events.extend(parser.finish());
+            assert_eq!(events.len(), 3, "morceaux de {size} octets");
+            assert!(events.iter().all(|event| event.event.is_none()));
+            assert_eq!(json(&events[1])["message"]["content"], " marche 🦙");
+            assert_eq!(json(&events[2])["eval_count"], 5);
+        }
+    }
+}
+
+#[test]
+fn multi_line_data_is_joined_and_unterminated_event_is_flushed() {
+    let stream = ": ouverture\nevent: note\ndata:première\ndata:  seconde\nid: 3\nretry: 1000\n\n\
+                  data\n\nevent: vide\n\ndata: {\"fin\":true}";
+    let events = parse(stream.as_bytes(), stream.len());
+    assert_eq!(
+        events,
+        [
+            SseEvent {
+                event: Some("note".to_string()),
+                data: "première\n seconde".to_string(),
+            },
+            SseEvent {
+                event: None,
+                data: String::new(),
+            },
+            SseEvent {
+                event: None,
+                data: "{\"fin\":true}".to_string(),
+            },
+        ]
+    );
+    for size in 1..=7 {
+        assert_eq!(parse(stream.as_bytes(), size), events);
+    }
+}
+
+#[test]
+fn leading_byte_order_mark_is_ignored() {
+    let stream = "\u{feff}data: bonjour\n\n";
+    for size in 1..=4 {
+        assert_eq!(
+            parse(stream.as_bytes(), size),
+            [SseEvent {
+                event: None,
+                data: "bonjour".to_string(),
+            }]
+        );
+    }
+}
+
+#[test]
+fn multibyte_characters_survive_every_split_point() {
+    let text = "Réponse très détaillée, ça déchire 🎉🇫🇷 — ½ œuvre";
+    let stream = format!(
+        "data: {}\n\n",
+        serde_json::json!({ "choices": [{ "delta": { "content": text } }] })
+    );
+    let bytes = stream.as_bytes();
+    for split in 1..bytes.len() {
+        let mut parser = SseParser::new();
+        let mut events = parser.feed(&bytes[..split]);
+        events.extend(parser.feed(&bytes[split..]));
+        events.extend(parser.finish());
+        assert_eq!(events.len(), 1, "coupure à l'octet {split}");
+        assert_eq!(
+            json(&events[0])["choices"][0]["delta"]["content"],
+            text,
+            "coupure à l'octet {split}"
+        );
+        assert!(!events[0].data.contains('\u{fffd}'));
+    }
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use backend::{access::ModelPolicy, models::ConversationTemplate, providers::AiModelChoice};
+use serde_json::json;
+
+use common::TestApp;
+
+fn code_review() -> ConversationTemplate {
+    ConversationTemplate {
+        id: "revue-de-code".to_string(),
+        title: "Revue de code".to_string(),
+        description: Some("Relecture d'un diff".to_string()),
+        icon: Some("🔍".to_string()),
+        system_message: Some("Tu relis du code Rust et signales les bugs.".to_string()),
+        user_message: Some("Relis ce diff :".to_string()),
+        model: Some("gpt-5".to_string()),
+    }
+}
+
+#[tokio::test]
+async fn session_is_created_from_template() {
+    let app = TestApp::spawn_with(|config| {
+        config.templates = vec![code_review()];
+    })
+    .await;
+
+    let (status, templates) = app.request(Method::GET, "/api/templates", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(templates[0]["id"], "revue-de-code");
+    assert_eq!(templates[0]["model"], "gpt-5");
+
+    let (status, session) = app
+        .request(
+            Method::POST,
+            "/api/chat/sessions/from-template/revue-de-code",
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    assert_eq!(session["title"], "Revue de code");
+    assert_eq!(session["icon"], "🔍");
+    assert_eq!(session["messages"][0]["role"], "system");
+    assert_eq!(session["draft"]["content"], "Relis ce diff :");
+
+    // Le message système du gabarit accompagne chaque question, après le prompt système.
+    let session_id = session["id"].as_str().unwrap();
+    let (status, session) = app
+        .request(
+            Method::POST,
+            &format!("/api/chat/sessions/{session_id}/messages"),
+            Some(json!({ "content": "Relis ce diff : +fn main() {}", "model": "gpt-5" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{session}");
+    assert_eq!(session["title"], "Revue de code");
+    assert_eq!(session["draft"], json!(null));
+    let request = app.provider().requests().pop().unwrap();
+    let roles: Vec<&str> = request
+        .messages
+        .iter()
+        .map(|message| message.role.as_str())
+        .collect();
+    assert_eq!(roles, ["system", "system", "user"]);
+    assert_eq!(
+        request.messages[1].content,
+        "Tu relis du code Rust et signales les bugs."
+    );
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            "/api/chat/sessions/from-template/inconnu",
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn unavailable_suggested_model_is_hidden() {
+    let app = TestApp::spawn_with(|config| {
+        config.templates = vec![code_review()];
+        config.models = ModelPolicy {
+            default: AiModelChoice::GroqLlama31,
+            allowed: Some(vec![AiModelChoice::GroqLlama31]),
+        };
+    })
+    .await;
+
+    let (_, templates) = app.request(Method::GET, "/api/templates", None).await;
+    assert_eq!(templates[0]["title"], "Revue de code");
+    assert_eq!(templates[0]["model"], json!(null));
+}